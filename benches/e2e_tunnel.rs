@@ -76,7 +76,7 @@ fn bench_tunnel_setup(c: &mut Criterion) {
 /// Benchmark plugin chain execution
 fn bench_plugin_chain(c: &mut Criterion) {
     use ferrotunnel_plugin::builtin::{LoggerPlugin, RateLimitPlugin, TokenAuthPlugin};
-    use ferrotunnel_plugin::{PluginRegistry, RequestContext};
+    use ferrotunnel_plugin::{ByteCounter, PluginRegistry, RequestContext};
     use std::sync::Arc;
     use tokio::sync::RwLock;
 
@@ -86,7 +86,7 @@ fn bench_plugin_chain(c: &mut Criterion) {
 
     // Setup registry with plugins
     let registry = rt.block_on(async {
-        let mut reg = PluginRegistry::new();
+        let reg = PluginRegistry::new();
         reg.register(Arc::new(RwLock::new(TokenAuthPlugin::new(vec![
             "token".to_string()
         ]))));
@@ -103,8 +103,10 @@ fn bench_plugin_chain(c: &mut Criterion) {
     let ctx = RequestContext {
         tunnel_id: "bench".to_string(),
         session_id: "session".to_string(),
+        request_id: "request".to_string(),
         remote_addr: "127.0.0.1:8080".parse().unwrap(),
         timestamp: std::time::SystemTime::now(),
+        request_bytes: ByteCounter::default(),
     };
 
     group.bench_function("execute_3_plugins", |b| {
@@ -116,7 +116,7 @@ fn bench_batched_sender(c: &mut Criterion) {
 
                     // Spawn batched sender
                     tokio::spawn(async move {
-                        ferrotunnel_core::transport::batched_sender::run_batched_sender(
+                        let _ = ferrotunnel_core::transport::batched_sender::run_batched_sender(
                             rx,
                             writer,
                             TunnelCodec::new(),
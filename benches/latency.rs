@@ -78,7 +78,7 @@ fn bench_batched_sender_latency(c: &mut Criterion) {
 
             // Spawn batched sender
             tokio::spawn(async move {
-                ferrotunnel_core::transport::batched_sender::run_batched_sender(
+                let _ = ferrotunnel_core::transport::batched_sender::run_batched_sender(
                     rx,
                     writer,
                     TunnelCodec::new(),
@@ -35,10 +35,23 @@
 //!   - Default: false (HTTP/1.1 is more compatible)
 //!   - Enable for: gRPC services, modern APIs with HTTP/2 support
 //!   - Disable for: Legacy services, simple HTTP/1.1 endpoints
+//!
+//! - **max_idle_time**: Stop reusing connections before the local service closes them
+//!   - Default: unset (falls back to `idle_timeout`)
+//!   - Set below the service's keep-alive timeout (e.g. 4s for Node.js, which closes after 5s)
+//!
+//! - **health_check_on_acquire**: Probe idle connections before reusing them
+//!   - Default: true (avoids 502s from connections closed while idle)
+//!
+//! - **max_connections** / **acquire_timeout**: Cap open HTTP/1.1 connections to the service
+//!   - Default: unbounded, 5s timeout
+//!   - Requests beyond the cap wait in arrival order and fail with 503 after the timeout
 
 use ferrotunnel_http::{HttpProxy, PoolConfig};
+use std::num::NonZeroUsize;
 use std::time::Duration;
 
+#[allow(clippy::too_many_lines)]
 fn main() {
     println!("Connection Pool Configuration Examples");
     println!("=====================================");
@@ -61,6 +74,7 @@ fn main() {
         max_idle_per_host: 128,
         idle_timeout: Duration::from_secs(120),
         prefer_h2: false,
+        ..Default::default()
     };
     let _high_throughput_proxy =
         HttpProxy::with_pool_config("127.0.0.1:8080".into(), high_throughput_config);
@@ -79,6 +93,7 @@ fn main() {
         max_idle_per_host: 8,
         idle_timeout: Duration::from_secs(60),
         prefer_h2: false,
+        ..Default::default()
     };
     let _memory_constrained_proxy =
         HttpProxy::with_pool_config("127.0.0.1:8080".into(), memory_constrained_config);
@@ -97,6 +112,7 @@ fn main() {
         max_idle_per_host: 16,
         idle_timeout: Duration::from_secs(300),
         prefer_h2: true,
+        ..Default::default()
     };
     let _http2_proxy = HttpProxy::with_pool_config("127.0.0.1:50051".into(), http2_config);
     println!("   • Max idle connections: 16");
@@ -114,16 +130,39 @@ fn main() {
         max_idle_per_host: 4,
         idle_timeout: Duration::from_secs(30),
         prefer_h2: false,
+        max_idle_time: Some(Duration::from_secs(4)),
+        health_check_on_acquire: true,
+        dns_cache_ttl: Duration::ZERO,
+        max_connections: None,
+        acquire_timeout: Duration::from_secs(5),
     };
     let _short_lived_proxy =
         HttpProxy::with_pool_config("127.0.0.1:3000".into(), short_lived_config);
     println!("   • Max idle connections: 4");
     println!("   • Idle timeout: 30 seconds");
+    println!("   • Max idle time before reuse: 4 seconds");
     println!("   • HTTP/2 preference: disabled");
+    println!("   • DNS cache: disabled, the target is resolved on every connect");
     println!("   • Use case: Development, testing, frequently changing backends");
     println!("   • Benefits: Fast resource cleanup, low overhead");
     println!();
 
+    // Example 6: Capacity-limited local service
+    println!("6. Capacity-Limited Configuration:");
+    println!("   --------------------------------");
+    let limited_config = PoolConfig {
+        max_connections: NonZeroUsize::new(16),
+        acquire_timeout: Duration::from_secs(10),
+        ..Default::default()
+    };
+    let _limited_proxy = HttpProxy::with_pool_config("127.0.0.1:8080".into(), limited_config);
+    println!("   • Max open connections: 16");
+    println!("   • Acquire timeout: 10 seconds");
+    println!("   • Use case: Services with a fixed worker count, rate-limited upstreams");
+    println!("   • Benefits: Bursts queue in arrival order instead of overloading the service");
+    println!("   • Trade-offs: Requests still queued after the timeout fail with 503");
+    println!();
+
     println!("Key Takeaways:");
     println!("-------------");
     println!("• Start with default settings for most applications");
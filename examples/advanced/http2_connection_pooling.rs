@@ -140,6 +140,7 @@ async fn run_client() -> ferrotunnel::Result<()> {
     println!("      max_idle_per_host: 64,                    // Increase pool size");
     println!("      idle_timeout: Duration::from_secs(120),   // Longer timeout");
     println!("      prefer_h2: true,                          // Prefer HTTP/2");
+    println!("      ..Default::default()");
     println!("  }};");
     println!();
     println!("  let proxy = HttpProxy::with_pool_config(\"127.0.0.1:8000\".into(), pool_config);");
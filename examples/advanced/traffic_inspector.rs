@@ -0,0 +1,90 @@
+//! Example: Inspecting Tunneled Traffic
+//!
+//! This example shows how to observe every request forwarded through the
+//! tunnel with a custom `TrafficInspector`, without running the dashboard.
+//!
+//! # What This Shows
+//!
+//! - Implementing `TrafficInspector` for request and response hooks
+//! - Attaching it with `HttpProxy::with_inspector`
+//! - Running a `TunnelClient` that forwards streams through the proxy
+//!
+//! # Usage
+//!
+//! ```bash
+//! # Start a local HTTP server on port 8000 (e.g., with Python)
+//! python3 -m http.server 8000
+//!
+//! # Run this example
+//! cargo run --example traffic_inspector -- \
+//!     --server localhost:7835 \
+//!     --token my-secret-token \
+//!     --local-addr 127.0.0.1:8000
+//! ```
+
+use async_trait::async_trait;
+use ferrotunnel::core::TunnelClient;
+use ferrotunnel_http::{HttpProxy, RequestParts, ResponseParts, TrafficInspector};
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Counts requests and logs one line per response
+#[derive(Default)]
+struct RequestCounter {
+    requests: AtomicU64,
+    errors: AtomicU64,
+}
+
+#[async_trait]
+impl TrafficInspector for RequestCounter {
+    async fn on_request(&self, request: &RequestParts) {
+        let n = self.requests.fetch_add(1, Ordering::Relaxed) + 1;
+        println!("#{n} {} {}", request.method, request.uri);
+    }
+
+    async fn on_response(&self, response: &ResponseParts, duration: Duration) {
+        if response.status.is_server_error() {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        println!("   -> {} in {duration:?}", response.status);
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt().with_env_filter("info").init();
+
+    let args: Vec<String> = env::args().collect();
+    let server_addr = get_arg(&args, "--server").unwrap_or_else(|| "localhost:7835".to_string());
+    let token = get_arg(&args, "--token").unwrap_or_else(|| "secret".to_string());
+    let local_addr = get_arg(&args, "--local-addr").unwrap_or_else(|| "127.0.0.1:8000".to_string());
+
+    let counter = Arc::new(RequestCounter::default());
+    let proxy = Arc::new(HttpProxy::new(local_addr).with_inspector(counter.clone()));
+
+    let mut client = TunnelClient::new(server_addr, token);
+    let run = client.connect_and_run(move |stream| {
+        let proxy = proxy.clone();
+        async move { proxy.handle_stream(stream) }
+    });
+
+    tokio::select! {
+        result = run => result?,
+        _ = tokio::signal::ctrl_c() => {}
+    }
+
+    println!(
+        "\nForwarded {} requests ({} server errors)",
+        counter.requests.load(Ordering::Relaxed),
+        counter.errors.load(Ordering::Relaxed)
+    );
+    Ok(())
+}
+
+fn get_arg(args: &[String], flag: &str) -> Option<String> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1).cloned())
+}
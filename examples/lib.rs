@@ -24,7 +24,8 @@
 //! │   └── server_observability.rs
 //! └── scenarios/          # Common usage scenarios
 //!     ├── expose_local_dev.rs
-//!     └── receive_webhooks_locally.rs
+//!     ├── receive_webhooks_locally.rs
+//!     └── expose_database.rs
 //! ```
 //!
 //! ## Basic Examples
@@ -85,10 +86,12 @@
 //!
 //! - **`expose_local_dev`** - Expose your local dev server (e.g. React) for sharing and testing
 //! - **`receive_webhooks_locally`** - Forward webhooks (GitHub, Stripe) to your local machine
+//! - **`expose_database`** - Tunnel a local Postgres over raw TCP behind an IP allowlist and secret
 //!
 //! ```bash
 //! cargo run -p ferrotunnel-examples --example expose_local_dev
 //! cargo run -p ferrotunnel-examples --example receive_webhooks_locally
+//! cargo run -p ferrotunnel-examples --example expose_database -- server
 //! ```
 //!
 //! ## Quick Start
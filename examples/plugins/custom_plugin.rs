@@ -15,7 +15,9 @@
 //! ```
 
 use async_trait::async_trait;
-use ferrotunnel_plugin::{Plugin, PluginAction, PluginRegistry, RequestContext, ResponseContext};
+use ferrotunnel_plugin::{
+    ByteCounter, Plugin, PluginAction, PluginRegistry, RequestContext, ResponseContext,
+};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -153,7 +155,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     println!();
 
     // Create plugin registry
-    let mut registry = PluginRegistry::new();
+    let registry = PluginRegistry::new();
 
     // Register our custom plugins
     let metrics = Arc::new(RwLock::new(MetricsPlugin::new()));
@@ -181,8 +183,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let ctx = RequestContext {
         tunnel_id: "demo-tunnel".to_string(),
         session_id: "demo-session".to_string(),
+        request_id: "request".to_string(),
         remote_addr: "127.0.0.1:12345".parse().unwrap(),
         timestamp: std::time::SystemTime::now(),
+        request_bytes: ByteCounter::default(),
     };
 
     // Simulate request to allowed path
@@ -1,7 +1,7 @@
 //! Example: Header Filter Plugin
 #![allow(clippy::print_stdout)]
 use async_trait::async_trait;
-use ferrotunnel_plugin::{Plugin, PluginAction, PluginRegistry, RequestContext};
+use ferrotunnel_plugin::{ByteCounter, Plugin, PluginAction, PluginRegistry, RequestContext};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -57,7 +57,7 @@ impl Plugin for HeaderFilterPlugin {
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     tracing_subscriber::fmt().init();
 
-    let mut registry = PluginRegistry::new();
+    let registry = PluginRegistry::new();
     registry.register(Arc::new(RwLock::new(HeaderFilterPlugin::new())));
     registry.init_all().await?;
 
@@ -70,8 +70,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let ctx = RequestContext {
         tunnel_id: "test".into(),
         session_id: "test".into(),
+        request_id: "request".into(),
         remote_addr: "127.0.0.1:0".parse()?,
         timestamp: std::time::SystemTime::now(),
+        request_bytes: ByteCounter::default(),
     };
 
     registry.execute_request_hooks(&mut req, &ctx).await?;
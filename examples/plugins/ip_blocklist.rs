@@ -1,7 +1,7 @@
 //! Example: IP Blocklist Plugin
 #![allow(clippy::print_stdout)]
 use async_trait::async_trait;
-use ferrotunnel_plugin::{Plugin, PluginAction, PluginRegistry, RequestContext};
+use ferrotunnel_plugin::{ByteCounter, Plugin, PluginAction, PluginRegistry, RequestContext};
 use std::collections::HashSet;
 use std::net::IpAddr;
 use std::sync::Arc;
@@ -50,7 +50,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     tracing_subscriber::fmt().init();
 
     let blocked_ip = "192.168.1.100".parse()?;
-    let mut registry = PluginRegistry::new();
+    let registry = PluginRegistry::new();
     registry.register(Arc::new(RwLock::new(IpBlocklistPlugin::new(vec![
         blocked_ip,
     ]))));
@@ -62,8 +62,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let allowed_ctx = RequestContext {
         tunnel_id: "test".into(),
         session_id: "test".into(),
+        request_id: "request".into(),
         remote_addr: "127.0.0.1:1234".parse()?,
         timestamp: std::time::SystemTime::now(),
+        request_bytes: ByteCounter::default(),
     };
 
     let action = registry
@@ -77,8 +79,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let blocked_ctx = RequestContext {
         tunnel_id: "test".into(),
         session_id: "test".into(),
+        request_id: "request".into(),
         remote_addr: format!("{blocked_ip}:1234").parse()?,
         timestamp: std::time::SystemTime::now(),
+        request_bytes: ByteCounter::default(),
     };
 
     let action = registry
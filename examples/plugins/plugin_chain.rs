@@ -24,7 +24,7 @@
 //! ```
 
 use ferrotunnel_plugin::builtin::{LoggerPlugin, RateLimitPlugin, TokenAuthPlugin};
-use ferrotunnel_plugin::{PluginAction, PluginRegistry, RequestContext};
+use ferrotunnel_plugin::{ByteCounter, PluginAction, PluginRegistry, RequestContext};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -43,7 +43,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     println!();
 
     // Create plugin registry and register builtin plugins
-    let mut registry = PluginRegistry::new();
+    let registry = PluginRegistry::new();
 
     // Plugin 1: Token authentication (valid tokens: "secret-123", "admin-token")
     let auth = TokenAuthPlugin::new(vec!["secret-123".to_string(), "admin-token".to_string()])
@@ -169,8 +169,10 @@ async fn execute_request(
     let ctx = RequestContext {
         tunnel_id: "demo-tunnel".to_string(),
         session_id: "demo-session".to_string(),
+        request_id: "request".to_string(),
         remote_addr: remote_addr.parse().unwrap(),
         timestamp: std::time::SystemTime::now(),
+        request_bytes: ByteCounter::default(),
     };
 
     registry.execute_request_hooks(&mut req, &ctx).await
@@ -0,0 +1,147 @@
+//! Example: Expose a Local Database (raw TCP) Behind an Auth Gate
+//!
+//! Tunnel a local Postgres so a teammate or CI job can reach it, without
+//! leaving the public port wide open. A database port has no HTTP layer to
+//! carry a token, so the TCP ingress checks every connection itself:
+//! - an IP allowlist (CIDRs) for the peer address
+//! - a shared secret the connection must send before any Postgres bytes
+//!
+//! The example runs in three roles:
+//! - `server`  - tunnel server plus a gated TCP ingress on `--public`
+//! - `client`  - next to the database; forwards tunnel streams to `--local`
+//! - `connect` - on the caller's machine; listens on `--listen`, sends the
+//!   secret to the ingress and then bridges, so `psql` needs no changes
+//!
+//! # Usage
+//!
+//! ```bash
+//! cargo run --example expose_database -- server --public 0.0.0.0:5433 --allow 203.0.113.0/24
+//! cargo run --example expose_database -- client --server tunnel.example.com:7835
+//! cargo run --example expose_database -- connect --ingress tunnel.example.com:5433
+//! psql -h 127.0.0.1 -p 15432 -U postgres
+//! ```
+
+use ferrotunnel::core::ip_filter::IpFilter;
+use ferrotunnel::core::{TunnelClient, TunnelServer};
+use ferrotunnel::http::{TcpAuth, TcpIngress, TcpIngressConfig};
+use ferrotunnel::{Result, TunnelError};
+use std::env;
+use std::net::SocketAddr;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+
+/// Capability the ingress routes database connections to
+const CAPABILITY: &str = "postgres";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter("info")
+        .compact()
+        .init();
+
+    let args: Vec<String> = env::args().collect();
+    let token = get_arg(&args, "--token").unwrap_or_else(|| "db-tunnel-token".to_string());
+    let secret = get_arg(&args, "--secret").unwrap_or_else(|| "change-me".to_string());
+
+    match args.get(1).map(String::as_str) {
+        Some("server") => run_server(&args, token, secret).await,
+        Some("client") => run_client(&args, token).await,
+        Some("connect") => run_connect(&args, &secret).await,
+        _ => {
+            eprintln!("usage: expose_database <server|client|connect> [options]");
+            Ok(())
+        }
+    }
+}
+
+/// Tunnel server with a TCP ingress that only bridges authorized connections
+async fn run_server(args: &[String], token: String, secret: String) -> Result<()> {
+    let bind = parse_addr(args, "--bind", "0.0.0.0:7835")?;
+    let public = parse_addr(args, "--public", "0.0.0.0:5433")?;
+    let allow: Vec<String> = get_arg(args, "--allow").into_iter().collect();
+    let ip_filter = IpFilter::from_lists(&allow, Vec::<&str>::new())
+        .map_err(|e| TunnelError::Config(e.to_string()))?;
+
+    let server = TunnelServer::new(bind, token);
+    let sessions = server.sessions();
+    tokio::spawn(async move {
+        if let Err(e) = server.run().await {
+            eprintln!("Tunnel server failed: {}", e);
+        }
+    });
+
+    let config = TcpIngressConfig {
+        port_capabilities: [(public.port(), CAPABILITY.to_string())].into(),
+        auth: Some(TcpAuth {
+            ip_filter,
+            secret: Some(secret.into_bytes()),
+        }),
+        ..Default::default()
+    };
+    println!("Database ingress on {} (allow: {:?})", public, allow);
+    TcpIngress::with_config(public, sessions, config)
+        .start()
+        .await
+}
+
+/// Tunnel client forwarding every stream to the local database
+async fn run_client(args: &[String], token: String) -> Result<()> {
+    let server_addr = get_arg(args, "--server").unwrap_or_else(|| "localhost:7835".to_string());
+    let local_addr = get_arg(args, "--local").unwrap_or_else(|| "127.0.0.1:5432".to_string());
+
+    println!("Forwarding tunnel streams to {}", local_addr);
+    let mut client = TunnelClient::new(server_addr, token).with_capability(CAPABILITY);
+    client
+        .connect_and_run(move |mut stream| {
+            let local_addr = local_addr.clone();
+            async move {
+                tokio::spawn(async move {
+                    match TcpStream::connect(&local_addr).await {
+                        Ok(mut db) => {
+                            let _ = tokio::io::copy_bidirectional(&mut stream, &mut db).await;
+                        }
+                        Err(e) => eprintln!("Database at {} unreachable: {}", local_addr, e),
+                    }
+                });
+            }
+        })
+        .await
+}
+
+/// Local forwarder that prefixes each connection with the secret
+async fn run_connect(args: &[String], secret: &str) -> Result<()> {
+    let listen = parse_addr(args, "--listen", "127.0.0.1:15432")?;
+    let ingress = get_arg(args, "--ingress").unwrap_or_else(|| "localhost:5433".to_string());
+
+    let listener = TcpListener::bind(listen).await?;
+    println!("Connect your database client to {}", listen);
+    loop {
+        let (mut local, _) = listener.accept().await?;
+        let ingress = ingress.clone();
+        let secret = secret.to_string();
+        tokio::spawn(async move {
+            let bridged = async {
+                let mut remote = TcpStream::connect(&ingress).await?;
+                remote.write_all(secret.as_bytes()).await?;
+                tokio::io::copy_bidirectional(&mut local, &mut remote).await
+            };
+            if let Err(e) = bridged.await {
+                eprintln!("Connection to {} failed: {}", ingress, e);
+            }
+        });
+    }
+}
+
+fn parse_addr(args: &[String], flag: &str, default: &str) -> Result<SocketAddr> {
+    let value = get_arg(args, flag).unwrap_or_else(|| default.to_string());
+    value
+        .parse()
+        .map_err(|_| TunnelError::Config(format!("invalid {flag}: {value}")))
+}
+
+fn get_arg(args: &[String], flag: &str) -> Option<String> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1).cloned())
+}
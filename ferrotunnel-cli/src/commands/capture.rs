@@ -0,0 +1,113 @@
+//! Capture subcommand implementation
+//!
+//! Prints the frames of a capture file written by a `FrameRecorder`.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::Args;
+use ferrotunnel_core::recorder::{Direction, RecordedFrame, RecordingReader};
+use ferrotunnel_protocol::Frame;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Payload bytes of a data frame shown in the listing
+const DATA_PREVIEW_LEN: usize = 32;
+
+#[derive(Args, Debug)]
+pub struct CaptureArgs {
+    /// Capture file recorded with `with_frame_recorder`
+    pub file: PathBuf,
+}
+
+pub fn run(args: &CaptureArgs) -> Result<()> {
+    let reader = RecordingReader::open(&args.file)
+        .with_context(|| format!("Failed to open capture {}", args.file.display()))?;
+    let mut out = std::io::stdout().lock();
+    for record in reader {
+        let record =
+            record.with_context(|| format!("Failed to read capture {}", args.file.display()))?;
+        writeln!(out, "{}", format_record(&record))?;
+    }
+    Ok(())
+}
+
+/// One line per frame: time, direction (`->` sent, `<-` received), frame
+fn format_record(record: &RecordedFrame) -> String {
+    let time: DateTime<Utc> = record.timestamp.into();
+    let arrow = match record.direction {
+        Direction::Sent => "->",
+        Direction::Received => "<-",
+    };
+    format!(
+        "{}  {arrow}  {}",
+        time.format("%Y-%m-%dT%H:%M:%S%.6fZ"),
+        describe(&record.frame)
+    )
+}
+
+/// Debug form of `frame`, with data payloads cut to a short preview
+fn describe(frame: &Frame) -> String {
+    match frame {
+        Frame::Data {
+            stream_id,
+            data,
+            end_of_stream,
+        } => {
+            let preview = &data[..data.len().min(DATA_PREVIEW_LEN)];
+            let ellipsis = if preview.len() < data.len() {
+                "..."
+            } else {
+                ""
+            };
+            format!(
+                "Data {{ stream_id: {stream_id}, len: {}, end_of_stream: {end_of_stream}, \
+                 data: \"{}{ellipsis}\" }}",
+                data.len(),
+                preview.escape_ascii()
+            )
+        }
+        other => format!("{other:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_format_record() {
+        let record = RecordedFrame {
+            timestamp: UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456),
+            direction: Direction::Sent,
+            frame: Frame::Heartbeat { timestamp: 7 },
+        };
+        assert_eq!(
+            format_record(&record),
+            "2023-11-14T22:13:20.123456Z  ->  Heartbeat { timestamp: 7 }"
+        );
+
+        let data = Frame::Data {
+            stream_id: 5,
+            data: Bytes::from(vec![b'a'; 40]),
+            end_of_stream: false,
+        };
+        assert_eq!(
+            describe(&data),
+            format!(
+                "Data {{ stream_id: 5, len: 40, end_of_stream: false, data: \"{}...\" }}",
+                "a".repeat(32)
+            )
+        );
+        let data = Frame::Data {
+            stream_id: 1,
+            data: Bytes::from_static(b"hi\r\n"),
+            end_of_stream: true,
+        };
+        assert_eq!(
+            describe(&data),
+            "Data { stream_id: 1, len: 4, end_of_stream: true, data: \"hi\\r\\n\" }"
+        );
+    }
+}
@@ -1,21 +1,30 @@
 //! Client subcommand implementation
 
+use crate::config::{self, merge, ClientFileConfig};
 use anyhow::{Context, Result};
 use chrono::Utc;
-use clap::Args;
+use clap::{ArgMatches, Args};
+use ferrotunnel_core::stream::TrafficCounters;
 use ferrotunnel_core::TunnelClient;
+use ferrotunnel_http::dns::{DnsCache, DEFAULT_DNS_CACHE_TTL};
 use ferrotunnel_http::proxy::LocalProxyService;
 use ferrotunnel_http::proxy::ProxyError;
+use ferrotunnel_http::udp_ingress::relay_udp_stream;
 use ferrotunnel_observability::dashboard::models::{DashboardTunnelInfo, TunnelStatus};
 use ferrotunnel_observability::{init_basic_observability, init_minimal_logging, shutdown_tracing};
 use ferrotunnel_protocol::frame::Protocol;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::net::TcpStream;
 use tracing::{error, info};
 
 use crate::middleware::DashboardCaptureLayer;
 
+/// Idle timeout for a relayed UDP flow (UDP has no close, so flows expire on silence)
+const UDP_FLOW_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often the dashboard's tunnel byte counters are refreshed
+const DASHBOARD_TRAFFIC_INTERVAL: Duration = Duration::from_secs(1);
+
 // Need to match the BoxBody type used in ferrotunnel-http
 type BoxBody = http_body_util::combinators::BoxBody<bytes::Bytes, ProxyError>;
 
@@ -102,9 +111,13 @@ pub struct TelemetryConfig {
 
 #[derive(Args, Debug)]
 pub struct ClientArgs {
-    /// Server address (host:port)
+    /// Path to a TOML config file; flags and env vars override its values
+    #[arg(long, env = "FERROTUNNEL_CONFIG")]
+    config: Option<std::path::PathBuf>,
+
+    /// Server address (host:port), required here or in the config file
     #[arg(long, env = "FERROTUNNEL_SERVER")]
-    server: String,
+    server: Option<String>,
 
     /// Authentication token. If omitted, uses FERROTUNNEL_TOKEN env var, or prompts securely.
     #[arg(long, env = "FERROTUNNEL_TOKEN")]
@@ -142,6 +155,80 @@ pub struct ClientArgs {
     tls_key: Option<std::path::PathBuf>,
 }
 
+impl ClientArgs {
+    /// Fill in options from `--config`, if given, where no flag or env var set them
+    pub fn apply_config_file(&mut self, matches: &ArgMatches) -> Result<()> {
+        let Some(path) = &self.config else {
+            return Ok(());
+        };
+        let file: ClientFileConfig = config::load(path)?;
+
+        merge(matches, "server", &mut self.server, file.server.map(Some));
+        merge(matches, "token", &mut self.token, file.token.map(Some));
+        merge(matches, "log_level", &mut self.log_level, file.log_level);
+        merge(matches, "local_addr", &mut self.local_addr, file.local_addr);
+        merge(
+            matches,
+            "tunnel_id",
+            &mut self.tunnel_id,
+            file.tunnel_id.map(Some),
+        );
+        merge(matches, "tls_ca", &mut self.tls_ca, file.tls_ca.map(Some));
+        merge(
+            matches,
+            "tls_server_name",
+            &mut self.tls_server_name,
+            file.tls_server_name.map(Some),
+        );
+        merge(
+            matches,
+            "tls_cert",
+            &mut self.tls_cert,
+            file.tls_cert.map(Some),
+        );
+        merge(
+            matches,
+            "tls_key",
+            &mut self.tls_key,
+            file.tls_key.map(Some),
+        );
+
+        let features = &mut self.features;
+        merge(
+            matches,
+            "port",
+            &mut features.dashboard.port,
+            file.dashboard_port,
+        );
+        merge(
+            matches,
+            "disabled",
+            &mut features.dashboard.disabled,
+            file.no_dashboard,
+        );
+        merge(matches, "enabled", &mut features.tls.enabled, file.tls);
+        merge(
+            matches,
+            "skip_verify",
+            &mut features.tls.skip_verify,
+            file.tls_skip_verify,
+        );
+        merge(
+            matches,
+            "observability",
+            &mut features.telemetry.observability,
+            file.observability,
+        );
+        merge(
+            matches,
+            "metrics",
+            &mut features.telemetry.metrics,
+            file.metrics,
+        );
+        Ok(())
+    }
+}
+
 /// Resolve token from args, then env, then secure prompt.
 fn resolve_token(args: &ClientArgs) -> Result<String> {
     if let Some(ref t) = args.token {
@@ -159,6 +246,7 @@ fn prompt_token() -> Result<String> {
         .context("Could not read token from terminal (is stdin a TTY?). Set FERROTUNNEL_TOKEN or pass --token")
 }
 
+#[allow(clippy::too_many_lines)]
 pub async fn run(args: ClientArgs) -> Result<()> {
     let enable_tracing = args.features.telemetry.observability;
     let enable_metrics = args.features.telemetry.metrics;
@@ -171,6 +259,9 @@ pub async fn run(args: ClientArgs) -> Result<()> {
 
     info!("Starting FerroTunnel Client v{}", env!("CARGO_PKG_VERSION"));
 
+    let server_addr = args.server.clone().context(
+        "No server address given: pass --server, set FERROTUNNEL_SERVER or add `server` to the config file",
+    )?;
     let token = resolve_token(&args)?;
 
     // Determine tunnel ID for routing
@@ -186,9 +277,18 @@ pub async fn run(args: ClientArgs) -> Result<()> {
     let dashboard_tunnel_id: Option<uuid::Uuid> =
         tunnel_id_string.as_ref().and_then(|s| s.parse().ok());
 
+    // Byte counters shared by every reconnect of this tunnel
+    let traffic = TrafficCounters::new();
+
+    // Resolved addresses of the local service, shared by raw TCP streams
+    let local_dns = Arc::new(DnsCache::new(
+        args.local_addr.clone(),
+        DEFAULT_DNS_CACHE_TTL,
+    ));
+
     // Start Dashboard and configure proxy
     let proxy: Arc<dyn StreamHandler> = if let Some(tunnel_id) = dashboard_tunnel_id {
-        setup_dashboard(&args, tunnel_id).await
+        setup_dashboard(&args, tunnel_id, traffic.clone()).await
     } else {
         Arc::new(ferrotunnel_http::HttpProxy::new(args.local_addr.clone()))
     };
@@ -197,7 +297,8 @@ pub async fn run(args: ClientArgs) -> Result<()> {
     tokio::select! {
         _ = async {
             loop {
-                let mut client = TunnelClient::new(args.server.clone(), token.clone());
+                let mut client = TunnelClient::new(server_addr.clone(), token.clone())
+                    .with_traffic(traffic.clone());
                 if let Some(ref tid) = tunnel_id_string {
                     client = client.with_tunnel_id(tid.clone());
                 }
@@ -206,15 +307,17 @@ pub async fn run(args: ClientArgs) -> Result<()> {
                 let proxy_ref = proxy.clone();
 
                 let local_addr_config = args.local_addr.clone();
+                let local_dns_ref = local_dns.clone();
                 match client
                     .connect_and_run(move |stream| {
                         let proxy = proxy_ref.clone();
                         let local_addr = local_addr_config.clone();
+                        let local_dns = local_dns_ref.clone();
                         async move {
                             if stream.protocol() == Protocol::TCP {
                                 // Handle raw TCP stream
                                 tokio::spawn(async move {
-                                    match TcpStream::connect(&local_addr).await {
+                                    match local_dns.connect().await {
                                         Ok(mut local_stream) => {
                                             let mut tunnel_stream = stream;
                                             let _ = tokio::io::copy_bidirectional(
@@ -231,6 +334,22 @@ pub async fn run(args: ClientArgs) -> Result<()> {
                                         }
                                     }
                                 });
+                            } else if stream.protocol() == Protocol::UDP {
+                                // Relay framed datagrams to the local UDP service
+                                tokio::spawn(async move {
+                                    if let Err(e) = relay_udp_stream(
+                                        stream,
+                                        &local_addr,
+                                        UDP_FLOW_IDLE_TIMEOUT,
+                                    )
+                                    .await
+                                    {
+                                        error!(
+                                            "Failed to relay to local UDP service {}: {}",
+                                            local_addr, e
+                                        );
+                                    }
+                                });
                             } else {
                                 // Handle HTTP/WebSocket stream via proxy
                                 proxy.handle(stream);
@@ -260,7 +379,11 @@ pub async fn run(args: ClientArgs) -> Result<()> {
     Ok(())
 }
 
-async fn setup_dashboard(args: &ClientArgs, tunnel_id: uuid::Uuid) -> Arc<dyn StreamHandler> {
+async fn setup_dashboard(
+    args: &ClientArgs,
+    tunnel_id: uuid::Uuid,
+    traffic: TrafficCounters,
+) -> Arc<dyn StreamHandler> {
     use ferrotunnel_observability::dashboard::{create_router, DashboardState, EventBroadcaster};
     use tokio::sync::RwLock;
 
@@ -292,13 +415,30 @@ async fn setup_dashboard(args: &ClientArgs, tunnel_id: uuid::Uuid) -> Arc<dyn St
             subdomain: None,
             public_url: None,
             local_addr: args.local_addr.clone(),
+            client_addr: None,
             created_at: Utc::now(),
             status: TunnelStatus::Connected,
+            bytes_in: 0,
+            bytes_out: 0,
         };
         state.add_tunnel(tunnel_info);
         info!("Registered tunnel {} in dashboard", tunnel_id);
     }
 
+    // Keep the dashboard's byte counters current
+    let traffic_state = dashboard_state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(DASHBOARD_TRAFFIC_INTERVAL);
+        loop {
+            interval.tick().await;
+            traffic_state.write().await.set_tunnel_traffic(
+                tunnel_id,
+                traffic.bytes_in(),
+                traffic.bytes_out(),
+            );
+        }
+    });
+
     // Initialize Proxy with Middleware
     info!("Traffic inspection enabled");
     let capture_layer = DashboardCaptureLayer {
@@ -338,3 +478,73 @@ fn setup_tls(mut client: TunnelClient, args: &ClientArgs) -> TunnelClient {
     }
     client
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches, Parser};
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[command(flatten)]
+        args: ClientArgs,
+    }
+
+    fn parse(argv: &[&str]) -> Result<ClientArgs> {
+        let matches = TestCli::command().try_get_matches_from(argv)?;
+        let mut args = TestCli::from_arg_matches(&matches)?.args;
+        args.apply_config_file(&matches)?;
+        Ok(args)
+    }
+
+    #[test]
+    fn test_config_file_with_flag_precedence() {
+        let path =
+            std::env::temp_dir().join(format!("ferrotunnel-client-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"
+server = "tunnel.example.com:7835"
+local_addr = "127.0.0.1:3000"
+tunnel_id = "from-file"
+dashboard_port = 5050
+tls = true
+tls_server_name = "tunnel.example.com"
+"#,
+        )
+        .unwrap();
+        let config = path.to_str().unwrap();
+
+        let args = parse(&["client", "--config", config]).unwrap();
+        assert_eq!(args.server.as_deref(), Some("tunnel.example.com:7835"));
+        assert_eq!(args.local_addr, "127.0.0.1:3000");
+        assert_eq!(args.tunnel_id.as_deref(), Some("from-file"));
+        assert_eq!(args.features.dashboard.port, 5050);
+        assert!(args.features.tls.enabled);
+        assert!(!args.features.tls.skip_verify);
+        assert_eq!(args.tls_server_name.as_deref(), Some("tunnel.example.com"));
+
+        let args = parse(&[
+            "client",
+            "--config",
+            config,
+            "--local-addr",
+            "127.0.0.1:4000",
+            "--dashboard-port",
+            "6060",
+        ])
+        .unwrap();
+        assert_eq!(args.local_addr, "127.0.0.1:4000");
+        assert_eq!(args.features.dashboard.port, 6060);
+        assert_eq!(args.tunnel_id.as_deref(), Some("from-file"));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_no_config_file_keeps_flags() {
+        let args = parse(&["client", "--server", "127.0.0.1:7835"]).unwrap();
+        assert_eq!(args.server.as_deref(), Some("127.0.0.1:7835"));
+        assert_eq!(args.local_addr, "127.0.0.1:8000");
+    }
+}
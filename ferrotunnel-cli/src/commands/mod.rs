@@ -1,5 +1,8 @@
 //! CLI command implementations
 
+pub mod capture;
 pub mod client;
+pub mod generate_cert;
 pub mod server;
+pub mod status;
 pub mod version;
@@ -1,24 +1,39 @@
 //! Server subcommand implementation
 
-use anyhow::Result;
-use clap::Args;
-use ferrotunnel_core::TunnelServer;
+use crate::config::{self, merge, ServerFileConfig};
+use anyhow::{Context, Result};
+use clap::{ArgMatches, Args};
+use ferrotunnel_common::LimitsConfig;
+use ferrotunnel_core::resource_limits::ServerResourceLimits;
+use ferrotunnel_core::transport::listener::ListenerConfig;
+use ferrotunnel_core::{announce_shutdown, TunnelServer};
 use ferrotunnel_observability::{
     gather_metrics, init_basic_observability, init_minimal_logging, shutdown_tracing,
+    tunnel_metrics,
 };
 use std::net::SocketAddr;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::time::Duration;
 use tracing::{error, info};
 
+/// How long to keep running after telling clients about a shutdown
+const SHUTDOWN_NOTICE_GRACE: Duration = Duration::from_millis(200);
+
 #[derive(Args, Debug)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct ServerArgs {
+    /// Path to a TOML config file; flags and env vars override its values
+    #[arg(long, env = "FERROTUNNEL_CONFIG")]
+    config: Option<PathBuf>,
+
     /// Address to bind to
     #[arg(long, default_value = "0.0.0.0:7835", env = "FERROTUNNEL_BIND")]
     bind: SocketAddr,
 
-    /// Authentication token
+    /// Authentication token (required here or in the config file)
     #[arg(long, env = "FERROTUNNEL_TOKEN")]
-    token: String,
+    token: Option<String>,
 
     /// Log level
     #[arg(long, default_value = "info", env = "RUST_LOG")]
@@ -52,6 +67,10 @@ pub struct ServerArgs {
     #[arg(long, env = "FERROTUNNEL_TCP_BIND")]
     tcp_bind: Option<SocketAddr>,
 
+    /// UDP Ingress bind address (optional, for UDP datagram tunneling)
+    #[arg(long, env = "FERROTUNNEL_UDP_BIND")]
+    udp_bind: Option<SocketAddr>,
+
     /// Enable tracing (metrics is separate via --metrics)
     #[arg(long, env = "FERROTUNNEL_OBSERVABILITY")]
     observability: bool,
@@ -59,8 +78,124 @@ pub struct ServerArgs {
     /// Enable metrics endpoint
     #[arg(long, env = "FERROTUNNEL_METRICS")]
     metrics: bool,
+
+    /// Export per-tunnel request and byte counters, labelling at most this
+    /// many tunnels (the rest are counted as "other"); requires --metrics
+    #[arg(long, env = "FERROTUNNEL_TUNNEL_METRICS_LIMIT")]
+    tunnel_metrics_limit: Option<usize>,
+
+    /// Accept backlog of each listener (default 1024; the OS may cap it)
+    #[arg(long, env = "FERROTUNNEL_BACKLOG")]
+    backlog: Option<u32>,
+
+    /// Set SO_REUSEPORT so other processes can bind the same addresses
+    #[arg(long, env = "FERROTUNNEL_REUSE_PORT")]
+    reuse_port: bool,
+
+    /// Listeners per control and HTTP address, each with its own accept
+    /// loop; more than one implies --reuse-port
+    #[arg(long, env = "FERROTUNNEL_ACCEPT_TASKS")]
+    accept_tasks: Option<NonZeroUsize>,
+
+    /// Resource limits from the config file's `[limits]` table (the session,
+    /// streams-per-session and in-flight frame limits are applied)
+    #[arg(skip)]
+    limits: Option<LimitsConfig>,
 }
 
+impl ServerArgs {
+    /// Fill in options from `--config`, if given, where no flag or env var set them
+    pub fn apply_config_file(&mut self, matches: &ArgMatches) -> Result<()> {
+        let Some(path) = &self.config else {
+            return Ok(());
+        };
+        let file: ServerFileConfig = config::load(path)?;
+
+        merge(matches, "bind", &mut self.bind, file.bind);
+        merge(matches, "token", &mut self.token, file.token.map(Some));
+        merge(matches, "log_level", &mut self.log_level, file.log_level);
+        merge(matches, "http_bind", &mut self.http_bind, file.http_bind);
+        merge(
+            matches,
+            "metrics_bind",
+            &mut self.metrics_bind,
+            file.metrics_bind,
+        );
+        merge(
+            matches,
+            "tls_cert",
+            &mut self.tls_cert,
+            file.tls_cert.map(Some),
+        );
+        merge(
+            matches,
+            "tls_key",
+            &mut self.tls_key,
+            file.tls_key.map(Some),
+        );
+        merge(matches, "tls_ca", &mut self.tls_ca, file.tls_ca.map(Some));
+        merge(
+            matches,
+            "tls_client_auth",
+            &mut self.tls_client_auth,
+            file.tls_client_auth,
+        );
+        merge(
+            matches,
+            "tcp_bind",
+            &mut self.tcp_bind,
+            file.tcp_bind.map(Some),
+        );
+        merge(
+            matches,
+            "udp_bind",
+            &mut self.udp_bind,
+            file.udp_bind.map(Some),
+        );
+        merge(
+            matches,
+            "observability",
+            &mut self.observability,
+            file.observability,
+        );
+        merge(matches, "metrics", &mut self.metrics, file.metrics);
+        merge(
+            matches,
+            "tunnel_metrics_limit",
+            &mut self.tunnel_metrics_limit,
+            file.tunnel_metrics_limit.map(Some),
+        );
+        merge(
+            matches,
+            "backlog",
+            &mut self.backlog,
+            file.backlog.map(Some),
+        );
+        merge(matches, "reuse_port", &mut self.reuse_port, file.reuse_port);
+        merge(
+            matches,
+            "accept_tasks",
+            &mut self.accept_tasks,
+            file.accept_tasks.map(Some),
+        );
+        self.limits = file.limits;
+        Ok(())
+    }
+
+    /// Socket options for the control, HTTP and TCP listeners
+    fn listener_config(&self) -> ListenerConfig {
+        let mut config = ListenerConfig::default().with_reuse_port(self.reuse_port);
+        if let Some(backlog) = self.backlog {
+            config = config.with_backlog(backlog);
+        }
+        if let Some(accept_tasks) = self.accept_tasks {
+            config = config.with_accept_tasks(accept_tasks.get());
+        }
+        config
+    }
+}
+
+#[allow(clippy::too_many_lines)]
 pub async fn run(args: ServerArgs) -> Result<()> {
     let enable_tracing = args.observability;
     let enable_metrics = args.metrics;
@@ -72,6 +207,10 @@ pub async fn run(args: ServerArgs) -> Result<()> {
     }
 
     if enable_metrics {
+        if let (Some(m), Some(max_tunnels)) = (tunnel_metrics(), args.tunnel_metrics_limit) {
+            m.enable_tunnel_usage(max_tunnels);
+        }
+
         // Start metrics endpoint in background (only when metrics is enabled)
         let metrics_addr = args.metrics_bind;
         tokio::spawn(async move {
@@ -93,7 +232,20 @@ pub async fn run(args: ServerArgs) -> Result<()> {
 
     info!("Starting FerroTunnel Server v{}", env!("CARGO_PKG_VERSION"));
 
-    let mut server = TunnelServer::new(args.bind, args.token.clone());
+    let token = args.token.clone().context(
+        "No token given: pass --token, set FERROTUNNEL_TOKEN or add `token` to the config file",
+    )?;
+    let listener = args.listener_config();
+    let mut server =
+        TunnelServer::new(args.bind, token.clone()).with_listener_config(listener.clone());
+
+    if let Some(limits) = &args.limits {
+        server = server.with_resource_limits(ServerResourceLimits::new(
+            limits.max_sessions,
+            limits.max_streams_per_session,
+            limits.max_inflight_frames,
+        ));
+    }
 
     if let (Some(cert_path), Some(key_path)) = (&args.tls_cert, &args.tls_key) {
         info!(
@@ -117,7 +269,7 @@ pub async fn run(args: ServerArgs) -> Result<()> {
     let server_handle = tokio::spawn(async move { server.run().await });
 
     info!("Initializing Plugin System");
-    let mut registry = ferrotunnel_plugin::PluginRegistry::new();
+    let registry = ferrotunnel_plugin::PluginRegistry::new();
 
     // built-in plugins
     // 1. Logger
@@ -127,25 +279,43 @@ pub async fn run(args: ServerArgs) -> Result<()> {
 
     // 2. Token Auth
     registry.register(std::sync::Arc::new(tokio::sync::RwLock::new(
-        ferrotunnel_plugin::builtin::TokenAuthPlugin::new(vec![args.token.clone()]),
+        ferrotunnel_plugin::builtin::TokenAuthPlugin::new(vec![token]),
     )));
 
     let registry = std::sync::Arc::new(registry);
 
     info!("Starting HTTP Ingress on {}", args.http_bind);
-    let http_ingress =
-        ferrotunnel_http::HttpIngress::new(args.http_bind, sessions.clone(), registry.clone());
+    let http_ingress = ferrotunnel_http::HttpIngress::with_config(
+        args.http_bind,
+        sessions.clone(),
+        registry.clone(),
+        ferrotunnel_http::IngressConfig::default().listener(listener.clone()),
+    );
     let http_handle = tokio::spawn(async move { http_ingress.start().await });
 
     // Start TCP Ingress (if enabled)
     let tcp_handle = if let Some(tcp_addr) = args.tcp_bind {
         info!("Starting TCP Ingress on {}", tcp_addr);
-        let tcp_ingress = ferrotunnel_http::TcpIngress::new(tcp_addr, sessions.clone());
+        let tcp_config = ferrotunnel_http::TcpIngressConfig {
+            listener,
+            ..Default::default()
+        };
+        let tcp_ingress =
+            ferrotunnel_http::TcpIngress::with_config(tcp_addr, sessions.clone(), tcp_config);
         Some(tokio::spawn(async move { tcp_ingress.start().await }))
     } else {
         None
     };
 
+    // Start UDP Ingress (if enabled)
+    let udp_handle = if let Some(udp_addr) = args.udp_bind {
+        info!("Starting UDP Ingress on {}", udp_addr);
+        let udp_ingress = ferrotunnel_http::UdpIngress::new(udp_addr, sessions.clone());
+        Some(tokio::spawn(async move { udp_ingress.start().await }))
+    } else {
+        None
+    };
+
     // Initialize plugins (after binding ports to avoid "Connection Refused")
     // Incoming requests will wait on the plugin lock if they arrive during init
     registry
@@ -165,6 +335,13 @@ pub async fn run(args: ServerArgs) -> Result<()> {
                     } else {
                         Ok(())
                     }
+                },
+                async {
+                    if let Some(h) = udp_handle {
+                        h.await.map_err(std::io::Error::other)?
+                    } else {
+                        Ok(())
+                    }
                 }
             )
         } => {
@@ -172,9 +349,138 @@ pub async fn run(args: ServerArgs) -> Result<()> {
         }
         _ = tokio::signal::ctrl_c() => {
             info!("Received shutdown signal, shutting down gracefully...");
+            let notified =
+                announce_shutdown(&sessions, "server shutting down", None).await;
+            if notified > 0 {
+                info!("Notified {} clients of shutdown", notified);
+                // Give the connections a moment to flush the notices
+                tokio::time::sleep(SHUTDOWN_NOTICE_GRACE).await;
+            }
             shutdown_tracing();
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches, Parser};
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[command(flatten)]
+        args: ServerArgs,
+    }
+
+    fn write_config(contents: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("ferrotunnel-server-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn parse(argv: &[&str]) -> Result<ServerArgs> {
+        let matches = TestCli::command().try_get_matches_from(argv)?;
+        let mut args = TestCli::from_arg_matches(&matches)?.args;
+        args.apply_config_file(&matches)?;
+        Ok(args)
+    }
+
+    const SAMPLE: &str = r#"
+bind = "127.0.0.1:7000"
+token = "file-token"
+http_bind = "127.0.0.1:8000"
+tls_cert = "/etc/ferrotunnel/server.crt"
+tls_key = "/etc/ferrotunnel/server.key"
+tcp_bind = "127.0.0.1:9000"
+metrics = true
+
+[limits]
+max_sessions = 10
+max_streams_per_session = 4
+"#;
+
+    #[test]
+    fn test_config_file_values_applied() {
+        let path = write_config(SAMPLE);
+        let args = parse(&["server", "--config", path.to_str().unwrap()]).unwrap();
+
+        assert_eq!(args.bind, "127.0.0.1:7000".parse().unwrap());
+        assert_eq!(args.token.as_deref(), Some("file-token"));
+        assert_eq!(args.http_bind, "127.0.0.1:8000".parse().unwrap());
+        assert_eq!(
+            args.tls_cert,
+            Some(PathBuf::from("/etc/ferrotunnel/server.crt"))
+        );
+        assert_eq!(args.tcp_bind, Some("127.0.0.1:9000".parse().unwrap()));
+        assert!(args.metrics);
+        // Not in the file: built-in default
+        assert_eq!(args.metrics_bind, "0.0.0.0:9090".parse().unwrap());
+
+        let limits = args.limits.unwrap();
+        assert_eq!(limits.max_sessions, 10);
+        assert_eq!(limits.max_streams_per_session, 4);
+        assert_eq!(
+            limits.max_inflight_frames,
+            LimitsConfig::default().max_inflight_frames
+        );
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_flags_override_config_file() {
+        let path = write_config(SAMPLE);
+        let args = parse(&[
+            "server",
+            "--config",
+            path.to_str().unwrap(),
+            "--bind",
+            "127.0.0.1:7100",
+            "--token",
+            "flag-token",
+        ])
+        .unwrap();
+
+        assert_eq!(args.bind, "127.0.0.1:7100".parse().unwrap());
+        assert_eq!(args.token.as_deref(), Some("flag-token"));
+        // Options without a flag still come from the file
+        assert_eq!(args.http_bind, "127.0.0.1:8000".parse().unwrap());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_listener_options() {
+        let args = parse(&["server"]).unwrap();
+        assert_eq!(args.listener_config(), ListenerConfig::default());
+
+        let path = write_config("backlog = 2048\naccept_tasks = 2\n");
+        let args = parse(&[
+            "server",
+            "--config",
+            path.to_str().unwrap(),
+            "--accept-tasks",
+            "4",
+        ])
+        .unwrap();
+        let listener = args.listener_config();
+        assert_eq!(listener.backlog, 2048);
+        assert_eq!(listener.accept_tasks, 4);
+        assert!(listener.reuse_port);
+        let _ = std::fs::remove_file(path);
+
+        assert!(parse(&["server", "--accept-tasks", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_unknown_config_key_is_an_error() {
+        let path = write_config("bind = \"127.0.0.1:7000\"\nhttp_bnd = \"127.0.0.1:8000\"\n");
+        let err = parse(&["server", "--config", path.to_str().unwrap()]).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("unknown field `http_bnd`"),
+            "{err:#}"
+        );
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,245 @@
+//! Status subcommand implementation
+//!
+//! Queries the dashboard API of a running client and prints its tunnels.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::Args;
+use ferrotunnel_observability::dashboard::models::{
+    DashboardTunnelInfo, HealthResponse, TunnelStatus,
+};
+use std::fmt::Write as _;
+use std::time::Duration;
+
+/// How long to wait for the dashboard before giving up
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Args, Debug)]
+pub struct StatusArgs {
+    /// Base URL of the client's dashboard
+    #[arg(
+        long,
+        default_value = "http://127.0.0.1:4040",
+        env = "FERROTUNNEL_DASHBOARD_URL"
+    )]
+    pub dashboard_url: String,
+}
+
+/// Everything `status` prints, as returned by the dashboard
+#[derive(Debug)]
+struct Snapshot {
+    health: HealthResponse,
+    tunnels: Vec<DashboardTunnelInfo>,
+}
+
+pub async fn run(args: StatusArgs) -> Result<()> {
+    let snapshot = fetch(&args.dashboard_url).await?;
+    print!("{}", render(&args.dashboard_url, &snapshot, Utc::now()));
+    Ok(())
+}
+
+async fn fetch(dashboard_url: &str) -> Result<Snapshot> {
+    let base = dashboard_url.trim_end_matches('/');
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .context("Failed to build HTTP client")?;
+    let health = get_json(&client, base, "/api/v1/health").await?;
+    let tunnels = get_json(&client, base, "/api/v1/tunnels").await?;
+    Ok(Snapshot { health, tunnels })
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(
+    client: &reqwest::Client,
+    base: &str,
+    path: &str,
+) -> Result<T> {
+    let url = format!("{base}{path}");
+    let response = match client.get(&url).send().await {
+        Ok(response) => response,
+        Err(e) if e.is_connect() => bail!(
+            "Could not connect to the dashboard at {base}. Is a ferrotunnel client \
+             running with its dashboard enabled? (use --dashboard-url to point elsewhere)"
+        ),
+        Err(e) => return Err(e).with_context(|| format!("Request to {url} failed")),
+    };
+    let status = response.status();
+    if !status.is_success() {
+        bail!("Dashboard returned {status} for {url}");
+    }
+    response
+        .json()
+        .await
+        .with_context(|| format!("Invalid response from {url}"))
+}
+
+fn render(dashboard_url: &str, snapshot: &Snapshot, now: DateTime<Utc>) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "Dashboard {dashboard_url}: {} (v{})",
+        snapshot.health.status, snapshot.health.version
+    );
+    if snapshot.tunnels.is_empty() {
+        out.push_str("No active tunnels\n");
+        return out;
+    }
+
+    let rows: Vec<[String; 4]> = snapshot
+        .tunnels
+        .iter()
+        .map(|t| {
+            [
+                t.id.to_string(),
+                t.local_addr.clone(),
+                status_label(t.status).to_string(),
+                format_uptime(now.signed_duration_since(t.created_at)),
+            ]
+        })
+        .collect();
+    let header = ["ID", "LOCAL ADDR", "STATUS", "UPTIME"].map(String::from);
+    let mut widths = header.clone().map(|h| h.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        let _ = writeln!(out, "{}", line.trim_end());
+    }
+    out
+}
+
+fn status_label(status: TunnelStatus) -> &'static str {
+    match status {
+        TunnelStatus::Connecting => "connecting",
+        TunnelStatus::Connected => "connected",
+        TunnelStatus::Disconnected => "disconnected",
+    }
+}
+
+/// Compact uptime such as `2d 3h`, `1h 05m` or `42s`
+fn format_uptime(elapsed: chrono::Duration) -> String {
+    let secs = elapsed.num_seconds().max(0);
+    let (days, hours, mins, secs) = (secs / 86_400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {mins:02}m")
+    } else if mins > 0 {
+        format!("{mins}m {secs:02}s")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Json, Router};
+    use uuid::Uuid;
+
+    fn tunnel(local_addr: &str, status: TunnelStatus, age_secs: i64) -> DashboardTunnelInfo {
+        DashboardTunnelInfo {
+            id: Uuid::new_v4(),
+            subdomain: None,
+            public_url: None,
+            local_addr: local_addr.to_string(),
+            client_addr: None,
+            created_at: Utc::now() - chrono::Duration::seconds(age_secs),
+            status,
+            bytes_in: 0,
+            bytes_out: 0,
+        }
+    }
+
+    async fn mock_dashboard(tunnels: Vec<DashboardTunnelInfo>) -> String {
+        let app = Router::new()
+            .route(
+                "/api/v1/health",
+                get(|| async {
+                    Json(HealthResponse {
+                        status: "healthy".to_string(),
+                        version: "9.9.9".to_string(),
+                    })
+                }),
+            )
+            .route(
+                "/api/v1/tunnels",
+                get(move || {
+                    let tunnels = tunnels.clone();
+                    async move { Json(tunnels) }
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{addr}/")
+    }
+
+    #[tokio::test]
+    async fn test_status_renders_mock_dashboard() {
+        let web = tunnel("127.0.0.1:3000", TunnelStatus::Connected, 3725);
+        let api = tunnel("localhost:8080", TunnelStatus::Connecting, 5);
+        let url = mock_dashboard(vec![web.clone(), api.clone()]).await;
+
+        let snapshot = fetch(&url).await.unwrap();
+        assert_eq!(snapshot.health.version, "9.9.9");
+        assert_eq!(snapshot.tunnels.len(), 2);
+
+        let now = web.created_at + chrono::Duration::seconds(3725);
+        let output = render(&url, &snapshot, now);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], format!("Dashboard {url}: healthy (v9.9.9)"));
+        assert!(lines[1].starts_with("ID "), "{output}");
+        assert!(lines[1].contains("LOCAL ADDR"), "{output}");
+        assert!(lines[2].starts_with(&web.id.to_string()), "{output}");
+        assert!(lines[2].contains("127.0.0.1:3000"), "{output}");
+        assert!(lines[2].contains("connected"), "{output}");
+        assert!(lines[2].ends_with("1h 02m"), "{output}");
+        assert!(lines[3].contains("connecting"), "{output}");
+        // Columns line up under the header
+        let col = lines[1].find("STATUS").unwrap();
+        assert_eq!(lines[2].find("connected"), Some(col));
+    }
+
+    #[tokio::test]
+    async fn test_status_without_tunnels() {
+        let url = mock_dashboard(Vec::new()).await;
+        let snapshot = fetch(&url).await.unwrap();
+        let output = render(&url, &snapshot, Utc::now());
+        assert!(output.ends_with("No active tunnels\n"), "{output}");
+    }
+
+    #[tokio::test]
+    async fn test_connection_refused_is_explained() {
+        // Bind then drop to get a port nothing is listening on
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let err = fetch(&format!("http://{addr}"))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Could not connect to the dashboard"), "{err}");
+        assert!(err.contains(&addr.to_string()), "{err}");
+    }
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(chrono::Duration::seconds(42)), "42s");
+        assert_eq!(format_uptime(chrono::Duration::seconds(65)), "1m 05s");
+        assert_eq!(format_uptime(chrono::Duration::seconds(3725)), "1h 02m");
+        assert_eq!(format_uptime(chrono::Duration::seconds(183_600)), "2d 3h");
+        assert_eq!(format_uptime(chrono::Duration::seconds(-5)), "0s");
+    }
+}
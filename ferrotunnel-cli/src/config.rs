@@ -0,0 +1,113 @@
+//! Config file support for the CLI subcommands
+//!
+//! `--config <path>` loads a TOML file whose keys are the subcommand's option
+//! names in snake_case (e.g. `http_bind`, `tls_cert`). Precedence is
+//! command-line flag, then environment variable, then config file, then the
+//! built-in default. Unknown keys are rejected so typos do not go unnoticed.
+
+use anyhow::{Context, Result};
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use ferrotunnel_common::LimitsConfig;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+/// Values for `ferrotunnel server` read from a config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerFileConfig {
+    pub bind: Option<SocketAddr>,
+    pub token: Option<String>,
+    pub log_level: Option<String>,
+    pub http_bind: Option<SocketAddr>,
+    pub metrics_bind: Option<SocketAddr>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub tls_ca: Option<PathBuf>,
+    pub tls_client_auth: Option<bool>,
+    pub tcp_bind: Option<SocketAddr>,
+    pub udp_bind: Option<SocketAddr>,
+    pub observability: Option<bool>,
+    pub metrics: Option<bool>,
+    pub tunnel_metrics_limit: Option<usize>,
+    pub backlog: Option<u32>,
+    pub reuse_port: Option<bool>,
+    pub accept_tasks: Option<NonZeroUsize>,
+    /// `[limits]` table; only available from the config file
+    pub limits: Option<LimitsConfig>,
+}
+
+/// Values for `ferrotunnel client` read from a config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientFileConfig {
+    pub server: Option<String>,
+    pub token: Option<String>,
+    pub log_level: Option<String>,
+    pub local_addr: Option<String>,
+    pub tunnel_id: Option<String>,
+    pub dashboard_port: Option<u16>,
+    pub no_dashboard: Option<bool>,
+    pub tls: Option<bool>,
+    pub tls_skip_verify: Option<bool>,
+    pub tls_ca: Option<PathBuf>,
+    pub tls_server_name: Option<String>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub observability: Option<bool>,
+    pub metrics: Option<bool>,
+}
+
+/// Read and parse a TOML config file
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    toml::from_str(&text).with_context(|| format!("Invalid config file {}", path.display()))
+}
+
+/// Overwrite `target` with a config file `value`, unless the option `id` was
+/// given on the command line or through its environment variable
+pub fn merge<T>(matches: &ArgMatches, id: &str, target: &mut T, value: Option<T>) {
+    let Some(value) = value else {
+        return;
+    };
+    let explicit = matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    );
+    if !explicit {
+        *target = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_key_rejected() {
+        let err = toml::from_str::<ServerFileConfig>("bind = \"0.0.0.0:7835\"\nbnd = 1\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown field `bnd`"), "{err}");
+    }
+
+    #[test]
+    fn test_unknown_limits_key_rejected() {
+        let err = toml::from_str::<ServerFileConfig>("[limits]\nmax_session = 5\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown field `max_session`"), "{err}");
+    }
+
+    #[test]
+    fn test_missing_file_error_names_path() {
+        let err = load::<ClientFileConfig>(Path::new("/nonexistent/ferrotunnel.toml"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("/nonexistent/ferrotunnel.toml"), "{err}");
+    }
+}
@@ -7,10 +7,11 @@
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 mod commands;
+mod config;
 mod middleware;
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(
@@ -35,6 +36,15 @@ enum Commands {
     /// Run the tunnel client
     Client(commands::client::ClientArgs),
 
+    /// Show the tunnels of a running client via its dashboard
+    Status(commands::status::StatusArgs),
+
+    /// Print the frames of a frame capture file
+    Capture(commands::capture::CaptureArgs),
+
+    /// Generate self-signed TLS certificates for local testing
+    GenerateCert(commands::generate_cert::GenerateCertArgs),
+
     /// Show version information
     Version,
 }
@@ -48,11 +58,25 @@ async fn main() -> Result<()> {
     // provider has already been installed (e.g. in tests), which is fine.
     let _ = rustls::crypto::ring::default_provider().install_default();
 
-    let cli = Cli::parse();
+    // Keep the raw matches: config file values only fill in options that were
+    // not given as flags or env vars
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let no_matches = ArgMatches::default();
+    let sub_matches = matches.subcommand().map_or(&no_matches, |(_, m)| m);
 
     match cli.command {
-        Commands::Server(args) => commands::server::run(args).await,
-        Commands::Client(args) => commands::client::run(args).await,
+        Commands::Server(mut args) => {
+            args.apply_config_file(sub_matches)?;
+            commands::server::run(args).await
+        }
+        Commands::Client(mut args) => {
+            args.apply_config_file(sub_matches)?;
+            commands::client::run(args).await
+        }
+        Commands::Status(args) => commands::status::run(args).await,
+        Commands::Capture(args) => commands::capture::run(&args),
+        Commands::GenerateCert(args) => commands::generate_cert::run(&args),
         Commands::Version => {
             commands::version::run();
             Ok(())
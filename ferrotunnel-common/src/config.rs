@@ -21,7 +21,11 @@ pub struct TlsConfig {
 }
 
 /// Resource limits configuration
+///
+/// Missing fields take their defaults when deserialized; unknown fields are
+/// an error.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct LimitsConfig {
     /// Maximum frame size in bytes (default: 16MB)
     pub max_frame_bytes: u64,
@@ -1,5 +1,6 @@
 //! Error types for `FerroTunnel`
 
+use std::time::Duration;
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -26,10 +27,20 @@ pub enum TunnelError {
     #[error("Stream {0} not found")]
     StreamNotFound(u32),
 
-    /// Timeout error
+    /// An operation did not complete in time, e.g. a handshake or heartbeat
+    /// ack that never arrived
     #[error("Timeout: {0}")]
     Timeout(String),
 
+    /// A resource limit was reached, e.g. the server's session limit or a
+    /// session's stream limit
+    #[error("Resource exhausted: {0}")]
+    ResourceExhausted(String),
+
+    /// Rejected by a rate limit
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+
     /// Configuration error
     #[error("Configuration error: {0}")]
     Config(String),
@@ -53,6 +64,33 @@ pub enum TunnelError {
     /// Serialization error
     #[error("Serialization error: {0}")]
     Serialization(String),
+
+    /// No protocol version supported by both peers
+    #[error("Protocol version mismatch: {0}")]
+    VersionMismatch(String),
+
+    /// The server announced it is shutting down, optionally suggesting how
+    /// long to wait before reconnecting
+    #[error("Server shutting down: {reason}")]
+    ServerShutdown {
+        reason: String,
+        reconnect_after: Option<Duration>,
+    },
+}
+
+impl TunnelError {
+    /// Whether retrying the operation that failed may succeed
+    ///
+    /// Rejected credentials, invalid configuration and protocol version
+    /// mismatches fail the same way every time; everything else (I/O errors,
+    /// timeouts, dropped connections, busy or rate-limited servers) may be
+    /// transient.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(
+            self,
+            Self::Authentication(_) | Self::Config(_) | Self::VersionMismatch(_)
+        )
+    }
 }
 
 /// Result type alias
@@ -86,4 +124,21 @@ mod tests {
         let tunnel_err: TunnelError = io_err.into();
         assert!(matches!(tunnel_err, TunnelError::Io(_)));
     }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(TunnelError::Connection("refused".into()).is_retryable());
+        assert!(TunnelError::Timeout("no ack".into()).is_retryable());
+        assert!(TunnelError::ServiceUnavailable("busy".into()).is_retryable());
+        assert!(TunnelError::ResourceExhausted("sessions".into()).is_retryable());
+        assert!(TunnelError::RateLimited("streams".into()).is_retryable());
+        assert!(TunnelError::ServerShutdown {
+            reason: "restarting".into(),
+            reconnect_after: None,
+        }
+        .is_retryable());
+        assert!(!TunnelError::Authentication("bad token".into()).is_retryable());
+        assert!(!TunnelError::Config("missing token".into()).is_retryable());
+        assert!(!TunnelError::VersionMismatch("v9".into()).is_retryable());
+    }
 }
@@ -66,9 +66,52 @@ fn bench_multiplexer_stream_creation(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_virtual_stream_bulk_write(c: &mut Criterion) {
+    use tokio::io::AsyncWriteExt;
+
+    let mut group = c.benchmark_group("virtual_stream_bulk_write");
+    let rt = tokio::runtime::Runtime::new().unwrap();
+
+    // Large enough to span many 64KB data frames
+    const PAYLOAD_SIZE: usize = 4 * 1024 * 1024;
+    group.throughput(Throughput::Bytes(PAYLOAD_SIZE as u64));
+    let payload = bytes::Bytes::from(vec![0u8; PAYLOAD_SIZE]);
+
+    for (name, fast_path) in [("write_all", false), ("send_bytes", true)] {
+        let payload = payload.clone();
+        group.bench_function(name, |b| {
+            let payload = payload.clone();
+            b.to_async(&rt).iter_custom(move |iters| {
+                let payload = payload.clone();
+                async move {
+                    let (frame_tx, frame_rx) = bounded_async(1024);
+                    let (multiplexer, _new_stream_rx) = Multiplexer::new(frame_tx, true);
+
+                    // Dummy consumer
+                    tokio::spawn(async move { while frame_rx.recv().await.is_ok() {} });
+                    let mut stream = multiplexer.open_stream(Protocol::TCP).await.unwrap();
+
+                    let start = std::time::Instant::now();
+                    for _ in 0..iters {
+                        if fast_path {
+                            stream.send_bytes(payload.clone()).await.unwrap();
+                        } else {
+                            stream.write_all(&payload).await.unwrap();
+                        }
+                    }
+                    start.elapsed()
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_multiplexer_throughput,
-    bench_multiplexer_stream_creation
+    bench_multiplexer_stream_creation,
+    bench_virtual_stream_bulk_write
 );
 criterion_main!(benches);
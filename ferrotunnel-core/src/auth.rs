@@ -1,7 +1,15 @@
 //! Authentication utilities for secure token handling
 
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
 use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
 use subtle::ConstantTimeEq;
+use tracing::{info, warn};
 
 /// Constant-time comparison of two byte slices
 /// Returns true if slices are equal, false otherwise
@@ -16,6 +24,304 @@ pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
     a.ct_eq(b).into()
 }
 
+/// Set of currently valid authentication tokens
+///
+/// Cheap to clone; all clones share the same underlying token list so that a
+/// reload is immediately visible to every connection handler.
+#[derive(Debug, Clone, Default)]
+pub struct TokenStore {
+    tokens: Arc<RwLock<Vec<String>>>,
+}
+
+impl TokenStore {
+    /// Create a store holding the given tokens
+    #[must_use]
+    pub fn new(tokens: Vec<String>) -> Self {
+        Self {
+            tokens: Arc::new(RwLock::new(tokens)),
+        }
+    }
+
+    /// Check whether `token` matches any valid token
+    ///
+    /// Every candidate is compared in constant time and the loop does not
+    /// short-circuit, so timing does not reveal which token matched.
+    #[must_use]
+    pub fn contains(&self, token: &str) -> bool {
+        let tokens = match self.tokens.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut matched = false;
+        for candidate in tokens.iter() {
+            matched |= constant_time_eq(token.as_bytes(), candidate.as_bytes());
+        }
+        matched
+    }
+
+    /// Check whether `response` answers `nonce` for any valid token, i.e.
+    /// equals its [`challenge_response`]
+    ///
+    /// Like [`contains`](Self::contains), every token is checked so timing
+    /// does not reveal which one matched.
+    #[must_use]
+    pub fn answers_challenge(&self, nonce: &[u8], response: &[u8]) -> bool {
+        self.challenge_client_id(nonce, response).is_some()
+    }
+
+    /// [`token_client_id`] of the token whose [`challenge_response`] to
+    /// `nonce` is `response`, if any
+    ///
+    /// Every token is checked, like in [`answers_challenge`](Self::answers_challenge).
+    #[must_use]
+    pub fn challenge_client_id(&self, nonce: &[u8], response: &[u8]) -> Option<String> {
+        let tokens = match self.tokens.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut matched = None;
+        for candidate in tokens.iter() {
+            if constant_time_eq(&challenge_response(candidate, nonce), response) {
+                matched = Some(token_client_id(candidate));
+            }
+        }
+        matched
+    }
+
+    /// Replace the current token set
+    pub fn replace(&self, tokens: Vec<String>) {
+        let mut guard = match self.tokens.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *guard = tokens;
+    }
+
+    /// Number of currently valid tokens
+    #[must_use]
+    pub fn len(&self) -> usize {
+        match self.tokens.read() {
+            Ok(guard) => guard.len(),
+            Err(poisoned) => poisoned.into_inner().len(),
+        }
+    }
+
+    /// Returns true if no token is currently valid
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reload tokens from a newline-delimited file
+    ///
+    /// Blank lines and lines starting with `#` are ignored. On error the
+    /// current token set is left untouched.
+    pub fn reload_from_file(&self, path: &Path) -> std::io::Result<usize> {
+        let tokens = read_token_file(path)?;
+        let count = tokens.len();
+        self.replace(tokens);
+        Ok(count)
+    }
+
+    /// Poll `path` every `interval` and reload when its modification time changes
+    ///
+    /// The returned task runs until aborted.
+    pub fn spawn_file_watcher(
+        &self,
+        path: PathBuf,
+        interval: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let store = self.clone();
+        tokio::spawn(async move {
+            let mut last_modified = file_mtime(&path);
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let modified = file_mtime(&path);
+                if modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                match store.reload_from_file(&path) {
+                    Ok(count) => info!("Reloaded {} auth tokens from {}", count, path.display()),
+                    Err(e) => warn!("Failed to reload token file {}: {}", path.display(), e),
+                }
+            }
+        })
+    }
+}
+
+/// Outcome of authenticating a client handshake
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthResult {
+    /// The client may connect, within the limits of the grant
+    Granted(AuthGrant),
+    /// The client is rejected with `HandshakeStatus::InvalidToken`
+    Denied,
+}
+
+impl AuthResult {
+    /// Grant with no restrictions
+    #[must_use]
+    pub fn granted() -> Self {
+        Self::Granted(AuthGrant::default())
+    }
+}
+
+/// Restrictions attached to an accepted client
+///
+/// The default grant allows everything the client asks for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuthGrant {
+    /// Tunnel IDs (subdomains) the client may register; `None` allows any.
+    /// A client restricted this way must request one of them explicitly.
+    pub tunnel_ids: Option<Vec<String>>,
+    /// Routing capabilities the client may claim; `None` allows any. Other
+    /// advertised capabilities are left off the session, so ingress routes
+    /// keyed on them never reach this client.
+    pub capabilities: Option<Vec<String>>,
+    /// Concurrent ingress requests allowed for the client's tunnel; `None`
+    /// uses the ingress-wide limit and 0 lifts it
+    pub max_in_flight_requests: Option<usize>,
+    /// Who the client proved to be when it answered a challenge instead of
+    /// sending its token. Only a client with the same ID may resume its
+    /// session; challenge-auth sessions without one cannot be resumed.
+    pub client_id: Option<String>,
+}
+
+impl AuthGrant {
+    /// Only allow registering one of `tunnel_ids`
+    #[must_use]
+    pub fn with_tunnel_ids(mut self, tunnel_ids: Vec<String>) -> Self {
+        self.tunnel_ids = Some(tunnel_ids);
+        self
+    }
+
+    /// Only allow claiming the given routing capabilities
+    #[must_use]
+    pub fn with_capabilities(mut self, capabilities: Vec<String>) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
+    /// Override the ingress limit on concurrent requests to this client's
+    /// tunnel
+    #[must_use]
+    pub fn with_max_in_flight_requests(mut self, limit: usize) -> Self {
+        self.max_in_flight_requests = Some(limit);
+        self
+    }
+
+    /// Identify the client for session resumption, see [`Self::client_id`]
+    #[must_use]
+    pub fn with_client_id(mut self, client_id: impl Into<String>) -> Self {
+        self.client_id = Some(client_id.into());
+        self
+    }
+
+    /// Whether the grant covers registering `tunnel_id`
+    #[must_use]
+    pub fn allows_tunnel_id(&self, tunnel_id: &str) -> bool {
+        self.tunnel_ids
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(|id| id == tunnel_id))
+    }
+
+    /// The advertised capabilities this grant lets the client keep
+    #[must_use]
+    pub fn filter_capabilities(&self, advertised: Vec<String>) -> Vec<String> {
+        match &self.capabilities {
+            None => advertised,
+            Some(allowed) => advertised
+                .into_iter()
+                .filter(|cap| allowed.contains(cap))
+                .collect(),
+        }
+    }
+}
+
+/// Decides whether a client handshake is accepted
+///
+/// The server calls this for every handshake with the client's token, the
+/// tunnel ID it asked for (if any) and its address. Implement it to check
+/// tokens against an external service (OIDC introspection, a database,
+/// signed tokens) instead of a static list. [`TokenStore`] is the default
+/// implementation.
+#[async_trait]
+pub trait Authenticator: Send + Sync {
+    async fn authenticate(
+        &self,
+        token: &str,
+        tunnel_id: Option<&str>,
+        peer: SocketAddr,
+    ) -> AuthResult;
+
+    /// Authenticate a client that answered the server's `nonce` with
+    /// `response` instead of sending its token
+    ///
+    /// `response` should be the [`challenge_response`] of a valid token. Only
+    /// backends holding the raw tokens can check it, so the default denies
+    /// and clients of other backends must send their token.
+    /// Set [`AuthGrant::client_id`] on grants so the client can later
+    /// resume its session.
+    async fn authenticate_challenge(
+        &self,
+        _nonce: &[u8],
+        _response: &[u8],
+        _tunnel_id: Option<&str>,
+        _peer: SocketAddr,
+    ) -> AuthResult {
+        AuthResult::Denied
+    }
+}
+
+#[async_trait]
+impl Authenticator for TokenStore {
+    async fn authenticate(
+        &self,
+        token: &str,
+        _tunnel_id: Option<&str>,
+        _peer: SocketAddr,
+    ) -> AuthResult {
+        if self.contains(token) {
+            AuthResult::granted()
+        } else {
+            AuthResult::Denied
+        }
+    }
+
+    async fn authenticate_challenge(
+        &self,
+        nonce: &[u8],
+        response: &[u8],
+        _tunnel_id: Option<&str>,
+        _peer: SocketAddr,
+    ) -> AuthResult {
+        match self.challenge_client_id(nonce, response) {
+            Some(client_id) => AuthResult::Granted(AuthGrant::default().with_client_id(client_id)),
+            None => AuthResult::Denied,
+        }
+    }
+}
+
+/// Read a newline-delimited token file
+///
+/// Blank lines and lines starting with `#` are ignored.
+pub fn read_token_file(path: &Path) -> std::io::Result<Vec<String>> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+fn file_mtime(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
 /// Hash a token using SHA-256
 ///
 /// Useful for storing tokens securely - store the hash, compare against hash
@@ -26,6 +332,31 @@ pub fn hash_token(token: &str) -> [u8; 32] {
     hasher.finalize().into()
 }
 
+/// Client ID for a token: the hex SHA-256 of it, so sessions can tell clients
+/// apart without keeping the token
+#[must_use]
+pub fn token_client_id(token: &str) -> String {
+    hash_token(token)
+        .iter()
+        .fold(String::with_capacity(64), |mut id, byte| {
+            let _ = write!(id, "{byte:02x}");
+            id
+        })
+}
+
+/// Answer to an auth challenge: `HMAC-SHA256(token, nonce)`
+///
+/// Clients send this in place of their token, so the token never crosses the
+/// wire. The server picks a fresh nonce for every connection, which makes an
+/// answer captured from one connection useless on the next.
+#[must_use]
+pub fn challenge_response(token: &str, nonce: &[u8]) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(token.as_bytes())
+        .unwrap_or_else(|_| unreachable!("HMAC accepts keys of any length"));
+    mac.update(nonce);
+    mac.finalize().into_bytes().into()
+}
+
 /// Verify a token against a stored hash using constant-time comparison
 #[must_use]
 pub fn verify_token_hash(token: &str, expected_hash: &[u8; 32]) -> bool {
@@ -33,6 +364,13 @@ pub fn verify_token_hash(token: &str, expected_hash: &[u8; 32]) -> bool {
     constant_time_eq(&token_hash, expected_hash)
 }
 
+/// Longest token the server accepts when checking its static token list
+pub const MAX_TOKEN_LEN: usize = 256;
+
+/// Longest token accepted when a custom [`Authenticator`] checks tokens, so
+/// signed tokens such as JWTs fit
+pub const MAX_AUTHENTICATOR_TOKEN_LEN: usize = 8 * 1024;
+
 /// Validate token format
 ///
 /// Returns Ok(()) if token is valid, Err with reason if not
@@ -91,6 +429,25 @@ mod tests {
         assert!(!verify_token_hash("wrong-token", &hash));
     }
 
+    #[test]
+    fn test_challenge_response() {
+        let nonce = [1u8; 32];
+        let response = challenge_response("secret", &nonce);
+        assert_eq!(response, challenge_response("secret", &nonce));
+        assert_ne!(response, challenge_response("other", &nonce));
+        assert_ne!(response, challenge_response("secret", &[2u8; 32]));
+
+        let store = TokenStore::new(vec!["old".into(), "secret".into()]);
+        assert!(store.answers_challenge(&nonce, &response));
+        assert!(!store.answers_challenge(&[2u8; 32], &response));
+        assert!(!store.answers_challenge(&nonce, &response[..16]));
+        assert_eq!(
+            store.challenge_client_id(&nonce, &response),
+            Some(token_client_id("secret"))
+        );
+        assert_ne!(token_client_id("secret"), token_client_id("old"));
+    }
+
     #[test]
     fn test_validate_token_format() {
         assert!(validate_token_format("valid-token-123", 256).is_ok());
@@ -98,4 +455,73 @@ mod tests {
         assert!(validate_token_format("x".repeat(300).as_str(), 256).is_err());
         assert!(validate_token_format("has\nnewline", 256).is_err());
     }
+
+    #[test]
+    fn test_token_store_multiple_tokens() {
+        let store = TokenStore::new(vec!["old-token".into(), "new-token".into()]);
+
+        assert!(store.contains("old-token"));
+        assert!(store.contains("new-token"));
+        assert!(!store.contains("other-token"));
+        assert!(!store.contains(""));
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn test_token_store_reload_from_file() {
+        let path = std::env::temp_dir().join(format!("ferrotunnel-tokens-{}", std::process::id()));
+        std::fs::write(&path, "old-token\n").unwrap();
+
+        let store = TokenStore::default();
+        assert_eq!(store.reload_from_file(&path).unwrap(), 1);
+        assert!(store.contains("old-token"));
+
+        // Operator adds a new token during rotation
+        std::fs::write(&path, "# rotation in progress\nold-token\n\nnew-token\n").unwrap();
+        assert_eq!(store.reload_from_file(&path).unwrap(), 2);
+        assert!(store.contains("old-token"));
+        assert!(store.contains("new-token"));
+
+        // ... then removes the old one once clients have migrated
+        std::fs::write(&path, "new-token\n").unwrap();
+        assert_eq!(store.reload_from_file(&path).unwrap(), 1);
+        assert!(!store.contains("old-token"));
+        assert!(store.contains("new-token"));
+
+        // A failed reload keeps the previous set
+        std::fs::remove_file(&path).unwrap();
+        assert!(store.reload_from_file(&path).is_err());
+        assert!(store.contains("new-token"));
+    }
+
+    #[tokio::test]
+    async fn test_token_store_authenticator() {
+        let store = TokenStore::new(vec!["secret".to_string()]);
+        let peer: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        assert_eq!(
+            store.authenticate("secret", None, peer).await,
+            AuthResult::granted()
+        );
+        assert_eq!(
+            store.authenticate("wrong", Some("app"), peer).await,
+            AuthResult::Denied
+        );
+    }
+
+    #[test]
+    fn test_auth_grant_restrictions() {
+        let open = AuthGrant::default();
+        assert!(open.allows_tunnel_id("anything"));
+        assert_eq!(open.filter_capabilities(vec!["a".into()]), vec!["a"]);
+
+        let grant = AuthGrant::default()
+            .with_tunnel_ids(vec!["app".into()])
+            .with_capabilities(vec!["web".into()]);
+        assert!(grant.allows_tunnel_id("app"));
+        assert!(!grant.allows_tunnel_id("other"));
+        assert_eq!(
+            grant.filter_capabilities(vec!["ssh".into(), "web".into()]),
+            vec!["web"]
+        );
+    }
 }
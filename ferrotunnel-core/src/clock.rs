@@ -0,0 +1,163 @@
+//! Time source for rate limiting and session expiry
+//!
+//! Production code uses [`SystemClock`]. Tests can swap in a `MockClock`
+//! (with the `test-util` feature) and move time forward by hand instead of
+//! sleeping. Types that store a clock without being generic over it hold an
+//! [`AnyClock`].
+
+use std::time::Instant;
+
+/// Source of the current instant
+///
+/// Code that takes a clock is generic over it, so [`SystemClock`] compiles
+/// down to a plain `Instant::now()` call.
+pub trait Clock: Clone + Send + Sync + 'static {
+    fn now(&self) -> Instant;
+}
+
+/// The real monotonic clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock held by the session stores
+///
+/// A thin enum rather than a type parameter, so the stores keep their
+/// non-generic public types. Outside tests only [`AnyClock::System`] exists
+/// and `now()` is a plain `Instant::now()`.
+#[derive(Debug, Clone, Default)]
+pub enum AnyClock {
+    #[default]
+    System,
+    #[cfg(any(test, feature = "test-util"))]
+    Mock(MockClock),
+}
+
+impl Clock for AnyClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        match self {
+            AnyClock::System => Instant::now(),
+            #[cfg(any(test, feature = "test-util"))]
+            AnyClock::Mock(clock) => clock.now(),
+        }
+    }
+}
+
+impl From<SystemClock> for AnyClock {
+    fn from(_: SystemClock) -> Self {
+        AnyClock::System
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl From<MockClock> for AnyClock {
+    fn from(clock: MockClock) -> Self {
+        AnyClock::Mock(clock)
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+pub use mock::MockClock;
+
+#[cfg(any(test, feature = "test-util"))]
+mod mock {
+    use super::Clock;
+    use std::sync::{Arc, Mutex, PoisonError};
+    use std::time::{Duration, Instant};
+
+    /// Clock that only moves when [`MockClock::advance`] is called
+    ///
+    /// Starts at the instant it was created; clones share the same time.
+    #[derive(Debug, Clone)]
+    pub struct MockClock {
+        origin: Instant,
+        elapsed: Arc<Mutex<Duration>>,
+    }
+
+    impl MockClock {
+        pub fn new() -> Self {
+            Self {
+                origin: Instant::now(),
+                elapsed: Arc::new(Mutex::new(Duration::ZERO)),
+            }
+        }
+
+        /// Move the clock forward by `by`
+        ///
+        /// A `Duration` is never left half-written, so a lock poisoned by a
+        /// panicking test thread is recovered rather than dropping the advance.
+        pub fn advance(&self, by: Duration) {
+            *self.elapsed.lock().unwrap_or_else(PoisonError::into_inner) += by;
+        }
+
+        /// Time advanced since the clock was created
+        pub fn elapsed(&self) -> Duration {
+            *self.elapsed.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+
+    impl Default for MockClock {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            self.origin + self.elapsed()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_mock_clock_survives_poisoned_lock() {
+            let clock = MockClock::new();
+            let poisoner = clock.clone();
+            let _ = std::thread::spawn(move || {
+                let _guard = poisoner.elapsed.lock().unwrap();
+                panic!("poison the clock");
+            })
+            .join();
+
+            clock.advance(Duration::from_secs(5));
+            assert_eq!(clock.elapsed(), Duration::from_secs(5));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_mock_clock_advances_only_when_told() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+
+        let shared = clock.clone();
+        shared.advance(Duration::from_secs(90));
+        assert_eq!(clock.now() - start, Duration::from_secs(90));
+        assert_eq!(clock.elapsed(), Duration::from_secs(90));
+    }
+
+    #[test]
+    fn test_any_clock_follows_mock() {
+        let mock = MockClock::new();
+        let clock = AnyClock::from(mock.clone());
+        let start = clock.now();
+        mock.advance(Duration::from_secs(30));
+        assert_eq!(clock.now() - start, Duration::from_secs(30));
+    }
+}
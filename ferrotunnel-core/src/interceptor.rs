@@ -0,0 +1,53 @@
+//! Frame-level hooks for the control connection
+//!
+//! A [`FrameInterceptor`] sees every frame a [`TunnelServer`](crate::TunnelServer)
+//! or [`TunnelClient`](crate::TunnelClient) writes to or reads from its
+//! connection once the session is running, for debugging, custom metrics or
+//! experimenting with new frame types. Hooks only observe: they get a shared
+//! reference and run inline on the send and receive paths, so they should be
+//! cheap and never block.
+
+use ferrotunnel_protocol::Frame;
+use std::sync::Arc;
+
+/// Observes frames on the wire; both hooks do nothing by default
+pub trait FrameInterceptor: Send + Sync {
+    /// Called for each frame right before it is encoded onto the connection
+    fn on_send(&self, frame: &Frame) {
+        let _ = frame;
+    }
+
+    /// Called for each frame read from the connection, before it is handled
+    fn on_recv(&self, frame: &Frame) {
+        let _ = frame;
+    }
+}
+
+/// Interceptor shared between a connection's sender and reader tasks
+pub type SharedFrameInterceptor = Arc<dyn FrameInterceptor>;
+
+/// Shows each frame to the first interceptor, then the second
+struct Chain(SharedFrameInterceptor, SharedFrameInterceptor);
+
+impl FrameInterceptor for Chain {
+    fn on_send(&self, frame: &Frame) {
+        self.0.on_send(frame);
+        self.1.on_send(frame);
+    }
+
+    fn on_recv(&self, frame: &Frame) {
+        self.0.on_recv(frame);
+        self.1.on_recv(frame);
+    }
+}
+
+/// Single interceptor running `first` and then `second`, whichever are set
+pub(crate) fn chain(
+    first: Option<SharedFrameInterceptor>,
+    second: Option<SharedFrameInterceptor>,
+) -> Option<SharedFrameInterceptor> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(Arc::new(Chain(first, second))),
+        (first, second) => first.or(second),
+    }
+}
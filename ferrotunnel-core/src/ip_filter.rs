@@ -0,0 +1,244 @@
+//! Control-plane IP filtering
+//!
+//! [`IpFilter`] decides which peers may connect to the tunnel server at all.
+//! It is checked as soon as a TCP connection is accepted, before the TLS
+//! handshake or any protocol frame, so rejected networks cost the server
+//! nothing beyond the accept.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// A CIDR string that could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid CIDR `{0}`")]
+pub struct InvalidCidr(String);
+
+/// An IPv4 or IPv6 network, e.g. `10.0.0.0/8` or `2001:db8::/32`
+///
+/// A bare address parses as a single-host network (`/32` or `/128`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    /// Network of `prefix_len` bits starting at `addr`; host bits are cleared
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self, InvalidCidr> {
+        let bits = address_bits(addr);
+        if prefix_len > bits {
+            return Err(InvalidCidr(format!("{addr}/{prefix_len}")));
+        }
+        let masked = to_u128(addr) & prefix_mask(prefix_len, bits);
+        let network = match addr {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::from_bits(
+                u32::try_from(masked).unwrap_or_default(),
+            )),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::from_bits(masked)),
+        };
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+
+    /// Whether `ip` falls inside this network.
+    ///
+    /// IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`, as reported by
+    /// dual-stack listeners) match IPv4 networks.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        if ip.is_ipv4() != self.network.is_ipv4() {
+            return false;
+        }
+        let mask = prefix_mask(self.prefix_len, address_bits(ip));
+        to_u128(ip) & mask == to_u128(self.network)
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = InvalidCidr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidCidr(s.to_string());
+        let s = s.trim();
+        match s.split_once('/') {
+            Some((addr, prefix)) => {
+                let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+                let prefix_len: u8 = prefix.parse().map_err(|_| invalid())?;
+                Self::new(addr, prefix_len).map_err(|_| invalid())
+            }
+            None => {
+                let addr: IpAddr = s.parse().map_err(|_| invalid())?;
+                Self::new(addr, address_bits(addr))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+fn address_bits(addr: IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+fn to_u128(addr: IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(v4) => u128::from(v4.to_bits()),
+        IpAddr::V6(v6) => v6.to_bits(),
+    }
+}
+
+/// Mask selecting the top `prefix_len` of `bits` address bits
+fn prefix_mask(prefix_len: u8, bits: u8) -> u128 {
+    let width = if bits == 128 {
+        u128::MAX
+    } else {
+        (1u128 << bits) - 1
+    };
+    let host_bits = u32::from(bits - prefix_len);
+    width.checked_shl(host_bits).map_or(0, |mask| mask & width)
+}
+
+/// Allow and deny lists of networks for incoming connections
+///
+/// A peer is rejected if it matches any deny entry. When the allow list is
+/// not empty, a peer must also match one of its entries. The default filter
+/// admits everyone.
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+}
+
+impl IpFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Admit peers in `cidr`; once any network is allowed, peers outside
+    /// every allowed network are rejected
+    #[must_use]
+    pub fn allow(mut self, cidr: Cidr) -> Self {
+        self.allow.push(cidr);
+        self
+    }
+
+    /// Reject peers in `cidr`, even if they are also allowed
+    #[must_use]
+    pub fn deny(mut self, cidr: Cidr) -> Self {
+        self.deny.push(cidr);
+        self
+    }
+
+    /// Build a filter from CIDR strings, e.g. from a config file
+    pub fn from_lists<A, D>(allow: A, deny: D) -> Result<Self, InvalidCidr>
+    where
+        A: IntoIterator,
+        A::Item: AsRef<str>,
+        D: IntoIterator,
+        D::Item: AsRef<str>,
+    {
+        Ok(Self {
+            allow: allow
+                .into_iter()
+                .map(|s| s.as_ref().parse())
+                .collect::<Result<_, _>>()?,
+            deny: deny
+                .into_iter()
+                .map(|s| s.as_ref().parse())
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Whether a peer at `ip` may connect
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        if self.deny.iter().any(|cidr| cidr.contains(ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|cidr| cidr.contains(ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn cidr(s: &str) -> Cidr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!(cidr("10.1.2.3/8").to_string(), "10.0.0.0/8");
+        assert_eq!(cidr("192.168.1.5").to_string(), "192.168.1.5/32");
+        assert_eq!(cidr("2001:db8::1/32").to_string(), "2001:db8::/32");
+        assert_eq!(cidr("::1").to_string(), "::1/128");
+
+        for bad in [
+            "",
+            "10.0.0.0/33",
+            "::/129",
+            "10.0.0/8",
+            "10.0.0.0/x",
+            "host/8",
+        ] {
+            assert!(bad.parse::<Cidr>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_contains_ipv4() {
+        let net = cidr("192.168.0.0/16");
+        assert!(net.contains(ip("192.168.255.1")));
+        assert!(!net.contains(ip("192.169.0.1")));
+        assert!(!net.contains(ip("::1")));
+        // Dual-stack listeners report IPv4 peers as mapped IPv6 addresses
+        assert!(net.contains(ip("::ffff:192.168.3.4")));
+
+        assert!(cidr("0.0.0.0/0").contains(ip("8.8.8.8")));
+    }
+
+    #[test]
+    fn test_contains_ipv6() {
+        let net = cidr("2001:db8::/32");
+        assert!(net.contains(ip("2001:db8:ffff::1")));
+        assert!(!net.contains(ip("2001:db9::1")));
+        assert!(!net.contains(ip("32.1.13.184")));
+
+        assert!(cidr("::/0").contains(ip("fe80::1")));
+        assert!(!cidr("::/0").contains(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn test_filter_deny_wins_over_allow() {
+        let filter = IpFilter::from_lists(["10.0.0.0/8"], ["10.13.0.0/16"]).unwrap();
+        assert!(filter.is_allowed(ip("10.1.1.1")));
+        assert!(!filter.is_allowed(ip("10.13.1.1")));
+        assert!(!filter.is_allowed(ip("172.16.0.1")));
+    }
+
+    #[test]
+    fn test_filter_defaults() {
+        assert!(IpFilter::new().is_allowed(ip("203.0.113.9")));
+
+        let deny_only = IpFilter::new().deny(cidr("203.0.113.0/24"));
+        assert!(!deny_only.is_allowed(ip("203.0.113.9")));
+        assert!(deny_only.is_allowed(ip("198.51.100.1")));
+
+        let err = IpFilter::from_lists(["10.0.0.0/8", "nope"], Vec::<String>::new()).unwrap_err();
+        assert_eq!(err.to_string(), "invalid CIDR `nope`");
+    }
+}
@@ -1,11 +1,15 @@
 pub mod auth;
+pub mod clock;
+pub mod interceptor;
+pub mod ip_filter;
 pub mod rate_limit;
 pub mod reconnect;
+pub mod recorder;
 pub mod resource_limits;
 pub mod stream;
 pub mod transport;
 pub mod tunnel;
 
 // Re-export specific items for convenience
-pub use tunnel::client::TunnelClient;
-pub use tunnel::server::TunnelServer;
+pub use tunnel::client::{ControlRtt, GrantedCapabilities, PublicUrl, StreamOpener, TunnelClient};
+pub use tunnel::server::{announce_shutdown, TunnelServer};
@@ -1,13 +1,15 @@
 //! Rate limiting for tunnel sessions
 
+use crate::clock::{Clock, SystemClock};
 use governor::{
-    clock::DefaultClock,
+    clock::ReasonablyRealtime,
     middleware::NoOpMiddleware,
     state::{InMemoryState, NotKeyed},
     Quota, RateLimiter,
 };
 use std::num::NonZeroU32;
 use std::sync::Arc;
+use std::time::Instant;
 
 /// Session rate limiter configuration
 #[derive(Debug, Clone)]
@@ -31,26 +33,59 @@ impl Default for RateLimiterConfig {
     }
 }
 
+/// Lets governor read time from a [`Clock`]
+#[derive(Debug, Clone)]
+struct GovernorClock<C>(C);
+
+impl<C: Clock> governor::clock::Clock for GovernorClock<C> {
+    type Instant = Instant;
+
+    #[inline]
+    fn now(&self) -> Instant {
+        self.0.now()
+    }
+}
+
+impl<C: Clock> ReasonablyRealtime for GovernorClock<C> {}
+
+type DirectRateLimiter<C> =
+    RateLimiter<NotKeyed, InMemoryState, GovernorClock<C>, NoOpMiddleware<Instant>>;
+
 /// Rate limiter for a single session
 #[derive(Clone)]
-pub struct SessionRateLimiter {
+pub struct SessionRateLimiter<C: Clock = SystemClock> {
     /// Limits stream open rate
-    stream_limiter: Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock, NoOpMiddleware>>,
+    stream_limiter: Arc<DirectRateLimiter<C>>,
     /// Limits data throughput
-    bytes_limiter: Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock, NoOpMiddleware>>,
+    bytes_limiter: Arc<DirectRateLimiter<C>>,
 }
 
 impl SessionRateLimiter {
     /// Create a new session rate limiter
     #[must_use]
     pub fn new(config: &RateLimiterConfig) -> Self {
+        Self::with_clock(config, SystemClock)
+    }
+}
+
+impl<C: Clock> SessionRateLimiter<C> {
+    /// Create a session rate limiter that refills by `clock`, e.g. a
+    /// `MockClock` in tests
+    #[must_use]
+    pub fn with_clock(config: &RateLimiterConfig, clock: C) -> Self {
         let stream_quota =
             Quota::per_second(config.streams_per_sec).allow_burst(config.burst_factor);
         let bytes_quota = Quota::per_second(config.bytes_per_sec).allow_burst(config.burst_factor);
 
         Self {
-            stream_limiter: Arc::new(RateLimiter::direct(stream_quota)),
-            bytes_limiter: Arc::new(RateLimiter::direct(bytes_quota)),
+            stream_limiter: Arc::new(RateLimiter::direct_with_clock(
+                stream_quota,
+                GovernorClock(clock.clone()),
+            )),
+            bytes_limiter: Arc::new(RateLimiter::direct_with_clock(
+                bytes_quota,
+                GovernorClock(clock),
+            )),
         }
     }
 
@@ -81,7 +116,7 @@ impl SessionRateLimiter {
     }
 }
 
-impl std::fmt::Debug for SessionRateLimiter {
+impl<C: Clock> std::fmt::Debug for SessionRateLimiter<C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("SessionRateLimiter").finish_non_exhaustive()
     }
@@ -98,7 +133,7 @@ pub enum RateLimitError {
 
 impl From<RateLimitError> for ferrotunnel_common::TunnelError {
     fn from(err: RateLimitError) -> Self {
-        ferrotunnel_common::TunnelError::ServiceUnavailable(err.to_string())
+        ferrotunnel_common::TunnelError::RateLimited(err.to_string())
     }
 }
 
@@ -117,6 +152,8 @@ impl From<ferrotunnel_common::config::RateLimitConfig> for RateLimiterConfig {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
+    use std::time::Duration;
 
     #[test]
     fn test_rate_limiter_creation() {
@@ -141,6 +178,40 @@ mod tests {
             assert!(limiter.check_stream_open().is_ok());
         }
         // Should be rate limited after burst
+        let err = limiter.check_stream_open().unwrap_err();
+        assert!(matches!(
+            ferrotunnel_common::TunnelError::from(err),
+            ferrotunnel_common::TunnelError::RateLimited(_)
+        ));
+    }
+
+    #[test]
+    fn test_refill_follows_clock() {
+        let config = RateLimiterConfig {
+            streams_per_sec: NonZeroU32::new(2).unwrap(),
+            bytes_per_sec: NonZeroU32::new(1000).unwrap(),
+            burst_factor: NonZeroU32::new(2).unwrap(),
+        };
+        let clock = MockClock::new();
+        let limiter = SessionRateLimiter::with_clock(&config, clock.clone());
+
+        assert!(limiter.check_stream_open().is_ok());
+        assert!(limiter.check_stream_open().is_ok());
+        assert!(limiter.check_stream_open().is_err());
+
+        // Time stands still until the clock is advanced
+        assert!(limiter.check_stream_open().is_err());
+        clock.advance(Duration::from_millis(250));
+        assert!(limiter.check_stream_open().is_err());
+        // One slot refills every 500ms
+        clock.advance(Duration::from_millis(300));
+        assert!(limiter.check_stream_open().is_ok());
+        assert!(limiter.check_stream_open().is_err());
+
+        // A long pause refills up to the burst, no further
+        clock.advance(Duration::from_secs(10));
+        assert!(limiter.check_stream_open().is_ok());
+        assert!(limiter.check_stream_open().is_ok());
         assert!(limiter.check_stream_open().is_err());
     }
 }
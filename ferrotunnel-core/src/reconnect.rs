@@ -1,7 +1,8 @@
 //! Exponential backoff reconnection logic
 
+use crate::clock::{Clock, SystemClock};
 use rand::Rng;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Backoff configuration
 #[derive(Debug, Clone)]
@@ -104,21 +105,36 @@ pub enum ReconnectState {
 }
 
 /// Reconnection manager
+///
+/// Generic over its [`Clock`] so tests can step through backoff waits with a
+/// `MockClock`; the default [`SystemClock`] adds no overhead.
 #[derive(Debug)]
-pub struct ReconnectManager {
+pub struct ReconnectManager<C: Clock = SystemClock> {
     backoff: Backoff,
     state: ReconnectState,
     max_attempts: Option<u32>,
+    clock: C,
+    retry_at: Option<Instant>,
 }
 
 impl ReconnectManager {
     /// Create a new reconnection manager
     #[must_use]
     pub fn new(config: BackoffConfig, max_attempts: Option<u32>) -> Self {
+        Self::with_clock(config, max_attempts, SystemClock)
+    }
+}
+
+impl<C: Clock> ReconnectManager<C> {
+    /// Create a reconnection manager that reads the time from `clock`
+    #[must_use]
+    pub fn with_clock(config: BackoffConfig, max_attempts: Option<u32>, clock: C) -> Self {
         Self {
             backoff: Backoff::new(config),
             state: ReconnectState::Connecting,
             max_attempts,
+            clock,
+            retry_at: None,
         }
     }
 
@@ -126,6 +142,7 @@ impl ReconnectManager {
     pub fn on_connected(&mut self) {
         self.backoff.reset();
         self.state = ReconnectState::Connected;
+        self.retry_at = None;
     }
 
     /// Handle connection failure
@@ -140,9 +157,18 @@ impl ReconnectManager {
 
         self.state = ReconnectState::Backoff;
         let delay = self.backoff.next_delay();
+        self.retry_at = Some(self.clock.now() + delay);
         Some(delay)
     }
 
+    /// Time left before the next attempt is due; zero when not backing off
+    #[must_use]
+    pub fn time_until_retry(&self) -> Duration {
+        self.retry_at.map_or(Duration::ZERO, |at| {
+            at.saturating_duration_since(self.clock.now())
+        })
+    }
+
     /// Mark as reconnecting (after backoff wait)
     pub fn start_reconnect(&mut self) {
         self.state = ReconnectState::Reconnecting;
@@ -164,6 +190,7 @@ impl ReconnectManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
 
     #[test]
     fn test_backoff_growth() {
@@ -218,6 +245,34 @@ mod tests {
         assert_eq!(backoff.attempts(), 0);
     }
 
+    #[test]
+    fn test_time_until_retry_follows_clock() {
+        let config = BackoffConfig {
+            base: Duration::from_secs(4),
+            max: Duration::from_secs(60),
+            factor: 2.0,
+            jitter: 0.0,
+        };
+        let clock = MockClock::new();
+        let mut manager = ReconnectManager::with_clock(config, None, clock.clone());
+        assert_eq!(manager.time_until_retry(), Duration::ZERO);
+
+        let delay = manager.on_disconnected().unwrap();
+        assert_eq!(manager.time_until_retry(), delay);
+
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(
+            manager.time_until_retry(),
+            delay.saturating_sub(Duration::from_secs(3))
+        );
+
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(manager.time_until_retry(), Duration::ZERO);
+
+        manager.on_connected();
+        assert_eq!(manager.time_until_retry(), Duration::ZERO);
+    }
+
     #[test]
     fn test_reconnect_manager() {
         let config = BackoffConfig::default();
@@ -0,0 +1,483 @@
+//! Frame capture files for debugging
+//!
+//! [`FrameRecorder`] is a [`FrameInterceptor`] that appends every frame a
+//! session sends or receives to a file, and [`RecordingReader`] reads such a
+//! file back (`ferrotunnel capture <file>` prints one). Attach a recorder
+//! with [`TunnelServer::with_frame_recorder`](crate::TunnelServer::with_frame_recorder)
+//! or [`TunnelClient::with_frame_recorder`](crate::TunnelClient::with_frame_recorder).
+//!
+//! A capture starts with [`CAPTURE_MAGIC`], followed by one record per frame:
+//!
+//! ```text
+//! [timestamp: u64, microseconds since the Unix epoch][direction: u8][frame]
+//! ```
+//!
+//! The frame is encoded with [`TunnelCodec`], so it carries its own length
+//! prefix. Integers are big-endian.
+
+use crate::interceptor::FrameInterceptor;
+use bytes::{Buf, BufMut, BytesMut};
+use ferrotunnel_protocol::codec::TunnelCodec;
+use ferrotunnel_protocol::Frame;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_util::codec::{Decoder, Encoder};
+use tracing::warn;
+
+/// First bytes of every capture file
+pub const CAPTURE_MAGIC: &[u8; 8] = b"FTCAP\0\0\x01";
+
+/// Default size at which a capture file is rotated
+pub const DEFAULT_MAX_CAPTURE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Frames queued for the writer thread before new ones are dropped
+pub const RECORD_QUEUE_CAPACITY: usize = 4096;
+
+/// Records buffered in memory before they are written to the file
+const WRITE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Longest time a record stays buffered before the file is flushed, as
+/// long as frames keep coming
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Timestamp and direction
+const RECORD_HEADER_LEN: usize = 9;
+
+const DIRECTION_SENT: u8 = 0;
+const DIRECTION_RECEIVED: u8 = 1;
+
+/// Which way a recorded frame went, seen from the recording peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// A frame read back from a capture
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedFrame {
+    pub timestamp: SystemTime,
+    pub direction: Direction,
+    pub frame: Frame,
+}
+
+/// [`FrameInterceptor`] writing every frame to a capture file
+///
+/// When the next record would take the file past its size cap, the file is
+/// renamed to the same path with `.1` appended (replacing an earlier one)
+/// and a new capture is started, so at most about twice the cap stays on
+/// disk. A server records all of its sessions into the same file.
+///
+/// Frames are handed to a dedicated writer thread, so the session's frame
+/// paths never wait on the disk. If the writer falls more than
+/// [`RECORD_QUEUE_CAPACITY`] frames behind, further frames are left out of
+/// the capture and counted in [`dropped`](Self::dropped). Records reach the
+/// file at least once a second while frames flow, on [`flush`](Self::flush)
+/// and when the recorder is dropped.
+///
+/// A write error stops the recording with a warning; the session carries on.
+pub struct FrameRecorder {
+    path: PathBuf,
+    max_bytes: Arc<AtomicU64>,
+    records: SyncSender<Command>,
+    dropped: AtomicU64,
+}
+
+enum Command {
+    Record {
+        timestamp: u64,
+        direction: u8,
+        frame: Frame,
+    },
+    Flush(mpsc::Sender<io::Result<()>>),
+}
+
+/// State of the writer thread
+struct CaptureWriter {
+    path: PathBuf,
+    max_bytes: Arc<AtomicU64>,
+    /// `None` once recording stopped after an error
+    writer: Option<BufWriter<File>>,
+    /// Bytes in the current file
+    written: u64,
+    last_flush: Instant,
+    codec: TunnelCodec,
+    record: BytesMut,
+}
+
+impl FrameRecorder {
+    /// Start a capture at `path`, replacing any existing file
+    pub fn create(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let max_bytes = Arc::new(AtomicU64::new(DEFAULT_MAX_CAPTURE_BYTES));
+        let mut capture = CaptureWriter {
+            path: path.clone(),
+            max_bytes: max_bytes.clone(),
+            writer: Some(start_capture(&path)?),
+            written: CAPTURE_MAGIC.len() as u64,
+            last_flush: Instant::now(),
+            codec: TunnelCodec::new(),
+            record: BytesMut::new(),
+        };
+        let (records, queue) = mpsc::sync_channel(RECORD_QUEUE_CAPACITY);
+        thread::Builder::new()
+            .name("ferrotunnel-capture".into())
+            .spawn(move || capture.run(&queue))?;
+        Ok(Self {
+            path,
+            max_bytes,
+            records,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// Rotate the capture before it grows past `max_bytes`.
+    ///
+    /// Default: [`DEFAULT_MAX_CAPTURE_BYTES`]
+    #[must_use]
+    pub fn with_max_bytes(self, max_bytes: u64) -> Self {
+        self.max_bytes.store(max_bytes, Ordering::Relaxed);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Frames left out of the capture because the writer fell behind
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Write every record queued so far to the file.
+    ///
+    /// Blocks until the writer thread has done so.
+    pub fn flush(&self) -> io::Result<()> {
+        let (done, result) = mpsc::channel();
+        if self.records.send(Command::Flush(done)).is_err() {
+            return Ok(());
+        }
+        result.recv().unwrap_or(Ok(()))
+    }
+
+    fn record(&self, direction: u8, frame: &Frame) {
+        let record = Command::Record {
+            timestamp: unix_micros(),
+            direction,
+            frame: frame.clone(),
+        };
+        if let Err(TrySendError::Full(_)) = self.records.try_send(record) {
+            if self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                warn!(
+                    "Frame capture to {} is falling behind; leaving frames out",
+                    self.path.display()
+                );
+            }
+        }
+    }
+}
+
+impl CaptureWriter {
+    /// Write queued records until the recorder is dropped
+    fn run(&mut self, queue: &Receiver<Command>) {
+        for command in queue {
+            match command {
+                Command::Record {
+                    timestamp,
+                    direction,
+                    frame,
+                } => {
+                    if self.writer.is_none() {
+                        continue;
+                    }
+                    if let Err(e) = self.append(timestamp, direction, frame) {
+                        warn!("Stopping frame capture to {}: {}", self.path.display(), e);
+                        self.writer = None;
+                    }
+                }
+                Command::Flush(done) => {
+                    let _ = done.send(self.flush());
+                }
+            }
+        }
+        if let Err(e) = self.flush() {
+            warn!("Failed to flush frame capture: {}", e);
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.writer {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn append(&mut self, timestamp: u64, direction: u8, frame: Frame) -> io::Result<()> {
+        let Self {
+            path,
+            max_bytes,
+            writer,
+            written,
+            last_flush,
+            codec,
+            record,
+        } = self;
+        record.clear();
+        record.put_u64(timestamp);
+        record.put_u8(direction);
+        codec.encode(frame, record)?;
+
+        let len = record.len() as u64;
+        if *written + len > max_bytes.load(Ordering::Relaxed)
+            && *written > CAPTURE_MAGIC.len() as u64
+        {
+            if let Some(mut full) = writer.take() {
+                full.flush()?;
+            }
+            fs::rename(&*path, rotated_path(path))?;
+            *writer = Some(start_capture(path)?);
+            *written = CAPTURE_MAGIC.len() as u64;
+        }
+        if let Some(writer) = writer {
+            writer.write_all(record)?;
+            *written += len;
+            if last_flush.elapsed() >= FLUSH_INTERVAL {
+                writer.flush()?;
+                *last_flush = Instant::now();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FrameInterceptor for FrameRecorder {
+    fn on_send(&self, frame: &Frame) {
+        self.record(DIRECTION_SENT, frame);
+    }
+
+    fn on_recv(&self, frame: &Frame) {
+        self.record(DIRECTION_RECEIVED, frame);
+    }
+}
+
+fn start_capture(path: &Path) -> io::Result<BufWriter<File>> {
+    let mut writer = BufWriter::with_capacity(WRITE_BUFFER_SIZE, File::create(path)?);
+    writer.write_all(CAPTURE_MAGIC)?;
+    Ok(writer)
+}
+
+/// `path` with `.1` appended
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut rotated = OsString::from(path.as_os_str());
+    rotated.push(".1");
+    PathBuf::from(rotated)
+}
+
+fn unix_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| {
+            u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX)
+        })
+}
+
+/// Reads the frames of a capture in the order they were recorded
+///
+/// Yields an error, then stops, if the capture is corrupt or ends in the
+/// middle of a record (e.g. the recording process was killed).
+pub struct RecordingReader<R> {
+    reader: R,
+    codec: TunnelCodec,
+    buf: BytesMut,
+    done: bool,
+}
+
+impl RecordingReader<BufReader<File>> {
+    /// Open the capture file at `path`
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> RecordingReader<R> {
+    /// Read a capture from `reader`, checking its header
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0u8; CAPTURE_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != CAPTURE_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a ferrotunnel frame capture",
+            ));
+        }
+        Ok(Self {
+            reader,
+            codec: TunnelCodec::new(),
+            buf: BytesMut::new(),
+            done: false,
+        })
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<RecordedFrame>> {
+        let mut header = [0u8; RECORD_HEADER_LEN + 4];
+        // A capture may only end between records
+        if self.reader.read(&mut header[..1])? == 0 {
+            return Ok(None);
+        }
+        self.reader.read_exact(&mut header[1..])?;
+
+        let mut fields = &header[..];
+        let timestamp = UNIX_EPOCH + Duration::from_micros(fields.get_u64());
+        let direction = match fields.get_u8() {
+            DIRECTION_SENT => Direction::Sent,
+            DIRECTION_RECEIVED => Direction::Received,
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid frame direction {other}"),
+                ))
+            }
+        };
+        let frame_len = fields.get_u32() as usize;
+        if frame_len > self.codec.max_frame_size() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("recorded frame too large: {frame_len} bytes"),
+            ));
+        }
+
+        self.buf.clear();
+        self.buf.extend_from_slice(&header[RECORD_HEADER_LEN..]);
+        self.buf.resize(4 + frame_len, 0);
+        self.reader.read_exact(&mut self.buf[4..])?;
+        let frame = self.codec.decode(&mut self.buf)?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "incomplete recorded frame")
+        })?;
+        Ok(Some(RecordedFrame {
+            timestamp,
+            direction,
+            frame,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for RecordingReader<R> {
+    type Item = io::Result<RecordedFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let record = self.read_record().transpose();
+        if !matches!(record, Some(Ok(_))) {
+            self.done = true;
+        }
+        record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn temp_capture(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "ferrotunnel-capture-{name}-{}",
+            uuid::Uuid::new_v4()
+        ))
+    }
+
+    fn frames(path: &Path) -> Vec<(Direction, Frame)> {
+        RecordingReader::open(path)
+            .unwrap()
+            .map(|record| {
+                let record = record.unwrap();
+                (record.direction, record.frame)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = temp_capture("round-trip");
+        let recorder = FrameRecorder::create(&path).unwrap();
+        let data = Frame::Data {
+            stream_id: 3,
+            data: Bytes::from_static(b"hello"),
+            end_of_stream: true,
+        };
+        let before = SystemTime::now() - Duration::from_secs(1);
+        recorder.on_send(&Frame::Heartbeat { timestamp: 42 });
+        recorder.on_recv(&data);
+        recorder.flush().unwrap();
+
+        let records: Vec<RecordedFrame> = RecordingReader::open(&path)
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records[0].timestamp >= before);
+        assert_eq!(records[0].direction, Direction::Sent);
+        assert_eq!(records[0].frame, Frame::Heartbeat { timestamp: 42 });
+        assert_eq!(records[1].direction, Direction::Received);
+        assert_eq!(records[1].frame, data);
+        assert_eq!(recorder.dropped(), 0);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_rotation_caps_file_size() {
+        let path = temp_capture("rotation");
+        let recorder = FrameRecorder::create(&path).unwrap().with_max_bytes(200);
+        for timestamp in 0..20 {
+            recorder.on_send(&Frame::Heartbeat { timestamp });
+        }
+        recorder.flush().unwrap();
+
+        assert!(fs::metadata(&path).unwrap().len() <= 200);
+        let rotated = rotated_path(&path);
+        assert!(fs::metadata(&rotated).unwrap().len() <= 200);
+
+        // The current file continues where the rotated one stopped
+        let last = |path: &Path| match frames(path).last() {
+            Some((_, Frame::Heartbeat { timestamp })) => *timestamp,
+            other => panic!("unexpected record {other:?}"),
+        };
+        assert_eq!(last(&path), 19);
+        let first_current = match frames(&path).first() {
+            Some((_, Frame::Heartbeat { timestamp })) => *timestamp,
+            other => panic!("unexpected record {other:?}"),
+        };
+        assert_eq!(last(&rotated) + 1, first_current);
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(&rotated);
+    }
+
+    #[test]
+    fn test_reader_rejects_bad_input() {
+        assert!(RecordingReader::new(&b"not a capture"[..]).is_err());
+
+        // A record cut short yields an error after the complete ones
+        let path = temp_capture("truncated");
+        let recorder = FrameRecorder::create(&path).unwrap();
+        recorder.on_send(&Frame::Heartbeat { timestamp: 1 });
+        recorder.on_send(&Frame::Heartbeat { timestamp: 2 });
+        recorder.flush().unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 3);
+
+        let mut reader = RecordingReader::new(&bytes[..]).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+        let _ = fs::remove_file(&path);
+    }
+}
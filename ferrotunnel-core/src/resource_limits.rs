@@ -16,7 +16,7 @@ pub enum ResourceLimitError {
 
 impl From<ResourceLimitError> for ferrotunnel_common::TunnelError {
     fn from(err: ResourceLimitError) -> Self {
-        ferrotunnel_common::TunnelError::ServiceUnavailable(err.to_string())
+        ferrotunnel_common::TunnelError::ResourceExhausted(err.to_string())
     }
 }
 
@@ -49,6 +49,18 @@ impl ServerResourceLimits {
         }
     }
 
+    /// Set the maximum number of concurrently open streams per session
+    ///
+    /// Enforced by each session's multiplexer: streams the client opens past
+    /// the limit are closed with `QuotaExceeded`, and the server cannot open
+    /// new streams to it until one closes. The server refuses to start with
+    /// a limit of 0.
+    #[must_use]
+    pub fn with_max_streams_per_session(mut self, max_streams: usize) -> Self {
+        self.max_streams_per_session = max_streams;
+        self
+    }
+
     /// Try to acquire a session slot
     /// Returns a permit that must be held for the session's lifetime
     pub fn try_acquire_session(&self) -> Result<SessionPermit, ResourceLimitError> {
@@ -164,8 +176,11 @@ mod tests {
         assert_eq!(limits.available_sessions(), 0);
 
         // Should fail - no more slots
-        let permit3 = limits.try_acquire_session();
-        assert!(permit3.is_err());
+        let err = limits.try_acquire_session().unwrap_err();
+        assert!(matches!(
+            ferrotunnel_common::TunnelError::from(err),
+            ferrotunnel_common::TunnelError::ResourceExhausted(msg) if msg.contains("sessions")
+        ));
 
         // Drop permit1, slot should be available again
         drop(permit1);
@@ -182,4 +197,11 @@ mod tests {
         // Should fail
         assert!(limits.try_acquire_stream().is_err());
     }
+
+    #[test]
+    fn test_with_max_streams_per_session() {
+        let limits = ServerResourceLimits::new(2, 10, 100).with_max_streams_per_session(3);
+        assert_eq!(limits.max_streams_per_session, 3);
+        assert!(limits.try_acquire_session().is_ok());
+    }
 }
@@ -0,0 +1,160 @@
+//! Per-stream credit-based flow control
+//!
+//! Each stream starts with a send window equal to the peer's receive window.
+//! Sending a `Frame::Data` consumes credit; the receiver returns credit with
+//! `Frame::WindowUpdate` as it drains the stream. A slow consumer therefore
+//! stalls only its own writer instead of filling its channel and blocking the
+//! shared connection read loop for every other stream.
+
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicI64, Ordering};
+use tokio::sync::Notify;
+
+/// Default per-stream receive window (1MB)
+pub const DEFAULT_STREAM_WINDOW: u32 = 1024 * 1024;
+
+/// Handshake capability prefix advertising flow control and its window size
+pub const FLOW_CONTROL_CAPABILITY: &str = "flow_control";
+
+/// Capability string advertising a receive window of `window` bytes
+pub fn capability(window: NonZeroU32) -> String {
+    format!("{FLOW_CONTROL_CAPABILITY}:{window}")
+}
+
+/// Whether `cap` is a flow control capability, valid or not
+pub(crate) fn is_capability(cap: &str) -> bool {
+    cap.strip_prefix(FLOW_CONTROL_CAPABILITY)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(':'))
+}
+
+/// Find the window advertised by a peer's capabilities, if any.
+///
+/// A zero window is treated like a malformed one: with no credit no stream
+/// could ever send data.
+pub fn parse_capability(capabilities: &[String]) -> Option<NonZeroU32> {
+    capabilities.iter().find_map(|cap| {
+        cap.strip_prefix(FLOW_CONTROL_CAPABILITY)?
+            .strip_prefix(':')?
+            .parse()
+            .ok()
+    })
+}
+
+/// Credit charged for a data frame.
+///
+/// Every frame costs at least `window / channel_capacity`, rounded up, so that
+/// a full window of tiny frames cannot exceed the per-stream channel capacity.
+#[inline]
+pub(crate) fn frame_cost(len: usize, window: u32, channel_capacity: usize) -> u32 {
+    let min_cost = (window as usize).div_ceil(channel_capacity).max(1);
+    u32::try_from(len.max(min_cost)).unwrap_or(u32::MAX)
+}
+
+/// Sender-side credit for a single stream
+#[derive(Debug)]
+pub struct SendWindow {
+    credit: AtomicI64,
+    notify: Notify,
+}
+
+impl SendWindow {
+    /// Create a window with `initial` bytes of credit
+    pub fn new(initial: u32) -> Self {
+        Self {
+            credit: AtomicI64::new(i64::from(initial)),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Currently available credit (may be negative after an oversized frame)
+    pub fn available(&self) -> i64 {
+        self.credit.load(Ordering::Acquire)
+    }
+
+    /// Wait until credit is available, then consume `cost`
+    pub async fn acquire(&self, cost: u32) {
+        loop {
+            // Register interest before checking so a concurrent grant is not missed
+            let notified = self.notify.notified();
+            if self.available() > 0 {
+                self.credit.fetch_sub(i64::from(cost), Ordering::AcqRel);
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Return `delta` bytes of credit and wake a waiting writer
+    pub fn grant(&self, delta: u32) {
+        self.credit.fetch_add(i64::from(delta), Ordering::AcqRel);
+        self.notify.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_capability_round_trip() {
+        let window = NonZeroU32::new(65_536).unwrap();
+        let caps = vec!["basic".to_string(), capability(window)];
+        assert_eq!(parse_capability(&caps), Some(window));
+        assert_eq!(parse_capability(&["basic".to_string()]), None);
+        assert_eq!(parse_capability(&["flow_control:abc".to_string()]), None);
+    }
+
+    #[test]
+    fn test_zero_window_capability_ignored() {
+        let caps = ["flow_control:0".to_string()];
+        assert_eq!(parse_capability(&caps), None);
+        assert!(is_capability(&caps[0]));
+        assert!(!is_capability("flow_controlled"));
+    }
+
+    #[test]
+    fn test_frame_cost_has_floor() {
+        assert_eq!(frame_cost(1, 1024, 128), 8);
+        assert_eq!(frame_cost(100, 1024, 128), 100);
+        assert_eq!(frame_cost(0, 0, 128), 1);
+    }
+
+    #[tokio::test]
+    async fn test_window_of_tiny_frames_fits_channel() {
+        use crate::stream::multiplexer::STREAM_CHANNEL_CAPACITY;
+
+        // 100000 is not a multiple of the capacity, so rounding down would
+        // let one frame more than the channel holds through
+        let window = SendWindow::new(100_000);
+        let cost = frame_cost(1, 100_000, STREAM_CHANNEL_CAPACITY);
+        let mut frames = 0;
+        while window.available() > 0 {
+            window.acquire(cost).await;
+            frames += 1;
+        }
+        assert!(frames <= STREAM_CHANNEL_CAPACITY, "{frames} frames fit");
+    }
+
+    #[tokio::test]
+    async fn test_send_window_blocks_until_grant() {
+        let window = Arc::new(SendWindow::new(10));
+        window.acquire(10).await;
+        assert_eq!(window.available(), 0);
+
+        let waiter = {
+            let window = window.clone();
+            tokio::spawn(async move { window.acquire(5).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        window.grant(5);
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(window.available(), 0);
+    }
+}
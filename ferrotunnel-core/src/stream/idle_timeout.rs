@@ -0,0 +1,82 @@
+//! Per-stream inactivity timeout
+//!
+//! A stream times out when it has a read or write waiting and neither
+//! direction has made progress for the configured window. Reads and writes
+//! share one progress clock, so a long upload keeps a pending read alive and
+//! vice versa, but each direction keeps its own timer so a stream split
+//! across two tasks wakes whichever side is waiting.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::Context;
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+
+/// Inactivity state carried by a `VirtualStream` when a timeout is configured
+#[derive(Debug)]
+pub(crate) struct IdleTimeout {
+    timeout: Duration,
+    last_progress: Instant,
+    read_timer: Option<Pin<Box<Sleep>>>,
+    write_timer: Option<Pin<Box<Sleep>>>,
+    expired: bool,
+}
+
+/// Which side of the stream is waiting
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Direction {
+    Read,
+    Write,
+}
+
+impl IdleTimeout {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            last_progress: Instant::now(),
+            read_timer: None,
+            write_timer: None,
+            expired: false,
+        }
+    }
+
+    /// Data moved in either direction
+    pub(crate) fn progress(&mut self) {
+        self.last_progress = Instant::now();
+    }
+
+    /// Whether the stream has already timed out
+    pub(crate) fn is_expired(&self) -> bool {
+        self.expired
+    }
+
+    /// Called when `direction` is about to return `Pending`: arms its timer
+    /// and reports whether the stream has now been idle for the whole window
+    pub(crate) fn poll_expired(&mut self, direction: Direction, cx: &mut Context<'_>) -> bool {
+        if self.expired {
+            return true;
+        }
+        let deadline = self.last_progress + self.timeout;
+        let timer = match direction {
+            Direction::Read => &mut self.read_timer,
+            Direction::Write => &mut self.write_timer,
+        };
+        let timer = timer.get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+        if timer.deadline() != deadline {
+            timer.as_mut().reset(deadline);
+        }
+        if timer.as_mut().poll(cx).is_ready() {
+            self.expired = true;
+        }
+        self.expired
+    }
+
+    /// Error returned by reads and writes once the stream has timed out
+    pub(crate) fn error(&self) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("stream made no progress for {:?}", self.timeout),
+        )
+    }
+}
@@ -1,6 +1,12 @@
 pub mod bytes_pool;
+pub mod flow_control;
+mod idle_timeout;
 pub mod multiplexer;
 pub mod pool;
+pub mod traffic;
 
-pub use multiplexer::{Multiplexer, PrioritizedFrame, VirtualStream};
+pub use multiplexer::{
+    Multiplexer, PrioritizedFrame, StreamAbortHandle, VirtualStream, DEFAULT_PING_TIMEOUT,
+};
 pub use pool::{ByteBufferPool, ObjectPool, Poolable, PooledObject};
+pub use traffic::TrafficCounters;
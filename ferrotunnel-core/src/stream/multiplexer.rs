@@ -6,19 +6,29 @@
 //! - Larger per-stream channel capacity (128) for better throughput
 //! - Reduced backpressure with larger buffers
 
+use super::flow_control::{frame_cost, SendWindow};
+use super::idle_timeout::{Direction, IdleTimeout};
 use super::pool::ObjectPool;
+use super::traffic::TrafficCounters;
+use crate::resource_limits::ResourceLimitError;
 use bytes::Bytes;
 use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
-use ferrotunnel_common::Result;
-use ferrotunnel_protocol::frame::{Frame, OpenStreamFrame, Protocol, StreamPriority};
+use ferrotunnel_common::{Result, TunnelError};
+use ferrotunnel_protocol::frame::{
+    CloseReason, Frame, OpenStreamFrame, Protocol, ResetCode, StreamPriority,
+};
 use kanal::{bounded_async, AsyncReceiver, AsyncSender, ReceiveError, SendError};
 use std::io;
+use std::num::{NonZeroU32, NonZeroUsize};
 use std::pin::Pin;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
 use tracing::warn;
 
 /// Pool for reusing read buffers in `VirtualStream`
@@ -26,13 +36,16 @@ pub type ReadBufferPool = ObjectPool<Vec<u8>>;
 
 /// P1.2: Per-stream channel capacity (was 10, now 128 for better throughput)
 /// This reduces backpressure and HOL blocking in multiplex scenarios.
-const STREAM_CHANNEL_CAPACITY: usize = 128;
+pub(super) const STREAM_CHANNEL_CAPACITY: usize = 128;
 
 /// New stream queue capacity
 const NEW_STREAM_QUEUE_CAPACITY: usize = 32;
 
 type CachedSender = (u32, AsyncSender<Result<Frame>>);
 
+/// How long [`Multiplexer::ping`] waits for the matching pong
+pub const DEFAULT_PING_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Channel item for the batched sender: priority (send order) and frame.
 pub type PrioritizedFrame = (StreamPriority, Frame);
 
@@ -45,37 +58,163 @@ pub type PrioritizedFrame = (StreamPriority, Frame);
 /// - `ObjectPool` for read buffer reuse
 #[derive(Clone, Debug)]
 pub struct Multiplexer {
-    streams: Arc<DashMap<u32, AsyncSender<Result<Frame>>>>,
-    /// Stream priority for send scheduling (cleaned on CloseStream).
+    streams: Arc<StreamTable>,
+    /// Send windows for streams when flow control is enabled (cleaned on close).
+    send_windows: Arc<DashMap<u32, Arc<SendWindow>>>,
+    /// Per-stream window size; `None` disables flow control.
+    stream_window: Option<u32>,
+    /// Stream priority for send scheduling (cleaned on close).
     stream_priorities: Arc<DashMap<u32, StreamPriority>>,
+    /// Lifetime trackers for open streams (released on CloseStream or drop).
+    stream_lifetimes: Arc<StreamLifetimes>,
+    /// Number of streams currently open on this multiplexer.
+    active_streams: Arc<AtomicUsize>,
+    /// Cap on `active_streams`; `None` means unlimited.
+    max_streams: Option<usize>,
+    /// Data payload bytes received and sent by all streams.
+    traffic: TrafficCounters,
+    /// Inactivity timeout given to every stream; `None` disables it.
+    stream_idle_timeout: Option<Duration>,
+    /// Largest payload a stream puts in one `Data` frame.
+    max_data_payload: usize,
+    /// Whether the peer negotiated `Ping`/`Pong` frames.
+    ping: bool,
+    /// Pings awaiting their pong, keyed by nonce.
+    pending_pings: Arc<DashMap<u64, oneshot::Sender<()>>>,
     last_sender: Arc<Mutex<Option<CachedSender>>>,
     next_stream_id: Arc<AtomicU32>,
     frame_tx: AsyncSender<PrioritizedFrame>,
     new_stream_tx: AsyncSender<VirtualStream>,
     buffer_pool: ReadBufferPool,
+    /// Cancelled by [`Multiplexer::close`] to make the connection task hang up.
+    closed: CancellationToken,
 }
 
 impl Multiplexer {
     pub fn new(
         frame_tx: AsyncSender<PrioritizedFrame>,
         is_client: bool,
+    ) -> (Self, AsyncReceiver<VirtualStream>) {
+        Self::build(frame_tx, is_client, None)
+    }
+
+    /// Create a multiplexer with per-stream flow control.
+    ///
+    /// Each stream may have at most `window_size` bytes in flight before the
+    /// writer waits for a `WindowUpdate` from the peer. Both peers must enable
+    /// flow control with the same window size.
+    pub fn with_flow_control(
+        frame_tx: AsyncSender<PrioritizedFrame>,
+        is_client: bool,
+        window_size: NonZeroU32,
+    ) -> (Self, AsyncReceiver<VirtualStream>) {
+        Self::build(frame_tx, is_client, Some(window_size.get()))
+    }
+
+    fn build(
+        frame_tx: AsyncSender<PrioritizedFrame>,
+        is_client: bool,
+        stream_window: Option<u32>,
     ) -> (Self, AsyncReceiver<VirtualStream>) {
         let (new_stream_tx, new_stream_rx) = bounded_async(NEW_STREAM_QUEUE_CAPACITY);
         let initial_stream_id = if is_client { 1 } else { 2 };
         (
             Self {
-                streams: Arc::new(DashMap::new()),
+                streams: Arc::new(StreamTable::default()),
+                send_windows: Arc::new(DashMap::new()),
+                stream_window,
                 stream_priorities: Arc::new(DashMap::new()),
+                stream_lifetimes: Arc::new(DashMap::new()),
+                active_streams: Arc::new(AtomicUsize::new(0)),
+                max_streams: None,
+                traffic: TrafficCounters::new(),
+                stream_idle_timeout: None,
+                max_data_payload: MAX_DATA_FRAME_PAYLOAD,
+                ping: false,
+                pending_pings: Arc::new(DashMap::new()),
                 last_sender: Arc::new(Mutex::new(None)),
                 next_stream_id: Arc::new(AtomicU32::new(initial_stream_id)),
                 frame_tx,
                 new_stream_tx,
                 buffer_pool: ReadBufferPool::with_default_capacity(),
+                closed: CancellationToken::new(),
             },
             new_stream_rx,
         )
     }
 
+    /// Limit how many streams may be open at once, counting both directions.
+    ///
+    /// Once the limit is reached, peer `OpenStream` frames are answered with
+    /// `CloseStream { reason: QuotaExceeded }` and local opens fail with
+    /// [`ResourceLimitError::MaxStreamsReached`].
+    #[must_use]
+    pub fn with_max_streams(mut self, max_streams: NonZeroUsize) -> Self {
+        self.max_streams = Some(max_streams.get());
+        self
+    }
+
+    /// Count traffic into `traffic` instead of fresh counters, e.g. to keep
+    /// totals across reconnects.
+    #[must_use]
+    pub fn with_traffic(mut self, traffic: TrafficCounters) -> Self {
+        self.traffic = traffic;
+        self
+    }
+
+    /// Fail a stream's reads and writes with `TimedOut` once it has had a
+    /// read or write waiting for `timeout` without data moving either way.
+    ///
+    /// The peer is sent `CloseStream { reason: Timeout }`. Bounds how long a
+    /// wedged local service or remote peer can pin a stream, whatever the
+    /// protocol on top.
+    #[must_use]
+    pub fn with_stream_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.stream_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Keep every `Data` frame within `max_frame_size` encoded bytes, e.g.
+    /// the size negotiated in the handshake.
+    ///
+    /// Payloads stay at most 64KB either way; a smaller limit only makes
+    /// writes split into smaller chunks.
+    #[must_use]
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_data_payload = max_frame_size
+            .saturating_sub(DATA_FRAME_HEADER_SIZE)
+            .clamp(1, MAX_DATA_FRAME_PAYLOAD);
+        self
+    }
+
+    /// Allow [`ping`](Self::ping), once the peer has advertised
+    /// [`PING_CAPABILITY`](ferrotunnel_protocol::constants::PING_CAPABILITY).
+    /// Peers without it may not understand `Ping` frames.
+    #[must_use]
+    pub fn with_ping(mut self) -> Self {
+        self.ping = true;
+        self
+    }
+
+    /// Largest payload a stream puts in one `Data` frame
+    pub fn max_data_payload(&self) -> usize {
+        self.max_data_payload
+    }
+
+    /// Reuse read buffers from `pool`, e.g. one built with
+    /// [`ReadBufferPool::with_capacity_and_limit`] to bound the memory kept
+    /// after a burst of streams. Set before any stream is opened.
+    #[must_use]
+    pub fn with_buffer_pool(mut self, pool: ReadBufferPool) -> Self {
+        self.buffer_pool = pool;
+        self
+    }
+
+    /// Data payload bytes received from and sent to the peer
+    pub fn traffic(&self) -> &TrafficCounters {
+        &self.traffic
+    }
+
     /// Priority for a frame when sending (used by batched sender order).
     fn priority_for_frame(
         frame: &Frame,
@@ -85,10 +224,19 @@ impl Multiplexer {
             Frame::Data { stream_id, .. } => priorities
                 .get(stream_id)
                 .map_or(StreamPriority::Normal, |r| *r),
-            Frame::Heartbeat { .. } | Frame::HandshakeAck { .. } => StreamPriority::Critical,
-            Frame::CloseStream { stream_id, .. } => priorities
-                .get(stream_id)
-                .map_or(StreamPriority::Normal, |r| *r),
+            Frame::Heartbeat { .. }
+            | Frame::HeartbeatAck { .. }
+            | Frame::HandshakeAck { .. }
+            | Frame::WindowUpdate { .. }
+            | Frame::Ping { .. }
+            | Frame::Pong { .. } => StreamPriority::Critical,
+            // Never ahead of the stream's `OpenStream`, or the peer would
+            // drop it for an unknown stream
+            Frame::CloseStream { stream_id, .. } | Frame::ResetStream { stream_id, .. } => {
+                priorities
+                    .get(stream_id)
+                    .map_or(StreamPriority::Normal, |r| *r)
+            }
             _ => StreamPriority::Normal,
         }
     }
@@ -98,6 +246,49 @@ impl Multiplexer {
         &self.buffer_pool
     }
 
+    /// Ask the task running this multiplexer's connection to close it, e.g.
+    /// when a reconnect replaced its session.
+    pub fn close(&self) {
+        self.closed.cancel();
+    }
+
+    /// Whether [`Self::close`] was called
+    pub fn is_closed(&self) -> bool {
+        self.closed.is_cancelled()
+    }
+
+    /// Resolves once [`Self::close`] is called
+    pub async fn closed(&self) {
+        self.closed.cancelled().await;
+    }
+
+    /// Number of streams currently open on this multiplexer
+    ///
+    /// A stream stops counting once either side sends `CloseStream` or
+    /// `ResetStream`, both
+    /// sides have shut down their write halves, or its `VirtualStream` is
+    /// dropped, whichever happens first.
+    pub fn active_streams(&self) -> usize {
+        self.active_streams.load(Ordering::Acquire)
+    }
+
+    /// Claim a slot in the active-stream count, or `None` when at the limit.
+    fn reserve_stream(&self) -> Option<Arc<StreamLifetime>> {
+        match self.max_streams {
+            Some(max) => {
+                self.active_streams
+                    .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                        (n < max).then_some(n + 1)
+                    })
+                    .ok()?;
+            }
+            None => {
+                self.active_streams.fetch_add(1, Ordering::AcqRel);
+            }
+        }
+        Some(Arc::new(StreamLifetime::new(self.active_streams.clone())))
+    }
+
     /// Allocate a new stream ID atomically (lock-free)
     #[inline]
     fn allocate_stream_id(&self) -> u32 {
@@ -122,12 +313,30 @@ impl Multiplexer {
             Frame::OpenStream(open_stream) => {
                 let stream_id = open_stream.stream_id;
                 let priority = open_stream.priority;
+                if self.streams.contains_key(&stream_id) {
+                    warn!("Stream {} already exists", stream_id);
+                    return Ok(());
+                }
+                let Some(lifetime) = self.reserve_stream() else {
+                    warn!(
+                        "Rejecting stream {}: stream limit ({}) reached",
+                        stream_id,
+                        self.max_streams.unwrap_or_default()
+                    );
+                    return self
+                        .send_frame(Frame::CloseStream {
+                            stream_id,
+                            reason: CloseReason::QuotaExceeded,
+                        })
+                        .await;
+                };
                 // P1.2: Use larger channel capacity
                 let (tx, rx) = bounded_async(STREAM_CHANNEL_CAPACITY);
 
                 match self.streams.entry(stream_id) {
                     Entry::Occupied(_) => {
                         warn!("Stream {} already exists", stream_id);
+                        lifetime.release();
                         return Ok(());
                     }
                     Entry::Vacant(entry) => {
@@ -145,7 +354,9 @@ impl Multiplexer {
                     read_buffer,
                     self.buffer_pool.clone(),
                     open_stream.protocol,
-                );
+                )
+                .with_headers(open_stream.headers.clone());
+                let stream = self.track_stream(self.attach_flow_control(stream), lifetime);
 
                 // OpenStream is a control path - use async send for reliability
                 if self.new_stream_tx.send(stream).await.is_err() {
@@ -153,8 +364,11 @@ impl Multiplexer {
                     self.streams.remove(&stream_id);
                 }
             }
-            Frame::Data { stream_id, .. } => {
+            Frame::Data {
+                stream_id, data, ..
+            } => {
                 let stream_id = *stream_id;
+                self.traffic.record_in(data.len());
                 let tx = self
                     .cached_sender(stream_id)
                     .or_else(|| self.lookup_and_cache_sender(stream_id));
@@ -164,27 +378,123 @@ impl Multiplexer {
                     // P1.2: Larger channel (128) reduces chance of blocking
                     if tx.send(Ok(frame)).await.is_err() {
                         self.streams.remove(&stream_id);
+                        self.stream_lifetimes.remove(&stream_id);
                     }
                 }
             }
-            Frame::CloseStream { stream_id, .. } => {
+            Frame::CloseStream { stream_id, .. } | Frame::ResetStream { stream_id, .. } => {
                 let stream_id = *stream_id;
                 let tx = self
                     .cached_sender(stream_id)
                     .or_else(|| self.lookup_and_cache_sender(stream_id));
 
                 if let Some(tx) = tx {
-                    // Best effort delivery of close frame
+                    // Best effort delivery of close or reset frame
                     let _ = tx.send(Ok(frame)).await;
                 }
                 self.streams.remove(&stream_id);
                 self.stream_priorities.remove(&stream_id);
+                self.send_windows.remove(&stream_id);
+                if let Some((_, lifetime)) = self.stream_lifetimes.remove(&stream_id) {
+                    lifetime.release();
+                }
+            }
+            Frame::WindowUpdate { stream_id, delta } => {
+                if let Some(window) = self.send_windows.get(stream_id) {
+                    window.grant(*delta);
+                }
+            }
+            Frame::Ping { nonce } => {
+                self.send_frame(Frame::Pong { nonce: *nonce }).await?;
+            }
+            Frame::Pong { nonce } => {
+                if let Some((_, waiter)) = self.pending_pings.remove(nonce) {
+                    let _ = waiter.send(());
+                }
             }
             _ => {}
         }
         Ok(())
     }
 
+    /// Send a `Ping` carrying `nonce` and wait for the matching `Pong`,
+    /// returning the round-trip time.
+    ///
+    /// Fails with [`TunnelError::Timeout`] if no pong arrives within
+    /// [`DEFAULT_PING_TIMEOUT`], and with [`TunnelError::Protocol`] without
+    /// sending anything unless the peer negotiated pings (see
+    /// [`with_ping`](Self::with_ping)). Nonces should be unique among pings
+    /// in flight: reusing one cancels the earlier ping.
+    pub async fn ping(&self, nonce: u64) -> Result<Duration> {
+        self.ping_with_timeout(nonce, DEFAULT_PING_TIMEOUT).await
+    }
+
+    /// [`ping`](Self::ping) with an explicit timeout
+    pub async fn ping_with_timeout(&self, nonce: u64, timeout: Duration) -> Result<Duration> {
+        if !self.ping {
+            return Err(TunnelError::Protocol("peer did not negotiate ping".into()));
+        }
+        let (waiter, pong) = oneshot::channel();
+        self.pending_pings.insert(nonce, waiter);
+        let started = Instant::now();
+
+        let result = match self.send_frame(Frame::Ping { nonce }).await {
+            Ok(()) => match tokio::time::timeout(timeout, pong).await {
+                Ok(Ok(())) => Ok(started.elapsed()),
+                Ok(Err(_)) => Err(TunnelError::Connection(format!(
+                    "ping {nonce} was cancelled"
+                ))),
+                Err(_) => Err(TunnelError::Timeout(format!(
+                    "no pong for ping {nonce} within {timeout:?}"
+                ))),
+            },
+            Err(e) => {
+                drop(pong);
+                Err(e)
+            }
+        };
+        // Forget this ping unless a newer one has taken over its nonce
+        self.pending_pings
+            .remove_if(&nonce, |_, waiter| waiter.is_closed());
+        result
+    }
+
+    /// Give a new stream its send window and receive-side accounting.
+    fn attach_flow_control(&self, mut stream: VirtualStream) -> VirtualStream {
+        if let Some(window_size) = self.stream_window {
+            let window = Arc::new(SendWindow::new(window_size));
+            self.send_windows.insert(stream.stream_id, window.clone());
+            stream.flow = Some(StreamFlow {
+                window_size,
+                send_window: window,
+                unacked: 0,
+                pending_update: None,
+            });
+        }
+        stream
+    }
+
+    /// Keep a new stream's reserved slot until it is closed or dropped.
+    fn track_stream(
+        &self,
+        mut stream: VirtualStream,
+        lifetime: Arc<StreamLifetime>,
+    ) -> VirtualStream {
+        self.stream_lifetimes
+            .insert(stream.stream_id, lifetime.clone());
+        stream.lifetime = Some(lifetime);
+        stream.lifetimes = Some(self.stream_lifetimes.clone());
+        stream.entries = Some(StreamEntries {
+            streams: Arc::downgrade(&self.streams),
+            priorities: Arc::downgrade(&self.stream_priorities),
+            send_windows: Arc::downgrade(&self.send_windows),
+        });
+        stream.traffic = Some(self.traffic.clone());
+        stream.idle = self.stream_idle_timeout.map(IdleTimeout::new);
+        stream.max_data_payload = self.max_data_payload;
+        stream
+    }
+
     fn cached_sender(&self, stream_id: u32) -> Option<AsyncSender<Result<Frame>>> {
         let guard = self.last_sender.try_lock().ok()?;
         let (cached_id, tx) = guard.as_ref()?;
@@ -214,28 +524,72 @@ impl Multiplexer {
         protocol: Protocol,
         priority: StreamPriority,
     ) -> Result<VirtualStream> {
+        self.open_stream_inner(protocol, priority, Vec::new()).await
+    }
+
+    /// Open a new outbound stream carrying metadata headers in its `OpenStream`
+    /// frame (e.g. the original client address), readable by the peer through
+    /// [`VirtualStream::header`].
+    pub async fn open_stream_with_headers(
+        &self,
+        protocol: Protocol,
+        headers: Vec<(String, String)>,
+    ) -> Result<VirtualStream> {
+        self.open_stream_inner(protocol, StreamPriority::default(), headers)
+            .await
+    }
+
+    /// Open a new outbound stream with the given priority, carrying metadata
+    /// headers in its `OpenStream` frame.
+    pub async fn open_stream_with_priority_and_headers(
+        &self,
+        protocol: Protocol,
+        priority: StreamPriority,
+        headers: Vec<(String, String)>,
+    ) -> Result<VirtualStream> {
+        self.open_stream_inner(protocol, priority, headers).await
+    }
+
+    async fn open_stream_inner(
+        &self,
+        protocol: Protocol,
+        priority: StreamPriority,
+        headers: Vec<(String, String)>,
+    ) -> Result<VirtualStream> {
+        let Some(lifetime) = self.reserve_stream() else {
+            return Err(ResourceLimitError::MaxStreamsReached {
+                max: self.max_streams.unwrap_or_default(),
+            }
+            .into());
+        };
         let stream_id = self.allocate_stream_id();
 
         let (tx, rx) = bounded_async(STREAM_CHANNEL_CAPACITY);
         self.streams.insert(stream_id, tx);
 
         self.stream_priorities.insert(stream_id, priority);
-        self.frame_tx
+        let sent = self
+            .frame_tx
             .send((
                 priority,
                 Frame::OpenStream(Box::new(OpenStreamFrame {
                     stream_id,
                     protocol,
-                    headers: vec![],
+                    headers: headers.clone(),
                     body_hint: None,
                     priority,
                 })),
             ))
-            .await
-            .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e.to_string()))?;
+            .await;
+        if let Err(e) = sent {
+            self.streams.remove(&stream_id);
+            self.stream_priorities.remove(&stream_id);
+            lifetime.release();
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, e.to_string()).into());
+        }
 
         let read_buffer = self.buffer_pool.try_acquire().unwrap_or_default();
-        Ok(VirtualStream::new(
+        let stream = VirtualStream::new(
             stream_id,
             rx,
             self.frame_tx.clone(),
@@ -243,7 +597,9 @@ impl Multiplexer {
             read_buffer,
             self.buffer_pool.clone(),
             protocol,
-        ))
+        )
+        .with_headers(headers);
+        Ok(self.track_stream(self.attach_flow_control(stream), lifetime))
     }
 }
 
@@ -252,6 +608,10 @@ impl Multiplexer {
 /// Chunking large writes ensures reliable decoding on the wire.
 const MAX_DATA_FRAME_PAYLOAD: usize = 64 * 1024; // 64KB
 
+/// Bytes of a `Data` frame counted against the frame size besides the
+/// payload: type, stream ID and flags
+const DATA_FRAME_HEADER_SIZE: usize = 1 + 4 + 1;
+
 /// Boxed future type for receiving frames
 type RecvFuture = Pin<
     Box<dyn std::future::Future<Output = std::result::Result<Result<Frame>, ReceiveError>> + Send>,
@@ -261,6 +621,112 @@ type RecvFuture = Pin<
 type SendFuture =
     Pin<Box<dyn std::future::Future<Output = std::result::Result<(), SendError>> + Send>>;
 
+/// Flow control state carried by a stream when enabled on its multiplexer
+struct StreamFlow {
+    window_size: u32,
+    send_window: Arc<SendWindow>,
+    /// Credit consumed locally but not yet returned to the peer
+    unacked: u32,
+    /// In-flight `WindowUpdate` send
+    pending_update: Option<SendFuture>,
+}
+
+/// Inbound channels of the open streams, keyed by stream ID
+///
+/// Shared by every clone of a [`Multiplexer`]. When the last clone goes away
+/// each stream still open is sent `CloseStream { reason: Shutdown }`, so its
+/// reader ends with EOF and a close reason instead of a bare channel close.
+#[derive(Debug, Default)]
+struct StreamTable(DashMap<u32, AsyncSender<Result<Frame>>>);
+
+impl std::ops::Deref for StreamTable {
+    type Target = DashMap<u32, AsyncSender<Result<Frame>>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for StreamTable {
+    fn drop(&mut self) {
+        for entry in &self.0 {
+            // Best effort: a full channel must not block the drop, and its
+            // reader still sees EOF once the sender is gone
+            let _ = entry.value().try_send(Ok(Frame::CloseStream {
+                stream_id: *entry.key(),
+                reason: CloseReason::Shutdown,
+            }));
+        }
+    }
+}
+
+/// A stream's entries in its multiplexer's tables
+///
+/// Only the side receiving `CloseStream` cleans up in `process_frame`, so the
+/// stream removes its own entries once released. Held weakly so open streams
+/// do not keep the [`StreamTable`] alive past the last `Multiplexer` clone.
+#[derive(Debug)]
+struct StreamEntries {
+    streams: Weak<StreamTable>,
+    priorities: Weak<DashMap<u32, StreamPriority>>,
+    send_windows: Weak<DashMap<u32, Arc<SendWindow>>>,
+}
+
+impl StreamEntries {
+    fn remove(&self, stream_id: u32) {
+        if let Some(streams) = self.streams.upgrade() {
+            streams.remove(&stream_id);
+        }
+        if let Some(priorities) = self.priorities.upgrade() {
+            priorities.remove(&stream_id);
+        }
+        if let Some(send_windows) = self.send_windows.upgrade() {
+            send_windows.remove(&stream_id);
+        }
+    }
+}
+
+/// Lifetimes of a multiplexer's open streams, keyed by stream ID
+type StreamLifetimes = DashMap<u32, Arc<StreamLifetime>>;
+
+/// Tracks how long a stream has been open and keeps the active counts correct
+///
+/// Created once the stream's slot has been counted in `active`. Released
+/// exactly once, either when the peer closes the stream or when the
+/// `VirtualStream` is dropped without a clean `CloseStream`.
+#[derive(Debug)]
+struct StreamLifetime {
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    opened_at: Instant,
+    released: AtomicBool,
+    active: Arc<AtomicUsize>,
+}
+
+impl StreamLifetime {
+    fn new(active: Arc<AtomicUsize>) -> Self {
+        #[cfg(feature = "metrics")]
+        if let Some(m) = ferrotunnel_observability::tunnel_metrics() {
+            m.stream_opened();
+        }
+        Self {
+            opened_at: Instant::now(),
+            released: AtomicBool::new(false),
+            active,
+        }
+    }
+
+    fn release(&self) {
+        if self.released.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        self.active.fetch_sub(1, Ordering::AcqRel);
+        #[cfg(feature = "metrics")]
+        if let Some(m) = ferrotunnel_observability::tunnel_metrics() {
+            m.stream_closed(self.opened_at.elapsed());
+        }
+    }
+}
+
 /// A virtual stream that implements `AsyncRead` + `AsyncWrite`
 ///
 /// Uses kanal channels for async communication.
@@ -291,6 +757,32 @@ pub struct VirtualStream {
     pending_send_len: usize,
     /// Protocol for this stream
     protocol: Protocol,
+    /// Credit-based flow control, if enabled
+    flow: Option<StreamFlow>,
+    /// Active-stream accounting, set when created by a `Multiplexer`
+    lifetime: Option<Arc<StreamLifetime>>,
+    /// The multiplexer's lifetime registry, left when the lifetime is released
+    lifetimes: Option<Arc<StreamLifetimes>>,
+    /// The multiplexer's table entries, removed when the lifetime is released
+    entries: Option<StreamEntries>,
+    /// Multiplexer traffic counters, set when created by a `Multiplexer`
+    traffic: Option<TrafficCounters>,
+    /// Inactivity timeout, if enabled on the multiplexer
+    idle: Option<IdleTimeout>,
+    /// Largest payload per `Data` frame
+    max_data_payload: usize,
+    /// Metadata headers from the `OpenStream` frame
+    headers: Vec<(String, String)>,
+    /// Reason from the peer's `CloseStream`, once received
+    close_reason: Option<CloseReason>,
+    /// Code from the peer's `ResetStream`, once received
+    reset_code: Option<ResetCode>,
+    /// `shutdown()` was called: no more data goes to the peer
+    write_closed: bool,
+    /// The peer sent end-of-stream: no more data will be read
+    read_closed: bool,
+    /// Set by a [`StreamAbortHandle`] once it has closed the stream
+    aborted: Option<Arc<AtomicBool>>,
 }
 
 impl std::fmt::Debug for VirtualStream {
@@ -326,6 +818,19 @@ impl VirtualStream {
             pending_send: None,
             pending_send_len: 0,
             protocol,
+            flow: None,
+            lifetime: None,
+            lifetimes: None,
+            entries: None,
+            traffic: None,
+            idle: None,
+            max_data_payload: MAX_DATA_FRAME_PAYLOAD,
+            headers: Vec::new(),
+            close_reason: None,
+            reset_code: None,
+            write_closed: false,
+            read_closed: false,
+            aborted: None,
         }
     }
 
@@ -336,10 +841,205 @@ impl VirtualStream {
     pub fn protocol(&self) -> Protocol {
         self.protocol
     }
+
+    /// Send-scheduling priority set when the stream was opened
+    pub fn priority(&self) -> StreamPriority {
+        self.priority
+    }
+
+    /// Attach `OpenStream` metadata headers
+    #[must_use]
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Metadata headers sent with the `OpenStream` frame
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Count a `Data` frame payload handed to the connection
+    fn record_sent(&self, bytes: usize) {
+        if let Some(traffic) = &self.traffic {
+            traffic.record_out(bytes);
+        }
+    }
+
+    /// Look up a metadata header by name (ASCII case-insensitive)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Why the peer closed the stream, once its `CloseStream` has been read
+    pub fn close_reason(&self) -> Option<&CloseReason> {
+        self.close_reason.as_ref()
+    }
+
+    /// Why the peer reset the stream, once its `ResetStream` has been read
+    pub fn reset_code(&self) -> Option<ResetCode> {
+        self.reset_code
+    }
+
+    /// Close the stream, telling the peer why.
+    ///
+    /// Unlike `shutdown()`, which only ends our write half, this ends both
+    /// directions. The peer's reads end with EOF for [`CloseReason::Normal`]
+    /// and [`CloseReason::Shutdown`], and with an error for any other reason.
+    /// Fails with
+    /// [`io::ErrorKind::BrokenPipe`] if the connection is gone before an
+    /// in-flight write or the close is queued.
+    pub async fn close_with_reason(&mut self, reason: CloseReason) -> io::Result<()> {
+        // Let an in-flight write go out before the close
+        if let Some(fut) = self.pending_send.take() {
+            let len = std::mem::take(&mut self.pending_send_len);
+            fut.await
+                .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e.to_string()))?;
+            self.record_sent(len);
+        }
+        let frame = Frame::CloseStream {
+            stream_id: self.stream_id,
+            reason,
+        };
+        self.tx
+            .send((self.priority, frame))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e.to_string()))
+    }
+
+    /// Abort the stream with `ResetStream`.
+    ///
+    /// Whatever was already sent is to be discarded: the peer's reads fail
+    /// with [`io::ErrorKind::ConnectionReset`] instead of ending with EOF.
+    /// The reset is queued at the stream's own priority, so it cannot
+    /// overtake the stream's `OpenStream`. Writes fail afterwards.
+    pub async fn reset(&mut self, error_code: ResetCode) -> io::Result<()> {
+        let aborted = self
+            .aborted
+            .get_or_insert_with(|| Arc::new(AtomicBool::new(false)));
+        if aborted.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        // A half-sent chunk is dropped rather than finished
+        self.pending_send = None;
+        self.pending_send_len = 0;
+        let frame = Frame::ResetStream {
+            stream_id: self.stream_id,
+            error_code,
+        };
+        self.tx
+            .send((self.priority, frame))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e.to_string()))
+    }
+
+    /// Handle that closes this stream with a reason after the stream itself
+    /// has been handed to something that only shuts it down cleanly, such as
+    /// a hyper connection. See [`StreamAbortHandle::abort`].
+    pub fn abort_handle(&mut self) -> StreamAbortHandle {
+        let aborted = self
+            .aborted
+            .get_or_insert_with(|| Arc::new(AtomicBool::new(false)))
+            .clone();
+        StreamAbortHandle {
+            stream_id: self.stream_id,
+            priority: self.priority,
+            tx: self.tx.clone(),
+            aborted,
+        }
+    }
+
+    /// Writes are refused after `shutdown()`, an abort or a reset
+    fn is_write_closed(&self) -> bool {
+        self.write_closed
+            || self.reset_code.is_some()
+            || self
+                .aborted
+                .as_ref()
+                .is_some_and(|aborted| aborted.load(Ordering::Acquire))
+    }
+
+    /// Send `data` as a run of `Data` frames.
+    ///
+    /// Faster than `write_all` for large buffers: the payload is split with
+    /// [`Bytes::slice`] instead of being copied, and the frames are queued in
+    /// a loop without boxing a future per chunk. Flow control and traffic
+    /// counters apply as for `poll_write`.
+    pub async fn send_bytes(&mut self, data: Bytes) -> io::Result<()> {
+        if self.is_write_closed() {
+            return Err(write_closed_error());
+        }
+        // Keep ordering with an in-flight `poll_write` chunk
+        if let Some(fut) = self.pending_send.take() {
+            let len = std::mem::take(&mut self.pending_send_len);
+            fut.await
+                .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e.to_string()))?;
+            self.record_sent(len);
+        }
+
+        let mut offset = 0;
+        while offset < data.len() {
+            let end = data.len().min(offset + self.max_data_payload);
+            let len = end - offset;
+            if let Some(flow) = &self.flow {
+                let cost = frame_cost(len, flow.window_size, STREAM_CHANNEL_CAPACITY);
+                flow.send_window.acquire(cost).await;
+            }
+            let frame = Frame::Data {
+                stream_id: self.stream_id,
+                data: data.slice(offset..end),
+                end_of_stream: false,
+            };
+            self.tx
+                .send((self.priority, frame))
+                .await
+                .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e.to_string()))?;
+            self.record_sent(len);
+            self.record_progress();
+            offset = end;
+        }
+        Ok(())
+    }
+
+    /// Account for a received data frame and return credit to the peer once
+    /// half the window has been consumed.
+    fn consume_credit(&mut self, len: usize, cx: &mut Context<'_>) {
+        let stream_id = self.stream_id;
+        let tx = self.tx.clone();
+        let Some(flow) = self.flow.as_mut() else {
+            return;
+        };
+        flow.unacked =
+            flow.unacked
+                .saturating_add(frame_cost(len, flow.window_size, STREAM_CHANNEL_CAPACITY));
+        if flow.pending_update.is_none() && flow.unacked >= flow.window_size / 2 {
+            let frame = Frame::WindowUpdate {
+                stream_id,
+                delta: std::mem::take(&mut flow.unacked),
+            };
+            flow.pending_update = Some(Box::pin(async move {
+                tx.send((StreamPriority::Critical, frame)).await
+            }));
+        }
+        Self::poll_window_update(flow, cx);
+    }
+
+    /// Drive an in-flight `WindowUpdate` send, if any.
+    fn poll_window_update(flow: &mut StreamFlow, cx: &mut Context<'_>) {
+        if let Some(fut) = flow.pending_update.as_mut() {
+            if fut.as_mut().poll(cx).is_ready() {
+                flow.pending_update = None;
+            }
+        }
+    }
 }
 
 impl Drop for VirtualStream {
     fn drop(&mut self) {
+        self.release_lifetime();
         // Return the read buffer to the pool if available
         if let Some(pool) = self.buffer_pool.take() {
             let buffer = std::mem::take(&mut self.read_buffer);
@@ -348,12 +1048,120 @@ impl Drop for VirtualStream {
     }
 }
 
-impl AsyncRead for VirtualStream {
-    fn poll_read(
+/// Closes a [`VirtualStream`] from outside the task that owns it
+///
+/// Once aborted, the stream sends no further data and no end-of-stream, so
+/// the peer's reads end with the abort's error rather than a clean EOF.
+#[derive(Clone)]
+pub struct StreamAbortHandle {
+    stream_id: u32,
+    priority: StreamPriority,
+    tx: AsyncSender<PrioritizedFrame>,
+    aborted: Arc<AtomicBool>,
+}
+
+impl std::fmt::Debug for StreamAbortHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamAbortHandle")
+            .field("stream_id", &self.stream_id)
+            .field("aborted", &self.is_aborted())
+            .finish_non_exhaustive()
+    }
+}
+
+impl StreamAbortHandle {
+    /// Send `CloseStream` with `reason` to the peer. Only the first call has
+    /// any effect.
+    ///
+    /// Safe to call from a poll function: the frame is queued without waiting
+    /// unless the connection's send queue is full.
+    pub fn abort(&self, reason: CloseReason) {
+        let frame = Frame::CloseStream {
+            stream_id: self.stream_id,
+            reason,
+        };
+        self.send_once(self.priority, frame);
+    }
+
+    /// Send `ResetStream` with `error_code` to the peer, whose reads then
+    /// fail instead of ending with EOF. Only the first call to this or
+    /// [`abort`](Self::abort) has any effect.
+    ///
+    /// Safe to call from a poll function, as for `abort`.
+    pub fn reset(&self, error_code: ResetCode) {
+        let frame = Frame::ResetStream {
+            stream_id: self.stream_id,
+            error_code,
+        };
+        self.send_once(self.priority, frame);
+    }
+
+    /// Queue `frame` unless the stream was already aborted
+    fn send_once(&self, priority: StreamPriority, frame: Frame) {
+        if self.aborted.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let tx = self.tx.clone();
+        match tx.try_send((priority, frame.clone())) {
+            Ok(true) | Err(_) => {}
+            Ok(false) => {
+                tokio::spawn(async move {
+                    let _ = tx.send((priority, frame)).await;
+                });
+            }
+        }
+    }
+
+    /// Whether [`abort`](Self::abort) or [`reset`](Self::reset) has been called
+    pub fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::Acquire)
+    }
+}
+
+/// Error surfaced to a reader for an abnormal close; `None` for a clean EOF
+fn close_error(reason: &CloseReason) -> Option<io::Error> {
+    let kind = match reason {
+        CloseReason::Normal | CloseReason::Shutdown => return None,
+        CloseReason::Timeout => io::ErrorKind::TimedOut,
+        CloseReason::Error(_) => io::ErrorKind::ConnectionReset,
+        CloseReason::LocalServiceUnreachable => io::ErrorKind::ConnectionRefused,
+        CloseReason::ProtocolViolation => io::ErrorKind::InvalidData,
+        CloseReason::QuotaExceeded => io::ErrorKind::ConnectionAborted,
+    };
+    Some(io::Error::new(
+        kind,
+        format!("stream closed by peer: {reason:?}"),
+    ))
+}
+
+/// Error surfaced to a reader once the peer reset the stream
+fn reset_error(code: ResetCode) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::ConnectionReset,
+        format!("stream reset by peer: {code:?}"),
+    )
+}
+
+/// Error for writes after `shutdown()`
+fn write_closed_error() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "stream write half is closed")
+}
+
+impl VirtualStream {
+    fn poll_read_frames(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
+        if let Some(flow) = self.flow.as_mut() {
+            VirtualStream::poll_window_update(flow, cx);
+        }
+
+        // Every read after a reset fails: the data is incomplete
+        if let Some(code) = self.reset_code {
+            return Poll::Ready(Err(reset_error(code)));
+        }
+
         if let Some(buffered_bytes) = self.read_buffer_bytes.as_mut() {
             if !buffered_bytes.is_empty() {
                 let len = std::cmp::min(buf.remaining(), buffered_bytes.len());
@@ -383,6 +1191,11 @@ impl AsyncRead for VirtualStream {
             return Poll::Ready(Ok(()));
         }
 
+        // Nothing follows the peer's CloseStream or end-of-stream
+        if self.close_reason.is_some() || self.read_closed {
+            return Poll::Ready(Ok(()));
+        }
+
         // Check if we have a pending receive future
         if self.pending_recv.is_none() {
             let rx = self.rx.clone();
@@ -400,9 +1213,13 @@ impl AsyncRead for VirtualStream {
                 match result {
                     Ok(Ok(Frame::Data {
                         data: bytes,
-                        end_of_stream: _,
+                        end_of_stream,
                         ..
                     })) => {
+                        self.consume_credit(bytes.len(), cx);
+                        if end_of_stream {
+                            self.close_read_half();
+                        }
                         let len = std::cmp::min(buf.remaining(), bytes.len());
                         buf.put_slice(&bytes[..len]);
                         if len < bytes.len() {
@@ -410,10 +1227,17 @@ impl AsyncRead for VirtualStream {
                         }
                         Poll::Ready(Ok(()))
                     }
-                    Ok(Ok(Frame::CloseStream { .. })) | Err(ReceiveError::Closed) => {
-                        Poll::Ready(Ok(())) // EOF
+                    Ok(Ok(Frame::CloseStream { reason, .. })) => {
+                        let result = close_error(&reason).map_or(Ok(()), Err);
+                        self.close_reason = Some(reason);
+                        Poll::Ready(result)
                     }
-                    Ok(Ok(_)) => Poll::Pending, // Ignore other frame types
+                    Ok(Ok(Frame::ResetStream { error_code, .. })) => {
+                        self.reset_code = Some(error_code);
+                        Poll::Ready(Err(reset_error(error_code)))
+                    }
+                    Err(ReceiveError::Closed) => Poll::Ready(Ok(())), // EOF
+                    Ok(Ok(_)) => Poll::Pending,                       // Ignore other frame types
                     Ok(Err(e)) => Poll::Ready(Err(io::Error::other(e.to_string()))),
                     Err(ReceiveError::SendClosed) => Poll::Ready(Ok(())), // EOF
                 }
@@ -421,14 +1245,16 @@ impl AsyncRead for VirtualStream {
             Poll::Pending => Poll::Pending,
         }
     }
-}
 
-impl AsyncWrite for VirtualStream {
-    fn poll_write(
+    fn poll_write_frames(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
+        if self.is_write_closed() {
+            return Poll::Ready(Err(write_closed_error()));
+        }
+
         // If we have a pending send, poll it first
         if let Some(fut) = self.pending_send.as_mut() {
             match fut.as_mut().poll(cx) {
@@ -443,6 +1269,7 @@ impl AsyncWrite for VirtualStream {
                             e.to_string(),
                         )));
                     }
+                    self.record_sent(bytes_written);
                     return Poll::Ready(Ok(bytes_written));
                 }
                 Poll::Pending => return Poll::Pending,
@@ -450,7 +1277,7 @@ impl AsyncWrite for VirtualStream {
         }
 
         // Chunk large writes to stay within protocol limits
-        let chunk_size = buf.len().min(MAX_DATA_FRAME_PAYLOAD);
+        let chunk_size = buf.len().min(self.max_data_payload);
 
         // Zero-copy: use Bytes::copy_from_slice for optimal performance
         // This is still a copy, but avoids BytesMut allocation overhead
@@ -467,7 +1294,17 @@ impl AsyncWrite for VirtualStream {
         let tx = self.tx.clone();
         // P3.1: Store length instead of frame, move frame into future
         self.pending_send_len = chunk_size;
-        self.pending_send = Some(Box::pin(async move { tx.send((priority, frame)).await }));
+        self.pending_send = Some(match self.flow.as_ref() {
+            Some(flow) => {
+                let window = flow.send_window.clone();
+                let cost = frame_cost(chunk_size, flow.window_size, STREAM_CHANNEL_CAPACITY);
+                Box::pin(async move {
+                    window.acquire(cost).await;
+                    tx.send((priority, frame)).await
+                })
+            }
+            None => Box::pin(async move { tx.send((priority, frame)).await }),
+        });
 
         // Poll the new future (we set it in the block above)
         let fut = match self.pending_send.as_mut() {
@@ -479,7 +1316,10 @@ impl AsyncWrite for VirtualStream {
                 self.pending_send = None;
                 self.pending_send_len = 0;
                 match result {
-                    Ok(()) => Poll::Ready(Ok(chunk_size)),
+                    Ok(()) => {
+                        self.record_sent(chunk_size);
+                        Poll::Ready(Ok(chunk_size))
+                    }
                     Err(e) => Poll::Ready(Err(io::Error::new(
                         io::ErrorKind::BrokenPipe,
                         e.to_string(),
@@ -490,49 +1330,178 @@ impl AsyncWrite for VirtualStream {
         }
     }
 
-    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        // Kanal channels don't require explicit flushing
-        Poll::Ready(Ok(()))
+    /// Turn a `Pending` read or write into a timeout error once the stream
+    /// has been idle for its whole window; the peer is told with
+    /// `CloseStream { reason: Timeout }`
+    fn poll_idle(&mut self, direction: Direction, cx: &mut Context<'_>) -> Option<io::Error> {
+        let idle = self.idle.as_mut()?;
+        let newly_expired = !idle.is_expired();
+        if !idle.poll_expired(direction, cx) {
+            return None;
+        }
+        let error = idle.error();
+        if newly_expired {
+            warn!("Stream {} idle, closing: {}", self.stream_id, error);
+            let frame = Frame::CloseStream {
+                stream_id: self.stream_id,
+                reason: CloseReason::Timeout,
+            };
+            let priority = self.priority;
+            let tx = self.tx.clone();
+            // Queue the close without blocking the caller
+            tokio::spawn(async move {
+                let _ = tx.send((priority, frame)).await;
+            });
+        }
+        Some(error)
     }
 
-    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        // If we have a pending send, poll it first
-        if let Some(fut) = self.pending_send.as_mut() {
-            match fut.as_mut().poll(cx) {
-                Poll::Ready(_) => {
-                    self.pending_send = None;
-                    self.pending_send_len = 0;
-                }
-                Poll::Pending => return Poll::Pending,
+    fn record_progress(&mut self) {
+        if let Some(idle) = self.idle.as_mut() {
+            idle.progress();
+        }
+    }
+
+    /// The peer finished writing; the stream is done once our side has too
+    fn close_read_half(&mut self) {
+        self.read_closed = true;
+        if self.write_closed {
+            self.release_lifetime();
+        }
+    }
+
+    fn release_lifetime(&mut self) {
+        if let Some(lifetime) = self.lifetime.take() {
+            lifetime.release();
+            // Streams that end without a `CloseStream` from the peer are only
+            // removed here
+            if let Some(lifetimes) = self.lifetimes.take() {
+                lifetimes.remove_if(&self.stream_id, |_, tracked| {
+                    Arc::ptr_eq(tracked, &lifetime)
+                });
+            }
+            if let Some(entries) = self.entries.take() {
+                entries.remove(self.stream_id);
             }
         }
+    }
 
-        // Send close frame
-        let frame = Frame::CloseStream {
+    /// Frame that closes our write half.
+    ///
+    /// While the peer is still writing this is an empty `Data` frame with
+    /// `end_of_stream` set, so the reverse direction keeps flowing. Once the
+    /// peer has finished too, the stream is over and `CloseStream` releases
+    /// it on both sides.
+    fn end_of_stream_send(&mut self) -> SendFuture {
+        let priority = self.priority;
+        let tx = self.tx.clone();
+        if self.read_closed {
+            self.release_lifetime();
+            let frame = Frame::CloseStream {
+                stream_id: self.stream_id,
+                reason: CloseReason::Normal,
+            };
+            return Box::pin(async move { tx.send((priority, frame)).await });
+        }
+        let frame = Frame::Data {
             stream_id: self.stream_id,
-            reason: ferrotunnel_protocol::frame::CloseReason::Normal,
+            data: Bytes::new(),
+            end_of_stream: true,
         };
-        let priority = self.priority;
+        match self.flow.as_ref() {
+            Some(flow) => {
+                let window = flow.send_window.clone();
+                let cost = frame_cost(0, flow.window_size, STREAM_CHANNEL_CAPACITY);
+                Box::pin(async move {
+                    window.acquire(cost).await;
+                    tx.send((priority, frame)).await
+                })
+            }
+            None => Box::pin(async move { tx.send((priority, frame)).await }),
+        }
+    }
+}
 
-        let tx = self.tx.clone();
-        self.pending_send = Some(Box::pin(async move { tx.send((priority, frame)).await }));
+impl AsyncRead for VirtualStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if let Some(idle) = self.idle.as_ref().filter(|idle| idle.is_expired()) {
+            return Poll::Ready(Err(idle.error()));
+        }
+        let filled = buf.filled().len();
+        match self.as_mut().poll_read_frames(cx, buf) {
+            Poll::Ready(Ok(())) if buf.filled().len() > filled => {
+                self.record_progress();
+                Poll::Ready(Ok(()))
+            }
+            Poll::Pending => match self.poll_idle(Direction::Read, cx) {
+                Some(error) => Poll::Ready(Err(error)),
+                None => Poll::Pending,
+            },
+            result @ Poll::Ready(_) => result,
+        }
+    }
+}
 
-        let fut = match self.pending_send.as_mut() {
-            Some(f) => f,
-            None => return Poll::Pending,
-        };
-        match fut.as_mut().poll(cx) {
-            Poll::Ready(result) => {
+impl AsyncWrite for VirtualStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if let Some(idle) = self.idle.as_ref().filter(|idle| idle.is_expired()) {
+            return Poll::Ready(Err(idle.error()));
+        }
+        match self.as_mut().poll_write_frames(cx, buf) {
+            Poll::Ready(Ok(n)) => {
+                self.record_progress();
+                Poll::Ready(Ok(n))
+            }
+            Poll::Pending => match self.poll_idle(Direction::Write, cx) {
+                Some(error) => Poll::Ready(Err(error)),
+                None => Poll::Pending,
+            },
+            result @ Poll::Ready(_) => result,
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Kanal channels don't require explicit flushing
+        Poll::Ready(Ok(()))
+    }
+
+    /// Close the write half: the peer reads EOF while data can still be
+    /// read from it (TCP half-close)
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            // Finish an in-flight write, or the end-of-stream frame itself
+            if let Some(fut) = self.pending_send.as_mut() {
+                let result = match fut.as_mut().poll(cx) {
+                    Poll::Ready(result) => result,
+                    Poll::Pending => return Poll::Pending,
+                };
                 self.pending_send = None;
-                match result {
-                    Ok(()) => Poll::Ready(Ok(())),
-                    Err(e) => Poll::Ready(Err(io::Error::new(
-                        io::ErrorKind::BrokenPipe,
-                        e.to_string(),
-                    ))),
+                let len = std::mem::take(&mut self.pending_send_len);
+                if self.write_closed {
+                    return Poll::Ready(
+                        result
+                            .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e.to_string())),
+                    );
+                }
+                if result.is_ok() {
+                    self.record_sent(len);
                 }
             }
-            Poll::Pending => Poll::Pending,
+            // An aborted stream was already closed with an error; an
+            // end-of-stream now would not reach the peer as anything else
+            if self.is_write_closed() {
+                return Poll::Ready(Ok(()));
+            }
+            self.write_closed = true;
+            self.pending_send = Some(self.end_of_stream_send());
         }
     }
 }
@@ -540,6 +1509,9 @@ impl AsyncWrite for VirtualStream {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::stream::flow_control::DEFAULT_STREAM_WINDOW;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
 
     #[tokio::test]
     async fn test_stream_id_allocation() {
@@ -563,4 +1535,679 @@ mod tests {
         let s4 = server_mux.open_stream(Protocol::HTTP).await.unwrap();
         assert_eq!(s4.id(), 4);
     }
+
+    #[tokio::test]
+    async fn test_active_streams_tracks_open_and_dropped() {
+        use tokio::io::AsyncReadExt;
+
+        let (client_mux, server_mux, server_streams) = connected_pair(DEFAULT_STREAM_WINDOW);
+
+        let mut local = Vec::new();
+        let mut remote = Vec::new();
+        for _ in 0..5 {
+            local.push(client_mux.open_stream(Protocol::TCP).await.unwrap());
+            remote.push(server_streams.recv().await.unwrap());
+        }
+        assert_eq!(client_mux.active_streams(), 5);
+        assert_eq!(server_mux.active_streams(), 5);
+
+        // Dropped without a clean CloseStream
+        local.truncate(3);
+        remote.truncate(3);
+        assert_eq!(client_mux.active_streams(), 3);
+        assert_eq!(server_mux.active_streams(), 3);
+
+        // Clean close: a stream counts until both sides have shut down, and is
+        // not double counted when its handles are dropped later.
+        let mut closing = local.pop().unwrap();
+        closing.shutdown().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(server_mux.active_streams(), 3);
+        let mut closing_remote = remote.pop().unwrap();
+        let mut rest = Vec::new();
+        closing_remote.read_to_end(&mut rest).await.unwrap();
+        closing_remote.shutdown().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(server_mux.active_streams(), 2);
+        assert_eq!(client_mux.active_streams(), 2);
+        drop(closing_remote);
+        remote.clear();
+        assert_eq!(server_mux.active_streams(), 0);
+
+        drop(closing);
+        local.clear();
+        assert_eq!(client_mux.active_streams(), 0);
+
+        assert!(client_mux.stream_lifetimes.is_empty());
+        assert!(server_mux.stream_lifetimes.is_empty());
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn test_dropped_streams_recorded_as_closed() {
+        ferrotunnel_observability::init_metrics();
+        let metrics = ferrotunnel_observability::tunnel_metrics().unwrap();
+        let (client_mux, server_mux, server_streams) = connected_pair(DEFAULT_STREAM_WINDOW);
+
+        let closed_before = metrics.streams_closed();
+        let mut streams = Vec::new();
+        for _ in 0..3 {
+            streams.push(client_mux.open_stream(Protocol::TCP).await.unwrap());
+            streams.push(server_streams.recv().await.unwrap());
+        }
+
+        // Neither side sends CloseStream; dropping must still end each stream.
+        // Other tests record streams concurrently, so check for growth only.
+        streams.clear();
+        assert!(metrics.streams_closed() >= closed_before + 6);
+        assert!(client_mux.stream_lifetimes.is_empty());
+        assert!(server_mux.stream_lifetimes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_close_is_seen_by_clones() {
+        let (tx, _rx) = bounded_async(100);
+        let (mux, _streams) = Multiplexer::new(tx, false);
+        let connection = mux.clone();
+        let waiter = tokio::spawn(async move { connection.closed().await });
+
+        assert!(!mux.is_closed());
+        mux.close();
+        assert!(mux.is_closed());
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("closed() did not resolve")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dropping_multiplexer_shuts_down_streams() {
+        use tokio::io::AsyncReadExt;
+
+        let (tx, _rx) = bounded_async(100);
+        let (mux, _streams) = Multiplexer::new(tx, true);
+        let mut first = mux.open_stream(Protocol::HTTP).await.unwrap();
+        let mut second = mux.open_stream(Protocol::TCP).await.unwrap();
+
+        // A clone keeps the streams open
+        let clone = mux.clone();
+        drop(mux);
+        assert_eq!(first.close_reason(), None);
+
+        drop(clone);
+        for stream in [&mut first, &mut second] {
+            let mut buf = Vec::new();
+            let n = tokio::time::timeout(Duration::from_secs(1), stream.read_to_end(&mut buf))
+                .await
+                .expect("stream was not closed")
+                .unwrap();
+            assert_eq!(n, 0);
+            assert_eq!(stream.close_reason(), Some(&CloseReason::Shutdown));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_open_stream_headers_reach_peer() {
+        let (client_mux, _server_mux, server_streams) = connected_pair(DEFAULT_STREAM_WINDOW);
+
+        let local = client_mux
+            .open_stream_with_headers(
+                Protocol::HTTP,
+                vec![("remote-addr".to_string(), "203.0.113.7:5000".to_string())],
+            )
+            .await
+            .unwrap();
+        let remote = server_streams.recv().await.unwrap();
+
+        assert_eq!(local.header("remote-addr"), Some("203.0.113.7:5000"));
+        assert_eq!(remote.header("Remote-Addr"), Some("203.0.113.7:5000"));
+        assert_eq!(remote.headers().len(), 1);
+        assert_eq!(remote.header("missing"), None);
+    }
+
+    #[tokio::test]
+    async fn test_close_reason_reaches_reader() {
+        use tokio::io::AsyncReadExt;
+
+        let (client_mux, _server_mux, server_streams) = connected_pair(DEFAULT_STREAM_WINDOW);
+        let cases = [
+            (CloseReason::Normal, None),
+            (CloseReason::Timeout, Some(io::ErrorKind::TimedOut)),
+            (
+                CloseReason::Error("upstream reset".to_string()),
+                Some(io::ErrorKind::ConnectionReset),
+            ),
+            (
+                CloseReason::QuotaExceeded,
+                Some(io::ErrorKind::ConnectionAborted),
+            ),
+        ];
+
+        for (reason, expected_kind) in cases {
+            let mut local = client_mux.open_stream(Protocol::TCP).await.unwrap();
+            let mut remote = server_streams.recv().await.unwrap();
+
+            local.write_all(b"data").await.unwrap();
+            local.close_with_reason(reason.clone()).await.unwrap();
+
+            // Data sent before the close is still delivered
+            let mut buf = [0u8; 4];
+            remote.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"data");
+
+            let mut rest = Vec::new();
+            let result = remote.read_to_end(&mut rest).await;
+            assert_eq!(result.err().map(|e| e.kind()), expected_kind);
+            assert_eq!(remote.close_reason(), Some(&reason));
+
+            // Later reads see EOF
+            assert_eq!(remote.read(&mut buf).await.unwrap(), 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_close_with_reason_reports_closed_connection() {
+        let (tx, rx) = bounded_async(100);
+        let (mux, _streams) = Multiplexer::new(tx, true);
+        let mut stream = mux.open_stream(Protocol::TCP).await.unwrap();
+
+        drop(rx);
+        let err = stream
+            .close_with_reason(CloseReason::Normal)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[tokio::test]
+    async fn test_reset_after_open_in_one_batch_releases_peer_stream() {
+        use crate::transport::batched_sender::schedule_batch;
+
+        let (client_tx, client_rx) = bounded_async::<PrioritizedFrame>(16);
+        let (server_tx, _server_rx) = bounded_async::<PrioritizedFrame>(16);
+        let (client_mux, _client_streams) = Multiplexer::new(client_tx, true);
+        let (server_mux, _server_streams) = Multiplexer::new(server_tx, false);
+
+        for from_handle in [false, true] {
+            let mut stream = client_mux.open_stream(Protocol::TCP).await.unwrap();
+            if from_handle {
+                stream.abort_handle().reset(ResetCode::Cancel);
+            } else {
+                stream.reset(ResetCode::Cancel).await.unwrap();
+            }
+
+            // Both frames are drained into the same batch before sending
+            let mut batch = vec![
+                client_rx.recv().await.unwrap(),
+                client_rx.recv().await.unwrap(),
+            ];
+            schedule_batch(&mut batch);
+            for (_, frame) in batch {
+                server_mux.process_frame(frame).await.unwrap();
+            }
+            assert_eq!(server_mux.active_streams(), 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_abort_handle_closes_with_error() {
+        use tokio::io::AsyncReadExt;
+
+        let (client_mux, _server_mux, server_streams) = connected_pair(DEFAULT_STREAM_WINDOW);
+        let mut local = client_mux.open_stream(Protocol::TCP).await.unwrap();
+        let mut remote = server_streams.recv().await.unwrap();
+        let abort = local.abort_handle();
+
+        local.write_all(b"partial").await.unwrap();
+        abort.abort(CloseReason::Error("client disconnected".to_string()));
+        abort.abort(CloseReason::Normal);
+        assert!(abort.is_aborted());
+
+        // Neither more data nor an end-of-stream follows the abort
+        assert_eq!(
+            local.write_all(b"more").await.unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+        local.shutdown().await.unwrap();
+
+        let mut buf = [0u8; 7];
+        remote.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"partial");
+        let mut rest = Vec::new();
+        let err = remote.read_to_end(&mut rest).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+        assert!(rest.is_empty());
+        assert_eq!(
+            remote.close_reason(),
+            Some(&CloseReason::Error("client disconnected".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reset_fails_reads_instead_of_eof() {
+        use tokio::io::AsyncReadExt;
+
+        let (client_mux, server_mux, server_streams) = connected_pair(DEFAULT_STREAM_WINDOW);
+        let mut local = client_mux.open_stream(Protocol::TCP).await.unwrap();
+        let mut remote = server_streams.recv().await.unwrap();
+
+        local.reset(ResetCode::LocalServiceFailed).await.unwrap();
+        assert_eq!(
+            local.write_all(b"more").await.unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+        // No end-of-stream follows the reset
+        local.shutdown().await.unwrap();
+
+        let mut rest = Vec::new();
+        let err = remote.read_to_end(&mut rest).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+        assert_eq!(remote.reset_code(), Some(ResetCode::LocalServiceFailed));
+        assert_eq!(remote.close_reason(), None);
+
+        // Unlike a close, later reads keep failing
+        let mut buf = [0u8; 4];
+        let err = remote.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+        assert_eq!(
+            remote.write_all(b"reply").await.unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+        assert_eq!(server_mux.active_streams(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_abort_handle_reset() {
+        use tokio::io::AsyncReadExt;
+
+        let (client_mux, _server_mux, server_streams) = connected_pair(DEFAULT_STREAM_WINDOW);
+        let mut local = client_mux.open_stream(Protocol::TCP).await.unwrap();
+        let mut remote = server_streams.recv().await.unwrap();
+        let abort = local.abort_handle();
+
+        abort.reset(ResetCode::Cancel);
+        abort.abort(CloseReason::Normal);
+        assert!(abort.is_aborted());
+        local.shutdown().await.unwrap();
+
+        let mut rest = Vec::new();
+        let err = remote.read_to_end(&mut rest).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+        assert_eq!(remote.reset_code(), Some(ResetCode::Cancel));
+        assert_eq!(remote.close_reason(), None);
+    }
+
+    #[tokio::test]
+    async fn test_half_close_keeps_reverse_direction_open() {
+        use tokio::io::AsyncReadExt;
+
+        let (client_mux, server_mux, server_streams) = connected_pair(DEFAULT_STREAM_WINDOW);
+        let mut local = client_mux.open_stream(Protocol::TCP).await.unwrap();
+        let mut remote = server_streams.recv().await.unwrap();
+
+        // Client sends its request and half-closes, like `shutdown(SHUT_WR)`
+        local.write_all(b"QUIT").await.unwrap();
+        local.shutdown().await.unwrap();
+        assert_eq!(
+            local.write_all(b"late").await.unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+
+        // The server reads the request up to EOF...
+        let mut request = Vec::new();
+        remote.read_to_end(&mut request).await.unwrap();
+        assert_eq!(request, b"QUIT");
+        assert_eq!(remote.close_reason(), None);
+
+        // ...and can still answer over the other direction
+        remote.write_all(b"221 Bye").await.unwrap();
+        remote.shutdown().await.unwrap();
+        let mut reply = Vec::new();
+        local.read_to_end(&mut reply).await.unwrap();
+        assert_eq!(reply, b"221 Bye");
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(client_mux.active_streams(), 0);
+        assert_eq!(server_mux.active_streams(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_half_close_clears_both_stream_tables() {
+        use tokio::io::AsyncReadExt;
+
+        let (client_mux, server_mux, server_streams) = connected_pair(DEFAULT_STREAM_WINDOW);
+        let mut local = client_mux.open_stream(Protocol::TCP).await.unwrap();
+        let mut remote = server_streams.recv().await.unwrap();
+
+        // The client finishes first, so only the server sends `CloseStream`
+        local.shutdown().await.unwrap();
+        remote.read_to_end(&mut Vec::new()).await.unwrap();
+        remote.shutdown().await.unwrap();
+        local.read_to_end(&mut Vec::new()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Both handles are still alive: cleanup must not wait for drop
+        for mux in [&client_mux, &server_mux] {
+            assert!(mux.streams.is_empty());
+            assert!(mux.stream_priorities.is_empty());
+            assert!(mux.send_windows.is_empty());
+            assert!(mux.stream_lifetimes.is_empty());
+        }
+        drop((local, remote));
+    }
+
+    /// Wire two multiplexers together, delivering frames in order on a single
+    /// task per direction (like a connection read loop).
+    fn connected_pair(window: u32) -> (Multiplexer, Multiplexer, AsyncReceiver<VirtualStream>) {
+        let window = NonZeroU32::new(window).unwrap();
+        let (client_tx, client_rx) = bounded_async::<PrioritizedFrame>(1024);
+        let (server_tx, server_rx) = bounded_async::<PrioritizedFrame>(1024);
+        let (client_mux, _client_streams) = Multiplexer::with_flow_control(client_tx, true, window);
+        let (server_mux, server_streams) = Multiplexer::with_flow_control(server_tx, false, window);
+        let (client_mux, server_mux) = (client_mux.with_ping(), server_mux.with_ping());
+        wire_pair(&client_mux, client_rx, &server_mux, server_rx);
+        (client_mux, server_mux, server_streams)
+    }
+
+    /// Deliver each multiplexer's outgoing frames to the other one
+    fn wire_pair(
+        client_mux: &Multiplexer,
+        client_rx: AsyncReceiver<PrioritizedFrame>,
+        server_mux: &Multiplexer,
+        server_rx: AsyncReceiver<PrioritizedFrame>,
+    ) {
+        let to_server = server_mux.clone();
+        tokio::spawn(async move {
+            while let Ok((_, frame)) = client_rx.recv().await {
+                to_server.process_frame(frame).await.unwrap();
+            }
+        });
+        let to_client = client_mux.clone();
+        tokio::spawn(async move {
+            while let Ok((_, frame)) = server_rx.recv().await {
+                to_client.process_frame(frame).await.unwrap();
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn test_ping_round_trip() {
+        let (client_mux, server_mux, _server_streams) = connected_pair(DEFAULT_STREAM_WINDOW);
+
+        let rtt = client_mux.ping(7).await.unwrap();
+        assert!(rtt < DEFAULT_PING_TIMEOUT);
+        server_mux.ping(8).await.unwrap();
+        assert!(client_mux.pending_pings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ping_times_out_without_pong() {
+        // Frames are sent but never delivered to a peer
+        let (tx, _rx) = bounded_async(16);
+        let (mux, _streams) = Multiplexer::new(tx, true);
+        let mux = mux.with_ping();
+
+        let err = mux
+            .ping_with_timeout(1, Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, TunnelError::Timeout(_)), "{err}");
+        assert!(mux.pending_pings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ping_requires_negotiation() {
+        let (tx, rx) = bounded_async(16);
+        let (mux, _streams) = Multiplexer::new(tx, true);
+
+        let err = mux.ping(1).await.unwrap_err();
+        assert!(matches!(err, TunnelError::Protocol(_)), "{err}");
+        assert!(rx.is_empty());
+        assert!(mux.pending_pings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_traffic_counts_data_bytes_both_ways() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (client_tx, client_rx) = bounded_async::<PrioritizedFrame>(1024);
+        let (server_tx, server_rx) = bounded_async::<PrioritizedFrame>(1024);
+        let (client_mux, _client_streams) = Multiplexer::new(client_tx, true);
+        let (server_mux, server_streams) = Multiplexer::new(server_tx, false);
+        wire_pair(&client_mux, client_rx, &server_mux, server_rx);
+
+        // Larger than one frame, so the write is chunked
+        let upload = vec![7u8; MAX_DATA_FRAME_PAYLOAD * 2 + 123];
+        let mut local = client_mux.open_stream(Protocol::TCP).await.unwrap();
+        let mut remote = server_streams.recv().await.unwrap();
+        local.write_all(&upload).await.unwrap();
+        let mut received = vec![0u8; upload.len()];
+        remote.read_exact(&mut received).await.unwrap();
+        remote.write_all(b"done").await.unwrap();
+        let mut reply = [0u8; 4];
+        local.read_exact(&mut reply).await.unwrap();
+
+        let sent = upload.len() as u64;
+        assert_eq!(client_mux.traffic().bytes_out(), sent);
+        assert_eq!(server_mux.traffic().bytes_in(), sent);
+        assert_eq!(server_mux.traffic().bytes_out(), 4);
+        assert_eq!(client_mux.traffic().bytes_in(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_max_frame_size_limits_data_chunks() {
+        use bytes::BytesMut;
+        use ferrotunnel_protocol::codec::TunnelCodec;
+        use tokio_util::codec::Encoder;
+
+        const MAX_FRAME_SIZE: usize = 16 * 1024;
+        let (tx, rx) = bounded_async::<PrioritizedFrame>(1024);
+        let (mux, _streams) = Multiplexer::new(tx, true);
+        let mux = mux.with_max_frame_size(MAX_FRAME_SIZE);
+        assert_eq!(
+            mux.max_data_payload(),
+            MAX_FRAME_SIZE - DATA_FRAME_HEADER_SIZE
+        );
+
+        let mut stream = mux.open_stream(Protocol::TCP).await.unwrap();
+        stream.write_all(&vec![1u8; 40 * 1024]).await.unwrap();
+        stream
+            .send_bytes(Bytes::from(vec![2u8; 40 * 1024]))
+            .await
+            .unwrap();
+
+        let mut codec = TunnelCodec::with_max_frame_size(MAX_FRAME_SIZE);
+        let mut sent = 0;
+        while let Ok(Some((_, frame))) = rx.try_recv() {
+            if let Frame::Data { data, .. } = &frame {
+                assert!(data.len() <= mux.max_data_payload());
+                sent += data.len();
+            }
+            codec.encode(frame, &mut BytesMut::new()).unwrap();
+        }
+        assert_eq!(sent, 80 * 1024);
+    }
+
+    #[tokio::test]
+    async fn test_send_bytes_chunks_under_flow_control() {
+        use tokio::io::AsyncReadExt;
+
+        // A window smaller than the payload, so the sender has to wait for
+        // credit several times
+        let (client_mux, _server_mux, server_streams) = connected_pair(64 * 1024);
+        let payload: Vec<u8> = (0..MAX_DATA_FRAME_PAYLOAD * 5 + 17)
+            .map(|i| u8::try_from(i % 251).unwrap())
+            .collect();
+        let mut local = client_mux.open_stream(Protocol::TCP).await.unwrap();
+        let mut remote = server_streams.recv().await.unwrap();
+
+        let bulk = Bytes::from(payload.clone());
+        let sender = tokio::spawn(async move {
+            local.write_all(b"head").await.unwrap();
+            local.send_bytes(bulk).await.unwrap();
+            local.write_all(b"tail").await.unwrap();
+            local
+        });
+
+        let mut received = vec![0u8; payload.len() + 8];
+        tokio::time::timeout(Duration::from_secs(5), remote.read_exact(&mut received))
+            .await
+            .expect("send_bytes stalled")
+            .unwrap();
+        assert_eq!(&received[..4], b"head");
+        assert_eq!(&received[4..payload.len() + 4], &payload[..]);
+        assert_eq!(&received[payload.len() + 4..], b"tail");
+
+        sender.await.unwrap();
+        assert_eq!(client_mux.traffic().bytes_out(), received.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_peer_times_out_stream() {
+        use tokio::io::AsyncReadExt;
+
+        let idle = Duration::from_millis(200);
+        let (client_mux, _server_mux, server_streams) = connected_pair(64 * 1024);
+        let client_mux = client_mux.with_stream_idle_timeout(idle);
+
+        // The peer takes the first bytes, then neither answers nor closes
+        let mut local = client_mux.open_stream(Protocol::TCP).await.unwrap();
+        let mut remote = server_streams.recv().await.unwrap();
+        local.write_all(b"request").await.unwrap();
+        let mut buf = [0u8; 7];
+        remote.read_exact(&mut buf).await.unwrap();
+
+        let started = Instant::now();
+        let err = tokio::time::timeout(Duration::from_secs(5), local.read(&mut buf))
+            .await
+            .expect("idle read never timed out")
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() >= idle);
+        // The stream stays failed
+        assert_eq!(
+            local.write(b"more").await.unwrap_err().kind(),
+            io::ErrorKind::TimedOut
+        );
+
+        // The peer learns why the stream went away
+        let err = remote.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(remote.close_reason(), Some(&CloseReason::Timeout));
+
+        // A writer blocked on a peer that stopped reading times out as well
+        let mut local = client_mux.open_stream(Protocol::TCP).await.unwrap();
+        let _remote = server_streams.recv().await.unwrap();
+        let upload = vec![0u8; 1024 * 1024];
+        let err = tokio::time::timeout(Duration::from_secs(5), local.write_all(&upload))
+            .await
+            .expect("blocked write never timed out")
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn test_active_stream_does_not_time_out() {
+        use tokio::io::AsyncReadExt;
+
+        let idle = Duration::from_millis(300);
+        let (client_mux, _server_mux, server_streams) = connected_pair(DEFAULT_STREAM_WINDOW);
+        let client_mux = client_mux.with_stream_idle_timeout(idle);
+        let mut local = client_mux.open_stream(Protocol::TCP).await.unwrap();
+        let mut remote = server_streams.recv().await.unwrap();
+
+        // A slow but steady peer keeps the stream alive past the window
+        tokio::spawn(async move {
+            for _ in 0..6 {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                remote.write_all(b"tick").await.unwrap();
+            }
+        });
+        let mut buf = [0u8; 24];
+        local.read_exact(&mut buf).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_max_streams_rejects_excess_opens() {
+        use tokio::io::AsyncReadExt;
+
+        let (client_tx, client_rx) = bounded_async::<PrioritizedFrame>(1024);
+        let (server_tx, server_rx) = bounded_async::<PrioritizedFrame>(1024);
+        let (client_mux, _client_streams) = Multiplexer::new(client_tx, true);
+        let (server_mux, server_streams) = Multiplexer::new(server_tx, false);
+        let server_mux = server_mux.with_max_streams(NonZeroUsize::new(2).unwrap());
+        wire_pair(&client_mux, client_rx, &server_mux, server_rx);
+
+        // Up to the limit, peer opens are accepted
+        let mut local = Vec::new();
+        let mut remote = Vec::new();
+        for _ in 0..2 {
+            local.push(client_mux.open_stream(Protocol::TCP).await.unwrap());
+            remote.push(server_streams.recv().await.unwrap());
+        }
+        assert_eq!(server_mux.active_streams(), 2);
+
+        // The next one is closed straight back with QuotaExceeded
+        let mut rejected = client_mux.open_stream(Protocol::TCP).await.unwrap();
+        let mut buf = [0u8; 1];
+        let err = tokio::time::timeout(Duration::from_secs(1), rejected.read(&mut buf))
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+        assert_eq!(rejected.close_reason(), Some(&CloseReason::QuotaExceeded));
+        assert!(server_streams.try_recv().unwrap().is_none());
+        assert_eq!(server_mux.active_streams(), 2);
+
+        // Local opens count against the same limit
+        assert!(server_mux.open_stream(Protocol::TCP).await.is_err());
+
+        // A stream frees its slot once both sides are done with it
+        local.pop().unwrap().shutdown().await.unwrap();
+        drop(remote.pop());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(server_mux.active_streams(), 1);
+        let _fresh = server_mux.open_stream(Protocol::TCP).await.unwrap();
+        assert_eq!(server_mux.active_streams(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_slow_stream_does_not_starve_fast_stream() {
+        use tokio::io::AsyncReadExt;
+
+        let window = 64 * 1024;
+        let (client_mux, _server_mux, server_streams) = connected_pair(window);
+
+        let mut slow = client_mux.open_stream(Protocol::TCP).await.unwrap();
+        let _slow_remote = server_streams.recv().await.unwrap(); // never read
+        let mut fast = client_mux.open_stream(Protocol::TCP).await.unwrap();
+        let mut fast_remote = server_streams.recv().await.unwrap();
+
+        // Far more than the window and the per-stream channel can hold
+        tokio::spawn(async move {
+            let chunk = vec![0u8; 1024];
+            loop {
+                if slow.write_all(&chunk).await.is_err() {
+                    break;
+                }
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let payload = vec![7u8; 4 * window as usize];
+        let expected = payload.clone();
+        tokio::spawn(async move {
+            fast.write_all(&payload).await.unwrap();
+        });
+
+        let mut received = vec![0u8; expected.len()];
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            fast_remote.read_exact(&mut received),
+        )
+        .await
+        .expect("fast stream starved by slow stream")
+        .unwrap();
+        assert_eq!(received, expected);
+    }
 }
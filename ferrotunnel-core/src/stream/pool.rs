@@ -3,6 +3,7 @@
 //! Uses crossbeam's `ArrayQueue` for high-performance pooling.
 
 use crossbeam_queue::ArrayQueue;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 /// Default pool capacity - enough for typical concurrent stream count
@@ -19,19 +20,20 @@ pub trait Poolable: Sized {
 /// The pool attempts to reuse objects when possible, falling back to
 /// creating new instances when the pool is empty. Objects are returned
 /// to the pool when dropped via `PooledObject`.
+///
+/// At most `max_pooled` idle objects are retained; releases beyond that
+/// drop the object so memory held after a traffic spike stays bounded.
 #[derive(Debug)]
 pub struct ObjectPool<T: Poolable> {
     queue: Arc<ArrayQueue<T>>,
     capacity: usize,
+    max_pooled: Arc<AtomicUsize>,
 }
 
 impl<T: Poolable> ObjectPool<T> {
     /// Create a new pool with the specified capacity
     pub fn new(capacity: usize) -> Self {
-        Self {
-            queue: Arc::new(ArrayQueue::new(capacity)),
-            capacity,
-        }
+        Self::with_capacity_and_limit(capacity, capacity)
     }
 
     /// Create a pool with default capacity (256)
@@ -39,6 +41,16 @@ impl<T: Poolable> ObjectPool<T> {
         Self::new(DEFAULT_POOL_CAPACITY)
     }
 
+    /// Create a pool with room for `capacity` objects that retains at most
+    /// `max_pooled` of them (clamped to `capacity`)
+    pub fn with_capacity_and_limit(capacity: usize, max_pooled: usize) -> Self {
+        Self {
+            queue: Arc::new(ArrayQueue::new(capacity)),
+            capacity,
+            max_pooled: Arc::new(AtomicUsize::new(max_pooled.min(capacity))),
+        }
+    }
+
     /// Acquire an object from the pool, or None if pool is empty
     pub fn try_acquire(&self) -> Option<T> {
         self.queue.pop()
@@ -47,11 +59,18 @@ impl<T: Poolable> ObjectPool<T> {
     /// Release an object back to the pool
     ///
     /// The object is reset before being added to the pool.
-    /// If the pool is full, the object is dropped.
-    pub fn release(&self, mut obj: T) {
-        obj.reset();
-        // Ignore push failure if pool is full - object will be dropped
-        let _ = self.queue.push(obj);
+    /// If the pool already holds `max_pooled` objects, the object is dropped.
+    pub fn release(&self, obj: T) {
+        return_to_pool(&self.queue, &self.max_pooled, obj);
+    }
+
+    /// Wrap `obj` so it is released back to this pool when dropped
+    pub fn wrap(&self, obj: T) -> PooledObject<T> {
+        PooledObject {
+            obj: Some(obj),
+            pool: Arc::clone(&self.queue),
+            max_pooled: Arc::clone(&self.max_pooled),
+        }
     }
 
     /// Current number of pooled objects
@@ -69,6 +88,29 @@ impl<T: Poolable> ObjectPool<T> {
         self.capacity
     }
 
+    /// Maximum number of idle objects the pool retains
+    pub fn max_pooled(&self) -> usize {
+        self.max_pooled.load(Ordering::Relaxed)
+    }
+
+    /// Change the retention limit (clamped to the capacity), dropping pooled
+    /// objects above the new limit. Shared by every clone of this pool.
+    pub fn set_max_pooled(&self, max_pooled: usize) {
+        let max_pooled = max_pooled.min(self.capacity);
+        self.max_pooled.store(max_pooled, Ordering::Relaxed);
+        self.shrink_to(max_pooled);
+    }
+
+    /// Drop pooled objects until at most `target` remain, without changing
+    /// the limit. Call periodically to give memory back once traffic idles.
+    pub fn shrink_to(&self, target: usize) {
+        while self.queue.len() > target {
+            if self.queue.pop().is_none() {
+                break;
+            }
+        }
+    }
+
     /// Get a clone of the internal queue for sharing
     pub fn queue(&self) -> Arc<ArrayQueue<T>> {
         Arc::clone(&self.queue)
@@ -80,6 +122,7 @@ impl<T: Poolable> Clone for ObjectPool<T> {
         Self {
             queue: Arc::clone(&self.queue),
             capacity: self.capacity,
+            max_pooled: Arc::clone(&self.max_pooled),
         }
     }
 }
@@ -90,18 +133,43 @@ impl<T: Poolable> Default for ObjectPool<T> {
     }
 }
 
+/// Reset `obj` and push it onto `queue` unless `max_pooled` objects are
+/// already pooled, in which case it is dropped
+fn return_to_pool<T: Poolable>(queue: &ArrayQueue<T>, max_pooled: &AtomicUsize, mut obj: T) {
+    let limit = max_pooled.load(Ordering::Relaxed);
+    if queue.len() >= limit {
+        return;
+    }
+    obj.reset();
+    // Ignore push failure if pool is full - object will be dropped
+    if queue.push(obj).is_ok() {
+        // Concurrent releases may race past the check above; trim the excess
+        while queue.len() > limit {
+            if queue.pop().is_none() {
+                break;
+            }
+        }
+    }
+}
+
 /// A wrapper that returns the object to the pool when dropped
 pub struct PooledObject<T: Poolable> {
     obj: Option<T>,
     pool: Arc<ArrayQueue<T>>,
+    max_pooled: Arc<AtomicUsize>,
 }
 
 impl<T: Poolable> PooledObject<T> {
     /// Create a new pooled object wrapper
+    ///
+    /// The object may fill the whole queue on return; use
+    /// [`ObjectPool::wrap`] to respect the pool's retention limit.
     pub fn new(obj: T, pool: Arc<ArrayQueue<T>>) -> Self {
+        let max_pooled = Arc::new(AtomicUsize::new(pool.capacity()));
         Self {
             obj: Some(obj),
             pool,
+            max_pooled,
         }
     }
 
@@ -133,10 +201,8 @@ impl<T: Poolable> std::ops::DerefMut for PooledObject<T> {
 
 impl<T: Poolable> Drop for PooledObject<T> {
     fn drop(&mut self) {
-        if let Some(mut obj) = self.obj.take() {
-            obj.reset();
-            // Return to pool, ignore if full
-            let _ = self.pool.push(obj);
+        if let Some(obj) = self.obj.take() {
+            return_to_pool(&self.pool, &self.max_pooled, obj);
         }
     }
 }
@@ -225,6 +291,80 @@ mod tests {
         assert_eq!(acquired.value, 0); // Reset
     }
 
+    /// Counts how many instances have been dropped
+    #[derive(Debug)]
+    struct Tracked(Arc<AtomicUsize>);
+
+    impl Poolable for Tracked {
+        fn reset(&mut self) {}
+    }
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_pool_never_retains_more_than_limit() {
+        let pool: ObjectPool<TestObject> = ObjectPool::with_capacity_and_limit(16, 3);
+        assert_eq!(pool.capacity(), 16);
+        assert_eq!(pool.max_pooled(), 3);
+
+        for _ in 0..10 {
+            pool.release(TestObject::default());
+            assert!(pool.len() <= 3);
+        }
+        assert_eq!(pool.len(), 3);
+
+        // Wrapped objects respect the limit too
+        drop(pool.wrap(TestObject::default()));
+        assert_eq!(pool.len(), 3);
+
+        // The limit is clamped to the capacity
+        let clamped: ObjectPool<TestObject> = ObjectPool::with_capacity_and_limit(2, 8);
+        assert_eq!(clamped.max_pooled(), 2);
+    }
+
+    #[test]
+    fn test_over_limit_releases_are_dropped() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let pool: ObjectPool<Tracked> = ObjectPool::with_capacity_and_limit(8, 2);
+
+        for _ in 0..5 {
+            pool.release(Tracked(dropped.clone()));
+        }
+        assert_eq!(pool.len(), 2);
+        assert_eq!(dropped.load(Ordering::Relaxed), 3);
+
+        drop(pool.wrap(Tracked(dropped.clone())));
+        assert_eq!(dropped.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn test_pool_shrinks_after_spike() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let pool: ObjectPool<Tracked> = ObjectPool::new(8);
+        for _ in 0..8 {
+            pool.release(Tracked(dropped.clone()));
+        }
+        assert_eq!(pool.len(), 8);
+
+        pool.shrink_to(3);
+        assert_eq!(pool.len(), 3);
+        assert_eq!(dropped.load(Ordering::Relaxed), 5);
+        assert_eq!(pool.max_pooled(), 8);
+
+        // Lowering the limit trims immediately and applies to every clone
+        let shared = pool.clone();
+        shared.set_max_pooled(1);
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.max_pooled(), 1);
+        pool.release(Tracked(dropped.clone()));
+        assert_eq!(pool.len(), 1);
+        assert_eq!(dropped.load(Ordering::Relaxed), 8);
+    }
+
     #[test]
     fn test_byte_buffer_pool() {
         let pool: ByteBufferPool = ObjectPool::new(10);
@@ -0,0 +1,101 @@
+//! Byte counters for tunnel traffic
+//!
+//! Counts the payload bytes of `Data` frames flowing through a multiplexer in
+//! each direction. Counters are relaxed atomics shared by the multiplexer and
+//! its streams, so they are cheap to update on every frame and can be read at
+//! any time, e.g. from the session store or a dashboard.
+//!
+//! With the `metrics` feature, counters tagged with a tunnel ID also feed the
+//! per-tunnel usage metrics when those are enabled. The tunnel's counters are
+//! resolved once when tagged, and its metrics label is held until the last
+//! clone is dropped.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Default)]
+struct Counters {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+/// Shared handle to a pair of traffic counters
+#[derive(Debug, Clone, Default)]
+pub struct TrafficCounters {
+    inner: Arc<Counters>,
+    /// Per-tunnel usage counters the traffic is also reported to
+    #[cfg(feature = "metrics")]
+    usage: Option<ferrotunnel_observability::TunnelUsage>,
+}
+
+impl TrafficCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also report this traffic under `tunnel_id` in the per-tunnel usage
+    /// metrics (`ferrotunnel_bytes_total`), if they are enabled by now
+    #[cfg(feature = "metrics")]
+    #[must_use]
+    pub fn reported_as(mut self, tunnel_id: &str) -> Self {
+        self.usage = ferrotunnel_observability::tunnel_metrics()
+            .and_then(|m| m.tunnel_usage())
+            .map(|usage| usage.tunnel(tunnel_id));
+        self
+    }
+
+    /// Count a request forwarded to the tunnel in the per-tunnel usage
+    /// metrics (`ferrotunnel_requests_total`), if this traffic is reported
+    #[cfg(feature = "metrics")]
+    pub fn record_request(&self) {
+        if let Some(usage) = &self.usage {
+            usage.record_request();
+        }
+    }
+
+    /// Payload bytes received from the peer
+    pub fn bytes_in(&self) -> u64 {
+        self.inner.bytes_in.load(Ordering::Relaxed)
+    }
+
+    /// Payload bytes sent to the peer
+    pub fn bytes_out(&self) -> u64 {
+        self.inner.bytes_out.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_in(&self, bytes: usize) {
+        self.inner
+            .bytes_in
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        if let Some(usage) = &self.usage {
+            usage.record_bytes_in(bytes);
+        }
+    }
+
+    pub(crate) fn record_out(&self, bytes: usize) {
+        self.inner
+            .bytes_out
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        if let Some(usage) = &self.usage {
+            usage.record_bytes_out(bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_counters() {
+        let counters = TrafficCounters::new();
+        let handle = counters.clone();
+        counters.record_in(10);
+        counters.record_out(3);
+        handle.record_out(4);
+        assert_eq!(handle.bytes_in(), 10);
+        assert_eq!(counters.bytes_out(), 7);
+    }
+}
@@ -6,13 +6,17 @@
 //! ## Performance Optimizations (P2)
 //! - Adaptive batching: immediate flush when idle (single frame, low load)
 //! - Only batch when under sustained load (reduces latency for interactive use)
+//! - Batch size and wait follow recent fill rates within fixed bounds
 //! - Removed unnecessary flush() for raw TCP (TCP_NODELAY handles it)
 
+use crate::interceptor::SharedFrameInterceptor;
 use crate::stream::PrioritizedFrame;
 use bytes::{BufMut, Bytes, BytesMut};
-use ferrotunnel_protocol::codec::TunnelCodec;
+use ferrotunnel_common::{Result, TunnelError};
+use ferrotunnel_protocol::codec::{frame_checksum, TunnelCodec};
 use ferrotunnel_protocol::Frame;
-use kanal::AsyncReceiver;
+use kanal::{AsyncReceiver, AsyncSender};
+use std::collections::VecDeque;
 use std::io;
 use std::io::IoSlice;
 use std::time::{Duration, Instant};
@@ -21,19 +25,74 @@ use tokio::time::timeout;
 use tokio_util::codec::Encoder;
 use tracing::warn;
 
-/// Maximum frames to batch before flushing
-const MAX_BATCH_SIZE: usize = 256;
+/// Frames to batch before flushing, before any adaptation
+const DEFAULT_BATCH_SIZE: usize = 256;
 
-/// Batch timeout for collecting more frames (microseconds)
+/// Bounds for the adapted batch size
+const MIN_BATCH_SIZE: usize = 32;
+const MAX_BATCH_SIZE: usize = 1024;
+
+/// Batch timeout for collecting more frames (microseconds), before any adaptation
 /// Short enough to not hurt latency, long enough to batch effectively
 const BATCH_TIMEOUT_MICROS: u64 = 50;
 
+/// Upper bound for the adapted batch timeout (microseconds); the lower bound
+/// is zero, i.e. flush as soon as the queue is drained
+const MAX_BATCH_TIMEOUT_MICROS: u64 = 200;
+
+/// Average fill (per mille of the batch size) above which batches grow
+const GROW_FILL_PERMILLE: usize = 900;
+
+/// Average fill (per mille of the batch size) below which batches shrink
+const SHRINK_FILL_PERMILLE: usize = 100;
+
+/// Average fill that neither grows nor shrinks batches
+const NEUTRAL_FILL_PERMILLE: usize = usize::midpoint(GROW_FILL_PERMILLE, SHRINK_FILL_PERMILLE);
+
 /// Minimum frames before we consider waiting for more
 /// If we have fewer frames, flush immediately for lower latency
 const MIN_FRAMES_FOR_BATCHING: usize = 2;
 
+/// Default number of frames queued for the batched sender per connection
+pub const DEFAULT_FRAME_CHANNEL_CAPACITY: usize = 1024;
+
+/// Smallest accepted frame channel capacity
+pub const MIN_FRAME_CHANNEL_CAPACITY: usize = 16;
+
+/// Largest accepted frame channel capacity
+pub const MAX_FRAME_CHANNEL_CAPACITY: usize = 65536;
+
+/// Default time a write may go without progress before the sender gives up
+pub const DEFAULT_WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Clamp a frame channel capacity to
+/// `MIN_FRAME_CHANNEL_CAPACITY..=MAX_FRAME_CHANNEL_CAPACITY` and round it up
+/// to a power of two
+///
+/// Each queued frame may be as large as the codec's maximum frame size, so
+/// the worst-case memory held per connection is `capacity` times that size;
+/// in practice data frames are far smaller. Senders wait while the channel
+/// is full, so a smaller capacity bounds memory at the cost of more
+/// write-side blocking under bursts.
+pub fn frame_channel_capacity(capacity: usize) -> usize {
+    capacity
+        .clamp(MIN_FRAME_CHANNEL_CAPACITY, MAX_FRAME_CHANNEL_CAPACITY)
+        .next_power_of_two()
+}
+
+/// Channel feeding a batched sender, holding at most `capacity` frames
+pub fn frame_channel(
+    capacity: usize,
+) -> (
+    AsyncSender<PrioritizedFrame>,
+    AsyncReceiver<PrioritizedFrame>,
+) {
+    kanal::bounded_async(capacity)
+}
+
 /// Spawns a batched sender task that collects frames and flushes them together.
-/// Frames are drained in priority order (Critical → High → Normal → Low).
+/// Frames are drained in priority order (Critical → High → Normal → Low), and
+/// streams of the same priority take turns within a batch.
 ///
 /// With length-prefixed framing, each frame is encoded with a header. Data
 /// frames use vectored writes to avoid copying payload bytes.
@@ -42,15 +101,40 @@ const MIN_FRAMES_FOR_BATCHING: usize = 2;
 /// - Always try to batch frames for throughput
 /// - Short timeout (50µs) balances latency vs throughput
 /// - Single frame: flush immediately (no wait)
+/// - Consistently full batches double the size and timeout (up to 1024
+///   frames / 200µs); mostly empty ones halve them (down to 32 frames and
+///   no wait)
+///
+/// Returns `Ok` once every sender of `frame_rx` is gone. A failed write, or
+/// one that makes no progress within [`DEFAULT_WRITE_TIMEOUT`] because the
+/// peer stopped reading, ends the task with an error instead of leaving it
+/// blocked with the frame channel backing up behind it.
 pub async fn run_batched_sender<W>(
+    frame_rx: AsyncReceiver<PrioritizedFrame>,
+    writer: W,
+    codec: TunnelCodec,
+) -> Result<()>
+where
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    run_batched_sender_with_interceptor(frame_rx, writer, codec, None, DEFAULT_WRITE_TIMEOUT).await
+}
+
+/// [`run_batched_sender`] that shows each frame to `interceptor` just before
+/// it is encoded and gives up on writes stalled for `write_timeout`
+pub async fn run_batched_sender_with_interceptor<W>(
     frame_rx: AsyncReceiver<PrioritizedFrame>,
     mut writer: W,
     mut codec: TunnelCodec,
-) where
+    interceptor: Option<SharedFrameInterceptor>,
+    write_timeout: Duration,
+) -> Result<()>
+where
     W: AsyncWrite + Unpin + Send + 'static,
 {
-    let mut frames = Vec::with_capacity(MAX_BATCH_SIZE);
-    let mut encoded_segments = Vec::with_capacity(MAX_BATCH_SIZE * 2);
+    let mut batch = AdaptiveBatch::default();
+    let mut frames = Vec::with_capacity(DEFAULT_BATCH_SIZE);
+    let mut encoded_segments = Vec::with_capacity(DEFAULT_BATCH_SIZE * 2);
 
     loop {
         frames.clear();
@@ -60,11 +144,11 @@ pub async fn run_batched_sender<W>(
         if let Ok(pf) = frame_rx.recv().await {
             frames.push(pf);
         } else {
-            break;
+            return Ok(());
         }
 
         // Try to collect more frames without blocking (non-blocking drain)
-        while frames.len() < MAX_BATCH_SIZE {
+        while frames.len() < batch.max_size {
             match frame_rx.try_recv() {
                 Ok(Some(pf)) => frames.push(pf),
                 Ok(None) | Err(_) => break,
@@ -73,11 +157,11 @@ pub async fn run_batched_sender<W>(
 
         // If we got multiple frames, try to collect more with a short timeout
         // This improves throughput under load while keeping latency low
-        if frames.len() >= MIN_FRAMES_FOR_BATCHING && frames.len() < MAX_BATCH_SIZE {
-            let deadline = Duration::from_micros(BATCH_TIMEOUT_MICROS);
+        if frames.len() >= MIN_FRAMES_FOR_BATCHING && frames.len() < batch.max_size {
+            let deadline = batch.timeout;
             let start = Instant::now();
 
-            while frames.len() < MAX_BATCH_SIZE {
+            while frames.len() < batch.max_size {
                 let remaining = deadline.saturating_sub(start.elapsed());
                 if remaining.is_zero() {
                     break;
@@ -89,8 +173,10 @@ pub async fn run_batched_sender<W>(
             }
         }
 
+        batch.record(frames.len());
+
         // Send in priority order: Critical first, then High, Normal, Low
-        frames.sort_by_key(|(p, _)| p.drain_order());
+        schedule_batch(&mut frames);
 
         #[cfg(feature = "metrics")]
         let n_frames = frames.len();
@@ -104,6 +190,9 @@ pub async fn run_batched_sender<W>(
         let encode_start = Instant::now();
         // Encode all frames using vectored writes for zero-copy data frames
         for (_priority, frame) in frames.drain(..) {
+            if let Some(interceptor) = &interceptor {
+                interceptor.on_send(&frame);
+            }
             if let Err(e) = encode_frame_segments(&mut codec, frame, &mut encoded_segments) {
                 warn!("Skipping invalid frame: {}", e);
             }
@@ -118,16 +207,129 @@ pub async fn run_batched_sender<W>(
 
         // Write to socket using vectored I/O
         if !encoded_segments.is_empty() {
-            if let Err(e) = write_all_vectored(&mut writer, &encoded_segments).await {
-                warn!("Failed to write batched frames: {}", e);
+            if let Err(e) = write_all_vectored(&mut writer, &encoded_segments, write_timeout).await
+            {
+                warn!("Failed to write batched frames, closing sender: {}", e);
+                return Err(match e.kind() {
+                    io::ErrorKind::TimedOut => TunnelError::Timeout(e.to_string()),
+                    _ => TunnelError::Io(e),
+                });
+            }
+        }
+    }
+}
+
+/// Batch size and timeout adapted to recent load
+///
+/// Keeps a moving average of how full recent batches were. When batches keep
+/// hitting the size limit the sender is saturated, so the size and timeout
+/// double to amortize more frames per syscall; when they stay nearly empty
+/// both halve, down to flushing as soon as the queue is drained.
+#[derive(Debug)]
+struct AdaptiveBatch {
+    max_size: usize,
+    timeout: Duration,
+    /// Moving average of batch fill, in per mille of `max_size`
+    fill_permille: usize,
+}
+
+impl Default for AdaptiveBatch {
+    fn default() -> Self {
+        Self {
+            max_size: DEFAULT_BATCH_SIZE,
+            timeout: Duration::from_micros(BATCH_TIMEOUT_MICROS),
+            fill_permille: NEUTRAL_FILL_PERMILLE,
+        }
+    }
+}
+
+impl AdaptiveBatch {
+    /// Account for a batch of `frames` and adjust the limits for the next one
+    fn record(&mut self, frames: usize) {
+        let fill = frames.min(self.max_size) * 1000 / self.max_size;
+        self.fill_permille = (self.fill_permille * 7 + fill) / 8;
+
+        if self.fill_permille >= GROW_FILL_PERMILLE {
+            if self.timeout.is_zero() {
+                self.timeout = Duration::from_micros(BATCH_TIMEOUT_MICROS);
+            } else {
+                self.timeout =
+                    (self.timeout * 2).min(Duration::from_micros(MAX_BATCH_TIMEOUT_MICROS));
+            }
+            self.max_size = (self.max_size * 2).min(MAX_BATCH_SIZE);
+            // Judge the new limits on the batches they produce
+            self.fill_permille = NEUTRAL_FILL_PERMILLE;
+        } else if self.fill_permille <= SHRINK_FILL_PERMILLE {
+            if self.max_size == MIN_BATCH_SIZE {
+                self.timeout = Duration::ZERO;
+            } else {
+                self.timeout /= 2;
+            }
+            self.max_size = (self.max_size / 2).max(MIN_BATCH_SIZE);
+            self.fill_permille = NEUTRAL_FILL_PERMILLE;
+        }
+    }
+}
+
+/// Order a batch for sending: by priority, then round-robin across streams
+/// within each priority so one busy stream cannot delay the others by a
+/// whole batch. Frames of the same stream keep their relative order.
+pub(crate) fn schedule_batch(frames: &mut Vec<PrioritizedFrame>) {
+    frames.sort_by_key(|(p, _)| p.drain_order());
+    if frames.len() < MIN_FRAMES_FOR_BATCHING {
+        return;
+    }
+
+    // One queue per (priority, stream) in order of first appearance; control
+    // frames without a stream share a queue
+    let mut queues: Vec<(u8, Option<u32>, VecDeque<PrioritizedFrame>)> = Vec::new();
+    for pf in frames.drain(..) {
+        let order = pf.0.drain_order();
+        let stream = stream_id_of(&pf.1);
+        match queues
+            .iter_mut()
+            .find(|(o, s, _)| *o == order && *s == stream)
+        {
+            Some((_, _, queue)) => queue.push_back(pf),
+            None => queues.push((order, stream, VecDeque::from([pf]))),
+        }
+    }
+
+    let mut start = 0;
+    while start < queues.len() {
+        let order = queues[start].0;
+        let end = start
+            + queues[start..]
+                .iter()
+                .take_while(|(o, _, _)| *o == order)
+                .count();
+        loop {
+            let before = frames.len();
+            for (_, _, queue) in &mut queues[start..end] {
+                frames.extend(queue.pop_front());
+            }
+            if frames.len() == before {
                 break;
             }
         }
+        start = end;
+    }
+}
+
+fn stream_id_of(frame: &Frame) -> Option<u32> {
+    match frame {
+        Frame::Data { stream_id, .. }
+        | Frame::CloseStream { stream_id, .. }
+        | Frame::ResetStream { stream_id, .. }
+        | Frame::WindowUpdate { stream_id, .. } => Some(*stream_id),
+        Frame::OpenStream(open) => Some(open.stream_id),
+        _ => None,
     }
 }
 
 const FRAME_TYPE_DATA: u8 = 0x01;
 const FLAG_EOS: u8 = 0x01;
+const FLAG_COMPRESSED: u8 = 0x02;
 
 fn encode_frame_segments(
     codec: &mut TunnelCodec,
@@ -140,7 +342,13 @@ fn encode_frame_segments(
             data,
             end_of_stream,
         } => {
-            let payload_len = 1 + 4 + 1 + data.len();
+            let (data, compressed) = codec.compress_payload(data)?;
+            let mut flags = if end_of_stream { FLAG_EOS } else { 0 };
+            if compressed {
+                flags |= FLAG_COMPRESSED;
+            }
+            let checksum_len = if codec.checksum() { 4 } else { 0 };
+            let payload_len = 1 + 4 + 1 + data.len() + checksum_len;
             if payload_len > codec.max_frame_size() {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidData,
@@ -156,12 +364,19 @@ fn encode_frame_segments(
             header.put_u32(payload_len as u32);
             header.put_u8(FRAME_TYPE_DATA);
             header.put_u32(stream_id);
-            header.put_u8(if end_of_stream { FLAG_EOS } else { 0 });
-            out.push(header.freeze());
+            header.put_u8(flags);
+            let header = header.freeze();
+            let crc = codec
+                .checksum()
+                .then(|| frame_checksum(&[&header[4..], &data]));
+            out.push(header);
 
             if !data.is_empty() {
                 out.push(data);
             }
+            if let Some(crc) = crc {
+                out.push(Bytes::copy_from_slice(&crc.to_be_bytes()));
+            }
         }
         control_frame => {
             let mut buf = BytesMut::new();
@@ -173,9 +388,12 @@ fn encode_frame_segments(
     Ok(())
 }
 
+/// Write all of `buffers`, failing with `TimedOut` if any single write makes
+/// no progress within `write_timeout`
 async fn write_all_vectored<W: AsyncWrite + Unpin>(
     writer: &mut W,
     buffers: &[Bytes],
+    write_timeout: Duration,
 ) -> io::Result<()> {
     let mut index = 0;
     let mut offset = 0;
@@ -201,7 +419,14 @@ async fn write_all_vectored<W: AsyncWrite + Unpin>(
             break;
         }
 
-        let written = writer.write_vectored(&slices).await?;
+        let written = timeout(write_timeout, writer.write_vectored(&slices))
+            .await
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("peer stopped reading: no write progress in {write_timeout:?}"),
+                )
+            })??;
         if written == 0 {
             return Err(io::Error::new(
                 io::ErrorKind::WriteZero,
@@ -236,6 +461,10 @@ mod tests {
     use ferrotunnel_protocol::codec::TunnelCodec;
     use ferrotunnel_protocol::frame::StreamPriority;
     use kanal::bounded_async;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
     use tokio::io::duplex;
     use tokio::io::AsyncReadExt;
 
@@ -249,7 +478,7 @@ mod tests {
         let (writer, mut reader) = duplex(8192);
 
         tokio::spawn(async move {
-            run_batched_sender(rx, writer, TunnelCodec::new()).await;
+            let _ = run_batched_sender(rx, writer, TunnelCodec::new()).await;
         });
 
         tx.send(pf(
@@ -272,7 +501,7 @@ mod tests {
         let (writer, mut reader) = duplex(8192);
 
         tokio::spawn(async move {
-            run_batched_sender(rx, writer, TunnelCodec::new()).await;
+            let _ = run_batched_sender(rx, writer, TunnelCodec::new()).await;
         });
 
         for i in 0..5 {
@@ -297,7 +526,7 @@ mod tests {
         let (writer, mut reader) = duplex(65536);
 
         tokio::spawn(async move {
-            run_batched_sender(rx, writer, TunnelCodec::new()).await;
+            let _ = run_batched_sender(rx, writer, TunnelCodec::new()).await;
         });
 
         for i in 0..3 {
@@ -326,7 +555,7 @@ mod tests {
         let (writer, mut reader) = duplex(8192);
 
         tokio::spawn(async move {
-            run_batched_sender(rx, writer, TunnelCodec::new()).await;
+            let _ = run_batched_sender(rx, writer, TunnelCodec::new()).await;
         });
 
         let start = Instant::now();
@@ -353,4 +582,354 @@ mod tests {
 
         drop(tx);
     }
+
+    #[tokio::test]
+    async fn test_equal_priority_streams_interleave() {
+        use futures::StreamExt;
+        use tokio_util::codec::FramedRead;
+
+        let (tx, rx) = bounded_async::<PrioritizedFrame>(16);
+        let (writer, reader) = duplex(65536);
+
+        // Queue a burst from stream 1 followed by a burst from stream 3 so
+        // both land in the same batch
+        for stream_id in [1, 3] {
+            for _ in 0..4 {
+                tx.send(pf(
+                    StreamPriority::Normal,
+                    Frame::Data {
+                        stream_id,
+                        data: Bytes::from_static(b"chunk"),
+                        end_of_stream: false,
+                    },
+                ))
+                .await
+                .unwrap();
+            }
+        }
+        tx.send(pf(
+            StreamPriority::Critical,
+            Frame::Heartbeat { timestamp: 7 },
+        ))
+        .await
+        .unwrap();
+
+        tokio::spawn(async move {
+            let _ = run_batched_sender(rx, writer, TunnelCodec::new()).await;
+        });
+
+        let frames: Vec<Frame> = FramedRead::new(reader, TunnelCodec::new())
+            .take(9)
+            .map(io::Result::unwrap)
+            .collect()
+            .await;
+        drop(tx);
+        assert!(matches!(frames[0], Frame::Heartbeat { timestamp: 7 }));
+        let order: Vec<u32> = frames[1..]
+            .iter()
+            .map(|frame| match frame {
+                Frame::Data { stream_id, .. } => *stream_id,
+                other => panic!("unexpected frame {other:?}"),
+            })
+            .collect();
+        assert_eq!(order, [1, 3, 1, 3, 1, 3, 1, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_batched_sender_with_checksum() {
+        use futures::StreamExt;
+        use tokio_util::codec::FramedRead;
+
+        let (tx, rx) = bounded_async::<PrioritizedFrame>(10);
+        let (writer, reader) = duplex(8192);
+        let codec = TunnelCodec::new().with_checksum(true);
+
+        let sent = vec![
+            Frame::Heartbeat { timestamp: 5 },
+            Frame::Data {
+                stream_id: 3,
+                data: Bytes::from_static(b"payload"),
+                end_of_stream: true,
+            },
+            Frame::Data {
+                stream_id: 4,
+                data: Bytes::new(),
+                end_of_stream: true,
+            },
+        ];
+        for frame in &sent {
+            tx.send(pf(StreamPriority::Normal, frame.clone()))
+                .await
+                .unwrap();
+        }
+        tokio::spawn(async move {
+            let _ = run_batched_sender(rx, writer, codec).await;
+        });
+
+        // The decoder verifies every trailer
+        let received: Vec<Frame> = FramedRead::new(reader, codec)
+            .take(sent.len())
+            .map(io::Result::unwrap)
+            .collect()
+            .await;
+        drop(tx);
+        // Batches may be reordered across streams
+        assert_eq!(received.len(), sent.len());
+        assert!(sent.iter().all(|frame| received.contains(frame)));
+    }
+
+    #[tokio::test]
+    async fn test_batched_sender_with_compression() {
+        use ferrotunnel_protocol::FrameCompression;
+        use futures::StreamExt;
+        use tokio_util::codec::FramedRead;
+
+        for compression in FrameCompression::default_preference() {
+            let (tx, rx) = bounded_async::<PrioritizedFrame>(10);
+            let (writer, reader) = duplex(64 * 1024);
+            let codec = TunnelCodec::new()
+                .with_compression(Some(compression))
+                .with_checksum(true);
+            let payload = Bytes::from("compressible ".repeat(500));
+            tx.send(pf(
+                StreamPriority::Normal,
+                Frame::Data {
+                    stream_id: 3,
+                    data: payload.clone(),
+                    end_of_stream: true,
+                },
+            ))
+            .await
+            .unwrap();
+            drop(tx);
+            let _ = run_batched_sender(rx, writer, codec).await;
+
+            // Only the compressed payload went over the wire
+            let mut wire = Vec::new();
+            let mut reader = reader;
+            reader.read_to_end(&mut wire).await.unwrap();
+            assert!(wire.len() < payload.len(), "{}", compression.name());
+            let received: Vec<Frame> = FramedRead::new(wire.as_slice(), codec)
+                .map(io::Result::unwrap)
+                .collect()
+                .await;
+            assert_eq!(
+                received,
+                [Frame::Data {
+                    stream_id: 3,
+                    data: payload,
+                    end_of_stream: true,
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn test_schedule_batch_keeps_stream_order() {
+        let data = |stream_id, end_of_stream| Frame::Data {
+            stream_id,
+            data: Bytes::new(),
+            end_of_stream,
+        };
+        let mut frames = vec![
+            pf(StreamPriority::Normal, data(1, false)),
+            pf(StreamPriority::Normal, data(1, true)),
+            pf(StreamPriority::Low, data(5, false)),
+            pf(StreamPriority::Normal, data(3, true)),
+            pf(StreamPriority::High, data(7, false)),
+        ];
+        schedule_batch(&mut frames);
+
+        let order: Vec<(u32, bool)> = frames
+            .iter()
+            .map(|(_, frame)| match frame {
+                Frame::Data {
+                    stream_id,
+                    end_of_stream,
+                    ..
+                } => (*stream_id, *end_of_stream),
+                other => panic!("unexpected frame {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            order,
+            [(7, false), (1, false), (3, true), (1, true), (5, false)]
+        );
+    }
+
+    #[test]
+    fn test_frame_channel_capacity_clamped() {
+        assert_eq!(frame_channel_capacity(1024), 1024);
+        assert_eq!(frame_channel_capacity(100), 128);
+        assert_eq!(
+            frame_channel_capacity(MIN_FRAME_CHANNEL_CAPACITY),
+            MIN_FRAME_CHANNEL_CAPACITY
+        );
+        assert_eq!(frame_channel_capacity(0), MIN_FRAME_CHANNEL_CAPACITY);
+        assert_eq!(
+            frame_channel_capacity(MAX_FRAME_CHANNEL_CAPACITY + 1),
+            MAX_FRAME_CHANNEL_CAPACITY
+        );
+    }
+
+    #[tokio::test]
+    async fn test_full_frame_channel_applies_backpressure() {
+        let (tx, rx) = frame_channel(MIN_FRAME_CHANNEL_CAPACITY);
+        assert_eq!(tx.capacity(), MIN_FRAME_CHANNEL_CAPACITY);
+        let heartbeat = |timestamp| pf(StreamPriority::Normal, Frame::Heartbeat { timestamp });
+
+        for timestamp in 0..MIN_FRAME_CHANNEL_CAPACITY as u64 {
+            tx.send(heartbeat(timestamp)).await.unwrap();
+        }
+        // A full channel makes the sender wait instead of dropping the frame
+        let pending = tokio::spawn({
+            let tx = tx.clone();
+            async move { tx.send(heartbeat(MIN_FRAME_CHANNEL_CAPACITY as u64)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!pending.is_finished());
+
+        // Draining one frame lets the waiting sender through
+        rx.recv().await.unwrap();
+        pending.await.unwrap().unwrap();
+        drop(tx);
+
+        let mut received = Vec::new();
+        while let Ok((_, Frame::Heartbeat { timestamp })) = rx.recv().await {
+            received.push(timestamp);
+        }
+        let expected: Vec<u64> = (1..=MIN_FRAME_CHANNEL_CAPACITY as u64).collect();
+        assert_eq!(received, expected);
+    }
+    #[test]
+    fn test_adaptive_batch_stays_within_bounds() {
+        let mut batch = AdaptiveBatch::default();
+        for _ in 0..200 {
+            batch.record(batch.max_size);
+        }
+        assert_eq!(batch.max_size, MAX_BATCH_SIZE);
+        assert_eq!(
+            batch.timeout,
+            Duration::from_micros(MAX_BATCH_TIMEOUT_MICROS)
+        );
+
+        for _ in 0..500 {
+            batch.record(1);
+        }
+        assert_eq!(batch.max_size, MIN_BATCH_SIZE);
+        assert!(batch.timeout.is_zero());
+
+        // Saturation after idling restores batching
+        for _ in 0..50 {
+            batch.record(batch.max_size);
+        }
+        assert!(batch.max_size > MIN_BATCH_SIZE);
+        assert!(!batch.timeout.is_zero());
+    }
+
+    /// Writer taking every vectored write whole and counting the writes
+    #[derive(Clone, Default)]
+    struct CountingWriter {
+        writes: Arc<AtomicUsize>,
+    }
+
+    impl AsyncWrite for CountingWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.poll_write_vectored(cx, &[IoSlice::new(buf)])
+        }
+
+        fn poll_write_vectored(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<io::Result<usize>> {
+            let len = bufs.iter().map(|buf| buf.len()).sum();
+            self.writes.fetch_add(1, Ordering::SeqCst);
+            Poll::Ready(Ok(len))
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            true
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batches_grow_when_saturated_and_flush_singly_when_idle() {
+        const FRAMES: usize = 20_000;
+        let heartbeat = |timestamp| pf(StreamPriority::Normal, Frame::Heartbeat { timestamp });
+
+        // Saturating producer: the queue never runs dry
+        let (tx, rx) = bounded_async::<PrioritizedFrame>(FRAMES);
+        for timestamp in 0..FRAMES as u64 {
+            tx.send(heartbeat(timestamp)).await.unwrap();
+        }
+        drop(tx);
+        let writer = CountingWriter::default();
+        run_batched_sender(rx, writer.clone(), TunnelCodec::new())
+            .await
+            .unwrap();
+        let saturated_writes = writer.writes.load(Ordering::SeqCst);
+        assert!(
+            FRAMES / saturated_writes > DEFAULT_BATCH_SIZE,
+            "{saturated_writes} writes for {FRAMES} frames"
+        );
+
+        // Idle producer: every frame goes out on its own
+        let (tx, rx) = bounded_async::<PrioritizedFrame>(16);
+        let writer = CountingWriter::default();
+        let sender = tokio::spawn(run_batched_sender(rx, writer.clone(), TunnelCodec::new()));
+        for timestamp in 0..20 {
+            tx.send(heartbeat(timestamp)).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        drop(tx);
+        sender.await.unwrap().unwrap();
+        assert_eq!(writer.writes.load(Ordering::SeqCst), 20);
+    }
+
+    #[tokio::test]
+    async fn test_sender_exits_when_peer_stops_reading() {
+        let write_timeout = Duration::from_millis(100);
+        // The reader never consumes, so the small pipe fills up and stays full
+        let (writer, _reader) = duplex(1024);
+        let (tx, rx) = bounded_async::<PrioritizedFrame>(16);
+        let sender = tokio::spawn(run_batched_sender_with_interceptor(
+            rx,
+            writer,
+            TunnelCodec::new(),
+            None,
+            write_timeout,
+        ));
+
+        let data = pf(
+            StreamPriority::Normal,
+            Frame::Data {
+                stream_id: 1,
+                data: Bytes::from(vec![0u8; 64 * 1024]),
+                end_of_stream: false,
+            },
+        );
+        tx.send(data).await.unwrap();
+
+        let result = timeout(write_timeout * 10, sender)
+            .await
+            .expect("sender still blocked on a stalled write")
+            .unwrap();
+        assert!(matches!(result, Err(TunnelError::Timeout(_))), "{result:?}");
+        // Senders see the task gone instead of a silently full queue
+        let heartbeat = pf(StreamPriority::Normal, Frame::Heartbeat { timestamp: 0 });
+        assert!(tx.send(heartbeat).await.is_err());
+    }
 }
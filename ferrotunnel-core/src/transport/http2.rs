@@ -0,0 +1,334 @@
+//! HTTP/2 transport
+//!
+//! Carries the length-delimited [`Frame`](ferrotunnel_protocol::Frame) byte
+//! stream over a single long-lived, bidirectional HTTP/2 request stream, so
+//! tunnels can pass through networks that only allow HTTP/2 traffic. The
+//! client opens one `POST` to [`Http2TransportConfig::path`]; the server
+//! answers `200` immediately and both sides then exchange DATA frames.
+//!
+//! Runs over cleartext TCP (h2c, prior knowledge) or over TLS with ALPN `h2`.
+
+use super::socket_tuning::configure_socket_silent;
+use super::tls::{self, TlsTransportConfig};
+use super::BoxedStream;
+use bytes::Bytes;
+use h2::client::SendRequest;
+use h2::{RecvStream, SendStream};
+use http::{Method, Request, Response, StatusCode};
+use std::io::{self, ErrorKind};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tracing::debug;
+
+/// Default request path for the tunnel stream
+pub const DEFAULT_HTTP2_PATH: &str = "/ferrotunnel";
+
+/// ALPN protocol identifier for HTTP/2 over TLS
+pub const H2_ALPN: &[u8] = b"h2";
+
+/// Per-stream flow-control window; the tunnel uses a single stream, so it gets
+/// most of the connection window.
+const STREAM_WINDOW: u32 = 4 * 1024 * 1024;
+const CONNECTION_WINDOW: u32 = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Http2TransportConfig {
+    /// Request path of the tunnel stream (default: `/ferrotunnel`)
+    pub path: String,
+    /// Run HTTP/2 over TLS with ALPN `h2`; cleartext h2c when `None`
+    pub tls: Option<TlsTransportConfig>,
+}
+
+impl Default for Http2TransportConfig {
+    fn default() -> Self {
+        Self {
+            path: DEFAULT_HTTP2_PATH.to_string(),
+            tls: None,
+        }
+    }
+}
+
+fn h2_error(e: h2::Error) -> io::Error {
+    if e.is_io() {
+        e.into_io()
+            .unwrap_or_else(|| io::Error::other("HTTP/2 I/O error"))
+    } else {
+        io::Error::other(e)
+    }
+}
+
+/// Open the tunnel stream to `addr`
+pub async fn connect(addr: &str, config: &Http2TransportConfig) -> io::Result<BoxedStream> {
+    let tcp_stream = TcpStream::connect(addr).await?;
+    configure_socket_silent(&tcp_stream);
+    connect_over(tcp_stream, addr, config).await
+}
+
+/// Open the tunnel stream on an already connected (and tuned) socket to `addr`
+pub async fn connect_over(
+    tcp_stream: TcpStream,
+    addr: &str,
+    config: &Http2TransportConfig,
+) -> io::Result<BoxedStream> {
+    let io: BoxedStream = match &config.tls {
+        Some(tls_config) => {
+            Box::pin(tls::connect_over(tcp_stream, addr, tls_config, vec![H2_ALPN.to_vec()]).await?)
+        }
+        None => Box::pin(tcp_stream),
+    };
+
+    let (send_request, connection) = h2::client::Builder::new()
+        .initial_window_size(STREAM_WINDOW)
+        .initial_connection_window_size(CONNECTION_WINDOW)
+        .handshake::<_, Bytes>(io)
+        .await
+        .map_err(h2_error)?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            debug!("HTTP/2 transport connection closed: {}", e);
+        }
+    });
+
+    let scheme = if config.tls.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(format!("{scheme}://{addr}{}", config.path))
+        .body(())
+        .map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?;
+
+    let mut send_request = send_request.ready().await.map_err(h2_error)?;
+    let (response, send) = send_request
+        .send_request(request, false)
+        .map_err(h2_error)?;
+    let response = response.await.map_err(h2_error)?;
+    if response.status() != StatusCode::OK {
+        return Err(io::Error::new(
+            ErrorKind::ConnectionRefused,
+            format!("HTTP/2 tunnel request rejected: {}", response.status()),
+        ));
+    }
+
+    Ok(Box::pin(Http2Stream::new(
+        send,
+        response.into_body(),
+        Some(send_request),
+    )))
+}
+
+/// Complete the HTTP/2 handshake on an accepted connection and wait for the
+/// client's tunnel stream. Any further streams on the connection are refused.
+pub async fn accept<IO>(io: IO, config: &Http2TransportConfig) -> io::Result<BoxedStream>
+where
+    IO: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let mut connection = h2::server::Builder::new()
+        .initial_window_size(STREAM_WINDOW)
+        .initial_connection_window_size(CONNECTION_WINDOW)
+        .handshake::<_, Bytes>(io)
+        .await
+        .map_err(h2_error)?;
+
+    let (request, mut respond) = match connection.accept().await {
+        Some(result) => result.map_err(h2_error)?,
+        None => {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "HTTP/2 connection closed before opening a stream",
+            ))
+        }
+    };
+
+    if request.uri().path() != config.path {
+        let mut not_found = Response::new(());
+        *not_found.status_mut() = StatusCode::NOT_FOUND;
+        let _ = respond.send_response(not_found, true);
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("unexpected HTTP/2 tunnel path: {}", request.uri().path()),
+        ));
+    }
+
+    let send = respond
+        .send_response(Response::new(()), false)
+        .map_err(h2_error)?;
+
+    // Keep driving the connection (window updates, pings, GOAWAY)
+    tokio::spawn(async move {
+        while let Some(result) = connection.accept().await {
+            match result {
+                Ok((_, mut respond)) => respond.send_reset(h2::Reason::REFUSED_STREAM),
+                Err(e) => {
+                    debug!("HTTP/2 transport connection closed: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(Box::pin(Http2Stream::new(send, request.into_body(), None)))
+}
+
+/// `AsyncRead`/`AsyncWrite` over one HTTP/2 stream
+pub struct Http2Stream {
+    send: SendStream<Bytes>,
+    recv: RecvStream,
+    read_buf: Bytes,
+    /// Client-side request handle, held so the connection is not wound down
+    _client: Option<SendRequest<Bytes>>,
+}
+
+impl Http2Stream {
+    fn new(send: SendStream<Bytes>, recv: RecvStream, client: Option<SendRequest<Bytes>>) -> Self {
+        Self {
+            send,
+            recv,
+            read_buf: Bytes::new(),
+            _client: client,
+        }
+    }
+}
+
+impl AsyncRead for Http2Stream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        while self.read_buf.is_empty() {
+            match ready!(self.recv.poll_data(cx)) {
+                Some(Ok(data)) => {
+                    // Return the window as soon as the data is ours
+                    let _ = self.recv.flow_control().release_capacity(data.len());
+                    self.read_buf = data;
+                }
+                Some(Err(e)) => return Poll::Ready(Err(h2_error(e))),
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+
+        let n = self.read_buf.len().min(buf.remaining());
+        let chunk = self.read_buf.split_to(n);
+        buf.put_slice(&chunk);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for Http2Stream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        loop {
+            self.send.reserve_capacity(buf.len());
+            let capacity = self.send.capacity();
+            if capacity > 0 {
+                let n = capacity.min(buf.len());
+                self.send
+                    .send_data(Bytes::copy_from_slice(&buf[..n]), false)
+                    .map_err(h2_error)?;
+                return Poll::Ready(Ok(n));
+            }
+            match ready!(self.send.poll_capacity(cx)) {
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Poll::Ready(Err(h2_error(e))),
+                None => return Poll::Ready(Err(ErrorKind::BrokenPipe.into())),
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Queued DATA frames are written by the connection task
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Ignore errors from a stream that is already closed or reset
+        let _ = self.send.send_data(Bytes::new(), true);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_http2_stream_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let config = Http2TransportConfig::default();
+
+        let server_config = config.clone();
+        let server = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut stream = accept(tcp, &server_config).await.unwrap();
+            let mut buf = vec![0u8; 5];
+            stream.read_exact(&mut buf).await.unwrap();
+            stream.write_all(&buf).await.unwrap();
+            stream.write_all(b" back").await.unwrap();
+            stream.shutdown().await.unwrap();
+        });
+
+        let mut client = connect(&addr, &config).await.unwrap();
+        client.write_all(b"hello").await.unwrap();
+        let mut reply = Vec::new();
+        client.read_to_end(&mut reply).await.unwrap();
+        assert_eq!(reply, b"hello back");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http2_large_write_respects_flow_control() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let config = Http2TransportConfig::default();
+        let payload: Vec<u8> = (0..3 * STREAM_WINDOW)
+            .map(|i| u8::try_from(i % 251).unwrap())
+            .collect();
+        let expected = payload.clone();
+
+        let server_config = config.clone();
+        let server = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut stream = accept(tcp, &server_config).await.unwrap();
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).await.unwrap();
+            received
+        });
+
+        let mut client = connect(&addr, &config).await.unwrap();
+        client.write_all(&payload).await.unwrap();
+        client.shutdown().await.unwrap();
+        assert_eq!(server.await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_http2_wrong_path_rejected() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let server = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            accept(tcp, &Http2TransportConfig::default()).await
+        });
+
+        let config = Http2TransportConfig {
+            path: "/other".to_string(),
+            ..Default::default()
+        };
+        assert!(connect(&addr, &config).await.is_err());
+        assert!(server.await.unwrap().is_err());
+    }
+}
@@ -0,0 +1,357 @@
+//! Listener socket options: accept backlog, `SO_REUSEADDR`, `SO_REUSEPORT`,
+//! `IPV6_V6ONLY` and `TCP_FASTOPEN`
+//!
+//! With `SO_REUSEPORT`, several listeners can bind the same address and the
+//! kernel spreads incoming connections across them, so a server can run one
+//! accept loop per listener instead of funnelling every connection through a
+//! single task. `SO_REUSEPORT` is only available on Unix platforms other
+//! than Solaris, illumos and Cygwin.
+//!
+//! A listener on `[::]` can serve IPv4 clients too, which then appear as
+//! IPv4-mapped addresses (`::ffff:a.b.c.d`). Whether it does by default
+//! differs by platform: Linux and macOS accept both families unless
+//! configured otherwise, Windows accepts IPv6 only and OpenBSD cannot mix
+//! them at all. [`ListenerConfig::dual_stack`] sets `IPV6_V6ONLY` explicitly
+//! so the behaviour is the same everywhere.
+
+use socket2::SockRef;
+use std::io;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, TcpSocket};
+use tracing::warn;
+
+/// Accept backlog used by `TcpListener::bind`
+const DEFAULT_BACKLOG: u32 = 1024;
+
+/// Pending Fast Open requests a listener queues when Fast Open is enabled
+/// without an explicit queue length
+pub const DEFAULT_FAST_OPEN_QUEUE: u32 = 256;
+
+/// Whether this platform supports `SO_REUSEPORT`
+pub const REUSE_PORT_SUPPORTED: bool = cfg!(all(
+    unix,
+    not(any(
+        target_os = "solaris",
+        target_os = "illumos",
+        target_os = "cygwin"
+    ))
+));
+
+/// Whether this platform lets an IPv6 listener accept IPv4 connections
+pub const DUAL_STACK_SUPPORTED: bool = cfg!(not(target_os = "openbsd"));
+
+/// Socket options applied when binding a listener
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenerConfig {
+    /// Maximum queue of connections waiting to be accepted; the OS may cap
+    /// it, e.g. at `net.core.somaxconn` on Linux (default: 1024)
+    pub backlog: u32,
+    /// `SO_REUSEADDR`: rebind a port that still has connections in
+    /// `TIME_WAIT` (default: true on Unix, matching `TcpListener::bind`)
+    pub reuse_address: bool,
+    /// `SO_REUSEPORT`: let several listeners bind the same address
+    /// (default: false). Binding fails with
+    /// [`io::ErrorKind::Unsupported`] where the platform lacks it; see
+    /// [`REUSE_PORT_SUPPORTED`].
+    pub reuse_port: bool,
+    /// Listeners to bind on the address, each served by its own accept loop
+    /// (default: 1). More than one requires `reuse_port`.
+    pub accept_tasks: usize,
+    /// For IPv6 addresses, clear `IPV6_V6ONLY` so a listener on `[::]` also
+    /// accepts IPv4 connections; when false it accepts IPv6 only. Ignored
+    /// for IPv4 addresses (default: true where [`DUAL_STACK_SUPPORTED`])
+    pub dual_stack: bool,
+    /// `TCP_FASTOPEN`: issue Fast Open cookies and accept data in the SYN
+    /// from clients holding one, queueing at most this many such
+    /// connections before the handshake completes (default: off). Linux
+    /// only, and the kernel must allow server-side Fast Open
+    /// (`net.ipv4.tcp_fastopen` bit 2); elsewhere, or if the kernel refuses,
+    /// the listener is bound without it.
+    pub fast_open_queue: Option<u32>,
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        Self {
+            backlog: DEFAULT_BACKLOG,
+            reuse_address: cfg!(unix),
+            reuse_port: false,
+            accept_tasks: 1,
+            dual_stack: DUAL_STACK_SUPPORTED,
+            fast_open_queue: None,
+        }
+    }
+}
+
+impl ListenerConfig {
+    #[must_use]
+    pub fn with_backlog(mut self, backlog: u32) -> Self {
+        self.backlog = backlog;
+        self
+    }
+
+    #[must_use]
+    pub fn with_reuse_address(mut self, reuse_address: bool) -> Self {
+        self.reuse_address = reuse_address;
+        self
+    }
+
+    #[must_use]
+    pub fn with_reuse_port(mut self, reuse_port: bool) -> Self {
+        self.reuse_port = reuse_port;
+        self
+    }
+
+    #[must_use]
+    pub fn with_dual_stack(mut self, dual_stack: bool) -> Self {
+        self.dual_stack = dual_stack;
+        self
+    }
+
+    #[must_use]
+    pub fn with_fast_open_queue(mut self, queue: Option<u32>) -> Self {
+        self.fast_open_queue = queue;
+        self
+    }
+
+    /// Bind `accept_tasks` listeners sharing the address through
+    /// `SO_REUSEPORT`, which this enables
+    #[must_use]
+    pub fn with_accept_tasks(mut self, accept_tasks: usize) -> Self {
+        self.accept_tasks = accept_tasks.max(1);
+        if self.accept_tasks > 1 {
+            self.reuse_port = true;
+        }
+        self
+    }
+
+    /// Bind a single listener on `addr` with these options
+    pub fn bind(&self, addr: SocketAddr) -> io::Result<TcpListener> {
+        let socket = if addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            let socket = TcpSocket::new_v6()?;
+            SockRef::from(&socket).set_only_v6(!self.dual_stack)?;
+            socket
+        };
+        socket.set_reuseaddr(self.reuse_address)?;
+        if self.reuse_port {
+            set_reuse_port(&socket)?;
+        }
+        socket.bind(addr)?;
+        if let Some(queue) = self.fast_open_queue {
+            if let Err(e) = enable_fast_open(&socket, queue) {
+                warn!("TCP Fast Open unavailable on {}: {}", addr, e);
+            }
+        }
+        socket.listen(self.backlog)
+    }
+
+    /// Bind `accept_tasks` listeners on `addr`. When `addr` has port 0, the
+    /// port assigned to the first listener is reused for the others.
+    pub fn bind_all(&self, addr: SocketAddr) -> io::Result<Vec<TcpListener>> {
+        let count = self.accept_tasks.max(1);
+        if count > 1 && !self.reuse_port {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "multiple accept tasks require SO_REUSEPORT",
+            ));
+        }
+        let first = self.bind(addr)?;
+        let addr = first.local_addr()?;
+        let mut listeners = Vec::with_capacity(count);
+        listeners.push(first);
+        for _ in 1..count {
+            listeners.push(self.bind(addr)?);
+        }
+        Ok(listeners)
+    }
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))
+))]
+fn set_reuse_port(socket: &TcpSocket) -> io::Result<()> {
+    socket.set_reuseport(true)
+}
+
+#[cfg(not(all(
+    unix,
+    not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))
+)))]
+fn set_reuse_port(_socket: &TcpSocket) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "SO_REUSEPORT is not supported on this platform",
+    ))
+}
+
+/// `TCP_FASTOPEN`, which nix does not wrap
+#[cfg(target_os = "linux")]
+mod sockopt {
+    use nix::{getsockopt_impl, setsockopt_impl, sockopt_impl};
+
+    sockopt_impl!(
+        /// Queue length of pending Fast Open connections on a listener
+        TcpFastOpen,
+        Both,
+        libc::IPPROTO_TCP,
+        libc::TCP_FASTOPEN,
+        libc::c_int
+    );
+}
+
+#[cfg(target_os = "linux")]
+fn enable_fast_open(socket: &TcpSocket, queue: u32) -> io::Result<()> {
+    let queue = libc::c_int::try_from(queue).unwrap_or(libc::c_int::MAX);
+    nix::sys::socket::setsockopt(socket, sockopt::TcpFastOpen, &queue).map_err(io::Error::from)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn enable_fast_open(_socket: &TcpSocket, _queue: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "TCP Fast Open is only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use tokio::net::TcpStream;
+
+    fn localhost() -> SocketAddr {
+        "127.0.0.1:0".parse().unwrap()
+    }
+
+    /// Bind `[::]:0` with `config`, or `None` when the host has no IPv6
+    fn bind_unspecified_v6(config: &ListenerConfig) -> Option<TcpListener> {
+        config.bind("[::]:0".parse().unwrap()).ok()
+    }
+
+    /// Connect to `ip` on the listener's port and return the peer address
+    /// the listener saw
+    async fn accept_from(listener: &TcpListener, ip: IpAddr) -> io::Result<SocketAddr> {
+        let addr = SocketAddr::new(ip, listener.local_addr()?.port());
+        let (client, accepted) = tokio::join!(TcpStream::connect(addr), listener.accept());
+        client?;
+        Ok(accepted?.1)
+    }
+
+    #[tokio::test]
+    async fn test_default_options() {
+        let listener = ListenerConfig::default().bind(localhost()).unwrap();
+        let socket = SockRef::from(&listener);
+        assert_eq!(socket.reuse_address().unwrap(), cfg!(unix));
+        #[cfg(target_os = "linux")]
+        assert!(!socket.reuse_port().unwrap());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_fast_open_queue_set() {
+        use nix::sys::socket::getsockopt;
+
+        let plain = ListenerConfig::default().bind(localhost()).unwrap();
+        assert_eq!(getsockopt(&plain, sockopt::TcpFastOpen).unwrap(), 0);
+
+        let config = ListenerConfig::default().with_fast_open_queue(Some(64));
+        let listener = config.bind(localhost()).unwrap();
+        assert_eq!(getsockopt(&listener, sockopt::TcpFastOpen).unwrap(), 64);
+    }
+
+    #[tokio::test]
+    async fn test_multiple_accept_tasks_require_reuse_port() {
+        let config = ListenerConfig {
+            accept_tasks: 2,
+            ..Default::default()
+        };
+        let err = config.bind_all(localhost()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_reuse_port_listeners_share_address() {
+        let config = ListenerConfig::default()
+            .with_backlog(4096)
+            .with_accept_tasks(3);
+        let listeners = config.bind_all(localhost()).unwrap();
+        assert_eq!(listeners.len(), 3);
+
+        let addr = listeners[0].local_addr().unwrap();
+        assert_ne!(addr.port(), 0);
+        for listener in &listeners {
+            assert_eq!(listener.local_addr().unwrap(), addr);
+            let socket = SockRef::from(listener);
+            assert!(socket.reuse_port().unwrap());
+            assert!(socket.reuse_address().unwrap());
+        }
+
+        // Without SO_REUSEPORT the address is taken
+        let plain = ListenerConfig::default().with_reuse_address(false);
+        assert!(plain.bind(addr).is_err());
+
+        // The shared address accepts connections
+        for _ in 0..8 {
+            TcpStream::connect(addr).await.unwrap();
+        }
+    }
+
+    #[cfg(not(target_os = "openbsd"))]
+    #[tokio::test]
+    async fn test_dual_stack_accepts_both_families() {
+        let config = ListenerConfig::default();
+        assert!(config.dual_stack);
+        let Some(listener) = bind_unspecified_v6(&config) else {
+            return; // IPv6 unavailable
+        };
+        assert!(!SockRef::from(&listener).only_v6().unwrap());
+
+        let peer = accept_from(&listener, IpAddr::V6(Ipv6Addr::LOCALHOST))
+            .await
+            .unwrap();
+        assert_eq!(peer.ip(), Ipv6Addr::LOCALHOST);
+
+        // IPv4 clients arrive as IPv4-mapped addresses
+        let peer = accept_from(&listener, IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .await
+            .unwrap();
+        assert_eq!(peer.ip().to_canonical(), Ipv4Addr::LOCALHOST);
+
+        // Windows clients cannot dial a mapped address from an IPv6 socket
+        #[cfg(unix)]
+        {
+            let mapped = Ipv4Addr::LOCALHOST.to_ipv6_mapped();
+            let peer = accept_from(&listener, IpAddr::V6(mapped)).await.unwrap();
+            assert_eq!(peer.ip().to_canonical(), Ipv4Addr::LOCALHOST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ipv6_only_rejects_ipv4() {
+        let config = ListenerConfig::default().with_dual_stack(false);
+        let Some(listener) = bind_unspecified_v6(&config) else {
+            return; // IPv6 unavailable
+        };
+        assert!(SockRef::from(&listener).only_v6().unwrap());
+
+        let port = listener.local_addr().unwrap().port();
+        let v4 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+        assert!(TcpStream::connect(v4).await.is_err());
+        let peer = accept_from(&listener, IpAddr::V6(Ipv6Addr::LOCALHOST))
+            .await
+            .unwrap();
+        assert_eq!(peer.ip(), Ipv6Addr::LOCALHOST);
+    }
+
+    #[cfg(windows)]
+    #[tokio::test]
+    async fn test_reuse_port_unsupported() {
+        let config = ListenerConfig::default().with_reuse_port(true);
+        let err = config.bind(localhost()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+}
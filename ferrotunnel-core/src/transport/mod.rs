@@ -1,21 +1,29 @@
-//! Transport layer abstraction for TCP and TLS
+//! Transport layer abstraction for TCP, TLS, HTTP/2 and in-process pipes
 //!
-//! For a transport-agnostic frame API (QUIC/HTTP2 ready), see [`FrameSender`] and [`FrameReceiver`].
+//! For a transport-agnostic frame API (QUIC ready), see [`FrameSender`] and [`FrameReceiver`].
 
+use crate::ip_filter::IpFilter;
 use std::io;
 use std::net::SocketAddr;
 use std::pin::Pin;
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
+use tracing::debug;
 
 pub mod batched_sender;
 pub mod frame_transport;
+pub mod http2;
+pub mod listener;
+pub mod memory;
 pub mod socket_tuning;
 pub mod tcp;
 pub mod tcp_frame;
 pub mod tls;
 
 pub use frame_transport::{FrameConnectionSplit, FrameReceiver, FrameSender};
+pub use listener::ListenerConfig;
+pub use memory::MemoryTransport;
+pub use socket_tuning::SocketTuningConfig;
 pub use tcp_frame::{TcpFrameReceiver, TcpFrameSender};
 
 pub trait AsyncStream: AsyncRead + AsyncWrite + Send + Unpin {}
@@ -26,20 +34,43 @@ pub type BoxedStream = Pin<Box<dyn AsyncStream>>;
 
 /// Transport selection for the control connection.
 ///
-/// Future versions may extend this with `Quic(...)` (v0.4) or `Http2(...)` (v0.2);
-/// the same [`Frame`](ferrotunnel_protocol::Frame) protocol can run over any variant
-/// via [`FrameSender`] / [`FrameReceiver`].
+/// Future versions may extend this with `Quic(...)` (v0.4); the same
+/// [`Frame`](ferrotunnel_protocol::Frame) protocol runs unchanged over every
+/// variant, so capability negotiation does not depend on the transport.
 #[derive(Debug, Clone, Default)]
 pub enum TransportConfig {
     #[default]
     Tcp,
     Tls(tls::TlsTransportConfig),
+    /// A single long-lived HTTP/2 stream, over h2c or TLS
+    Http2(http2::Http2TransportConfig),
+    /// In-process pipes with no sockets; the server accepts from the same
+    /// [`MemoryTransport`] the client connects through
+    Memory(MemoryTransport),
 }
 
 pub async fn connect(config: &TransportConfig, addr: &str) -> io::Result<BoxedStream> {
+    connect_tuned(config, addr, &SocketTuningConfig::default()).await
+}
+
+/// Connect to `addr`, applying `tuning` to the underlying TCP socket
+pub async fn connect_tuned(
+    config: &TransportConfig,
+    addr: &str,
+    tuning: &SocketTuningConfig,
+) -> io::Result<BoxedStream> {
     match config {
-        TransportConfig::Tcp => tcp::connect(addr).await,
-        TransportConfig::Tls(tls_config) => tls::connect(addr, tls_config).await,
+        TransportConfig::Tcp => Ok(Box::pin(tcp::connect_tuned(addr, tuning).await?)),
+        TransportConfig::Tls(tls_config) => {
+            let tcp_stream = tcp::connect_tuned(addr, tuning).await?;
+            let tls_stream = tls::connect_over(tcp_stream, addr, tls_config, Vec::new()).await?;
+            Ok(Box::pin(tls_stream))
+        }
+        TransportConfig::Http2(h2_config) => {
+            let tcp_stream = tcp::connect_tuned(addr, tuning).await?;
+            http2::connect_over(tcp_stream, addr, h2_config).await
+        }
+        TransportConfig::Memory(memory) => memory.connect(),
     }
 }
 
@@ -47,14 +78,179 @@ pub async fn accept(
     config: &TransportConfig,
     listener: &TcpListener,
 ) -> io::Result<(BoxedStream, SocketAddr)> {
+    accept_tuned(config, listener, &SocketTuningConfig::default()).await
+}
+
+/// Accept a connection, applying `tuning` to the accepted TCP socket
+pub async fn accept_tuned(
+    config: &TransportConfig,
+    listener: &TcpListener,
+    tuning: &SocketTuningConfig,
+) -> io::Result<(BoxedStream, SocketAddr)> {
+    let (stream, addr, _) = accept_with_identity(config, listener, tuning).await?;
+    Ok((stream, addr))
+}
+
+/// Accept a connection like [`accept_tuned`], also returning the identity of
+/// the client certificate when the TLS handshake requested one
+pub async fn accept_with_identity(
+    config: &TransportConfig,
+    listener: &TcpListener,
+    tuning: &SocketTuningConfig,
+) -> io::Result<(BoxedStream, SocketAddr, Option<tls::PeerIdentity>)> {
     let (tcp_stream, addr) = listener.accept().await?;
-    socket_tuning::configure_socket_silent(&tcp_stream);
+    upgrade_accepted(config, tcp_stream, addr, tuning).await
+}
+
+/// Tune an accepted socket and run the server side of the transport handshake
+async fn upgrade_accepted(
+    config: &TransportConfig,
+    tcp_stream: TcpStream,
+    addr: SocketAddr,
+    tuning: &SocketTuningConfig,
+) -> io::Result<(BoxedStream, SocketAddr, Option<tls::PeerIdentity>)> {
+    tuning.apply_silent(&tcp_stream);
 
     match config {
-        TransportConfig::Tcp => Ok((Box::pin(tcp_stream), addr)),
+        TransportConfig::Tcp => Ok((Box::pin(tcp_stream), addr, None)),
         TransportConfig::Tls(tls_config) => {
             let tls_stream = tls::accept_tls(tcp_stream, tls_config).await?;
-            Ok((Box::pin(tls_stream), addr))
+            let identity = tls::peer_identity(&tls_stream);
+            Ok((Box::pin(tls_stream), addr, identity))
+        }
+        TransportConfig::Http2(h2_config) => match &h2_config.tls {
+            Some(tls_config) => {
+                let alpn = vec![http2::H2_ALPN.to_vec()];
+                let tls_stream = tls::accept_tls_with_alpn(tcp_stream, tls_config, alpn).await?;
+                let identity = tls::peer_identity(&tls_stream);
+                Ok((http2::accept(tls_stream, h2_config).await?, addr, identity))
+            }
+            None => Ok((http2::accept(tcp_stream, h2_config).await?, addr, None)),
+        },
+        TransportConfig::Memory(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "in-memory transport does not accept TCP connections",
+        )),
+    }
+}
+
+/// Server-side listener for a [`TransportConfig`]
+#[derive(Debug)]
+pub enum TransportListener {
+    Tcp(TcpListener),
+    /// In-memory transports accept from their [`MemoryTransport`]; no socket
+    /// is bound
+    Memory(MemoryTransport),
+}
+
+impl TransportListener {
+    /// Bind `addr` for socket transports, or attach to the in-memory endpoint
+    pub async fn bind(config: &TransportConfig, addr: SocketAddr) -> io::Result<Self> {
+        match config {
+            TransportConfig::Memory(memory) => Ok(Self::Memory(memory.clone())),
+            _ => Ok(Self::Tcp(TcpListener::bind(addr).await?)),
+        }
+    }
+
+    /// Bind `addr` with `options` for socket transports, one listener per
+    /// accept task, or attach to the in-memory endpoint
+    pub fn bind_all(
+        config: &TransportConfig,
+        addr: SocketAddr,
+        options: &ListenerConfig,
+    ) -> io::Result<Vec<Self>> {
+        match config {
+            TransportConfig::Memory(memory) => Ok(vec![Self::Memory(memory.clone())]),
+            _ => Ok(options.bind_all(addr)?.into_iter().map(Self::Tcp).collect()),
+        }
+    }
+
+    /// Accept the next connection, applying `tuning` to TCP sockets. The
+    /// identity is set when the client presented a TLS certificate.
+    pub async fn accept(
+        &self,
+        config: &TransportConfig,
+        tuning: &SocketTuningConfig,
+    ) -> io::Result<(BoxedStream, SocketAddr, Option<tls::PeerIdentity>)> {
+        self.accept_filtered(config, tuning, &IpFilter::default())
+            .await
+    }
+
+    /// Accept the next connection from a peer that `filter` allows. Other
+    /// peers are dropped right after the TCP accept, before any TLS or
+    /// HTTP/2 handshake.
+    pub async fn accept_filtered(
+        &self,
+        config: &TransportConfig,
+        tuning: &SocketTuningConfig,
+        filter: &IpFilter,
+    ) -> io::Result<(BoxedStream, SocketAddr, Option<tls::PeerIdentity>)> {
+        self.accept_connection(filter)
+            .await?
+            .upgrade(config, tuning)
+            .await
+    }
+
+    /// Accept the next connection from a peer that `filter` allows without
+    /// running the transport handshake, so the caller can run it elsewhere
+    /// and a slow peer does not hold up the next accept
+    pub async fn accept_connection(&self, filter: &IpFilter) -> io::Result<AcceptedConnection> {
+        loop {
+            let accepted = match self {
+                Self::Tcp(listener) => {
+                    let (tcp_stream, addr) = listener.accept().await?;
+                    AcceptedConnection::Tcp(tcp_stream, addr)
+                }
+                Self::Memory(memory) => {
+                    let (stream, addr) = memory.accept().await?;
+                    AcceptedConnection::Memory(stream, addr)
+                }
+            };
+            if filter.is_allowed(accepted.peer_addr().ip()) {
+                return Ok(accepted);
+            }
+            debug!(
+                "Dropped connection from {}: rejected by IP filter",
+                accepted.peer_addr()
+            );
+        }
+    }
+}
+
+/// A connection taken from a [`TransportListener`] before its transport
+/// handshake; see [`AcceptedConnection::upgrade`]
+pub enum AcceptedConnection {
+    Tcp(TcpStream, SocketAddr),
+    Memory(BoxedStream, SocketAddr),
+}
+
+impl std::fmt::Debug for AcceptedConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp(_, addr) => f.debug_tuple("Tcp").field(addr).finish(),
+            Self::Memory(_, addr) => f.debug_tuple("Memory").field(addr).finish(),
+        }
+    }
+}
+
+impl AcceptedConnection {
+    pub fn peer_addr(&self) -> SocketAddr {
+        match self {
+            Self::Tcp(_, addr) | Self::Memory(_, addr) => *addr,
+        }
+    }
+
+    /// Apply `tuning` and run the server side of `config`'s handshake (TLS,
+    /// HTTP/2). The identity is set when the client presented a TLS
+    /// certificate.
+    pub async fn upgrade(
+        self,
+        config: &TransportConfig,
+        tuning: &SocketTuningConfig,
+    ) -> io::Result<(BoxedStream, SocketAddr, Option<tls::PeerIdentity>)> {
+        match self {
+            Self::Tcp(tcp_stream, addr) => upgrade_accepted(config, tcp_stream, addr, tuning).await,
+            Self::Memory(stream, addr) => Ok((stream, addr, None)),
         }
     }
 }
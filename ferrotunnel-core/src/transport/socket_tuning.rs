@@ -4,6 +4,13 @@
 //! - `TCP_NODELAY`: Disable Nagle's algorithm for lower latency
 //! - Increased buffer sizes: Better throughput for sustained traffic
 //! - TCP keepalive: Detect dead connections faster
+//! - `TCP_USER_TIMEOUT` (Linux): Bound how long unacknowledged data may linger
+//! - TCP Fast Open (Linux, opt-in): Send the first data in the SYN on connect
+//!
+//! Keepalive and user-timeout settings are configurable through
+//! [`SocketTuningConfig`] so operators can choose how quickly half-open
+//! control connections (e.g. behind a NAT that dropped its mapping) are
+//! detected by the OS.
 
 use socket2::SockRef;
 use std::io;
@@ -15,22 +22,154 @@ const SEND_BUFFER_SIZE: usize = 1024 * 1024;
 const KEEPALIVE_TIME: Duration = Duration::from_secs(30);
 const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
 
-pub fn configure_socket(stream: &TcpStream) -> io::Result<()> {
-    stream.set_nodelay(true)?;
+/// TCP options applied to tunnel connections
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketTuningConfig {
+    /// Disable Nagle's algorithm (default: true)
+    pub nodelay: bool,
+    /// Idle time before the first keepalive probe; keepalive is off when
+    /// `None` (default: 30s)
+    pub keepalive_idle: Option<Duration>,
+    /// Time between unanswered keepalive probes (default: 10s)
+    pub keepalive_interval: Option<Duration>,
+    /// Unanswered probes before the connection is dropped (default: OS
+    /// setting). Ignored on Windows.
+    pub keepalive_retries: Option<u32>,
+    /// `TCP_USER_TIMEOUT`: how long sent data may stay unacknowledged before
+    /// the connection is dropped (default: OS setting). Linux and Android only.
+    pub user_timeout: Option<Duration>,
+    /// Connect with `TCP_FASTOPEN_CONNECT`, so the first write (the tunnel
+    /// handshake) rides in the SYN once the server has issued a cookie,
+    /// saving a round trip on reconnects (default: false). Linux only;
+    /// elsewhere, or if the kernel refuses, connections open normally.
+    ///
+    /// Once a cookie is cached, a connect completes before the TCP handshake
+    /// does, so a refused connection is only reported by the first read or
+    /// write.
+    pub tcp_fast_open: bool,
+}
+
+impl Default for SocketTuningConfig {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive_idle: Some(KEEPALIVE_TIME),
+            keepalive_interval: Some(KEEPALIVE_INTERVAL),
+            keepalive_retries: None,
+            user_timeout: None,
+            tcp_fast_open: false,
+        }
+    }
+}
 
-    let socket = SockRef::from(stream);
+impl SocketTuningConfig {
+    /// Apply these options (and the larger socket buffers) to `stream`
+    pub fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
 
-    socket.set_recv_buffer_size(RECV_BUFFER_SIZE)?;
-    socket.set_send_buffer_size(SEND_BUFFER_SIZE)?;
+        let socket = SockRef::from(stream);
 
-    let keepalive = socket2::TcpKeepalive::new()
-        .with_time(KEEPALIVE_TIME)
-        .with_interval(KEEPALIVE_INTERVAL);
-    socket.set_tcp_keepalive(&keepalive)?;
+        socket.set_recv_buffer_size(RECV_BUFFER_SIZE)?;
+        socket.set_send_buffer_size(SEND_BUFFER_SIZE)?;
 
-    Ok(())
+        if let Some(idle) = self.keepalive_idle {
+            let mut keepalive = socket2::TcpKeepalive::new().with_time(idle);
+            if let Some(interval) = self.keepalive_interval {
+                keepalive = keepalive.with_interval(interval);
+            }
+            #[cfg(not(windows))]
+            if let Some(retries) = self.keepalive_retries {
+                keepalive = keepalive.with_retries(retries);
+            }
+            socket.set_tcp_keepalive(&keepalive)?;
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if let Some(timeout) = self.user_timeout {
+            socket.set_tcp_user_timeout(Some(timeout))?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply these options, ignoring failures (tuning is best effort)
+    pub fn apply_silent(&self, stream: &TcpStream) {
+        let _ = self.apply(stream);
+    }
+}
+
+pub fn configure_socket(stream: &TcpStream) -> io::Result<()> {
+    SocketTuningConfig::default().apply(stream)
 }
 
 pub fn configure_socket_silent(stream: &TcpStream) {
     let _ = configure_socket(stream);
 }
+
+#[cfg(test)]
+#[cfg(target_os = "linux")]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    async fn connected_stream() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = TcpStream::connect(addr).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (client, server)
+    }
+
+    #[tokio::test]
+    async fn test_default_tuning() {
+        let (stream, _peer) = connected_stream().await;
+        configure_socket(&stream).unwrap();
+
+        let socket = SockRef::from(&stream);
+        assert!(stream.nodelay().unwrap());
+        assert!(socket.keepalive().unwrap());
+        assert_eq!(socket.tcp_keepalive_time().unwrap(), KEEPALIVE_TIME);
+        assert_eq!(socket.tcp_keepalive_interval().unwrap(), KEEPALIVE_INTERVAL);
+    }
+
+    #[tokio::test]
+    async fn test_custom_tuning() {
+        let (stream, _peer) = connected_stream().await;
+        let config = SocketTuningConfig {
+            nodelay: false,
+            keepalive_idle: Some(Duration::from_secs(15)),
+            keepalive_interval: Some(Duration::from_secs(5)),
+            keepalive_retries: Some(3),
+            user_timeout: Some(Duration::from_secs(20)),
+            tcp_fast_open: false,
+        };
+        config.apply(&stream).unwrap();
+
+        let socket = SockRef::from(&stream);
+        assert!(!stream.nodelay().unwrap());
+        assert_eq!(
+            socket.tcp_keepalive_time().unwrap(),
+            Duration::from_secs(15)
+        );
+        assert_eq!(
+            socket.tcp_keepalive_interval().unwrap(),
+            Duration::from_secs(5)
+        );
+        assert_eq!(socket.tcp_keepalive_retries().unwrap(), 3);
+        assert_eq!(
+            socket.tcp_user_timeout().unwrap(),
+            Some(Duration::from_secs(20))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_disabled() {
+        let (stream, _peer) = connected_stream().await;
+        let config = SocketTuningConfig {
+            keepalive_idle: None,
+            ..Default::default()
+        };
+        config.apply(&stream).unwrap();
+        assert!(!SockRef::from(&stream).keepalive().unwrap());
+    }
+}
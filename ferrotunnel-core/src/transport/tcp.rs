@@ -1,11 +1,12 @@
 //! Plain TCP transport
 
-use super::socket_tuning::configure_socket_silent;
+use super::socket_tuning::{configure_socket_silent, SocketTuningConfig};
 use super::BoxedStream;
 use ferrotunnel_common::Result;
 use std::io;
 use std::net::SocketAddr;
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use tracing::debug;
 
 pub struct TcpTransport;
 
@@ -27,3 +28,120 @@ pub async fn connect(addr: &str) -> io::Result<BoxedStream> {
     configure_socket_silent(&stream);
     Ok(Box::pin(stream))
 }
+
+/// Connect and apply `tuning` to the socket
+pub async fn connect_tuned(addr: &str, tuning: &SocketTuningConfig) -> io::Result<TcpStream> {
+    let stream = if tuning.tcp_fast_open {
+        connect_fast_open(addr).await?
+    } else {
+        TcpStream::connect(addr).await?
+    };
+    tuning.apply_silent(&stream);
+    Ok(stream)
+}
+
+/// Connect to the first address of `addr` that accepts, with TCP Fast Open
+/// enabled where the platform supports it
+async fn connect_fast_open(addr: &str) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for target in tokio::net::lookup_host(addr).await? {
+        let socket = if target.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        if let Err(e) = enable_fast_open_connect(&socket) {
+            debug!("TCP Fast Open unavailable, connecting normally: {}", e);
+        }
+        match socket.connect(target).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{addr} resolved to no addresses"),
+        )
+    }))
+}
+
+/// Defer the SYN until the first write and carry that data in it; the
+/// kernel falls back to a regular handshake when it has no cookie or the
+/// server does not support Fast Open
+#[cfg(target_os = "linux")]
+fn enable_fast_open_connect(socket: &TcpSocket) -> io::Result<()> {
+    use nix::sys::socket::{setsockopt, sockopt::TcpFastOpenConnect};
+    setsockopt(socket, TcpFastOpenConnect, &true).map_err(io::Error::from)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn enable_fast_open_connect(_socket: &TcpSocket) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "TCP Fast Open is only supported on Linux",
+    ))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use nix::sys::socket::{getsockopt, sockopt::TcpFastOpenConnect};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn fast_open_tuning() -> SocketTuningConfig {
+        SocketTuningConfig {
+            tcp_fast_open: true,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fast_open_option_set() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let stream = connect_tuned(&addr, &fast_open_tuning()).await.unwrap();
+        assert!(getsockopt(&stream, TcpFastOpenConnect).unwrap());
+
+        let plain = connect_tuned(&addr, &SocketTuningConfig::default())
+            .await
+            .unwrap();
+        assert!(!getsockopt(&plain, TcpFastOpenConnect).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_fast_open_connects_to_peer_without_fast_open() {
+        // A plain listener never enables TCP_FASTOPEN, so no cookie is issued
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut conn, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 5];
+            conn.read_exact(&mut buf).await.unwrap();
+            conn.write_all(&buf).await.unwrap();
+        });
+
+        let mut stream = connect_tuned(&addr, &fast_open_tuning()).await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        let mut echoed = [0u8; 5];
+        stream.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(&echoed, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_fast_open_refused_connection_fails() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        // Depending on the kernel the refusal shows at connect or first write
+        if let Ok(mut stream) = connect_tuned(&addr, &fast_open_tuning()).await {
+            let written = stream.write_all(b"hello").await;
+            let mut buf = [0u8; 1];
+            let read = stream.read(&mut buf).await;
+            assert!(written.is_err() || read.is_err() || read.unwrap() == 0);
+        }
+    }
+}
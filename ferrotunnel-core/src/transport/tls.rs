@@ -2,16 +2,58 @@
 
 use super::socket_tuning::configure_socket_silent;
 use super::BoxedStream;
+use rustls::client::{ClientSessionMemoryCache, Resumption};
 use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::ServerSessionMemoryCache;
 use rustls::{ClientConfig, RootCertStore, ServerConfig};
 use rustls_pki_types::pem::PemObject;
+use std::fmt;
 use std::io::{self, ErrorKind};
+use std::net::IpAddr;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use tokio::net::TcpStream;
 use tokio_rustls::{TlsAcceptor, TlsConnector};
+use x509_parser::certificate::X509Certificate;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::FromDer;
+
+/// Number of sessions kept by each side of a [`TlsSessionCache`]
+const SESSION_CACHE_SIZE: usize = 256;
+
+/// TLS session state shared by a [`TlsTransportConfig`] and all of its clones
+///
+/// Connectors and acceptors are rebuilt for every connection, so the session
+/// stores live here instead of in the rustls configs; otherwise a reconnect
+/// would never find the ticket from the previous connection.
+#[derive(Clone)]
+pub struct TlsSessionCache {
+    client: Arc<ClientSessionMemoryCache>,
+    server: Arc<ServerSessionMemoryCache>,
+    /// Client config built for the first connection. rustls only resumes a
+    /// session with the certificate verifier that stored it, so later
+    /// connections reuse this config instead of building their own.
+    client_config: Arc<OnceLock<Arc<ClientConfig>>>,
+}
+
+impl Default for TlsSessionCache {
+    fn default() -> Self {
+        Self {
+            client: Arc::new(ClientSessionMemoryCache::new(SESSION_CACHE_SIZE)),
+            server: ServerSessionMemoryCache::new(SESSION_CACHE_SIZE),
+            client_config: Arc::new(OnceLock::new()),
+        }
+    }
+}
+
+impl fmt::Debug for TlsSessionCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsSessionCache").finish_non_exhaustive()
+    }
+}
 
 #[derive(Debug, Clone, Default)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct TlsTransportConfig {
     pub ca_cert_path: Option<String>,
     pub cert_path: String,
@@ -20,6 +62,28 @@ pub struct TlsTransportConfig {
     pub client_auth: bool,
     /// Skip certificate verification (insecure, for self-signed certs)
     pub skip_verify: bool,
+    /// Resume earlier TLS sessions on reconnect instead of running a full
+    /// handshake (disabled by default)
+    pub session_resumption: bool,
+    /// Send the first bytes of a resumed client connection as TLS 1.3 0-RTT
+    /// early data (disabled by default; requires `session_resumption`)
+    ///
+    /// Early data is not protected against replay: an attacker who captures it
+    /// can resend it to the server, so anything sent in 0-RTT must be safe to
+    /// process twice. The tunnel handshake frame carries the auth token, so the
+    /// ferrotunnel server never accepts early data itself and a client talking
+    /// to it falls back to sending after the handshake. Only enable this when
+    /// the peer is a TLS terminator that applies its own replay protection.
+    pub early_data: bool,
+    /// ALPN protocol ids to offer (client) or accept (server), e.g.
+    /// `ferrotunnel/1`; no ALPN when empty (default)
+    ///
+    /// A server with ALPN configured rejects clients that do not negotiate
+    /// one of these protocols, so tunnel and web traffic can share a port
+    /// behind an ALPN-aware proxy.
+    pub alpn_protocols: Vec<String>,
+    /// Session state shared across connections made with this config
+    pub session_cache: TlsSessionCache,
 }
 
 impl TlsTransportConfig {
@@ -47,8 +111,32 @@ impl TlsTransportConfig {
             server_name: config.server_name.clone(),
             client_auth: config.client_auth,
             skip_verify: false,
+            session_resumption: false,
+            early_data: false,
+            alpn_protocols: Vec::new(),
+            session_cache: TlsSessionCache::default(),
         })
     }
+
+    /// Negotiate one of `protocols` through ALPN during the handshake
+    #[must_use]
+    pub fn alpn(mut self, protocols: Vec<String>) -> Self {
+        self.alpn_protocols = protocols;
+        self
+    }
+
+    /// ALPN ids to use for a handshake; `explicit` ids (e.g. `h2` for the
+    /// HTTP/2 transport) take precedence over the configured ones
+    fn effective_alpn(&self, explicit: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        if explicit.is_empty() {
+            self.alpn_protocols
+                .iter()
+                .map(|p| p.as_bytes().to_vec())
+                .collect()
+        } else {
+            explicit
+        }
+    }
 }
 
 fn load_certs(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
@@ -113,6 +201,18 @@ impl rustls::client::danger::ServerCertVerifier for InsecureServerCertVerifier {
 }
 
 pub fn create_client_config(config: &TlsTransportConfig) -> io::Result<Arc<ClientConfig>> {
+    if !config.session_resumption {
+        return build_client_config(config);
+    }
+    let cache = &config.session_cache.client_config;
+    if let Some(client_config) = cache.get() {
+        return Ok(client_config.clone());
+    }
+    let client_config = build_client_config(config)?;
+    Ok(cache.get_or_init(|| client_config).clone())
+}
+
+fn build_client_config(config: &TlsTransportConfig) -> io::Result<Arc<ClientConfig>> {
     let builder = ClientConfig::builder();
 
     let builder = if config.skip_verify {
@@ -137,7 +237,7 @@ pub fn create_client_config(config: &TlsTransportConfig) -> io::Result<Arc<Clien
         builder.with_root_certificates(root_store)
     };
 
-    let client_config = if !config.cert_path.is_empty() && !config.key_path.is_empty() {
+    let mut client_config = if !config.cert_path.is_empty() && !config.key_path.is_empty() {
         let certs = load_certs(Path::new(&config.cert_path))?;
         let key = load_private_key(Path::new(&config.key_path))?;
         builder
@@ -147,6 +247,13 @@ pub fn create_client_config(config: &TlsTransportConfig) -> io::Result<Arc<Clien
         builder.with_no_client_auth()
     };
 
+    if config.session_resumption {
+        client_config.resumption = Resumption::store(config.session_cache.client.clone());
+        client_config.enable_early_data = config.early_data;
+    } else {
+        client_config.resumption = Resumption::disabled();
+    }
+
     Ok(Arc::new(client_config))
 }
 
@@ -156,7 +263,7 @@ pub fn create_server_config(config: &TlsTransportConfig) -> io::Result<Arc<Serve
 
     let builder = ServerConfig::builder();
 
-    let server_config = if config.client_auth {
+    let mut server_config = if config.client_auth {
         let mut root_store = RootCertStore::empty();
         if let Some(ca_path) = &config.ca_cert_path {
             let ca_certs = load_certs(Path::new(ca_path))?;
@@ -186,15 +293,56 @@ pub fn create_server_config(config: &TlsTransportConfig) -> io::Result<Arc<Serve
             .map_err(|e| io::Error::new(ErrorKind::InvalidData, format!("TLS config error: {e}")))?
     };
 
+    // Early data stays rejected (`max_early_data_size` of 0): a replayed
+    // handshake frame must not be able to open a session.
+    if config.session_resumption {
+        server_config.session_storage = config.session_cache.server.clone();
+    }
+
     Ok(Arc::new(server_config))
 }
 
 pub async fn connect(addr: &str, config: &TlsTransportConfig) -> io::Result<BoxedStream> {
-    let client_config = create_client_config(config)?;
-    let connector = TlsConnector::from(client_config);
+    connect_with_alpn(addr, config, Vec::new()).await
+}
+
+/// Connect over TLS, offering `alpn_protocols` during the handshake
+pub async fn connect_with_alpn(
+    addr: &str,
+    config: &TlsTransportConfig,
+    alpn_protocols: Vec<Vec<u8>>,
+) -> io::Result<BoxedStream> {
+    let tls_stream = connect_tls(addr, config, alpn_protocols).await?;
+    Ok(Box::pin(tls_stream))
+}
 
+/// Connect over TLS and return the rustls stream, e.g. to inspect how the
+/// handshake went
+pub async fn connect_tls(
+    addr: &str,
+    config: &TlsTransportConfig,
+    alpn_protocols: Vec<Vec<u8>>,
+) -> io::Result<tokio_rustls::client::TlsStream<TcpStream>> {
     let tcp_stream = TcpStream::connect(addr).await?;
     configure_socket_silent(&tcp_stream);
+    connect_over(tcp_stream, addr, config, alpn_protocols).await
+}
+
+/// Run the client TLS handshake on an already connected (and tuned) socket
+/// to `addr`
+pub async fn connect_over(
+    tcp_stream: TcpStream,
+    addr: &str,
+    config: &TlsTransportConfig,
+    alpn_protocols: Vec<Vec<u8>>,
+) -> io::Result<tokio_rustls::client::TlsStream<TcpStream>> {
+    let mut client_config = create_client_config(config)?;
+    let alpn_protocols = config.effective_alpn(alpn_protocols);
+    if !alpn_protocols.is_empty() {
+        Arc::make_mut(&mut client_config).alpn_protocols = alpn_protocols;
+    }
+    let connector = TlsConnector::from(client_config)
+        .early_data(config.session_resumption && config.early_data);
 
     let server_name = if let Some(name) = &config.server_name {
         ServerName::try_from(name.clone()).map_err(|e| {
@@ -210,15 +358,118 @@ pub async fn connect(addr: &str, config: &TlsTransportConfig) -> io::Result<Boxe
         })?
     };
 
-    let tls_stream = connector.connect(server_name, tcp_stream).await?;
-    Ok(Box::pin(tls_stream))
+    connector.connect(server_name, tcp_stream).await
 }
 
 pub async fn accept_tls(
     tcp_stream: TcpStream,
     config: &TlsTransportConfig,
 ) -> io::Result<tokio_rustls::server::TlsStream<TcpStream>> {
-    let server_config = create_server_config(config)?;
+    accept_tls_with_alpn(tcp_stream, config, Vec::new()).await
+}
+
+/// Accept a TLS connection, advertising `alpn_protocols` during the handshake
+pub async fn accept_tls_with_alpn(
+    tcp_stream: TcpStream,
+    config: &TlsTransportConfig,
+    alpn_protocols: Vec<Vec<u8>>,
+) -> io::Result<tokio_rustls::server::TlsStream<TcpStream>> {
+    let mut server_config = create_server_config(config)?;
+    let alpn_protocols = config.effective_alpn(alpn_protocols);
+    if !alpn_protocols.is_empty() {
+        Arc::make_mut(&mut server_config)
+            .alpn_protocols
+            .clone_from(&alpn_protocols);
+    }
     let acceptor = TlsAcceptor::from(server_config);
-    acceptor.accept(tcp_stream).await
+    let tls_stream = acceptor.accept(tcp_stream).await?;
+
+    // rustls already refuses clients offering only other protocols; clients
+    // offering no ALPN at all get through the handshake and are refused here
+    if !config.alpn_protocols.is_empty() {
+        let negotiated = tls_stream.get_ref().1.alpn_protocol();
+        let matched = negotiated.is_some_and(|p| alpn_protocols.iter().any(|a| a == p));
+        if !matched {
+            return Err(io::Error::new(
+                ErrorKind::PermissionDenied,
+                format!(
+                    "client did not negotiate ALPN protocol {:?}",
+                    config.alpn_protocols
+                ),
+            ));
+        }
+    }
+    Ok(tls_stream)
+}
+
+/// Identity taken from the certificate a client presented during the TLS
+/// handshake (mutual TLS)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerIdentity {
+    /// Full subject distinguished name, e.g. `CN=agent-1, O=Example`
+    pub subject: String,
+    /// Subject common name, if present
+    pub common_name: Option<String>,
+    /// DNS names, email addresses, URIs and IP addresses from the subject
+    /// alternative name extension
+    pub sans: Vec<String>,
+}
+
+impl PeerIdentity {
+    /// Parse the identity out of a DER-encoded certificate
+    pub fn from_der(der: &[u8]) -> Option<Self> {
+        let (_, cert) = X509Certificate::from_der(der).ok()?;
+        let common_name = cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(str::to_string);
+        let sans = match cert.subject_alternative_name() {
+            Ok(Some(ext)) => ext
+                .value
+                .general_names
+                .iter()
+                .filter_map(general_name_to_string)
+                .collect(),
+            _ => Vec::new(),
+        };
+        Some(Self {
+            subject: cert.subject().to_string(),
+            common_name,
+            sans,
+        })
+    }
+
+    /// The common name followed by every subject alternative name
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.common_name
+            .as_deref()
+            .into_iter()
+            .chain(self.sans.iter().map(String::as_str))
+    }
+}
+
+fn general_name_to_string(name: &GeneralName<'_>) -> Option<String> {
+    match name {
+        GeneralName::DNSName(s) | GeneralName::RFC822Name(s) | GeneralName::URI(s) => {
+            Some((*s).to_string())
+        }
+        GeneralName::IPAddress(bytes) => match bytes.len() {
+            4 => <[u8; 4]>::try_from(*bytes)
+                .ok()
+                .map(|b| IpAddr::from(b).to_string()),
+            16 => <[u8; 16]>::try_from(*bytes)
+                .ok()
+                .map(|b| IpAddr::from(b).to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Identity of the client certificate presented on `stream`, if any
+pub fn peer_identity<IO>(stream: &tokio_rustls::server::TlsStream<IO>) -> Option<PeerIdentity> {
+    let certs = stream.get_ref().1.peer_certificates()?;
+    PeerIdentity::from_der(certs.first()?.as_ref())
 }
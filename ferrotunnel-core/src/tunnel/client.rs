@@ -1,27 +1,216 @@
-use crate::auth::validate_token_format;
-use crate::stream::{Multiplexer, PrioritizedFrame, VirtualStream};
-use crate::transport::batched_sender::run_batched_sender;
-use crate::transport::{self, TransportConfig};
-use crate::tunnel::common::clamp_u128_to_u64;
+use crate::auth::{challenge_response, validate_token_format, MAX_AUTHENTICATOR_TOKEN_LEN};
+use crate::interceptor::{self, FrameInterceptor, SharedFrameInterceptor};
+use crate::recorder::FrameRecorder;
+use crate::stream::flow_control::{self, DEFAULT_STREAM_WINDOW};
+use crate::stream::{Multiplexer, TrafficCounters, VirtualStream};
+use crate::transport::batched_sender::{
+    frame_channel, frame_channel_capacity, run_batched_sender_with_interceptor,
+    DEFAULT_FRAME_CHANNEL_CAPACITY, DEFAULT_WRITE_TIMEOUT,
+};
+use crate::transport::{self, SocketTuningConfig, TransportConfig};
+use crate::tunnel::common::{
+    clamp_u128_to_u64, frame_reader, frame_size_capability, parse_frame_size_capability,
+    resume_capability, validate_max_frame_size, FrameReader,
+};
+use bytes::Bytes;
 use ferrotunnel_common::{Result, TunnelError};
 use ferrotunnel_protocol::codec::TunnelCodec;
-use ferrotunnel_protocol::constants::{MAX_PROTOCOL_VERSION, MIN_PROTOCOL_VERSION};
-use ferrotunnel_protocol::frame::{Frame, HandshakeFrame, HandshakeStatus};
+use ferrotunnel_protocol::constants::{
+    CHALLENGE_AUTH_CAPABILITY, CHECKSUM_CAPABILITY, MAX_FRAME_SIZE, MAX_PROTOCOL_VERSION,
+    MIN_PROTOCOL_VERSION, PATH_RULES_CAPABILITY, PING_CAPABILITY, PUBLIC_URL_CAPABILITY,
+    STREAM_PROTOCOLS_CAPABILITY,
+};
+use ferrotunnel_protocol::frame::{
+    Frame, HandshakeFrame, HandshakeStatus, Protocol, RegisterStatus,
+};
+use ferrotunnel_protocol::{FrameCompression, PathRules};
 use futures::{SinkExt, StreamExt};
-use kanal::bounded_async;
 use std::future::Future;
+use std::num::NonZeroU32;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
 use tokio::time::interval;
 use tokio_util::codec::Framed;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
+/// Default interval between heartbeats sent to the server
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Default time without a `HeartbeatAck` before the connection is considered dead
+/// (three missed heartbeat intervals)
+pub const DEFAULT_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Default time to wait for the server to answer the handshake
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest reconnect delay a server's shutdown notice may ask for; longer
+/// hints are clamped so a server cannot park its clients indefinitely
+pub const MAX_SHUTDOWN_RECONNECT_DELAY: Duration = Duration::from_secs(300);
+
+/// Stream protocols a client accepts unless told otherwise; see
+/// [`TunnelClient::with_stream_protocols`]
+pub const DEFAULT_STREAM_PROTOCOLS: [Protocol; 3] =
+    [Protocol::TCP, Protocol::UDP, Protocol::WebSocket];
+
+/// Round-trip time of the control connection, measured on the monotonic
+/// clock from the timestamp the server echoes in each `HeartbeatAck`
+///
+/// Cheap to clone; clones observe the same client.
+#[derive(Debug, Clone)]
+pub struct ControlRtt {
+    /// Last RTT in milliseconds, `u64::MAX` until the first ack arrives
+    millis: Arc<AtomicU64>,
+}
+
+impl ControlRtt {
+    fn new() -> Self {
+        Self {
+            millis: Arc::new(AtomicU64::new(u64::MAX)),
+        }
+    }
+
+    fn record(&self, rtt: Duration) {
+        let millis = clamp_u128_to_u64(rtt.as_millis()).min(u64::MAX - 1);
+        self.millis.store(millis, Ordering::Relaxed);
+    }
+
+    /// RTT of the most recently acknowledged heartbeat, if any
+    pub fn get(&self) -> Option<Duration> {
+        match self.millis.load(Ordering::Relaxed) {
+            u64::MAX => None,
+            millis => Some(Duration::from_millis(millis)),
+        }
+    }
+}
+
+/// Capabilities the server granted in the last successful handshake
+///
+/// Empty until a handshake succeeds. Cheap to clone; clones observe the same
+/// client.
+#[derive(Debug, Clone, Default)]
+pub struct GrantedCapabilities {
+    capabilities: Arc<RwLock<Vec<String>>>,
+}
+
+impl GrantedCapabilities {
+    /// The granted capabilities, in the order the server listed them
+    pub fn get(&self) -> Vec<String> {
+        self.capabilities
+            .read()
+            .map(|capabilities| capabilities.clone())
+            .unwrap_or_default()
+    }
+
+    /// Whether `capability` was granted
+    pub fn contains(&self, capability: &str) -> bool {
+        self.capabilities
+            .read()
+            .is_ok_and(|capabilities| capabilities.iter().any(|cap| cap == capability))
+    }
+
+    fn set(&self, capabilities: Vec<String>) {
+        if let Ok(mut current) = self.capabilities.write() {
+            *current = capabilities;
+        }
+    }
+}
+
+/// Public URL of the tunnel as reported by the server in the last successful
+/// handshake
+///
+/// `None` until then, or when the server has no public base configured.
+/// Cheap to clone; clones observe the same client.
+#[derive(Debug, Clone, Default)]
+pub struct PublicUrl {
+    url: Arc<RwLock<Option<String>>>,
+}
+
+impl PublicUrl {
+    /// The public URL, e.g. `https://myapp.tunnel.example.com`
+    pub fn get(&self) -> Option<String> {
+        self.url.read().ok().and_then(|url| url.clone())
+    }
+
+    fn set(&self, url: Option<String>) {
+        if let Ok(mut current) = self.url.write() {
+            *current = url;
+        }
+    }
+}
+
+/// Opens streams to the server over the client's current session, for
+/// protocols where the client initiates; see
+/// [`TunnelServer::on_client_stream`](crate::TunnelServer::on_client_stream)
+///
+/// Opening fails while the client is not connected. Cheap to clone; clones
+/// observe the same client.
+#[derive(Debug, Clone, Default)]
+pub struct StreamOpener {
+    multiplexer: Arc<RwLock<Option<Multiplexer>>>,
+}
+
+impl StreamOpener {
+    /// Open a stream to the server's client stream handler
+    pub async fn open_stream(&self, protocol: Protocol) -> Result<VirtualStream> {
+        self.current()?.open_stream(protocol).await
+    }
+
+    /// Open a stream carrying metadata headers, readable by the server's
+    /// handler through [`VirtualStream::header`]
+    pub async fn open_stream_with_headers(
+        &self,
+        protocol: Protocol,
+        headers: Vec<(String, String)>,
+    ) -> Result<VirtualStream> {
+        self.current()?
+            .open_stream_with_headers(protocol, headers)
+            .await
+    }
+
+    fn current(&self) -> Result<Multiplexer> {
+        self.multiplexer
+            .read()
+            .ok()
+            .and_then(|multiplexer| multiplexer.clone())
+            .ok_or_else(|| TunnelError::Connection("Not connected".into()))
+    }
+
+    fn set(&self, multiplexer: Option<Multiplexer>) {
+        if let Ok(mut current) = self.multiplexer.write() {
+            *current = multiplexer;
+        }
+    }
+}
+
 pub struct TunnelClient {
     server_addr: String,
     auth_token: String,
     session_id: Option<Uuid>,
     tunnel_id: Option<String>,
     transport_config: TransportConfig,
+    socket_tuning: SocketTuningConfig,
+    heartbeat_interval: Duration,
+    heartbeat_timeout: Duration,
+    handshake_timeout: Duration,
+    stream_window: NonZeroU32,
+    frame_channel_capacity: usize,
+    max_frame_size: u32,
+    stream_protocols: Vec<Protocol>,
+    extra_capabilities: Vec<String>,
+    granted_capabilities: GrantedCapabilities,
+    public_url: PublicUrl,
+    streams: StreamOpener,
+    rtt: ControlRtt,
+    traffic: TrafficCounters,
+    stream_idle_timeout: Option<Duration>,
+    write_timeout: Duration,
+    interceptor: Option<SharedFrameInterceptor>,
+    recorder: Option<Arc<FrameRecorder>>,
+    path_rules: PathRules,
 }
 
 impl TunnelClient {
@@ -32,7 +221,211 @@ impl TunnelClient {
             session_id: None,
             tunnel_id: None,
             transport_config: TransportConfig::default(),
+            socket_tuning: SocketTuningConfig::default(),
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            heartbeat_timeout: DEFAULT_HEARTBEAT_TIMEOUT,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+            stream_window: NonZeroU32::new(DEFAULT_STREAM_WINDOW).unwrap_or(NonZeroU32::MIN),
+            frame_channel_capacity: DEFAULT_FRAME_CHANNEL_CAPACITY,
+            max_frame_size: MAX_FRAME_SIZE,
+            stream_protocols: DEFAULT_STREAM_PROTOCOLS.to_vec(),
+            extra_capabilities: Vec::new(),
+            granted_capabilities: GrantedCapabilities::default(),
+            public_url: PublicUrl::default(),
+            streams: StreamOpener::default(),
+            rtt: ControlRtt::new(),
+            traffic: TrafficCounters::new(),
+            stream_idle_timeout: None,
+            write_timeout: DEFAULT_WRITE_TIMEOUT,
+            interceptor: None,
+            recorder: None,
+            path_rules: PathRules::default(),
+        }
+    }
+
+    /// Set TCP keepalive, `TCP_USER_TIMEOUT` and `TCP_NODELAY` for the
+    /// control connection.
+    #[must_use]
+    pub fn with_socket_tuning(mut self, tuning: SocketTuningConfig) -> Self {
+        self.socket_tuning = tuning;
+        self
+    }
+
+    /// Advertise an additional capability to the server (e.g. `"ssh"`).
+    ///
+    /// Servers use capabilities to route raw TCP ingress ports to this tunnel.
+    /// The server may not grant every advertised capability; see
+    /// [`granted_capabilities`](Self::granted_capabilities).
+    #[must_use]
+    pub fn with_capability(mut self, capability: impl Into<String>) -> Self {
+        self.extra_capabilities.push(capability.into());
+        self
+    }
+
+    /// Advertise only the stream protocols the stream handler can serve
+    /// (default: [`DEFAULT_STREAM_PROTOCOLS`]).
+    ///
+    /// The server's ingresses only send a protocol with a
+    /// [capability](Protocol::capability) to clients that advertised it, so a
+    /// handler that only speaks HTTP should leave out TCP, UDP and
+    /// WebSocket. HTTP and gRPC streams are always accepted.
+    #[must_use]
+    pub fn with_stream_protocols(mut self, protocols: &[Protocol]) -> Self {
+        self.stream_protocols = protocols.to_vec();
+        self
+    }
+
+    /// Set the per-stream flow control window offered to the server.
+    ///
+    /// The smaller of the client and server windows is used. Flow control is
+    /// skipped when the server does not support it.
+    #[must_use]
+    pub fn with_stream_window(mut self, window: NonZeroU32) -> Self {
+        self.stream_window = window;
+        self
+    }
+
+    /// Set how many outgoing frames may queue on the control connection before senders
+    /// wait (default: [`DEFAULT_FRAME_CHANNEL_CAPACITY`]).
+    ///
+    /// Rounded up to a power of two. Worst-case memory on the control connection is the
+    /// capacity times the maximum frame size; see [`frame_channel_capacity`].
+    ///
+    /// Values outside `MIN_FRAME_CHANNEL_CAPACITY..=MAX_FRAME_CHANNEL_CAPACITY`
+    /// are clamped to that range.
+    #[must_use]
+    pub fn with_frame_channel_capacity(mut self, capacity: usize) -> Self {
+        self.frame_channel_capacity = frame_channel_capacity(capacity);
+        self
+    }
+
+    /// Set the largest frame, in bytes, this client accepts (default:
+    /// [`MAX_FRAME_SIZE`]).
+    ///
+    /// The limit is advertised in the handshake and the smaller of the client
+    /// and server limits applies to the session in both directions; servers
+    /// that do not negotiate it accept up to [`MAX_FRAME_SIZE`]. Stream data
+    /// is split into frames that fit it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TunnelError::Config`] if `max_frame_size` is outside
+    /// `MIN_FRAME_SIZE..=MAX_FRAME_SIZE`.
+    pub fn with_max_frame_size(mut self, max_frame_size: u32) -> Result<Self> {
+        self.max_frame_size = validate_max_frame_size(max_frame_size)?;
+        Ok(self)
+    }
+
+    /// Ask the server for a CRC32 on every frame after the handshake, to catch
+    /// corruption on links that TCP's checksum misses. Off by default; only
+    /// used when the server grants it.
+    #[must_use]
+    pub fn with_frame_checksum(self) -> Self {
+        self.with_capability(CHECKSUM_CAPABILITY)
+    }
+
+    /// Offer `algorithms` for compressing data frames; the server picks one
+    /// it supports by its own preference order (see
+    /// [`TunnelServer::with_frame_compression`](crate::TunnelServer::with_frame_compression)).
+    /// Off by default; [`FrameCompression::default_preference`] offers every
+    /// algorithm this build has.
+    #[must_use]
+    pub fn with_frame_compression(mut self, algorithms: &[FrameCompression]) -> Self {
+        for algorithm in algorithms {
+            self = self.with_capability(algorithm.capability());
         }
+        self
+    }
+
+    /// Prove the token to the server by answering a challenge with
+    /// `HMAC-SHA256(token, nonce)` instead of sending the token, so it stays
+    /// secret even over plaintext TCP. Off by default; the server must grant
+    /// it, otherwise the handshake is rejected as carrying no token.
+    #[must_use]
+    pub fn with_challenge_auth(self) -> Self {
+        self.with_capability(CHALLENGE_AUTH_CAPABILITY)
+    }
+
+    /// Count session traffic into `traffic`, e.g. to keep totals across
+    /// clients created for each reconnect.
+    #[must_use]
+    pub fn with_traffic(mut self, traffic: TrafficCounters) -> Self {
+        self.traffic = traffic;
+        self
+    }
+
+    /// Fail streams that have a read or write waiting with no data moving for
+    /// `timeout`, e.g. a raw TCP tunnel to a local service that stopped
+    /// responding; see [`Multiplexer::with_stream_idle_timeout`]. Off by
+    /// default.
+    #[must_use]
+    pub fn with_stream_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.stream_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Drop the connection (and reconnect) when the server accepts no data for
+    /// `timeout` while frames are waiting to be written. Defaults to
+    /// [`DEFAULT_WRITE_TIMEOUT`].
+    #[must_use]
+    pub fn with_write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// Show every frame of the running session to `interceptor`: outgoing
+    /// frames as they are encoded, incoming ones before they are handled.
+    #[must_use]
+    pub fn with_frame_interceptor(mut self, interceptor: Arc<dyn FrameInterceptor>) -> Self {
+        self.interceptor = Some(interceptor);
+        self
+    }
+
+    /// Record every frame of each session to a capture file at `path`, replacing
+    /// any existing file; see [`FrameRecorder`]. Runs alongside a
+    /// [frame interceptor](Self::with_frame_interceptor).
+    pub fn with_frame_recorder(mut self, path: impl Into<PathBuf>) -> Result<Self> {
+        self.recorder = Some(Arc::new(FrameRecorder::create(path)?));
+        Ok(self)
+    }
+
+    /// The recorder set with [`with_frame_recorder`](Self::with_frame_recorder),
+    /// e.g. to flush it
+    pub fn frame_recorder(&self) -> Option<Arc<FrameRecorder>> {
+        self.recorder.clone()
+    }
+
+    /// Set how often heartbeats are sent to the server.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TunnelError::Config`] if `interval` is zero.
+    pub fn with_heartbeat_interval(mut self, interval: Duration) -> Result<Self> {
+        if interval.is_zero() {
+            return Err(TunnelError::Config(
+                "heartbeat interval must be greater than zero".into(),
+            ));
+        }
+        self.heartbeat_interval = interval;
+        Ok(self)
+    }
+
+    /// Set how long to wait for a `HeartbeatAck` before treating the connection as dead.
+    ///
+    /// Measured as the wall-clock gap since the last ack, so a busy runtime that
+    /// delays a single tick does not trip it.
+    #[must_use]
+    pub fn with_heartbeat_timeout(mut self, timeout: Duration) -> Self {
+        self.heartbeat_timeout = timeout;
+        self
+    }
+
+    /// Set how long to wait for the server to complete the handshake after
+    /// connecting before failing with [`TunnelError::Timeout`].
+    #[must_use]
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
     }
 
     #[must_use]
@@ -47,6 +440,17 @@ impl TunnelClient {
         self
     }
 
+    /// Limit which HTTP paths the server's ingress forwards to this tunnel
+    ///
+    /// The rules follow the handshake in a `PathRules` frame, announced by the
+    /// [`PATH_RULES_CAPABILITY`]. The server checks them during the handshake
+    /// and rejects invalid ones with [`HandshakeStatus::InvalidPathRules`].
+    #[must_use]
+    pub fn with_path_rules(mut self, rules: PathRules) -> Self {
+        self.path_rules = rules;
+        self
+    }
+
     /// Enable TLS for the connection with certificate verification skipped.
     ///
     /// This is insecure and should only be used for self-signed certificates.
@@ -65,7 +469,7 @@ impl TunnelClient {
 
     /// Enable TLS for the connection with a custom CA certificate.
     #[must_use]
-    pub fn with_tls_ca(mut self, ca_cert_path: impl Into<std::path::PathBuf>) -> Self {
+    pub fn with_tls_ca(mut self, ca_cert_path: impl Into<PathBuf>) -> Self {
         let ca = Some(ca_cert_path.into().to_string_lossy().to_string());
         if let TransportConfig::Tls(ref mut tls) = self.transport_config {
             tls.ca_cert_path = ca;
@@ -80,11 +484,7 @@ impl TunnelClient {
 
     /// Enable mutual TLS by providing a client certificate and private key.
     #[must_use]
-    pub fn with_tls(
-        mut self,
-        cert_path: impl Into<std::path::PathBuf>,
-        key_path: impl Into<std::path::PathBuf>,
-    ) -> Self {
+    pub fn with_tls(mut self, cert_path: impl Into<PathBuf>, key_path: impl Into<PathBuf>) -> Self {
         let (cert, key) = (
             cert_path.into().to_string_lossy().to_string(),
             key_path.into().to_string_lossy().to_string(),
@@ -117,6 +517,75 @@ impl TunnelClient {
         self
     }
 
+    /// Resume the previous TLS session when reconnecting.
+    ///
+    /// With `early_data`, the first bytes of a resumed connection may be sent
+    /// as 0-RTT data, which can be replayed; see
+    /// [`TlsTransportConfig::early_data`](transport::tls::TlsTransportConfig::early_data).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TunnelError::Config`] unless TLS was already configured,
+    /// e.g. with [`with_tls_ca`](Self::with_tls_ca) or
+    /// [`with_tls_skip_verify`](Self::with_tls_skip_verify).
+    pub fn with_tls_session_resumption(mut self, early_data: bool) -> Result<Self> {
+        let TransportConfig::Tls(ref mut tls) = self.transport_config else {
+            return Err(TunnelError::Config(
+                "TLS session resumption requires a TLS transport".into(),
+            ));
+        };
+        tls.session_resumption = true;
+        tls.early_data = early_data;
+        Ok(self)
+    }
+
+    /// Round-trip time of the last acknowledged heartbeat, if any
+    pub fn last_rtt(&self) -> Option<Duration> {
+        self.rtt.get()
+    }
+
+    /// Handle for reading the control-connection RTT while the session runs
+    pub fn rtt_handle(&self) -> ControlRtt {
+        self.rtt.clone()
+    }
+
+    /// Capabilities the server granted in the last successful handshake
+    pub fn granted_capabilities(&self) -> Vec<String> {
+        self.granted_capabilities.get()
+    }
+
+    /// Handle for reading the granted capabilities while the session runs
+    pub fn granted_capabilities_handle(&self) -> GrantedCapabilities {
+        self.granted_capabilities.clone()
+    }
+
+    /// Public URL the server reported in the last successful handshake
+    pub fn public_url(&self) -> Option<String> {
+        self.public_url.get()
+    }
+
+    /// Handle for reading the public URL, e.g. from the `on_connected`
+    /// callback
+    pub fn public_url_handle(&self) -> PublicUrl {
+        self.public_url.clone()
+    }
+
+    /// Open a stream to the server over the current session; see
+    /// [`StreamOpener::open_stream`]
+    pub async fn open_stream(&self, protocol: Protocol) -> Result<VirtualStream> {
+        self.streams.open_stream(protocol).await
+    }
+
+    /// Handle for opening streams to the server while the session runs
+    pub fn stream_opener(&self) -> StreamOpener {
+        self.streams.clone()
+    }
+
+    /// Data bytes received from and sent to the server, across reconnects
+    pub fn traffic(&self) -> TrafficCounters {
+        self.traffic.clone()
+    }
+
     /// Connect to the server and start the session
     pub async fn connect_and_run<F, Fut>(&mut self, stream_handler: F) -> Result<()>
     where
@@ -138,64 +607,248 @@ impl TunnelClient {
         Fut: Future<Output = ()> + Send + 'static,
         C: FnOnce(Uuid) + Send + 'static,
     {
-        validate_token_format(&self.auth_token, 256)
+        // The server decides whether a long token is acceptable: servers
+        // with a custom authenticator take signed tokens such as JWTs
+        validate_token_format(&self.auth_token, MAX_AUTHENTICATOR_TOKEN_LEN)
             .map_err(|e| TunnelError::Authentication(format!("Invalid token: {e}")))?;
 
         info!("Connecting to {}", self.server_addr);
-        let stream = transport::connect(&self.transport_config, &self.server_addr).await?;
+        #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+        let connect_start = Instant::now();
+        let stream = transport::connect_tuned(
+            &self.transport_config,
+            &self.server_addr,
+            &self.socket_tuning,
+        )
+        .await?;
         info!("Connected to {}", self.server_addr);
 
-        let mut framed = Framed::new(stream, TunnelCodec::new());
-        let session_id = Self::handshake(&mut framed, self, on_connected).await?;
+        let codec = TunnelCodec::with_max_frame_size(self.max_frame_size as usize);
+        let mut framed = Framed::new(stream, codec);
+        let handshake = Self::handshake(&mut framed, self, on_connected);
+        let (session_id, stream_window) = tokio::time::timeout(self.handshake_timeout, handshake)
+            .await
+            .map_err(|_| {
+                TunnelError::Timeout(format!(
+                    "no handshake reply from {} within {:?}",
+                    self.server_addr, self.handshake_timeout
+                ))
+            })??;
         self.session_id = Some(session_id);
+        #[cfg(feature = "metrics")]
+        if let Some(m) = ferrotunnel_observability::tunnel_metrics() {
+            m.record_client_connect(connect_start.elapsed());
+        }
 
-        let (multiplexer, mut split_stream) = Self::setup_multiplexer(framed, stream_handler);
+        let interceptor = interceptor::chain(
+            self.interceptor.clone(),
+            self.recorder
+                .clone()
+                .map(|recorder| recorder as SharedFrameInterceptor),
+        );
+        let (multiplexer, mut split_stream, mut sender) =
+            self.setup_multiplexer(framed, stream_handler, stream_window, interceptor.clone());
+        self.streams.set(Some(multiplexer.clone()));
 
-        Self::run_session_loop(multiplexer, &mut split_stream).await
+        let result = Self::run_session_loop(
+            multiplexer,
+            &mut split_stream,
+            &mut sender,
+            self.heartbeat_interval,
+            self.heartbeat_timeout,
+            &self.rtt,
+            interceptor.as_deref(),
+        )
+        .await;
+        self.streams.set(None);
+        if let Some(recorder) = self.recorder.clone() {
+            // Flushing waits on the capture's writer thread
+            let flushed = tokio::task::spawn_blocking(move || recorder.flush())
+                .await
+                .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+            if let Err(e) = flushed {
+                warn!("Failed to flush frame capture: {}", e);
+            }
+        }
+        result
     }
 }
 
 impl TunnelClient {
+    fn capabilities(&self) -> Vec<String> {
+        let mut capabilities = vec!["basic".to_string(), STREAM_PROTOCOLS_CAPABILITY.to_string()];
+        capabilities.extend(
+            self.stream_protocols
+                .iter()
+                .filter_map(|protocol| protocol.capability())
+                .map(str::to_string),
+        );
+        capabilities.push(flow_control::capability(self.stream_window));
+        capabilities.push(frame_size_capability(self.max_frame_size));
+        capabilities.push(PING_CAPABILITY.to_string());
+        capabilities.push(PUBLIC_URL_CAPABILITY.to_string());
+        // Take over the session of a connection that dropped
+        capabilities.extend(self.session_id.map(resume_capability));
+        if !self.path_rules.is_empty() {
+            capabilities.push(PATH_RULES_CAPABILITY.to_string());
+        }
+        capabilities.extend(self.extra_capabilities.iter().cloned());
+        capabilities
+    }
+
+    #[allow(clippy::too_many_lines)]
     async fn handshake<C>(
         framed: &mut Framed<transport::BoxedStream, TunnelCodec>,
         client: &TunnelClient,
         on_connected: C,
-    ) -> Result<Uuid>
+    ) -> Result<(Uuid, Option<NonZeroU32>)>
     where
         C: FnOnce(Uuid) + Send + 'static,
     {
+        let challenge = client
+            .extra_capabilities
+            .iter()
+            .any(|cap| cap == CHALLENGE_AUTH_CAPABILITY);
+        let token = if challenge {
+            String::new()
+        } else {
+            client.auth_token.clone()
+        };
+        let capabilities = client.capabilities();
+        let path_rules = capabilities.iter().any(|cap| cap == PATH_RULES_CAPABILITY);
         framed
             .send(Frame::Handshake(Box::new(HandshakeFrame {
                 min_version: MIN_PROTOCOL_VERSION,
                 max_version: MAX_PROTOCOL_VERSION,
-                token: client.auth_token.clone(),
+                token,
                 tunnel_id: client.tunnel_id.clone(),
-                capabilities: vec!["basic".to_string(), "tcp".to_string()],
+                capabilities,
             })))
             .await?;
+        // The server reads the rules right after the handshake, before replying
+        if path_rules {
+            framed
+                .send(Frame::PathRules(Box::new(client.path_rules.clone())))
+                .await?;
+        }
 
-        if let Some(result) = framed.next().await {
+        let mut reply = framed.next().await;
+        if challenge {
+            if let Some(Ok(Frame::AuthChallenge { nonce })) = &reply {
+                let mac = challenge_response(&client.auth_token, nonce);
+                framed
+                    .send(Frame::AuthResponse {
+                        mac: Bytes::copy_from_slice(&mac),
+                    })
+                    .await?;
+                reply = framed.next().await;
+            }
+        }
+        if let Some(result) = reply {
             match result? {
                 Frame::HandshakeAck {
                     status,
                     session_id,
                     version,
-                    server_capabilities: _,
+                    server_capabilities,
                 } => match status {
                     HandshakeStatus::Success => {
+                        if !(MIN_PROTOCOL_VERSION..=MAX_PROTOCOL_VERSION).contains(&version) {
+                            return Err(TunnelError::VersionMismatch(format!(
+                                "Server negotiated unsupported protocol version {version}"
+                            )));
+                        }
+                        // Read before the codec switch: it is sent like the ack
+                        let has_public_url = server_capabilities
+                            .iter()
+                            .any(|cap| cap == PUBLIC_URL_CAPABILITY);
+                        let public_url = if has_public_url {
+                            Some(Self::read_public_url(framed).await?)
+                        } else {
+                            None
+                        };
+                        // Servers that do not negotiate it accept the protocol maximum
+                        let negotiated = parse_frame_size_capability(&server_capabilities);
+                        let max_frame_size = match negotiated {
+                            Some(size) => validate_max_frame_size(size)
+                                .map_err(|_| {
+                                    TunnelError::Protocol(format!(
+                                        "Server negotiated invalid max frame size {size}"
+                                    ))
+                                })?
+                                .min(client.max_frame_size),
+                            None => client.max_frame_size,
+                        };
                         info!(
                             "Handshake successful. Session ID: {}, Protocol v{}",
                             session_id, version
                         );
+                        let checksum = server_capabilities
+                            .iter()
+                            .any(|cap| cap == CHECKSUM_CAPABILITY);
+                        let compression = FrameCompression::from_capabilities(&server_capabilities);
+                        if let Some(compression) = compression {
+                            info!("Compressing data frames with {}", compression.name());
+                        }
+                        *framed.codec_mut() =
+                            TunnelCodec::with_max_frame_size(max_frame_size as usize)
+                                .with_checksum(checksum)
+                                .with_compression(compression);
+                        let stream_window = flow_control::parse_capability(&server_capabilities);
+                        if stream_window.is_none()
+                            && server_capabilities
+                                .iter()
+                                .any(|cap| flow_control::is_capability(cap))
+                        {
+                            return Err(TunnelError::Protocol(
+                                "Server negotiated invalid stream window".into(),
+                            ));
+                        }
+                        client.granted_capabilities.set(server_capabilities);
+                        if let Some(url) = &public_url {
+                            info!("Tunnel available at {}", url);
+                        }
+                        client.public_url.set(public_url);
                         on_connected(session_id);
-                        Ok(session_id)
+                        Ok((session_id, stream_window))
                     }
-                    HandshakeStatus::VersionMismatch => {
+                    HandshakeStatus::VersionMismatch | HandshakeStatus::UnsupportedVersion => {
                         error!("Protocol version mismatch. Server requires different version.");
-                        Err(TunnelError::Protocol(
+                        Err(TunnelError::VersionMismatch(
                             "No compatible protocol version found".into(),
                         ))
                     }
+                    HandshakeStatus::InvalidPathRules => {
+                        error!("Handshake rejected: invalid path rules");
+                        Err(TunnelError::Config(
+                            "Server rejected the tunnel's path rules".into(),
+                        ))
+                    }
+                    HandshakeStatus::InvalidFrameSize => {
+                        error!("Handshake rejected: invalid max frame size");
+                        Err(TunnelError::Config(
+                            "Server rejected the tunnel's max frame size".into(),
+                        ))
+                    }
+                    // The server may accept the same handshake later
+                    HandshakeStatus::RateLimited => {
+                        warn!("Handshake rejected: {:?}", status);
+                        Err(TunnelError::RateLimited(format!(
+                            "Handshake rejected: {status:?}"
+                        )))
+                    }
+                    HandshakeStatus::ServerFull => {
+                        warn!("Handshake rejected: {:?}", status);
+                        Err(TunnelError::ResourceExhausted(format!(
+                            "Handshake rejected: {status:?}"
+                        )))
+                    }
+                    HandshakeStatus::TunnelIdTaken => {
+                        warn!("Handshake rejected: {:?}", status);
+                        Err(TunnelError::ServiceUnavailable(format!(
+                            "Handshake rejected: {status:?}"
+                        )))
+                    }
                     status => {
                         error!("Handshake failed: {:?}", status);
                         Err(TunnelError::Authentication(format!(
@@ -210,13 +863,31 @@ impl TunnelClient {
         }
     }
 
+    /// Read the `RegisterAck` a server granting the `public_url` capability
+    /// sends right after its `HandshakeAck`
+    async fn read_public_url(
+        framed: &mut Framed<transport::BoxedStream, TunnelCodec>,
+    ) -> Result<String> {
+        match framed.next().await {
+            Some(Ok(Frame::RegisterAck {
+                public_url,
+                status: RegisterStatus::Success,
+            })) => Ok(public_url),
+            Some(Ok(_)) => Err(TunnelError::Protocol(
+                "Expected the tunnel's public URL".into(),
+            )),
+            Some(Err(e)) => Err(e.into()),
+            None => Err(TunnelError::Connection("Connection closed".into())),
+        }
+    }
+
     fn setup_multiplexer<F, Fut>(
+        &self,
         framed: Framed<transport::BoxedStream, TunnelCodec>,
         stream_handler: F,
-    ) -> (
-        Multiplexer,
-        tokio_util::codec::FramedRead<tokio::io::ReadHalf<transport::BoxedStream>, TunnelCodec>,
-    )
+        stream_window: Option<NonZeroU32>,
+        interceptor: Option<SharedFrameInterceptor>,
+    ) -> (Multiplexer, FrameReader, JoinHandle<Result<()>>)
     where
         F: Fn(VirtualStream) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = ()> + Send + 'static,
@@ -224,59 +895,114 @@ impl TunnelClient {
         let parts = framed.into_parts();
         let (read_half, write_half) = tokio::io::split(parts.io);
 
-        let mut split_stream = tokio_util::codec::FramedRead::new(read_half, parts.codec);
-        if !parts.read_buf.is_empty() {
-            split_stream
-                .read_buffer_mut()
-                .extend_from_slice(&parts.read_buf);
-        }
+        let split_stream = frame_reader(read_half, parts.codec, parts.read_buf);
 
-        let (frame_tx, frame_rx) = bounded_async::<PrioritizedFrame>(1024);
-        tokio::spawn(run_batched_sender(frame_rx, write_half, parts.codec));
+        let (frame_tx, frame_rx) = frame_channel(self.frame_channel_capacity);
+        let sender = tokio::spawn(run_batched_sender_with_interceptor(
+            frame_rx,
+            write_half,
+            parts.codec,
+            interceptor,
+            self.write_timeout,
+        ));
 
-        let (multiplexer, new_stream_rx) = Multiplexer::new(frame_tx, true);
+        let (multiplexer, new_stream_rx) = match stream_window {
+            Some(window) => Multiplexer::with_flow_control(frame_tx, true, window),
+            None => Multiplexer::new(frame_tx, true),
+        };
+        let mut multiplexer = multiplexer
+            .with_traffic(self.traffic.clone())
+            .with_max_frame_size(parts.codec.max_frame_size());
+        if let Some(timeout) = self.stream_idle_timeout {
+            multiplexer = multiplexer.with_stream_idle_timeout(timeout);
+        }
+        if self.granted_capabilities.contains(PING_CAPABILITY) {
+            multiplexer = multiplexer.with_ping();
+        }
         tokio::spawn(async move {
             while let Ok(s) = new_stream_rx.recv().await {
                 stream_handler(s).await;
             }
         });
 
-        (multiplexer, split_stream)
+        (multiplexer, split_stream, sender)
     }
 
     async fn run_session_loop(
         multiplexer: Multiplexer,
-        split_stream: &mut tokio_util::codec::FramedRead<
-            tokio::io::ReadHalf<transport::BoxedStream>,
-            TunnelCodec,
-        >,
+        split_stream: &mut FrameReader,
+        sender: &mut JoinHandle<Result<()>>,
+        heartbeat_period: Duration,
+        heartbeat_timeout: Duration,
+        rtt: &ControlRtt,
+        interceptor: Option<&dyn FrameInterceptor>,
     ) -> Result<()> {
-        let mut heartbeat_interval = interval(Duration::from_secs(30));
+        let mut heartbeat_interval = interval(heartbeat_period);
+        let mut last_ack = Instant::now();
+        // Heartbeat timestamps count from here, so a stale ack from an earlier
+        // connection cannot be mistaken for one of ours
+        let epoch = Instant::now();
 
         loop {
             #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
             let decode_start = Instant::now();
             tokio::select! {
                 _ = heartbeat_interval.tick() => {
-                    let ts = clamp_u128_to_u64(
-                        std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_millis()
-                    );
+                    let since_ack = last_ack.elapsed();
+                    if since_ack > heartbeat_timeout {
+                        warn!(
+                            "No heartbeat ack from server for {:?}, treating connection as dead",
+                            since_ack
+                        );
+                        return Err(TunnelError::Timeout(format!(
+                            "no heartbeat ack received for {since_ack:?}"
+                        )));
+                    }
+                    let ts = heartbeat_timestamp(epoch);
                     if let Err(e) = multiplexer.send_frame(Frame::Heartbeat { timestamp: ts }).await {
                         error!("Failed to send heartbeat: {}", e);
                         return Err(e);
                     }
                 }
+                sent = &mut *sender => {
+                    warn!("Frame sender stopped, dropping the connection");
+                    return Err(match sent {
+                        Ok(Err(e)) => e,
+                        _ => TunnelError::Connection("Frame sender stopped".into()),
+                    });
+                }
                 result = split_stream.next() => {
+                    if let (Some(interceptor), Some(Ok(frame))) = (interceptor, &result) {
+                        interceptor.on_recv(frame);
+                    }
                     match result {
-                        Some(Ok(Frame::HeartbeatAck { .. })) => {
+                        Some(Ok(Frame::HeartbeatAck { timestamp })) => {
+                            last_ack = Instant::now();
+                            if let Some(sample) = heartbeat_rtt(epoch, timestamp) {
+                                rtt.record(sample);
+                                #[cfg(feature = "metrics")]
+                                if let Some(m) = ferrotunnel_observability::tunnel_metrics() {
+                                    m.set_control_rtt(sample);
+                                }
+                            }
                             #[cfg(feature = "metrics")]
                             if let Some(m) = ferrotunnel_observability::tunnel_metrics() {
                                 m.record_decode(1, 0, decode_start.elapsed());
                             }
                         }
+                        Some(Ok(Frame::Shutdown { reason, reconnect_after_ms })) => {
+                            let reconnect_after = reconnect_after_ms.map(|ms| {
+                                Duration::from_millis(ms).min(MAX_SHUTDOWN_RECONNECT_DELAY)
+                            });
+                            match reconnect_after {
+                                Some(delay) => info!(
+                                    "Server is shutting down ({}), reconnect suggested in {:?}",
+                                    reason, delay
+                                ),
+                                None => info!("Server is shutting down ({})", reason),
+                            }
+                            return Err(TunnelError::ServerShutdown { reason, reconnect_after });
+                        }
                         Some(Ok(frame)) => {
                             #[cfg(feature = "metrics")]
                             if let Some(m) = ferrotunnel_observability::tunnel_metrics() {
@@ -304,3 +1030,338 @@ impl TunnelClient {
         }
     }
 }
+
+/// Timestamp for a heartbeat: microseconds on the monotonic clock since
+/// `epoch`. The server echoes it untouched, so only this client reads it.
+fn heartbeat_timestamp(epoch: Instant) -> u64 {
+    clamp_u128_to_u64(epoch.elapsed().as_micros())
+}
+
+/// Round-trip time of the heartbeat that carried `timestamp`, or `None` for a
+/// timestamp this connection has not sent yet
+fn heartbeat_rtt(epoch: Instant, timestamp: u64) -> Option<Duration> {
+    epoch
+        .elapsed()
+        .checked_sub(Duration::from_micros(timestamp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_tls_session_resumption_requires_tls() {
+        let client = TunnelClient::new("127.0.0.1:7835".into(), "secret".into());
+        assert!(client.with_tls_session_resumption(false).is_err());
+
+        let client = TunnelClient::new("127.0.0.1:7835".into(), "secret".into())
+            .with_tls_skip_verify()
+            .with_tls_session_resumption(true)
+            .unwrap();
+        let TransportConfig::Tls(tls) = &client.transport_config else {
+            panic!("expected a TLS transport");
+        };
+        assert!(tls.session_resumption);
+        assert!(tls.early_data);
+    }
+
+    /// Accepts one client, completes the handshake, then swallows every frame
+    /// without ever acknowledging heartbeats (a half-open peer).
+    async fn spawn_silent_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut framed = Framed::new(socket, TunnelCodec::new());
+            let _handshake = framed.next().await;
+            framed
+                .send(Frame::HandshakeAck {
+                    status: HandshakeStatus::Success,
+                    session_id: Uuid::new_v4(),
+                    version: MAX_PROTOCOL_VERSION,
+                    server_capabilities: vec!["basic".to_string()],
+                })
+                .await
+                .unwrap();
+            while framed.next().await.is_some() {}
+        });
+
+        addr
+    }
+
+    /// Reconnect hint a client reports after a shutdown notice asking for
+    /// `reconnect_after_ms`
+    async fn shutdown_hint(reconnect_after_ms: u64) -> Option<Duration> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut framed = Framed::new(socket, TunnelCodec::new());
+            let _handshake = framed.next().await;
+            framed
+                .send(Frame::HandshakeAck {
+                    status: HandshakeStatus::Success,
+                    session_id: Uuid::new_v4(),
+                    version: MAX_PROTOCOL_VERSION,
+                    server_capabilities: vec!["basic".to_string()],
+                })
+                .await
+                .unwrap();
+            framed
+                .send(Frame::Shutdown {
+                    reason: "planned restart".to_string(),
+                    reconnect_after_ms: Some(reconnect_after_ms),
+                })
+                .await
+                .unwrap();
+            while framed.next().await.is_some() {}
+        });
+
+        let mut client = TunnelClient::new(addr, "test-token".to_string());
+        let err = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.connect_and_run(|_stream| async {}),
+        )
+        .await
+        .unwrap()
+        .unwrap_err();
+        match err {
+            TunnelError::ServerShutdown {
+                reason,
+                reconnect_after,
+            } => {
+                assert_eq!(reason, "planned restart");
+                reconnect_after
+            }
+            other => panic!("expected ServerShutdown, got {other}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_frame_ends_session_with_hint() {
+        assert_eq!(shutdown_hint(1500).await, Some(Duration::from_millis(1500)));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_hint_clamped() {
+        assert_eq!(
+            shutdown_hint(u64::MAX).await,
+            Some(MAX_SHUTDOWN_RECONNECT_DELAY)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_timeout_detects_dead_peer() {
+        let addr = spawn_silent_server().await;
+        let mut client = TunnelClient::new(addr, "test-token".to_string())
+            .with_heartbeat_interval(Duration::from_millis(50))
+            .unwrap()
+            .with_heartbeat_timeout(Duration::from_millis(200));
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.connect_and_run(|_stream| async {}),
+        )
+        .await
+        .expect("client should give up on a silent server");
+
+        assert!(matches!(result, Err(TunnelError::Timeout(_))));
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_handshake_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            // Read the handshake and never answer it
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut framed = Framed::new(socket, TunnelCodec::new());
+            while framed.next().await.is_some() {}
+        });
+
+        let mut client = TunnelClient::new(addr, "test-token".to_string())
+            .with_handshake_timeout(Duration::from_millis(200));
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.connect_and_run(|_stream| async {}),
+        )
+        .await
+        .expect("client should give up on an unanswered handshake");
+
+        assert!(matches!(result, Err(TunnelError::Timeout(_))), "{result:?}");
+    }
+
+    /// Error returned by a client whose handshake the server rejects with `status`
+    async fn rejected_handshake(status: HandshakeStatus) -> TunnelError {
+        rejected_ack(Frame::HandshakeAck {
+            status,
+            session_id: Uuid::nil(),
+            version: MAX_PROTOCOL_VERSION,
+            server_capabilities: vec![],
+        })
+        .await
+    }
+
+    /// Error returned by a client whose handshake the server answers with `ack`
+    async fn rejected_ack(ack: Frame) -> TunnelError {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut framed = Framed::new(socket, TunnelCodec::new());
+            let _handshake = framed.next().await;
+            framed.send(ack).await.unwrap();
+        });
+
+        let mut client = TunnelClient::new(addr, "test-token".to_string());
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            client.connect_and_run(|_stream| async {}),
+        )
+        .await
+        .unwrap()
+        .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejections_map_to_error_variants() {
+        let err = rejected_handshake(HandshakeStatus::RateLimited).await;
+        assert!(matches!(err, TunnelError::RateLimited(_)), "{err}");
+        let err = rejected_handshake(HandshakeStatus::ServerFull).await;
+        assert!(matches!(err, TunnelError::ResourceExhausted(_)), "{err}");
+        let err = rejected_handshake(HandshakeStatus::TunnelIdTaken).await;
+        assert!(matches!(err, TunnelError::ServiceUnavailable(_)), "{err}");
+        assert!(err.is_retryable());
+        let err = rejected_handshake(HandshakeStatus::InvalidFrameSize).await;
+        assert!(matches!(err, TunnelError::Config(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_ack_with_invalid_frame_size_rejected() {
+        let err = rejected_ack(Frame::HandshakeAck {
+            status: HandshakeStatus::Success,
+            session_id: Uuid::new_v4(),
+            version: MAX_PROTOCOL_VERSION,
+            server_capabilities: vec!["max_frame:0".to_string()],
+        })
+        .await;
+        assert!(matches!(err, TunnelError::Protocol(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_ack_with_unsupported_version_rejected() {
+        let err = rejected_ack(Frame::HandshakeAck {
+            status: HandshakeStatus::Success,
+            session_id: Uuid::new_v4(),
+            version: MAX_PROTOCOL_VERSION + 1,
+            server_capabilities: vec![],
+        })
+        .await;
+        assert!(matches!(err, TunnelError::VersionMismatch(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_rtt_recorded_from_heartbeat_ack() {
+        let addr = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap()
+        };
+        let server = crate::TunnelServer::new(addr, "test-token".into());
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        for _ in 0..50 {
+            if tokio::net::TcpStream::connect(addr).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let mut client = TunnelClient::new(addr.to_string(), "test-token".to_string())
+            .with_heartbeat_interval(Duration::from_millis(50))
+            .unwrap();
+        assert!(client.last_rtt().is_none());
+        let rtt = client.rtt_handle();
+        tokio::spawn(async move {
+            let _ = client.connect_and_run(|_stream| async {}).await;
+        });
+
+        let mut sample = None;
+        for _ in 0..100 {
+            sample = rtt.get();
+            if sample.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let sample = sample.expect("no heartbeat ack recorded");
+        assert!(
+            sample < Duration::from_secs(1),
+            "loopback RTT too large: {sample:?}"
+        );
+    }
+
+    #[test]
+    fn test_heartbeat_defaults() {
+        let client = TunnelClient::new("127.0.0.1:7835".to_string(), "token".to_string());
+        assert_eq!(client.heartbeat_interval, DEFAULT_HEARTBEAT_INTERVAL);
+        assert_eq!(client.heartbeat_timeout, DEFAULT_HEARTBEAT_TIMEOUT);
+    }
+
+    #[test]
+    fn test_heartbeat_rtt_uses_monotonic_timestamps() {
+        let epoch = Instant::now().checked_sub(Duration::from_secs(5)).unwrap();
+        let sent = heartbeat_timestamp(epoch);
+        assert!(sent >= 5_000_000);
+        let rtt = heartbeat_rtt(epoch, sent).unwrap();
+        assert!(rtt < Duration::from_secs(1), "rtt too large: {rtt:?}");
+
+        // An echoed value this connection never sent is ignored
+        assert_eq!(heartbeat_rtt(epoch, sent + 60_000_000), None);
+    }
+
+    #[test]
+    fn test_zero_heartbeat_interval_rejected() {
+        let client = TunnelClient::new("127.0.0.1:7835".to_string(), "token".to_string());
+        assert!(matches!(
+            client.with_heartbeat_interval(Duration::ZERO),
+            Err(TunnelError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_capabilities_follow_stream_protocols() {
+        let client = TunnelClient::new("127.0.0.1:7835".to_string(), "token".to_string());
+        let capabilities = client.capabilities();
+        for capability in ["basic", "tcp", "udp", "websocket"] {
+            assert!(capabilities.iter().any(|cap| cap == capability));
+        }
+
+        let client = client
+            .with_stream_protocols(&[Protocol::HTTP, Protocol::WebSocket])
+            .with_capability("ssh");
+        let capabilities = client.capabilities();
+        assert_eq!(capabilities[..3], ["basic", "protocols", "websocket"]);
+        assert!(capabilities.iter().any(|cap| cap == "ssh"));
+        assert!(!capabilities.iter().any(|cap| cap == "tcp" || cap == "udp"));
+    }
+
+    #[test]
+    fn test_frame_channel_capacity() {
+        use crate::transport::batched_sender::MIN_FRAME_CHANNEL_CAPACITY;
+
+        let client = TunnelClient::new("127.0.0.1:7835".to_string(), "token".to_string());
+        assert_eq!(
+            client.frame_channel_capacity,
+            DEFAULT_FRAME_CHANNEL_CAPACITY
+        );
+
+        let client = client.with_frame_channel_capacity(3000);
+        assert_eq!(client.frame_channel_capacity, 4096);
+
+        let client = client.with_frame_channel_capacity(1);
+        assert_eq!(client.frame_channel_capacity, MIN_FRAME_CHANNEL_CAPACITY);
+    }
+}
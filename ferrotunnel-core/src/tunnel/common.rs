@@ -1,3 +1,128 @@
+use crate::transport::BoxedStream;
+use bytes::BytesMut;
+use ferrotunnel_common::{Result, TunnelError};
+use ferrotunnel_protocol::codec::TunnelCodec;
+use ferrotunnel_protocol::constants::{
+    MAX_FRAME_SIZE, MAX_FRAME_SIZE_CAPABILITY, MIN_FRAME_SIZE, RESUME_CAPABILITY,
+};
+use ferrotunnel_protocol::Frame;
+use tokio::io::ReadHalf;
+use tokio_util::codec::{Framed, FramedParts};
+use uuid::Uuid;
+
+/// Read half of a connection once the handshake is over
+pub type FrameReader = Framed<ReadHalf<BoxedStream>, TunnelCodec>;
+
+/// Reader for the frames after the handshake, starting with any that arrived
+/// together with it in `read_buf`.
+///
+/// Unlike `FramedRead`, whose buffer is only decoded after the next read from
+/// the socket, this returns buffered frames right away, so a frame sent right
+/// behind the handshake is not held up until more traffic arrives.
+pub fn frame_reader(
+    read_half: ReadHalf<BoxedStream>,
+    codec: TunnelCodec,
+    read_buf: BytesMut,
+) -> FrameReader {
+    let mut parts = FramedParts::new::<Frame>(read_half, codec);
+    parts.read_buf = read_buf;
+    Framed::from_parts(parts)
+}
+
 pub fn clamp_u128_to_u64(i: u128) -> u64 {
     i.min(u128::from(u64::MAX)) as u64
 }
+
+/// Check a max frame size before it is advertised in the handshake
+pub fn validate_max_frame_size(max_frame_size: u32) -> Result<u32> {
+    if !(MIN_FRAME_SIZE..=MAX_FRAME_SIZE).contains(&max_frame_size) {
+        return Err(TunnelError::Config(format!(
+            "max frame size {max_frame_size} outside {MIN_FRAME_SIZE}..={MAX_FRAME_SIZE}"
+        )));
+    }
+    Ok(max_frame_size)
+}
+
+/// Capability string advertising a max frame size of `max_frame_size` bytes
+pub fn frame_size_capability(max_frame_size: u32) -> String {
+    format!("{MAX_FRAME_SIZE_CAPABILITY}:{max_frame_size}")
+}
+
+/// Whether `cap` is a max frame size capability, valid or not
+pub fn is_frame_size_capability(cap: &str) -> bool {
+    cap.strip_prefix(MAX_FRAME_SIZE_CAPABILITY)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(':'))
+}
+
+/// Find the max frame size advertised by a peer's capabilities, if any.
+///
+/// A malformed size is read as 0, which is below [`MIN_FRAME_SIZE`] and so
+/// rejected like any other invalid size.
+pub fn parse_frame_size_capability(capabilities: &[String]) -> Option<u32> {
+    capabilities
+        .iter()
+        .find(|cap| is_frame_size_capability(cap))
+        .map(|cap| {
+            cap.strip_prefix(MAX_FRAME_SIZE_CAPABILITY)
+                .and_then(|rest| rest.strip_prefix(':'))
+                .and_then(|size| size.parse().ok())
+                .unwrap_or(0)
+        })
+}
+
+/// Capability string asking the server to replace session `session_id`
+pub fn resume_capability(session_id: Uuid) -> String {
+    format!("{RESUME_CAPABILITY}:{session_id}")
+}
+
+/// Whether `cap` is a resume capability, valid or not
+pub fn is_resume_capability(cap: &str) -> bool {
+    cap.strip_prefix(RESUME_CAPABILITY)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(':'))
+}
+
+/// Find the previous session a client asks to resume, if any. A malformed
+/// session ID resumes nothing.
+pub fn parse_resume_capability(capabilities: &[String]) -> Option<Uuid> {
+    capabilities.iter().find_map(|cap| {
+        cap.strip_prefix(RESUME_CAPABILITY)?
+            .strip_prefix(':')?
+            .parse()
+            .ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_size_capability_round_trip() {
+        let caps = vec!["basic".to_string(), frame_size_capability(65_536)];
+        assert_eq!(parse_frame_size_capability(&caps), Some(65_536));
+        assert_eq!(parse_frame_size_capability(&["basic".to_string()]), None);
+        assert_eq!(
+            parse_frame_size_capability(&["max_frame:abc".to_string()]),
+            Some(0)
+        );
+        assert_eq!(
+            parse_frame_size_capability(&["max_frame".to_string()]),
+            Some(0)
+        );
+        assert!(!is_frame_size_capability("max_frames"));
+    }
+
+    #[test]
+    fn test_resume_capability_round_trip() {
+        let session_id = Uuid::new_v4();
+        let caps = vec!["basic".to_string(), resume_capability(session_id)];
+        assert_eq!(parse_resume_capability(&caps), Some(session_id));
+        assert_eq!(parse_resume_capability(&["basic".to_string()]), None);
+        assert_eq!(
+            parse_resume_capability(&["resume:not-a-uuid".to_string()]),
+            None
+        );
+        assert!(is_resume_capability("resume:not-a-uuid"));
+        assert!(!is_resume_capability("resumed"));
+    }
+}
@@ -3,4 +3,4 @@ mod common;
 pub mod server;
 pub mod session;
 
-pub use session::{SessionStoreBackend, ShardedSessionStore};
+pub use session::{PoolPolicy, SessionSnapshot, SessionStoreBackend, ShardedSessionStore};
@@ -1,48 +1,287 @@
-use crate::auth::{constant_time_eq, validate_token_format};
+use crate::auth::{
+    validate_token_format, AuthGrant, AuthResult, Authenticator, TokenStore,
+    MAX_AUTHENTICATOR_TOKEN_LEN, MAX_TOKEN_LEN,
+};
+use crate::interceptor::{self, FrameInterceptor, SharedFrameInterceptor};
+use crate::ip_filter::IpFilter;
+use crate::recorder::FrameRecorder;
 use crate::resource_limits::{ServerResourceLimits, SessionPermit};
-use crate::stream::{Multiplexer, PrioritizedFrame};
-use crate::transport::batched_sender::run_batched_sender;
-use crate::transport::{self, BoxedStream, TransportConfig};
-use crate::tunnel::common::clamp_u128_to_u64;
-use crate::tunnel::session::{Session, SessionStoreBackend, ShardedSessionStore};
+use crate::stream::flow_control::{self, DEFAULT_STREAM_WINDOW};
+#[cfg(feature = "metrics")]
+use crate::stream::TrafficCounters;
+use crate::stream::{Multiplexer, VirtualStream};
+use crate::transport::batched_sender::{
+    frame_channel, frame_channel_capacity, run_batched_sender_with_interceptor,
+    DEFAULT_FRAME_CHANNEL_CAPACITY, DEFAULT_WRITE_TIMEOUT,
+};
+use crate::transport::listener::DEFAULT_FAST_OPEN_QUEUE;
+use crate::transport::tls::PeerIdentity;
+use crate::transport::{
+    self, AcceptedConnection, BoxedStream, ListenerConfig, SocketTuningConfig, TransportConfig,
+    TransportListener,
+};
+use crate::tunnel::common::{
+    clamp_u128_to_u64, frame_reader, frame_size_capability, is_frame_size_capability,
+    is_resume_capability, parse_frame_size_capability, parse_resume_capability,
+    validate_max_frame_size, FrameReader,
+};
+use crate::tunnel::session::{PoolPolicy, Session, SessionStoreBackend, ShardedSessionStore};
+use bytes::Bytes;
 use ferrotunnel_common::{Result, TunnelError};
 use ferrotunnel_protocol::codec::TunnelCodec;
-use ferrotunnel_protocol::constants::{MAX_PROTOCOL_VERSION, MIN_PROTOCOL_VERSION};
-use ferrotunnel_protocol::frame::{Frame, HandshakeFrame, HandshakeStatus};
+use ferrotunnel_protocol::compression::COMPRESSION_CAPABILITY_PREFIX;
+use ferrotunnel_protocol::constants::{
+    AUTH_NONCE_SIZE, CHALLENGE_AUTH_CAPABILITY, CHECKSUM_CAPABILITY, MAX_FRAME_SIZE,
+    MAX_PROTOCOL_VERSION, MIN_FRAME_SIZE, MIN_PROTOCOL_VERSION, PATH_RULES_CAPABILITY,
+    PING_CAPABILITY, PUBLIC_URL_CAPABILITY,
+};
+use ferrotunnel_protocol::frame::{
+    CloseReason, Frame, HandshakeFrame, HandshakeStatus, RegisterStatus,
+};
+use ferrotunnel_protocol::{FrameCompression, PathRules};
+use futures::future::{join_all, BoxFuture};
 use futures::{SinkExt, StreamExt};
-use kanal::bounded_async;
+use std::future::Future;
 use std::net::SocketAddr;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+use tokio::task::AbortHandle;
 use tokio_util::codec::Framed;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+/// How often a configured token file is checked for changes
+const TOKEN_FILE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Default time a connection may go without sending any frame before it is closed
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Default time an accepted connection has to finish the transport handshake
+/// (TLS, HTTP/2) before it is dropped
+pub const DEFAULT_TRANSPORT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time a connection over the session limit has to send its handshake before
+/// it is dropped without a `ServerFull` answer
+const SERVER_FULL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connections over the session limit answered with `ServerFull` at once;
+/// beyond this they are dropped without an answer
+const MAX_PENDING_REJECTIONS: usize = 64;
+
+/// Time a session's connection has to take a shutdown notice before the
+/// session is skipped
+const SHUTDOWN_NOTICE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How often the active session count is reported to metrics
+#[cfg(feature = "metrics")]
+const SESSION_METRICS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Decides whether a client certificate may register a tunnel ID; see
+/// [`TunnelServer::with_authorizer`]
+pub type Authorizer = Arc<dyn Fn(&PeerIdentity, &str) -> bool + Send + Sync>;
+
+/// Serves streams opened by clients, given the client's tunnel ID; see
+/// [`TunnelServer::on_client_stream`]
+pub type ClientStreamHandler =
+    Arc<dyn Fn(String, VirtualStream) -> BoxFuture<'static, ()> + Send + Sync>;
+
 pub struct TunnelServer {
     addr: SocketAddr,
-    auth_token: String,
+    tokens: TokenStore,
+    token_file: Option<PathBuf>,
     sessions: SessionStoreBackend,
     session_timeout: Duration,
     resource_limits: ServerResourceLimits,
+    rejections: Arc<Semaphore>,
     transport_config: TransportConfig,
+    socket_tuning: SocketTuningConfig,
+    listener_config: ListenerConfig,
+    stream_window: NonZeroU32,
+    frame_channel_capacity: usize,
+    max_frame_size: u32,
+    idle_timeout: Duration,
+    transport_handshake_timeout: Duration,
+    stream_idle_timeout: Option<Duration>,
+    write_timeout: Duration,
+    authorizer: Option<Authorizer>,
+    client_stream_handler: Option<ClientStreamHandler>,
+    ip_filter: IpFilter,
+    authenticator: Option<Arc<dyn Authenticator>>,
+    interceptor: Option<SharedFrameInterceptor>,
+    recorder: Option<Arc<FrameRecorder>>,
+    capabilities: Option<Arc<Vec<String>>>,
+    frame_compression: Arc<Vec<FrameCompression>>,
+    public_base: Option<Arc<str>>,
 }
 
 impl TunnelServer {
     pub fn new(addr: SocketAddr, auth_token: String) -> Self {
         Self {
             addr,
-            auth_token,
+            tokens: TokenStore::new(vec![auth_token]),
+            token_file: None,
             sessions: SessionStoreBackend::default(),
             session_timeout: Duration::from_secs(90),
             resource_limits: ServerResourceLimits::default(),
+            rejections: Arc::new(Semaphore::new(MAX_PENDING_REJECTIONS)),
             transport_config: TransportConfig::default(),
+            socket_tuning: SocketTuningConfig::default(),
+            listener_config: ListenerConfig::default(),
+            stream_window: NonZeroU32::new(DEFAULT_STREAM_WINDOW).unwrap_or(NonZeroU32::MIN),
+            frame_channel_capacity: DEFAULT_FRAME_CHANNEL_CAPACITY,
+            max_frame_size: MAX_FRAME_SIZE,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            transport_handshake_timeout: DEFAULT_TRANSPORT_HANDSHAKE_TIMEOUT,
+            stream_idle_timeout: None,
+            write_timeout: DEFAULT_WRITE_TIMEOUT,
+            authorizer: None,
+            client_stream_handler: None,
+            ip_filter: IpFilter::default(),
+            authenticator: None,
+            interceptor: None,
+            recorder: None,
+            capabilities: None,
+            frame_compression: Arc::new(FrameCompression::default_preference()),
+            public_base: None,
         }
     }
 
+    /// Close connections that send no frames (not even heartbeats) for `timeout`.
+    ///
+    /// Applies to the handshake and to established sessions, so a wedged client
+    /// is torn down as soon as the window passes instead of at the next stale
+    /// session sweep. Should exceed the clients' heartbeat interval.
+    #[must_use]
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Drop accepted connections that have not finished the TLS or HTTP/2
+    /// handshake within `timeout` (default:
+    /// [`DEFAULT_TRANSPORT_HANDSHAKE_TIMEOUT`]).
+    ///
+    /// The handshake runs on the connection's own task, so a slow client
+    /// never delays accepting the next one.
+    #[must_use]
+    pub fn with_transport_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.transport_handshake_timeout = timeout;
+        self
+    }
+
+    /// Fail streams that have a read or write waiting with no data moving for
+    /// `timeout`; see [`Multiplexer::with_stream_idle_timeout`]. Off by
+    /// default.
+    #[must_use]
+    pub fn with_stream_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.stream_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Close sessions whose socket accepts no data for `timeout` while frames
+    /// are waiting to be written, e.g. because the client stopped reading.
+    /// Defaults to [`DEFAULT_WRITE_TIMEOUT`].
+    #[must_use]
+    pub fn with_write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum per-stream flow control window.
+    ///
+    /// The smaller of the client and server windows is used for each session.
+    #[must_use]
+    pub fn with_stream_window(mut self, window: NonZeroU32) -> Self {
+        self.stream_window = window;
+        self
+    }
+
+    /// Set how many outgoing frames may queue per connection before senders
+    /// wait (default: [`DEFAULT_FRAME_CHANNEL_CAPACITY`]).
+    ///
+    /// Rounded up to a power of two. Worst-case memory per connection is the
+    /// capacity times the maximum frame size; see [`frame_channel_capacity`].
+    ///
+    /// Values outside `MIN_FRAME_CHANNEL_CAPACITY..=MAX_FRAME_CHANNEL_CAPACITY`
+    /// are clamped to that range.
+    #[must_use]
+    pub fn with_frame_channel_capacity(mut self, capacity: usize) -> Self {
+        self.frame_channel_capacity = frame_channel_capacity(capacity);
+        self
+    }
+
+    /// Set the largest frame, in bytes, this server accepts (default:
+    /// [`MAX_FRAME_SIZE`]).
+    ///
+    /// Each session uses the smaller of this and the limit the client
+    /// advertises in its handshake, in both directions; the negotiated value
+    /// is granted back as a `max_frame` capability. Clients that advertise
+    /// none are assumed to accept up to [`MAX_FRAME_SIZE`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TunnelError::Config`] if `max_frame_size` is outside
+    /// `MIN_FRAME_SIZE..=MAX_FRAME_SIZE`.
+    pub fn with_max_frame_size(mut self, max_frame_size: u32) -> Result<Self> {
+        self.max_frame_size = validate_max_frame_size(max_frame_size)?;
+        Ok(self)
+    }
+
+    /// Accept any of the given tokens, replacing the constructor token.
+    ///
+    /// Useful for zero-downtime rotation: add the new token, migrate clients,
+    /// then drop the old one.
+    #[must_use]
+    pub fn with_tokens(mut self, tokens: Vec<String>) -> Self {
+        self.tokens = TokenStore::new(tokens);
+        self
+    }
+
+    /// Load valid tokens from a newline-delimited file, replacing any configured tokens.
+    ///
+    /// The file is re-read whenever its modification time changes while the server runs.
+    pub fn with_token_file(mut self, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        self.tokens.reload_from_file(&path).map_err(|e| {
+            TunnelError::Config(format!("failed to read token file {}: {e}", path.display()))
+        })?;
+        self.token_file = Some(path);
+        Ok(self)
+    }
+
+    /// Currently valid tokens (shared with running connection handlers).
+    pub fn tokens(&self) -> TokenStore {
+        self.tokens.clone()
+    }
+
     /// Use a sharded session store for lower contention under many concurrent tunnel_id lookups.
     #[must_use]
     pub fn with_sharded_sessions(mut self, n_shards: usize) -> Self {
-        self.sessions = SessionStoreBackend::Sharded(ShardedSessionStore::with_shards(n_shards));
+        let pool_policy = self.sessions.pool_policy();
+        self.sessions = SessionStoreBackend::Sharded(
+            ShardedSessionStore::with_shards(n_shards).with_pool_policy(pool_policy),
+        );
+        self
+    }
+
+    /// Let several clients register the same tunnel ID and spread streams
+    /// across them with `policy`, e.g. to run redundant instances of a
+    /// service behind one hostname. Off by default: a second client claiming
+    /// a registered tunnel ID is rejected.
+    #[must_use]
+    pub fn with_pool_policy(mut self, policy: PoolPolicy) -> Self {
+        self.sessions = self.sessions.with_pool_policy(Some(policy));
+        self
+    }
+
+    /// Register sessions in an existing store, e.g. one the embedder keeps to
+    /// inspect live tunnels.
+    #[must_use]
+    pub fn with_session_store(mut self, sessions: SessionStoreBackend) -> Self {
+        self.sessions = sessions;
         self
     }
 
@@ -52,6 +291,24 @@ impl TunnelServer {
         self
     }
 
+    /// Set TCP keepalive, `TCP_USER_TIMEOUT` and `TCP_NODELAY` for accepted
+    /// connections. With `tcp_fast_open`, [`run`](Self::run) also enables
+    /// `TCP_FASTOPEN` on the control listener.
+    #[must_use]
+    pub fn with_socket_tuning(mut self, tuning: SocketTuningConfig) -> Self {
+        self.socket_tuning = tuning;
+        self
+    }
+
+    /// Set the accept backlog, `SO_REUSEADDR` and `SO_REUSEPORT` for the
+    /// control listener. With more than one accept task, [`run`](Self::run)
+    /// binds that many listeners on the address and accepts on each.
+    #[must_use]
+    pub fn with_listener_config(mut self, config: ListenerConfig) -> Self {
+        self.listener_config = config;
+        self
+    }
+
     #[must_use]
     pub fn with_transport(mut self, config: TransportConfig) -> Self {
         self.transport_config = config;
@@ -60,11 +317,7 @@ impl TunnelServer {
 
     /// Configure TLS for the server using certificate and key files.
     #[must_use]
-    pub fn with_tls(
-        mut self,
-        cert_path: impl Into<std::path::PathBuf>,
-        key_path: impl Into<std::path::PathBuf>,
-    ) -> Self {
+    pub fn with_tls(mut self, cert_path: impl Into<PathBuf>, key_path: impl Into<PathBuf>) -> Self {
         let (cert, key) = (
             cert_path.into().to_string_lossy().to_string(),
             key_path.into().to_string_lossy().to_string(),
@@ -85,7 +338,7 @@ impl TunnelServer {
 
     /// Enable client certificate authentication (Mutual TLS) using the provided CA.
     #[must_use]
-    pub fn with_client_auth(mut self, ca_path: impl Into<std::path::PathBuf>) -> Self {
+    pub fn with_client_auth(mut self, ca_path: impl Into<PathBuf>) -> Self {
         let ca = Some(ca_path.into().to_string_lossy().to_string());
         if let TransportConfig::Tls(ref mut tls) = self.transport_config {
             tls.ca_cert_path = ca;
@@ -100,20 +353,226 @@ impl TunnelServer {
         self
     }
 
+    /// Let reconnecting clients resume their previous TLS session.
+    ///
+    /// Early data (0-RTT) is always rejected by the server.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TunnelError::Config`] unless TLS was already configured with
+    /// [`with_tls`](Self::with_tls).
+    pub fn with_tls_session_resumption(mut self) -> Result<Self> {
+        let TransportConfig::Tls(ref mut tls) = self.transport_config else {
+            return Err(TunnelError::Config(
+                "TLS session resumption requires a TLS transport".into(),
+            ));
+        };
+        tls.session_resumption = true;
+        Ok(self)
+    }
+
+    /// Only let a client register a tunnel ID when `authorize(identity,
+    /// tunnel_id)` returns true for its TLS client certificate.
+    ///
+    /// Use together with [`with_client_auth`](Self::with_client_auth): once an
+    /// authorizer is set, clients that present no certificate are rejected.
+    /// Rejected clients get [`HandshakeStatus::Unauthorized`].
+    #[must_use]
+    pub fn with_authorizer<F>(mut self, authorize: F) -> Self
+    where
+        F: Fn(&PeerIdentity, &str) -> bool + Send + Sync + 'static,
+    {
+        self.authorizer = Some(Arc::new(authorize));
+        self
+    }
+
+    /// Serve streams that clients open (with
+    /// [`TunnelClient::open_stream`](crate::TunnelClient::open_stream)) with
+    /// `handler`, called with the client's tunnel ID on a task of its own for
+    /// every stream.
+    ///
+    /// Without a handler such streams are closed with an error as soon as
+    /// they are opened.
+    #[must_use]
+    pub fn on_client_stream<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(String, VirtualStream) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.client_stream_handler = Some(Arc::new(move |tunnel_id, stream| {
+            Box::pin(handler(tunnel_id, stream))
+        }));
+        self
+    }
+
+    /// Authenticate handshakes with `authenticator` instead of the static
+    /// token list.
+    ///
+    /// Tokens from [`new`](Self::new), [`with_tokens`](Self::with_tokens) and
+    /// [`with_token_file`](Self::with_token_file) are then not consulted. The
+    /// returned [`AuthGrant`](crate::auth::AuthGrant) can restrict which
+    /// tunnel IDs and routing capabilities the client may use. Tokens of up to
+    /// [`MAX_AUTHENTICATOR_TOKEN_LEN`] bytes are passed to it, instead of the
+    /// [`MAX_TOKEN_LEN`] the static list allows.
+    #[must_use]
+    pub fn with_authenticator<A>(mut self, authenticator: A) -> Self
+    where
+        A: Authenticator + 'static,
+    {
+        self.authenticator = Some(Arc::new(authenticator));
+        self
+    }
+
+    /// Show every frame of established sessions to `interceptor`: outgoing
+    /// frames as they are encoded, incoming ones before they are handled.
+    #[must_use]
+    pub fn with_frame_interceptor(mut self, interceptor: Arc<dyn FrameInterceptor>) -> Self {
+        self.interceptor = Some(interceptor);
+        self
+    }
+
+    /// Record every frame of established sessions to a capture file at `path`, replacing
+    /// any existing file; see [`FrameRecorder`]. Runs alongside a
+    /// [frame interceptor](Self::with_frame_interceptor).
+    pub fn with_frame_recorder(mut self, path: impl Into<PathBuf>) -> Result<Self> {
+        self.recorder = Some(Arc::new(FrameRecorder::create(path)?));
+        Ok(self)
+    }
+
+    /// The recorder set with [`with_frame_recorder`](Self::with_frame_recorder),
+    /// e.g. to flush it
+    pub fn frame_recorder(&self) -> Option<Arc<FrameRecorder>> {
+        self.recorder.clone()
+    }
+
+    /// Only grant clients the listed capabilities (e.g. `"basic"`, `"udp"`).
+    ///
+    /// Each client gets the intersection of what it advertises and this list,
+    /// returned in its `HandshakeAck`; leave `"tcp"`, `"udp"` or
+    /// `"websocket"` out to keep that kind of stream away from every client.
+    /// Flow control is negotiated separately.
+    /// By default every advertised capability is granted.
+    #[must_use]
+    pub fn with_capabilities(mut self, capabilities: Vec<String>) -> Self {
+        self.capabilities = Some(Arc::new(capabilities));
+        self
+    }
+
+    /// Compress data frames with the first of `preference` that the client
+    /// offers, e.g. lz4 first for latency or zstd first for bandwidth; an
+    /// empty list turns compression off.
+    ///
+    /// Defaults to every algorithm this build has, in
+    /// [`FrameCompression::default_preference`] order. Clients only get
+    /// compression when they ask for it.
+    #[must_use]
+    pub fn with_frame_compression(mut self, preference: Vec<FrameCompression>) -> Self {
+        self.frame_compression = Arc::new(preference);
+        self
+    }
+
+    /// Tell clients where their tunnel is reachable: a tunnel is published as
+    /// a subdomain of `base`, so `https://tunnel.example.com` gives tunnel
+    /// `myapp` the URL `https://myapp.tunnel.example.com`.
+    ///
+    /// Should match the ingress `base_domain` and the scheme and port the
+    /// ingress is reachable on from outside. A base without a scheme uses
+    /// `http`. Only clients advertising the `public_url` capability are sent
+    /// their URL.
+    #[must_use]
+    pub fn with_public_base(mut self, base: impl Into<String>) -> Self {
+        let base = base.into();
+        self.public_base = Some(Arc::from(base.trim_end_matches('/')));
+        self
+    }
+
+    /// Only accept control connections from peers that `filter` allows.
+    ///
+    /// Checked right after the TCP accept, before TLS and the handshake, so
+    /// untrusted networks cannot spend server resources on either.
+    #[must_use]
+    pub fn with_ip_filter(mut self, filter: IpFilter) -> Self {
+        self.ip_filter = filter;
+        self
+    }
+
     pub fn sessions(&self) -> SessionStoreBackend {
         self.sessions.clone()
     }
 
     pub async fn run(self) -> Result<()> {
-        let listener = TcpListener::bind(self.addr).await?;
-        info!("Server listening on {}", self.addr);
+        let listeners = TransportListener::bind_all(
+            &self.transport_config,
+            self.addr,
+            &self.listener_options(),
+        )?;
+        self.serve_all(listeners).await
+    }
+
+    /// Listener options, with Fast Open enabled when the socket tuning asks
+    /// for it, so clients connecting with Fast Open get cookies from us
+    fn listener_options(&self) -> ListenerConfig {
+        let mut options = self.listener_config.clone();
+        if self.socket_tuning.tcp_fast_open && options.fast_open_queue.is_none() {
+            options.fast_open_queue = Some(DEFAULT_FAST_OPEN_QUEUE);
+        }
+        options
+    }
+
+    /// Serve connections from an already bound `listener` instead of binding
+    /// the configured address
+    ///
+    /// Lets embedders bind port 0 up front and learn the assigned port before
+    /// the server runs.
+    pub async fn serve(self, listener: TransportListener) -> Result<()> {
+        self.serve_all(vec![listener]).await
+    }
+
+    /// Serve connections from several already bound listeners, e.g. ones
+    /// sharing a port through `SO_REUSEPORT`, with an accept loop for each
+    pub async fn serve_all(self, listeners: Vec<TransportListener>) -> Result<()> {
+        if self.resource_limits.max_streams_per_session == 0 {
+            return Err(TunnelError::Config(
+                "max_streams_per_session must be greater than zero".into(),
+            ));
+        }
+        let mut listeners = listeners.into_iter();
+        let Some(first) = listeners.next() else {
+            return Err(TunnelError::Config("no listeners to serve".into()));
+        };
+        let rest: Vec<TransportListener> = listeners.collect();
+        for listener in std::iter::once(&first).chain(&rest) {
+            match listener {
+                TransportListener::Memory(_) => info!("Server accepting in-memory connections"),
+                TransportListener::Tcp(tcp) => {
+                    info!("Server listening on {}", tcp.local_addr()?);
+                }
+            }
+        }
 
         let sessions = self.sessions.clone();
         let timeout = self.session_timeout;
+        let interceptor = interceptor::chain(
+            self.interceptor.clone(),
+            self.recorder
+                .clone()
+                .map(|recorder| recorder as SharedFrameInterceptor),
+        );
+
+        // Background tasks stop with the server, including when this future
+        // is dropped instead of returning
+        let mut tasks = AbortOnDrop::default();
+        if let Some(path) = &self.token_file {
+            tasks.push(
+                self.tokens
+                    .spawn_file_watcher(path.clone(), TOKEN_FILE_POLL_INTERVAL)
+                    .abort_handle(),
+            );
+        }
 
         // Spawn session cleanup task
         let cleanup_sessions = sessions.clone();
-        tokio::spawn(async move {
+        let cleanup = tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(30));
             loop {
                 interval.tick().await;
@@ -123,25 +582,80 @@ impl TunnelServer {
                 }
             }
         });
+        tasks.push(cleanup.abort_handle());
 
+        #[cfg(feature = "metrics")]
+        {
+            let metrics_sessions = sessions.clone();
+            let metrics = tokio::spawn(async move {
+                let mut interval = tokio::time::interval(SESSION_METRICS_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Some(m) = ferrotunnel_observability::tunnel_metrics() {
+                        m.set_active_sessions(metrics_sessions.count());
+                    }
+                }
+            });
+            tasks.push(metrics.abort_handle());
+        }
+
+        let server = Arc::new(self);
+        for listener in rest {
+            let accept = tokio::spawn(server.clone().accept_loop(listener, interceptor.clone()));
+            tasks.push(accept.abort_handle());
+        }
+        server.accept_loop(first, interceptor).await
+    }
+
+    /// Accept connections from `listener` and serve each on its own task
+    async fn accept_loop(
+        self: Arc<Self>,
+        listener: TransportListener,
+        interceptor: Option<SharedFrameInterceptor>,
+    ) -> Result<()> {
         loop {
-            match transport::accept(&self.transport_config, &listener).await {
-                Ok((stream, addr)) => {
+            match listener.accept_connection(&self.ip_filter).await {
+                Ok(accepted) => {
+                    let addr = accepted.peer_addr();
                     let session_permit = match self.resource_limits.try_acquire_session() {
                         Ok(permit) => permit,
                         Err(e) => {
                             warn!("Rejecting connection from {}: {}", addr, e);
+                            // Under a flood, drop the rest instead of holding
+                            // a task and socket for each
+                            if let Ok(permit) = self.rejections.clone().try_acquire_owned() {
+                                let server = self.clone();
+                                tokio::spawn(async move {
+                                    if let Some((stream, addr, _)) =
+                                        server.upgrade(accepted, SERVER_FULL_TIMEOUT).await
+                                    {
+                                        reject_server_full(stream, addr).await;
+                                    }
+                                    drop(permit);
+                                });
+                            }
                             continue;
                         }
                     };
 
-                    let sessions = sessions.clone();
-                    let token = self.auth_token.clone();
-
+                    let server = self.clone();
+                    let interceptor = interceptor.clone();
                     tokio::spawn(async move {
-                        if let Err(e) =
-                            Self::handle_connection(stream, addr, sessions, token, session_permit)
-                                .await
+                        let timeout = server.transport_handshake_timeout;
+                        let Some((stream, addr, peer_identity)) =
+                            server.upgrade(accepted, timeout).await
+                        else {
+                            return;
+                        };
+                        if let Err(e) = server
+                            .handle_connection(
+                                stream,
+                                addr,
+                                peer_identity,
+                                interceptor,
+                                session_permit,
+                            )
+                            .await
                         {
                             warn!("Connection error for {}: {}", addr, e);
                         }
@@ -154,18 +668,76 @@ impl TunnelServer {
         }
     }
 
+    /// Run the transport handshake on an accepted connection, giving up after
+    /// `timeout`; failures are logged and yield `None`
+    async fn upgrade(
+        &self,
+        accepted: AcceptedConnection,
+        timeout: Duration,
+    ) -> Option<(BoxedStream, SocketAddr, Option<PeerIdentity>)> {
+        let addr = accepted.peer_addr();
+        let upgrade = accepted.upgrade(&self.transport_config, &self.socket_tuning);
+        match tokio::time::timeout(timeout, upgrade).await {
+            Ok(Ok(upgraded)) => Some(upgraded),
+            Ok(Err(e)) => {
+                warn!("Transport handshake with {} failed: {}", addr, e);
+                None
+            }
+            Err(_) => {
+                warn!(
+                    "Transport handshake with {} timed out after {:?}",
+                    addr, timeout
+                );
+                None
+            }
+        }
+    }
+
+    /// Run the handshake and then the session for one accepted connection
     #[allow(clippy::too_many_lines)]
     async fn handle_connection(
+        self: Arc<Self>,
         stream: BoxedStream,
         addr: SocketAddr,
-        sessions: SessionStoreBackend,
-        expected_token: String,
+        peer_identity: Option<PeerIdentity>,
+        interceptor: Option<SharedFrameInterceptor>,
         _session_permit: SessionPermit,
     ) -> Result<()> {
-        let mut framed = Framed::new(stream, TunnelCodec::new());
+        let sessions = self.sessions.clone();
+        let max_token_len = if self.authenticator.is_some() {
+            MAX_AUTHENTICATOR_TOKEN_LEN
+        } else {
+            MAX_TOKEN_LEN
+        };
+        let authenticator = self
+            .authenticator
+            .clone()
+            .unwrap_or_else(|| Arc::new(self.tokens.clone()));
+        let authorizer = self.authorizer.clone();
+        let client_stream_handler = self.client_stream_handler.clone();
+        let max_stream_window = self.stream_window;
+        let frame_channel_capacity = self.frame_channel_capacity;
+        let max_frame_size = self.max_frame_size;
+        let idle_timeout = self.idle_timeout;
+        let max_streams = NonZeroUsize::new(self.resource_limits.max_streams_per_session);
+        let stream_idle_timeout = self.stream_idle_timeout;
+        let write_timeout = self.write_timeout;
+        let supported_capabilities = self.capabilities.clone();
+        let frame_compression = self.frame_compression.clone();
+        let public_base = self.public_base.clone();
+        let handshake_start = Instant::now();
+        let codec = TunnelCodec::with_max_frame_size(max_frame_size as usize);
+        let mut framed = Framed::new(stream, codec);
 
         // 1. Handshake
-        if let Some(result) = framed.next().await {
+        let Ok(first_frame) = tokio::time::timeout(idle_timeout, framed.next()).await else {
+            warn!(
+                "No handshake from {} within {:?}, closing",
+                addr, idle_timeout
+            );
+            return Err(TunnelError::Timeout("handshake not received".into()));
+        };
+        if let Some(result) = first_frame {
             let frame = result?;
             match frame {
                 Frame::Handshake(handshake) => {
@@ -176,7 +748,31 @@ impl TunnelServer {
                         tunnel_id,
                         capabilities,
                     } = *handshake;
-                    if let Err(e) = validate_token_format(&token, 256) {
+                    // Sent by the client without waiting for a reply, so read
+                    // before anything else is exchanged
+                    let has_path_rules =
+                        capabilities.iter().any(|cap| cap == PATH_RULES_CAPABILITY);
+                    let path_rules = if has_path_rules {
+                        read_path_rules(&mut framed, idle_timeout).await?
+                    } else {
+                        PathRules::default()
+                    };
+                    let client_max_frame_size = parse_frame_size_capability(&capabilities);
+                    let resume_session_id = parse_resume_capability(&capabilities);
+                    // A client answering a challenge proves its token without
+                    // sending it, so its handshake carries none
+                    let challenge = capabilities
+                        .iter()
+                        .any(|cap| cap == CHALLENGE_AUTH_CAPABILITY)
+                        && supported_capabilities.as_deref().is_none_or(|supported| {
+                            supported.iter().any(|cap| cap == CHALLENGE_AUTH_CAPABILITY)
+                        });
+                    let token_format = if challenge {
+                        Ok(())
+                    } else {
+                        validate_token_format(&token, max_token_len)
+                    };
+                    if let Err(e) = token_format {
                         warn!("Invalid token format from {}: {}", addr, e);
                         framed
                             .send(Frame::HandshakeAck {
@@ -186,19 +782,69 @@ impl TunnelServer {
                                 server_capabilities: vec![],
                             })
                             .await?;
+                        record_handshake(HandshakeStatus::InvalidToken, handshake_start);
                         return Ok(());
                     }
 
-                    if !constant_time_eq(token.as_bytes(), expected_token.as_bytes()) {
-                        warn!("Invalid token from {}", addr);
+                    let auth = if challenge {
+                        challenge_client(
+                            &mut framed,
+                            authenticator.as_ref(),
+                            tunnel_id.as_deref(),
+                            addr,
+                            idle_timeout,
+                        )
+                        .await?
+                    } else {
+                        authenticator
+                            .authenticate(&token, tunnel_id.as_deref(), addr)
+                            .await
+                    };
+                    let grant = match auth {
+                        AuthResult::Granted(grant) => grant,
+                        AuthResult::Denied => {
+                            warn!("Invalid token from {}", addr);
+                            framed
+                                .send(Frame::HandshakeAck {
+                                    status: HandshakeStatus::InvalidToken,
+                                    session_id: Uuid::nil(),
+                                    version: 0,
+                                    server_capabilities: vec![],
+                                })
+                                .await?;
+                            record_handshake(HandshakeStatus::InvalidToken, handshake_start);
+                            return Ok(());
+                        }
+                    };
+
+                    if let Err(e) = path_rules.validate() {
+                        warn!("Invalid path rules from {}: {}", addr, e);
                         framed
                             .send(Frame::HandshakeAck {
-                                status: HandshakeStatus::InvalidToken,
+                                status: HandshakeStatus::InvalidPathRules,
+                                session_id: Uuid::nil(),
+                                version: 0,
+                                server_capabilities: vec![],
+                            })
+                            .await?;
+                        record_handshake(HandshakeStatus::InvalidPathRules, handshake_start);
+                        return Ok(());
+                    }
+
+                    if let Some(size @ ..MIN_FRAME_SIZE) = client_max_frame_size {
+                        warn!(
+                            "Max frame size {} from {} is below the minimum {}",
+                            size, addr, MIN_FRAME_SIZE
+                        );
+                        framed
+                            .send(Frame::HandshakeAck {
+                                status: HandshakeStatus::InvalidFrameSize,
                                 session_id: Uuid::nil(),
                                 version: 0,
                                 server_capabilities: vec![],
                             })
                             .await?;
+                        record_handshake(HandshakeStatus::InvalidFrameSize, handshake_start);
                         return Ok(());
                     }
 
@@ -215,6 +861,7 @@ impl TunnelServer {
                                     server_capabilities: vec![],
                                 })
                                 .await?;
+                            record_handshake(HandshakeStatus::VersionMismatch, handshake_start);
                             return Ok(());
                         }
                     };
@@ -230,67 +877,248 @@ impl TunnelServer {
                     // Determine tunnel ID: prefer requested, fallback to random session ID
                     let tunnel_id = tunnel_id.unwrap_or_else(|| session_id.to_string());
 
-                    // Setup multiplexer with kanal channels
-                    let parts = framed.into_parts();
-                    let (read_half, write_half) = tokio::io::split(parts.io);
+                    if !grant.allows_tunnel_id(&tunnel_id) {
+                        warn!(
+                            "Client {} is not authorized for tunnel '{}'",
+                            addr, tunnel_id
+                        );
+                        framed
+                            .send(Frame::HandshakeAck {
+                                status: HandshakeStatus::Unauthorized,
+                                session_id: Uuid::nil(),
+                                version: 0,
+                                server_capabilities: vec![],
+                            })
+                            .await?;
+                        record_handshake(HandshakeStatus::Unauthorized, handshake_start);
+                        return Ok(());
+                    }
 
-                    // Reconstruct FramedRead for reading, preserving any buffered data.
-                    // Dropping read_buf causes decoder desync ("Frame too large: 2021161080").
-                    let mut stream = tokio_util::codec::FramedRead::new(read_half, parts.codec);
-                    if !parts.read_buf.is_empty() {
-                        stream.read_buffer_mut().extend_from_slice(&parts.read_buf);
+                    if let Some(authorize) = &authorizer {
+                        let allowed = peer_identity
+                            .as_ref()
+                            .is_some_and(|identity| authorize(identity, &tunnel_id));
+                        if !allowed {
+                            warn!(
+                                "Client {} ({:?}) is not authorized for tunnel '{}'",
+                                addr,
+                                peer_identity.as_ref().map(|identity| &identity.subject),
+                                tunnel_id
+                            );
+                            framed
+                                .send(Frame::HandshakeAck {
+                                    status: HandshakeStatus::Unauthorized,
+                                    session_id: Uuid::nil(),
+                                    version: 0,
+                                    server_capabilities: vec![],
+                                })
+                                .await?;
+                            record_handshake(HandshakeStatus::Unauthorized, handshake_start);
+                            return Ok(());
+                        }
                     }
 
-                    let (frame_tx, frame_rx) = bounded_async::<PrioritizedFrame>(1024);
+                    // Both peers accept frames up to the smaller limit; clients
+                    // that do not advertise one accept the protocol maximum
+                    let max_frame_size = client_max_frame_size
+                        .unwrap_or(MAX_FRAME_SIZE)
+                        .min(max_frame_size);
+                    *framed.codec_mut() = TunnelCodec::with_max_frame_size(max_frame_size as usize);
 
-                    // Spawn batched sender task for vectored I/O performance
-                    tokio::spawn(run_batched_sender(frame_rx, write_half, parts.codec));
+                    let (frame_tx, frame_rx) = frame_channel(frame_channel_capacity);
 
-                    let (multiplexer, new_stream_rx) = Multiplexer::new(frame_tx, false);
+                    // Flow control only when the client supports it, using the smaller window
+                    let stream_window = flow_control::parse_capability(&capabilities)
+                        .map(|client_window| client_window.min(max_stream_window));
+                    let (multiplexer, new_stream_rx) = match stream_window {
+                        Some(window) => Multiplexer::with_flow_control(frame_tx, false, window),
+                        None => Multiplexer::new(frame_tx, false),
+                    };
+                    let mut multiplexer = multiplexer.with_max_frame_size(max_frame_size as usize);
+                    if capabilities.iter().any(|cap| cap == PING_CAPABILITY) {
+                        multiplexer = multiplexer.with_ping();
+                    }
+                    // Never unset: serve_all() rejects a limit of 0
+                    if let Some(max_streams) = max_streams {
+                        multiplexer = multiplexer.with_max_streams(max_streams);
+                    }
+                    if let Some(timeout) = stream_idle_timeout {
+                        multiplexer = multiplexer.with_stream_idle_timeout(timeout);
+                    }
+                    #[cfg(feature = "metrics")]
+                    {
+                        multiplexer = multiplexer
+                            .with_traffic(TrafficCounters::new().reported_as(&tunnel_id));
+                    }
 
-                    // Log unexpected streams from client (for now)
+                    let stream_tunnel_id = tunnel_id.clone();
                     tokio::spawn(async move {
-                        while let Ok(_stream) = new_stream_rx.recv().await {
-                            warn!("Client tried to open stream (not supported in MVP)");
+                        while let Ok(stream) = new_stream_rx.recv().await {
+                            match &client_stream_handler {
+                                Some(handler) => {
+                                    tokio::spawn(handler(stream_tunnel_id.clone(), stream));
+                                }
+                                None => {
+                                    warn!(
+                                        "No handler for stream {} opened by tunnel '{}'",
+                                        stream.id(),
+                                        stream_tunnel_id
+                                    );
+                                    let mut stream = stream;
+                                    let reason =
+                                        CloseReason::Error("no client stream handler".into());
+                                    if let Err(e) = stream.close_with_reason(reason).await {
+                                        debug!(
+                                            "Failed to close stream {} of tunnel '{}': {}",
+                                            stream.id(),
+                                            stream_tunnel_id,
+                                            e
+                                        );
+                                    }
+                                }
+                            }
                         }
                     });
 
+                    // Only a server that knows where tunnels are published has
+                    // a URL to send
+                    let tunnel_url = public_base
+                        .as_deref()
+                        .filter(|_| capabilities.iter().any(|cap| cap == PUBLIC_URL_CAPABILITY))
+                        .map(|base| public_url(base, &tunnel_id));
+                    let mut granted = negotiate_capabilities(
+                        capabilities,
+                        supported_capabilities.as_deref().map(Vec::as_slice),
+                        &grant,
+                        stream_window,
+                        client_max_frame_size.map(|_| max_frame_size),
+                        &frame_compression,
+                    );
+                    if tunnel_url.is_some() {
+                        granted.push(PUBLIC_URL_CAPABILITY.to_string());
+                    }
+                    let checksum = granted.iter().any(|cap| cap == CHECKSUM_CAPABILITY);
+                    let compression = FrameCompression::from_capabilities(&granted);
+                    // A challenged client's token field was never checked, so
+                    // only its client ID may identify it when resuming
                     let session = Session::new(
                         session_id,
                         tunnel_id.clone(),
                         addr,
-                        token,
-                        capabilities,
+                        if challenge { String::new() } else { token },
+                        granted.clone(),
                         Some(multiplexer.clone()),
-                    );
+                    )
+                    .with_peer_identity(peer_identity)
+                    .with_client_id(grant.client_id.clone())
+                    .with_path_rules(path_rules)
+                    .with_max_in_flight_requests(grant.max_in_flight_requests);
 
-                    if let Err(e) = sessions.add(session) {
-                        warn!("Failed to register session: {}", e);
-                        multiplexer
-                            .send_frame(Frame::HandshakeAck {
-                                status: HandshakeStatus::TunnelIdTaken,
-                                session_id,
-                                version: 0,
-                                server_capabilities: vec![],
-                            })
-                            .await?;
-                        return Err(TunnelError::Protocol(format!(
-                            "Tunnel ID '{tunnel_id}' already in use"
-                        )));
+                    let replaced = match sessions.add_or_resume(session, resume_session_id) {
+                        Ok(replaced) => replaced,
+                        Err(e) => {
+                            warn!("Failed to register session: {}", e);
+                            framed
+                                .send(Frame::HandshakeAck {
+                                    status: HandshakeStatus::TunnelIdTaken,
+                                    session_id,
+                                    version: 0,
+                                    server_capabilities: vec![],
+                                })
+                                .await?;
+                            record_handshake(HandshakeStatus::TunnelIdTaken, handshake_start);
+                            return Err(TunnelError::Protocol(format!(
+                                "Tunnel ID '{tunnel_id}' already in use"
+                            )));
+                        }
+                    };
+                    // The client reconnected before its old connection timed
+                    // out; hang that one up instead of waiting for it
+                    if let Some(old) = replaced {
+                        info!(
+                            "Session {} replaces session {} for tunnel '{}'",
+                            session_id, old.id, tunnel_id
+                        );
+                        if let Some(old_multiplexer) = &old.multiplexer {
+                            old_multiplexer.close();
+                        }
                     }
 
                     info!("Session established: {}", session_id);
-                    multiplexer
-                        .send_frame(Frame::HandshakeAck {
-                            status: HandshakeStatus::Success,
-                            session_id,
-                            version: negotiated_version,
-                            server_capabilities: vec!["basic".to_string()],
-                        })
-                        .await?;
+                    // Sent before the batched sender starts, so the ack goes out
+                    // without a checksum and ahead of frames already queued for
+                    // the new session
+                    let ack = Frame::HandshakeAck {
+                        status: HandshakeStatus::Success,
+                        session_id,
+                        version: negotiated_version,
+                        server_capabilities: granted,
+                    };
+                    if let Some(interceptor) = &interceptor {
+                        interceptor.on_send(&ack);
+                    }
+                    framed.send(ack).await?;
+                    // Granting `public_url` promises the URL right after the ack
+                    if let Some(public_url) = tunnel_url {
+                        let register_ack = Frame::RegisterAck {
+                            public_url,
+                            status: RegisterStatus::Success,
+                        };
+                        if let Some(interceptor) = &interceptor {
+                            interceptor.on_send(&register_ack);
+                        }
+                        framed.send(register_ack).await?;
+                    }
+                    record_handshake(HandshakeStatus::Success, handshake_start);
 
-                    // Enter message loop
-                    Self::process_messages(stream, session_id, sessions, multiplexer).await?;
+                    // Every later frame carries a CRC32 when the client asked for
+                    // it, and data frames are compressed once an algorithm is agreed
+                    let codec = framed
+                        .codec()
+                        .with_checksum(checksum)
+                        .with_compression(compression);
+                    let parts = framed.into_parts();
+                    let (read_half, write_half) = tokio::io::split(parts.io);
+
+                    // Keep any buffered data: dropping read_buf causes decoder
+                    // desync ("Frame too large: 2021161080").
+                    let stream = frame_reader(read_half, codec, parts.read_buf);
+
+                    // Spawn batched sender task for vectored I/O performance
+                    let mut sender_task = tokio::spawn(run_batched_sender_with_interceptor(
+                        frame_rx,
+                        write_half,
+                        codec,
+                        interceptor.clone(),
+                        write_timeout,
+                    ));
+
+                    // Enter message loop, ending the session if the sender gives up
+                    let result = tokio::select! {
+                        result = Self::process_messages(
+                            stream,
+                            session_id,
+                            sessions.clone(),
+                            multiplexer.clone(),
+                            idle_timeout,
+                            interceptor,
+                        ) => result,
+                        sent = &mut sender_task => match sent {
+                            Ok(Err(e)) => {
+                                warn!("Session {} can no longer be written to: {}", session_id, e);
+                                sessions.remove(&session_id);
+                                multiplexer.close();
+                                Err(e)
+                            }
+                            // Every frame sender is gone, so the session already is
+                            Ok(Ok(())) => Ok(()),
+                            Err(e) => Err(TunnelError::Connection(e.to_string())),
+                        },
+                    };
+                    // Drop the write half too so the connection actually closes,
+                    // even while streams still hold the multiplexer
+                    sender_task.abort();
+                    result?;
                 }
                 _ => {
                     return Err(TunnelError::Protocol("Expected handshake".into()));
@@ -304,17 +1132,36 @@ impl TunnelServer {
     }
 
     async fn process_messages(
-        mut stream: tokio_util::codec::FramedRead<tokio::io::ReadHalf<BoxedStream>, TunnelCodec>,
+        mut stream: FrameReader,
         session_id: Uuid,
         sessions: SessionStoreBackend,
         multiplexer: Multiplexer,
+        idle_timeout: Duration,
+        interceptor: Option<SharedFrameInterceptor>,
     ) -> Result<()> {
         loop {
             #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
             let decode_start = Instant::now();
-            let result = stream.next().await;
+            let next = tokio::select! {
+                next = tokio::time::timeout(idle_timeout, stream.next()) => next,
+                () = multiplexer.closed() => {
+                    // Already removed from the store by whatever replaced it
+                    info!("Session {} was replaced, closing its connection", session_id);
+                    return Ok(());
+                }
+            };
+            let Ok(result) = next else {
+                warn!(
+                    "No frames from session {} for {:?}, closing idle connection",
+                    session_id, idle_timeout
+                );
+                break;
+            };
             let Some(frame_result) = result else { break };
             let frame = frame_result?;
+            if let Some(interceptor) = &interceptor {
+                interceptor.on_recv(&frame);
+            }
 
             #[cfg(feature = "metrics")]
             if let Some(m) = ferrotunnel_observability::tunnel_metrics() {
@@ -326,24 +1173,16 @@ impl TunnelServer {
             }
 
             // Update heartbeat for any activity
-            if let Some(mut session) = sessions.get_mut(&session_id) {
-                session.update_heartbeat();
-            } else {
+            if !sessions.record_heartbeat(&session_id) {
                 // Session removed (shutdown/timeout)
                 return Err(TunnelError::Protocol("Session not found".into()));
             }
 
             match frame {
-                Frame::Heartbeat { .. } => {
+                Frame::Heartbeat { timestamp } => {
+                    // Echo the client's timestamp so it can compute the RTT
                     multiplexer
-                        .send_frame(Frame::HeartbeatAck {
-                            timestamp: clamp_u128_to_u64(
-                                std::time::SystemTime::now()
-                                    .duration_since(std::time::UNIX_EPOCH)
-                                    .unwrap_or_default()
-                                    .as_millis(),
-                            ),
-                        })
+                        .send_frame(Frame::HeartbeatAck { timestamp })
                         .await?;
                 }
                 _ => {
@@ -358,6 +1197,208 @@ impl TunnelServer {
     }
 }
 
+/// Tell every connected client that the server is about to go away.
+///
+/// Clients log `reason` and, when `reconnect_after` is given, wait that long
+/// before reconnecting instead of using their own backoff. Call this before
+/// stopping the server; the notices are queued on each session's connection.
+/// Sessions are notified concurrently, and one whose connection does not take
+/// the notice within a second is skipped, so a stalled client cannot hold up
+/// the shutdown. Returns how many clients were notified.
+pub async fn announce_shutdown(
+    sessions: &SessionStoreBackend,
+    reason: &str,
+    reconnect_after: Option<Duration>,
+) -> usize {
+    let reconnect_after_ms = reconnect_after.map(|delay| clamp_u128_to_u64(delay.as_millis()));
+
+    let notices = sessions
+        .snapshot()
+        .into_iter()
+        .filter_map(|session| session.multiplexer)
+        .map(|multiplexer| async move {
+            let frame = Frame::Shutdown {
+                reason: reason.to_string(),
+                reconnect_after_ms,
+            };
+            matches!(
+                tokio::time::timeout(SHUTDOWN_NOTICE_TIMEOUT, multiplexer.send_frame(frame)).await,
+                Ok(Ok(()))
+            )
+        });
+    join_all(notices)
+        .await
+        .into_iter()
+        .filter(|sent| *sent)
+        .count()
+}
+
+/// Capabilities granted to a client: those it advertised that the server
+/// supports and its auth grant allows, plus the agreed flow control window
+/// and max frame size in place of the ones the client offered
+fn negotiate_capabilities(
+    advertised: Vec<String>,
+    supported: Option<&[String]>,
+    grant: &AuthGrant,
+    stream_window: Option<NonZeroU32>,
+    max_frame_size: Option<u32>,
+    compression: &[FrameCompression],
+) -> Vec<String> {
+    // Protocol features are granted whatever the token allows
+    let ping = advertised.iter().any(|cap| cap == PING_CAPABILITY);
+    let (offered_compression, mut granted): (Vec<String>, Vec<String>) = grant
+        .filter_capabilities(advertised)
+        .into_iter()
+        .filter(|cap| {
+            !flow_control::is_capability(cap)
+                && !is_frame_size_capability(cap)
+                && !is_resume_capability(cap)
+                && cap != PATH_RULES_CAPABILITY
+                && cap != PUBLIC_URL_CAPABILITY
+                && cap != PING_CAPABILITY
+        })
+        .filter(|cap| supported.is_none_or(|supported| supported.contains(cap)))
+        .partition(|cap| cap.starts_with(COMPRESSION_CAPABILITY_PREFIX));
+    granted.extend(stream_window.map(flow_control::capability));
+    granted.extend(max_frame_size.map(frame_size_capability));
+    if ping {
+        granted.push(PING_CAPABILITY.to_string());
+    }
+    granted.extend(
+        FrameCompression::negotiate(&offered_compression, compression)
+            .map(FrameCompression::capability),
+    );
+    granted
+}
+
+/// Send a client that asked for challenge auth a fresh nonce and check its
+/// answer with `authenticator`
+async fn challenge_client(
+    framed: &mut Framed<BoxedStream, TunnelCodec>,
+    authenticator: &dyn Authenticator,
+    tunnel_id: Option<&str>,
+    addr: SocketAddr,
+    timeout: Duration,
+) -> Result<AuthResult> {
+    let nonce: [u8; AUTH_NONCE_SIZE] = rand::random();
+    framed
+        .send(Frame::AuthChallenge {
+            nonce: Bytes::copy_from_slice(&nonce),
+        })
+        .await?;
+    match tokio::time::timeout(timeout, framed.next()).await {
+        Ok(Some(Ok(Frame::AuthResponse { mac }))) => Ok(authenticator
+            .authenticate_challenge(&nonce, &mac, tunnel_id, addr)
+            .await),
+        Ok(Some(Ok(_))) => {
+            warn!("Client {} did not answer the auth challenge", addr);
+            Ok(AuthResult::Denied)
+        }
+        Ok(Some(Err(e))) => Err(e.into()),
+        Ok(None) => Err(TunnelError::Connection(
+            "Connection closed during auth challenge".into(),
+        )),
+        Err(_) => Err(TunnelError::Timeout("auth challenge not answered".into())),
+    }
+}
+
+/// Read the `PathRules` frame a client advertising the `path_rules`
+/// capability sends right after its handshake
+async fn read_path_rules(
+    framed: &mut Framed<BoxedStream, TunnelCodec>,
+    timeout: Duration,
+) -> Result<PathRules> {
+    match tokio::time::timeout(timeout, framed.next()).await {
+        Ok(Some(Ok(Frame::PathRules(rules)))) => Ok(*rules),
+        Ok(Some(Ok(_))) => Err(TunnelError::Protocol(
+            "Expected path rules after the handshake".into(),
+        )),
+        Ok(Some(Err(e))) => Err(e.into()),
+        Ok(None) => Err(TunnelError::Connection(
+            "Connection closed before path rules".into(),
+        )),
+        Err(_) => Err(TunnelError::Timeout("path rules not received".into())),
+    }
+}
+
+/// Answer the handshake of a connection over the session limit with
+/// [`HandshakeStatus::ServerFull`], so the client backs off instead of
+/// seeing a dropped connection
+async fn reject_server_full(stream: BoxedStream, addr: SocketAddr) {
+    let handshake_start = Instant::now();
+    let mut framed = Framed::new(stream, TunnelCodec::new());
+    let reject = async {
+        // Read the handshake first: closing with it unread would reset the
+        // connection before the client sees the ack
+        let Some(Ok(Frame::Handshake(_))) = framed.next().await else {
+            return;
+        };
+        let ack = Frame::HandshakeAck {
+            status: HandshakeStatus::ServerFull,
+            session_id: Uuid::nil(),
+            version: 0,
+            server_capabilities: vec![],
+        };
+        if let Err(e) = framed.send(ack).await {
+            debug!("Failed to reject {}: {}", addr, e);
+            return;
+        }
+        record_handshake(HandshakeStatus::ServerFull, handshake_start);
+    };
+    let _ = tokio::time::timeout(SERVER_FULL_TIMEOUT, reject).await;
+}
+
+/// Report a handshake answered with `status` to metrics
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+fn record_handshake(status: HandshakeStatus, started: Instant) {
+    #[cfg(feature = "metrics")]
+    if let Some(m) = ferrotunnel_observability::tunnel_metrics() {
+        m.record_handshake(handshake_status_label(status), started.elapsed());
+    }
+}
+
+/// `status` label of `ferrotunnel_handshakes_total`
+#[cfg(feature = "metrics")]
+fn handshake_status_label(status: HandshakeStatus) -> &'static str {
+    match status {
+        HandshakeStatus::Success => "success",
+        HandshakeStatus::InvalidToken => "invalid_token",
+        HandshakeStatus::UnsupportedVersion | HandshakeStatus::VersionMismatch => {
+            "version_mismatch"
+        }
+        HandshakeStatus::RateLimited => "rate_limited",
+        HandshakeStatus::TunnelIdTaken => "tunnel_taken",
+        HandshakeStatus::Unauthorized => "unauthorized",
+        HandshakeStatus::InvalidPathRules => "invalid_path_rules",
+        HandshakeStatus::ServerFull => "server_full",
+        HandshakeStatus::InvalidFrameSize => "invalid_frame_size",
+    }
+}
+
+/// Aborts the tasks it holds when dropped
+#[derive(Default)]
+struct AbortOnDrop(Vec<AbortHandle>);
+
+impl AbortOnDrop {
+    fn push(&mut self, task: AbortHandle) {
+        self.0.push(task);
+    }
+}
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
+        }
+    }
+}
+
+/// URL of `tunnel_id` published as a subdomain of `base`
+pub fn public_url(base: &str, tunnel_id: &str) -> String {
+    let (scheme, host) = base.split_once("://").unwrap_or(("http", base));
+    format!("{scheme}://{tunnel_id}.{host}")
+}
+
 /// Negotiate protocol version between client and server
 fn negotiate_version(client_min: u8, client_max: u8) -> Result<u8> {
     // Find highest common version
@@ -379,6 +1420,93 @@ fn negotiate_version(client_min: u8, client_max: u8) -> Result<u8> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tunnel::common::resume_capability;
+
+    #[test]
+    fn test_fast_open_tuning_enables_listener_fast_open() {
+        let addr = "127.0.0.1:0".parse().unwrap();
+        let server = TunnelServer::new(addr, "secret".into());
+        assert_eq!(server.listener_options().fast_open_queue, None);
+
+        let tuning = SocketTuningConfig {
+            tcp_fast_open: true,
+            ..Default::default()
+        };
+        let server = TunnelServer::new(addr, "secret".into()).with_socket_tuning(tuning.clone());
+        assert_eq!(
+            server.listener_options().fast_open_queue,
+            Some(DEFAULT_FAST_OPEN_QUEUE)
+        );
+
+        // An explicit queue length is kept
+        let server = TunnelServer::new(addr, "secret".into())
+            .with_socket_tuning(tuning)
+            .with_listener_config(ListenerConfig::default().with_fast_open_queue(Some(16)));
+        assert_eq!(server.listener_options().fast_open_queue, Some(16));
+    }
+
+    #[test]
+    fn test_tls_session_resumption_requires_tls() {
+        let addr = "127.0.0.1:0".parse().unwrap();
+        assert!(TunnelServer::new(addr, "secret".into())
+            .with_tls_session_resumption()
+            .is_err());
+
+        let server = TunnelServer::new(addr, "secret".into())
+            .with_tls("server.crt", "server.key")
+            .with_tls_session_resumption()
+            .unwrap();
+        let TransportConfig::Tls(tls) = &server.transport_config else {
+            panic!("expected a TLS transport");
+        };
+        assert!(tls.session_resumption);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_file_watcher_stops_with_server() {
+        let path =
+            std::env::temp_dir().join(format!("ferrotunnel-watched-tokens-{}", std::process::id()));
+        let rewrite = |contents: &str, age: u64| {
+            std::fs::write(&path, contents).unwrap();
+            // Bump the mtime explicitly; rewrites within a second may not change it
+            let mtime = std::time::SystemTime::now() + Duration::from_secs(age);
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(mtime)
+                .unwrap();
+        };
+        rewrite("token-a\n", 0);
+
+        let server = TunnelServer::new("127.0.0.1:0".parse().unwrap(), "secret".into())
+            .with_token_file(&path)
+            .unwrap();
+        let tokens = server.tokens();
+        let listener = TransportListener::Memory(transport::MemoryTransport::new());
+        let serving = tokio::spawn(server.serve(listener));
+        tokio::task::yield_now().await;
+
+        rewrite("token-a\ntoken-b\n", 60);
+        tokio::time::sleep(TOKEN_FILE_POLL_INTERVAL * 2).await;
+        assert_eq!(tokens.len(), 2);
+
+        serving.abort();
+        let _ = serving.await;
+        rewrite("token-a\ntoken-b\ntoken-c\n", 120);
+        tokio::time::sleep(TOKEN_FILE_POLL_INTERVAL * 2).await;
+        assert_eq!(tokens.len(), 2);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_zero_max_streams_rejected() {
+        let server = TunnelServer::new("127.0.0.1:0".parse().unwrap(), "secret".into())
+            .with_resource_limits(ServerResourceLimits::new(10, 0, 100));
+        let err = server.run().await.unwrap_err();
+        assert!(err.to_string().contains("max_streams_per_session"));
+    }
 
     #[test]
     fn test_version_negotiation_success() {
@@ -394,4 +1522,288 @@ mod tests {
         // Client requires 3+, Server only has 1
         assert!(negotiate_version(3, 5).is_err());
     }
+
+    #[test]
+    fn test_public_url() {
+        assert_eq!(
+            public_url("https://tunnel.example.com:8443", "myapp"),
+            "https://myapp.tunnel.example.com:8443"
+        );
+        assert_eq!(public_url("example.com", "api"), "http://api.example.com");
+        let server = TunnelServer::new("127.0.0.1:0".parse().unwrap(), "token".into())
+            .with_public_base("https://example.com/");
+        assert_eq!(server.public_base.as_deref(), Some("https://example.com"));
+    }
+
+    #[test]
+    fn test_capability_negotiation() {
+        let advertised = || {
+            ["basic", "tcp", "udp", "ssh", "flow_control:65536"]
+                .map(String::from)
+                .to_vec()
+        };
+        let open = AuthGrant::default();
+
+        // Without a server list everything advertised is granted
+        assert_eq!(
+            negotiate_capabilities(advertised(), None, &open, NonZeroU32::new(4096), None, &[]),
+            ["basic", "tcp", "udp", "ssh", "flow_control:4096"]
+        );
+
+        // The server list and the grant both narrow the set
+        let supported = ["basic", "udp", "ssh", "web"].map(String::from);
+        assert_eq!(
+            negotiate_capabilities(advertised(), Some(&supported), &open, None, None, &[]),
+            ["basic", "udp", "ssh"]
+        );
+        let grant = AuthGrant::default().with_capabilities(vec!["basic".into(), "tcp".into()]);
+        assert_eq!(
+            negotiate_capabilities(
+                advertised(),
+                Some(&supported),
+                &grant,
+                NonZeroU32::new(1024),
+                None,
+                &[]
+            ),
+            ["basic", "flow_control:1024"]
+        );
+
+        // Pings are granted even when the token narrows the set
+        let mut with_ping = advertised();
+        with_ping.push(PING_CAPABILITY.to_string());
+        assert_eq!(
+            negotiate_capabilities(with_ping, Some(&supported), &grant, None, None, &[]),
+            ["basic", "ping"]
+        );
+
+        // So is the max frame size, replaced with the negotiated one
+        let mut with_frame_size = advertised();
+        with_frame_size.push(frame_size_capability(MAX_FRAME_SIZE));
+        assert_eq!(
+            negotiate_capabilities(
+                with_frame_size,
+                Some(&supported),
+                &grant,
+                None,
+                Some(65_536),
+                &[]
+            ),
+            ["basic", "max_frame:65536"]
+        );
+
+        // A resume request is acted on, never echoed back
+        let mut resuming = advertised();
+        resuming.push(resume_capability(Uuid::new_v4()));
+        assert_eq!(
+            negotiate_capabilities(resuming, Some(&supported), &open, None, None, &[]),
+            ["basic", "udp", "ssh"]
+        );
+
+        // The public URL is granted only by the handshake, which knows the base
+        let mut with_url = advertised();
+        with_url.push(PUBLIC_URL_CAPABILITY.to_string());
+        assert_eq!(
+            negotiate_capabilities(with_url, Some(&supported), &open, None, None, &[]),
+            ["basic", "udp", "ssh"]
+        );
+
+        // A zero window is never echoed back; flow control stays off
+        let zero_window = vec!["basic".to_string(), "flow_control:0".to_string()];
+        let stream_window = flow_control::parse_capability(&zero_window);
+        assert_eq!(stream_window, None);
+        assert_eq!(
+            negotiate_capabilities(zero_window, None, &open, stream_window, None, &[]),
+            ["basic"]
+        );
+    }
+
+    #[test]
+    fn test_compression_negotiation() {
+        let open = AuthGrant::default();
+        let advertised = |compression: &[String]| {
+            let mut capabilities = vec!["basic".to_string()];
+            capabilities.extend_from_slice(compression);
+            capabilities
+        };
+        let all = FrameCompression::default_preference();
+        let offered: Vec<String> = all.iter().rev().map(|a| a.capability()).collect();
+
+        // The server's preference wins, and a single algorithm is granted
+        let mut expected = vec!["basic".to_string()];
+        expected.extend(all.first().map(|a| a.capability()));
+        assert_eq!(
+            negotiate_capabilities(advertised(&offered), None, &open, None, None, &all),
+            expected
+        );
+
+        // An empty preference turns compression off
+        assert_eq!(
+            negotiate_capabilities(advertised(&offered), None, &open, None, None, &[]),
+            ["basic"]
+        );
+        let unknown = ["comp:brotli".to_string()];
+        assert_eq!(
+            negotiate_capabilities(advertised(&unknown), None, &open, None, None, &all),
+            ["basic"]
+        );
+    }
+
+    const IDLE_TIMEOUT: Duration = Duration::from_millis(200);
+
+    async fn start_idle_server() -> (SocketAddr, SessionStoreBackend) {
+        let addr = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap()
+        };
+        let server = TunnelServer::new(addr, "test-token".into()).with_idle_timeout(IDLE_TIMEOUT);
+        let sessions = server.sessions();
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        for _ in 0..50 {
+            if tokio::net::TcpStream::connect(addr).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        (addr, sessions)
+    }
+
+    #[tokio::test]
+    async fn test_silent_connection_closed_after_idle_timeout() {
+        use tokio::io::AsyncReadExt;
+
+        let (addr, _sessions) = start_idle_server().await;
+        let mut conn = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let start = Instant::now();
+
+        let mut buf = [0u8; 16];
+        let n = tokio::time::timeout(IDLE_TIMEOUT * 5, conn.read(&mut buf))
+            .await
+            .expect("connection not closed within the idle window")
+            .unwrap_or(0);
+        assert_eq!(n, 0);
+        assert!(start.elapsed() >= IDLE_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn test_idle_session_torn_down_after_handshake() {
+        let (addr, sessions) = start_idle_server().await;
+        let conn = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut framed = Framed::new(conn, TunnelCodec::new());
+        framed
+            .send(Frame::Handshake(Box::new(HandshakeFrame {
+                min_version: MIN_PROTOCOL_VERSION,
+                max_version: MAX_PROTOCOL_VERSION,
+                token: "test-token".into(),
+                tunnel_id: Some("idle".into()),
+                capabilities: vec![],
+            })))
+            .await
+            .unwrap();
+        let ack = framed.next().await.unwrap().unwrap();
+        assert!(matches!(
+            ack,
+            Frame::HandshakeAck {
+                status: HandshakeStatus::Success,
+                ..
+            }
+        ));
+        assert!(sessions.get_by_tunnel_id("idle").is_some());
+
+        // Send nothing further: no heartbeats, no data
+        let closed = tokio::time::timeout(IDLE_TIMEOUT * 5, framed.next())
+            .await
+            .expect("connection not closed within the idle window");
+        assert!(matches!(closed, None | Some(Err(_))));
+        assert!(sessions.get_by_tunnel_id("idle").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_below_min_frame_size_rejected() {
+        let (addr, sessions) = start_idle_server().await;
+        let conn = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut framed = Framed::new(conn, TunnelCodec::new());
+        framed
+            .send(Frame::Handshake(Box::new(HandshakeFrame {
+                min_version: MIN_PROTOCOL_VERSION,
+                max_version: MAX_PROTOCOL_VERSION,
+                token: "test-token".into(),
+                tunnel_id: Some("tiny".into()),
+                capabilities: vec![frame_size_capability(16)],
+            })))
+            .await
+            .unwrap();
+        let ack = framed.next().await.unwrap().unwrap();
+        assert!(matches!(
+            ack,
+            Frame::HandshakeAck {
+                status: HandshakeStatus::InvalidFrameSize,
+                ..
+            }
+        ));
+        assert!(sessions.get_by_tunnel_id("tiny").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_missing_path_rules_frame_closes_connection() {
+        let (addr, sessions) = start_idle_server().await;
+        let conn = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut framed = Framed::new(conn, TunnelCodec::new());
+        framed
+            .send(Frame::Handshake(Box::new(HandshakeFrame {
+                min_version: MIN_PROTOCOL_VERSION,
+                max_version: MAX_PROTOCOL_VERSION,
+                token: "test-token".into(),
+                tunnel_id: Some("no-rules".into()),
+                capabilities: vec![PATH_RULES_CAPABILITY.into()],
+            })))
+            .await
+            .unwrap();
+        // Anything but the promised `PathRules` frame
+        framed
+            .send(Frame::Heartbeat { timestamp: 1 })
+            .await
+            .unwrap();
+
+        let closed = tokio::time::timeout(IDLE_TIMEOUT * 5, framed.next())
+            .await
+            .expect("connection not closed");
+        assert!(matches!(closed, None | Some(Err(_))));
+        assert!(sessions.get_by_tunnel_id("no-rules").is_none());
+    }
+
+    #[test]
+    fn test_frame_channel_capacity() {
+        use crate::transport::batched_sender::MAX_FRAME_CHANNEL_CAPACITY;
+
+        let addr = "127.0.0.1:0".parse().unwrap();
+        let server = TunnelServer::new(addr, "token".into());
+        assert_eq!(
+            server.frame_channel_capacity,
+            DEFAULT_FRAME_CHANNEL_CAPACITY
+        );
+
+        let server = server.with_frame_channel_capacity(64);
+        assert_eq!(server.frame_channel_capacity, 64);
+        let server = server.with_frame_channel_capacity(1 << 20);
+        assert_eq!(server.frame_channel_capacity, MAX_FRAME_CHANNEL_CAPACITY);
+    }
+
+    #[test]
+    fn test_max_frame_size() {
+        let addr = "127.0.0.1:0".parse().unwrap();
+        let server = TunnelServer::new(addr, "token".into());
+        assert_eq!(server.max_frame_size, MAX_FRAME_SIZE);
+
+        let server = server.with_max_frame_size(64 * 1024).unwrap();
+        assert_eq!(server.max_frame_size, 64 * 1024);
+        for size in [1024, MAX_FRAME_SIZE + 1] {
+            assert!(matches!(
+                TunnelServer::new(addr, "token".into()).with_max_frame_size(size),
+                Err(TunnelError::Config(_))
+            ));
+        }
+    }
 }
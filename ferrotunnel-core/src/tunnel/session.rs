@@ -1,9 +1,15 @@
+use crate::clock::{AnyClock, Clock};
 use crate::rate_limit::SessionRateLimiter;
-use crate::stream::Multiplexer;
+use crate::stream::{Multiplexer, TrafficCounters};
+use crate::transport::tls::PeerIdentity;
 use dashmap::DashMap;
+use ferrotunnel_protocol::constants::{STREAM_PROTOCOLS_CAPABILITY, WEBSOCKET_CAPABILITY};
+use ferrotunnel_protocol::frame::Protocol;
+use ferrotunnel_protocol::PathRules;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use uuid::Uuid;
@@ -11,8 +17,29 @@ use uuid::Uuid;
 /// Default number of shards for [`ShardedSessionStore`]. Tune for contention vs memory.
 const DEFAULT_SESSION_STORE_SHARDS: usize = 16;
 
-/// One shard: tunnel_id -> Uuid, and Uuid -> Session.
-type SessionShard = (DashMap<String, Uuid>, DashMap<Uuid, Session>);
+/// One shard: tunnel_id -> sessions, and Uuid -> Session.
+type SessionShard = (DashMap<String, TunnelPool>, DashMap<Uuid, Session>);
+
+/// How a pooled tunnel picks a session for each new stream
+///
+/// Pooling is opt-in: without a policy a tunnel ID belongs to one session and
+/// a second client registering it is rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoolPolicy {
+    /// Take the sessions in turn
+    #[default]
+    RoundRobin,
+    /// Take the session with the fewest open streams; ties go round-robin
+    LeastStreams,
+}
+
+/// Sessions registered under one tunnel ID, oldest first
+#[derive(Debug, Default)]
+struct TunnelPool {
+    members: Vec<Uuid>,
+    /// Round-robin position
+    cursor: AtomicUsize,
+}
 
 /// Represents an active client session
 #[derive(Debug)]
@@ -26,6 +53,19 @@ pub struct Session {
     pub capabilities: Vec<String>,
     pub multiplexer: Option<Multiplexer>,
     pub rate_limiter: Option<SessionRateLimiter>,
+    /// Data bytes received from (`bytes_in`) and sent to (`bytes_out`) the
+    /// client; shared with the session's multiplexer
+    pub traffic: TrafficCounters,
+    /// Identity from the client's TLS certificate, when mutual TLS is used
+    pub peer_identity: Option<PeerIdentity>,
+    /// Who the client authenticated as, from its [`AuthGrant`](crate::auth::AuthGrant);
+    /// the only way to tell challenge-auth clients apart, as they send no token
+    pub client_id: Option<String>,
+    /// HTTP paths the ingress may forward to this session
+    pub path_rules: PathRules,
+    /// Concurrent ingress requests allowed for this tunnel, overriding the
+    /// ingress-wide limit
+    pub max_in_flight_requests: Option<usize>,
 }
 
 impl Session {
@@ -38,6 +78,10 @@ impl Session {
         multiplexer: Option<Multiplexer>,
     ) -> Self {
         let now = Instant::now();
+        let traffic = multiplexer
+            .as_ref()
+            .map(|m| m.traffic().clone())
+            .unwrap_or_default();
         Self {
             id,
             tunnel_id,
@@ -48,6 +92,11 @@ impl Session {
             capabilities,
             multiplexer,
             rate_limiter: None,
+            traffic,
+            peer_identity: None,
+            client_id: None,
+            path_rules: PathRules::default(),
+            max_in_flight_requests: None,
         }
     }
 
@@ -57,8 +106,101 @@ impl Session {
         self
     }
 
+    #[must_use]
+    pub fn with_peer_identity(mut self, identity: Option<PeerIdentity>) -> Self {
+        self.peer_identity = identity;
+        self
+    }
+
+    #[must_use]
+    pub fn with_client_id(mut self, client_id: Option<String>) -> Self {
+        self.client_id = client_id;
+        self
+    }
+
+    #[must_use]
+    pub fn with_path_rules(mut self, rules: PathRules) -> Self {
+        self.path_rules = rules;
+        self
+    }
+
+    #[must_use]
+    pub fn with_max_in_flight_requests(mut self, limit: Option<usize>) -> Self {
+        self.max_in_flight_requests = limit;
+        self
+    }
+
     pub fn update_heartbeat(&mut self) {
-        self.last_heartbeat = Instant::now();
+        self.update_heartbeat_at(Instant::now());
+    }
+
+    /// Record activity at `now`, as read from the store's clock
+    pub fn update_heartbeat_at(&mut self, now: Instant) {
+        self.last_heartbeat = now;
+    }
+
+    /// Whether the session was granted `capability`
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|cap| cap == capability)
+    }
+
+    /// Whether the client handles streams of `protocol`: protocols with a
+    /// [capability](Protocol::capability) need it granted
+    ///
+    /// WebSocket is only gated for sessions granted
+    /// [`STREAM_PROTOCOLS_CAPABILITY`], since earlier clients, and grants
+    /// written for them, never list `websocket`.
+    pub fn accepts(&self, protocol: Protocol) -> bool {
+        match protocol.capability() {
+            None => true,
+            Some(WEBSOCKET_CAPABILITY) if !self.has_capability(STREAM_PROTOCOLS_CAPABILITY) => true,
+            Some(capability) => self.has_capability(capability),
+        }
+    }
+
+    /// Whether `other` authenticated as the same client: same token, same
+    /// client ID and, with mutual TLS, the same certificate identity
+    ///
+    /// A session with neither a token nor a client ID, such as a challenge-auth
+    /// one from an authenticator that sets no ID, is nobody's but its own.
+    pub fn same_client(&self, other: &Session) -> bool {
+        (!self.token.is_empty() || self.client_id.is_some())
+            && crate::auth::constant_time_eq(self.token.as_bytes(), other.token.as_bytes())
+            && self.client_id == other.client_id
+            && self.peer_identity == other.peer_identity
+    }
+}
+
+/// Owned copy of a session's routing details, taken without keeping any
+/// store lock
+///
+/// Use it to do async work per session, such as sending frames: holding a
+/// store guard across `.await` can deadlock against writers of the same shard.
+#[derive(Debug, Clone)]
+pub struct SessionSnapshot {
+    pub id: Uuid,
+    pub tunnel_id: String,
+    pub client_addr: SocketAddr,
+    pub connected_at: Instant,
+    pub last_heartbeat: Instant,
+    pub capabilities: Vec<String>,
+    /// Shares the session's connection; cloning is cheap
+    pub multiplexer: Option<Multiplexer>,
+    pub traffic: TrafficCounters,
+}
+
+impl From<&Session> for SessionSnapshot {
+    fn from(session: &Session) -> Self {
+        Self {
+            id: session.id,
+            tunnel_id: session.tunnel_id.clone(),
+            client_addr: session.client_addr,
+            connected_at: session.connected_at,
+            last_heartbeat: session.last_heartbeat,
+            capabilities: session.capabilities.clone(),
+            multiplexer: session.multiplexer.clone(),
+            traffic: session.traffic.clone(),
+        }
     }
 }
 
@@ -79,7 +221,9 @@ impl From<SessionStoreError> for ferrotunnel_common::TunnelError {
 #[derive(Debug, Clone, Default)]
 pub struct SessionStore {
     sessions: Arc<DashMap<Uuid, Session>>,
-    tunnel_index: Arc<DashMap<String, Uuid>>,
+    tunnel_index: Arc<DashMap<String, TunnelPool>>,
+    pool_policy: Option<PoolPolicy>,
+    clock: AnyClock,
 }
 
 impl SessionStore {
@@ -87,42 +231,68 @@ impl SessionStore {
         Self {
             sessions: Arc::new(DashMap::new()),
             tunnel_index: Arc::new(DashMap::new()),
+            pool_policy: None,
+            clock: AnyClock::System,
         }
     }
 
+    /// Let several sessions share a tunnel ID, picking among them with `policy`.
+    #[must_use]
+    pub fn with_pool_policy(mut self, policy: Option<PoolPolicy>) -> Self {
+        self.pool_policy = policy;
+        self
+    }
+
+    /// Read the current time for session timestamps and stale-session cleanup
+    /// from `clock`
+    #[must_use]
+    pub fn with_clock(mut self, clock: impl Into<AnyClock>) -> Self {
+        self.clock = clock.into();
+        self
+    }
+
+    /// Policy for tunnel IDs shared by several sessions; `None` when pooling is off
+    pub fn pool_policy(&self) -> Option<PoolPolicy> {
+        self.pool_policy
+    }
+
     /// Add a new session.
-    /// Returns error if `tunnel_id` is already registered by a different session.
+    /// Returns error if `tunnel_id` is already registered by a different session
+    /// and pooling is off.
     pub fn add(&self, session: Session) -> Result<(), SessionStoreError> {
-        let tunnel_id = session.tunnel_id.clone();
-        let session_id = session.id;
-
-        // Check if tunnel_id already exists and belongs to a different session
-        if let Some(existing_id) = self.tunnel_index.get(&tunnel_id) {
-            if *existing_id != session_id {
-                return Err(SessionStoreError::TunnelIdAlreadyExists(tunnel_id));
-            }
-        }
+        self.add_or_resume(session, None).map(|_| ())
+    }
 
-        self.tunnel_index.insert(tunnel_id, session_id);
-        self.sessions.insert(session_id, session);
-        Ok(())
+    /// Add a session for a client reconnecting after holding `previous`.
+    ///
+    /// When `previous` is still registered under the same tunnel ID by the
+    /// same client (see [`Session::same_client`]), it is removed and returned
+    /// so the caller can close its connection; otherwise this behaves like
+    /// [`Self::add`].
+    pub fn add_or_resume(
+        &self,
+        session: Session,
+        previous: Option<Uuid>,
+    ) -> Result<Option<Session>, SessionStoreError> {
+        add_session(
+            &self.tunnel_index,
+            &self.sessions,
+            self.pool_policy.is_some(),
+            session,
+            previous,
+            self.clock.now(),
+        )
     }
 
     /// Add or replace a session, removing any existing session with the same `tunnel_id`.
     /// Use this for explicit session replacement (e.g., reconnection).
     pub fn add_or_replace(&self, session: Session) {
-        let tunnel_id = session.tunnel_id.clone();
-        let session_id = session.id;
-
-        // Remove existing session with the same tunnel_id if it exists
-        if let Some((_, old_session_id)) = self.tunnel_index.remove(&tunnel_id) {
-            if old_session_id != session_id {
-                self.sessions.remove(&old_session_id);
-            }
-        }
-
-        self.tunnel_index.insert(tunnel_id, session_id);
-        self.sessions.insert(session_id, session);
+        replace_sessions(
+            &self.tunnel_index,
+            &self.sessions,
+            session,
+            self.clock.now(),
+        );
     }
 
     /// Get a session by ID
@@ -130,13 +300,32 @@ impl SessionStore {
         self.sessions.get(id)
     }
 
-    /// Get a session by `tunnel_id`
+    /// Get a session by `tunnel_id`; the oldest one when the tunnel is pooled
     pub fn get_by_tunnel_id(
         &self,
         tunnel_id: &str,
     ) -> Option<dashmap::mapref::one::Ref<'_, Uuid, Session>> {
-        let id = self.tunnel_index.get(tunnel_id)?;
-        self.sessions.get(&id)
+        let pool = self.tunnel_index.get(tunnel_id)?;
+        pool.members.iter().find_map(|id| self.sessions.get(id))
+    }
+
+    /// Pick the session that should serve the next stream for `tunnel_id`,
+    /// skipping `excluded` (e.g. sessions that just failed to open a stream).
+    ///
+    /// Sessions with a multiplexer are chosen by the pool policy; a session
+    /// still setting up is only returned when no other is ready.
+    pub fn select_by_tunnel_id(
+        &self,
+        tunnel_id: &str,
+        excluded: &[Uuid],
+    ) -> Option<dashmap::mapref::one::Ref<'_, Uuid, Session>> {
+        select_session(
+            &self.tunnel_index,
+            &self.sessions,
+            self.pool_policy,
+            tunnel_id,
+            excluded,
+        )
     }
 
     /// Get a mutable session by ID (e.g. to update heartbeat)
@@ -144,14 +333,17 @@ impl SessionStore {
         self.sessions.get_mut(id)
     }
 
+    /// Record activity from session `id` at the store clock's time; `false`
+    /// when the session is gone
+    pub fn record_heartbeat(&self, id: &Uuid) -> bool {
+        self.get_mut(id)
+            .map(|mut session| session.update_heartbeat_at(self.clock.now()))
+            .is_some()
+    }
+
     /// Remove a session
     pub fn remove(&self, id: &Uuid) -> Option<Session> {
-        if let Some((_, session)) = self.sessions.remove(id) {
-            self.tunnel_index.remove(&session.tunnel_id);
-            Some(session)
-        } else {
-            None
-        }
+        remove_session(&self.tunnel_index, &self.sessions, id)
     }
 
     /// Count active sessions
@@ -162,7 +354,7 @@ impl SessionStore {
     /// Clean up stale sessions that haven't sent a heartbeat within the timeout
     /// Returns the number of removed sessions
     pub fn cleanup_stale_sessions(&self, timeout: Duration) -> usize {
-        let now = Instant::now();
+        let now = self.clock.now();
         let mut to_remove = Vec::new();
 
         // Identify stale sessions
@@ -182,6 +374,22 @@ impl SessionStore {
         count
     }
 
+    /// Visit every active session. Shard locks are held while `f` runs, so keep it short.
+    pub fn for_each(&self, mut f: impl FnMut(&Session)) {
+        for r in self.sessions.iter() {
+            f(r.value());
+        }
+    }
+
+    /// Copy every active session out of the store; no lock is held once
+    /// this returns, so the result is safe to use across `.await`
+    pub fn snapshot(&self) -> Vec<SessionSnapshot> {
+        self.sessions
+            .iter()
+            .map(|r| SessionSnapshot::from(r.value()))
+            .collect()
+    }
+
     pub fn find_multiplexer(&self) -> Option<Multiplexer> {
         for r in self.sessions.iter() {
             if let Some(m) = &r.multiplexer {
@@ -202,6 +410,23 @@ impl SessionStore {
         }
         None
     }
+
+    /// Find a multiplexer for a session that has `capability` and accepts
+    /// `protocol` streams
+    pub fn find_multiplexer_for(
+        &self,
+        capability: &str,
+        protocol: Protocol,
+    ) -> Option<Multiplexer> {
+        for r in self.sessions.iter() {
+            if r.has_capability(capability) && r.accepts(protocol) {
+                if let Some(m) = &r.multiplexer {
+                    return Some(m.clone());
+                }
+            }
+        }
+        None
+    }
 }
 
 /// Sharded session store to reduce contention on tunnel_id lookups.
@@ -210,6 +435,8 @@ impl SessionStore {
 pub struct ShardedSessionStore {
     shards: Arc<Vec<SessionShard>>,
     n_shards: usize,
+    pool_policy: Option<PoolPolicy>,
+    clock: AnyClock,
 }
 
 fn shard_index(tunnel_id: &str, n_shards: usize) -> usize {
@@ -232,40 +459,59 @@ impl ShardedSessionStore {
         Self {
             shards: Arc::new(shards),
             n_shards,
+            pool_policy: None,
+            clock: AnyClock::System,
         }
     }
 
-    /// Add a new session. Returns error if `tunnel_id` is already registered by a different session.
+    /// Let several sessions share a tunnel ID, picking among them with `policy`.
+    #[must_use]
+    pub fn with_pool_policy(mut self, policy: Option<PoolPolicy>) -> Self {
+        self.pool_policy = policy;
+        self
+    }
+
+    /// Read the current time for session timestamps and stale-session cleanup
+    /// from `clock`
+    #[must_use]
+    pub fn with_clock(mut self, clock: impl Into<AnyClock>) -> Self {
+        self.clock = clock.into();
+        self
+    }
+
+    /// Policy for tunnel IDs shared by several sessions; `None` when pooling is off
+    pub fn pool_policy(&self) -> Option<PoolPolicy> {
+        self.pool_policy
+    }
+
+    /// Add a new session. Returns error if `tunnel_id` is already registered by a
+    /// different session and pooling is off.
     pub fn add(&self, session: Session) -> Result<(), SessionStoreError> {
-        let tunnel_id = session.tunnel_id.clone();
-        let session_id = session.id;
-        let idx = shard_index(&tunnel_id, self.n_shards);
-        let (tunnel_index, sessions) = &self.shards[idx];
+        self.add_or_resume(session, None).map(|_| ())
+    }
 
-        if let Some(existing_id) = tunnel_index.get(&tunnel_id) {
-            if *existing_id != session_id {
-                return Err(SessionStoreError::TunnelIdAlreadyExists(tunnel_id));
-            }
-        }
-        tunnel_index.insert(tunnel_id, session_id);
-        sessions.insert(session_id, session);
-        Ok(())
+    /// Add a session for a client reconnecting after holding `previous`; see
+    /// [`SessionStore::add_or_resume`].
+    pub fn add_or_resume(
+        &self,
+        session: Session,
+        previous: Option<Uuid>,
+    ) -> Result<Option<Session>, SessionStoreError> {
+        let (tunnel_index, sessions) = &self.shards[shard_index(&session.tunnel_id, self.n_shards)];
+        add_session(
+            tunnel_index,
+            sessions,
+            self.pool_policy.is_some(),
+            session,
+            previous,
+            self.clock.now(),
+        )
     }
 
     /// Add or replace a session, removing any existing session with the same `tunnel_id`.
     pub fn add_or_replace(&self, session: Session) {
-        let tunnel_id = session.tunnel_id.clone();
-        let session_id = session.id;
-        let idx = shard_index(&tunnel_id, self.n_shards);
-        let (tunnel_index, sessions) = &self.shards[idx];
-
-        if let Some((_, old_id)) = tunnel_index.remove(&tunnel_id) {
-            if old_id != session_id {
-                sessions.remove(&old_id);
-            }
-        }
-        tunnel_index.insert(tunnel_id, session_id);
-        sessions.insert(session_id, session);
+        let (tunnel_index, sessions) = &self.shards[shard_index(&session.tunnel_id, self.n_shards)];
+        replace_sessions(tunnel_index, sessions, session, self.clock.now());
     }
 
     /// Get a session by ID. Requires scanning shards; prefer [`Self::get_by_tunnel_id`] when possible.
@@ -278,15 +524,33 @@ impl ShardedSessionStore {
         None
     }
 
-    /// Get a session by `tunnel_id` (shard-local, low contention).
+    /// Get a session by `tunnel_id` (shard-local, low contention); the oldest one
+    /// when the tunnel is pooled.
     pub fn get_by_tunnel_id(
         &self,
         tunnel_id: &str,
     ) -> Option<dashmap::mapref::one::Ref<'_, Uuid, Session>> {
         let idx = shard_index(tunnel_id, self.n_shards);
         let (tunnel_index, sessions) = &self.shards[idx];
-        let id = tunnel_index.get(tunnel_id)?;
-        sessions.get(&id)
+        let pool = tunnel_index.get(tunnel_id)?;
+        pool.members.iter().find_map(|id| sessions.get(id))
+    }
+
+    /// Pick the session that should serve the next stream for `tunnel_id`;
+    /// see [`SessionStore::select_by_tunnel_id`].
+    pub fn select_by_tunnel_id(
+        &self,
+        tunnel_id: &str,
+        excluded: &[Uuid],
+    ) -> Option<dashmap::mapref::one::Ref<'_, Uuid, Session>> {
+        let (tunnel_index, sessions) = &self.shards[shard_index(tunnel_id, self.n_shards)];
+        select_session(
+            tunnel_index,
+            sessions,
+            self.pool_policy,
+            tunnel_id,
+            excluded,
+        )
     }
 
     /// Get a mutable session by ID.
@@ -299,15 +563,18 @@ impl ShardedSessionStore {
         None
     }
 
+    /// Record activity from session `id`; see [`SessionStore::record_heartbeat`].
+    pub fn record_heartbeat(&self, id: &Uuid) -> bool {
+        self.get_mut(id)
+            .map(|mut session| session.update_heartbeat_at(self.clock.now()))
+            .is_some()
+    }
+
     /// Remove a session by ID.
     pub fn remove(&self, id: &Uuid) -> Option<Session> {
-        for (tunnel_index, sessions) in &*self.shards {
-            if let Some((_, session)) = sessions.remove(id) {
-                tunnel_index.remove(&session.tunnel_id);
-                return Some(session);
-            }
-        }
-        None
+        self.shards
+            .iter()
+            .find_map(|(tunnel_index, sessions)| remove_session(tunnel_index, sessions, id))
     }
 
     /// Count active sessions (sum across shards).
@@ -317,7 +584,7 @@ impl ShardedSessionStore {
 
     /// Clean up stale sessions. Returns the number of removed sessions.
     pub fn cleanup_stale_sessions(&self, timeout: Duration) -> usize {
-        let now = Instant::now();
+        let now = self.clock.now();
         let mut to_remove = Vec::new();
         for (_, sessions) in &*self.shards {
             for r in sessions {
@@ -333,6 +600,22 @@ impl ShardedSessionStore {
         count
     }
 
+    /// Visit every active session across shards. Shard locks are held while `f` runs.
+    pub fn for_each(&self, mut f: impl FnMut(&Session)) {
+        for (_, sessions) in &*self.shards {
+            for r in sessions {
+                f(r.value());
+            }
+        }
+    }
+
+    /// Copy every active session out of all shards; see [`SessionStore::snapshot`]
+    pub fn snapshot(&self) -> Vec<SessionSnapshot> {
+        let mut snapshot = Vec::with_capacity(self.count());
+        self.for_each(|session| snapshot.push(SessionSnapshot::from(session)));
+        snapshot
+    }
+
     /// Find any multiplexer (scans shards).
     pub fn find_multiplexer(&self) -> Option<Multiplexer> {
         for (_, sessions) in &*self.shards {
@@ -358,6 +641,25 @@ impl ShardedSessionStore {
         }
         None
     }
+
+    /// Find a multiplexer for a session that has `capability` and accepts
+    /// `protocol` streams.
+    pub fn find_multiplexer_for(
+        &self,
+        capability: &str,
+        protocol: Protocol,
+    ) -> Option<Multiplexer> {
+        for (_, sessions) in &*self.shards {
+            for r in sessions {
+                if r.has_capability(capability) && r.accepts(protocol) {
+                    if let Some(m) = &r.multiplexer {
+                        return Some(m.clone());
+                    }
+                }
+            }
+        }
+        None
+    }
 }
 
 impl Default for ShardedSessionStore {
@@ -366,20 +668,169 @@ impl Default for ShardedSessionStore {
     }
 }
 
+fn add_session(
+    tunnel_index: &DashMap<String, TunnelPool>,
+    sessions: &DashMap<Uuid, Session>,
+    pooled: bool,
+    mut session: Session,
+    previous: Option<Uuid>,
+    now: Instant,
+) -> Result<Option<Session>, SessionStoreError> {
+    session.connected_at = now;
+    session.last_heartbeat = now;
+    let session_id = session.id;
+    let mut replaced = None;
+    {
+        let mut pool = tunnel_index.entry(session.tunnel_id.clone()).or_default();
+        // Re-adding a registered session only refreshes it
+        if !pool.members.contains(&session_id) {
+            // Only the client that held the previous session may take it over
+            let takeover = previous.filter(|id| {
+                *id != session_id
+                    && pool.members.contains(id)
+                    && sessions
+                        .get(id)
+                        .is_some_and(|old| old.same_client(&session))
+            });
+            if !pooled && pool.members.iter().any(|id| Some(*id) != takeover) {
+                return Err(SessionStoreError::TunnelIdAlreadyExists(session.tunnel_id));
+            }
+            if let Some(old_id) = takeover {
+                pool.members.retain(|id| *id != old_id);
+                replaced = sessions.remove(&old_id).map(|(_, old)| old);
+            }
+            pool.members.push(session_id);
+        }
+    }
+    sessions.insert(session_id, session);
+    Ok(replaced)
+}
+
+fn replace_sessions(
+    tunnel_index: &DashMap<String, TunnelPool>,
+    sessions: &DashMap<Uuid, Session>,
+    mut session: Session,
+    now: Instant,
+) {
+    session.connected_at = now;
+    session.last_heartbeat = now;
+    let session_id = session.id;
+    {
+        let mut pool = tunnel_index.entry(session.tunnel_id.clone()).or_default();
+        for old_id in pool.members.drain(..) {
+            if old_id != session_id {
+                sessions.remove(&old_id);
+            }
+        }
+        pool.members.push(session_id);
+    }
+    sessions.insert(session_id, session);
+}
+
+fn remove_session(
+    tunnel_index: &DashMap<String, TunnelPool>,
+    sessions: &DashMap<Uuid, Session>,
+    id: &Uuid,
+) -> Option<Session> {
+    let (_, session) = sessions.remove(id)?;
+    if let Some(mut pool) = tunnel_index.get_mut(&session.tunnel_id) {
+        pool.members.retain(|member| member != id);
+    }
+    tunnel_index.remove_if(&session.tunnel_id, |_, pool| pool.members.is_empty());
+    Some(session)
+}
+
+fn select_session<'a>(
+    tunnel_index: &DashMap<String, TunnelPool>,
+    sessions: &'a DashMap<Uuid, Session>,
+    policy: Option<PoolPolicy>,
+    tunnel_id: &str,
+    excluded: &[Uuid],
+) -> Option<dashmap::mapref::one::Ref<'a, Uuid, Session>> {
+    let chosen = {
+        let pool = tunnel_index.get(tunnel_id)?;
+        let candidates: Vec<Uuid> = pool
+            .members
+            .iter()
+            .filter(|id| !excluded.contains(*id))
+            .copied()
+            .collect();
+        // Candidates whose multiplexer is up, with their open stream counts
+        let ready: Vec<(Uuid, usize)> = candidates
+            .iter()
+            .filter_map(|id| {
+                let streams = sessions.get(id)?.multiplexer.as_ref()?.active_streams();
+                Some((*id, streams))
+            })
+            .collect();
+        match ready.len() {
+            0 => candidates.first().copied(),
+            1 => ready.first().map(|(id, _)| *id),
+            n => {
+                let start = pool.cursor.fetch_add(1, Ordering::Relaxed) % n;
+                let mut rotated = ready.iter().cycle().skip(start).take(n);
+                let pick = match policy {
+                    Some(PoolPolicy::LeastStreams) => rotated.min_by_key(|(_, streams)| *streams),
+                    _ => rotated.next(),
+                };
+                pick.map(|(id, _)| *id)
+            }
+        }
+    };
+    sessions.get(&chosen?)
+}
+
 /// Session store backend: default (single DashMap pair) or sharded (for high contention).
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub enum SessionStoreBackend {
     Default(SessionStore),
     Sharded(ShardedSessionStore),
 }
 
 impl SessionStoreBackend {
+    /// Let several sessions share a tunnel ID, picking among them with `policy`.
+    #[must_use]
+    pub fn with_pool_policy(self, policy: Option<PoolPolicy>) -> Self {
+        match self {
+            SessionStoreBackend::Default(s) => {
+                SessionStoreBackend::Default(s.with_pool_policy(policy))
+            }
+            SessionStoreBackend::Sharded(s) => {
+                SessionStoreBackend::Sharded(s.with_pool_policy(policy))
+            }
+        }
+    }
+    /// Read the current time for session timestamps and stale-session cleanup
+    /// from `clock`
+    #[must_use]
+    pub fn with_clock(self, clock: impl Into<AnyClock>) -> Self {
+        match self {
+            SessionStoreBackend::Default(s) => SessionStoreBackend::Default(s.with_clock(clock)),
+            SessionStoreBackend::Sharded(s) => SessionStoreBackend::Sharded(s.with_clock(clock)),
+        }
+    }
+    pub fn pool_policy(&self) -> Option<PoolPolicy> {
+        match self {
+            SessionStoreBackend::Default(s) => s.pool_policy(),
+            SessionStoreBackend::Sharded(s) => s.pool_policy(),
+        }
+    }
     pub fn add(&self, session: Session) -> Result<(), SessionStoreError> {
         match self {
             SessionStoreBackend::Default(s) => s.add(session),
             SessionStoreBackend::Sharded(s) => s.add(session),
         }
     }
+    pub fn add_or_resume(
+        &self,
+        session: Session,
+        previous: Option<Uuid>,
+    ) -> Result<Option<Session>, SessionStoreError> {
+        match self {
+            SessionStoreBackend::Default(s) => s.add_or_resume(session, previous),
+            SessionStoreBackend::Sharded(s) => s.add_or_resume(session, previous),
+        }
+    }
     pub fn add_or_replace(&self, session: Session) {
         match self {
             SessionStoreBackend::Default(s) => s.add_or_replace(session),
@@ -401,12 +852,28 @@ impl SessionStoreBackend {
             SessionStoreBackend::Sharded(s) => s.get_by_tunnel_id(tunnel_id),
         }
     }
+    pub fn select_by_tunnel_id(
+        &self,
+        tunnel_id: &str,
+        excluded: &[Uuid],
+    ) -> Option<dashmap::mapref::one::Ref<'_, Uuid, Session>> {
+        match self {
+            SessionStoreBackend::Default(s) => s.select_by_tunnel_id(tunnel_id, excluded),
+            SessionStoreBackend::Sharded(s) => s.select_by_tunnel_id(tunnel_id, excluded),
+        }
+    }
     pub fn get_mut(&self, id: &Uuid) -> Option<dashmap::mapref::one::RefMut<'_, Uuid, Session>> {
         match self {
             SessionStoreBackend::Default(s) => s.get_mut(id),
             SessionStoreBackend::Sharded(s) => s.get_mut(id),
         }
     }
+    pub fn record_heartbeat(&self, id: &Uuid) -> bool {
+        match self {
+            SessionStoreBackend::Default(s) => s.record_heartbeat(id),
+            SessionStoreBackend::Sharded(s) => s.record_heartbeat(id),
+        }
+    }
     pub fn remove(&self, id: &Uuid) -> Option<Session> {
         match self {
             SessionStoreBackend::Default(s) => s.remove(id),
@@ -425,12 +892,34 @@ impl SessionStoreBackend {
             SessionStoreBackend::Sharded(s) => s.cleanup_stale_sessions(timeout),
         }
     }
+    pub fn for_each(&self, f: impl FnMut(&Session)) {
+        match self {
+            SessionStoreBackend::Default(s) => s.for_each(f),
+            SessionStoreBackend::Sharded(s) => s.for_each(f),
+        }
+    }
+    pub fn snapshot(&self) -> Vec<SessionSnapshot> {
+        match self {
+            SessionStoreBackend::Default(s) => s.snapshot(),
+            SessionStoreBackend::Sharded(s) => s.snapshot(),
+        }
+    }
     pub fn find_multiplexer_with_capability(&self, capability: &str) -> Option<Multiplexer> {
         match self {
             SessionStoreBackend::Default(s) => s.find_multiplexer_with_capability(capability),
             SessionStoreBackend::Sharded(s) => s.find_multiplexer_with_capability(capability),
         }
     }
+    pub fn find_multiplexer_for(
+        &self,
+        capability: &str,
+        protocol: Protocol,
+    ) -> Option<Multiplexer> {
+        match self {
+            SessionStoreBackend::Default(s) => s.find_multiplexer_for(capability, protocol),
+            SessionStoreBackend::Sharded(s) => s.find_multiplexer_for(capability, protocol),
+        }
+    }
 }
 
 impl Default for SessionStoreBackend {
@@ -442,6 +931,7 @@ impl Default for SessionStoreBackend {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
     use uuid::Uuid;
 
     #[test]
@@ -465,21 +955,55 @@ mod tests {
 
     #[test]
     fn test_stale_cleanup() {
-        let store = SessionStore::new();
-        let id = Uuid::new_v4();
         let addr = "127.0.0.1:1234".parse().unwrap();
-        let mut session =
-            Session::new(id, "test-tunnel".into(), addr, "token".into(), vec![], None);
-
-        // Artificially age the session
-        session.last_heartbeat = Instant::now()
-            .checked_sub(Duration::from_secs(100))
-            .unwrap();
-        store.add(session).unwrap();
+        for store in [
+            SessionStoreBackend::default(),
+            SessionStoreBackend::Sharded(ShardedSessionStore::with_shards(4)),
+        ] {
+            let clock = MockClock::new();
+            let store = store.with_clock(clock.clone());
+            let id = Uuid::new_v4();
+            let session =
+                Session::new(id, "test-tunnel".into(), addr, "token".into(), vec![], None);
+            store.add(session).unwrap();
+
+            let timeout = Duration::from_secs(90);
+            clock.advance(Duration::from_secs(60));
+            assert_eq!(store.cleanup_stale_sessions(timeout), 0);
+            assert!(store.get(&id).is_some());
+
+            clock.advance(Duration::from_secs(40));
+            assert_eq!(store.cleanup_stale_sessions(timeout), 1);
+            assert_eq!(store.count(), 0);
+        }
+    }
 
-        let count = store.cleanup_stale_sessions(Duration::from_secs(90));
-        assert_eq!(count, 1);
-        assert_eq!(store.count(), 0);
+    #[test]
+    fn test_stale_cleanup_uses_store_clock() {
+        let addr = "127.0.0.1:1234".parse().unwrap();
+        for store in [
+            SessionStoreBackend::default(),
+            SessionStoreBackend::Sharded(ShardedSessionStore::with_shards(4)),
+        ] {
+            let clock = MockClock::new();
+            let store = store.with_clock(clock.clone());
+            // Real time passes that the mock clock never sees
+            std::thread::sleep(Duration::from_millis(100));
+            let id = Uuid::new_v4();
+            let session =
+                Session::new(id, "test-tunnel".into(), addr, "token".into(), vec![], None);
+            store.add(session).unwrap();
+
+            let timeout = Duration::from_millis(50);
+            clock.advance(Duration::from_millis(40));
+            assert!(store.record_heartbeat(&id));
+            clock.advance(Duration::from_millis(40));
+            assert_eq!(store.cleanup_stale_sessions(timeout), 0);
+
+            clock.advance(Duration::from_millis(20));
+            assert_eq!(store.cleanup_stale_sessions(timeout), 1);
+            assert!(!store.record_heartbeat(&id));
+        }
     }
 
     #[test]
@@ -531,6 +1055,76 @@ mod tests {
         assert!(store.get_by_tunnel_id("my-tunnel").is_some());
     }
 
+    #[test]
+    fn test_add_or_resume_requires_same_client() {
+        let addr = "127.0.0.1:1234".parse().unwrap();
+        for store in [
+            SessionStoreBackend::default(),
+            SessionStoreBackend::Sharded(ShardedSessionStore::with_shards(4)),
+        ] {
+            let id1 = Uuid::new_v4();
+            let session1 =
+                Session::new(id1, "my-tunnel".into(), addr, "token".into(), vec![], None);
+            store.add(session1).unwrap();
+
+            // Another token naming the old session is still rejected
+            let other = Session::new(
+                Uuid::new_v4(),
+                "my-tunnel".into(),
+                addr,
+                "other".into(),
+                vec![],
+                None,
+            );
+            assert!(store.add_or_resume(other, Some(id1)).is_err());
+            // So is the same token without it
+            let fresh = Session::new(
+                Uuid::new_v4(),
+                "my-tunnel".into(),
+                addr,
+                "token".into(),
+                vec![],
+                None,
+            );
+            assert!(store.add_or_resume(fresh, None).is_err());
+
+            let id2 = Uuid::new_v4();
+            let session2 =
+                Session::new(id2, "my-tunnel".into(), addr, "token".into(), vec![], None);
+            let replaced = store.add_or_resume(session2, Some(id1)).unwrap();
+            assert_eq!(replaced.map(|old| old.id), Some(id1));
+            assert_eq!(store.count(), 1);
+            assert!(store.get(&id1).is_none());
+            assert_eq!(store.get_by_tunnel_id("my-tunnel").unwrap().id, id2);
+
+            // Challenge-auth sessions carry no token and only match by client ID
+            let challenged = |id, client_id: Option<&str>| {
+                Session::new(id, "my-tunnel".into(), addr, String::new(), vec![], None)
+                    .with_client_id(client_id.map(str::to_string))
+            };
+            let id4 = Uuid::new_v4();
+            store.remove(&id2);
+            store.add(challenged(id4, Some("a"))).unwrap();
+            let other = challenged(Uuid::new_v4(), Some("b"));
+            assert!(store.add_or_resume(other, Some(id4)).is_err());
+            let id5 = Uuid::new_v4();
+            let replaced = store.add_or_resume(challenged(id5, Some("a")), Some(id4));
+            assert_eq!(replaced.unwrap().map(|old| old.id), Some(id4));
+            store.remove(&id5);
+            let id6 = Uuid::new_v4();
+            store.add(challenged(id6, None)).unwrap();
+            let unidentified = challenged(Uuid::new_v4(), None);
+            assert!(store.add_or_resume(unidentified, Some(id6)).is_err());
+            store.remove(&id6);
+
+            // A session that is already gone leaves nothing to replace
+            let id3 = Uuid::new_v4();
+            let session3 =
+                Session::new(id3, "new-tunnel".into(), addr, "token".into(), vec![], None);
+            assert!(store.add_or_resume(session3, Some(id1)).unwrap().is_none());
+        }
+    }
+
     #[test]
     fn test_sharded_store_same_api() {
         let store = ShardedSessionStore::with_shards(4);
@@ -554,4 +1148,182 @@ mod tests {
         assert_eq!(store.count(), 0);
         assert!(store.get_by_tunnel_id("shard-tunnel").is_none());
     }
+
+    #[test]
+    fn test_for_each_visits_all_sessions() {
+        let addr = "127.0.0.1:1234".parse().unwrap();
+        for store in [
+            SessionStoreBackend::default(),
+            SessionStoreBackend::Sharded(ShardedSessionStore::with_shards(4)),
+        ] {
+            for name in ["a", "b", "c"] {
+                let session = Session::new(
+                    Uuid::new_v4(),
+                    name.into(),
+                    addr,
+                    "token".into(),
+                    vec![],
+                    None,
+                );
+                store.add(session).unwrap();
+            }
+            let mut seen = Vec::new();
+            store.for_each(|s| seen.push(s.tunnel_id.clone()));
+            seen.sort();
+            assert_eq!(seen, ["a", "b", "c"]);
+        }
+    }
+
+    /// A session for `tunnel_id` with a live multiplexer
+    fn pooled_session(
+        tunnel_id: &str,
+    ) -> (
+        Session,
+        kanal::AsyncReceiver<crate::stream::PrioritizedFrame>,
+    ) {
+        let (frame_tx, frame_rx) = kanal::bounded_async(16);
+        let (multiplexer, _) = Multiplexer::new(frame_tx, false);
+        let session = Session::new(
+            Uuid::new_v4(),
+            tunnel_id.into(),
+            "127.0.0.1:1234".parse().unwrap(),
+            "token".into(),
+            vec![],
+            Some(multiplexer),
+        );
+        (session, frame_rx)
+    }
+
+    #[tokio::test]
+    async fn test_find_multiplexer_for_requires_protocol_capability() {
+        for store in [
+            SessionStoreBackend::default(),
+            SessionStoreBackend::Sharded(ShardedSessionStore::with_shards(4)),
+        ] {
+            let (mut ssh_only, _frames) = pooled_session("ssh-only");
+            ssh_only.capabilities = vec!["basic".into(), "ssh".into(), "protocols".into()];
+            assert!(ssh_only.accepts(Protocol::HTTP));
+            assert!(!ssh_only.accepts(Protocol::TCP));
+            assert!(!ssh_only.accepts(Protocol::WebSocket));
+            store.add(ssh_only).unwrap();
+
+            assert!(store.find_multiplexer_with_capability("ssh").is_some());
+            assert!(store.find_multiplexer_for("ssh", Protocol::TCP).is_none());
+
+            let (mut ssh_tcp, _frames) = pooled_session("ssh-tcp");
+            ssh_tcp.capabilities = vec!["ssh".into(), "tcp".into()];
+            assert!(ssh_tcp.accepts(Protocol::TCP));
+            store.add(ssh_tcp).unwrap();
+            assert!(store.find_multiplexer_for("ssh", Protocol::TCP).is_some());
+            assert!(store.find_multiplexer_for("ssh", Protocol::UDP).is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_legacy_session_accepts_websocket() {
+        let (mut legacy, _frames) = pooled_session("legacy");
+        legacy.capabilities = vec!["basic".into()];
+        assert!(legacy.accepts(Protocol::WebSocket));
+        assert!(!legacy.accepts(Protocol::TCP));
+
+        legacy.capabilities.push(STREAM_PROTOCOLS_CAPABILITY.into());
+        assert!(!legacy.accepts(Protocol::WebSocket));
+        legacy.capabilities.push(WEBSOCKET_CAPABILITY.into());
+        assert!(legacy.accepts(Protocol::WebSocket));
+    }
+
+    #[tokio::test]
+    async fn test_pool_round_robin() {
+        let store = SessionStoreBackend::default().with_pool_policy(Some(PoolPolicy::RoundRobin));
+        let (a, _a_frames) = pooled_session("web");
+        let (b, _b_frames) = pooled_session("web");
+        let (a_id, b_id) = (a.id, b.id);
+        store.add(a).unwrap();
+        store.add(b).unwrap();
+        assert_eq!(store.count(), 2);
+
+        let picks: Vec<Uuid> = (0..4)
+            .map(|_| store.select_by_tunnel_id("web", &[]).unwrap().id)
+            .collect();
+        assert_eq!(picks.iter().filter(|id| **id == a_id).count(), 2);
+        assert_eq!(picks.iter().filter(|id| **id == b_id).count(), 2);
+        assert_eq!(store.select_by_tunnel_id("web", &[a_id]).unwrap().id, b_id);
+        assert!(store.select_by_tunnel_id("web", &[a_id, b_id]).is_none());
+
+        // The pool shrinks as members leave
+        store.remove(&a_id);
+        assert_eq!(store.get_by_tunnel_id("web").unwrap().id, b_id);
+        assert_eq!(store.select_by_tunnel_id("web", &[]).unwrap().id, b_id);
+        store.remove(&b_id);
+        assert!(store.select_by_tunnel_id("web", &[]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pool_least_streams() {
+        let store = SessionStoreBackend::Sharded(ShardedSessionStore::with_shards(4))
+            .with_pool_policy(Some(PoolPolicy::LeastStreams));
+        let (a, _a_frames) = pooled_session("api");
+        let (b, _b_frames) = pooled_session("api");
+        let (a_id, b_id) = (a.id, b.id);
+        let busy = a.multiplexer.clone().unwrap();
+        store.add(a).unwrap();
+        store.add(b).unwrap();
+
+        let _stream = busy.open_stream(Protocol::HTTP).await.unwrap();
+        for _ in 0..4 {
+            assert_eq!(store.select_by_tunnel_id("api", &[]).unwrap().id, b_id);
+        }
+        assert_eq!(store.select_by_tunnel_id("api", &[b_id]).unwrap().id, a_id);
+    }
+
+    #[tokio::test]
+    async fn test_pool_disabled_rejects_second_session() {
+        let store = SessionStoreBackend::default();
+        assert_eq!(store.pool_policy(), None);
+        let (a, _a_frames) = pooled_session("solo");
+        let (b, _b_frames) = pooled_session("solo");
+        store.add(a).unwrap();
+        assert!(matches!(
+            store.add(b),
+            Err(SessionStoreError::TunnelIdAlreadyExists(_))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_holds_no_guards() {
+        let addr = "127.0.0.1:1234".parse().unwrap();
+        for store in [
+            SessionStoreBackend::default(),
+            SessionStoreBackend::Sharded(ShardedSessionStore::with_shards(2)),
+        ] {
+            let ids: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+            for (i, id) in ids.iter().enumerate() {
+                let session = Session::new(
+                    *id,
+                    format!("tunnel-{i}"),
+                    addr,
+                    "token".into(),
+                    vec![],
+                    None,
+                );
+                store.add(session).unwrap();
+            }
+
+            let snapshot = store.snapshot();
+            let mut seen: Vec<Uuid> = snapshot.iter().map(|s| s.id).collect();
+            seen.sort();
+            let mut expected = ids.clone();
+            expected.sort();
+            assert_eq!(seen, expected);
+
+            // Writing to every shard while the snapshot is alive would
+            // deadlock if it still held a guard
+            for session in &snapshot {
+                store.get_mut(&session.id).unwrap().update_heartbeat();
+                assert!(store.remove(&session.id).is_some());
+            }
+            assert_eq!(store.count(), 0);
+            assert_eq!(snapshot.len(), ids.len());
+        }
+    }
 }
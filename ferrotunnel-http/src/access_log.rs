@@ -0,0 +1,235 @@
+//! Access logging for the HTTP ingress
+//!
+//! One line is emitted per request under the `ferrotunnel::access` tracing
+//! target once the response body has finished (or been dropped), so streaming
+//! and buffered responses report the same fields: tunnel ID, method, path,
+//! status, request/response bytes and total duration.
+
+use bytes::Bytes;
+use ferrotunnel_plugin::ByteCounter;
+use http_body_util::combinators::BoxBody;
+use hyper::body::{Body, Frame, SizeHint};
+use hyper::{Request, Response};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+/// Tracing target used for access log lines
+pub const ACCESS_LOG_TARGET: &str = "ferrotunnel::access";
+
+/// Output format for ingress access logs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AccessLogFormat {
+    /// Human readable line: `peer tunnel "METHOD path" status req_bytes resp_bytes duration`
+    Text,
+    /// One JSON object per line
+    Json,
+    /// No access logging
+    #[default]
+    Off,
+}
+
+/// Fields for a single in-flight request
+#[derive(Debug)]
+pub(crate) struct AccessLogEntry {
+    format: AccessLogFormat,
+    remote_addr: SocketAddr,
+    tunnel_id: String,
+    method: String,
+    path: String,
+    status: u16,
+    start: Instant,
+    request_bytes: ByteCounter,
+}
+
+impl AccessLogEntry {
+    /// Start timing a request; `tunnel_id` is the normalized Host, if valid
+    pub(crate) fn start<B>(
+        format: AccessLogFormat,
+        req: &Request<B>,
+        remote_addr: SocketAddr,
+        tunnel_id: Option<String>,
+    ) -> Self {
+        Self {
+            format,
+            remote_addr,
+            tunnel_id: tunnel_id.unwrap_or_else(|| "-".to_string()),
+            method: req.method().to_string(),
+            path: req.uri().path().to_string(),
+            status: 0,
+            start: Instant::now(),
+            request_bytes: ByteCounter::default(),
+        }
+    }
+
+    /// Counter to be incremented as request body bytes are forwarded
+    pub(crate) fn request_bytes(&self) -> ByteCounter {
+        self.request_bytes.clone()
+    }
+
+    /// Wrap `res` so the entry is emitted once its body completes
+    pub(crate) fn attach<E>(
+        mut self,
+        res: Response<BoxBody<Bytes, E>>,
+    ) -> Response<BoxBody<Bytes, E>>
+    where
+        E: 'static,
+    {
+        self.status = res.status().as_u16();
+        res.map(|body| {
+            BoxBody::new(LoggedBody {
+                inner: body,
+                response_bytes: 0,
+                entry: Some(self),
+            })
+        })
+    }
+
+    fn emit(&self, response_bytes: u64) {
+        let request_bytes = self.request_bytes.get();
+        let duration_ms = u64::try_from(self.start.elapsed().as_millis()).unwrap_or(u64::MAX);
+        match self.format {
+            AccessLogFormat::Json => {
+                let line = serde_json::json!({
+                    "remote_addr": self.remote_addr.to_string(),
+                    "tunnel_id": self.tunnel_id,
+                    "method": self.method,
+                    "path": self.path,
+                    "status": self.status,
+                    "request_bytes": request_bytes,
+                    "response_bytes": response_bytes,
+                    "duration_ms": duration_ms,
+                });
+                tracing::info!(target: ACCESS_LOG_TARGET, "{}", line);
+            }
+            AccessLogFormat::Text => {
+                tracing::info!(
+                    target: ACCESS_LOG_TARGET,
+                    "{} {} \"{} {}\" {} {} {} {}ms",
+                    self.remote_addr,
+                    self.tunnel_id,
+                    self.method,
+                    self.path,
+                    self.status,
+                    request_bytes,
+                    response_bytes,
+                    duration_ms
+                );
+            }
+            AccessLogFormat::Off => {}
+        }
+    }
+}
+
+/// Body wrapper counting data bytes into a shared counter
+pub(crate) struct CountingBody<B> {
+    inner: B,
+    bytes: ByteCounter,
+}
+
+impl<B> CountingBody<B> {
+    pub(crate) fn new(inner: B, bytes: ByteCounter) -> Self {
+        Self { inner, bytes }
+    }
+}
+
+impl<B> Body for CountingBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            if let Some(data) = frame.data_ref() {
+                self.bytes.add(data.len() as u64);
+            }
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Response body that emits its access log entry when finished or dropped
+struct LoggedBody<E> {
+    inner: BoxBody<Bytes, E>,
+    response_bytes: u64,
+    entry: Option<AccessLogEntry>,
+}
+
+impl<E> Body for LoggedBody<E> {
+    type Data = Bytes;
+    type Error = E;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, E>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    self.response_bytes += data.len() as u64;
+                }
+            }
+            Poll::Ready(None) => self.finish(),
+            _ => {}
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl<E> LoggedBody<E> {
+    fn finish(&mut self) {
+        if let Some(entry) = self.entry.take() {
+            entry.emit(self.response_bytes);
+        }
+    }
+}
+
+impl<E> Drop for LoggedBody<E> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::{BodyExt, Full};
+
+    #[tokio::test]
+    async fn test_counting_body_counts_data_bytes() {
+        let counter = ByteCounter::default();
+        let body = CountingBody::new(Full::new(Bytes::from_static(b"hello")), counter.clone());
+        let collected = body.collect().await.unwrap().to_bytes();
+        assert_eq!(collected, "hello");
+        assert_eq!(counter.get(), 5);
+    }
+
+    #[test]
+    fn test_access_log_format_default_is_off() {
+        assert_eq!(AccessLogFormat::default(), AccessLogFormat::Off);
+    }
+}
@@ -0,0 +1,694 @@
+//! Response cache for the HTTP ingress
+//!
+//! Successful `GET` responses are kept in memory and served again without a
+//! round trip through the tunnel while they are fresh. Entries are keyed on
+//! the tunnel, `Host`, path and query, and one variant is kept per URL: the
+//! request headers named in the response's `Vary` must match for a hit.
+//!
+//! Only responses that declare their freshness are stored: the upstream
+//! `Cache-Control` (`s-maxage`, then `max-age`) or `Expires`, capped at
+//! [`CacheConfig::max_ttl`]. Responses marked `no-store` or `private`,
+//! setting cookies, varying on `*` or larger than
+//! [`CacheConfig::max_body_size`] are never stored, and `no-cache` responses
+//! are stored only to be revalidated. A stale entry with an `ETag` is
+//! revalidated with `If-None-Match`, and a `304 Not Modified` from upstream
+//! refreshes it. Clients whose `If-None-Match` matches a fresh entry get a
+//! 304 straight from the cache.
+//!
+//! Requests carrying `Authorization` or `Cookie` only share responses marked
+//! `public` or with `s-maxage`. Requests asking for `no-cache`/`no-store`
+//! bypass the cache, and other methods on a cached URL evict it.
+
+use crate::ingress::BoxError;
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Empty, Full};
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use hyper::{Method, Response, StatusCode};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Headers a `304 Not Modified` carries over from the cached response
+const NOT_MODIFIED_HEADERS: &[HeaderName] = &[
+    header::CACHE_CONTROL,
+    header::CONTENT_LOCATION,
+    header::DATE,
+    header::ETAG,
+    header::EXPIRES,
+    header::LAST_MODIFIED,
+    header::VARY,
+];
+
+/// Response cache settings for the HTTP ingress
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Maximum number of cached responses; the least recently used one is
+    /// evicted to make room (default: 1000)
+    pub capacity: usize,
+    /// Upper bound on the freshness a response declares (default: 1 hour)
+    pub max_ttl: Duration,
+    /// Responses with a larger or unknown `Content-Length` are not cached
+    /// (default: 1MB)
+    pub max_body_size: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1000,
+            max_ttl: Duration::from_secs(3600),
+            max_body_size: 1024 * 1024,
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Set the maximum number of cached responses
+    #[must_use]
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Set the longest time a response is served from the cache without
+    /// revalidation, whatever freshness it declares
+    #[must_use]
+    pub fn with_max_ttl(mut self, max_ttl: Duration) -> Self {
+        self.max_ttl = max_ttl;
+        self
+    }
+
+    /// Set the largest response body worth caching
+    #[must_use]
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+}
+
+/// Outcome of looking a request up in the cache
+pub enum Lookup {
+    /// Answer the request with this response
+    Hit(Response<BoxBody<Bytes, BoxError>>),
+    /// The entry is stale; revalidate it upstream with this `ETag`
+    Stale(HeaderValue),
+    /// Nothing usable is cached
+    Miss,
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// Request headers named in `Vary`, with the values they were stored under
+    vary: Vec<(HeaderName, Option<HeaderValue>)>,
+    headers: HeaderMap,
+    body: Bytes,
+    stored_at: Instant,
+    ttl: Duration,
+    /// Marked `public` or `s-maxage`, so requests with credentials may use it
+    shared: bool,
+    last_used: u64,
+}
+
+impl Entry {
+    fn matches(&self, request: &HeaderMap) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| request.get(name) == value.as_ref())
+            && (self.shared || !has_credentials(request))
+    }
+
+    fn response(&self) -> Response<BoxBody<Bytes, BoxError>> {
+        let body = Full::new(self.body.clone())
+            .map_err(|never| match never {})
+            .boxed();
+        let mut res = Response::new(body);
+        *res.headers_mut() = self.headers.clone();
+        res.headers_mut()
+            .insert(header::AGE, self.stored_at.elapsed().as_secs().into());
+        res
+    }
+
+    fn not_modified(&self) -> Response<BoxBody<Bytes, BoxError>> {
+        let mut res = Response::new(Empty::new().map_err(|never| match never {}).boxed());
+        *res.status_mut() = StatusCode::NOT_MODIFIED;
+        for name in NOT_MODIFIED_HEADERS {
+            for value in self.headers.get_all(name) {
+                res.headers_mut().append(name, value.clone());
+            }
+        }
+        res
+    }
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    by_key: HashMap<String, Entry>,
+    /// Keys by their last use, oldest first, for least-recently-used eviction
+    by_use: BTreeMap<u64, String>,
+    /// Use counter
+    clock: u64,
+}
+
+impl Entries {
+    /// Mark the entry under `key` as just used
+    fn touch(&mut self, key: &str) {
+        self.clock += 1;
+        let now = self.clock;
+        if let Some(entry) = self.by_key.get_mut(key) {
+            self.by_use.remove(&entry.last_used);
+            entry.last_used = now;
+            self.by_use.insert(now, key.to_string());
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.by_key.remove(key)?;
+        self.by_use.remove(&entry.last_used);
+        Some(entry)
+    }
+
+    fn insert(&mut self, key: String, mut entry: Entry) {
+        self.remove(&key);
+        self.clock += 1;
+        entry.last_used = self.clock;
+        self.by_use.insert(self.clock, key.clone());
+        self.by_key.insert(key, entry);
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some((_, key)) = self.by_use.pop_first() {
+            self.by_key.remove(&key);
+        }
+    }
+}
+
+/// In-memory cache of upstream responses, shared by all ingress connections
+#[derive(Debug)]
+pub struct ResponseCache {
+    config: CacheConfig,
+    entries: Mutex<Entries>,
+}
+
+impl ResponseCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        match self.entries.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Number of cached responses
+    pub fn len(&self) -> usize {
+        self.lock().by_key.len()
+    }
+
+    /// Whether nothing is cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Cache key for a request to `tunnel_id`, or `None` when the request
+    /// must bypass the cache
+    pub fn key(
+        tunnel_id: &str,
+        method: &Method,
+        uri: &hyper::Uri,
+        headers: &HeaderMap,
+    ) -> Option<String> {
+        if method != Method::GET {
+            return None;
+        }
+        let bypass = |name| {
+            directives(headers, name).any(|(directive, _)| {
+                directive.eq_ignore_ascii_case("no-cache")
+                    || directive.eq_ignore_ascii_case("no-store")
+            })
+        };
+        if bypass(header::CACHE_CONTROL) || bypass(header::PRAGMA) {
+            return None;
+        }
+        Some(url_key(tunnel_id, uri, headers))
+    }
+
+    /// Evict the response cached for the request to `uri` on `tunnel_id`,
+    /// after a request that may have changed it
+    pub fn invalidate(&self, tunnel_id: &str, uri: &hyper::Uri, headers: &HeaderMap) {
+        self.lock().remove(&url_key(tunnel_id, uri, headers));
+    }
+
+    /// Look up the response cached under `key` for a request with `headers`
+    pub fn lookup(&self, key: &str, headers: &HeaderMap) -> Lookup {
+        let mut entries = self.lock();
+        if !entries.by_key.get(key).is_some_and(|e| e.matches(headers)) {
+            return Lookup::Miss;
+        }
+        entries.touch(key);
+        let Some(entry) = entries.by_key.get(key) else {
+            return Lookup::Miss;
+        };
+        if entry.stored_at.elapsed() < entry.ttl {
+            let etag = entry.headers.get(header::ETAG);
+            if etag.is_some_and(|etag| etag_matches(headers, etag)) {
+                return Lookup::Hit(entry.not_modified());
+            }
+            return Lookup::Hit(entry.response());
+        }
+        match entry.headers.get(header::ETAG) {
+            Some(etag) => Lookup::Stale(etag.clone()),
+            None => {
+                entries.remove(key);
+                Lookup::Miss
+            }
+        }
+    }
+
+    /// Whether a response with `status` and `headers` to a request with
+    /// `request` headers may be stored, judged before its body is read
+    pub fn is_storable(
+        &self,
+        request: &HeaderMap,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> bool {
+        if status != StatusCode::OK || self.config.capacity == 0 {
+            return false;
+        }
+        if has_credentials(request) && !is_shared(headers) {
+            return false;
+        }
+        if headers.contains_key(header::SET_COOKIE)
+            || directives(headers, header::VARY).any(|(name, _)| name == "*")
+        {
+            return false;
+        }
+        let fits = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<usize>().ok())
+            .is_some_and(|len| len <= self.config.max_body_size);
+        match self.freshness(headers) {
+            Some(ttl) if ttl.is_zero() => fits && headers.contains_key(header::ETAG),
+            Some(_) => fits,
+            None => false,
+        }
+    }
+
+    /// Store a response to the request with `request` headers under `key`
+    pub fn store(
+        &self,
+        key: String,
+        request: &HeaderMap,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: Bytes,
+    ) {
+        if !self.is_storable(request, status, headers) || body.len() > self.config.max_body_size {
+            return;
+        }
+        let Some(ttl) = self.freshness(headers) else {
+            return;
+        };
+        let vary = directives(headers, header::VARY)
+            .filter_map(|(name, _)| HeaderName::from_bytes(name.as_bytes()).ok())
+            .map(|name| {
+                let value = request.get(&name).cloned();
+                (name, value)
+            })
+            .collect();
+
+        let mut entries = self.lock();
+        if !entries.by_key.contains_key(&key) && entries.by_key.len() >= self.config.capacity {
+            entries.evict_oldest();
+        }
+        entries.insert(
+            key,
+            Entry {
+                vary,
+                headers: headers.clone(),
+                body,
+                stored_at: Instant::now(),
+                ttl,
+                shared: is_shared(headers),
+                last_used: 0,
+            },
+        );
+    }
+
+    /// Mark the entry under `key` fresh again after upstream answered its
+    /// revalidation with `304 Not Modified` and `headers`, returning the
+    /// cached response, or `None` if the entry is gone
+    pub fn refresh(
+        &self,
+        key: &str,
+        headers: &HeaderMap,
+    ) -> Option<Response<BoxBody<Bytes, BoxError>>> {
+        let ttl = self.freshness(headers);
+        let mut entries = self.lock();
+        let entry = entries.by_key.get_mut(key)?;
+        entry.stored_at = Instant::now();
+        if let Some(ttl) = ttl {
+            entry.ttl = ttl;
+        }
+        Some(entry.response())
+    }
+
+    /// How long a response with `headers` stays fresh, or `None` if it must
+    /// not be stored or does not say
+    fn freshness(&self, headers: &HeaderMap) -> Option<Duration> {
+        let mut max_age = None;
+        let mut shared_max_age = None;
+        for (directive, value) in directives(headers, header::CACHE_CONTROL) {
+            let seconds = || value.and_then(|v| v.trim_matches('"').parse::<u64>().ok());
+            match directive.to_ascii_lowercase().as_str() {
+                "no-store" | "private" => return None,
+                "no-cache" => return Some(Duration::ZERO),
+                "max-age" => max_age = seconds(),
+                "s-maxage" => shared_max_age = seconds(),
+                _ => {}
+            }
+        }
+        shared_max_age
+            .or(max_age)
+            .map(Duration::from_secs)
+            .or_else(|| expires_in(headers))
+            .map(|ttl| ttl.min(self.config.max_ttl))
+    }
+}
+
+/// Cache key of the URL a request with `headers` targets on `tunnel_id`
+fn url_key(tunnel_id: &str, uri: &hyper::Uri, headers: &HeaderMap) -> String {
+    let host = uri
+        .authority()
+        .map(hyper::http::uri::Authority::as_str)
+        .or_else(|| headers.get(header::HOST).and_then(|v| v.to_str().ok()))
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let path = uri.path_and_query().map_or("/", |pq| pq.as_str());
+    format!("{tunnel_id} {host}{path}")
+}
+
+/// Whether a request carries credentials that may personalize the response
+fn has_credentials(request: &HeaderMap) -> bool {
+    request.contains_key(header::AUTHORIZATION) || request.contains_key(header::COOKIE)
+}
+
+/// Whether a response may be shared with requests that carry credentials
+fn is_shared(headers: &HeaderMap) -> bool {
+    directives(headers, header::CACHE_CONTROL).any(|(directive, _)| {
+        directive.eq_ignore_ascii_case("public") || directive.eq_ignore_ascii_case("s-maxage")
+    })
+}
+
+/// Freshness from `Expires`, relative to `Date` when the response has one.
+/// An unparseable `Expires` means already expired.
+fn expires_in(headers: &HeaderMap) -> Option<Duration> {
+    let date = |name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| httpdate::parse_http_date(v).ok())
+    };
+    let expires = date(header::EXPIRES)?;
+    let now = date(header::DATE).flatten().unwrap_or_else(SystemTime::now);
+    Some(
+        expires
+            .and_then(|expires| expires.duration_since(now).ok())
+            .unwrap_or_default(),
+    )
+}
+
+/// Comma-separated directives of every `name` header, split into name and
+/// optional `=value`
+fn directives(
+    headers: &HeaderMap,
+    name: HeaderName,
+) -> impl Iterator<Item = (&str, Option<&str>)> + '_ {
+    headers
+        .get_all(name)
+        .into_iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .map(|directive| match directive.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (directive, None),
+        })
+}
+
+/// Whether the request's `If-None-Match` matches `etag`, using the weak
+/// comparison (compression may have weakened the client's copy)
+fn etag_matches(headers: &HeaderMap, etag: &HeaderValue) -> bool {
+    let Ok(etag) = etag.to_str() else {
+        return false;
+    };
+    let etag = etag.trim_start_matches("W/");
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .into_iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fresh() -> (HeaderName, &'static str) {
+        (header::CACHE_CONTROL, "max-age=60")
+    }
+
+    fn headers(pairs: &[(HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn cacheable(extra: &[(HeaderName, &str)]) -> HeaderMap {
+        let mut pairs = vec![(header::CONTENT_LENGTH, "5")];
+        pairs.extend_from_slice(extra);
+        headers(&pairs)
+    }
+
+    fn key(path: &str, headers: &HeaderMap) -> Option<String> {
+        ResponseCache::key("app", &Method::GET, &path.parse().unwrap(), headers)
+    }
+
+    #[test]
+    fn test_request_bypass() {
+        let host = headers(&[(header::HOST, "App.Example.com")]);
+        assert_eq!(
+            key("/a?b=1", &host).as_deref(),
+            Some("app app.example.com/a?b=1")
+        );
+        assert_ne!(
+            key("/a", &host),
+            key("/a", &headers(&[(header::HOST, "other.example.com")]))
+        );
+        let plain = HeaderMap::new();
+        assert!(ResponseCache::key("app", &Method::POST, &"/a".parse().unwrap(), &plain).is_none());
+        assert!(key("/a", &headers(&[(header::CACHE_CONTROL, "no-cache")])).is_none());
+        assert!(key("/a", &headers(&[(header::PRAGMA, "no-cache")])).is_none());
+    }
+
+    #[test]
+    fn test_storable_responses() {
+        let cache = ResponseCache::new(CacheConfig::default().with_max_body_size(10));
+        let request = HeaderMap::new();
+        let storable = |headers: &HeaderMap| cache.is_storable(&request, StatusCode::OK, headers);
+        assert!(storable(&cacheable(&[fresh()])));
+        assert!(!cache.is_storable(&request, StatusCode::NOT_FOUND, &cacheable(&[fresh()])));
+        // Freshness must be declared
+        assert!(!storable(&cacheable(&[])));
+        assert!(storable(&cacheable(&[
+            (header::DATE, "Thu, 01 Jan 2026 00:00:00 GMT"),
+            (header::EXPIRES, "Thu, 01 Jan 2026 00:01:00 GMT"),
+        ])));
+        assert!(!storable(&cacheable(&[(header::EXPIRES, "0")])));
+        assert!(!storable(&cacheable(&[(
+            header::CACHE_CONTROL,
+            "public, no-store"
+        )])));
+        assert!(!storable(&cacheable(&[(header::CACHE_CONTROL, "private")])));
+        assert!(!storable(&cacheable(&[
+            fresh(),
+            (header::SET_COOKIE, "a=b")
+        ])));
+        assert!(!storable(&cacheable(&[fresh(), (header::VARY, "*")])));
+        // no-cache is only worth storing when it can be revalidated
+        let no_cache = (header::CACHE_CONTROL, "no-cache");
+        assert!(!storable(&cacheable(std::slice::from_ref(&no_cache))));
+        assert!(storable(&cacheable(&[no_cache, (header::ETAG, "\"v1\"")])));
+        // Unknown or oversized bodies are streamed instead
+        assert!(!storable(&headers(&[fresh()])));
+        assert!(!storable(&headers(&[
+            fresh(),
+            (header::CONTENT_LENGTH, "11")
+        ])));
+    }
+
+    #[test]
+    fn test_credentials_share_only_public_responses() {
+        let cache = ResponseCache::new(CacheConfig::default());
+        let cookie = headers(&[(header::COOKIE, "session=alice")]);
+        let auth = headers(&[(header::AUTHORIZATION, "Bearer x")]);
+        let ok = StatusCode::OK;
+        assert!(!cache.is_storable(&cookie, ok, &cacheable(&[fresh()])));
+        assert!(!cache.is_storable(&auth, ok, &cacheable(&[fresh()])));
+        let public = (header::CACHE_CONTROL, "public, max-age=60");
+        assert!(cache.is_storable(&cookie, ok, &cacheable(std::slice::from_ref(&public))));
+        let shared = (header::CACHE_CONTROL, "s-maxage=60");
+        assert!(cache.is_storable(&auth, ok, &cacheable(&[shared])));
+
+        // An anonymous response is not served to a request with credentials
+        let anonymous = HeaderMap::new();
+        cache.store(
+            "k".into(),
+            &anonymous,
+            ok,
+            &cacheable(&[fresh()]),
+            "hello".into(),
+        );
+        assert!(matches!(cache.lookup("k", &cookie), Lookup::Miss));
+        assert!(matches!(cache.lookup("k", &anonymous), Lookup::Hit(_)));
+
+        cache.store(
+            "p".into(),
+            &cookie,
+            ok,
+            &cacheable(&[public]),
+            "hello".into(),
+        );
+        assert!(matches!(cache.lookup("p", &auth), Lookup::Hit(_)));
+    }
+
+    #[test]
+    fn test_declared_freshness_is_capped() {
+        let cache = ResponseCache::new(CacheConfig::default().with_max_ttl(Duration::from_secs(5)));
+        let year = cacheable(&[(header::CACHE_CONTROL, "max-age=31536000")]);
+        assert_eq!(cache.freshness(&year), Some(Duration::from_secs(5)));
+        let shared = cacheable(&[(header::CACHE_CONTROL, "max-age=1, s-maxage=2")]);
+        assert_eq!(cache.freshness(&shared), Some(Duration::from_secs(2)));
+        assert_eq!(cache.freshness(&cacheable(&[])), None);
+    }
+
+    #[test]
+    fn test_hit_and_max_age_expiry() {
+        let cache = ResponseCache::new(CacheConfig::default());
+        let request = HeaderMap::new();
+        let response = cacheable(&[(header::CACHE_CONTROL, "max-age=0")]);
+        cache.store(
+            "k".into(),
+            &request,
+            StatusCode::OK,
+            &response,
+            "hello".into(),
+        );
+        // Expires at once and cannot be revalidated, so it is not kept
+        assert!(matches!(cache.lookup("k", &request), Lookup::Miss));
+        assert!(cache.is_empty());
+
+        let response = cacheable(&[fresh()]);
+        cache.store(
+            "k".into(),
+            &request,
+            StatusCode::OK,
+            &response,
+            "hello".into(),
+        );
+        let Lookup::Hit(res) = cache.lookup("k", &request) else {
+            panic!("expected a hit");
+        };
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[header::AGE], "0");
+    }
+
+    #[test]
+    fn test_vary_and_etag() {
+        let cache = ResponseCache::new(CacheConfig::default());
+        let gzip = headers(&[(header::ACCEPT_ENCODING, "gzip")]);
+        let response = cacheable(&[
+            fresh(),
+            (header::VARY, "Accept-Encoding"),
+            (header::ETAG, "\"v1\""),
+        ]);
+        cache.store("k".into(), &gzip, StatusCode::OK, &response, "hello".into());
+
+        assert!(matches!(cache.lookup("k", &HeaderMap::new()), Lookup::Miss));
+        assert!(matches!(cache.lookup("k", &gzip), Lookup::Hit(_)));
+
+        let mut conditional = gzip.clone();
+        conditional.insert(header::IF_NONE_MATCH, HeaderValue::from_static("W/\"v1\""));
+        let Lookup::Hit(res) = cache.lookup("k", &conditional) else {
+            panic!("expected a hit");
+        };
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers()[header::ETAG], "\"v1\"");
+    }
+
+    #[test]
+    fn test_stale_entry_revalidated() {
+        let cache = ResponseCache::new(CacheConfig::default());
+        let request = HeaderMap::new();
+        let response = cacheable(&[
+            (header::CACHE_CONTROL, "no-cache"),
+            (header::ETAG, "\"v1\""),
+        ]);
+        cache.store(
+            "k".into(),
+            &request,
+            StatusCode::OK,
+            &response,
+            "hello".into(),
+        );
+
+        let Lookup::Stale(etag) = cache.lookup("k", &request) else {
+            panic!("expected a stale entry");
+        };
+        assert_eq!(etag, "\"v1\"");
+        let not_modified = headers(&[fresh()]);
+        assert!(cache.refresh("k", &not_modified).is_some());
+        assert!(matches!(cache.lookup("k", &request), Lookup::Hit(_)));
+        assert!(cache.refresh("gone", &not_modified).is_none());
+    }
+
+    #[test]
+    fn test_least_recently_used_evicted() {
+        let cache = ResponseCache::new(CacheConfig::default().with_capacity(2));
+        let request = HeaderMap::new();
+        let store = |key: &str| {
+            cache.store(
+                key.into(),
+                &request,
+                StatusCode::OK,
+                &cacheable(&[fresh()]),
+                "hello".into(),
+            );
+        };
+        store("a");
+        store("b");
+        assert!(matches!(cache.lookup("a", &request), Lookup::Hit(_)));
+        store("c");
+        assert_eq!(cache.len(), 2);
+        assert!(matches!(cache.lookup("b", &request), Lookup::Miss));
+        assert!(matches!(cache.lookup("a", &request), Lookup::Hit(_)));
+        // Storing over an existing key keeps a single recency slot
+        store("a");
+        store("d");
+        assert!(matches!(cache.lookup("c", &request), Lookup::Miss));
+        assert!(matches!(cache.lookup("a", &request), Lookup::Hit(_)));
+        assert_eq!(cache.lock().by_use.len(), 2);
+    }
+}
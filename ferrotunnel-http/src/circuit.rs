@@ -0,0 +1,220 @@
+//! Per-tunnel circuit breaker for the HTTP ingress
+//!
+//! Tracks consecutive tunnel-level failures (stream open, upstream handshake
+//! and transport errors) per tunnel ID, and timeouts separately. Once a tunnel
+//! reaches either threshold its circuit opens and requests are rejected
+//! immediately until the cooldown elapses; the next request is then let
+//! through as a probe that either closes the circuit again or re-opens it.
+
+use ferrotunnel_plugin::builtin::CircuitState;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct Circuit {
+    state: CircuitState,
+    consecutive_failures: u32,
+    consecutive_timeouts: u32,
+    /// When the circuit opened, or when the current half-open probe started
+    since: Instant,
+}
+
+/// Circuit breakers keyed by tunnel ID
+#[derive(Debug)]
+pub struct TunnelCircuitBreakers {
+    failure_threshold: u32,
+    timeout_threshold: u32,
+    cooldown: Duration,
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+impl TunnelCircuitBreakers {
+    /// Create breakers that open after `failure_threshold` consecutive failures
+    /// and stay open for `cooldown`. A threshold of 0 disables the breaker.
+    ///
+    /// Timeouts do not open the circuit unless a
+    /// [timeout threshold](Self::with_timeout_threshold) is set.
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            timeout_threshold: 0,
+            cooldown,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Also open a circuit after `threshold` consecutive timeouts; 0 (the
+    /// default) ignores timeouts
+    #[must_use]
+    pub fn with_timeout_threshold(mut self, threshold: u32) -> Self {
+        self.timeout_threshold = threshold;
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Circuit>> {
+        match self.circuits.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Current state of a tunnel's circuit
+    pub fn state(&self, tunnel_id: &str) -> CircuitState {
+        self.lock()
+            .get(tunnel_id)
+            .map_or(CircuitState::Closed, |c| c.state)
+    }
+
+    /// Whether a request to `tunnel_id` may proceed
+    ///
+    /// An open circuit moves to half-open once the cooldown has elapsed, letting
+    /// a single probe request through. A probe that never reports back is
+    /// replaced by another after a further cooldown.
+    pub fn allow(&self, tunnel_id: &str) -> bool {
+        let mut circuits = self.lock();
+        let Some(circuit) = circuits.get_mut(tunnel_id) else {
+            return true;
+        };
+        match circuit.state {
+            CircuitState::Closed => true,
+            CircuitState::Open | CircuitState::HalfOpen => {
+                if circuit.since.elapsed() >= self.cooldown {
+                    circuit.state = CircuitState::HalfOpen;
+                    circuit.since = Instant::now();
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    /// Record a request that reached the tunnel's upstream
+    pub fn record_success(&self, tunnel_id: &str) {
+        self.lock().remove(tunnel_id);
+    }
+
+    /// Record a tunnel-level failure
+    pub fn record_failure(&self, tunnel_id: &str) {
+        self.record(tunnel_id, self.failure_threshold, |circuit| {
+            &mut circuit.consecutive_failures
+        });
+    }
+
+    /// Record a request that timed out waiting for the tunnel
+    ///
+    /// Counted apart from failures, since a slow backend is not a broken one.
+    pub fn record_timeout(&self, tunnel_id: &str) {
+        self.record(tunnel_id, self.timeout_threshold, |circuit| {
+            &mut circuit.consecutive_timeouts
+        });
+    }
+
+    fn record(&self, tunnel_id: &str, threshold: u32, counter: fn(&mut Circuit) -> &mut u32) {
+        if threshold == 0 {
+            return;
+        }
+        let mut circuits = self.lock();
+        let circuit = circuits
+            .entry(tunnel_id.to_string())
+            .or_insert_with(|| Circuit {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                consecutive_timeouts: 0,
+                since: Instant::now(),
+            });
+        let count = counter(circuit);
+        *count = count.saturating_add(1);
+        let reached = *count >= threshold;
+        let should_open = match circuit.state {
+            CircuitState::Closed => reached,
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+        if should_open {
+            circuit.state = CircuitState::Open;
+            circuit.since = Instant::now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let breakers = TunnelCircuitBreakers::new(3, Duration::from_secs(30));
+        breakers.record_failure("a");
+        breakers.record_failure("a");
+        assert!(breakers.allow("a"));
+        breakers.record_failure("a");
+        assert_eq!(breakers.state("a"), CircuitState::Open);
+        assert!(!breakers.allow("a"));
+
+        // Other tunnels are unaffected
+        assert!(breakers.allow("b"));
+    }
+
+    #[test]
+    fn test_success_resets_failures() {
+        let breakers = TunnelCircuitBreakers::new(2, Duration::from_secs(30));
+        breakers.record_failure("a");
+        breakers.record_success("a");
+        breakers.record_failure("a");
+        assert_eq!(breakers.state("a"), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_half_open_probe_after_cooldown() {
+        let breakers = TunnelCircuitBreakers::new(1, Duration::from_millis(20));
+        breakers.record_failure("a");
+        assert!(!breakers.allow("a"));
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breakers.allow("a"));
+        assert_eq!(breakers.state("a"), CircuitState::HalfOpen);
+        // Only one probe at a time
+        assert!(!breakers.allow("a"));
+
+        // Failed probe re-opens
+        breakers.record_failure("a");
+        assert_eq!(breakers.state("a"), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breakers.allow("a"));
+        breakers.record_success("a");
+        assert_eq!(breakers.state("a"), CircuitState::Closed);
+        assert!(breakers.allow("a"));
+    }
+
+    #[test]
+    fn test_timeouts_counted_separately() {
+        // Timeouts are ignored without a timeout threshold
+        let breakers = TunnelCircuitBreakers::new(2, Duration::from_secs(30));
+        for _ in 0..10 {
+            breakers.record_timeout("a");
+        }
+        assert!(breakers.allow("a"));
+
+        // ... and do not add to the failure count when one is set
+        let breakers =
+            TunnelCircuitBreakers::new(2, Duration::from_secs(30)).with_timeout_threshold(3);
+        breakers.record_failure("a");
+        breakers.record_timeout("a");
+        breakers.record_timeout("a");
+        assert_eq!(breakers.state("a"), CircuitState::Closed);
+        breakers.record_timeout("a");
+        assert_eq!(breakers.state("a"), CircuitState::Open);
+    }
+
+    #[test]
+    fn test_zero_threshold_disables() {
+        let breakers = TunnelCircuitBreakers::new(0, Duration::from_secs(30));
+        for _ in 0..10 {
+            breakers.record_failure("a");
+        }
+        assert!(breakers.allow("a"));
+    }
+}
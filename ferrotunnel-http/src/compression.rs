@@ -0,0 +1,420 @@
+//! Response compression for the HTTP ingress
+//!
+//! Tunneled responses are compressed with brotli or gzip when the client
+//! advertises support in `Accept-Encoding`. The body is encoded as it streams,
+//! so `Content-Length` is dropped and the response is sent chunked. Responses
+//! that are already encoded, carry a compressed media type, are smaller than
+//! [`CompressionConfig::min_size`] or ask for `Cache-Control: no-transform`
+//! are passed through untouched.
+
+use crate::ingress::BoxError;
+use async_compression::tokio::bufread::{BrotliEncoder, GzipEncoder};
+use bytes::Bytes;
+use futures::{StreamExt, TryStreamExt};
+use http_body_util::combinators::BoxBody;
+use http_body_util::BodyExt;
+use hyper::body::{Body, Frame, SizeHint};
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Method, Response, StatusCode};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::AsyncRead;
+use tokio_util::io::{ReaderStream, StreamReader};
+use tracing::warn;
+
+/// Media types that are already compressed and gain nothing from encoding
+const COMPRESSED_TYPES: &[&str] = &[
+    "image/",
+    "video/",
+    "audio/",
+    "font/woff",
+    "application/zip",
+    "application/gzip",
+    "application/x-gzip",
+    "application/x-brotli",
+    "application/x-bzip2",
+    "application/x-7z-compressed",
+    "application/x-rar-compressed",
+    "application/zstd",
+    "application/octet-stream",
+    // Event streams must not be held back in the encoder's buffer
+    "text/event-stream",
+];
+
+/// Response compression settings for the HTTP ingress
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionConfig {
+    /// Responses declaring a smaller `Content-Length` are sent uncompressed;
+    /// bodies of unknown length are always compressed (default: 1024)
+    pub min_size: u64,
+    /// Use gzip when the client accepts it (default: true)
+    pub gzip: bool,
+    /// Use brotli when the client accepts it; preferred over gzip at equal
+    /// quality (default: true)
+    pub brotli: bool,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            min_size: 1024,
+            gzip: true,
+            brotli: true,
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Set the minimum response size worth compressing
+    #[must_use]
+    pub fn with_min_size(mut self, min_size: u64) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Enable or disable gzip
+    #[must_use]
+    pub fn with_gzip(mut self, enabled: bool) -> Self {
+        self.gzip = enabled;
+        self
+    }
+
+    /// Enable or disable brotli
+    #[must_use]
+    pub fn with_brotli(mut self, enabled: bool) -> Self {
+        self.brotli = enabled;
+        self
+    }
+
+    /// Pick the encoding for a request from its method and `Accept-Encoding`
+    pub(crate) fn negotiate(&self, method: &Method, headers: &HeaderMap) -> Option<Encoding> {
+        if method == Method::HEAD {
+            return None;
+        }
+        let accept = headers.get(header::ACCEPT_ENCODING)?.to_str().ok()?;
+
+        let mut gzip_q = None;
+        let mut brotli_q = None;
+        let mut any_q = None;
+        for item in accept.split(',') {
+            let mut parts = item.split(';');
+            let coding = parts.next().unwrap_or_default().trim();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if coding.eq_ignore_ascii_case("gzip") || coding.eq_ignore_ascii_case("x-gzip") {
+                gzip_q = Some(quality);
+            } else if coding.eq_ignore_ascii_case("br") {
+                brotli_q = Some(quality);
+            } else if coding == "*" {
+                any_q = Some(quality);
+            }
+        }
+
+        let gzip_q = if self.gzip {
+            gzip_q.or(any_q).unwrap_or(0.0)
+        } else {
+            0.0
+        };
+        let brotli_q = if self.brotli {
+            brotli_q.or(any_q).unwrap_or(0.0)
+        } else {
+            0.0
+        };
+        if brotli_q > 0.0 && brotli_q >= gzip_q {
+            Some(Encoding::Brotli)
+        } else if gzip_q > 0.0 {
+            Some(Encoding::Gzip)
+        } else {
+            None
+        }
+    }
+
+    /// Whether a response with these status and headers should be compressed
+    fn should_compress(&self, status: StatusCode, headers: &HeaderMap) -> bool {
+        if status.is_informational()
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::PARTIAL_CONTENT
+            || status == StatusCode::NOT_MODIFIED
+        {
+            return false;
+        }
+        if headers.contains_key(header::CONTENT_ENCODING)
+            || headers.contains_key(header::CONTENT_RANGE)
+        {
+            return false;
+        }
+        let header_str = |name: header::HeaderName| headers.get(name).and_then(|v| v.to_str().ok());
+        if header_str(header::CACHE_CONTROL)
+            .is_some_and(|v| v.to_ascii_lowercase().contains("no-transform"))
+        {
+            return false;
+        }
+        if let Some(content_type) = header_str(header::CONTENT_TYPE) {
+            let content_type = content_type.trim().to_ascii_lowercase();
+            if content_type != "image/svg+xml"
+                && COMPRESSED_TYPES.iter().any(|t| content_type.starts_with(t))
+            {
+                return false;
+            }
+        }
+        let declared_len = header_str(header::CONTENT_LENGTH).and_then(|v| v.parse::<u64>().ok());
+        declared_len.is_none_or(|len| len >= self.min_size)
+    }
+
+    /// Compress `res` with `encoding` if the response qualifies
+    pub(crate) fn compress_response(
+        &self,
+        res: Response<BoxBody<Bytes, BoxError>>,
+        encoding: Encoding,
+    ) -> Response<BoxBody<Bytes, BoxError>> {
+        if !self.should_compress(res.status(), res.headers()) {
+            return res;
+        }
+
+        let (mut parts, body) = res.into_parts();
+        parts.headers.remove(header::CONTENT_LENGTH);
+        parts.headers.insert(
+            header::CONTENT_ENCODING,
+            HeaderValue::from_static(encoding.as_str()),
+        );
+        let varies = parts.headers.get_all(header::VARY).iter().any(|v| {
+            v.to_str()
+                .is_ok_and(|v| v.to_ascii_lowercase().contains("accept-encoding"))
+        });
+        if !varies {
+            parts
+                .headers
+                .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+        }
+        // The encoded representation is no longer byte-identical
+        if let Some(etag) = parts.headers.get(header::ETAG) {
+            if etag.as_bytes().starts_with(b"\"") {
+                let mut weak = b"W/".to_vec();
+                weak.extend_from_slice(etag.as_bytes());
+                if let Ok(weak) = HeaderValue::from_bytes(&weak) {
+                    parts.headers.insert(header::ETAG, weak);
+                }
+            }
+        }
+
+        Response::from_parts(parts, BoxBody::new(CompressedBody::new(body, encoding)))
+    }
+}
+
+/// Content coding applied to a response body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    Gzip,
+    Brotli,
+}
+
+impl Encoding {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Brotli => "br",
+        }
+    }
+}
+
+type EncodedReader = Pin<Box<dyn AsyncRead + Send + Sync>>;
+
+/// Response body encoded on the fly; trailers of the original body are dropped
+struct CompressedBody {
+    inner: ReaderStream<EncodedReader>,
+}
+
+impl CompressedBody {
+    fn new(body: BoxBody<Bytes, BoxError>, encoding: Encoding) -> Self {
+        let reader = StreamReader::new(body.into_data_stream().map_err(io::Error::other));
+        let encoded: EncodedReader = match encoding {
+            Encoding::Gzip => Box::pin(GzipEncoder::new(reader)),
+            Encoding::Brotli => Box::pin(BrotliEncoder::new(reader)),
+        };
+        Self {
+            inner: ReaderStream::new(encoded),
+        }
+    }
+}
+
+impl Body for CompressedBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, BoxError>>> {
+        match futures::ready!(self.inner.poll_next_unpin(cx)) {
+            Some(Ok(data)) => Poll::Ready(Some(Ok(Frame::data(data)))),
+            Some(Err(e)) => {
+                // Upstream body errors come back out of the reader as they
+                // were. Encoder errors fail the body too, so the client sees
+                // an aborted response rather than a silently truncated one.
+                let kind = e.kind();
+                let err = e.into_inner().unwrap_or_else(|| {
+                    warn!("Response compression failed: {}", kind);
+                    Box::new(io::Error::from(kind))
+                });
+                Poll::Ready(Some(Err(err)))
+            }
+            None => Poll::Ready(None),
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::Full;
+    use std::io::Read;
+
+    fn request_headers(accept: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT_ENCODING,
+            HeaderValue::from_str(accept).unwrap(),
+        );
+        headers
+    }
+
+    fn text_response(body: &'static [u8]) -> Response<BoxBody<Bytes, BoxError>> {
+        let mut res = Response::new(
+            Full::new(Bytes::from_static(body))
+                .map_err(|never| match never {})
+                .boxed(),
+        );
+        res.headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        res.headers_mut()
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+        res
+    }
+
+    #[test]
+    fn test_negotiate_prefers_brotli() {
+        let config = CompressionConfig::default();
+        let get = Method::GET;
+        assert_eq!(
+            config.negotiate(&get, &request_headers("gzip, deflate, br")),
+            Some(Encoding::Brotli)
+        );
+        assert_eq!(
+            config.negotiate(&get, &request_headers("gzip;q=1.0, br;q=0.5")),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(
+            config.negotiate(&get, &request_headers("br;q=0, gzip")),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(
+            config.negotiate(&get, &request_headers("*")),
+            Some(Encoding::Brotli)
+        );
+        assert_eq!(config.negotiate(&get, &request_headers("identity")), None);
+        assert_eq!(config.negotiate(&get, &HeaderMap::new()), None);
+        assert_eq!(
+            config.negotiate(&Method::HEAD, &request_headers("gzip")),
+            None
+        );
+        assert_eq!(
+            config
+                .with_brotli(false)
+                .negotiate(&get, &request_headers("br, gzip")),
+            Some(Encoding::Gzip)
+        );
+    }
+
+    #[test]
+    fn test_should_compress_skips_ineligible_responses() {
+        let config = CompressionConfig::default();
+        let headers = |pairs: &[(header::HeaderName, &'static str)]| {
+            let mut map = HeaderMap::new();
+            for (name, value) in pairs {
+                map.insert(name.clone(), HeaderValue::from_static(value));
+            }
+            map
+        };
+
+        assert!(config.should_compress(
+            StatusCode::OK,
+            &headers(&[(header::CONTENT_TYPE, "text/html; charset=utf-8")])
+        ));
+        assert!(config.should_compress(
+            StatusCode::OK,
+            &headers(&[(header::CONTENT_TYPE, "image/svg+xml")])
+        ));
+        assert!(!config.should_compress(
+            StatusCode::OK,
+            &headers(&[(header::CONTENT_TYPE, "image/png")])
+        ));
+        assert!(!config.should_compress(
+            StatusCode::OK,
+            &headers(&[(header::CONTENT_ENCODING, "gzip")])
+        ));
+        assert!(
+            !config.should_compress(StatusCode::OK, &headers(&[(header::CONTENT_LENGTH, "100")]))
+        );
+        assert!(!config.should_compress(
+            StatusCode::OK,
+            &headers(&[(header::CACHE_CONTROL, "public, no-transform")])
+        ));
+        assert!(!config.should_compress(StatusCode::NO_CONTENT, &HeaderMap::new()));
+    }
+
+    #[tokio::test]
+    async fn test_compress_response_gzip_round_trip() {
+        const BODY: &[u8] = &[b'a'; 4096];
+        let mut res = text_response(BODY);
+        res.headers_mut()
+            .insert(header::ETAG, HeaderValue::from_static("\"v1\""));
+
+        let res = CompressionConfig::default().compress_response(res, Encoding::Gzip);
+        assert_eq!(res.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(res.headers()[header::VARY], "accept-encoding");
+        assert_eq!(res.headers()[header::ETAG], "W/\"v1\"");
+        assert!(!res.headers().contains_key(header::CONTENT_LENGTH));
+
+        let compressed = res.into_body().collect().await.unwrap().to_bytes();
+        assert!(compressed.len() < BODY.len());
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&compressed[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, BODY);
+    }
+
+    #[tokio::test]
+    async fn test_small_response_left_uncompressed() {
+        let res = CompressionConfig::default()
+            .compress_response(text_response(b"Hello, World!"), Encoding::Gzip);
+        assert!(!res.headers().contains_key(header::CONTENT_ENCODING));
+        assert_eq!(res.headers()[header::CONTENT_LENGTH], "13");
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "Hello, World!");
+    }
+
+    #[tokio::test]
+    async fn test_body_error_fails_compressed_body() {
+        let frames: Vec<Result<Frame<Bytes>, BoxError>> = vec![
+            Ok(Frame::data(Bytes::from_static(&[b'a'; 2048]))),
+            Err("upstream reset".into()),
+        ];
+        let body = http_body_util::StreamBody::new(futures::stream::iter(frames));
+        let mut res = Response::new(BoxBody::new(body));
+        res.headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+
+        let res = CompressionConfig::default().compress_response(res, Encoding::Gzip);
+        assert_eq!(res.headers()[header::CONTENT_ENCODING], "gzip");
+        let err = res.into_body().collect().await.unwrap_err();
+        assert_eq!(err.to_string(), "upstream reset");
+    }
+}
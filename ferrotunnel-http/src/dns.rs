@@ -0,0 +1,297 @@
+//! Cached resolution of the local service address
+//!
+//! A hostname target is resolved once and its addresses are kept for a TTL,
+//! so new connections neither wait on the resolver nor fail while it is
+//! briefly unavailable. Connections rotate through the resolved A/AAAA
+//! records and race IPv6 against IPv4 in the style of Happy Eyeballs
+//! ([RFC 8305](https://www.rfc-editor.org/rfc/rfc8305)).
+
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tracing::{debug, warn};
+
+/// Default time resolved addresses are reused before resolving again
+pub const DEFAULT_DNS_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Delay before racing the next address while an attempt is still pending
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+type LookupFn =
+    Arc<dyn Fn(String) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> + Send + Sync>;
+
+/// Addresses from the last successful lookup
+struct Resolved {
+    addrs: Vec<SocketAddr>,
+    at: Instant,
+}
+
+/// Resolver for one `host:port` target with a TTL cache
+pub struct DnsCache {
+    target: String,
+    ttl: Duration,
+    lookup: LookupFn,
+    cached: Mutex<Option<Resolved>>,
+    /// Rotates the first address tried across connections
+    next: AtomicUsize,
+}
+
+impl std::fmt::Debug for DnsCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DnsCache")
+            .field("target", &self.target)
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DnsCache {
+    /// Create a cache for `target`; a zero `ttl` resolves on every connect
+    pub fn new(target: impl Into<String>, ttl: Duration) -> Self {
+        Self::with_lookup(target, ttl, |target| {
+            async move {
+                let addrs = tokio::net::lookup_host(target).await?;
+                Ok::<_, io::Error>(addrs.collect())
+            }
+            .boxed()
+        })
+    }
+
+    /// Create a cache that resolves through `lookup` instead of the system resolver
+    pub fn with_lookup<F>(target: impl Into<String>, ttl: Duration, lookup: F) -> Self
+    where
+        F: Fn(String) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> + Send + Sync + 'static,
+    {
+        Self {
+            target: target.into(),
+            ttl,
+            lookup: Arc::new(lookup),
+            cached: Mutex::new(None),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Target this cache resolves
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Resolved addresses, from the cache while it is fresh
+    ///
+    /// When a refresh fails the stale addresses are served instead, so a
+    /// resolver outage does not take the tunnel down with it.
+    pub async fn resolve(&self) -> io::Result<Vec<SocketAddr>> {
+        if let Some(addrs) = self.fresh() {
+            return Ok(addrs);
+        }
+
+        match (self.lookup)(self.target.clone()).await {
+            Ok(addrs) if !addrs.is_empty() => {
+                debug!("Resolved {} to {:?}", self.target, addrs);
+                *self.lock() = Some(Resolved {
+                    addrs: addrs.clone(),
+                    at: Instant::now(),
+                });
+                Ok(addrs)
+            }
+            result => {
+                let err = match result {
+                    Ok(_) => io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("{} resolved to no addresses", self.target),
+                    ),
+                    Err(e) => e,
+                };
+                if let Some(stale) = self.lock().as_ref() {
+                    warn!(
+                        "Resolving {} failed ({}), reusing cached addresses",
+                        self.target, err
+                    );
+                    return Ok(stale.addrs.clone());
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Connect to the target, starting at the next address in rotation
+    pub async fn connect(&self) -> io::Result<TcpStream> {
+        let addrs = self.resolve().await?;
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % addrs.len();
+        let mut rotated = addrs;
+        rotated.rotate_left(start);
+        happy_eyeballs(&interleave(&rotated), CONNECTION_ATTEMPT_DELAY).await
+    }
+
+    fn fresh(&self) -> Option<Vec<SocketAddr>> {
+        let cached = self.lock();
+        let resolved = cached.as_ref()?;
+        (resolved.at.elapsed() < self.ttl).then(|| resolved.addrs.clone())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<Resolved>> {
+        self.cached
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// Alternate address families, starting with the family of the first address
+fn interleave(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return Vec::new();
+    };
+    let (preferred, other): (Vec<_>, Vec<_>) = addrs
+        .iter()
+        .copied()
+        .partition(|addr| addr.is_ipv6() == first.is_ipv6());
+    let mut other = other.into_iter();
+    let mut ordered = Vec::with_capacity(addrs.len());
+    for addr in preferred {
+        ordered.push(addr);
+        ordered.extend(other.next());
+    }
+    ordered.extend(other);
+    ordered
+}
+
+/// Connect to the first address that accepts, starting the next attempt
+/// whenever one fails or `delay` passes without an answer
+async fn happy_eyeballs(addrs: &[SocketAddr], delay: Duration) -> io::Result<TcpStream> {
+    let mut pending = addrs.iter().copied();
+    let mut attempts = FuturesUnordered::new();
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no addresses to connect to");
+
+    loop {
+        if attempts.is_empty() {
+            match pending.next() {
+                Some(addr) => attempts.push(TcpStream::connect(addr)),
+                None => return Err(last_err),
+            }
+        }
+        tokio::select! {
+            Some(result) = attempts.next() => match result {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    debug!("Connection attempt failed: {}", e);
+                    last_err = e;
+                }
+            },
+            () = tokio::time::sleep(delay) => {
+                if let Some(addr) = pending.next() {
+                    attempts.push(TcpStream::connect(addr));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    /// Cache whose lookups return `addrs` and are counted
+    fn counting_cache(addrs: Vec<SocketAddr>, ttl: Duration) -> (DnsCache, Arc<AtomicUsize>) {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let counter = lookups.clone();
+        let cache = DnsCache::with_lookup("service.internal:8080", ttl, move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let addrs = addrs.clone();
+            async move { Ok(addrs) }.boxed()
+        });
+        (cache, lookups)
+    }
+
+    #[test]
+    fn test_interleave_alternates_families() {
+        let addrs = [
+            addr("[::1]:80"),
+            addr("[::2]:80"),
+            addr("[::3]:80"),
+            addr("10.0.0.1:80"),
+        ];
+        assert_eq!(interleave(&addrs), [addrs[0], addrs[3], addrs[1], addrs[2]]);
+        assert!(interleave(&[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolution_is_cached() {
+        let (cache, lookups) =
+            counting_cache(vec![addr("127.0.0.1:8080")], Duration::from_secs(60));
+        for _ in 0..3 {
+            assert_eq!(cache.resolve().await.unwrap(), [addr("127.0.0.1:8080")]);
+        }
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        let (cache, lookups) = counting_cache(vec![addr("127.0.0.1:8080")], Duration::ZERO);
+        cache.resolve().await.unwrap();
+        cache.resolve().await.unwrap();
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stale_addresses_survive_failed_lookup() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let cache = DnsCache::with_lookup("service.internal:8080", Duration::ZERO, move |_| {
+            let first = counter.fetch_add(1, Ordering::SeqCst) == 0;
+            async move {
+                if first {
+                    Ok(vec![addr("127.0.0.1:8080")])
+                } else {
+                    Err(io::Error::other("resolver unavailable"))
+                }
+            }
+            .boxed()
+        });
+
+        assert_eq!(cache.resolve().await.unwrap(), [addr("127.0.0.1:8080")]);
+        assert_eq!(cache.resolve().await.unwrap(), [addr("127.0.0.1:8080")]);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_connect_round_robins_addresses() {
+        let first = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let second = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addrs = vec![first.local_addr().unwrap(), second.local_addr().unwrap()];
+        let (cache, lookups) = counting_cache(addrs.clone(), Duration::from_secs(60));
+
+        let mut peers = Vec::new();
+        for _ in 0..4 {
+            let stream = cache.connect().await.unwrap();
+            peers.push(stream.peer_addr().unwrap());
+        }
+
+        assert_eq!(peers, [addrs[0], addrs[1], addrs[0], addrs[1]]);
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_connect_falls_back_to_next_address() {
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let refused = closed.local_addr().unwrap();
+        drop(closed);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap();
+
+        let stream = happy_eyeballs(&[refused, open], Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), open);
+
+        assert!(happy_eyeballs(&[refused], Duration::from_secs(5))
+            .await
+            .is_err());
+    }
+}
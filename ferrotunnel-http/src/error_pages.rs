@@ -0,0 +1,201 @@
+//! Custom error pages for the HTTP ingress
+//!
+//! Errors the ingress generates itself (unknown tunnel, tunnel not ready,
+//! upstream timeout, ...) have short plain-text bodies by default. An
+//! [`ErrorPageSet`] replaces them with branded pages for visitors of public
+//! tunnels. Responses sent by the local service are never rewritten.
+
+use hyper::header::HeaderValue;
+use hyper::StatusCode;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Body and content type of a custom error page
+///
+/// The body is a template; these tokens are replaced when it is rendered:
+/// - `{tunnel_id}`: tunnel the request was routed to (empty if the Host
+///   header was invalid)
+/// - `{status}`: status code, e.g. `404`
+/// - `{reason}`: reason phrase, e.g. `Not Found`
+/// - `{message}`: the default plain-text body, e.g. `Tunnel not found`
+///
+/// With an HTML content type the values are HTML-escaped, since the tunnel
+/// ID comes from the request's Host header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorPage {
+    content_type: HeaderValue,
+    body: Arc<str>,
+}
+
+impl ErrorPage {
+    pub fn new(content_type: HeaderValue, body: impl Into<Arc<str>>) -> Self {
+        Self {
+            content_type,
+            body: body.into(),
+        }
+    }
+
+    /// A `text/html; charset=utf-8` page
+    pub fn html(body: impl Into<Arc<str>>) -> Self {
+        Self::new(HeaderValue::from_static("text/html; charset=utf-8"), body)
+    }
+
+    pub fn content_type(&self) -> &HeaderValue {
+        &self.content_type
+    }
+
+    /// Fill in the template tokens
+    pub fn render(&self, status: StatusCode, tunnel_id: &str, message: &str) -> String {
+        let html = self
+            .content_type
+            .to_str()
+            .is_ok_and(|ct| ct.to_ascii_lowercase().contains("html"));
+        let escape = |value: &str| {
+            if html {
+                escape_html(value)
+            } else {
+                value.to_string()
+            }
+        };
+        let values = [
+            ("{tunnel_id}", escape(tunnel_id)),
+            ("{status}", status.as_str().to_string()),
+            (
+                "{reason}",
+                status.canonical_reason().unwrap_or("").to_string(),
+            ),
+            ("{message}", escape(message)),
+        ];
+
+        // One pass, so tokens inside substituted values stay literal
+        let mut rendered = String::with_capacity(self.body.len());
+        let mut rest = &*self.body;
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            rest = &rest[start..];
+            match values.iter().find(|(token, _)| rest.starts_with(token)) {
+                Some((token, value)) => {
+                    rendered.push_str(value);
+                    rest = &rest[token.len()..];
+                }
+                None => {
+                    rendered.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+/// Error pages by status code, with fallbacks for whole status classes
+///
+/// Lookup order: the exact status, then the class page (4xx or 5xx). Errors
+/// without a page keep the default plain-text body.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorPageSet {
+    by_status: HashMap<StatusCode, ErrorPage>,
+    client_error: Option<ErrorPage>,
+    server_error: Option<ErrorPage>,
+}
+
+impl ErrorPageSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `page` for errors with `status`
+    #[must_use]
+    pub fn with_page(mut self, status: StatusCode, page: ErrorPage) -> Self {
+        self.by_status.insert(status, page);
+        self
+    }
+
+    /// Use `page` for 4xx errors without their own page
+    #[must_use]
+    pub fn with_client_error_page(mut self, page: ErrorPage) -> Self {
+        self.client_error = Some(page);
+        self
+    }
+
+    /// Use `page` for 5xx errors without their own page
+    #[must_use]
+    pub fn with_server_error_page(mut self, page: ErrorPage) -> Self {
+        self.server_error = Some(page);
+        self
+    }
+
+    /// Page for an error with `status`, if any
+    pub fn page_for(&self, status: StatusCode) -> Option<&ErrorPage> {
+        self.by_status.get(&status).or_else(|| {
+            if status.is_client_error() {
+                self.client_error.as_ref()
+            } else if status.is_server_error() {
+                self.server_error.as_ref()
+            } else {
+                None
+            }
+        })
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_lookup_order() {
+        let not_found = ErrorPage::html("missing");
+        let client = ErrorPage::html("client");
+        let pages = ErrorPageSet::new()
+            .with_page(StatusCode::NOT_FOUND, not_found.clone())
+            .with_client_error_page(client.clone());
+
+        assert_eq!(pages.page_for(StatusCode::NOT_FOUND), Some(&not_found));
+        assert_eq!(pages.page_for(StatusCode::FORBIDDEN), Some(&client));
+        assert_eq!(pages.page_for(StatusCode::BAD_GATEWAY), None);
+        assert_eq!(pages.page_for(StatusCode::OK), None);
+    }
+
+    #[test]
+    fn test_render_tokens() {
+        let page = ErrorPage::new(
+            HeaderValue::from_static("text/plain"),
+            "{status} {reason} for {tunnel_id}: {message}",
+        );
+        assert_eq!(
+            page.render(StatusCode::BAD_GATEWAY, "a&b", "Tunnel not ready"),
+            "502 Bad Gateway for a&b: Tunnel not ready"
+        );
+        // Substituted values are not expanded again; unknown tokens stay
+        assert_eq!(
+            page.render(StatusCode::NOT_FOUND, "{message}", "{x}"),
+            "404 Not Found for {message}: {x}"
+        );
+    }
+
+    #[test]
+    fn test_render_escapes_html() {
+        let page = ErrorPage::html("<h1>{tunnel_id}</h1>");
+        assert_eq!(
+            page.render(StatusCode::NOT_FOUND, "<script>", ""),
+            "<h1>&lt;script&gt;</h1>"
+        );
+    }
+}
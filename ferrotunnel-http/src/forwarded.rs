@@ -0,0 +1,159 @@
+//! Inbound forwarding headers at the HTTP ingress
+//!
+//! Any client can send `X-Forwarded-For` or `Forwarded`, so headers arriving
+//! straight from the internet cannot be believed: a spoofed chain would let
+//! a client pose as another address to the local service and to plugins
+//! that key on it. [`ForwardedHeaders`] decides, per connection, whether the
+//! inbound headers are dropped and rebuilt from the real peer or kept and
+//! extended because the peer is a known proxy.
+
+use crate::proxy::{X_FORWARDED_FOR, X_FORWARDED_HOST, X_FORWARDED_PROTO};
+use ferrotunnel_core::ip_filter::Cidr;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, FORWARDED, HOST};
+use std::net::IpAddr;
+
+const X_FORWARDED_PREFIX: &str = "x-forwarded-";
+
+/// Scheme reported in `X-Forwarded-Proto`; the ingress serves plain HTTP
+const INGRESS_PROTO: &str = "http";
+
+/// How the ingress treats `X-Forwarded-*` and `Forwarded` request headers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ForwardedHeaders {
+    /// Remove every inbound forwarding header, then set `X-Forwarded-For`,
+    /// `X-Forwarded-Host` and `X-Forwarded-Proto` from the connection itself
+    #[default]
+    Strip,
+    /// Keep the headers sent by peers inside these networks, appending the
+    /// peer to `X-Forwarded-For`; headers from any other peer are stripped
+    TrustProxies(Vec<Cidr>),
+}
+
+impl ForwardedHeaders {
+    /// Trust forwarding headers from peers inside `proxies`
+    pub fn trust_proxies(proxies: impl IntoIterator<Item = Cidr>) -> Self {
+        Self::TrustProxies(proxies.into_iter().collect())
+    }
+
+    /// Whether headers sent by `peer` are kept
+    pub fn is_trusted(&self, peer: IpAddr) -> bool {
+        match self {
+            Self::Strip => false,
+            Self::TrustProxies(proxies) => proxies.iter().any(|cidr| cidr.contains(peer)),
+        }
+    }
+
+    /// Sanitize the forwarding headers of a request received from `peer`
+    pub fn apply(&self, headers: &mut HeaderMap, peer: IpAddr) {
+        let peer = peer.to_canonical();
+        if !self.is_trusted(peer) {
+            strip(headers);
+        }
+
+        let chain = forwarded_for_chain(headers, peer);
+        if let Ok(value) = HeaderValue::from_str(&chain) {
+            headers.insert(X_FORWARDED_FOR, value);
+        }
+        if !headers.contains_key(X_FORWARDED_HOST) {
+            if let Some(host) = headers.get(HOST).cloned() {
+                headers.insert(X_FORWARDED_HOST, host);
+            }
+        }
+        if !headers.contains_key(X_FORWARDED_PROTO) {
+            headers.insert(X_FORWARDED_PROTO, HeaderValue::from_static(INGRESS_PROTO));
+        }
+    }
+}
+
+/// Remove `Forwarded` and every `X-Forwarded-*` header
+fn strip(headers: &mut HeaderMap) {
+    let names: Vec<HeaderName> = headers
+        .keys()
+        .filter(|name| *name == FORWARDED || name.as_str().starts_with(X_FORWARDED_PREFIX))
+        .cloned()
+        .collect();
+    for name in names {
+        headers.remove(name);
+    }
+}
+
+/// The existing `X-Forwarded-For` entries, joined, with `peer` appended
+fn forwarded_for_chain(headers: &HeaderMap, peer: IpAddr) -> String {
+    let mut chain: Vec<String> = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+        .collect();
+    chain.push(peer.to_string());
+    chain.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spoofed_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(HOST, HeaderValue::from_static("app.example.com"));
+        headers.insert(X_FORWARDED_FOR, HeaderValue::from_static("10.1.1.1"));
+        headers.insert(X_FORWARDED_HOST, HeaderValue::from_static("admin.internal"));
+        headers.insert(X_FORWARDED_PROTO, HeaderValue::from_static("https"));
+        headers.insert("x-forwarded-port", HeaderValue::from_static("443"));
+        headers.insert(FORWARDED, HeaderValue::from_static("for=10.1.1.1"));
+        headers
+    }
+
+    #[test]
+    fn test_strip_replaces_spoofed_headers() {
+        let mut headers = spoofed_headers();
+        ForwardedHeaders::default().apply(&mut headers, "203.0.113.9".parse().unwrap());
+
+        assert_eq!(headers.get(X_FORWARDED_FOR).unwrap(), "203.0.113.9");
+        assert_eq!(headers.get(X_FORWARDED_HOST).unwrap(), "app.example.com");
+        assert_eq!(headers.get(X_FORWARDED_PROTO).unwrap(), "http");
+        assert!(!headers.contains_key("x-forwarded-port"));
+        assert!(!headers.contains_key(FORWARDED));
+    }
+
+    #[test]
+    fn test_trusted_proxy_chain_is_appended() {
+        let policy = ForwardedHeaders::trust_proxies(["10.0.0.0/8".parse().unwrap()]);
+        let mut headers = spoofed_headers();
+        headers.append(X_FORWARDED_FOR, HeaderValue::from_static("10.2.2.2"));
+        policy.apply(&mut headers, "10.0.0.5".parse().unwrap());
+
+        assert_eq!(
+            headers.get(X_FORWARDED_FOR).unwrap(),
+            "10.1.1.1, 10.2.2.2, 10.0.0.5"
+        );
+        assert_eq!(headers.get_all(X_FORWARDED_FOR).iter().count(), 1);
+        assert_eq!(headers.get(X_FORWARDED_HOST).unwrap(), "admin.internal");
+        assert_eq!(headers.get(X_FORWARDED_PROTO).unwrap(), "https");
+        assert_eq!(headers.get("x-forwarded-port").unwrap(), "443");
+        assert_eq!(headers.get(FORWARDED).unwrap(), "for=10.1.1.1");
+    }
+
+    #[test]
+    fn test_untrusted_peer_is_stripped() {
+        let policy = ForwardedHeaders::trust_proxies(["10.0.0.0/8".parse().unwrap()]);
+        let mut headers = spoofed_headers();
+        policy.apply(&mut headers, "198.51.100.4".parse().unwrap());
+
+        assert_eq!(headers.get(X_FORWARDED_FOR).unwrap(), "198.51.100.4");
+        assert!(!headers.contains_key(FORWARDED));
+    }
+
+    #[test]
+    fn test_mapped_ipv4_peer() {
+        let policy = ForwardedHeaders::trust_proxies(["127.0.0.0/8".parse().unwrap()]);
+        let peer: IpAddr = "::ffff:127.0.0.1".parse().unwrap();
+        assert!(policy.is_trusted(peer));
+
+        let mut headers = HeaderMap::new();
+        policy.apply(&mut headers, peer);
+        assert_eq!(headers.get(X_FORWARDED_FOR).unwrap(), "127.0.0.1");
+    }
+}
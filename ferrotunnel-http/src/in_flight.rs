@@ -0,0 +1,247 @@
+//! Per-tunnel limit on concurrent requests for the HTTP ingress
+//!
+//! Connection limits do not protect a tunnel's local service on their own:
+//! HTTP/2 clients multiplex many requests over each connection. Every proxied
+//! request takes a permit from its tunnel's semaphore before a stream is
+//! opened and holds it until the upstream response is complete. Requests
+//! over the limit are answered with 503 instead of being forwarded.
+//!
+//! A tunnel's entry only lives while it has requests in flight: the last
+//! permit released removes it, so tunnels that disconnect leave nothing behind.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+type LimitMap = Mutex<HashMap<String, Limit>>;
+
+#[derive(Debug)]
+struct Limit {
+    max: usize,
+    semaphore: Arc<Semaphore>,
+    /// Permits still to be retired after the limit shrank below the number
+    /// in flight; released permits pay this off before returning to the pool
+    owed: usize,
+}
+
+impl Limit {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            semaphore: Arc::new(Semaphore::new(max)),
+            owed: 0,
+        }
+    }
+
+    /// Change the limit without resetting the permits already handed out
+    fn resize(&mut self, max: usize) {
+        if max > self.max {
+            let grow = max - self.max;
+            let repaid = grow.min(self.owed);
+            self.owed -= repaid;
+            self.semaphore.add_permits(grow - repaid);
+        } else {
+            let shrink = self.max - max;
+            self.owed += shrink - self.semaphore.forget_permits(shrink);
+        }
+        self.max = max;
+    }
+
+    fn in_flight(&self) -> usize {
+        (self.max + self.owed).saturating_sub(self.semaphore.available_permits())
+    }
+}
+
+/// In-flight request limits keyed by tunnel ID
+#[derive(Debug)]
+pub struct TunnelRequestLimits {
+    default_limit: Option<usize>,
+    limits: Arc<LimitMap>,
+}
+
+/// A request's slot under its tunnel's limit, released on drop
+#[derive(Debug)]
+pub struct InFlightPermit(#[allow(dead_code)] Option<HeldPermit>);
+
+#[derive(Debug)]
+struct HeldPermit {
+    permit: Option<OwnedSemaphorePermit>,
+    tunnel_id: String,
+    limits: Arc<LimitMap>,
+}
+
+impl Drop for HeldPermit {
+    fn drop(&mut self) {
+        let Some(permit) = self.permit.take() else {
+            return;
+        };
+        let mut limits = lock(&self.limits);
+        let Some(limit) = limits.get_mut(&self.tunnel_id) else {
+            return;
+        };
+        if !Arc::ptr_eq(&limit.semaphore, permit.semaphore()) {
+            return;
+        }
+        if limit.owed > 0 {
+            limit.owed -= 1;
+            permit.forget();
+        } else {
+            drop(permit);
+        }
+        if limit.in_flight() == 0 {
+            limits.remove(&self.tunnel_id);
+        }
+    }
+}
+
+fn lock(limits: &LimitMap) -> MutexGuard<'_, HashMap<String, Limit>> {
+    match limits.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+impl TunnelRequestLimits {
+    /// Create limits allowing `default_limit` concurrent requests per tunnel.
+    /// `None` or 0 leaves tunnels without their own limit unbounded.
+    pub fn new(default_limit: Option<usize>) -> Self {
+        Self {
+            default_limit,
+            limits: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Take a permit for a request to `tunnel_id`
+    ///
+    /// `tunnel_limit` is the limit the tunnel was registered with and
+    /// overrides the default; 0 makes that tunnel unbounded. Returns `None`
+    /// while the tunnel already has its limit of requests in flight.
+    pub fn try_acquire(
+        &self,
+        tunnel_id: &str,
+        tunnel_limit: Option<usize>,
+    ) -> Option<InFlightPermit> {
+        let max = match tunnel_limit.or(self.default_limit) {
+            Some(max) if max > 0 => max,
+            _ => return Some(InFlightPermit(None)),
+        };
+        let permit = {
+            let mut limits = lock(&self.limits);
+            let limit = limits
+                .entry(tunnel_id.to_string())
+                .or_insert_with(|| Limit::new(max));
+            // The tunnel re-registered with a different limit; requests
+            // already in flight keep counting against the new one
+            if limit.max != max {
+                limit.resize(max);
+            }
+            limit.semaphore.clone().try_acquire_owned().ok()
+        };
+        permit.map(|permit| {
+            InFlightPermit(Some(HeldPermit {
+                permit: Some(permit),
+                tunnel_id: tunnel_id.to_string(),
+                limits: self.limits.clone(),
+            }))
+        })
+    }
+
+    /// Requests to `tunnel_id` currently holding a permit
+    pub fn in_flight(&self, tunnel_id: &str) -> usize {
+        lock(&self.limits)
+            .get(tunnel_id)
+            .map_or(0, Limit::in_flight)
+    }
+
+    /// Tunnels with requests currently in flight
+    pub fn tracked_tunnels(&self) -> usize {
+        lock(&self.limits).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_is_per_tunnel() {
+        let limits = TunnelRequestLimits::new(Some(2));
+        let first = limits.try_acquire("a", None).unwrap();
+        let _second = limits.try_acquire("a", None).unwrap();
+        assert!(limits.try_acquire("a", None).is_none());
+        assert_eq!(limits.in_flight("a"), 2);
+
+        // Other tunnels have their own permits
+        assert!(limits.try_acquire("b", None).is_some());
+
+        drop(first);
+        assert_eq!(limits.in_flight("a"), 1);
+        assert!(limits.try_acquire("a", None).is_some());
+    }
+
+    #[test]
+    fn test_tunnel_limit_overrides_default() {
+        let limits = TunnelRequestLimits::new(Some(1));
+        let _held = [
+            limits.try_acquire("a", Some(3)).unwrap(),
+            limits.try_acquire("a", Some(3)).unwrap(),
+            limits.try_acquire("a", Some(3)).unwrap(),
+        ];
+        assert!(limits.try_acquire("a", Some(3)).is_none());
+
+        // 0 lifts the limit for that tunnel only
+        let _unbounded: Vec<_> = (0..10)
+            .map(|_| limits.try_acquire("b", Some(0)).unwrap())
+            .collect();
+        let _c = limits.try_acquire("c", None).unwrap();
+        assert!(limits.try_acquire("c", None).is_none());
+    }
+
+    #[test]
+    fn test_entry_removed_when_idle() {
+        let limits = TunnelRequestLimits::new(Some(2));
+        let first = limits.try_acquire("a", None).unwrap();
+        let second = limits.try_acquire("a", None).unwrap();
+        let other = limits.try_acquire("b", None).unwrap();
+        assert_eq!(limits.tracked_tunnels(), 2);
+
+        drop(first);
+        assert_eq!(limits.tracked_tunnels(), 2);
+        drop(second);
+        drop(other);
+        assert_eq!(limits.tracked_tunnels(), 0);
+        assert_eq!(limits.in_flight("a"), 0);
+    }
+
+    #[test]
+    fn test_resize_keeps_in_flight_count() {
+        let limits = TunnelRequestLimits::new(None);
+        let held: Vec<_> = (0..3)
+            .map(|_| limits.try_acquire("a", Some(3)).unwrap())
+            .collect();
+
+        // Shrinking below the requests in flight rejects until enough finish
+        assert!(limits.try_acquire("a", Some(1)).is_none());
+        assert_eq!(limits.in_flight("a"), 3);
+        let mut held = held.into_iter();
+        drop(held.next());
+        assert!(limits.try_acquire("a", Some(1)).is_none());
+        drop(held.next());
+        assert_eq!(limits.in_flight("a"), 1);
+        assert!(limits.try_acquire("a", Some(1)).is_none());
+
+        // Growing again counts the request still in flight
+        let _more = limits.try_acquire("a", Some(2)).unwrap();
+        assert_eq!(limits.in_flight("a"), 2);
+        assert!(limits.try_acquire("a", Some(2)).is_none());
+    }
+
+    #[test]
+    fn test_no_default_limit() {
+        let limits = TunnelRequestLimits::new(None);
+        let _held: Vec<_> = (0..100)
+            .map(|_| limits.try_acquire("a", None).unwrap())
+            .collect();
+        assert_eq!(limits.in_flight("a"), 0);
+    }
+}
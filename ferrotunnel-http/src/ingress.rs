@@ -1,53 +1,276 @@
-use ferrotunnel_common::Result;
+use crate::access_log::{AccessLogEntry, AccessLogFormat, CountingBody};
+use crate::cache::{CacheConfig, Lookup, ResponseCache};
+use crate::circuit::TunnelCircuitBreakers;
+use crate::compression::{CompressionConfig, Encoding};
+use crate::error_pages::ErrorPageSet;
+use crate::forwarded::ForwardedHeaders;
+use crate::in_flight::{InFlightPermit, TunnelRequestLimits};
+use crate::inspect::IngressInspector;
+use crate::priority::PriorityPolicy;
+use crate::proxy::{is_body_limit_error, REMOTE_ADDR_HEADER};
+use crate::proxy_protocol;
+use crate::trace_context::start_request_span;
+use crate::websocket::{copy_websocket, WebSocketLimits};
+use ferrotunnel_common::{Result, TunnelError};
+use ferrotunnel_core::stream::{Multiplexer, StreamAbortHandle, VirtualStream};
+use ferrotunnel_core::transport::ListenerConfig;
 use ferrotunnel_core::tunnel::session::SessionStoreBackend;
-use ferrotunnel_plugin::{PluginAction, PluginRegistry, RequestContext, ResponseContext};
-use ferrotunnel_protocol::frame::Protocol;
-use http_body_util::{BodyExt, Empty};
-use hyper::body::Bytes;
+use ferrotunnel_plugin::{
+    ByteCounter, PluginAction, PluginRegistry, RequestContext, ResponseContext,
+};
+use ferrotunnel_protocol::frame::{CloseReason, Protocol, StreamPriority};
+use http_body_util::{BodyExt, Empty, Limited, StreamBody};
+use hyper::body::{Body, Bytes, Frame};
 use hyper::service::service_fn;
 use hyper::{Request, Response, StatusCode};
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use hyper_util::server::conn::auto::Builder as AutoBuilder;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::future::Future;
 use std::net::SocketAddr;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll};
 use std::time::{Duration, SystemTime};
 use tokio::net::TcpListener;
 use tokio::sync::Semaphore;
-use tracing::{error, info, warn};
+use tracing::{error, info, warn, Instrument};
+use uuid::Uuid;
 
 /// Configuration for HTTP ingress limits and timeouts
 #[derive(Debug, Clone)]
 pub struct IngressConfig {
     /// Maximum concurrent connections (default: 10000)
+    ///
+    /// Connections over the limit are answered with 503 and closed.
     pub max_connections: usize,
+    /// `Retry-After` sent with the 503 for connections over
+    /// `max_connections`, rounded down to whole seconds; zero omits the
+    /// header (default: 1s)
+    pub connection_limit_retry_after: Duration,
+    /// Body of the 503 for connections over `max_connections`
+    /// (default: "Too many connections")
+    pub connection_limit_body: String,
     /// Maximum response body size in bytes (default: 100MB)
     pub max_response_size: usize,
+    /// Maximum request body size in bytes (default: 100MB)
+    ///
+    /// Requests declaring a larger `Content-Length` are rejected with 413 before
+    /// a tunnel stream is opened; bodies of unknown length are counted while
+    /// streaming and cut off with 413 once they exceed the limit.
+    pub max_request_size: usize,
+    /// Maximum number of request headers (default: 100)
+    ///
+    /// Requests with more headers are rejected with 431 before the Host header
+    /// is parsed or a tunnel stream is opened. Also caps the HTTP/1 parser.
+    pub max_header_count: usize,
+    /// Maximum total size of request header names and values in bytes,
+    /// including `Host` (default: 64KB)
+    ///
+    /// Requests over the limit are rejected with 431 before any tunnel work.
+    pub max_header_bytes: usize,
     /// Timeout for upstream handshake (default: 10s)
     pub handshake_timeout: Duration,
     /// Timeout for upstream response (default: 60s)
+    ///
+    /// Plugins ([`PluginAction::SetTimeout`]) and `timeout_header` can
+    /// override it per request. Bounds the wait for the response head; the
+    /// body of a `text/event-stream` response is bounded by
+    /// `event_stream_idle_timeout` instead.
     pub response_timeout: Duration,
+    /// Trusted request header carrying a per-request response timeout in
+    /// seconds, e.g. `X-Tunnel-Timeout: 300` (default: none)
+    ///
+    /// Any client can send this header, so only set it when a proxy in front
+    /// of the ingress strips or sets it. It is removed before forwarding.
+    pub timeout_header: Option<String>,
+    /// Upper bound for per-request timeout overrides (default: 10 minutes)
+    pub max_response_timeout: Duration,
+    /// How long a `text/event-stream` response may go without sending data
+    /// before the ingress ends it (default: 5 minutes)
+    ///
+    /// Event streams run indefinitely, so `response_timeout` only covers
+    /// their head. Servers should send keep-alive comments more often.
+    pub event_stream_idle_timeout: Duration,
+    /// Consecutive tunnel failures (stream open, handshake or transport errors)
+    /// before a tunnel's circuit opens; 0 disables the breaker (default: 0)
+    pub circuit_failure_threshold: u32,
+    /// Consecutive handshake or response timeouts before a tunnel's circuit
+    /// opens, counted apart from failures; 0 ignores timeouts (default: 0)
+    pub circuit_timeout_threshold: u32,
+    /// How long an open circuit rejects requests with 503 (default: 30s)
+    pub circuit_cooldown: Duration,
+    /// Maximum concurrent in-flight requests per tunnel (default: none)
+    ///
+    /// Requests over the limit are answered with 503 without reaching the
+    /// tunnel. A tunnel registered with its own limit
+    /// (`AuthGrant::max_in_flight_requests`) uses that instead.
+    pub max_in_flight_per_tunnel: Option<usize>,
+    /// Per-request access log format, emitted under the `ferrotunnel::access`
+    /// tracing target once the response completes (default: off)
+    pub access_log: AccessLogFormat,
+    /// Base domain for subdomain routing (default: none)
+    ///
+    /// When set, a request for `myapp.tunnel.example.com` with base domain
+    /// `tunnel.example.com` is routed to tunnel `myapp`; multi-level prefixes
+    /// such as `a.b.tunnel.example.com` route to `a.b`. Requests for the bare
+    /// base domain get 404. When unset, the whole host is the tunnel ID.
+    pub base_domain: Option<String>,
+    /// With a base domain set, reject hosts outside it with 404 instead of
+    /// looking them up as whole-host tunnel IDs (default: false)
+    pub strict_base_domain: bool,
+    /// Compress responses with gzip or brotli when the client accepts it
+    /// (default: off)
+    pub compression: Option<CompressionConfig>,
+    /// Pick each request's stream priority from the request, so interactive
+    /// traffic is sent ahead of bulk uploads on a busy tunnel (default: off,
+    /// every stream gets normal priority)
+    pub priority: Option<PriorityPolicy>,
+    /// Custom pages for errors the ingress answers itself, such as an
+    /// unknown tunnel, an upstream timeout or a plugin rejection (default:
+    /// none, plain-text bodies)
+    ///
+    /// Error responses from the local service are passed through unchanged.
+    pub error_pages: Option<ErrorPageSet>,
+    /// Idle and message size limits for upgraded WebSocket connections
+    /// (default: none)
+    pub websocket: WebSocketLimits,
+    /// Expect a PROXY protocol v2 header on every connection and use the
+    /// client address it carries (default: false)
+    ///
+    /// Enable only behind a load balancer that sends the header: connections
+    /// without one are closed.
+    pub proxy_protocol: bool,
+    /// Cache successful `GET` responses in memory, honouring `Cache-Control`
+    /// and `ETag` (default: off)
+    ///
+    /// Cached responses skip the tunnel and the response plugin hooks;
+    /// request hooks still run first, so plugins can reject or rewrite the
+    /// request before it is looked up.
+    pub cache: Option<CacheConfig>,
+    /// Accept backlog, `SO_REUSEADDR` and `SO_REUSEPORT` for the listener
+    /// bound by [`HttpIngress::start`]; with several accept tasks it binds
+    /// that many listeners on the address and accepts on each
+    pub listener: ListenerConfig,
+    /// Whether inbound `X-Forwarded-*` and `Forwarded` headers are stripped
+    /// or trusted from known proxies (default: strip)
+    ///
+    /// Either way the request reaches the tunnel with `X-Forwarded-For`
+    /// ending in the connection's peer address.
+    pub forwarded_headers: ForwardedHeaders,
 }
 
 impl Default for IngressConfig {
     fn default() -> Self {
         Self {
             max_connections: 10000,
+            connection_limit_retry_after: Duration::from_secs(1),
+            connection_limit_body: "Too many connections".to_string(),
             max_response_size: 100 * 1024 * 1024, // 100MB
+            max_request_size: 100 * 1024 * 1024,  // 100MB
+            max_header_count: 100,
+            max_header_bytes: 64 * 1024, // 64KB
             handshake_timeout: Duration::from_secs(10),
             response_timeout: Duration::from_secs(60),
+            timeout_header: None,
+            max_response_timeout: Duration::from_secs(600),
+            event_stream_idle_timeout: Duration::from_secs(300),
+            circuit_failure_threshold: 0,
+            circuit_timeout_threshold: 0,
+            circuit_cooldown: Duration::from_secs(30),
+            max_in_flight_per_tunnel: None,
+            access_log: AccessLogFormat::Off,
+            base_domain: None,
+            strict_base_domain: false,
+            compression: None,
+            priority: None,
+            error_pages: None,
+            websocket: WebSocketLimits::default(),
+            proxy_protocol: false,
+            cache: None,
+            listener: ListenerConfig::default(),
+            forwarded_headers: ForwardedHeaders::default(),
         }
     }
 }
 
+impl IngressConfig {
+    /// Enable response compression
+    #[must_use]
+    pub fn compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Assign stream priorities with `policy`
+    #[must_use]
+    pub fn priority(mut self, policy: PriorityPolicy) -> Self {
+        self.priority = Some(policy);
+        self
+    }
+
+    /// Answer ingress errors with `pages`
+    #[must_use]
+    pub fn error_pages(mut self, pages: ErrorPageSet) -> Self {
+        self.error_pages = Some(pages);
+        self
+    }
+
+    /// Cache upstream responses
+    #[must_use]
+    pub fn cache(mut self, cache: CacheConfig) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Bind the listener with `listener`'s socket options
+    #[must_use]
+    pub fn listener(mut self, listener: ListenerConfig) -> Self {
+        self.listener = listener;
+        self
+    }
+
+    /// Handle inbound forwarding headers according to `policy`
+    #[must_use]
+    pub fn forwarded_headers(mut self, policy: ForwardedHeaders) -> Self {
+        self.forwarded_headers = policy;
+        self
+    }
+}
+
+#[derive(Clone)]
 pub struct HttpIngress {
     addr: SocketAddr,
     sessions: SessionStoreBackend,
     registry: Arc<PluginRegistry>,
     config: IngressConfig,
     connection_semaphore: Arc<Semaphore>,
+    rejection_semaphore: Arc<Semaphore>,
+    circuit_breakers: Arc<TunnelCircuitBreakers>,
+    request_limits: Arc<TunnelRequestLimits>,
+    cache: Option<Arc<ResponseCache>>,
+    inspector: Option<Arc<dyn IngressInspector>>,
 }
 
-type BoxBody = http_body_util::combinators::BoxBody<Bytes, hyper::Error>;
+/// Error of the bodies the ingress serves and forwards: upstream body errors
+/// as well as failures of its own, such as response compression
+pub(crate) type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+type BoxBody = http_body_util::combinators::BoxBody<Bytes, BoxError>;
+
+/// Request body forwarded through the tunnel, capped at `max_request_size`
+///
+/// Unsync so plugin chunk hooks can run while it is polled.
+type ForwardBody = http_body_util::combinators::UnsyncBoxBody<Bytes, BoxError>;
+
+/// A plugin stopped a request while its body was streaming
+#[derive(Debug, thiserror::Error)]
+#[error("Request body rejected by plugin: {status} {reason}")]
+struct BodyRejected {
+    status: StatusCode,
+    reason: String,
+}
 
 impl HttpIngress {
     pub fn new(
@@ -62,57 +285,123 @@ impl HttpIngress {
         addr: SocketAddr,
         sessions: SessionStoreBackend,
         registry: Arc<PluginRegistry>,
-        config: IngressConfig,
+        mut config: IngressConfig,
     ) -> Self {
+        config.base_domain = config
+            .base_domain
+            .map(|domain| domain.trim_matches('.').to_lowercase())
+            .filter(|domain| !domain.is_empty());
         let connection_semaphore = Arc::new(Semaphore::new(config.max_connections));
+        let circuit_breakers = Arc::new(
+            TunnelCircuitBreakers::new(config.circuit_failure_threshold, config.circuit_cooldown)
+                .with_timeout_threshold(config.circuit_timeout_threshold),
+        );
+        let request_limits = Arc::new(TunnelRequestLimits::new(config.max_in_flight_per_tunnel));
+        let cache = config
+            .cache
+            .clone()
+            .map(|cache| Arc::new(ResponseCache::new(cache)));
         Self {
             addr,
             sessions,
             registry,
             config,
             connection_semaphore,
+            rejection_semaphore: Arc::new(Semaphore::new(MAX_PENDING_REJECTIONS)),
+            circuit_breakers,
+            request_limits,
+            cache,
+            inspector: None,
         }
     }
 
+    /// Call `inspector` for every request forwarded to a tunnel and its
+    /// response
+    #[must_use]
+    pub fn with_inspector(mut self, inspector: Arc<dyn IngressInspector>) -> Self {
+        self.inspector = Some(inspector);
+        self
+    }
+
     pub async fn start(self) -> Result<()> {
-        let listener = TcpListener::bind(self.addr).await?;
+        let listeners = self.config.listener.bind_all(self.addr)?;
+        self.serve_all(listeners).await
+    }
+
+    /// Serve requests from several already bound listeners, e.g. ones
+    /// sharing a port through `SO_REUSEPORT`, with an accept loop for each.
+    /// The connection limit applies across all of them.
+    pub async fn serve_all(self, listeners: Vec<TcpListener>) -> Result<()> {
+        let mut listeners = listeners.into_iter();
+        let Some(first) = listeners.next() else {
+            return Err(TunnelError::Config("no listeners to serve".into()));
+        };
+        for listener in listeners {
+            let ingress = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = ingress.serve(listener).await {
+                    error!("HTTP ingress accept loop failed: {}", e);
+                }
+            });
+        }
+        self.serve(first).await
+    }
+
+    /// Serve requests from an already bound `listener` instead of binding the
+    /// configured address
+    pub async fn serve(self, listener: TcpListener) -> Result<()> {
+        let ingress = Arc::new(self);
         info!(
             "HTTP Ingress listening on {} (HTTP/1.1 + HTTP/2)",
-            self.addr
+            listener.local_addr()?
         );
 
         loop {
             let (stream, peer_addr) = listener.accept().await?;
 
             // Acquire connection permit (limit concurrent connections)
-            let Ok(permit) = self.connection_semaphore.clone().try_acquire_owned() else {
+            let Ok(permit) = ingress.connection_semaphore.clone().try_acquire_owned() else {
                 warn!(
                     "Max connections reached, rejecting connection from {}",
                     peer_addr
                 );
-                drop(stream);
+                // Under a flood, drop the rest instead of holding a task and
+                // socket for each
+                if let Ok(permit) = ingress.rejection_semaphore.clone().try_acquire_owned() {
+                    let config = ingress.config.clone();
+                    tokio::spawn(async move {
+                        reject_over_limit(stream, peer_addr, config).await;
+                        drop(permit);
+                    });
+                }
                 continue;
             };
 
-            let io = TokioIo::new(stream);
-            let registry = self.registry.clone();
-            let sessions = self.sessions.clone();
-            let config = self.config.clone();
+            let ingress = ingress.clone();
 
             tokio::spawn(async move {
                 let _permit = permit; // Hold permit until connection closes
 
-                let service = service_fn(move |req| {
-                    handle_request(
-                        req,
-                        sessions.clone(),
-                        registry.clone(),
-                        peer_addr,
-                        config.clone(),
-                    )
-                });
-
-                let builder = AutoBuilder::new(TokioExecutor::new());
+                let mut stream = stream;
+                let peer_addr = if ingress.config.proxy_protocol {
+                    match proxy_protocol::read_source_addr(&mut stream, peer_addr).await {
+                        Ok(source) => source,
+                        Err(e) => {
+                            warn!("Rejecting connection from {}: {}", peer_addr, e);
+                            return;
+                        }
+                    }
+                } else {
+                    peer_addr
+                };
+                let io = TokioIo::new(stream);
+
+                let max_headers = ingress.config.max_header_count;
+                let service =
+                    service_fn(move |req| handle_request(req, ingress.clone(), peer_addr));
+
+                let mut builder = AutoBuilder::new(TokioExecutor::new());
+                builder.http1().max_headers(max_headers);
                 if let Err(err) = builder.serve_connection_with_upgrades(io, service).await {
                     if !is_connection_close_error(&err) {
                         error!("Error serving connection: {:?}", err);
@@ -123,19 +412,138 @@ impl HttpIngress {
     }
 }
 
-#[allow(clippy::too_many_lines)]
 async fn handle_request(
     mut req: Request<hyper::body::Incoming>,
-    sessions: SessionStoreBackend,
-    registry: Arc<PluginRegistry>,
+    ingress: Arc<HttpIngress>,
+    peer_addr: SocketAddr,
+) -> std::result::Result<Response<BoxBody>, hyper::Error> {
+    let span = start_request_span(&mut req);
+    async move {
+        let grpc = is_grpc(req.headers());
+        let config = &ingress.config;
+        if config.access_log == AccessLogFormat::Off && config.error_pages.is_none() {
+            let res = proxy_request(req, &ingress, peer_addr, None).await?;
+            return Ok(grpc_error_if_needed(grpc, res).await);
+        }
+
+        let tunnel_id = parse_and_normalize_host(req.headers().get("host"))
+            .ok()
+            .and_then(|host| route_host(host, config));
+        let entry = (config.access_log != AccessLogFormat::Off)
+            .then(|| AccessLogEntry::start(config.access_log, &req, peer_addr, tunnel_id.clone()));
+        let request_bytes = entry.as_ref().map(AccessLogEntry::request_bytes);
+        let error_pages = config.error_pages.clone();
+        let res = proxy_request(req, &ingress, peer_addr, request_bytes).await?;
+        let mut res = grpc_error_if_needed(grpc, res).await;
+        if let Some(pages) = &error_pages {
+            res = error_page_if_needed(pages, tunnel_id.as_deref().unwrap_or_default(), res).await;
+        }
+        Ok(match entry {
+            Some(entry) => entry.attach(res),
+            None => res,
+        })
+    }
+    .instrument(span)
+    .await
+}
+
+/// Upper bound on the time spent answering a connection over the limit
+const OVER_LIMIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connections over the limit answered with 503 at once; beyond this they are
+/// dropped without an answer
+const MAX_PENDING_REJECTIONS: usize = 64;
+
+/// Answer every request on a connection over `max_connections` with 503 and
+/// close it, so clients back off instead of waiting on a dropped socket
+async fn reject_over_limit(
+    mut stream: tokio::net::TcpStream,
     peer_addr: SocketAddr,
     config: IngressConfig,
+) {
+    let reject = async move {
+        if config.proxy_protocol {
+            proxy_protocol::read_source_addr(&mut stream, peer_addr)
+                .await
+                .ok()?;
+        }
+        let service = service_fn(move |_req: Request<hyper::body::Incoming>| {
+            let res = over_limit_response(&config);
+            async move { Ok::<_, hyper::Error>(res) }
+        });
+        let mut builder = AutoBuilder::new(TokioExecutor::new());
+        builder.http1().keep_alive(false);
+        builder
+            .serve_connection(TokioIo::new(stream), service)
+            .await
+            .ok()
+    };
+    let _ = tokio::time::timeout(OVER_LIMIT_TIMEOUT, reject).await;
+}
+
+/// Status and body for a stream the tunnel could not open, so clients can
+/// tell a busy or throttled tunnel from a broken one
+fn open_stream_failure(err: &TunnelError) -> (StatusCode, &'static str) {
+    match err {
+        TunnelError::ResourceExhausted(_) => {
+            (StatusCode::SERVICE_UNAVAILABLE, "Tunnel at capacity")
+        }
+        TunnelError::RateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, "Tunnel rate limited"),
+        TunnelError::Timeout(_) => (StatusCode::GATEWAY_TIMEOUT, "Timed out opening stream"),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, "Failed to open stream"),
+    }
+}
+
+fn over_limit_response(config: &IngressConfig) -> Response<BoxBody> {
+    let mut res = full_response(
+        StatusCode::SERVICE_UNAVAILABLE,
+        &config.connection_limit_body,
+    );
+    let retry_after = config.connection_limit_retry_after.as_secs();
+    if retry_after > 0 {
+        res.headers_mut()
+            .insert(hyper::header::RETRY_AFTER, retry_after.into());
+    }
+    res
+}
+
+#[allow(clippy::too_many_lines)]
+async fn proxy_request(
+    mut req: Request<hyper::body::Incoming>,
+    ingress: &HttpIngress,
+    peer_addr: SocketAddr,
+    request_bytes: Option<ByteCounter>,
 ) -> std::result::Result<Response<BoxBody>, hyper::Error> {
+    let sessions = ingress.sessions.clone();
+    let registry = ingress.registry.clone();
+    let config = ingress.config.clone();
+    let breakers = ingress.circuit_breakers.clone();
+    let request_limits = ingress.request_limits.clone();
+    let cache = ingress.cache.clone();
+
     // 0. Global Health Check
     if req.uri().path() == "/health" {
         return Ok(full_response(StatusCode::OK, "OK"));
     }
 
+    // Reject oversized header sets before normalizing the Host header
+    if headers_exceed_limits(req.headers(), &config) {
+        return Ok(full_response(
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            "Request header fields too large",
+        ));
+    }
+
+    // Reject oversized uploads before doing any routing work
+    if declared_content_length(req.headers())
+        .is_some_and(|len| len > config.max_request_size as u64)
+    {
+        return Ok(full_response(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "Request body too large",
+        ));
+    }
+
     // 1. Parse and normalize Host header
     let tunnel_id = match parse_and_normalize_host(req.headers().get("host")) {
         Ok(host) => host,
@@ -143,21 +551,43 @@ async fn handle_request(
             return Ok(full_response(StatusCode::BAD_REQUEST, msg));
         }
     };
+    let Some(tunnel_id) = route_host(tunnel_id, &config) else {
+        return Ok(full_response(StatusCode::NOT_FOUND, "Tunnel not found"));
+    };
+
+    // Before the plugins, so none of them sees a spoofed client chain
+    config
+        .forwarded_headers
+        .apply(req.headers_mut(), peer_addr.ip());
 
     let ctx = RequestContext {
         tunnel_id: tunnel_id.clone(),
-        session_id: uuid::Uuid::new_v4().to_string(),
+        session_id: Uuid::new_v4().to_string(),
+        request_id: Uuid::new_v4().to_string(),
         remote_addr: peer_addr,
         timestamp: SystemTime::now(),
+        request_bytes: request_bytes.unwrap_or_default(),
     };
+    let response_bytes = ByteCounter::default();
 
     let is_ws = is_websocket_upgrade(req.headers());
+    let encoding = match &config.compression {
+        Some(compression) if !is_ws => compression.negotiate(req.method(), req.headers()),
+        _ => None,
+    };
 
     let client_upgrade = if is_ws {
         Some(hyper::upgrade::on(&mut req))
     } else {
         None
     };
+    // Extensions are negotiated end to end, and the bridge cannot see inside
+    // compressed frames, so the offer must reach the local server as sent
+    let ws_extensions = if is_ws {
+        websocket_extensions(req.headers())
+    } else {
+        Vec::new()
+    };
 
     // 1. Run Request Hooks (On Headers Only - No Body Buffering)
     let (mut parts, body) = req.into_parts();
@@ -165,10 +595,20 @@ async fn handle_request(
     // Create a temporary request with empty body for plugins to inspect headers
     let mut plugin_req = Request::from_parts(parts.clone(), ());
 
+    let mut timeout_override = None;
     match registry.execute_request_hooks(&mut plugin_req, &ctx).await {
-        Ok(PluginAction::Continue | PluginAction::Modify { .. }) => {
-            // If modified, update parts (headers/uri/method)
-            // Note: Body modification is not supported in streaming mode yet
+        // Delays have already been waited out by the registry
+        Ok(
+            action @ (PluginAction::Continue
+            | PluginAction::Modify { .. }
+            | PluginAction::Delay { .. }
+            | PluginAction::SetTimeout(_)),
+        ) => {
+            if let PluginAction::SetTimeout(timeout) = action {
+                timeout_override = Some(timeout);
+            }
+            // If modified, update parts (headers/uri/method). The body is
+            // left to the chunk hooks below, as it streams.
             let (new_parts, ()) = plugin_req.into_parts();
             parts = new_parts;
         }
@@ -183,17 +623,7 @@ async fn handle_request(
             headers,
             body,
         }) => {
-            let mut res =
-                Response::builder().status(StatusCode::from_u16(status).unwrap_or(StatusCode::OK));
-            for (k, v) in headers {
-                res = res.header(k, v);
-            }
-            return Ok(res.body(full_body(Bytes::from(body))).unwrap_or_else(|_| {
-                full_response(
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Failed to build plugin response",
-                )
-            }));
+            return Ok(plugin_response(status, headers, body));
         }
 
         Err(e) => {
@@ -205,6 +635,26 @@ async fn handle_request(
         }
     }
 
+    if is_ws {
+        restore_websocket_extensions(&mut parts.headers, ws_extensions);
+    }
+
+    let response_timeout = effective_response_timeout(&config, &parts.headers, timeout_override);
+    if let Some(name) = &config.timeout_header {
+        parts.headers.remove(name.as_str());
+    }
+    let priority = match &config.priority {
+        Some(policy) => {
+            let priority = policy.resolve(&parts.headers, &body);
+            if let Some(name) = &policy.header {
+                parts.headers.remove(name.as_str());
+            }
+            priority
+        }
+        None => StreamPriority::default(),
+    };
+    let inspected_head = ingress.inspector.as_ref().map(|_| parts.clone());
+
     // 2. Identify Target Session (Routing Fix)
     // FIX #27: Use get_by_tunnel_id instead of find_multiplexer
     // We try to find by exact match of host (tunnel_id).
@@ -212,10 +662,25 @@ async fn handle_request(
     // but for security we should be strict.
     // However, for verify plan "Routing Fix", strict lookup is key.
 
-    // We need to clone multiplexer from the Ref
-    let multiplexer = if let Some(session) = sessions.get_by_tunnel_id(&tunnel_id) {
+    // We need to clone multiplexer from the Ref. Pooled tunnels pick one of
+    // their sessions per request.
+    let tunnel_limit;
+    let backend = if let Some(session) = sessions.select_by_tunnel_id(&tunnel_id, &[]) {
+        // The tunnel's own path rules apply to the path it would receive
+        if !session.path_rules.permits(parts.uri.path()) {
+            return Ok(full_response(StatusCode::FORBIDDEN, "Path not allowed"));
+        }
+        // Only clients that advertised WebSocket support get upgrades
+        if is_ws && !session.accepts(Protocol::WebSocket) {
+            warn!("Tunnel '{tunnel_id}' does not accept WebSocket connections");
+            return Ok(full_response(
+                StatusCode::NOT_IMPLEMENTED,
+                "Tunnel does not accept WebSocket connections",
+            ));
+        }
+        tunnel_limit = session.max_in_flight_requests;
         if let Some(m) = &session.multiplexer {
-            m.clone()
+            (session.id, m.clone())
         } else {
             return Ok(full_response(StatusCode::BAD_GATEWAY, "Tunnel not ready"));
         }
@@ -227,6 +692,33 @@ async fn handle_request(
         return Ok(full_response(StatusCode::NOT_FOUND, "Tunnel not found"));
     };
 
+    // Answer from the cache while the stored response is fresh. Stale entries
+    // are revalidated upstream with their ETag, unless the client sent its own
+    // conditional request.
+    let cache = cache.filter(|_| !is_ws);
+    let mut cache_key = None;
+    let mut cache_request_headers = None;
+    let mut revalidating = false;
+    if let Some(cache) = &cache {
+        if !parts.method.is_safe() {
+            cache.invalidate(&tunnel_id, &parts.uri, &parts.headers);
+        }
+        cache_key = ResponseCache::key(&tunnel_id, &parts.method, &parts.uri, &parts.headers);
+        if let Some(key) = &cache_key {
+            cache_request_headers = Some(parts.headers.clone());
+            match cache.lookup(key, &parts.headers) {
+                Lookup::Hit(res) => return Ok(compress(res, &config, encoding)),
+                Lookup::Stale(etag) => {
+                    if !parts.headers.contains_key(hyper::header::IF_NONE_MATCH) {
+                        parts.headers.insert(hyper::header::IF_NONE_MATCH, etag);
+                        revalidating = true;
+                    }
+                }
+                Lookup::Miss => {}
+            }
+        }
+    }
+
     // Reconstruct request for forwarding using the ORIGINAL streaming body
     // FIX #28: No body buffering here.
     // Recompute gRPC after plugin hooks: plugins may add or remove Content-Type.
@@ -239,7 +731,39 @@ async fn handle_request(
         Protocol::HTTP
     };
 
-    let mut forward_req = Request::from_parts(parts, body.boxed());
+    // Bodies are counted as they stream through, since a declared length may
+    // not hold.
+    //
+    // The body is never buffered here: hyper only polls it for more data while
+    // its write buffer has room, and the tunnel stream's `poll_write` stays
+    // pending until the stream's flow-control window has credit and the frame
+    // is queued. A slow local service therefore stops the ingress reading from
+    // the client socket, which pushes TCP backpressure back to the client.
+    let counted = CountingBody::new(body, ctx.request_bytes.clone());
+    let mut forward_body: ForwardBody =
+        Limited::new(counted, config.max_request_size).boxed_unsync();
+    // Plugins may resize chunks, so the original length no longer holds
+    if !forward_body.is_end_stream() && registry.needs_request_body().await {
+        forward_body = inspected_body(forward_body, registry.clone(), ctx.clone());
+        parts.headers.remove(hyper::header::CONTENT_LENGTH);
+    }
+    // Unconditional copy of a revalidation, sent if the entry is evicted
+    // before upstream's 304 arrives so the client never gets a 304 it did
+    // not ask for
+    let refetch = revalidating.then(|| {
+        let body: ForwardBody = Empty::new().map_err(|never| match never {}).boxed_unsync();
+        let mut refetch = Request::builder()
+            .method(parts.method.clone())
+            .uri(parts.uri.clone())
+            .version(parts.version)
+            .body(body);
+        if let Ok(refetch) = &mut refetch {
+            *refetch.headers_mut() = parts.headers.clone();
+            refetch.headers_mut().remove(hyper::header::IF_NONE_MATCH);
+        }
+        refetch
+    });
+    let mut forward_req = Request::from_parts(parts, forward_body);
 
     // HTTP/2 (gRPC) requires an absolute URI (scheme + authority).
     // Callers often send requests with a path-only URI and a Host header;
@@ -263,17 +787,53 @@ async fn handle_request(
         }
     }
 
-    // 3. Open Stream
-    let stream = match multiplexer.open_stream(protocol).await {
+    // Held until the upstream response is complete. Taken before the circuit
+    // check so a rejected request never uses up a half-open probe.
+    let Some(permit) = request_limits.try_acquire(&tunnel_id, tunnel_limit) else {
+        warn!("Too many requests in flight for tunnel '{}'", tunnel_id);
+        return Ok(full_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Too many requests in flight",
+        ));
+    };
+
+    // Fail fast while this tunnel's circuit is open
+    if !breakers.allow(&tunnel_id) {
+        return Ok(full_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Tunnel unavailable (circuit open)",
+        ));
+    }
+
+    // 3. Open Stream, telling the client who the request came from
+    let stream_headers = vec![(REMOTE_ADDR_HEADER.to_string(), peer_addr.to_string())];
+    #[cfg(feature = "metrics")]
+    let usage = backend.1.traffic().clone();
+    let opened = open_stream(
+        &sessions,
+        &tunnel_id,
+        backend,
+        protocol,
+        priority,
+        stream_headers,
+    )
+    .await;
+    let mut stream = match opened {
         Ok(s) => s,
         Err(e) => {
+            breakers.record_failure(&tunnel_id);
             error!("Failed to open stream: {}", e);
-            return Ok(full_response(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to open stream",
-            ));
+            let (status, message) = open_stream_failure(&e);
+            return Ok(full_response(status, message));
         }
     };
+    #[cfg(feature = "metrics")]
+    usage.record_request();
+
+    // A body that fails part way, e.g. because the client disconnected
+    // mid-upload, must not reach the local service as a complete request
+    let forward_req =
+        forward_req.map(|body| abort_on_body_error(body, stream.abort_handle(), tunnel_id.clone()));
 
     // 4. Handshake and Send Request (with timeout)
     let io = TokioIo::new(stream);
@@ -289,6 +849,7 @@ async fn handle_request(
         let (mut sender, conn) = match handshake_result {
             Ok(Ok(res)) => res,
             Ok(Err(e)) => {
+                breakers.record_failure(&tunnel_id);
                 error!("gRPC tunnel handshake failed: {}", e);
                 return Ok(full_response(
                     StatusCode::BAD_GATEWAY,
@@ -296,6 +857,7 @@ async fn handle_request(
                 ));
             }
             Err(_) => {
+                breakers.record_timeout(&tunnel_id);
                 error!("gRPC tunnel handshake timeout");
                 return Ok(full_response(
                     StatusCode::GATEWAY_TIMEOUT,
@@ -309,18 +871,40 @@ async fn handle_request(
         });
 
         let response_result =
-            tokio::time::timeout(config.response_timeout, sender.send_request(forward_req)).await;
+            tokio::time::timeout(response_timeout, sender.send_request(forward_req))
+                .await
+                .ok();
 
         let res = match response_result {
-            Ok(Ok(res)) => res,
-            Ok(Err(e)) => {
+            Some(Ok(res)) => {
+                breakers.record_success(&tunnel_id);
+                res
+            }
+            Some(Err(e)) if body_rejection(&e).is_some() => {
+                breakers.record_success(&tunnel_id);
+                return Ok(rejected_body_response(&e));
+            }
+            Some(Err(e)) if is_body_limit_error(&e) => {
+                breakers.record_success(&tunnel_id);
+                warn!(
+                    "gRPC request body exceeded {} bytes",
+                    config.max_request_size
+                );
+                return Ok(full_response(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "Request body too large",
+                ));
+            }
+            Some(Err(e)) => {
+                breakers.record_failure(&tunnel_id);
                 error!("gRPC request failed: {}", e);
                 return Ok(full_response(
                     StatusCode::BAD_GATEWAY,
                     "gRPC request failed",
                 ));
             }
-            Err(_) => {
+            None => {
+                breakers.record_timeout(&tunnel_id);
                 error!("gRPC response timeout");
                 return Ok(full_response(
                     StatusCode::GATEWAY_TIMEOUT,
@@ -331,7 +915,10 @@ async fn handle_request(
 
         let (parts, body) = res.into_parts();
         // Stream the response body directly to preserve HTTP/2 trailers
-        return Ok(Response::from_parts(parts, body.boxed()));
+        return Ok(Response::from_parts(
+            parts,
+            hold_permit(body.map_err(BoxError::from).boxed(), permit),
+        ));
     }
 
     let handshake_result = tokio::time::timeout(
@@ -343,6 +930,7 @@ async fn handle_request(
     let (mut sender, conn) = match handshake_result {
         Ok(Ok(res)) => res,
         Ok(Err(e)) => {
+            breakers.record_failure(&tunnel_id);
             error!("Handshake failed: {}", e);
             return Ok(full_response(
                 StatusCode::BAD_GATEWAY,
@@ -350,6 +938,7 @@ async fn handle_request(
             ));
         }
         Err(_) => {
+            breakers.record_timeout(&tunnel_id);
             error!("Handshake timeout");
             return Ok(full_response(
                 StatusCode::GATEWAY_TIMEOUT,
@@ -365,19 +954,37 @@ async fn handle_request(
     });
 
     // 5. Send Request and receive response (with timeout)
-    let response_result =
-        tokio::time::timeout(config.response_timeout, sender.send_request(forward_req)).await;
+    let response_result = tokio::time::timeout(response_timeout, sender.send_request(forward_req))
+        .await
+        .ok();
 
-    let res = match response_result {
-        Ok(Ok(res)) => res,
-        Ok(Err(e)) => {
+    let mut res = match response_result {
+        Some(Ok(res)) => {
+            breakers.record_success(&tunnel_id);
+            res
+        }
+        Some(Err(e)) if body_rejection(&e).is_some() => {
+            breakers.record_success(&tunnel_id);
+            return Ok(rejected_body_response(&e));
+        }
+        Some(Err(e)) if is_body_limit_error(&e) => {
+            breakers.record_success(&tunnel_id);
+            warn!("Request body exceeded {} bytes", config.max_request_size);
+            return Ok(full_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "Request body too large",
+            ));
+        }
+        Some(Err(e)) => {
+            breakers.record_failure(&tunnel_id);
             error!("Failed to send request: {}", e);
             return Ok(full_response(
                 StatusCode::BAD_GATEWAY,
                 "Failed to send request",
             ));
         }
-        Err(_) => {
+        None => {
+            breakers.record_timeout(&tunnel_id);
             error!("Response timeout");
             return Ok(full_response(
                 StatusCode::GATEWAY_TIMEOUT,
@@ -391,6 +998,7 @@ async fn handle_request(
         let tunnel_upgrade = hyper::upgrade::on(res);
 
         if let Some(client_upgrade) = client_upgrade {
+            let limits = config.websocket;
             tokio::spawn(async move {
                 let (tunnel_result, client_result) = tokio::join!(tunnel_upgrade, client_upgrade);
 
@@ -411,9 +1019,8 @@ async fn handle_request(
 
                 let mut tunnel_io = TokioIo::new(tunnel_upgraded);
                 let mut client_io = TokioIo::new(client_upgraded);
-                if let Err(e) = tokio::io::copy_bidirectional(&mut tunnel_io, &mut client_io).await
-                {
-                    error!("WebSocket copy error: {e}");
+                if let Err(e) = copy_websocket(&mut tunnel_io, &mut client_io, limits).await {
+                    warn!("WebSocket closed: {e}");
                 }
             });
         }
@@ -437,11 +1044,78 @@ async fn handle_request(
         return Ok(client_res);
     }
 
+    if revalidating && res.status() == StatusCode::NOT_MODIFIED {
+        if let (Some(cache), Some(key)) = (&cache, &cache_key) {
+            if let Some(cached) = cache.refresh(key, res.headers()) {
+                return Ok(compress(cached, &config, encoding));
+            }
+        }
+        // The entry was evicted meanwhile: fetch the full response instead
+        let refetched = match refetch {
+            Some(Ok(refetch)) => tokio::time::timeout(response_timeout, async {
+                sender.ready().await?;
+                sender.send_request(refetch).await
+            })
+            .await
+            .ok(),
+            _ => None,
+        };
+        match refetched {
+            Some(Ok(refetched)) => res = refetched,
+            _ => {
+                error!("Failed to refetch evicted cache entry for tunnel '{tunnel_id}'");
+                return Ok(full_response(
+                    StatusCode::BAD_GATEWAY,
+                    "Failed to send request",
+                ));
+            }
+        }
+    }
+
     let (parts, body) = res.into_parts();
+    if let (Some(inspector), Some(head)) = (&ingress.inspector, &inspected_head) {
+        let duration = ctx.timestamp.elapsed().unwrap_or_default();
+        inspector
+            .on_exchange(&tunnel_id, head, &parts, duration)
+            .await;
+    }
 
-    if !registry.needs_response_buffering().await {
-        let streaming_body = body.boxed();
-        return Ok(Response::from_parts(parts, streaming_body));
+    // Cacheable responses are buffered so they can be stored
+    let cache_store = cache
+        .zip(cache_key)
+        .zip(cache_request_headers)
+        .filter(|((cache, _), request)| cache.is_storable(request, parts.status, &parts.headers));
+
+    // Event streams never end, so they cannot be buffered for response
+    // hooks; plugins that want bodies see their head only
+    let event_stream = is_event_stream(parts.headers.get(hyper::header::CONTENT_TYPE));
+    if event_stream || (cache_store.is_none() && !registry.needs_response_buffering().await) {
+        let parts = if event_stream && registry.needs_response_buffering().await {
+            let mut head = Response::from_parts(parts, Vec::new());
+            let answer = streamed_response_hooks(&mut head, &registry, &ctx, &response_bytes).await;
+            if let Some(answer) = answer {
+                // Dropping the body closes the tunnel stream
+                return Ok(answer);
+            }
+            head.into_parts().0
+        } else {
+            parts
+        };
+        let body = CountingBody::new(body, response_bytes)
+            .map_err(BoxError::from)
+            .boxed();
+        let body = if event_stream {
+            let idle = config.event_stream_idle_timeout;
+            IdleTimeoutBody::new(body, idle, tunnel_id.clone()).boxed()
+        } else {
+            body
+        };
+        let streaming_body = hold_permit(body, permit);
+        return Ok(compress(
+            Response::from_parts(parts, streaming_body),
+            &config,
+            encoding,
+        ));
     }
 
     // Buffer response for plugin processing
@@ -453,31 +1127,422 @@ async fn handle_request(
         }
     };
 
+    response_bytes.add(body_bytes.len() as u64);
     let mut proxy_res = Response::from_parts(parts, body_bytes.to_vec());
 
-    let response_ctx = ResponseContext {
-        tunnel_id: ctx.tunnel_id.clone(),
-        session_id: ctx.session_id.clone(),
-        status_code: proxy_res.status().as_u16(),
-        duration_ms: u64::try_from(ctx.timestamp.elapsed().unwrap_or_default().as_millis())
-            .unwrap_or(u64::MAX),
-    };
+    let response_ctx = response_context(&ctx, proxy_res.status(), false, &response_bytes);
 
     // Run Response Hooks
     match registry
         .execute_response_hooks(&mut proxy_res, &response_ctx)
         .await
     {
-        Ok(PluginAction::Continue | _) => {}
+        // Plugin answers are not cached
+        Ok(action) => {
+            if let Some(answer) = plugin_answer(action) {
+                return Ok(answer);
+            }
+        }
         Err(e) => error!("Plugin response hook error: {}", e),
     }
 
     let (final_parts, final_body) = proxy_res.into_parts();
-    let boxed_body = http_body_util::Full::new(Bytes::from(final_body))
+    let final_body = Bytes::from(final_body);
+    if let Some(((cache, key), request_headers)) = cache_store {
+        cache.store(
+            key,
+            &request_headers,
+            final_parts.status,
+            &final_parts.headers,
+            final_body.clone(),
+        );
+    }
+    let boxed_body = http_body_util::Full::new(final_body)
         .map_err(|never| match never {})
         .boxed();
 
-    Ok(Response::from_parts(final_parts, boxed_body))
+    Ok(compress(
+        Response::from_parts(final_parts, boxed_body),
+        &config,
+        encoding,
+    ))
+}
+
+fn response_context(
+    ctx: &RequestContext,
+    status: StatusCode,
+    body_streamed: bool,
+    response_bytes: &ByteCounter,
+) -> ResponseContext {
+    ResponseContext {
+        tunnel_id: ctx.tunnel_id.clone(),
+        session_id: ctx.session_id.clone(),
+        request_id: ctx.request_id.clone(),
+        status_code: status.as_u16(),
+        duration_ms: u64::try_from(ctx.timestamp.elapsed().unwrap_or_default().as_millis())
+            .unwrap_or(u64::MAX),
+        body_streamed,
+        request_bytes: ctx.request_bytes.clone(),
+        response_bytes: response_bytes.clone(),
+    }
+}
+
+/// Run the response hooks on the head of a response whose body is streamed
+/// to the client, keeping any header or status changes they make
+///
+/// Returns the plugin's answer when a hook returns `Reject` or `Respond`,
+/// which then replaces the whole response.
+async fn streamed_response_hooks(
+    head: &mut Response<Vec<u8>>,
+    registry: &PluginRegistry,
+    ctx: &RequestContext,
+    response_bytes: &ByteCounter,
+) -> Option<Response<BoxBody>> {
+    let response_ctx = response_context(ctx, head.status(), true, response_bytes);
+    match registry.execute_response_hooks(head, &response_ctx).await {
+        Ok(action) => plugin_answer(action),
+        Err(e) => {
+            error!("Plugin response hook error: {}", e);
+            None
+        }
+    }
+}
+
+/// The response a response hook answers with instead of the upstream one,
+/// for `Reject` and `Respond`
+fn plugin_answer(action: PluginAction) -> Option<Response<BoxBody>> {
+    match action {
+        PluginAction::Reject { status, reason } => Some(full_response(
+            StatusCode::from_u16(status).unwrap_or(StatusCode::FORBIDDEN),
+            &reason,
+        )),
+        PluginAction::Respond {
+            status,
+            headers,
+            body,
+        } => Some(plugin_response(status, headers, body)),
+        _ => None,
+    }
+}
+
+/// Response built from a plugin's `Respond` action
+fn plugin_response(
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+) -> Response<BoxBody> {
+    let mut res =
+        Response::builder().status(StatusCode::from_u16(status).unwrap_or(StatusCode::OK));
+    for (k, v) in headers {
+        res = res.header(k, v);
+    }
+    res.body(full_body(Bytes::from(body))).unwrap_or_else(|_| {
+        full_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to build plugin response",
+        )
+    })
+}
+
+/// Keep a request counted against its tunnel's limit until the streamed
+/// response body is finished or dropped
+fn hold_permit(body: BoxBody, permit: InFlightPermit) -> BoxBody {
+    body.map_frame(move |frame| {
+        let _held = &permit;
+        frame
+    })
+    .boxed()
+}
+
+/// Request body fed through the plugins' chunk hooks as it streams
+///
+/// The hooks run once more with an empty chunk after the last data frame,
+/// before any trailers, so plugins can flush bytes they held back.
+fn inspected_body(
+    body: ForwardBody,
+    registry: Arc<PluginRegistry>,
+    ctx: RequestContext,
+) -> ForwardBody {
+    struct Inspection {
+        body: ForwardBody,
+        registry: Arc<PluginRegistry>,
+        ctx: RequestContext,
+        queued: VecDeque<Frame<Bytes>>,
+        done: bool,
+    }
+
+    let state = Inspection {
+        body,
+        registry,
+        ctx,
+        queued: VecDeque::new(),
+        done: false,
+    };
+    let frames = futures::stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(frame) = state.queued.pop_front() {
+                return Some((Ok(frame), state));
+            }
+            if state.done {
+                return None;
+            }
+            let (mut chunk, trailers) = match state.body.frame().await {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) if data.is_empty() => continue,
+                    Ok(data) => (data, None),
+                    Err(frame) => {
+                        state.done = true;
+                        (Bytes::new(), frame.into_trailers().ok())
+                    }
+                },
+                Some(Err(e)) => {
+                    state.done = true;
+                    return Some((Err(e), state));
+                }
+                None => {
+                    state.done = true;
+                    (Bytes::new(), None)
+                }
+            };
+
+            let rejected = match state
+                .registry
+                .execute_request_chunk_hooks(&mut chunk, &state.ctx)
+                .await
+            {
+                Ok(PluginAction::Reject { status, reason }) => Some(BodyRejected {
+                    status: StatusCode::from_u16(status).unwrap_or(StatusCode::FORBIDDEN),
+                    reason,
+                }),
+                Ok(PluginAction::Respond { status, body, .. }) => Some(BodyRejected {
+                    status: StatusCode::from_u16(status).unwrap_or(StatusCode::OK),
+                    reason: String::from_utf8_lossy(&body).into_owned(),
+                }),
+                // The registry only stops the body with the actions above
+                Ok(_) => None,
+                Err(e) => {
+                    error!("Plugin error: {}", e);
+                    Some(BodyRejected {
+                        status: StatusCode::INTERNAL_SERVER_ERROR,
+                        reason: "Plugin processing error".to_string(),
+                    })
+                }
+            };
+            if let Some(rejected) = rejected {
+                state.done = true;
+                let err: Box<dyn std::error::Error + Send + Sync> = Box::new(rejected);
+                return Some((Err(err), state));
+            }
+
+            if !chunk.is_empty() {
+                state.queued.push_back(Frame::data(chunk));
+            }
+            state.queued.extend(trailers.map(Frame::trailers));
+        }
+    });
+    StreamBody::new(frames).boxed_unsync()
+}
+
+/// Request body that closes the tunnel stream with an error if it fails
+///
+/// hyper only ever ends the stream cleanly, which the local service would
+/// read as the end of a complete body. Aborting sends `CloseStream` with
+/// [`CloseReason::Error`] instead, so the proxy sees a reset connection.
+fn abort_on_body_error(
+    body: ForwardBody,
+    abort: StreamAbortHandle,
+    tunnel_id: String,
+) -> ForwardBody {
+    body.map_err(move |err| {
+        if !abort.is_aborted() {
+            let reason = if client_disconnected(err.as_ref()) {
+                warn!("Client disconnected mid-upload to tunnel '{tunnel_id}': {err}");
+                "client disconnected mid-upload".to_string()
+            } else {
+                warn!("Request body to tunnel '{tunnel_id}' failed: {err}");
+                format!("request body failed: {err}")
+            };
+            abort.abort(CloseReason::Error(reason));
+        }
+        err
+    })
+    .boxed_unsync()
+}
+
+/// Whether a request body error means the client went away before sending
+/// all of it, rather than a limit or plugin stopping it
+fn client_disconnected(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(e) = source {
+        if let Some(err) = e.downcast_ref::<hyper::Error>() {
+            if err.is_incomplete_message() {
+                return true;
+            }
+        }
+        if e.is::<std::io::Error>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// The plugin rejection behind a failed request, if a chunk hook stopped it
+fn body_rejection(err: &hyper::Error) -> Option<&BodyRejected> {
+    let mut source = std::error::Error::source(err);
+    while let Some(e) = source {
+        if let Some(rejected) = e.downcast_ref::<BodyRejected>() {
+            return Some(rejected);
+        }
+        source = e.source();
+    }
+    None
+}
+
+fn rejected_body_response(err: &hyper::Error) -> Response<BoxBody> {
+    match body_rejection(err) {
+        Some(rejected) => {
+            warn!("{}", rejected);
+            full_response(rejected.status, &rejected.reason)
+        }
+        None => full_response(StatusCode::BAD_GATEWAY, "Failed to send request"),
+    }
+}
+
+/// Open a stream to the tunnel's client. If the chosen session has gone away
+/// and the tunnel is pooled, retry on its other sessions before giving up.
+async fn open_stream(
+    sessions: &SessionStoreBackend,
+    tunnel_id: &str,
+    mut backend: (Uuid, Multiplexer),
+    protocol: Protocol,
+    priority: StreamPriority,
+    headers: Vec<(String, String)>,
+) -> Result<VirtualStream> {
+    let mut failed = Vec::new();
+    loop {
+        let (session_id, multiplexer) = backend;
+        let err = match multiplexer
+            .open_stream_with_priority_and_headers(protocol, priority, headers.clone())
+            .await
+        {
+            Ok(stream) => return Ok(stream),
+            Err(e) => e,
+        };
+        failed.push(session_id);
+        let next = sessions
+            .select_by_tunnel_id(tunnel_id, &failed)
+            .and_then(|session| Some((session.id, session.multiplexer.clone()?)));
+        let Some(next) = next else {
+            return Err(err);
+        };
+        warn!(
+            "Failed to open stream on session {}: {}; retrying on session {}",
+            session_id, err, next.0
+        );
+        backend = next;
+    }
+}
+
+/// Apply the response compression negotiated for the request, if any
+fn compress(
+    res: Response<BoxBody>,
+    config: &IngressConfig,
+    encoding: Option<Encoding>,
+) -> Response<BoxBody> {
+    match (&config.compression, encoding) {
+        (Some(compression), Some(encoding)) => compression.compress_response(res, encoding),
+        _ => res,
+    }
+}
+
+/// Response timeout for one request: the plugin override, else the trusted
+/// header, else `response_timeout`. Overrides are capped at
+/// `max_response_timeout`.
+fn effective_response_timeout(
+    config: &IngressConfig,
+    headers: &hyper::HeaderMap,
+    plugin_override: Option<Duration>,
+) -> Duration {
+    let header_override = config
+        .timeout_header
+        .as_deref()
+        .and_then(|name| headers.get(name))
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+    plugin_override
+        .or(header_override)
+        .map_or(config.response_timeout, |timeout| {
+            timeout.min(config.max_response_timeout)
+        })
+}
+
+/// Event-stream response body that ends once no frame arrives for `idle`
+struct IdleTimeoutBody {
+    inner: BoxBody,
+    idle: Duration,
+    timer: Pin<Box<tokio::time::Sleep>>,
+    tunnel_id: String,
+}
+
+impl IdleTimeoutBody {
+    fn new(inner: BoxBody, idle: Duration, tunnel_id: String) -> Self {
+        Self {
+            inner,
+            idle,
+            timer: Box::pin(tokio::time::sleep(idle)),
+            tunnel_id,
+        }
+    }
+}
+
+impl Body for IdleTimeoutBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::result::Result<Frame<Bytes>, BoxError>>> {
+        let this = &mut *self;
+        match Pin::new(&mut this.inner).poll_frame(cx) {
+            Poll::Ready(frame) => {
+                let deadline = tokio::time::Instant::now() + this.idle;
+                this.timer.as_mut().reset(deadline);
+                Poll::Ready(frame)
+            }
+            Poll::Pending if this.timer.as_mut().poll(cx).is_ready() => {
+                warn!(
+                    "Event stream from tunnel '{}' idle for {:?}, closing it",
+                    this.tunnel_id, this.idle
+                );
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Whether an `Accept` or `Content-Type` value names Server-Sent Events
+fn is_event_stream(value: Option<&hyper::header::HeaderValue>) -> bool {
+    value.and_then(|v| v.to_str().ok()).is_some_and(|v| {
+        v.split(',').any(|item| {
+            item.split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .eq_ignore_ascii_case("text/event-stream")
+        })
+    })
 }
 
 fn is_grpc(headers: &hyper::HeaderMap) -> bool {
@@ -487,6 +1552,129 @@ fn is_grpc(headers: &hyper::HeaderMap) -> bool {
         .is_some_and(|v| v.starts_with("application/grpc"))
 }
 
+/// Longest error body carried over into `grpc-message`
+const GRPC_MESSAGE_LIMIT: usize = 1024;
+
+/// Read an error body of at most `GRPC_MESSAGE_LIMIT` bytes, `None` if it is
+/// longer or fails midway.
+///
+/// Reads frames directly instead of through `Limited`, whose error conversion
+/// keeps the connection future from being `Send` for hyper's executor.
+async fn collect_error_body(mut body: BoxBody) -> Option<Bytes> {
+    let mut buf = Vec::new();
+    while let Some(frame) = body.frame().await {
+        if let Ok(data) = frame.ok()?.into_data() {
+            if buf.len() + data.len() > GRPC_MESSAGE_LIMIT {
+                return None;
+            }
+            buf.extend_from_slice(&data);
+        }
+    }
+    Some(Bytes::from(buf))
+}
+
+/// Turn a plain HTTP error answering a gRPC request into a trailers-only gRPC
+/// response, so gRPC clients see a status code and message instead of a
+/// protocol error.
+///
+/// Successful responses and errors the upstream already sent as gRPC pass
+/// through untouched, trailers included.
+async fn grpc_error_if_needed(grpc: bool, res: Response<BoxBody>) -> Response<BoxBody> {
+    if !grpc || res.status().is_success() || is_grpc(res.headers()) {
+        return res;
+    }
+    let (parts, body) = res.into_parts();
+    // Ingress errors carry a short text body; fall back to the reason phrase
+    let message = collect_error_body(body)
+        .await
+        .filter(|bytes| !bytes.is_empty())
+        .map_or_else(
+            || {
+                parts
+                    .status
+                    .canonical_reason()
+                    .unwrap_or("Unknown error")
+                    .to_string()
+            },
+            |bytes| String::from_utf8_lossy(&bytes).into_owned(),
+        );
+    grpc_error_response(grpc_status_for(parts.status), &message)
+}
+
+/// Marks a response as an error the ingress generated itself, as opposed to
+/// one from the local service
+#[derive(Debug, Clone, Copy)]
+struct IngressError;
+
+/// Replace the body of an ingress-generated error with its custom page, if
+/// one is configured for its status
+async fn error_page_if_needed(
+    pages: &ErrorPageSet,
+    tunnel_id: &str,
+    res: Response<BoxBody>,
+) -> Response<BoxBody> {
+    if res.extensions().get::<IngressError>().is_none() {
+        return res;
+    }
+    let Some(page) = pages.page_for(res.status()) else {
+        return res;
+    };
+    let (mut parts, body) = res.into_parts();
+    // The default body becomes the `{message}` token
+    let message = collect_error_body(body)
+        .await
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .unwrap_or_default();
+    let rendered = page.render(parts.status, tunnel_id, &message);
+    parts.headers.remove(hyper::header::CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(hyper::header::CONTENT_TYPE, page.content_type().clone());
+    Response::from_parts(parts, full_body(Bytes::from(rendered)))
+}
+
+/// gRPC status code for an HTTP error, following the gRPC HTTP status mapping
+fn grpc_status_for(status: StatusCode) -> u8 {
+    match status {
+        StatusCode::BAD_REQUEST => 13,  // INTERNAL
+        StatusCode::UNAUTHORIZED => 16, // UNAUTHENTICATED
+        StatusCode::FORBIDDEN => 7,     // PERMISSION_DENIED
+        StatusCode::NOT_FOUND => 12,    // UNIMPLEMENTED
+        // RESOURCE_EXHAUSTED
+        StatusCode::PAYLOAD_TOO_LARGE | StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE => 8,
+        StatusCode::TOO_MANY_REQUESTS
+        | StatusCode::BAD_GATEWAY
+        | StatusCode::SERVICE_UNAVAILABLE
+        | StatusCode::GATEWAY_TIMEOUT => 14, // UNAVAILABLE
+        _ => 2, // UNKNOWN
+    }
+}
+
+/// Trailers-only gRPC response: HTTP 200 with the status in the headers
+fn grpc_error_response(code: u8, message: &str) -> Response<BoxBody> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(hyper::header::CONTENT_TYPE, "application/grpc")
+        .header("grpc-status", code.to_string())
+        .header("grpc-message", percent_encode_grpc_message(message))
+        .body(Empty::new().map_err(|never| match never {}).boxed())
+        .unwrap_or_else(|_| full_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal error"))
+}
+
+/// Percent-encode `message` as `grpc-message` requires: printable ASCII other
+/// than `%` is kept, every other byte becomes `%XX`
+fn percent_encode_grpc_message(message: &str) -> String {
+    let mut encoded = String::with_capacity(message.len());
+    for byte in message.bytes() {
+        if (b' '..=b'~').contains(&byte) && byte != b'%' {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
 fn is_websocket_upgrade(headers: &hyper::HeaderMap) -> bool {
     let upgrade = headers
         .get(hyper::header::UPGRADE)
@@ -502,6 +1690,50 @@ fn is_websocket_upgrade(headers: &hyper::HeaderMap) -> bool {
     upgrade && connection
 }
 
+/// Every `Sec-WebSocket-Extensions` value, in order
+fn websocket_extensions(headers: &hyper::HeaderMap) -> Vec<hyper::header::HeaderValue> {
+    headers
+        .get_all(hyper::header::SEC_WEBSOCKET_EXTENSIONS)
+        .iter()
+        .cloned()
+        .collect()
+}
+
+/// Put back the client's extension offer, undoing any plugin changes
+fn restore_websocket_extensions(
+    headers: &mut hyper::HeaderMap,
+    extensions: Vec<hyper::header::HeaderValue>,
+) {
+    headers.remove(hyper::header::SEC_WEBSOCKET_EXTENSIONS);
+    for value in extensions {
+        headers.append(hyper::header::SEC_WEBSOCKET_EXTENSIONS, value);
+    }
+}
+
+/// Body length declared by a valid `Content-Length` header, if any
+fn declared_content_length(headers: &hyper::HeaderMap) -> Option<u64> {
+    headers
+        .get(hyper::header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Whether `headers` has more entries than `max_header_count` or more name
+/// and value bytes than `max_header_bytes`
+fn headers_exceed_limits(headers: &hyper::HeaderMap, config: &IngressConfig) -> bool {
+    if headers.len() > config.max_header_count {
+        return true;
+    }
+    let bytes = headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .fold(0usize, usize::saturating_add);
+    bytes > config.max_header_bytes
+}
+
 /// Parse and normalize the Host header for secure multi-tenant routing.
 /// Handles IPv6 addresses, port stripping, and case normalization.
 fn parse_and_normalize_host(
@@ -545,6 +1777,26 @@ fn parse_and_normalize_host(
     Ok(normalized.to_string())
 }
 
+/// Map a normalized host to the tunnel ID it addresses under the configured
+/// base domain; `None` when the host cannot name a tunnel.
+fn route_host(host: String, config: &IngressConfig) -> Option<String> {
+    let Some(base) = config.base_domain.as_deref() else {
+        return Some(host);
+    };
+    if host == base {
+        return None;
+    }
+    match host
+        .strip_suffix(base)
+        .and_then(|prefix| prefix.strip_suffix('.'))
+    {
+        Some(label) if !label.is_empty() => Some(label.to_string()),
+        Some(_) => None,
+        None if config.strict_base_domain => None,
+        None => Some(host),
+    }
+}
+
 /// Initial reserve for response body collection to reduce reallocations.
 const BODY_COLLECT_RESERVE: usize = 64 * 1024;
 
@@ -592,11 +1844,15 @@ fn full_response(status: StatusCode, body: &str) -> Response<BoxBody> {
         | "Tunnel handshake timeout"
         | "Failed to send request"
         | "Upstream response timeout"
+        | "Request body too large"
+        | "Request header fields too large"
+        | "Path not allowed"
+        | "Tunnel unavailable (circuit open)"
         | "Response timeout" => Bytes::copy_from_slice(body.as_bytes()),
         _ => Bytes::copy_from_slice(body.as_bytes()),
     };
     // Response::builder() with valid status and body should never fail
-    Response::builder()
+    let mut res = Response::builder()
         .status(status)
         .body(
             http_body_util::Full::new(bytes)
@@ -609,7 +1865,11 @@ fn full_response(status: StatusCode, body: &str) -> Response<BoxBody> {
                     .map_err(|never| match never {})
                     .boxed(),
             )
-        })
+        });
+    if status.is_client_error() || status.is_server_error() {
+        res.extensions_mut().insert(IngressError);
+    }
+    res
 }
 
 fn full_body(bytes: Bytes) -> BoxBody {
@@ -685,6 +1945,79 @@ mod tests {
         assert!(parse_and_normalize_host(None).is_err());
     }
 
+    fn base_domain_config(strict: bool) -> IngressConfig {
+        IngressConfig {
+            base_domain: Some("tunnel.example.com".to_string()),
+            strict_base_domain: strict,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_route_host_without_base_domain() {
+        let config = IngressConfig::default();
+        assert_eq!(
+            route_host("myapp.tunnel.example.com".into(), &config).as_deref(),
+            Some("myapp.tunnel.example.com")
+        );
+    }
+
+    #[test]
+    fn test_route_host_subdomain() {
+        let config = base_domain_config(false);
+        assert_eq!(
+            route_host("myapp.tunnel.example.com".into(), &config).as_deref(),
+            Some("myapp")
+        );
+        assert_eq!(
+            route_host("a.b.tunnel.example.com".into(), &config).as_deref(),
+            Some("a.b")
+        );
+        // Suffix match must fall on a label boundary
+        assert_eq!(
+            route_host("eviltunnel.example.com".into(), &config).as_deref(),
+            Some("eviltunnel.example.com")
+        );
+    }
+
+    #[test]
+    fn test_route_host_bare_base_domain() {
+        assert_eq!(
+            route_host("tunnel.example.com".into(), &base_domain_config(false)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_route_host_outside_base_domain() {
+        assert_eq!(
+            route_host("other.com".into(), &base_domain_config(false)).as_deref(),
+            Some("other.com")
+        );
+        assert_eq!(
+            route_host("other.com".into(), &base_domain_config(true)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_base_domain_normalized() {
+        let config = IngressConfig {
+            base_domain: Some(".Tunnel.Example.COM.".to_string()),
+            ..Default::default()
+        };
+        let ingress = HttpIngress::with_config(
+            "127.0.0.1:0".parse().unwrap(),
+            SessionStoreBackend::default(),
+            Arc::new(PluginRegistry::new()),
+            config,
+        );
+        assert_eq!(
+            ingress.config.base_domain.as_deref(),
+            Some("tunnel.example.com")
+        );
+    }
+
     #[test]
     fn test_websocket_upgrade_detected() {
         let mut headers = hyper::HeaderMap::new();
@@ -704,6 +2037,32 @@ mod tests {
         assert!(is_websocket_upgrade(&headers));
     }
 
+    #[test]
+    fn test_websocket_extensions_restored() {
+        let mut headers = hyper::HeaderMap::new();
+        let ext = hyper::header::SEC_WEBSOCKET_EXTENSIONS;
+        headers.append(
+            ext.clone(),
+            "permessage-deflate; client_max_window_bits"
+                .parse()
+                .unwrap(),
+        );
+        headers.append(ext.clone(), "x-custom".parse().unwrap());
+        let offer = websocket_extensions(&headers);
+
+        headers.insert(ext.clone(), "permessage-deflate".parse().unwrap());
+        restore_websocket_extensions(&mut headers, offer);
+        let values: Vec<_> = headers.get_all(&ext).iter().collect();
+        assert_eq!(
+            values,
+            ["permessage-deflate; client_max_window_bits", "x-custom"]
+        );
+
+        headers.remove(&ext);
+        restore_websocket_extensions(&mut headers, Vec::new());
+        assert!(!headers.contains_key(&ext));
+    }
+
     #[test]
     fn test_not_websocket_without_upgrade_header() {
         let mut headers = hyper::HeaderMap::new();
@@ -718,9 +2077,206 @@ mod tests {
         assert!(!is_websocket_upgrade(&headers));
     }
 
+    #[test]
+    fn test_declared_content_length() {
+        let mut headers = hyper::HeaderMap::new();
+        assert_eq!(declared_content_length(&headers), None);
+        headers.insert(hyper::header::CONTENT_LENGTH, "1024".parse().unwrap());
+        assert_eq!(declared_content_length(&headers), Some(1024));
+        headers.insert(hyper::header::CONTENT_LENGTH, "abc".parse().unwrap());
+        assert_eq!(declared_content_length(&headers), None);
+    }
+
+    #[test]
+    fn test_headers_exceed_limits() {
+        let config = IngressConfig {
+            max_header_count: 3,
+            max_header_bytes: 64,
+            ..Default::default()
+        };
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert(hyper::header::HOST, "myapp".parse().unwrap());
+        headers.insert(hyper::header::ACCEPT, "*/*".parse().unwrap());
+        headers.append("x-a", "1".parse().unwrap());
+        assert!(!headers_exceed_limits(&headers, &config));
+
+        // Repeated names count once per value
+        headers.append("x-a", "2".parse().unwrap());
+        assert!(headers_exceed_limits(&headers, &config));
+
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert(hyper::header::HOST, "a".repeat(61).parse().unwrap());
+        assert!(headers_exceed_limits(&headers, &config));
+    }
+
+    #[test]
+    fn test_grpc_status_for_http_errors() {
+        assert_eq!(grpc_status_for(StatusCode::NOT_FOUND), 12);
+        assert_eq!(grpc_status_for(StatusCode::UNAUTHORIZED), 16);
+        assert_eq!(grpc_status_for(StatusCode::GATEWAY_TIMEOUT), 14);
+        assert_eq!(grpc_status_for(StatusCode::PAYLOAD_TOO_LARGE), 8);
+        assert_eq!(grpc_status_for(StatusCode::IM_A_TEAPOT), 2);
+    }
+
+    #[test]
+    fn test_percent_encode_grpc_message() {
+        assert_eq!(
+            percent_encode_grpc_message("Tunnel not found"),
+            "Tunnel not found"
+        );
+        assert_eq!(percent_encode_grpc_message("100%\n"), "100%25%0A");
+        assert_eq!(percent_encode_grpc_message("caf\u{e9}"), "caf%C3%A9");
+    }
+
+    #[tokio::test]
+    async fn test_grpc_error_if_needed() {
+        let res = grpc_error_if_needed(
+            true,
+            full_response(StatusCode::NOT_FOUND, "Tunnel not found"),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(is_grpc(res.headers()));
+        assert_eq!(res.headers()["grpc-status"], "12");
+        assert_eq!(res.headers()["grpc-message"], "Tunnel not found");
+
+        // Non-gRPC requests keep the plain HTTP error
+        let res = grpc_error_if_needed(
+            false,
+            full_response(StatusCode::NOT_FOUND, "Tunnel not found"),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_error_page_if_needed() {
+        use crate::error_pages::ErrorPage;
+
+        let pages = ErrorPageSet::new().with_server_error_page(ErrorPage::html("<p>{message}</p>"));
+        let res = error_page_if_needed(
+            &pages,
+            "app",
+            full_response(StatusCode::BAD_GATEWAY, "Tunnel not ready"),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            res.headers()[hyper::header::CONTENT_TYPE],
+            "text/html; charset=utf-8"
+        );
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "<p>Tunnel not ready</p>");
+
+        // Errors from the local service keep their body
+        let upstream = Response::builder()
+            .status(StatusCode::BAD_GATEWAY)
+            .body(full_body(Bytes::from_static(b"upstream")))
+            .unwrap();
+        let res = error_page_if_needed(&pages, "app", upstream).await;
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "upstream");
+
+        // As do statuses without a page
+        let res = error_page_if_needed(
+            &pages,
+            "app",
+            full_response(StatusCode::NOT_FOUND, "Tunnel not found"),
+        )
+        .await;
+        assert!(res.headers().get(hyper::header::CONTENT_TYPE).is_none());
+    }
+
     #[test]
     fn test_not_websocket_regular_request() {
         let headers = hyper::HeaderMap::new();
         assert!(!is_websocket_upgrade(&headers));
     }
+
+    #[test]
+    fn test_effective_response_timeout() {
+        let config = IngressConfig {
+            response_timeout: Duration::from_secs(60),
+            timeout_header: Some("X-Tunnel-Timeout".into()),
+            max_response_timeout: Duration::from_secs(600),
+            ..Default::default()
+        };
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            let mut headers = hyper::HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, hyper::header::HeaderValue::from_static(value));
+            }
+            headers
+        };
+        let secs = Duration::from_secs;
+
+        assert_eq!(
+            effective_response_timeout(&config, &headers(&[]), None),
+            secs(60)
+        );
+        let long = headers(&[("x-tunnel-timeout", "120")]);
+        assert_eq!(effective_response_timeout(&config, &long, None), secs(120));
+        assert_eq!(
+            effective_response_timeout(&config, &long, Some(secs(30))),
+            secs(30),
+            "plugin overrides win over the header"
+        );
+        let huge = headers(&[("x-tunnel-timeout", "86400")]);
+        assert_eq!(effective_response_timeout(&config, &huge, None), secs(600));
+        let bogus = headers(&[("x-tunnel-timeout", "-1")]);
+        assert_eq!(effective_response_timeout(&config, &bogus, None), secs(60));
+
+        let sse = headers(&[("accept", "text/event-stream")]);
+        assert_eq!(
+            effective_response_timeout(&config, &sse, None),
+            secs(60),
+            "clients cannot opt out of the timeout"
+        );
+
+        let untrusted = IngressConfig::default();
+        assert_eq!(
+            effective_response_timeout(&untrusted, &long, None),
+            secs(60)
+        );
+    }
+
+    #[test]
+    fn test_open_stream_failure_status() {
+        let status = |err: TunnelError| open_stream_failure(&err).0;
+        assert_eq!(
+            status(TunnelError::ResourceExhausted("streams".into())),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            status(TunnelError::RateLimited("streams".into())),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            status(TunnelError::Timeout("open".into())),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+        assert_eq!(
+            status(TunnelError::Connection("closed".into())),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[test]
+    fn test_over_limit_response() {
+        let config = IngressConfig {
+            connection_limit_retry_after: Duration::from_millis(7500),
+            connection_limit_body: "Busy".to_string(),
+            ..Default::default()
+        };
+        let res = over_limit_response(&config);
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(res.headers()[hyper::header::RETRY_AFTER], "7");
+
+        let config = IngressConfig {
+            connection_limit_retry_after: Duration::ZERO,
+            ..Default::default()
+        };
+        let res = over_limit_response(&config);
+        assert!(!res.headers().contains_key(hyper::header::RETRY_AFTER));
+    }
 }
@@ -0,0 +1,47 @@
+//! Request/response inspection hooks for the local proxy and the ingress
+//!
+//! A [`TrafficInspector`] sees the head of every request forwarded by
+//! [`HttpProxy`](crate::HttpProxy) and of the response that comes back, without
+//! buffering bodies. It is a lighter alternative to a full tower layer for
+//! embedders that only want to observe traffic. An [`IngressInspector`] does
+//! the same on the server for requests [`HttpIngress`](crate::HttpIngress)
+//! forwards through tunnels.
+
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// Request head (method, URI, version, headers) passed to inspectors
+pub type RequestParts = hyper::http::request::Parts;
+
+/// Response head (status, version, headers) passed to inspectors
+pub type ResponseParts = hyper::http::response::Parts;
+
+/// Callback invoked for each request forwarded to the local service
+///
+/// Hooks run inline on the request path, so slow work should be handed off
+/// (e.g. to a channel) rather than awaited.
+#[async_trait]
+pub trait TrafficInspector: Send + Sync {
+    /// Called before the request is forwarded
+    async fn on_request(&self, _request: &RequestParts) {}
+
+    /// Called once response headers arrive, with the time since forwarding began
+    async fn on_response(&self, _response: &ResponseParts, _duration: Duration) {}
+}
+
+/// Callback invoked for each request the HTTP ingress forwards to a tunnel
+///
+/// Like [`TrafficInspector`] it runs inline on the request path and sees
+/// heads only.
+#[async_trait]
+pub trait IngressInspector: Send + Sync {
+    /// Called once the response head arrives from `tunnel_id`, with the
+    /// request head as forwarded and the time since the request arrived
+    async fn on_exchange(
+        &self,
+        tunnel_id: &str,
+        request: &RequestParts,
+        response: &ResponseParts,
+        duration: Duration,
+    );
+}
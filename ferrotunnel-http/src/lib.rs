@@ -1,9 +1,36 @@
+pub mod access_log;
+pub mod cache;
+pub mod circuit;
+pub mod compression;
+pub mod dns;
+pub mod error_pages;
+pub mod forwarded;
+pub mod in_flight;
 pub mod ingress;
+pub mod inspect;
 pub mod pool;
+pub mod priority;
 pub mod proxy;
+pub mod proxy_protocol;
 pub mod tcp_ingress;
+pub mod trace_context;
+pub mod udp_ingress;
+pub mod websocket;
 
+pub use access_log::AccessLogFormat;
+pub use cache::{CacheConfig, ResponseCache};
+pub use circuit::TunnelCircuitBreakers;
+pub use compression::CompressionConfig;
+pub use dns::DnsCache;
+pub use error_pages::{ErrorPage, ErrorPageSet};
+pub use forwarded::ForwardedHeaders;
+pub use in_flight::TunnelRequestLimits;
 pub use ingress::{HttpIngress, IngressConfig};
+pub use inspect::{IngressInspector, RequestParts, ResponseParts, TrafficInspector};
 pub use pool::{ConnectionPool, PoolConfig};
-pub use proxy::HttpProxy;
-pub use tcp_ingress::{TcpIngress, TcpIngressConfig};
+pub use priority::PriorityPolicy;
+pub use proxy::{FallbackResponse, ForwardingConfig, HttpProxy};
+pub use tcp_ingress::{TcpAuth, TcpIngress, TcpIngressConfig};
+pub use trace_context::TraceParent;
+pub use udp_ingress::{UdpIngress, UdpIngressConfig};
+pub use websocket::WebSocketLimits;
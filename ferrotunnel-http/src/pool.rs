@@ -2,15 +2,19 @@
 //!
 //! This module provides connection reuse to avoid TCP handshake and HTTP protocol overhead.
 //! HTTP/1.1 connections are pooled in a LIFO queue, while HTTP/2 uses a single multiplexed connection.
+//! With [`PoolConfig::max_connections`] set, requests beyond the HTTP/1.1 connection cap queue
+//! in arrival order until a connection is released or [`PoolConfig::acquire_timeout`] elapses.
 
+use crate::dns::{DnsCache, DEFAULT_DNS_CACHE_TTL};
+use futures::FutureExt;
 use hyper::client::conn::{http1, http2};
 use hyper_util::rt::TokioIo;
 use std::collections::VecDeque;
-use std::sync::Arc;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 use thiserror::Error;
-use tokio::net::TcpStream;
-use tokio::sync::Mutex;
+use tokio::sync::{oneshot, Mutex, OwnedSemaphorePermit, Semaphore};
 use tracing::debug;
 
 /// Connection pool configuration
@@ -22,6 +26,35 @@ pub struct PoolConfig {
     pub idle_timeout: Duration,
     /// Prefer HTTP/2 when available (default: false)
     pub prefer_h2: bool,
+    /// Maximum time a connection may sit idle before it is discarded instead
+    /// of reused (default: `None`, falls back to `idle_timeout`).
+    ///
+    /// Set this below the local service's keep-alive timeout so the pool never
+    /// hands out a socket the service is about to close.
+    pub max_idle_time: Option<Duration>,
+    /// Probe pooled connections for liveness before reusing them (default: true)
+    pub health_check_on_acquire: bool,
+    /// How long resolved addresses of a hostname target are reused before
+    /// resolving again (default: 30s). `Duration::ZERO` resolves on every
+    /// new connection.
+    pub dns_cache_ttl: Duration,
+    /// Maximum HTTP/1.1 connections open to the local service at once
+    /// (default: `None`, unbounded).
+    ///
+    /// Once the cap is reached, further requests wait for a connection to be
+    /// released, served in arrival order. Zero is not allowed, since no
+    /// request could ever be served.
+    pub max_connections: Option<NonZeroUsize>,
+    /// How long a request waits for a connection while `max_connections` is
+    /// reached before failing with [`ConnectionPoolError::PoolFull`] (default: 5s)
+    pub acquire_timeout: Duration,
+}
+
+impl PoolConfig {
+    /// Idle duration after which a pooled connection is no longer reused
+    pub fn idle_threshold(&self) -> Duration {
+        self.max_idle_time.unwrap_or(self.idle_timeout)
+    }
 }
 
 impl Default for PoolConfig {
@@ -30,15 +63,31 @@ impl Default for PoolConfig {
             max_idle_per_host: 32,
             idle_timeout: Duration::from_secs(90),
             prefer_h2: false,
+            max_idle_time: None,
+            health_check_on_acquire: true,
+            dns_cache_ttl: DEFAULT_DNS_CACHE_TTL,
+            max_connections: None,
+            acquire_timeout: DEFAULT_ACQUIRE_TIMEOUT,
         }
     }
 }
 
+/// Default time a request waits for a connection when the pool is at capacity
+const DEFAULT_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Upper bound on the interval between background eviction passes
+const MAX_EVICTION_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Lower bound on the interval between background eviction passes
+const MIN_EVICTION_INTERVAL: Duration = Duration::from_millis(100);
+
 /// Connection pool errors
 #[derive(Debug, Error)]
 pub enum ConnectionPoolError {
     #[error("Connection error: {0}")]
     Connection(String),
+    #[error("Connection timed out: {0}")]
+    Timeout(String),
     #[error("Handshake error: {0}")]
     Handshake(String),
     #[error("Pool is full")]
@@ -47,20 +96,43 @@ pub enum ConnectionPoolError {
     NoConnection,
 }
 
+/// Classify a failed TCP connect to the local service
+fn connect_error(err: &std::io::Error) -> ConnectionPoolError {
+    if err.kind() == std::io::ErrorKind::TimedOut {
+        ConnectionPoolError::Timeout(err.to_string())
+    } else {
+        ConnectionPoolError::Connection(err.to_string())
+    }
+}
+
 /// Pooled HTTP/1.1 connection with metadata
 struct PooledH1Connection {
     sender: http1::SendRequest<BoxBody>,
     last_used: Instant,
 }
 
+/// Outcome of waiting for room under the HTTP/1.1 connection cap
+enum H1Slot {
+    /// A connection released by another request, ready for use
+    Released(http1::SendRequest<BoxBody>),
+    /// A free slot for opening a new connection
+    Free(OwnedSemaphorePermit),
+}
+
 /// Connection pool for HTTP/1.1 and HTTP/2
 pub struct ConnectionPool {
     target_addr: String,
     config: PoolConfig,
+    /// Resolved addresses of `target_addr`, shared by HTTP/1.1 and HTTP/2
+    dns: DnsCache,
     /// HTTP/1.1 idle connections (LIFO for cache warmth)
     h1_pool: Arc<Mutex<VecDeque<PooledH1Connection>>>,
     /// HTTP/2 multiplexed connection (shared across all requests)
     h2_connection: Arc<Mutex<Option<http2::SendRequest<BoxBody>>>>,
+    /// Slots for open HTTP/1.1 connections when `max_connections` is set
+    h1_limit: Option<Arc<Semaphore>>,
+    /// Requests waiting for a released HTTP/1.1 connection, oldest first
+    h1_waiters: Mutex<VecDeque<oneshot::Sender<http1::SendRequest<BoxBody>>>>,
 }
 
 /// Boxed body type used for both HTTP/1.1 and HTTP/2 connections.
@@ -72,53 +144,170 @@ impl ConnectionPool {
     /// Create a new connection pool
     pub fn new(target_addr: String, config: PoolConfig) -> Self {
         let pool = Self {
+            dns: DnsCache::new(target_addr.clone(), config.dns_cache_ttl),
             target_addr,
+            h1_limit: config
+                .max_connections
+                .map(|max| Arc::new(Semaphore::new(max.get().min(Semaphore::MAX_PERMITS)))),
             config,
             h1_pool: Arc::new(Mutex::new(VecDeque::new())),
             h2_connection: Arc::new(Mutex::new(None)),
+            h1_waiters: Mutex::new(VecDeque::new()),
         };
 
-        // Spawn background eviction task only if we're in a tokio runtime
+        // Spawn background eviction task only if we're in a tokio runtime.
+        // The task holds a weak reference so it stops once the pool is dropped.
         if tokio::runtime::Handle::try_current().is_ok() {
-            let eviction_pool = pool.h1_pool.clone();
-            let eviction_timeout = pool.config.idle_timeout;
-            tokio::spawn(async move {
-                loop {
-                    tokio::time::sleep(Duration::from_secs(30)).await;
-                    Self::evict_expired_internal(eviction_pool.clone(), eviction_timeout).await;
-                }
-            });
+            let eviction_pool = Arc::downgrade(&pool.h1_pool);
+            let threshold = pool.config.idle_threshold();
+            let interval = (threshold / 2).clamp(MIN_EVICTION_INTERVAL, MAX_EVICTION_INTERVAL);
+            tokio::spawn(Self::eviction_loop(eviction_pool, threshold, interval));
         }
 
         pool
     }
 
+    /// Periodically reap expired idle connections until the pool is dropped
+    async fn eviction_loop(
+        pool: Weak<Mutex<VecDeque<PooledH1Connection>>>,
+        threshold: Duration,
+        interval: Duration,
+    ) {
+        loop {
+            tokio::time::sleep(interval).await;
+            let Some(pool) = pool.upgrade() else {
+                break;
+            };
+            Self::evict_expired_internal(pool, threshold).await;
+        }
+    }
+
+    /// Address of the local service this pool connects to
+    pub fn target_addr(&self) -> &str {
+        &self.target_addr
+    }
+
     /// Acquire an HTTP/1.1 connection from the pool or create a new one
+    ///
+    /// Idle connections that have expired, been closed by the peer or fail the
+    /// liveness probe are discarded; when none are usable a fresh connection is
+    /// opened, so a burst of requests never fails just because the pool is empty.
+    ///
+    /// When `max_connections` connections are already open, waits in line for
+    /// one to be released or closed and fails with
+    /// [`ConnectionPoolError::PoolFull`] after `acquire_timeout`.
     pub async fn acquire_h1(&self) -> Result<http1::SendRequest<BoxBody>, ConnectionPoolError> {
+        if let Some(sender) = self.take_idle_h1().await {
+            return Ok(sender);
+        }
+
+        let permit = match &self.h1_limit {
+            None => None,
+            Some(limit) => match limit.clone().try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) => match self.wait_for_h1(limit).await? {
+                    H1Slot::Released(sender) => return Ok(sender),
+                    H1Slot::Free(permit) => Some(permit),
+                },
+            },
+        };
+
+        self.connect_h1(permit).await
+    }
+
+    /// Pop the most recently used idle connection that is still usable
+    async fn take_idle_h1(&self) -> Option<http1::SendRequest<BoxBody>> {
+        let threshold = self.config.idle_threshold();
+
         // Try to reuse an idle connection
         loop {
-            let mut pool = self.h1_pool.lock().await;
-
-            if let Some(mut conn) = pool.pop_back() {
-                // Check if connection is still valid
-                if !conn.sender.is_closed() && conn.last_used.elapsed() < self.config.idle_timeout {
-                    debug!("Reusing HTTP/1.1 connection from pool");
-                    conn.last_used = Instant::now();
-                    return Ok(conn.sender);
-                }
-                // Connection expired or closed, try next one
+            // Release the lock before probing so concurrent acquirers are not serialized
+            let Some(mut conn) = self.h1_pool.lock().await.pop_back() else {
+                break;
+            };
+
+            if conn.sender.is_closed() || conn.last_used.elapsed() >= threshold {
                 debug!("Discarding expired/closed HTTP/1.1 connection");
                 continue;
             }
+            if self.config.health_check_on_acquire && !Self::is_live(&mut conn.sender).await {
+                debug!("Discarding HTTP/1.1 connection that failed liveness probe");
+                continue;
+            }
+
+            debug!("Reusing HTTP/1.1 connection from pool");
+            return Some(conn.sender);
+        }
+        None
+    }
+
+    /// Wait in line for a released connection or a free slot under the cap
+    async fn wait_for_h1(&self, limit: &Arc<Semaphore>) -> Result<H1Slot, ConnectionPoolError> {
+        let deadline = tokio::time::Instant::now() + self.config.acquire_timeout;
+        debug!("HTTP/1.1 connection limit reached, waiting for a free connection");
+
+        // Later passes only happen when the connection handed over was dead,
+        // so the request goes back to the head of the line, not the end
+        let mut keep_place = false;
+        loop {
+            let (tx, mut rx) = oneshot::channel();
+            {
+                let mut waiters = self.h1_waiters.lock().await;
+                waiters.retain(|waiter| !waiter.is_closed());
+                if keep_place {
+                    waiters.push_front(tx);
+                } else {
+                    waiters.push_back(tx);
+                }
+            }
+            keep_place = true;
+
+            let released = tokio::select! {
+                released = &mut rx => released.ok(),
+                permit = limit.clone().acquire_owned() => {
+                    // A connection may have been handed over at the same time
+                    rx.close();
+                    match (rx.try_recv(), permit) {
+                        (Ok(sender), _) => Some(sender),
+                        (Err(_), Ok(permit)) => return Ok(H1Slot::Free(permit)),
+                        (Err(_), Err(_)) => return Err(ConnectionPoolError::PoolFull),
+                    }
+                }
+                () = tokio::time::sleep_until(deadline) => {
+                    rx.close();
+                    match rx.try_recv() {
+                        Ok(sender) => Some(sender),
+                        Err(_) => {
+                            debug!("Timed out waiting for an HTTP/1.1 connection");
+                            return Err(ConnectionPoolError::PoolFull);
+                        }
+                    }
+                }
+            };
 
-            // No valid connection in pool, create a new one
-            break;
+            let Some(mut sender) = released else {
+                continue;
+            };
+            // The previous request may still be reading its response body
+            match tokio::time::timeout_at(deadline, sender.ready()).await {
+                Ok(Ok(())) => return Ok(H1Slot::Released(sender)),
+                Ok(Err(_)) => debug!("Released HTTP/1.1 connection closed before reuse"),
+                Err(_) => return Err(ConnectionPoolError::PoolFull),
+            }
         }
+    }
 
+    /// Open a new HTTP/1.1 connection, holding `permit` until it closes
+    async fn connect_h1(
+        &self,
+        permit: Option<OwnedSemaphorePermit>,
+    ) -> Result<http1::SendRequest<BoxBody>, ConnectionPoolError> {
         debug!("Creating new HTTP/1.1 connection to {}", self.target_addr);
-        let stream = TcpStream::connect(&self.target_addr)
+        let stream = self
+            .dns
+            .connect()
             .await
-            .map_err(|e| ConnectionPoolError::Connection(e.to_string()))?;
+            .map_err(|err| connect_error(&err))?;
 
         ferrotunnel_core::transport::socket_tuning::configure_socket_silent(&stream);
         let io = TokioIo::new(stream);
@@ -132,19 +321,45 @@ impl ConnectionPool {
             if let Err(e) = conn.with_upgrades().await {
                 debug!("HTTP/1.1 connection error: {:?}", e);
             }
+            // Free the slot for requests waiting on the connection cap
+            drop(permit);
         });
 
         Ok(sender)
     }
 
+    /// Lightweight liveness probe for an idle HTTP/1.1 connection
+    ///
+    /// Yields once so the connection driver can observe a pending FIN/RST, then
+    /// checks that the sender is immediately ready without waiting on the socket.
+    async fn is_live(sender: &mut http1::SendRequest<BoxBody>) -> bool {
+        tokio::task::yield_now().await;
+        matches!(sender.ready().now_or_never(), Some(Ok(()))) && !sender.is_closed()
+    }
+
     /// Release an HTTP/1.1 connection back to the pool
-    pub async fn release_h1(&self, sender: http1::SendRequest<BoxBody>) {
+    ///
+    /// Requests waiting on the connection cap are served first, oldest first.
+    pub async fn release_h1(&self, mut sender: http1::SendRequest<BoxBody>) {
         // Don't return closed connections to the pool
         if sender.is_closed() {
             debug!("Not returning closed connection to pool");
             return;
         }
 
+        let mut waiters = self.h1_waiters.lock().await;
+        while let Some(waiter) = waiters.pop_front() {
+            match waiter.send(sender) {
+                Ok(()) => {
+                    debug!("Handed released HTTP/1.1 connection to a waiting request");
+                    return;
+                }
+                // That request already gave up
+                Err(returned) => sender = returned,
+            }
+        }
+        drop(waiters);
+
         let mut pool = self.h1_pool.lock().await;
 
         // Enforce per-host limit
@@ -178,9 +393,11 @@ impl ConnectionPool {
 
         // Create new HTTP/2 connection
         debug!("Creating new HTTP/2 connection to {}", self.target_addr);
-        let stream = TcpStream::connect(&self.target_addr)
+        let stream = self
+            .dns
+            .connect()
             .await
-            .map_err(|e| ConnectionPoolError::Connection(e.to_string()))?;
+            .map_err(|err| connect_error(&err))?;
 
         ferrotunnel_core::transport::socket_tuning::configure_socket_silent(&stream);
         let io = TokioIo::new(stream);
@@ -202,7 +419,7 @@ impl ConnectionPool {
 
     /// Evict expired connections from the pool
     pub async fn evict_expired(&self) {
-        Self::evict_expired_internal(self.h1_pool.clone(), self.config.idle_timeout).await;
+        Self::evict_expired_internal(self.h1_pool.clone(), self.config.idle_threshold()).await;
     }
 
     async fn evict_expired_internal(
@@ -224,6 +441,10 @@ impl ConnectionPool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use http_body_util::BodyExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
 
     #[test]
     fn test_pool_config_default() {
@@ -231,6 +452,9 @@ mod tests {
         assert_eq!(config.max_idle_per_host, 32);
         assert_eq!(config.idle_timeout, Duration::from_secs(90));
         assert!(!config.prefer_h2);
+        assert_eq!(config.dns_cache_ttl, Duration::from_secs(30));
+        assert_eq!(config.max_connections, None);
+        assert_eq!(config.acquire_timeout, Duration::from_secs(5));
     }
 
     #[test]
@@ -246,9 +470,288 @@ mod tests {
             max_idle_per_host: 10,
             idle_timeout: Duration::from_secs(60),
             prefer_h2: true,
+            max_idle_time: Some(Duration::from_secs(5)),
+            health_check_on_acquire: false,
+            dns_cache_ttl: Duration::ZERO,
+            max_connections: NonZeroUsize::new(4),
+            acquire_timeout: Duration::from_secs(1),
         };
         assert_eq!(config.max_idle_per_host, 10);
         assert_eq!(config.idle_timeout, Duration::from_secs(60));
         assert!(config.prefer_h2);
+        assert_eq!(config.idle_threshold(), Duration::from_secs(5));
+        assert!(!config.health_check_on_acquire);
+        assert_eq!(config.max_connections, NonZeroUsize::new(4));
+    }
+
+    /// Start a keep-alive HTTP/1.1 server that answers each request with `ok`.
+    ///
+    /// When `close_after` is set, each connection is closed that long after its
+    /// first response, mimicking a service with a short keep-alive timeout.
+    /// Returns the address and a counter of accepted connections.
+    async fn start_server(close_after: Option<Duration>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    loop {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(_) => {}
+                        }
+                        let response = b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok";
+                        if socket.write_all(response).await.is_err() {
+                            return;
+                        }
+                        if let Some(delay) = close_after {
+                            tokio::time::sleep(delay).await;
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (addr, accepted)
+    }
+
+    fn empty_body() -> BoxBody {
+        http_body_util::Empty::<bytes::Bytes>::new()
+            .map_err(|never| match never {})
+            .boxed()
+    }
+
+    /// Send one GET over a pooled connection and return it to the pool
+    async fn round_trip(pool: &ConnectionPool) {
+        let mut sender = pool.acquire_h1().await.unwrap();
+        send_ok(&mut sender).await;
+        pool.release_h1(sender).await;
+    }
+
+    /// Send a request over `sender` and read its `ok` response; the server
+    /// has accepted the connection once this returns
+    async fn send_ok(sender: &mut http1::SendRequest<BoxBody>) {
+        let request = hyper::Request::get("/")
+            .header(hyper::header::HOST, "localhost")
+            .body(empty_body())
+            .unwrap();
+        let response = sender.send_request(request).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        response.into_body().collect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reuses_live_connection() {
+        let (addr, accepted) = start_server(None).await;
+        let pool = ConnectionPool::new(addr, PoolConfig::default());
+
+        round_trip(&pool).await;
+        round_trip(&pool).await;
+
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_after_server_closes_idle_connection() {
+        let (addr, accepted) = start_server(Some(Duration::from_millis(20))).await;
+        let pool = ConnectionPool::new(addr, PoolConfig::default());
+
+        round_trip(&pool).await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        round_trip(&pool).await;
+
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_discards_connection_past_max_idle_time() {
+        let (addr, accepted) = start_server(None).await;
+        let config = PoolConfig {
+            max_idle_time: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let pool = ConnectionPool::new(addr, config);
+
+        round_trip(&pool).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        round_trip(&pool).await;
+
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_background_eviction_reaps_idle_connections() {
+        let (addr, _accepted) = start_server(None).await;
+        let config = PoolConfig {
+            max_idle_time: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let pool = ConnectionPool::new(addr, config);
+
+        round_trip(&pool).await;
+        assert_eq!(pool.h1_pool.lock().await.len(), 1);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(pool.h1_pool.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_burst_opens_fresh_connections() {
+        let (addr, accepted) = start_server(None).await;
+        let pool = ConnectionPool::new(addr, PoolConfig::default());
+
+        // Each request of the burst holds its connection, so none can be reused
+        let mut burst = Vec::new();
+        for _ in 0..8 {
+            let mut sender = pool.acquire_h1().await.unwrap();
+            send_ok(&mut sender).await;
+            burst.push(sender);
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 8);
+
+        for sender in burst {
+            pool.release_h1(sender).await;
+        }
+        assert_eq!(pool.h1_pool.lock().await.len(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_burst_over_the_cap_reuses_connections() {
+        let (addr, accepted) = start_server(None).await;
+        let pool = Arc::new(ConnectionPool::new(
+            addr,
+            limited_config(3, Duration::from_secs(5)),
+        ));
+
+        let mut held = Vec::new();
+        for _ in 0..3 {
+            let mut sender = pool.acquire_h1().await.unwrap();
+            send_ok(&mut sender).await;
+            held.push(sender);
+        }
+        // The rest of the burst queues for the three open connections
+        let queued: Vec<_> = (0..5)
+            .map(|_| {
+                let pool = pool.clone();
+                tokio::spawn(async move { round_trip(&pool).await })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(queued.iter().all(|handle| !handle.is_finished()));
+
+        for sender in held {
+            pool.release_h1(sender).await;
+        }
+        for handle in queued {
+            handle.await.unwrap();
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_hostname_target() {
+        let (addr, accepted) = start_server(None).await;
+        let port = addr.rsplit(':').next().unwrap();
+        // `localhost` may also resolve to `::1`, where nothing listens, so
+        // this covers falling back to another address of the hostname
+        let pool = ConnectionPool::new(format!("localhost:{port}"), PoolConfig::default());
+
+        round_trip(&pool).await;
+        round_trip(&pool).await;
+
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    }
+
+    fn limited_config(max_connections: usize, acquire_timeout: Duration) -> PoolConfig {
+        PoolConfig {
+            max_connections: NonZeroUsize::new(max_connections),
+            acquire_timeout,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queued_acquires_get_released_connections_in_order() {
+        let (addr, accepted) = start_server(None).await;
+        let pool = Arc::new(ConnectionPool::new(
+            addr,
+            limited_config(2, Duration::from_secs(5)),
+        ));
+        let first = pool.acquire_h1().await.unwrap();
+        let second = pool.acquire_h1().await.unwrap();
+
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let mut waiters = Vec::new();
+        for id in 0..2 {
+            let pool = pool.clone();
+            let served = served.clone();
+            waiters.push(tokio::spawn(async move {
+                let sender = pool.acquire_h1().await.unwrap();
+                served.lock().await.push(id);
+                sender
+            }));
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(served.lock().await.is_empty());
+
+        pool.release_h1(first).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(*served.lock().await, vec![0]);
+
+        pool.release_h1(second).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(*served.lock().await, vec![0, 1]);
+
+        for waiter in waiters {
+            let sender = waiter.await.unwrap();
+            pool.release_h1(sender).await;
+            round_trip(&pool).await;
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_times_out_when_saturated() {
+        let (addr, accepted) = start_server(None).await;
+        let pool = ConnectionPool::new(addr, limited_config(1, Duration::from_millis(100)));
+        let held = pool.acquire_h1().await.unwrap();
+
+        let started = Instant::now();
+        let result = pool.acquire_h1().await;
+        assert!(matches!(result, Err(ConnectionPoolError::PoolFull)));
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        // The timed out request no longer claims the released connection
+        pool.release_h1(held).await;
+        round_trip(&pool).await;
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_connection_frees_slot() {
+        let (addr, accepted) = start_server(None).await;
+        let pool = Arc::new(ConnectionPool::new(
+            addr,
+            limited_config(1, Duration::from_secs(5)),
+        ));
+        let held = pool.acquire_h1().await.unwrap();
+
+        let waiter = {
+            let pool = pool.clone();
+            tokio::spawn(async move { round_trip(&pool).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(held);
+
+        tokio::time::timeout(Duration::from_secs(2), waiter)
+            .await
+            .expect("waiter was not given the freed slot")
+            .unwrap();
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
     }
 }
@@ -0,0 +1,243 @@
+//! Stream priority policy for the HTTP ingress
+//!
+//! The multiplexer drains higher-priority streams first when the tunnel is
+//! busy. [`PriorityPolicy`] picks the priority of each request's stream from
+//! the request itself, so small interactive requests are not queued behind
+//! large uploads on the same tunnel.
+
+use ferrotunnel_protocol::frame::StreamPriority;
+use hyper::body::Body;
+use hyper::header::HeaderMap;
+
+/// Maps request attributes to the priority of the tunnel stream opened for it
+///
+/// Rules are checked in order: the trusted header, then small requests, then
+/// large uploads; requests matching none get `default_priority`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PriorityPolicy {
+    /// Trusted request header naming the priority (`low`, `normal` or
+    /// `high`), e.g. `X-Tunnel-Priority` (default: none)
+    ///
+    /// Any client can send this header, so only set it when a proxy in front
+    /// of the ingress strips or sets it. It is removed before forwarding and
+    /// unknown values are ignored.
+    pub header: Option<String>,
+    /// Requests whose body is at most this many bytes, including requests
+    /// without a body, get `small_request_priority` (default: 1024)
+    pub small_request_max_bytes: Option<u64>,
+    /// Priority of small requests (default: high)
+    pub small_request_priority: StreamPriority,
+    /// Requests whose body is at least this many bytes, or streams without a
+    /// known length (chunked HTTP/1.1, h2 and gRPC uploads), get
+    /// `large_request_priority` (default: 1MB)
+    pub large_request_min_bytes: Option<u64>,
+    /// Priority of large uploads (default: low)
+    pub large_request_priority: StreamPriority,
+    /// Priority of all other requests (default: normal)
+    pub default_priority: StreamPriority,
+}
+
+impl Default for PriorityPolicy {
+    fn default() -> Self {
+        Self {
+            header: None,
+            small_request_max_bytes: Some(1024),
+            small_request_priority: StreamPriority::High,
+            large_request_min_bytes: Some(1024 * 1024),
+            large_request_priority: StreamPriority::Low,
+            default_priority: StreamPriority::Normal,
+        }
+    }
+}
+
+impl PriorityPolicy {
+    /// Trust `name` to carry a per-request priority
+    #[must_use]
+    pub fn with_header(mut self, name: impl Into<String>) -> Self {
+        self.header = Some(name.into());
+        self
+    }
+
+    /// Treat requests with bodies up to `max_bytes` as small; `None` disables
+    #[must_use]
+    pub fn with_small_request_max_bytes(mut self, max_bytes: Option<u64>) -> Self {
+        self.small_request_max_bytes = max_bytes;
+        self
+    }
+
+    /// Treat bodies from `min_bytes` on as large uploads; `None` disables
+    #[must_use]
+    pub fn with_large_request_min_bytes(mut self, min_bytes: Option<u64>) -> Self {
+        self.large_request_min_bytes = min_bytes;
+        self
+    }
+
+    /// Priority for the stream of a request with `headers` and `body`
+    ///
+    /// The body size comes from the body's own size hint, which hyper sets
+    /// from `Content-Length` and the end of stream flag, so h2 requests
+    /// without a length are not mistaken for empty ones.
+    pub fn resolve<B: Body>(&self, headers: &HeaderMap, body: &B) -> StreamPriority {
+        if let Some(priority) = self
+            .header
+            .as_deref()
+            .and_then(|name| headers.get(name))
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_priority)
+        {
+            return priority;
+        }
+
+        match body_size(body) {
+            BodySize::Known(len) => {
+                if self.small_request_max_bytes.is_some_and(|max| len <= max) {
+                    self.small_request_priority
+                } else if self.large_request_min_bytes.is_some_and(|min| len >= min) {
+                    self.large_request_priority
+                } else {
+                    self.default_priority
+                }
+            }
+            BodySize::Streaming if self.large_request_min_bytes.is_some() => {
+                self.large_request_priority
+            }
+            BodySize::Streaming => self.default_priority,
+        }
+    }
+}
+
+/// Priority named by a header value; `critical` is reserved for control
+/// frames such as heartbeats
+fn parse_priority(value: &str) -> Option<StreamPriority> {
+    let value = value.trim();
+    [
+        ("low", StreamPriority::Low),
+        ("normal", StreamPriority::Normal),
+        ("high", StreamPriority::High),
+    ]
+    .into_iter()
+    .find_map(|(name, priority)| value.eq_ignore_ascii_case(name).then_some(priority))
+}
+
+enum BodySize {
+    Known(u64),
+    /// Body of unknown length
+    Streaming,
+}
+
+fn body_size<B: Body>(body: &B) -> BodySize {
+    if body.is_end_stream() {
+        return BodySize::Known(0);
+    }
+    match body.size_hint().exact() {
+        Some(len) => BodySize::Known(len),
+        None => BodySize::Streaming,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::{Empty, Full};
+    use hyper::body::{Bytes, Frame, SizeHint};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    /// Body streamed without a known length, like an h2 upload
+    struct Streaming;
+
+    impl Body for Streaming {
+        type Data = Bytes;
+        type Error = std::convert::Infallible;
+
+        fn poll_frame(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+            Poll::Ready(None)
+        }
+
+        fn size_hint(&self) -> SizeHint {
+            SizeHint::default()
+        }
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn sized(len: usize) -> Full<Bytes> {
+        Full::new(Bytes::from(vec![0; len]))
+    }
+
+    #[test]
+    fn test_size_rules() {
+        let policy = PriorityPolicy::default();
+        let none = HeaderMap::new();
+        assert_eq!(
+            policy.resolve(&none, &Empty::<Bytes>::new()),
+            StreamPriority::High
+        );
+        assert_eq!(policy.resolve(&none, &sized(512)), StreamPriority::High);
+        assert_eq!(
+            policy.resolve(&none, &sized(65_536)),
+            StreamPriority::Normal
+        );
+        assert_eq!(
+            policy.resolve(&none, &sized(1_048_576)),
+            StreamPriority::Low
+        );
+        assert_eq!(policy.resolve(&none, &Streaming), StreamPriority::Low);
+    }
+
+    #[test]
+    fn test_streaming_without_headers_is_not_small() {
+        // h2 and gRPC uploads carry neither Content-Length nor
+        // Transfer-Encoding; only the body knows they are still open
+        let policy = PriorityPolicy::default().with_large_request_min_bytes(None);
+        assert_eq!(
+            policy.resolve(&HeaderMap::new(), &Streaming),
+            StreamPriority::Normal
+        );
+    }
+
+    #[test]
+    fn test_disabled_rules_use_default() {
+        let policy = PriorityPolicy::default()
+            .with_small_request_max_bytes(None)
+            .with_large_request_min_bytes(None);
+        let none = HeaderMap::new();
+        assert_eq!(
+            policy.resolve(&none, &Empty::<Bytes>::new()),
+            StreamPriority::Normal
+        );
+        assert_eq!(policy.resolve(&none, &Streaming), StreamPriority::Normal);
+    }
+
+    #[test]
+    fn test_header_overrides_size() {
+        let policy = PriorityPolicy::default().with_header("X-Tunnel-Priority");
+        let empty = Empty::<Bytes>::new();
+        let upload = headers(&[("x-tunnel-priority", "High")]);
+        assert_eq!(
+            policy.resolve(&upload, &sized(5_000_000)),
+            StreamPriority::High
+        );
+        let small = headers(&[("x-tunnel-priority", " low ")]);
+        assert_eq!(policy.resolve(&small, &empty), StreamPriority::Low);
+
+        // Critical and unknown values fall through to the size rules
+        let critical = headers(&[("x-tunnel-priority", "critical")]);
+        assert_eq!(policy.resolve(&critical, &empty), StreamPriority::High);
+        let unknown = headers(&[("x-tunnel-priority", "urgent")]);
+        assert_eq!(policy.resolve(&unknown, &empty), StreamPriority::High);
+
+        // Untrusted without a configured header
+        let policy = PriorityPolicy::default();
+        assert_eq!(policy.resolve(&small, &empty), StreamPriority::High);
+    }
+}
@@ -1,77 +1,361 @@
 use bytes::Bytes;
-use ferrotunnel_core::stream::VirtualStream;
-use http_body_util::{BodyExt, Full};
+use ferrotunnel_core::stream::{StreamAbortHandle, VirtualStream};
+use ferrotunnel_protocol::frame::ResetCode;
+use http_body_util::{BodyExt, Full, LengthLimitError};
 use hyper::body::Incoming;
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_TYPE, HOST, RETRY_AFTER};
 use hyper::server::conn::{http1, http2};
 use hyper::{Request, Response, StatusCode};
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use hyper_util::service::TowerToHyperService;
 use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use thiserror::Error;
 use tower::{Layer, Service};
 
-use crate::pool::{ConnectionPool, PoolConfig};
-#[derive(Debug)]
+use crate::inspect::TrafficInspector;
+use crate::pool::{ConnectionPool, ConnectionPoolError, PoolConfig};
+use crate::websocket::{copy_websocket, WebSocketLimits};
+
+/// Errors on the path from the tunnel to the local service
+#[derive(Debug, Error)]
 pub enum ProxyError {
-    Hyper(hyper::Error),
+    /// hyper failed while streaming a request or response body
+    #[error("Hyper error: {0}")]
+    Hyper(#[from] hyper::Error),
+    /// The local service refused or could not be reached
+    #[error("Failed to connect to local service at {addr}: {reason}")]
+    ConnectFailed { addr: String, reason: String },
+    /// Connecting to or waiting on the local service timed out
+    #[error("Local service at {addr} timed out")]
+    UpstreamTimeout { addr: String },
+    /// The local service broke the HTTP exchange (bad handshake, reset,
+    /// malformed response)
+    #[error("Upstream protocol error from {addr}: {reason}")]
+    UpstreamProtocol { addr: String, reason: String },
+    /// No connection to the local service could be taken from the pool
+    #[error("Connection pool exhausted for {addr}")]
+    PoolExhausted { addr: String },
+    /// The request body exceeded a configured size limit
+    #[error("Request body too large: {reason}")]
+    BodyTooLarge { reason: String },
+    /// Any other failure
+    #[error("Proxy error: {0}")]
     Custom(String),
 }
 
-impl std::fmt::Display for ProxyError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl ProxyError {
+    /// HTTP status returned to the client for this error
+    pub fn status(&self) -> StatusCode {
         match self {
-            ProxyError::Hyper(e) => write!(f, "Hyper error: {e}"),
-            ProxyError::Custom(s) => write!(f, "Proxy error: {s}"),
+            Self::UpstreamTimeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            Self::PoolExhausted { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            Self::BodyTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::Hyper(_)
+            | Self::ConnectFailed { .. }
+            | Self::UpstreamProtocol { .. }
+            | Self::Custom(_) => StatusCode::BAD_GATEWAY,
         }
     }
-}
 
-impl std::error::Error for ProxyError {}
+    /// Body shown to the public client for this error
+    ///
+    /// Never includes the local service's address or the underlying error,
+    /// which are only logged.
+    pub fn public_message(&self) -> &'static str {
+        match self {
+            Self::ConnectFailed { .. } => "Failed to connect to local service",
+            Self::UpstreamTimeout { .. } => "Local service timed out",
+            Self::PoolExhausted { .. } => "Local service is busy",
+            Self::BodyTooLarge { .. } => "Request body too large",
+            Self::Hyper(_) | Self::UpstreamProtocol { .. } | Self::Custom(_) => "Proxy error",
+        }
+    }
 
-impl From<hyper::Error> for ProxyError {
-    fn from(e: hyper::Error) -> Self {
-        ProxyError::Hyper(e)
+    /// Map a failure to get a connection to the local service at `addr`
+    fn from_pool(err: ConnectionPoolError, addr: &str) -> Self {
+        let addr = addr.to_string();
+        match err {
+            ConnectionPoolError::Connection(reason) => Self::ConnectFailed { addr, reason },
+            ConnectionPoolError::Timeout(_) => Self::UpstreamTimeout { addr },
+            ConnectionPoolError::Handshake(reason) => Self::UpstreamProtocol { addr, reason },
+            ConnectionPoolError::PoolFull | ConnectionPoolError::NoConnection => {
+                Self::PoolExhausted { addr }
+            }
+        }
+    }
+
+    /// Map a failed request to the local service at `addr`
+    fn from_send(err: &hyper::Error, addr: &str) -> Self {
+        let addr = addr.to_string();
+        if is_body_limit_error(err) {
+            Self::BodyTooLarge {
+                reason: err.to_string(),
+            }
+        } else if err.is_timeout() {
+            Self::UpstreamTimeout { addr }
+        } else {
+            Self::UpstreamProtocol {
+                addr,
+                reason: err.to_string(),
+            }
+        }
     }
 }
 
 impl From<std::convert::Infallible> for ProxyError {
-    fn from(_: std::convert::Infallible) -> Self {
-        unreachable!()
+    fn from(never: std::convert::Infallible) -> Self {
+        match never {}
+    }
+}
+
+/// Whether a forwarding error was caused by the request body exceeding its limit
+pub(crate) fn is_body_limit_error(err: &hyper::Error) -> bool {
+    let mut source = std::error::Error::source(err);
+    while let Some(e) = source {
+        if e.is::<LengthLimitError>() {
+            return true;
+        }
+        source = e.source();
     }
+    false
 }
 
-use tracing::error;
+use tracing::{error, warn};
 
 type BoxBody = http_body_util::combinators::BoxBody<Bytes, ProxyError>;
 
+/// `OpenStream` metadata header carrying the original client's socket address
+pub const REMOTE_ADDR_HEADER: &str = "remote-addr";
+
+pub(crate) const X_FORWARDED_FOR: &str = "x-forwarded-for";
+pub(crate) const X_FORWARDED_HOST: &str = "x-forwarded-host";
+pub(crate) const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+
+/// Forwarding headers added to requests sent to the local service
+///
+/// Everything is disabled by default, so requests pass through unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct ForwardingConfig {
+    /// Append the original client IP to `X-Forwarded-For`, keeping any
+    /// existing chain; skipped when the chain already ends with it
+    pub forwarded_for: bool,
+    /// Set `X-Forwarded-Host` to the Host header the request arrived with
+    pub forwarded_host: bool,
+    /// Set `X-Forwarded-Proto` to this scheme (e.g. `"https"` behind a TLS ingress)
+    pub forwarded_proto: Option<String>,
+    /// Replace the outgoing `Host` header with this value
+    pub host_override: Option<String>,
+}
+
+impl ForwardingConfig {
+    fn is_enabled(&self) -> bool {
+        self.forwarded_for
+            || self.forwarded_host
+            || self.forwarded_proto.is_some()
+            || self.host_override.is_some()
+    }
+
+    /// Apply the configured headers; `client_ip` is the original client, if known
+    fn apply(&self, headers: &mut HeaderMap, client_ip: Option<IpAddr>) {
+        if let Some(ip) = client_ip.filter(|_| self.forwarded_for) {
+            let existing: Vec<&str> = headers
+                .get_all(X_FORWARDED_FOR)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(','))
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .collect();
+            let ip = ip.to_string();
+            // The ingress records the client as the last hop already
+            if existing.last() != Some(&ip.as_str()) {
+                let chain = if existing.is_empty() {
+                    ip
+                } else {
+                    format!("{}, {ip}", existing.join(", "))
+                };
+                if let Ok(value) = HeaderValue::from_str(&chain) {
+                    headers.insert(X_FORWARDED_FOR, value);
+                }
+            }
+        }
+        if self.forwarded_host {
+            if let Some(host) = headers.get(HOST).cloned() {
+                headers.insert(X_FORWARDED_HOST, host);
+            }
+        }
+        if let Some(proto) = &self.forwarded_proto {
+            if let Ok(value) = HeaderValue::from_str(proto) {
+                headers.insert(X_FORWARDED_PROTO, value);
+            }
+        }
+        if let Some(host) = &self.host_override {
+            if let Ok(value) = HeaderValue::from_str(host) {
+                headers.insert(HOST, value);
+            }
+        }
+    }
+}
+
+/// Response served instead of a 502 when the local service cannot be
+/// reached, e.g. a maintenance page while it restarts
+///
+/// Only connection failures are answered with it; timeouts and errors from a
+/// running service still get the plain error response.
+#[derive(Debug, Clone)]
+pub struct FallbackResponse {
+    status: StatusCode,
+    content_type: HeaderValue,
+    body: Bytes,
+    retry_after: Option<Duration>,
+}
+
+impl FallbackResponse {
+    pub fn new(status: StatusCode, content_type: HeaderValue, body: impl Into<Bytes>) -> Self {
+        Self {
+            status,
+            content_type,
+            body: body.into(),
+            retry_after: None,
+        }
+    }
+
+    /// A 503 `text/html; charset=utf-8` page
+    pub fn maintenance_page(body: impl Into<Bytes>) -> Self {
+        Self::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+            body,
+        )
+    }
+
+    /// Tell clients to retry after `delay` with a `Retry-After` header
+    #[must_use]
+    pub fn with_retry_after(mut self, delay: Duration) -> Self {
+        self.retry_after = Some(delay);
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    fn to_response(&self) -> Response<BoxBody> {
+        let mut builder = Response::builder()
+            .status(self.status)
+            .header(CONTENT_TYPE, self.content_type.clone());
+        if let Some(delay) = self.retry_after {
+            builder = builder.header(RETRY_AFTER, delay.as_secs());
+        }
+        builder
+            .body(
+                Full::new(self.body.clone())
+                    .map_err(|_| ProxyError::Custom("unreachable".into()))
+                    .boxed(),
+            )
+            .unwrap_or_else(|_| {
+                error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to build fallback response",
+                )
+            })
+    }
+}
+
+/// Response for a failed forward: the fallback if the local service could
+/// not be reached and one is configured, otherwise the plain error
+fn failure_response(err: &ProxyError, fallback: Option<&FallbackResponse>) -> Response<BoxBody> {
+    match (err, fallback) {
+        (ProxyError::ConnectFailed { .. }, Some(fallback)) => fallback.to_response(),
+        _ => proxy_error_response(err),
+    }
+}
+
 /// Service that forwards requests to a local TCP port.
 #[derive(Clone)]
 pub struct LocalProxyService {
     pool: Arc<ConnectionPool>,
     use_h2: bool,
+    forwarding: Arc<ForwardingConfig>,
+    client_ip: Option<IpAddr>,
+    inspector: Option<Arc<dyn TrafficInspector>>,
+    websocket: WebSocketLimits,
+    fallback: Option<Arc<FallbackResponse>>,
+    reset: Option<StreamAbortHandle>,
 }
 
 impl LocalProxyService {
     pub fn new(target_addr: String) -> Self {
         let pool = Arc::new(ConnectionPool::new(target_addr, PoolConfig::default()));
-        Self {
-            pool,
-            use_h2: false,
-        }
+        Self::with_pool(pool)
     }
 
     pub fn with_pool(pool: Arc<ConnectionPool>) -> Self {
         Self {
             pool,
             use_h2: false,
+            forwarding: Arc::new(ForwardingConfig::default()),
+            client_ip: None,
+            inspector: None,
+            websocket: WebSocketLimits::default(),
+            fallback: None,
+            reset: None,
         }
     }
 
     /// Create a service that uses HTTP/2 for forwarding (required for gRPC).
     pub fn with_pool_h2(pool: Arc<ConnectionPool>) -> Self {
-        Self { pool, use_h2: true }
+        Self {
+            use_h2: true,
+            ..Self::with_pool(pool)
+        }
+    }
+
+    /// Add forwarding headers for requests from `client_ip`
+    #[must_use]
+    pub fn with_forwarding(
+        mut self,
+        forwarding: Arc<ForwardingConfig>,
+        client_ip: Option<IpAddr>,
+    ) -> Self {
+        self.forwarding = forwarding;
+        self.client_ip = client_ip;
+        self
+    }
+
+    /// Report each forwarded request and its response to `inspector`
+    #[must_use]
+    pub fn with_inspector(mut self, inspector: Option<Arc<dyn TrafficInspector>>) -> Self {
+        self.inspector = inspector;
+        self
+    }
+
+    /// Apply `limits` to upgraded WebSocket connections
+    #[must_use]
+    pub fn with_websocket_limits(mut self, limits: WebSocketLimits) -> Self {
+        self.websocket = limits;
+        self
+    }
+
+    /// Answer with `fallback` when the local service cannot be reached
+    #[must_use]
+    pub fn with_fallback(mut self, fallback: Option<Arc<FallbackResponse>>) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Reset the tunnel stream through `handle` when a response body from
+    /// the local service fails part way, so the ingress does not take the
+    /// truncated body for a complete one
+    #[must_use]
+    pub fn with_reset_on_body_error(mut self, handle: Option<StreamAbortHandle>) -> Self {
+        self.reset = handle;
+        self
     }
 }
 
@@ -90,146 +374,224 @@ where
         Poll::Ready(Ok(()))
     }
 
-    #[allow(clippy::too_many_lines)]
     fn call(&mut self, mut req: Request<B>) -> Self::Future {
         let pool = self.pool.clone();
         let use_h2 = self.use_h2;
+        if self.forwarding.is_enabled() {
+            self.forwarding.apply(req.headers_mut(), self.client_ip);
+        }
+        let inspector = self.inspector.clone();
+        let websocket = self.websocket;
+        let fallback = self.fallback.clone();
+        let reset = self.reset.clone();
         Box::pin(async move {
-            // gRPC path: forward over HTTP/2, which preserves trailers
-            if use_h2 {
-                let req = req.map(|b| {
-                    b.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync + 'static>)
-                        .boxed()
-                });
-                let mut sender = match pool.acquire_h2().await {
-                    Ok(s) => s,
-                    Err(e) => {
-                        error!("Failed to acquire HTTP/2 connection from pool: {e}");
-                        return Ok(error_response(
-                            StatusCode::BAD_GATEWAY,
-                            &format!("Failed to connect to local service: {e}"),
-                        ));
-                    }
-                };
-                return match sender.send_request(req).await {
-                    Ok(res) => {
-                        let (parts, body) = res.into_parts();
-                        Ok(Response::from_parts(
-                            parts,
-                            body.map_err(Into::into).boxed(),
-                        ))
-                    }
-                    Err(e) => {
-                        error!("Failed to proxy gRPC request: {e}");
-                        Ok(error_response(StatusCode::BAD_GATEWAY, "Proxy error"))
-                    }
-                };
+            let Some(inspector) = inspector else {
+                let res = forward(pool, use_h2, websocket, req)
+                    .await
+                    .unwrap_or_else(|e| failure_response(&e, fallback.as_deref()));
+                return Ok(reset_on_body_error(res, reset));
+            };
+
+            let (parts, body) = req.into_parts();
+            inspector.on_request(&parts).await;
+            let start = Instant::now();
+            let res = forward(pool, use_h2, websocket, Request::from_parts(parts, body))
+                .await
+                .unwrap_or_else(|e| failure_response(&e, fallback.as_deref()));
+            let (parts, body) = res.into_parts();
+            inspector.on_response(&parts, start.elapsed()).await;
+            Ok(reset_on_body_error(
+                Response::from_parts(parts, body),
+                reset,
+            ))
+        })
+    }
+}
+
+/// Reset the tunnel stream if the response body fails part way
+///
+/// hyper would only drop the stream, which the ingress cannot tell from a
+/// body that ended; `ResetStream` makes its reads fail instead.
+fn reset_on_body_error(
+    res: Response<BoxBody>,
+    reset: Option<StreamAbortHandle>,
+) -> Response<BoxBody> {
+    let Some(reset) = reset else {
+        return res;
+    };
+    res.map(|body| {
+        body.map_err(move |err| {
+            if !reset.is_aborted() {
+                warn!("Response body from the local service failed: {err}");
+                reset.reset(ResetCode::LocalServiceFailed);
+            }
+            err
+        })
+        .boxed()
+    })
+}
+
+/// Forward a request to the local service over the pooled connection
+#[allow(clippy::too_many_lines)]
+async fn forward<B>(
+    pool: Arc<ConnectionPool>,
+    use_h2: bool,
+    websocket: WebSocketLimits,
+    mut req: Request<B>,
+) -> Result<Response<BoxBody>, ProxyError>
+where
+    B: Body<Data = Bytes> + Send + Sync + 'static,
+    B::Error: Into<ProxyError> + std::error::Error + Send + Sync + 'static,
+{
+    // gRPC path: forward over HTTP/2, which preserves trailers
+    if use_h2 {
+        let req = req.map(local_request_body);
+        let mut sender = match pool.acquire_h2().await {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to acquire HTTP/2 connection from pool: {e}");
+                return Err(ProxyError::from_pool(e, pool.target_addr()));
+            }
+        };
+        return match sender.send_request(req).await {
+            Ok(res) => {
+                let (parts, body) = res.into_parts();
+                Ok(Response::from_parts(
+                    parts,
+                    body.map_err(Into::into).boxed(),
+                ))
+            }
+            Err(e) => {
+                error!("Failed to proxy gRPC request: {e}");
+                Err(ProxyError::from_send(&e, pool.target_addr()))
             }
+        };
+    }
 
-            let is_upgrade = req
-                .headers()
-                .get(hyper::header::UPGRADE)
-                .and_then(|v| v.to_str().ok())
-                .is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
+    let is_upgrade = req
+        .headers()
+        .get(hyper::header::UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
 
-            let server_upgrade = if is_upgrade {
-                Some(hyper::upgrade::on(&mut req))
-            } else {
-                None
-            };
+    let server_upgrade = if is_upgrade {
+        Some(hyper::upgrade::on(&mut req))
+    } else {
+        None
+    };
 
-            // Try to acquire connection from pool
-            let mut sender = match pool.acquire_h1().await {
-                Ok(s) => s,
-                Err(e) => {
-                    error!("Failed to acquire connection from pool: {e}");
-                    return Ok(error_response(
-                        StatusCode::BAD_GATEWAY,
-                        &format!("Failed to connect to local service: {e}"),
-                    ));
-                }
-            };
+    // Try to acquire connection from pool
+    let mut sender = match pool.acquire_h1().await {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to acquire connection from pool: {e}");
+            return Err(ProxyError::from_pool(e, pool.target_addr()));
+        }
+    };
 
-            let req = req.map(|b| {
-                b.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync + 'static>)
-                    .boxed()
-            });
-
-            match sender.send_request(req).await {
-                Ok(res) => {
-                    if is_upgrade && res.status() == StatusCode::SWITCHING_PROTOCOLS {
-                        // Don't return upgraded connections to pool
-                        let upstream_headers = res.headers().clone();
-                        let local_upgrade = hyper::upgrade::on(res);
-
-                        if let Some(server_upgrade) = server_upgrade {
-                            tokio::spawn(async move {
-                                let (local_result, server_result) =
-                                    tokio::join!(local_upgrade, server_upgrade);
-
-                                let local_upgraded = match local_result {
-                                    Ok(u) => u,
-                                    Err(e) => {
-                                        error!("Local upgrade failed: {e}");
-                                        return;
-                                    }
-                                };
-                                let server_upgraded = match server_result {
-                                    Ok(u) => u,
-                                    Err(e) => {
-                                        error!("Server upgrade failed: {e}");
-                                        return;
-                                    }
-                                };
-
-                                let mut local_io = TokioIo::new(local_upgraded);
-                                let mut server_io = TokioIo::new(server_upgraded);
-                                let _ =
-                                    tokio::io::copy_bidirectional(&mut local_io, &mut server_io)
-                                        .await;
-                            });
-                        }
+    let req = req.map(local_request_body);
+
+    match sender.send_request(req).await {
+        Ok(res) => {
+            if is_upgrade && res.status() == StatusCode::SWITCHING_PROTOCOLS {
+                // Don't return upgraded connections to pool
+                let upstream_headers = res.headers().clone();
+                let local_upgrade = hyper::upgrade::on(res);
 
-                        let mut builder =
-                            Response::builder().status(StatusCode::SWITCHING_PROTOCOLS);
-                        for (key, value) in &upstream_headers {
-                            builder = builder.header(key, value);
+                if let Some(server_upgrade) = server_upgrade {
+                    tokio::spawn(async move {
+                        let (local_result, server_result) =
+                            tokio::join!(local_upgrade, server_upgrade);
+
+                        let local_upgraded = match local_result {
+                            Ok(u) => u,
+                            Err(e) => {
+                                error!("Local upgrade failed: {e}");
+                                return;
+                            }
+                        };
+                        let server_upgraded = match server_result {
+                            Ok(u) => u,
+                            Err(e) => {
+                                error!("Server upgrade failed: {e}");
+                                return;
+                            }
+                        };
+
+                        let mut local_io = TokioIo::new(local_upgraded);
+                        let mut server_io = TokioIo::new(server_upgraded);
+                        if let Err(e) =
+                            copy_websocket(&mut local_io, &mut server_io, websocket).await
+                        {
+                            warn!("WebSocket closed: {e}");
                         }
-                        Ok(builder
-                            .body(
-                                Full::new(Bytes::new())
-                                    .map_err(|_| ProxyError::Custom("unreachable".into()))
-                                    .boxed(),
-                            )
-                            .unwrap_or_else(|_| {
-                                error_response(
-                                    StatusCode::INTERNAL_SERVER_ERROR,
-                                    "Failed to build upgrade response",
-                                )
-                            }))
-                    } else {
-                        // Return connection to pool for reuse
-                        pool.release_h1(sender).await;
-
-                        let (parts, body) = res.into_parts();
-                        let boxed_body = body.map_err(Into::into).boxed();
-                        Ok(Response::from_parts(parts, boxed_body))
-                    }
+                    });
                 }
-                Err(e) => {
-                    // Don't return broken connections to pool
-                    error!("Failed to proxy request: {e}");
-                    Ok(error_response(StatusCode::BAD_GATEWAY, "Proxy error"))
+
+                let mut builder = Response::builder().status(StatusCode::SWITCHING_PROTOCOLS);
+                for (key, value) in &upstream_headers {
+                    builder = builder.header(key, value);
                 }
+                Ok(builder
+                    .body(
+                        Full::new(Bytes::new())
+                            .map_err(|_| ProxyError::Custom("unreachable".into()))
+                            .boxed(),
+                    )
+                    .unwrap_or_else(|_| {
+                        error_response(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "Failed to build upgrade response",
+                        )
+                    }))
+            } else {
+                // Return connection to pool for reuse
+                pool.release_h1(sender).await;
+
+                let (parts, body) = res.into_parts();
+                let boxed_body = body.map_err(Into::into).boxed();
+                Ok(Response::from_parts(parts, boxed_body))
             }
-        })
+        }
+        Err(e) => {
+            // Don't return broken connections to pool
+            error!("Failed to proxy request: {e}");
+            Err(ProxyError::from_send(&e, pool.target_addr()))
+        }
     }
 }
 
+/// Box a request body read from the tunnel for the local connection
+///
+/// A body that fails part way, because the ingress aborted the tunnel stream
+/// (e.g. the client disconnected mid-upload), fails the local request and
+/// tears down its connection instead of ending the body early, so the local
+/// service never takes a truncated upload for a complete one.
+fn local_request_body<B>(
+    body: B,
+) -> http_body_util::combinators::BoxBody<Bytes, Box<dyn std::error::Error + Send + Sync>>
+where
+    B: Body<Data = Bytes> + Send + Sync + 'static,
+    B::Error: std::error::Error + Send + Sync + 'static,
+{
+    body.map_err(|e| {
+        warn!("Request body from the tunnel ended early: {e}");
+        Box::new(e) as Box<dyn std::error::Error + Send + Sync + 'static>
+    })
+    .boxed()
+}
+
 /// Pre-allocated bytes for common error bodies (avoids allocation in hot/error path).
 const MSG_PROXY_ERROR: &[u8] = b"Proxy error";
 const MSG_INTERNAL_ERROR: &[u8] = b"Internal error";
 
+/// Plain-text response for `err`, with the status from [`ProxyError::status`]
+/// and the body from [`ProxyError::public_message`]; the details are logged
+pub fn proxy_error_response(err: &ProxyError) -> Response<BoxBody> {
+    warn!("{err}");
+    error_response(err.status(), err.public_message())
+}
+
 /// Builds a plain-text error response. Shared by proxy and CLI dashboard middleware.
 /// Uses static bytes for common messages to avoid allocation.
 pub fn error_response(status: StatusCode, msg: &str) -> Response<BoxBody> {
@@ -259,6 +621,10 @@ pub struct HttpProxy<L> {
     target_addr: String,
     layer: L,
     pool: Arc<ConnectionPool>,
+    forwarding: Arc<ForwardingConfig>,
+    inspector: Option<Arc<dyn TrafficInspector>>,
+    websocket: WebSocketLimits,
+    fallback: Option<Arc<FallbackResponse>>,
 }
 
 impl HttpProxy<tower::layer::util::Identity> {
@@ -271,6 +637,10 @@ impl HttpProxy<tower::layer::util::Identity> {
             target_addr,
             layer: tower::layer::util::Identity::new(),
             pool,
+            forwarding: Arc::new(ForwardingConfig::default()),
+            inspector: None,
+            websocket: WebSocketLimits::default(),
+            fallback: None,
         }
     }
 
@@ -280,6 +650,10 @@ impl HttpProxy<tower::layer::util::Identity> {
             target_addr,
             layer: tower::layer::util::Identity::new(),
             pool,
+            forwarding: Arc::new(ForwardingConfig::default()),
+            inspector: None,
+            websocket: WebSocketLimits::default(),
+            fallback: None,
         }
     }
 }
@@ -290,10 +664,55 @@ impl<L> HttpProxy<L> {
             target_addr: self.target_addr,
             layer,
             pool: self.pool,
+            forwarding: self.forwarding,
+            inspector: self.inspector,
+            websocket: self.websocket,
+            fallback: self.fallback,
         }
     }
 
-    pub fn handle_stream(&self, stream: VirtualStream)
+    /// Add `X-Forwarded-*` headers and/or override `Host` on forwarded requests
+    #[must_use]
+    pub fn with_forwarding(mut self, forwarding: ForwardingConfig) -> Self {
+        self.forwarding = Arc::new(forwarding);
+        self
+    }
+
+    /// Call `inspector` for every request forwarded to the local service and
+    /// for its response
+    #[must_use]
+    pub fn with_inspector(mut self, inspector: Arc<dyn TrafficInspector>) -> Self {
+        self.inspector = Some(inspector);
+        self
+    }
+
+    /// Close upgraded WebSocket connections to the local service that go idle
+    /// or carry oversized messages
+    #[must_use]
+    pub fn with_websocket_limits(mut self, limits: WebSocketLimits) -> Self {
+        self.websocket = limits;
+        self
+    }
+
+    /// Serve `fallback` instead of a 502 when the local service refuses or
+    /// cannot be reached, e.g. a maintenance page. gRPC streams still get
+    /// the plain error.
+    #[must_use]
+    pub fn with_fallback(mut self, fallback: FallbackResponse) -> Self {
+        self.fallback = Some(Arc::new(fallback));
+        self
+    }
+
+    /// Original client IP, as reported by the ingress when opening the
+    /// stream. IPv4 clients of a dual-stack listener are reported as IPv4.
+    fn client_ip(stream: &VirtualStream) -> Option<IpAddr> {
+        stream
+            .header(REMOTE_ADDR_HEADER)
+            .and_then(|addr| addr.parse::<SocketAddr>().ok())
+            .map(|addr| addr.ip().to_canonical())
+    }
+
+    pub fn handle_stream(&self, mut stream: VirtualStream)
     where
         L: Layer<LocalProxyService> + Clone + Send + 'static,
         L::Service: Service<Request<Incoming>, Response = Response<BoxBody>, Error = hyper::Error>
@@ -302,10 +721,13 @@ impl<L> HttpProxy<L> {
             + 'static,
         <L::Service as Service<Request<Incoming>>>::Future: Send,
     {
-        let service = self
-            .layer
-            .clone()
-            .layer(LocalProxyService::with_pool(self.pool.clone()));
+        let local = LocalProxyService::with_pool(self.pool.clone())
+            .with_forwarding(self.forwarding.clone(), Self::client_ip(&stream))
+            .with_inspector(self.inspector.clone())
+            .with_websocket_limits(self.websocket)
+            .with_fallback(self.fallback.clone())
+            .with_reset_on_body_error(Some(stream.abort_handle()));
+        let service = self.layer.clone().layer(local);
         let hyper_service = TowerToHyperService::new(service);
         let io = TokioIo::new(stream);
 
@@ -336,10 +758,10 @@ impl<L> HttpProxy<L> {
             self.target_addr.clone(),
             PoolConfig::default(),
         ));
-        let service = self
-            .layer
-            .clone()
-            .layer(LocalProxyService::with_pool_h2(grpc_pool));
+        let local = LocalProxyService::with_pool_h2(grpc_pool)
+            .with_forwarding(self.forwarding.clone(), Self::client_ip(&stream))
+            .with_inspector(self.inspector.clone());
+        let service = self.layer.clone().layer(local);
         let hyper_service = TowerToHyperService::new(service);
         let io = TokioIo::new(stream);
 
@@ -373,6 +795,71 @@ mod tests {
         assert!(display.contains("connection failed"));
     }
 
+    #[test]
+    fn test_proxy_error_status_codes() {
+        let addr = "127.0.0.1:3000".to_string();
+        let cases = [
+            (
+                ProxyError::ConnectFailed {
+                    addr: addr.clone(),
+                    reason: "connection refused".to_string(),
+                },
+                StatusCode::BAD_GATEWAY,
+            ),
+            (
+                ProxyError::UpstreamTimeout { addr: addr.clone() },
+                StatusCode::GATEWAY_TIMEOUT,
+            ),
+            (
+                ProxyError::UpstreamProtocol {
+                    addr: addr.clone(),
+                    reason: "invalid HTTP version".to_string(),
+                },
+                StatusCode::BAD_GATEWAY,
+            ),
+            (
+                ProxyError::PoolExhausted { addr },
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            (
+                ProxyError::BodyTooLarge {
+                    reason: "length limit exceeded".to_string(),
+                },
+                StatusCode::PAYLOAD_TOO_LARGE,
+            ),
+            (
+                ProxyError::Custom("other".to_string()),
+                StatusCode::BAD_GATEWAY,
+            ),
+        ];
+        for (err, status) in cases {
+            assert_eq!(err.status(), status, "{err}");
+            assert_eq!(proxy_error_response(&err).status(), status);
+            assert!(!err.public_message().contains("127.0.0.1"), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_proxy_error_from_pool() {
+        let addr = "127.0.0.1:3000";
+        assert!(matches!(
+            ProxyError::from_pool(ConnectionPoolError::Connection("refused".into()), addr),
+            ProxyError::ConnectFailed { .. }
+        ));
+        assert!(matches!(
+            ProxyError::from_pool(ConnectionPoolError::Timeout("timed out".into()), addr),
+            ProxyError::UpstreamTimeout { .. }
+        ));
+        assert!(matches!(
+            ProxyError::from_pool(ConnectionPoolError::Handshake("bad".into()), addr),
+            ProxyError::UpstreamProtocol { .. }
+        ));
+        assert!(matches!(
+            ProxyError::from_pool(ConnectionPoolError::PoolFull, addr),
+            ProxyError::PoolExhausted { .. }
+        ));
+    }
+
     #[test]
     fn test_local_proxy_service_new() {
         let service = LocalProxyService::new("127.0.0.1:8080".to_string());
@@ -405,6 +892,38 @@ mod tests {
         let body_bytes = response.into_body().collect().await.unwrap().to_bytes();
         let body_str = String::from_utf8(body_bytes.to_vec()).unwrap();
         assert!(body_str.contains("Failed to connect"));
+        assert!(!body_str.contains("127.0.0.1:12345"));
+    }
+
+    #[tokio::test]
+    async fn test_fallback_on_connection_error() {
+        let fallback = FallbackResponse::maintenance_page("<h1>Back soon</h1>")
+            .with_retry_after(Duration::from_secs(30));
+        let mut service = LocalProxyService::new("127.0.0.1:12345".to_string())
+            .with_fallback(Some(Arc::new(fallback)));
+
+        let req = Request::builder()
+            .uri("http://example.com")
+            .body(Full::new(Bytes::from("test")))
+            .unwrap();
+        let response = service.call(req).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "30");
+        let body_bytes = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body_bytes[..], b"<h1>Back soon</h1>");
+
+        // Other failures keep their plain error
+        let err = ProxyError::UpstreamTimeout {
+            addr: "127.0.0.1:12345".to_string(),
+        };
+        let fallback = FallbackResponse::maintenance_page("down");
+        let response = failure_response(&err, Some(&fallback));
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
     }
 
     #[test]
@@ -431,6 +950,72 @@ mod tests {
         assert_eq!(proxy.target_addr, "127.0.0.1:8080");
     }
 
+    #[test]
+    fn test_forwarding_appends_to_existing_chain() {
+        let config = ForwardingConfig {
+            forwarded_for: true,
+            forwarded_host: true,
+            forwarded_proto: Some("https".to_string()),
+            host_override: Some("app.internal:3000".to_string()),
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(HOST, HeaderValue::from_static("demo.example.com"));
+        headers.append(X_FORWARDED_FOR, HeaderValue::from_static("10.0.0.1"));
+        headers.append(X_FORWARDED_FOR, HeaderValue::from_static("10.0.0.2"));
+
+        config.apply(&mut headers, Some("203.0.113.7".parse().unwrap()));
+
+        assert_eq!(
+            headers.get(X_FORWARDED_FOR).unwrap(),
+            "10.0.0.1, 10.0.0.2, 203.0.113.7"
+        );
+        assert_eq!(headers.get_all(X_FORWARDED_FOR).iter().count(), 1);
+        assert_eq!(headers.get(X_FORWARDED_HOST).unwrap(), "demo.example.com");
+        assert_eq!(headers.get(X_FORWARDED_PROTO).unwrap(), "https");
+        assert_eq!(headers.get(HOST).unwrap(), "app.internal:3000");
+    }
+
+    #[test]
+    fn test_forwarding_disabled_by_default() {
+        let config = ForwardingConfig::default();
+        assert!(!config.is_enabled());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(HOST, HeaderValue::from_static("demo.example.com"));
+        config.apply(&mut headers, Some("203.0.113.7".parse().unwrap()));
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn test_forwarded_for_skips_client_already_recorded() {
+        let config = ForwardingConfig {
+            forwarded_for: true,
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            X_FORWARDED_FOR,
+            HeaderValue::from_static("10.0.0.1, 203.0.113.7"),
+        );
+        config.apply(&mut headers, Some("203.0.113.7".parse().unwrap()));
+        assert_eq!(
+            headers.get(X_FORWARDED_FOR).unwrap(),
+            "10.0.0.1, 203.0.113.7"
+        );
+    }
+
+    #[test]
+    fn test_forwarded_for_without_client_ip_keeps_chain() {
+        let config = ForwardingConfig {
+            forwarded_for: true,
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(X_FORWARDED_FOR, HeaderValue::from_static("10.0.0.1"));
+        config.apply(&mut headers, None);
+        assert_eq!(headers.get(X_FORWARDED_FOR).unwrap(), "10.0.0.1");
+    }
+
     #[test]
     fn test_http_proxy_with_layer() {
         let proxy = HttpProxy::new("127.0.0.1:8080".to_string());
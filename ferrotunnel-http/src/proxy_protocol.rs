@@ -0,0 +1,221 @@
+//! PROXY protocol v2 for ingress listeners behind an L4 load balancer
+//!
+//! A load balancer that forwards raw TCP replaces the client's address with
+//! its own. With PROXY protocol enabled it prepends a binary header carrying
+//! the original source address, which [`read_source_addr`] parses so
+//! `X-Forwarded-For`, access logs and plugins see the real client. See the
+//! [specification](https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt),
+//! section 2.2.
+
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// First 12 bytes of every v2 header
+pub const SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// How long a new connection may take to send its header
+pub const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Signature, version/command, family/transport and address length
+const FIXED_HEADER_LEN: usize = 16;
+
+const VERSION_2: u8 = 0x20;
+const COMMAND_LOCAL: u8 = 0x0;
+const COMMAND_PROXY: u8 = 0x1;
+const FAMILY_INET: u8 = 0x1;
+const FAMILY_INET6: u8 = 0x2;
+const TRANSPORT_STREAM: u8 = 0x1;
+
+/// Read the PROXY protocol v2 header at the start of `stream` and return
+/// the client address it carries.
+///
+/// `LOCAL` connections (e.g. load balancer health checks) and addresses of
+/// other families keep `peer_addr`. Fails when the connection does not start
+/// with a valid header within [`HEADER_TIMEOUT`]. Reads exactly the header,
+/// leaving the client's data in `stream`.
+pub async fn read_source_addr<S>(stream: &mut S, peer_addr: SocketAddr) -> io::Result<SocketAddr>
+where
+    S: AsyncRead + Unpin + ?Sized,
+{
+    match tokio::time::timeout(HEADER_TIMEOUT, read_header(stream)).await {
+        Ok(source) => Ok(source?.unwrap_or(peer_addr)),
+        Err(_) => Err(invalid("timed out waiting for PROXY protocol header")),
+    }
+}
+
+/// Source address from the header, `None` when it carries none
+async fn read_header<S>(stream: &mut S) -> io::Result<Option<SocketAddr>>
+where
+    S: AsyncRead + Unpin + ?Sized,
+{
+    let mut header = [0u8; FIXED_HEADER_LEN];
+    stream.read_exact(&mut header).await?;
+    if header[..SIGNATURE.len()] != SIGNATURE {
+        return Err(invalid("missing PROXY protocol v2 header"));
+    }
+    if header[12] & 0xf0 != VERSION_2 {
+        return Err(invalid("unsupported PROXY protocol version"));
+    }
+    let command = header[12] & 0x0f;
+    let family = header[13] >> 4;
+    let len = usize::from(u16::from_be_bytes([header[14], header[15]]));
+
+    // The address block is always consumed, including any TLVs after the
+    // addresses, so the client's data starts right after it
+    let mut addresses = vec![0u8; len];
+    stream.read_exact(&mut addresses).await?;
+
+    match command {
+        COMMAND_LOCAL => Ok(None),
+        COMMAND_PROXY => parse_source(family, &addresses),
+        _ => Err(invalid("unknown PROXY protocol command")),
+    }
+}
+
+/// Source address of an address block laid out as source address,
+/// destination address, source port, destination port
+fn parse_source(family: u8, addresses: &[u8]) -> io::Result<Option<SocketAddr>> {
+    let truncated = || invalid("truncated PROXY protocol address block");
+    match family {
+        FAMILY_INET => {
+            let block: &[u8; 12] = addresses
+                .get(..12)
+                .and_then(|block| block.try_into().ok())
+                .ok_or_else(truncated)?;
+            let ip = Ipv4Addr::new(block[0], block[1], block[2], block[3]);
+            let port = u16::from_be_bytes([block[8], block[9]]);
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        FAMILY_INET6 => {
+            let block: &[u8; 36] = addresses
+                .get(..36)
+                .and_then(|block| block.try_into().ok())
+                .ok_or_else(truncated)?;
+            let mut ip = [0u8; 16];
+            ip.copy_from_slice(&block[..16]);
+            let port = u16::from_be_bytes([block[32], block[33]]);
+            Ok(Some(SocketAddr::new(Ipv6Addr::from(ip).into(), port)))
+        }
+        // UNSPEC and UNIX sockets carry no usable IP address
+        _ => Ok(None),
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Encode a v2 `PROXY` header for a connection from `source` to
+/// `destination`, as a load balancer would send it
+///
+/// Mixed IPv4 and IPv6 addresses are sent as IPv6, with the IPv4 address
+/// mapped.
+pub fn encode_header(source: SocketAddr, destination: SocketAddr) -> Vec<u8> {
+    let mut header = SIGNATURE.to_vec();
+    header.push(VERSION_2 | COMMAND_PROXY);
+    let mut addresses = Vec::with_capacity(36);
+    match (source, destination) {
+        (SocketAddr::V4(src), SocketAddr::V4(dst)) => {
+            header.push((FAMILY_INET << 4) | TRANSPORT_STREAM);
+            addresses.extend_from_slice(&src.ip().octets());
+            addresses.extend_from_slice(&dst.ip().octets());
+        }
+        (src, dst) => {
+            header.push((FAMILY_INET6 << 4) | TRANSPORT_STREAM);
+            addresses.extend_from_slice(&to_ipv6(src).octets());
+            addresses.extend_from_slice(&to_ipv6(dst).octets());
+        }
+    }
+    addresses.extend_from_slice(&source.port().to_be_bytes());
+    addresses.extend_from_slice(&destination.port().to_be_bytes());
+    header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+    header.extend_from_slice(&addresses);
+    header
+}
+
+fn to_ipv6(addr: SocketAddr) -> Ipv6Addr {
+    match addr {
+        SocketAddr::V4(v4) => v4.ip().to_ipv6_mapped(),
+        SocketAddr::V6(v6) => *v6.ip(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    async fn parse(bytes: &[u8]) -> io::Result<SocketAddr> {
+        let mut stream = bytes;
+        read_source_addr(&mut stream, addr("10.0.0.1:1000")).await
+    }
+
+    #[tokio::test]
+    async fn test_ipv4_header() {
+        let mut wire = encode_header(addr("203.0.113.7:4242"), addr("10.0.0.2:80"));
+        wire.extend_from_slice(b"GET / HTTP/1.1\r\n");
+
+        let mut stream = &wire[..];
+        let source = read_source_addr(&mut stream, addr("10.0.0.1:1000"))
+            .await
+            .unwrap();
+        assert_eq!(source, addr("203.0.113.7:4242"));
+        // Only the header is consumed
+        assert_eq!(stream, b"GET / HTTP/1.1\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_ipv6_header_with_tlv() {
+        let mut wire = encode_header(addr("[2001:db8::7]:4242"), addr("[2001:db8::1]:443"));
+        // Append a NOOP TLV and fix up the address length
+        wire.extend_from_slice(&[0x04, 0x00, 0x01, 0x00]);
+        let len = u16::from_be_bytes([wire[14], wire[15]]) + 4;
+        wire[14..16].copy_from_slice(&len.to_be_bytes());
+        wire.extend_from_slice(b"data");
+
+        let mut stream = &wire[..];
+        let source = read_source_addr(&mut stream, addr("10.0.0.1:1000"))
+            .await
+            .unwrap();
+        assert_eq!(source, addr("[2001:db8::7]:4242"));
+        assert_eq!(stream, b"data");
+    }
+
+    #[tokio::test]
+    async fn test_local_command_keeps_peer_addr() {
+        let mut wire = SIGNATURE.to_vec();
+        wire.extend_from_slice(&[VERSION_2 | COMMAND_LOCAL, 0x00, 0x00, 0x00]);
+        assert_eq!(parse(&wire).await.unwrap(), addr("10.0.0.1:1000"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_headers_rejected() {
+        // Plain HTTP without a header
+        assert!(parse(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n").await.is_err());
+
+        // Version 1 (text) header
+        assert!(parse(b"PROXY TCP4 203.0.113.7 10.0.0.2 4242 80\r\n")
+            .await
+            .is_err());
+
+        // Address block shorter than an IPv4 pair
+        let mut wire = SIGNATURE.to_vec();
+        wire.extend_from_slice(&[
+            VERSION_2 | COMMAND_PROXY,
+            (FAMILY_INET << 4) | TRANSPORT_STREAM,
+            0,
+            4,
+        ]);
+        wire.extend_from_slice(&[203, 0, 113, 7]);
+        assert!(parse(&wire).await.is_err());
+
+        // Header cut short by the peer closing
+        let wire = encode_header(addr("203.0.113.7:4242"), addr("10.0.0.2:80"));
+        assert!(parse(&wire[..20]).await.is_err());
+    }
+}
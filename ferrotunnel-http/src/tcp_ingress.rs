@@ -3,13 +3,20 @@
 //! Provides protocol-agnostic TCP forwarding through the tunnel.
 //! Useful for database connections, SSH, and custom protocols.
 
+use crate::proxy_protocol;
 use ferrotunnel_common::Result;
+use ferrotunnel_core::auth::constant_time_eq;
+use ferrotunnel_core::ip_filter::IpFilter;
+use ferrotunnel_core::transport::ListenerConfig;
 use ferrotunnel_core::tunnel::session::SessionStoreBackend;
+use ferrotunnel_protocol::constants::TCP_CAPABILITY;
 use ferrotunnel_protocol::frame::Protocol;
+use std::collections::HashMap;
+use std::fmt;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::Semaphore;
 use tracing::{error, info, warn};
@@ -25,8 +32,92 @@ pub struct TcpIngressConfig {
     pub idle_timeout: Duration,
     /// Buffer size for bidirectional copy (default: 64KB)
     pub buffer_size: usize,
+    /// Extra listen ports routed to tunnels advertising a specific capability
+    /// (e.g. `2222 -> "ssh"`). Ports are bound on the ingress IP; a port not in
+    /// the map routes to tunnels with the `"tcp"` capability.
+    pub port_capabilities: HashMap<u16, String>,
+    /// Expect a PROXY protocol v2 header on every connection and use the
+    /// client address it carries (default: false). Connections without one
+    /// are closed before a tunnel stream is opened.
+    pub proxy_protocol: bool,
+    /// Gate applied to connections on every port without an entry in
+    /// `port_auth` (default: none, connections are bridged unchecked)
+    pub auth: Option<TcpAuth>,
+    /// Per-port gates, overriding `auth` (e.g. a stricter one for a database
+    /// port)
+    pub port_auth: HashMap<u16, TcpAuth>,
+    /// Socket options for the main and mapped port listeners, e.g. whether
+    /// a listener on `[::]` also accepts IPv4 clients
+    pub listener: ListenerConfig,
 }
 
+/// Connection-level gate for raw TCP ports
+///
+/// Raw ports have no HTTP layer to carry credentials, so the ingress checks
+/// each connection itself before opening a tunnel stream. The peer address
+/// (taken from the PROXY header when [`TcpIngressConfig::proxy_protocol`] is
+/// on) must pass `ip_filter`, and when `secret` is set the connection must
+/// start with exactly those bytes. The secret is consumed, so the tunneled
+/// service only sees what follows it. Rejected connections are closed.
+#[derive(Clone, Default)]
+pub struct TcpAuth {
+    /// Networks allowed to connect (default: everyone)
+    pub ip_filter: IpFilter,
+    /// Bytes the peer must send before anything else
+    pub secret: Option<Vec<u8>>,
+}
+
+impl fmt::Debug for TcpAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TcpAuth")
+            .field("ip_filter", &self.ip_filter)
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+/// How long a gated connection has to send its secret
+const SECRET_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl TcpAuth {
+    /// Whether the connection from `peer_addr` passes the gate, consuming the
+    /// secret from `stream` when one is required
+    async fn admit<S>(&self, stream: &mut S, peer_addr: SocketAddr) -> bool
+    where
+        S: AsyncRead + Unpin + ?Sized,
+    {
+        if !self.ip_filter.is_allowed(peer_addr.ip()) {
+            warn!("TCP connection from {} rejected by IP filter", peer_addr);
+            return false;
+        }
+        let Some(secret) = &self.secret else {
+            return true;
+        };
+        let mut received = vec![0u8; secret.len()];
+        match tokio::time::timeout(SECRET_TIMEOUT, stream.read_exact(&mut received)).await {
+            Ok(Ok(_)) if constant_time_eq(&received, secret) => true,
+            Ok(Ok(_)) => {
+                warn!("TCP connection from {} sent a wrong secret", peer_addr);
+                false
+            }
+            Ok(Err(e)) => {
+                warn!(
+                    "TCP connection from {} closed before its secret: {}",
+                    peer_addr, e
+                );
+                false
+            }
+            Err(_) => {
+                warn!("TCP connection from {} sent no secret in time", peer_addr);
+                false
+            }
+        }
+    }
+}
+
+/// Capability used for ports without an explicit mapping
+const DEFAULT_TCP_CAPABILITY: &str = TCP_CAPABILITY;
+
 impl Default for TcpIngressConfig {
     fn default() -> Self {
         Self {
@@ -34,6 +125,11 @@ impl Default for TcpIngressConfig {
             connection_timeout: Duration::from_secs(10),
             idle_timeout: Duration::from_secs(300),
             buffer_size: 64 * 1024,
+            port_capabilities: HashMap::new(),
+            proxy_protocol: false,
+            auth: None,
+            port_auth: HashMap::new(),
+            listener: ListenerConfig::default(),
         }
     }
 }
@@ -68,63 +164,153 @@ impl TcpIngress {
     }
 
     /// Start the TCP ingress server
+    ///
+    /// Binds the main address plus every port in
+    /// [`TcpIngressConfig::port_capabilities`] and serves them all until one fails.
+    /// Connections must pass the port's [`TcpAuth`] gate, if any, before
+    /// they are bridged.
     pub async fn start(self) -> Result<()> {
-        let listener = TcpListener::bind(self.addr).await?;
-        info!("TCP Ingress listening on {}", self.addr);
-
-        loop {
-            let (stream, peer_addr) = listener.accept().await?;
+        let listener = self.config.listener.bind(self.addr)?;
+        self.serve(listener).await
+    }
 
-            // CRITICAL: Set TCP_NODELAY to disable Nagle's algorithm for low latency
-            if let Err(e) = stream.set_nodelay(true) {
-                warn!("Failed to set TCP_NODELAY for {}: {}", peer_addr, e);
+    /// Like [`start`](Self::start), with an already bound `listener` in place
+    /// of the main address. Mapped ports are still bound on the ingress IP.
+    pub async fn serve(self, listener: TcpListener) -> Result<()> {
+        let main_port = listener.local_addr()?.port();
+        let mut listeners = vec![(listener, main_port)];
+        for port in self.config.port_capabilities.keys() {
+            if *port == main_port {
+                continue;
             }
+            let addr = SocketAddr::new(self.addr.ip(), *port);
+            listeners.push((self.config.listener.bind(addr)?, *port));
+        }
+
+        let accept_loops = listeners.into_iter().map(|(listener, port)| {
+            accept_loop(
+                listener,
+                self.capability_for_port(port),
+                self.auth_for_port(port),
+                self.sessions.clone(),
+                self.config.clone(),
+                self.connection_semaphore.clone(),
+            )
+        });
+        futures::future::try_join_all(accept_loops).await?;
+        Ok(())
+    }
+
+    fn capability_for_port(&self, port: u16) -> String {
+        self.config
+            .port_capabilities
+            .get(&port)
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_TCP_CAPABILITY.to_string())
+    }
+
+    fn auth_for_port(&self, port: u16) -> Option<Arc<TcpAuth>> {
+        self.config
+            .port_auth
+            .get(&port)
+            .or(self.config.auth.as_ref())
+            .cloned()
+            .map(Arc::new)
+    }
+}
 
-            // Acquire connection permit (limit concurrent connections)
-            let Ok(permit) = self.connection_semaphore.clone().try_acquire_owned() else {
+/// Accept connections on one listener and route them to tunnels with `capability`
+async fn accept_loop(
+    listener: TcpListener,
+    capability: String,
+    auth: Option<Arc<TcpAuth>>,
+    sessions: SessionStoreBackend,
+    config: TcpIngressConfig,
+    connection_semaphore: Arc<Semaphore>,
+) -> Result<()> {
+    info!(
+        "TCP Ingress listening on {} (capability '{}')",
+        listener.local_addr()?,
+        capability
+    );
+
+    loop {
+        let (stream, peer_addr) = listener.accept().await?;
+
+        // CRITICAL: Set TCP_NODELAY to disable Nagle's algorithm for low latency
+        if let Err(e) = stream.set_nodelay(true) {
+            warn!("Failed to set TCP_NODELAY for {}: {}", peer_addr, e);
+        }
+
+        // Acquire connection permit (limit concurrent connections)
+        let Ok(permit) = connection_semaphore.clone().try_acquire_owned() else {
+            warn!(
+                "Max TCP connections reached, rejecting connection from {}",
+                peer_addr
+            );
+            drop(stream);
+            continue;
+        };
+
+        // Find an active tunnel with the capability routed to this port that
+        // also accepts TCP streams
+        let Some(multiplexer) = sessions.find_multiplexer_for(&capability, Protocol::TCP) else {
+            if sessions
+                .find_multiplexer_with_capability(&capability)
+                .is_some()
+            {
                 warn!(
-                    "Max TCP connections reached, rejecting connection from {}",
-                    peer_addr
+                    "Rejecting connection from {}: tunnels with '{}' capability do not \
+                     accept TCP streams (no '{}' capability)",
+                    peer_addr, capability, TCP_CAPABILITY
                 );
-                drop(stream);
-                continue;
-            };
-
-            // Find an active tunnel with TCP capability
-            let Some(multiplexer) = self.sessions.find_multiplexer_with_capability("tcp") else {
+            } else {
                 warn!(
-                    "No active tunnel with 'tcp' capability for connection from {}",
-                    peer_addr
+                    "No active tunnel with '{}' capability for connection from {}",
+                    capability, peer_addr
                 );
-                drop(stream);
-                continue;
-            };
-
-            let config = self.config.clone();
-            tokio::spawn(async move {
-                let _permit = permit; // Hold permit until connection closes
-
-                if let Err(e) = handle_tcp_connection(stream, multiplexer, peer_addr, config).await
-                {
-                    error!(
-                        peer_addr = %peer_addr,
-                        error = %e,
-                        "TCP tunnel connection failed"
-                    );
-                }
-            });
-        }
+            }
+            drop(stream);
+            continue;
+        };
+
+        let config = config.clone();
+        let auth = auth.clone();
+        tokio::spawn(async move {
+            let _permit = permit; // Hold permit until connection closes
+
+            if let Err(e) =
+                handle_tcp_connection(stream, multiplexer, peer_addr, config, auth).await
+            {
+                error!(
+                    peer_addr = %peer_addr,
+                    error = %e,
+                    "TCP tunnel connection failed"
+                );
+            }
+        });
     }
 }
 
 /// Handle a single TCP connection through the tunnel
 async fn handle_tcp_connection(
-    client_stream: TcpStream,
+    mut client_stream: TcpStream,
     multiplexer: ferrotunnel_core::stream::Multiplexer,
     peer_addr: SocketAddr,
     config: TcpIngressConfig,
+    auth: Option<Arc<TcpAuth>>,
 ) -> Result<()> {
     let start = Instant::now();
+    let peer_addr = if config.proxy_protocol {
+        proxy_protocol::read_source_addr(&mut client_stream, peer_addr).await?
+    } else {
+        peer_addr
+    };
+    if let Some(auth) = auth {
+        if !auth.admit(&mut client_stream, peer_addr).await {
+            return Ok(());
+        }
+    }
 
     // Open virtual stream through tunnel with timeout
     let tunnel_stream = tokio::time::timeout(
@@ -214,6 +400,10 @@ mod tests {
         assert_eq!(config.connection_timeout, Duration::from_secs(10));
         assert_eq!(config.idle_timeout, Duration::from_secs(300));
         assert_eq!(config.buffer_size, 64 * 1024);
+        assert!(config.port_capabilities.is_empty());
+        assert!(!config.proxy_protocol);
+        assert!(config.auth.is_none());
+        assert!(config.port_auth.is_empty());
     }
 
     #[test]
@@ -233,8 +423,93 @@ mod tests {
             connection_timeout: Duration::from_secs(5),
             idle_timeout: Duration::from_secs(60),
             buffer_size: 32 * 1024,
+            port_capabilities: HashMap::new(),
+            proxy_protocol: false,
+            auth: None,
+            port_auth: HashMap::new(),
+            listener: ListenerConfig::default(),
         };
         let ingress = TcpIngress::with_config(addr, sessions, config.clone());
         assert_eq!(ingress.config.max_connections, 500);
     }
+
+    #[test]
+    fn test_tcp_ingress_port_capabilities() {
+        let sessions = SessionStoreBackend::default();
+        let addr = "127.0.0.1:5000".parse().unwrap();
+        let config = TcpIngressConfig {
+            port_capabilities: HashMap::from([
+                (5000, "postgres".to_string()),
+                (2222, "ssh".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let ingress = TcpIngress::with_config(addr, sessions, config);
+        assert_eq!(ingress.capability_for_port(5000), "postgres");
+        assert_eq!(ingress.capability_for_port(2222), "ssh");
+        assert_eq!(ingress.capability_for_port(6000), "tcp");
+    }
+
+    #[test]
+    fn test_tcp_ingress_port_auth_overrides_default() {
+        let sessions = SessionStoreBackend::default();
+        let addr = "127.0.0.1:5000".parse().unwrap();
+        let config = TcpIngressConfig {
+            auth: Some(TcpAuth {
+                secret: Some(b"default".to_vec()),
+                ..Default::default()
+            }),
+            port_auth: HashMap::from([(
+                5432,
+                TcpAuth {
+                    secret: Some(b"postgres".to_vec()),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let ingress = TcpIngress::with_config(addr, sessions, config);
+        let secret = |port| {
+            ingress
+                .auth_for_port(port)
+                .and_then(|auth| auth.secret.clone())
+        };
+        assert_eq!(secret(5432).as_deref(), Some(&b"postgres"[..]));
+        assert_eq!(secret(5000).as_deref(), Some(&b"default"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_tcp_auth_secret_is_consumed() {
+        let auth = TcpAuth {
+            secret: Some(b"open-sesame".to_vec()),
+            ..Default::default()
+        };
+        let peer = "127.0.0.1:40000".parse().unwrap();
+        let mut stream = &b"open-sesameSELECT 1"[..];
+        assert!(auth.admit(&mut stream, peer).await);
+        assert_eq!(stream, b"SELECT 1");
+
+        let mut stream = &b"open-sesamX"[..];
+        assert!(!auth.admit(&mut stream, peer).await);
+        let mut stream = &b"open"[..];
+        assert!(!auth.admit(&mut stream, peer).await);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_auth_ip_filter() {
+        let auth = TcpAuth {
+            ip_filter: IpFilter::from_lists(["10.0.0.0/8"], Vec::<&str>::new()).unwrap(),
+            ..Default::default()
+        };
+        let mut stream = &b""[..];
+        assert!(
+            auth.admit(&mut stream, "10.1.2.3:5432".parse().unwrap())
+                .await
+        );
+        assert!(
+            !auth
+                .admit(&mut stream, "192.168.0.1:5432".parse().unwrap())
+                .await
+        );
+    }
 }
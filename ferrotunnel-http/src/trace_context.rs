@@ -0,0 +1,243 @@
+//! W3C Trace Context propagation for tunneled requests
+//!
+//! Every request through the ingress runs in a `tunnel_request` span. The
+//! span's parent is the incoming `traceparent` (a new trace is started when
+//! there is none), and the request forwarded to the local service carries a
+//! `traceparent` naming the ingress span, so tracing backends show
+//! caller -> ingress -> local service.
+//!
+//! With the `otel` feature and OTLP export set up through
+//! `ferrotunnel_observability::init_tracing`, the span is exported and the
+//! forwarded header carries its OpenTelemetry span id. Otherwise the ingress
+//! still assigns its own span id, so the trace stays connected downstream.
+
+use hyper::header::{HeaderMap, HeaderValue};
+use hyper::Request;
+use std::fmt::{self, Write as _};
+use uuid::Uuid;
+
+/// Header carrying the trace context
+pub const TRACEPARENT: &str = "traceparent";
+
+/// `sampled` bit of the trace flags
+const FLAG_SAMPLED: u8 = 0x01;
+
+/// Parsed `traceparent` header value (`00-<trace-id>-<parent-id>-<flags>`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceParent {
+    pub trace_id: [u8; 16],
+    /// Id of the span that made the request
+    pub parent_id: [u8; 8],
+    pub flags: u8,
+}
+
+impl TraceParent {
+    /// Parse a header value; `None` when it is malformed or uses all-zero ids
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.trim().split('-');
+        let version = decode_hex::<1>(parts.next()?)?[0];
+        let trace_id = decode_hex::<16>(parts.next()?)?;
+        let parent_id = decode_hex::<8>(parts.next()?)?;
+        let flags = decode_hex::<1>(parts.next()?)?[0];
+        // Version 00 has exactly four fields; later versions may append more
+        let valid_version = match version {
+            0x00 => parts.next().is_none(),
+            0xff => false,
+            _ => true,
+        };
+        if !valid_version || trace_id == [0; 16] || parent_id == [0; 8] {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            parent_id,
+            flags,
+        })
+    }
+
+    /// Read the `traceparent` header, ignoring invalid values
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get(TRACEPARENT)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Self::parse)
+    }
+
+    /// Start a new, sampled trace
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: *Uuid::new_v4().as_bytes(),
+            parent_id: new_span_id(),
+            flags: FLAG_SAMPLED,
+        }
+    }
+
+    /// Same trace and flags with a new span as the parent
+    #[must_use]
+    pub fn child(&self) -> Self {
+        Self {
+            parent_id: new_span_id(),
+            ..*self
+        }
+    }
+
+    /// Trace id as 32 lowercase hex digits
+    pub fn trace_id_hex(&self) -> String {
+        encode_hex(&self.trace_id)
+    }
+
+    /// Parent id as 16 lowercase hex digits
+    pub fn parent_id_hex(&self) -> String {
+        encode_hex(&self.parent_id)
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            self.trace_id_hex(),
+            self.parent_id_hex(),
+            self.flags
+        )
+    }
+}
+
+fn new_span_id() -> [u8; 8] {
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&Uuid::new_v4().as_bytes()[..8]);
+    id
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    })
+}
+
+/// Decode exactly `N` bytes of lowercase hex
+fn decode_hex<const N: usize>(s: &str) -> Option<[u8; N]> {
+    if s.len() != N * 2 || !s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return None;
+    }
+    let mut out = [0u8; N];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(out)
+}
+
+/// Create the span for a tunneled request and rewrite the request's
+/// `traceparent` so the local service sees the ingress span as its parent
+pub(crate) fn start_request_span<B>(req: &mut Request<B>) -> tracing::Span {
+    let incoming = TraceParent::from_headers(req.headers());
+    let span = new_request_span(req, incoming);
+
+    let local = || incoming.map_or_else(TraceParent::new_root, |parent| parent.child());
+    #[cfg(feature = "otel")]
+    let outgoing = ferrotunnel_observability::tracing::span_traceparent(&span)
+        .as_deref()
+        .and_then(TraceParent::parse)
+        .unwrap_or_else(local);
+    #[cfg(not(feature = "otel"))]
+    let outgoing = local();
+
+    span.record("trace_id", outgoing.trace_id_hex().as_str());
+    span.record("span_id", outgoing.parent_id_hex().as_str());
+    if let Ok(value) = HeaderValue::from_str(&outgoing.to_string()) {
+        req.headers_mut().insert(TRACEPARENT, value);
+    }
+    span
+}
+
+fn new_request_span<B>(req: &Request<B>, incoming: Option<TraceParent>) -> tracing::Span {
+    let make_span = || {
+        tracing::info_span!(
+            "tunnel_request",
+            otel.kind = "server",
+            http.method = %req.method(),
+            http.target = %req.uri().path(),
+            trace_id = tracing::field::Empty,
+            span_id = tracing::field::Empty,
+        )
+    };
+    #[cfg(feature = "otel")]
+    if let Some(parent) = incoming {
+        return ferrotunnel_observability::tracing::with_remote_parent(
+            &parent.to_string(),
+            make_span,
+        );
+    }
+    #[cfg(not(feature = "otel"))]
+    let _ = incoming;
+    make_span()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_parse_round_trip() {
+        let parent = TraceParent::parse(SAMPLE).unwrap();
+        assert_eq!(parent.trace_id_hex(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(parent.parent_id_hex(), "00f067aa0ba902b7");
+        assert_eq!(parent.flags, 0x01);
+        assert_eq!(parent.to_string(), SAMPLE);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_values() {
+        for value in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+        ] {
+            assert_eq!(TraceParent::parse(value), None, "{value}");
+        }
+    }
+
+    #[test]
+    fn test_future_version_accepted() {
+        let value = "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra";
+        let parent = TraceParent::parse(value).unwrap();
+        // Re-emitted as the version this code understands
+        assert!(parent.to_string().starts_with("00-"));
+    }
+
+    #[test]
+    fn test_child_keeps_trace() {
+        let parent = TraceParent::parse(SAMPLE).unwrap();
+        let child = parent.child();
+        assert_eq!(child.trace_id, parent.trace_id);
+        assert_eq!(child.flags, parent.flags);
+        assert_ne!(child.parent_id, parent.parent_id);
+    }
+
+    #[test]
+    fn test_request_gets_child_traceparent() {
+        let mut req = Request::builder()
+            .header(TRACEPARENT, SAMPLE)
+            .body(())
+            .unwrap();
+        let _span = start_request_span(&mut req);
+        let forwarded = TraceParent::from_headers(req.headers()).unwrap();
+        let incoming = TraceParent::parse(SAMPLE).unwrap();
+        assert_eq!(forwarded.trace_id, incoming.trace_id);
+        assert_ne!(forwarded.parent_id, incoming.parent_id);
+
+        let mut req = Request::new(());
+        let _span = start_request_span(&mut req);
+        let created = TraceParent::from_headers(req.headers()).unwrap();
+        assert_eq!(created.flags, FLAG_SAMPLED);
+    }
+}
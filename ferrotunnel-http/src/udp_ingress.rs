@@ -0,0 +1,372 @@
+//! UDP ingress for datagram tunneling
+//!
+//! Each remote source address is mapped to its own virtual stream. Datagrams are
+//! length-prefixed inside the stream so boundaries survive the byte-oriented tunnel.
+//! Useful for DNS, game servers, WireGuard and other UDP services.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use ferrotunnel_common::Result;
+use ferrotunnel_core::stream::{Multiplexer, VirtualStream};
+use ferrotunnel_core::tunnel::session::SessionStoreBackend;
+use ferrotunnel_protocol::frame::Protocol;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::{debug, info, warn};
+
+/// Size of the datagram length prefix (big-endian u16)
+const LENGTH_PREFIX_SIZE: usize = 2;
+
+/// Largest possible UDP payload
+const MAX_UDP_PAYLOAD: usize = u16::MAX as usize;
+
+/// Configuration for UDP ingress limits and timeouts
+#[derive(Debug, Clone)]
+pub struct UdpIngressConfig {
+    /// Maximum concurrent flows (distinct source addresses) (default: 1000)
+    pub max_flows: usize,
+    /// Timeout for establishing tunnel stream (default: 10s)
+    pub connection_timeout: Duration,
+    /// Idle timeout after which a flow is closed (default: 60s)
+    pub flow_idle_timeout: Duration,
+    /// Per-flow queue of datagrams waiting for the tunnel (default: 256)
+    pub flow_queue_size: usize,
+}
+
+impl Default for UdpIngressConfig {
+    fn default() -> Self {
+        Self {
+            max_flows: 1000,
+            connection_timeout: Duration::from_secs(10),
+            flow_idle_timeout: Duration::from_secs(60),
+            flow_queue_size: 256,
+        }
+    }
+}
+
+type FlowTable = Arc<Mutex<HashMap<SocketAddr, mpsc::Sender<Bytes>>>>;
+
+/// UDP ingress server for datagram tunneling
+pub struct UdpIngress {
+    addr: SocketAddr,
+    sessions: SessionStoreBackend,
+    config: UdpIngressConfig,
+}
+
+impl UdpIngress {
+    /// Create a new UDP ingress with default configuration
+    pub fn new(addr: SocketAddr, sessions: SessionStoreBackend) -> Self {
+        Self::with_config(addr, sessions, UdpIngressConfig::default())
+    }
+
+    /// Create a new UDP ingress with custom configuration
+    pub fn with_config(
+        addr: SocketAddr,
+        sessions: SessionStoreBackend,
+        config: UdpIngressConfig,
+    ) -> Self {
+        Self {
+            addr,
+            sessions,
+            config,
+        }
+    }
+
+    /// Start the UDP ingress server
+    pub async fn start(self) -> Result<()> {
+        let socket = Arc::new(UdpSocket::bind(self.addr).await?);
+        info!("UDP Ingress listening on {}", self.addr);
+
+        let flows: FlowTable = Arc::new(Mutex::new(HashMap::new()));
+        let mut buf = vec![0u8; MAX_UDP_PAYLOAD];
+
+        loop {
+            let (n, peer_addr) = socket.recv_from(&mut buf).await?;
+            let mut datagram = Bytes::copy_from_slice(&buf[..n]);
+
+            let existing = lock_flows(&flows).get(&peer_addr).cloned();
+            if let Some(tx) = existing {
+                match tx.try_send(datagram) {
+                    Ok(()) => continue,
+                    Err(TrySendError::Full(_)) => {
+                        debug!("UDP flow queue full for {}, dropping datagram", peer_addr);
+                        continue;
+                    }
+                    // The flow ended but its task has not removed it yet; open a new one
+                    Err(TrySendError::Closed(returned)) => {
+                        remove_closed_flow(&flows, peer_addr);
+                        datagram = returned;
+                    }
+                }
+            }
+
+            if lock_flows(&flows).len() >= self.config.max_flows {
+                warn!(
+                    "Max UDP flows reached, dropping datagram from {}",
+                    peer_addr
+                );
+                continue;
+            }
+
+            let Some(multiplexer) = self.sessions.find_multiplexer_with_capability("udp") else {
+                warn!(
+                    "No active tunnel with 'udp' capability for datagram from {}",
+                    peer_addr
+                );
+                continue;
+            };
+
+            let (tx, rx) = mpsc::channel(self.config.flow_queue_size);
+            let _ = tx.try_send(datagram);
+            lock_flows(&flows).insert(peer_addr, tx);
+
+            let socket = socket.clone();
+            let flows = flows.clone();
+            let config = self.config.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_udp_flow(socket, multiplexer, peer_addr, rx, config).await {
+                    warn!(peer_addr = %peer_addr, error = %e, "UDP flow failed");
+                }
+                remove_closed_flow(&flows, peer_addr);
+            });
+        }
+    }
+}
+
+fn lock_flows(
+    flows: &FlowTable,
+) -> std::sync::MutexGuard<'_, HashMap<SocketAddr, mpsc::Sender<Bytes>>> {
+    match flows.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Remove the flow for `peer_addr` if its task has ended
+///
+/// A flow that replaced the ended one keeps its entry.
+fn remove_closed_flow(flows: &FlowTable, peer_addr: SocketAddr) {
+    let mut flows = lock_flows(flows);
+    if flows.get(&peer_addr).is_some_and(mpsc::Sender::is_closed) {
+        flows.remove(&peer_addr);
+    }
+}
+
+/// Relay datagrams for a single source address through its own virtual stream
+async fn handle_udp_flow(
+    socket: Arc<UdpSocket>,
+    multiplexer: Multiplexer,
+    peer_addr: SocketAddr,
+    mut rx: mpsc::Receiver<Bytes>,
+    config: UdpIngressConfig,
+) -> Result<()> {
+    let stream = tokio::time::timeout(
+        config.connection_timeout,
+        multiplexer.open_stream(Protocol::UDP),
+    )
+    .await
+    .map_err(|_| {
+        std::io::Error::new(std::io::ErrorKind::TimedOut, "Tunnel connection timeout")
+    })??;
+
+    debug!("UDP flow opened for {}", peer_addr);
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut from_tunnel = spawn_datagram_reader(reader);
+
+    loop {
+        tokio::select! {
+            datagram = rx.recv() => {
+                let Some(datagram) = datagram else { break };
+                write_datagram(&mut writer, &datagram).await?;
+            }
+            datagram = from_tunnel.recv() => {
+                let Some(datagram) = datagram else { break };
+                socket.send_to(&datagram, peer_addr).await?;
+            }
+            () = tokio::time::sleep(config.flow_idle_timeout) => {
+                debug!("UDP flow idle timeout for {}", peer_addr);
+                break;
+            }
+        }
+    }
+
+    let _ = writer.shutdown().await;
+    Ok(())
+}
+
+/// Relay a tunneled UDP stream to a local UDP service
+///
+/// Used on the client side: every framed datagram read from `stream` is sent to
+/// `local_addr`, and replies are framed back into the stream. The flow ends when
+/// the stream closes or no datagram is seen for `idle_timeout`.
+pub async fn relay_udp_stream(
+    stream: VirtualStream,
+    local_addr: &str,
+    idle_timeout: Duration,
+) -> Result<()> {
+    let target = tokio::net::lookup_host(local_addr)
+        .await?
+        .next()
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::AddrNotAvailable,
+                format!("could not resolve {local_addr}"),
+            )
+        })?;
+    let bind_addr: SocketAddr = if target.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = UdpSocket::bind(bind_addr).await?;
+    socket.connect(target).await?;
+
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut from_tunnel = spawn_datagram_reader(reader);
+    let mut buf = vec![0u8; MAX_UDP_PAYLOAD];
+
+    loop {
+        tokio::select! {
+            datagram = from_tunnel.recv() => {
+                let Some(datagram) = datagram else { break };
+                socket.send(&datagram).await?;
+            }
+            result = socket.recv(&mut buf) => {
+                let n = result?;
+                write_datagram(&mut writer, &buf[..n]).await?;
+            }
+            () = tokio::time::sleep(idle_timeout) => {
+                debug!("UDP relay idle timeout for {}", local_addr);
+                break;
+            }
+        }
+    }
+
+    let _ = writer.shutdown().await;
+    Ok(())
+}
+
+/// Decode datagrams from the tunnel on a dedicated task
+///
+/// `read_datagram` is not cancel-safe, so it must not race inside a `select!`.
+/// The channel closes when the stream ends or fails to decode.
+fn spawn_datagram_reader<R>(mut reader: R) -> mpsc::Receiver<Bytes>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(64);
+    tokio::spawn(async move {
+        loop {
+            match read_datagram(&mut reader).await {
+                Ok(Some(datagram)) => {
+                    if tx.send(datagram).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    debug!("UDP datagram decode failed: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+    rx
+}
+
+/// Write a single length-prefixed datagram
+pub async fn write_datagram<W>(writer: &mut W, datagram: &[u8]) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let len = u16::try_from(datagram.len())
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "datagram too large"))?;
+    let mut framed = BytesMut::with_capacity(LENGTH_PREFIX_SIZE + datagram.len());
+    framed.put_u16(len);
+    framed.put_slice(datagram);
+    writer.write_all(&framed).await?;
+    writer.flush().await
+}
+
+/// Read a single length-prefixed datagram
+///
+/// Returns `Ok(None)` on a clean end of stream between datagrams.
+pub async fn read_datagram<R>(reader: &mut R) -> std::io::Result<Option<Bytes>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut len_buf = [0u8; LENGTH_PREFIX_SIZE];
+    match reader.read_exact(&mut len_buf).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = usize::from(u16::from_be_bytes(len_buf));
+    let mut datagram = vec![0u8; len];
+    reader.read_exact(&mut datagram).await?;
+    Ok(Some(Bytes::from(datagram)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_udp_ingress_config_default() {
+        let config = UdpIngressConfig::default();
+        assert_eq!(config.max_flows, 1000);
+        assert_eq!(config.connection_timeout, Duration::from_secs(10));
+        assert_eq!(config.flow_idle_timeout, Duration::from_secs(60));
+        assert_eq!(config.flow_queue_size, 256);
+    }
+
+    #[test]
+    fn test_remove_closed_flow_keeps_live_flows() {
+        let flows: FlowTable = Arc::new(Mutex::new(HashMap::new()));
+        let ended: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let live: SocketAddr = "127.0.0.1:5001".parse().unwrap();
+
+        let (ended_tx, ended_rx) = mpsc::channel(1);
+        drop(ended_rx);
+        let (live_tx, _live_rx) = mpsc::channel(1);
+        lock_flows(&flows).insert(ended, ended_tx);
+        lock_flows(&flows).insert(live, live_tx);
+
+        remove_closed_flow(&flows, ended);
+        remove_closed_flow(&flows, live);
+
+        let flows = lock_flows(&flows);
+        assert!(!flows.contains_key(&ended));
+        assert!(flows.contains_key(&live));
+    }
+
+    #[tokio::test]
+    async fn test_datagram_framing_preserves_boundaries() {
+        let (mut a, mut b) = tokio::io::duplex(1024);
+
+        write_datagram(&mut a, b"first").await.unwrap();
+        write_datagram(&mut a, b"").await.unwrap();
+        write_datagram(&mut a, b"third datagram").await.unwrap();
+        drop(a);
+
+        assert_eq!(read_datagram(&mut b).await.unwrap().unwrap(), "first");
+        assert_eq!(read_datagram(&mut b).await.unwrap().unwrap(), "");
+        assert_eq!(
+            read_datagram(&mut b).await.unwrap().unwrap(),
+            "third datagram"
+        );
+        assert!(read_datagram(&mut b).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_datagram_rejected() {
+        let (mut a, _b) = tokio::io::duplex(1024);
+        let big = vec![0u8; MAX_UDP_PAYLOAD + 1];
+        assert!(write_datagram(&mut a, &big).await.is_err());
+    }
+}
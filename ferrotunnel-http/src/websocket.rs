@@ -0,0 +1,355 @@
+//! Limits for tunneled WebSocket connections
+//!
+//! Once an upgrade completes, the ingress and the local proxy bridge raw
+//! bytes between the two sides. [`copy_websocket`] replaces
+//! `copy_bidirectional` there so long-lived upgrades can be torn down when
+//! they go idle or a peer sends an oversized message.
+
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::Instant;
+
+/// Read buffer size for each direction of the copy
+const COPY_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Longest WebSocket frame header: 2 bytes, 8-byte extended length, 4-byte mask
+const MAX_FRAME_HEADER: usize = 14;
+
+/// Limits applied to an upgraded WebSocket connection
+///
+/// Both limits are off by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WebSocketLimits {
+    /// Close the connection once no bytes have moved in either direction for
+    /// this long. Ping/pong frames count as activity.
+    pub idle_timeout: Option<Duration>,
+    /// Close the connection when a peer sends a data message, summed over its
+    /// fragments, larger than this many bytes
+    pub max_message_size: Option<u64>,
+}
+
+impl WebSocketLimits {
+    /// Whether any limit is configured
+    pub fn is_enabled(&self) -> bool {
+        self.idle_timeout.is_some() || self.max_message_size.is_some()
+    }
+}
+
+/// Copy bytes between both sides of an upgraded WebSocket until both
+/// directions finish, enforcing `limits`.
+///
+/// Returns an error, dropping both connections, when a limit is violated.
+pub async fn copy_websocket<A, B>(a: &mut A, b: &mut B, limits: WebSocketLimits) -> io::Result<()>
+where
+    A: AsyncRead + AsyncWrite + Unpin + ?Sized,
+    B: AsyncRead + AsyncWrite + Unpin + ?Sized,
+{
+    if !limits.is_enabled() {
+        return tokio::io::copy_bidirectional(a, b).await.map(|_| ());
+    }
+
+    let (a_read, a_write) = tokio::io::split(a);
+    let (b_read, b_write) = tokio::io::split(b);
+    let activity = Activity::new();
+    let copy = async {
+        tokio::try_join!(
+            copy_direction(a_read, b_write, limits.max_message_size, &activity),
+            copy_direction(b_read, a_write, limits.max_message_size, &activity),
+        )
+        .map(|_| ())
+    };
+
+    match limits.idle_timeout {
+        Some(timeout) => tokio::select! {
+            result = copy => result,
+            err = idle_watchdog(&activity, timeout) => Err(err),
+        },
+        None => copy.await,
+    }
+}
+
+async fn copy_direction<R, W>(
+    mut reader: R,
+    mut writer: W,
+    max_message_size: Option<u64>,
+    activity: &Activity,
+) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut frames = max_message_size.map(MessageSizeGuard::new);
+    let mut buf = vec![0u8; COPY_BUFFER_SIZE];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            return writer.shutdown().await;
+        }
+        activity.touch();
+        if let Some(frames) = &mut frames {
+            frames.feed(&buf[..n])?;
+        }
+        writer.write_all(&buf[..n]).await?;
+        activity.touch();
+    }
+}
+
+/// Resolves with a timeout error once `activity` has been quiet for `timeout`
+async fn idle_watchdog(activity: &Activity, timeout: Duration) -> io::Error {
+    loop {
+        let deadline = activity.last() + timeout;
+        if Instant::now() >= deadline {
+            return io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("WebSocket idle for {timeout:?}"),
+            );
+        }
+        tokio::time::sleep_until(deadline).await;
+    }
+}
+
+/// Time of the last byte moved in either direction
+struct Activity {
+    started: Instant,
+    last_millis: AtomicU64,
+}
+
+impl Activity {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            last_millis: AtomicU64::new(0),
+        }
+    }
+
+    fn touch(&self) {
+        let elapsed = u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX);
+        self.last_millis.store(elapsed, Ordering::Relaxed);
+    }
+
+    fn last(&self) -> Instant {
+        self.started + Duration::from_millis(self.last_millis.load(Ordering::Relaxed))
+    }
+}
+
+/// Incremental WebSocket frame parser tracking the size of the data message
+/// being received (RFC 6455 section 5.2)
+struct MessageSizeGuard {
+    max: u64,
+    header: [u8; MAX_FRAME_HEADER],
+    header_len: usize,
+    /// Payload bytes of the current frame still to skip
+    remaining_payload: u64,
+    /// Payload bytes of the current data message so far
+    message_len: u64,
+}
+
+impl MessageSizeGuard {
+    fn new(max: u64) -> Self {
+        Self {
+            max,
+            header: [0; MAX_FRAME_HEADER],
+            header_len: 0,
+            remaining_payload: 0,
+            message_len: 0,
+        }
+    }
+
+    fn feed(&mut self, mut data: &[u8]) -> io::Result<()> {
+        while let Some((&byte, rest)) = data.split_first() {
+            if self.remaining_payload > 0 {
+                let skip = usize::try_from(self.remaining_payload)
+                    .map_or(data.len(), |remaining| remaining.min(data.len()));
+                self.remaining_payload -= skip as u64;
+                data = &data[skip..];
+                continue;
+            }
+
+            self.header[self.header_len] = byte;
+            self.header_len += 1;
+            data = rest;
+            if frame_header_len(&self.header[..self.header_len]) == Some(self.header_len) {
+                self.end_of_header()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn end_of_header(&mut self) -> io::Result<()> {
+        let header = &self.header[..self.header_len];
+        let fin = header[0] & 0x80 != 0;
+        let opcode = header[0] & 0x0f;
+        let payload_len = match header[1] & 0x7f {
+            126 => u64::from(u16::from_be_bytes([header[2], header[3]])),
+            127 => {
+                let mut len = [0u8; 8];
+                len.copy_from_slice(&header[2..10]);
+                u64::from_be_bytes(len)
+            }
+            len => u64::from(len),
+        };
+        self.header_len = 0;
+        self.remaining_payload = payload_len;
+
+        // Control frames (opcode 0x8 and above) may interleave with the
+        // fragments of a data message and are capped at 125 bytes
+        if opcode < 0x8 {
+            self.message_len = self.message_len.saturating_add(payload_len);
+            if self.message_len > self.max {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("WebSocket message exceeds {} bytes", self.max),
+                ));
+            }
+            if fin {
+                self.message_len = 0;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Full length of the frame header starting with `partial`, once enough of
+/// it is known
+fn frame_header_len(partial: &[u8]) -> Option<usize> {
+    let second = *partial.get(1)?;
+    let extended = match second & 0x7f {
+        126 => 2,
+        127 => 8,
+        _ => 0,
+    };
+    let mask = if second & 0x80 == 0 { 0 } else { 4 };
+    Some(2 + extended + mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frame header for a frame with `payload_len` bytes
+    fn header(fin: bool, opcode: u8, payload_len: usize, masked: bool) -> Vec<u8> {
+        let first = if fin { 0x80 } else { 0 };
+        let mut out = vec![first | opcode];
+        let mask = if masked { 0x80 } else { 0 };
+        if payload_len < 126 {
+            out.push(mask | payload_len as u8);
+        } else if let Ok(len) = u16::try_from(payload_len) {
+            out.push(mask | 0x7e);
+            out.extend_from_slice(&len.to_be_bytes());
+        } else {
+            out.push(mask | 0x7f);
+            out.extend_from_slice(&(payload_len as u64).to_be_bytes());
+        }
+        if masked {
+            out.extend_from_slice(&[1, 2, 3, 4]);
+        }
+        out
+    }
+
+    fn frame(fin: bool, opcode: u8, payload_len: usize, masked: bool) -> Vec<u8> {
+        let mut out = header(fin, opcode, payload_len, masked);
+        out.resize(out.len() + payload_len, b'x');
+        out
+    }
+
+    #[test]
+    fn test_message_size_guard_allows_small_messages() {
+        let mut guard = MessageSizeGuard::new(1024);
+        let mut wire = frame(true, 0x1, 100, true);
+        wire.extend(frame(true, 0x2, 1024, false));
+        wire.extend(frame(true, 0x9, 4, true));
+
+        // Byte-at-a-time delivery parses the same as one chunk
+        for byte in &wire {
+            guard.feed(std::slice::from_ref(byte)).unwrap();
+        }
+        MessageSizeGuard::new(1024).feed(&wire).unwrap();
+    }
+
+    #[test]
+    fn test_message_size_guard_rejects_large_frame() {
+        let mut guard = MessageSizeGuard::new(1024);
+        guard.feed(&frame(true, 0x1, 10, true)).unwrap();
+
+        // Rejected from the header alone, before the payload arrives
+        let err = guard.feed(&header(true, 0x2, 70_000, true)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_message_size_guard_sums_fragments() {
+        let mut guard = MessageSizeGuard::new(1000);
+        guard.feed(&frame(false, 0x1, 600, true)).unwrap();
+        // A ping between fragments does not count towards the message
+        guard.feed(&frame(true, 0x9, 100, true)).unwrap();
+        assert!(guard.feed(&frame(true, 0x0, 600, true)).is_err());
+
+        let mut guard = MessageSizeGuard::new(1000);
+        guard.feed(&frame(true, 0x1, 600, true)).unwrap();
+        guard.feed(&frame(true, 0x1, 600, true)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_idle_connection_is_closed() {
+        let (mut a, mut a_peer) = tokio::io::duplex(1024);
+        let (mut b, _b_peer) = tokio::io::duplex(1024);
+        let limits = WebSocketLimits {
+            idle_timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let copy = tokio::spawn(async move { copy_websocket(&mut a, &mut b, limits).await });
+
+        a_peer.write_all(b"hello").await.unwrap();
+        let err = tokio::time::timeout(Duration::from_secs(2), copy)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn test_active_connection_stays_open() {
+        let (mut a, mut a_peer) = tokio::io::duplex(1024);
+        let (mut b, mut b_peer) = tokio::io::duplex(1024);
+        let limits = WebSocketLimits {
+            idle_timeout: Some(Duration::from_millis(150)),
+            max_message_size: Some(64),
+        };
+        let copy = tokio::spawn(async move { copy_websocket(&mut a, &mut b, limits).await });
+
+        let message = frame(true, 0x1, 5, true);
+        let mut received = vec![0u8; message.len()];
+        for _ in 0..6 {
+            tokio::time::sleep(Duration::from_millis(75)).await;
+            a_peer.write_all(&message).await.unwrap();
+            b_peer.read_exact(&mut received).await.unwrap();
+            assert_eq!(received, message);
+        }
+        assert!(!copy.is_finished());
+
+        drop(a_peer);
+        drop(b_peer);
+        let _ = copy.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_oversized_message_closes_connection() {
+        let (mut a, mut a_peer) = tokio::io::duplex(1024);
+        let (mut b, _b_peer) = tokio::io::duplex(1024);
+        let limits = WebSocketLimits {
+            max_message_size: Some(64),
+            ..Default::default()
+        };
+        let copy = tokio::spawn(async move { copy_websocket(&mut a, &mut b, limits).await });
+
+        a_peer
+            .write_all(&header(true, 0x2, 4096, true))
+            .await
+            .unwrap();
+        let err = copy.await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,7 +1,9 @@
-//! Server-Sent Events (SSE) support for real-time dashboard updates.
+//! Server-Sent Events (SSE) and WebSocket support for real-time dashboard updates.
 //!
 //! This module provides event broadcasting infrastructure for pushing
-//! real-time updates to connected dashboard clients.
+//! real-time updates to connected dashboard clients. Both transports share the
+//! same [`EventBroadcaster`] and JSON event payloads; the WebSocket transport
+//! additionally accepts control messages from the client.
 //!
 //! # Example
 //!
@@ -16,9 +18,10 @@
 //!     tunnel_id: some_uuid,
 //! });
 //!
-//! // Mount the SSE handler on your router
+//! // Mount the SSE and WebSocket handlers on your router
 //! let app = Router::new()
 //!     .route("/api/v1/events", get(events_handler))
+//!     .route("/api/v1/ws", get(ws_handler))
 //!     .with_state(broadcaster);
 //! ```
 
@@ -26,8 +29,14 @@ use std::convert::Infallible;
 use std::sync::Arc;
 
 use axum::{
-    extract::{Query, State},
-    response::sse::{Event, Sse},
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    response::{
+        sse::{Event, Sse},
+        Response,
+    },
 };
 use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
@@ -37,7 +46,7 @@ use uuid::Uuid;
 
 use super::models::{DashboardTunnelInfo, RequestLogEntry};
 
-/// Events that can be broadcast to dashboard clients via SSE.
+/// Events that can be broadcast to dashboard clients via SSE or WebSocket.
 #[derive(Debug, Clone, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum DashboardEvent {
@@ -59,6 +68,11 @@ impl DashboardEvent {
         }
     }
 
+    /// Returns true if this event passes the optional tunnel filter.
+    fn matches(&self, filter_tunnel_id: Option<Uuid>) -> bool {
+        filter_tunnel_id.is_none_or(|filter_id| self.tunnel_id() == Some(filter_id))
+    }
+
     /// Returns the SSE event type name for this event.
     fn event_type(&self) -> &'static str {
         match self {
@@ -69,7 +83,7 @@ impl DashboardEvent {
     }
 }
 
-/// Broadcasts dashboard events to connected SSE clients.
+/// Broadcasts dashboard events to connected SSE and WebSocket clients.
 ///
 /// Uses a tokio broadcast channel internally to support multiple subscribers.
 /// Events are dropped if no clients are connected.
@@ -144,10 +158,8 @@ pub async fn events_handler(
 
     let event_stream = stream.filter_map(move |result: Result<DashboardEvent, _>| match result {
         Ok(event) => {
-            if let Some(filter_id) = filter_tunnel_id {
-                if event.tunnel_id() != Some(filter_id) {
-                    return None;
-                }
+            if !event.matches(filter_tunnel_id) {
+                return None;
             }
 
             let event_type = event.event_type();
@@ -162,6 +174,98 @@ pub async fn events_handler(
     Sse::new(event_stream)
 }
 
+/// Control messages accepted from WebSocket clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ControlMessage {
+    /// Stop delivering events to this client.
+    Pause,
+    /// Resume delivering events to this client.
+    Resume,
+}
+
+/// Axum handler for the WebSocket events endpoint.
+///
+/// Streams the same JSON payloads as [`events_handler`] as text messages and
+/// accepts [`ControlMessage`]s to pause or resume the live feed. Each control
+/// message is acknowledged with `{"type":"status","paused":<bool>}`.
+///
+/// # Endpoint
+///
+/// `GET /api/v1/ws?tunnel_id=<uuid>`
+///
+/// # Control Messages
+///
+/// ```text
+/// {"action":"pause"}
+/// {"action":"resume"}
+/// ```
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(broadcaster): State<Arc<EventBroadcaster>>,
+    Query(query): Query<EventsQuery>,
+) -> Response {
+    let receiver = broadcaster.subscribe();
+    ws.on_upgrade(move |socket| handle_ws(socket, receiver, query.tunnel_id))
+}
+
+/// Parse a client control message, ignoring anything unrecognized.
+fn parse_control_message(text: &str) -> Option<ControlMessage> {
+    serde_json::from_str(text)
+        .map_err(|e| tracing::debug!("Ignoring invalid dashboard control message: {}", e))
+        .ok()
+}
+
+/// Forward broadcast events to a WebSocket client until either side closes.
+async fn handle_ws(
+    mut socket: WebSocket,
+    mut receiver: broadcast::Receiver<DashboardEvent>,
+    filter_tunnel_id: Option<Uuid>,
+) {
+    let mut paused = false;
+
+    loop {
+        tokio::select! {
+            event = receiver.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::debug!("Dashboard WebSocket skipped {} events", skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if paused || !event.matches(filter_tunnel_id) {
+                    continue;
+                }
+                let Ok(data) = serde_json::to_string(&event) else {
+                    continue;
+                };
+                if socket.send(Message::Text(data.into())).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => {
+                match message {
+                    Some(Ok(Message::Text(text))) => {
+                        let Some(control) = parse_control_message(&text) else {
+                            continue;
+                        };
+                        paused = control == ControlMessage::Pause;
+                        let status = serde_json::json!({ "type": "status", "paused": paused });
+                        let ack = Message::Text(status.to_string().into());
+                        if socket.send(ack).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -193,6 +297,89 @@ mod tests {
         assert_eq!(event.tunnel_id(), Some(tunnel_id));
     }
 
+    #[test]
+    fn test_control_message_parse() {
+        assert_eq!(
+            parse_control_message(r#"{"action":"pause"}"#),
+            Some(ControlMessage::Pause)
+        );
+        assert_eq!(
+            parse_control_message(r#"{"action":"resume"}"#),
+            Some(ControlMessage::Resume)
+        );
+        assert_eq!(parse_control_message(r#"{"action":"stop"}"#), None);
+        assert_eq!(parse_control_message("not json"), None);
+    }
+
+    /// Read the next WebSocket text message as JSON
+    async fn next_json<S>(ws: &mut S) -> serde_json::Value
+    where
+        S: futures_util::Stream<
+                Item = Result<
+                    tokio_tungstenite::tungstenite::Message,
+                    tokio_tungstenite::tungstenite::Error,
+                >,
+            > + Unpin,
+    {
+        let msg = tokio::time::timeout(std::time::Duration::from_secs(5), StreamExt::next(ws))
+            .await
+            .expect("timed out waiting for message")
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(msg.to_text().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_websocket_streams_events_and_pauses() {
+        use futures_util::SinkExt;
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let broadcaster = Arc::new(EventBroadcaster::new(10));
+        let app = axum::Router::new()
+            .route("/api/v1/ws", axum::routing::get(ws_handler))
+            .with_state(broadcaster.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/api/v1/ws"))
+            .await
+            .unwrap();
+        // The subscription exists once the upgrade completes
+        let tunnel_id = Uuid::new_v4();
+        broadcaster.send(DashboardEvent::TunnelDisconnected { tunnel_id });
+        let event = next_json(&mut ws).await;
+        assert_eq!(event["type"], "tunnel_disconnected");
+        assert_eq!(event["tunnel_id"], tunnel_id.to_string());
+
+        ws.send(WsMessage::Text(r#"{"action":"pause"}"#.into()))
+            .await
+            .unwrap();
+        let status = next_json(&mut ws).await;
+        assert_eq!(status["paused"], true);
+
+        // Dropped while paused
+        broadcaster.send(DashboardEvent::TunnelDisconnected {
+            tunnel_id: Uuid::new_v4(),
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        ws.send(WsMessage::Text(r#"{"action":"resume"}"#.into()))
+            .await
+            .unwrap();
+        let status = next_json(&mut ws).await;
+        assert_eq!(status["paused"], false);
+
+        let resumed_id = Uuid::new_v4();
+        broadcaster.send(DashboardEvent::TunnelDisconnected {
+            tunnel_id: resumed_id,
+        });
+        let event = next_json(&mut ws).await;
+        assert_eq!(event["tunnel_id"], resumed_id.to_string());
+    }
+
     #[tokio::test]
     async fn test_broadcast_send_receive() {
         let broadcaster = EventBroadcaster::new(10);
@@ -1,6 +1,7 @@
 //! HTTP handlers for the Dashboard API.
 
 use axum::{
+    body::Bytes,
     extract::{Path, Query, State},
     http::StatusCode,
     response::{IntoResponse, Response},
@@ -10,10 +11,14 @@ use serde::Deserialize;
 use uuid::Uuid;
 
 use super::models::{
-    ApiError, DashboardTunnelInfo, HealthResponse, RequestLogEntry, SharedDashboardState,
+    ApiError, DashboardTunnelInfo, HealthResponse, ReplayOverrides, RequestDetails,
+    RequestLogEntry, SharedDashboardState,
 };
 use std::str::FromStr;
 
+/// Maximum number of upstream response bytes returned in a replay preview.
+const REPLAY_PREVIEW_LIMIT: usize = 4096;
+
 /// Creates a JSON error response with consistent format.
 fn error_response(status: StatusCode, code: &str, message: impl Into<String>) -> Response {
     let error = ApiError {
@@ -39,9 +44,7 @@ pub async fn health_handler() -> Json<HealthResponse> {
 pub async fn list_tunnels_handler(
     State(state): State<SharedDashboardState>,
 ) -> Json<Vec<DashboardTunnelInfo>> {
-    let state = state.read().await;
-    let tunnels: Vec<DashboardTunnelInfo> = state.tunnels.values().cloned().collect();
-    Json(tunnels)
+    Json(state.read().await.list_tunnels())
 }
 
 /// Get a specific tunnel by ID.
@@ -62,9 +65,8 @@ pub async fn get_tunnel_handler(
         }
     };
 
-    let state = state.read().await;
-    match state.tunnels.get(&id) {
-        Some(tunnel) => Json(tunnel.clone()).into_response(),
+    match state.read().await.find_tunnel(id) {
+        Some(tunnel) => Json(tunnel).into_response(),
         None => error_response(
             StatusCode::NOT_FOUND,
             "NOT_FOUND",
@@ -74,12 +76,30 @@ pub async fn get_tunnel_handler(
 }
 
 /// Query parameters for listing requests.
-#[derive(Debug, Deserialize)]
+///
+/// Filters combine: a request is listed only if it matches every one given.
+#[derive(Debug, Default, Deserialize)]
 pub struct ListRequestsQuery {
     /// Maximum number of requests to return (default: 50, max: 200).
     pub limit: Option<usize>,
     /// Filter by tunnel ID.
     pub tunnel_id: Option<Uuid>,
+    /// Filter by HTTP method, case-insensitive (e.g. `post`).
+    pub method: Option<String>,
+    /// Filter by exact status code (e.g. `404`).
+    pub status: Option<u16>,
+    /// Filter by status class: `1xx` through `5xx`.
+    pub status_class: Option<String>,
+}
+
+/// First digit of a status class such as `5xx`
+fn parse_status_class(class: &str) -> Option<u16> {
+    match class.as_bytes() {
+        [digit @ b'1'..=b'5', x1, x2] if x1.eq_ignore_ascii_case(&b'x') && x1 == x2 => {
+            Some(u16::from(digit - b'0'))
+        }
+        _ => None,
+    }
 }
 
 /// List recent requests.
@@ -88,8 +108,19 @@ pub struct ListRequestsQuery {
 pub async fn list_requests_handler(
     State(state): State<SharedDashboardState>,
     Query(query): Query<ListRequestsQuery>,
-) -> Json<Vec<RequestLogEntry>> {
+) -> Response {
     let limit = query.limit.unwrap_or(50).min(200);
+    let status_class = match query.status_class.as_deref().map(parse_status_class) {
+        None => None,
+        Some(Some(class)) => Some(class),
+        Some(None) => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "BAD_REQUEST",
+                "status_class must be one of 1xx, 2xx, 3xx, 4xx or 5xx",
+            );
+        }
+    };
     let state = state.read().await;
 
     let entries: Vec<RequestLogEntry> = state
@@ -97,11 +128,19 @@ pub async fn list_requests_handler(
         .iter()
         .rev()
         .filter(|r| query.tunnel_id.is_none() || query.tunnel_id == Some(r.tunnel_id))
+        .filter(|r| {
+            query
+                .method
+                .as_deref()
+                .is_none_or(|method| r.method.eq_ignore_ascii_case(method))
+        })
+        .filter(|r| query.status.is_none_or(|status| r.status == status))
+        .filter(|r| status_class.is_none_or(|class| r.status / 100 == class))
         .take(limit)
         .map(RequestLogEntry::from)
         .collect();
 
-    Json(entries)
+    Json(entries).into_response()
 }
 
 /// Get full details for a specific request.
@@ -154,12 +193,63 @@ pub async fn metrics_handler() -> Response {
         .into_response()
 }
 
+/// A stored request with replay overrides applied.
+#[derive(Debug)]
+struct ReplayRequest {
+    method: reqwest::Method,
+    headers: Vec<(String, String)>,
+    body: Option<String>,
+}
+
+/// Merge `overrides` over the stored request details.
+///
+/// `Host` and `Content-Length` are always dropped since the client recomputes them.
+fn apply_overrides(
+    details: &RequestDetails,
+    overrides: ReplayOverrides,
+) -> Result<ReplayRequest, String> {
+    let method_str = overrides.method.as_deref().unwrap_or(&details.method);
+    let method = reqwest::Method::from_str(&method_str.to_ascii_uppercase())
+        .map_err(|_| format!("Invalid HTTP method: {}", method_str))?;
+
+    let mut headers: Vec<(String, String)> = details
+        .request_headers
+        .iter()
+        .filter(|(k, _)| {
+            !overrides
+                .headers
+                .keys()
+                .any(|name| name.eq_ignore_ascii_case(k))
+        })
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    headers.extend(
+        overrides
+            .headers
+            .into_iter()
+            .filter_map(|(k, v)| v.map(|v| (k, v))),
+    );
+    headers.retain(|(k, _)| {
+        !k.eq_ignore_ascii_case("host") && !k.eq_ignore_ascii_case("content-length")
+    });
+
+    Ok(ReplayRequest {
+        method,
+        headers,
+        body: overrides.body.or_else(|| details.request_body.clone()),
+    })
+}
+
 /// Replay a specific request.
 ///
 /// POST /api/v1/requests/:id/replay
+///
+/// An optional JSON body of [`ReplayOverrides`] changes the method, headers or
+/// body before sending; with no body the stored request is replayed unchanged.
 pub async fn replay_request_handler(
     State(state): State<SharedDashboardState>,
     Path(id_str): Path<String>,
+    body: Bytes,
 ) -> Response {
     let id = match Uuid::parse_str(&id_str) {
         Ok(u) => u,
@@ -172,8 +262,23 @@ pub async fn replay_request_handler(
         }
     };
 
+    let overrides = if body.is_empty() {
+        ReplayOverrides::default()
+    } else {
+        match serde_json::from_slice::<ReplayOverrides>(&body) {
+            Ok(o) => o,
+            Err(e) => {
+                return error_response(
+                    StatusCode::BAD_REQUEST,
+                    "INVALID_OVERRIDES",
+                    format!("Invalid replay overrides: {}", e),
+                );
+            }
+        }
+    };
+
     // 1. Fetch request and tunnel info
-    let (req_details, tunnel_addr) = {
+    let (req_details, tunnel) = {
         let state = state.read().await;
         let req = match state.requests.iter().find(|r| r.id == id) {
             Some(r) => r.clone(),
@@ -186,71 +291,66 @@ pub async fn replay_request_handler(
             }
         };
 
-        let tunnel = state.tunnels.get(&req.tunnel_id).cloned();
+        let tunnel = state.find_tunnel(req.tunnel_id);
         (req, tunnel)
     };
 
     // 2. Determine target URL
-    // If we have a tunnel record, use its local address.
-    // If no tunnel record (maybe restart?), we fail or try to guess?
-    // In our case, if tunnel is gone, we can't replay safely to "unknown".
-    // But for debugging, maybe we assume the user knows.
-    // However, the request details don't store the *original* local target, only the tunnel ID.
-    // So we rely on the tunnel being active.
-    let target_host = if let Some(t) = tunnel_addr {
-        t.local_addr
-    } else {
-        // Fallback or error?
-        // Let's error for safety.
+    // The request details don't store the *original* local target, only the
+    // tunnel ID, so we rely on the tunnel being active. A server does not
+    // know the client's local service, so it replays through the tunnel's
+    // public URL instead.
+    let Some(tunnel) = tunnel else {
         return error_response(
             StatusCode::UNPROCESSABLE_ENTITY,
             "TUNNEL_INACTIVE",
             "The tunnel for this request is no longer active",
         );
     };
-
-    // Construct URL (assuming HTTP)
-    let url = format!("http://{}{}", target_host, req_details.path);
-    tracing::info!("Replaying request {} to {}", id, url);
-
-    // 3. Prepare Client
-    let client = reqwest::Client::new();
-    let method = match reqwest::Method::from_str(&req_details.method) {
-        Ok(m) => m,
-        Err(_) => {
+    let url = match (tunnel.local_addr.as_str(), &tunnel.public_url) {
+        ("", Some(public_url)) => {
+            format!("{}{}", public_url.trim_end_matches('/'), req_details.path)
+        }
+        ("", None) => {
             return error_response(
-                StatusCode::BAD_REQUEST,
-                "INVALID_METHOD",
-                "Invalid HTTP method",
-            )
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "REPLAY_TARGET_UNKNOWN",
+                "The tunnel has neither a local address nor a public URL to replay to",
+            );
         }
+        // Construct URL (assuming HTTP)
+        (local_addr, _) => format!("http://{}{}", local_addr, req_details.path),
     };
 
-    let mut request_builder = client.request(method, &url);
+    // 3. Prepare Client
+    let replay = match apply_overrides(&req_details, overrides) {
+        Ok(r) => r,
+        Err(msg) => return error_response(StatusCode::BAD_REQUEST, "INVALID_METHOD", msg),
+    };
+    tracing::info!("Replaying request {} as {} {}", id, replay.method, url);
 
-    // Add Headers (skip some)
-    for (k, v) in &req_details.request_headers {
-        if k.eq_ignore_ascii_case("host") || k.eq_ignore_ascii_case("content-length") {
-            continue;
-        }
+    let client = reqwest::Client::new();
+    let mut request_builder = client.request(replay.method.clone(), &url);
+    for (k, v) in &replay.headers {
         request_builder = request_builder.header(k, v);
     }
-
-    // Add Body
-    if let Some(body) = req_details.request_body {
+    if let Some(body) = replay.body {
         request_builder = request_builder.body(body);
     }
 
-    // 4. Send Request (fire and forget? or wait?)
-    // User probably wants to know if it worked.
+    // 4. Send Request and report the upstream result
     match request_builder.send().await {
         Ok(res) => {
             let status = res.status();
             tracing::info!("Replay success: status {}", status);
+            let (preview, truncated) = read_preview(res).await;
             Json(serde_json::json!({
                 "status": "replayed",
+                "method": replay.method.as_str(),
                 "target": url,
-                "response_status": status.as_u16()
+                "response_status": status.as_u16(),
+                "response_body_preview": String::from_utf8_lossy(&preview),
+                "response_body_truncated": truncated
             }))
             .into_response()
         }
@@ -264,3 +364,324 @@ pub async fn replay_request_handler(
         }
     }
 }
+
+/// Read at most [`REPLAY_PREVIEW_LIMIT`] bytes of a replayed response body,
+/// dropping the rest unread. Also returns whether the body was longer.
+async fn read_preview(mut res: reqwest::Response) -> (Vec<u8>, bool) {
+    let mut preview = Vec::new();
+    while let Ok(Some(chunk)) = res.chunk().await {
+        let room = REPLAY_PREVIEW_LIMIT - preview.len();
+        if chunk.len() > room {
+            preview.extend_from_slice(&chunk[..room]);
+            return (preview, true);
+        }
+        preview.extend_from_slice(&chunk);
+    }
+    (preview, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dashboard::models::{DashboardState, TunnelStatus};
+    use axum::body::to_bytes;
+    use chrono::Utc;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    /// Local service that echoes the method, `x-debug` header and body it received
+    async fn start_echo_service() -> String {
+        let app = axum::Router::new().fallback(
+            |method: axum::http::Method, headers: axum::http::HeaderMap, body: String| async move {
+                let debug = headers
+                    .get("x-debug")
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or("-")
+                    .to_string();
+                let auth = headers.contains_key("authorization");
+                format!("{method} debug={debug} auth={auth} body={body}")
+            },
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        addr
+    }
+
+    /// Dashboard state holding one stored POST request to an active tunnel
+    fn state_with_request(local_addr: String) -> (SharedDashboardState, Uuid) {
+        let tunnel_id = Uuid::new_v4();
+        let request_id = Uuid::new_v4();
+        let mut state = DashboardState::new(10);
+        state.add_tunnel(DashboardTunnelInfo {
+            id: tunnel_id,
+            subdomain: None,
+            public_url: None,
+            local_addr,
+            client_addr: None,
+            created_at: Utc::now(),
+            status: TunnelStatus::Connected,
+            bytes_in: 0,
+            bytes_out: 0,
+        });
+        state.add_request(RequestDetails {
+            id: request_id,
+            tunnel_id,
+            method: "POST".to_string(),
+            path: "/hook".to_string(),
+            request_headers: HashMap::from([
+                ("Authorization".to_string(), "Bearer secret".to_string()),
+                ("Host".to_string(), "example.com".to_string()),
+            ]),
+            request_body: Some("original".to_string()),
+            status: 200,
+            response_headers: HashMap::new(),
+            response_body: None,
+            duration_ms: 1,
+            timestamp: Utc::now(),
+        });
+        (Arc::new(RwLock::new(state)), request_id)
+    }
+
+    async fn replay(state: SharedDashboardState, id: Uuid, body: &str) -> serde_json::Value {
+        let response = replay_request_handler(
+            State(state),
+            Path(id.to_string()),
+            Bytes::from(body.to_string()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replay_without_overrides_is_unchanged() {
+        let (state, id) = state_with_request(start_echo_service().await);
+        let result = replay(state, id, "").await;
+        assert_eq!(result["method"], "POST");
+        assert_eq!(
+            result["response_body_preview"],
+            "POST debug=- auth=true body=original"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_header_override() {
+        let (state, id) = state_with_request(start_echo_service().await);
+        let overrides = r#"{"headers":{"X-Debug":"1","authorization":null}}"#;
+        let result = replay(state, id, overrides).await;
+        assert_eq!(
+            result["response_body_preview"],
+            "POST debug=1 auth=false body=original"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_body_override() {
+        let (state, id) = state_with_request(start_echo_service().await);
+        let result = replay(state, id, r#"{"body":"{\"patched\":true}"}"#).await;
+        assert_eq!(
+            result["response_body_preview"],
+            r#"POST debug=- auth=true body={"patched":true}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_method_change() {
+        let (state, id) = state_with_request(start_echo_service().await);
+        let result = replay(state, id, r#"{"method":"put"}"#).await;
+        assert_eq!(result["method"], "PUT");
+        assert_eq!(result["response_status"], 200);
+        assert!(result["target"].as_str().unwrap().ends_with("/hook"));
+        assert_eq!(
+            result["response_body_preview"],
+            "PUT debug=- auth=true body=original"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_preview_is_truncated() {
+        let (state, id) = state_with_request(start_echo_service().await);
+        let long_body = "a".repeat(REPLAY_PREVIEW_LIMIT * 2);
+        let result = replay(state, id, &format!(r#"{{"body":"{long_body}"}}"#)).await;
+        let preview = result["response_body_preview"].as_str().unwrap();
+        assert_eq!(preview.len(), REPLAY_PREVIEW_LIMIT);
+        assert!(preview.starts_with("POST debug=- auth=true body=aaaa"));
+        assert_eq!(result["response_body_truncated"], true);
+    }
+
+    #[tokio::test]
+    async fn test_replay_without_target_rejected() {
+        // A server knows neither the local service nor, here, a public URL
+        let (state, id) = state_with_request(String::new());
+        let response =
+            replay_request_handler(State(state), Path(id.to_string()), Bytes::new()).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    /// Dashboard state holding requests with the given methods and statuses,
+    /// oldest first
+    fn state_with_requests(requests: &[(&str, u16)]) -> SharedDashboardState {
+        let mut state = DashboardState::new(10);
+        for (method, status) in requests {
+            state.add_request(RequestDetails {
+                id: Uuid::new_v4(),
+                tunnel_id: Uuid::new_v4(),
+                method: (*method).to_string(),
+                path: "/".to_string(),
+                request_headers: HashMap::new(),
+                request_body: None,
+                status: *status,
+                response_headers: HashMap::new(),
+                response_body: None,
+                duration_ms: 1,
+                timestamp: Utc::now(),
+            });
+        }
+        Arc::new(RwLock::new(state))
+    }
+
+    /// Method and status of every request listed for `query`, newest first
+    async fn list(state: &SharedDashboardState, query: ListRequestsQuery) -> Vec<(String, u16)> {
+        let response = list_requests_handler(State(state.clone()), Query(query)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let entries: Vec<serde_json::Value> = serde_json::from_slice(&bytes).unwrap();
+        entries
+            .iter()
+            .map(|entry| {
+                let method = entry["method"].as_str().unwrap().to_string();
+                let status = u16::try_from(entry["status"].as_u64().unwrap()).unwrap();
+                (method, status)
+            })
+            .collect()
+    }
+
+    fn listed(entries: &[(&str, u16)]) -> Vec<(String, u16)> {
+        entries
+            .iter()
+            .map(|(method, status)| ((*method).to_string(), *status))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_list_requests_filters() {
+        let state = state_with_requests(&[
+            ("GET", 200),
+            ("POST", 502),
+            ("GET", 404),
+            ("GET", 503),
+            ("POST", 201),
+        ]);
+
+        let all = list(&state, ListRequestsQuery::default()).await;
+        assert_eq!(all.len(), 5);
+
+        let query = ListRequestsQuery {
+            method: Some("post".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            list(&state, query).await,
+            listed(&[("POST", 201), ("POST", 502)])
+        );
+
+        let query = ListRequestsQuery {
+            status: Some(404),
+            ..Default::default()
+        };
+        assert_eq!(list(&state, query).await, listed(&[("GET", 404)]));
+
+        let query = ListRequestsQuery {
+            status_class: Some("5xx".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            list(&state, query).await,
+            listed(&[("GET", 503), ("POST", 502)])
+        );
+
+        // Filters combine with AND
+        let query = ListRequestsQuery {
+            method: Some("GET".to_string()),
+            status_class: Some("5XX".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(list(&state, query).await, listed(&[("GET", 503)]));
+        let query = ListRequestsQuery {
+            method: Some("POST".to_string()),
+            status: Some(404),
+            ..Default::default()
+        };
+        assert!(list(&state, query).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_requests_rejects_bad_status_class() {
+        let state = state_with_requests(&[("GET", 200)]);
+        for class in ["5", "6xx", "50x", "server"] {
+            let query = ListRequestsQuery {
+                status_class: Some(class.to_string()),
+                ..Default::default()
+            };
+            let response = list_requests_handler(State(state.clone()), Query(query)).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{class}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_invalid_overrides() {
+        let (state, id) = state_with_request(start_echo_service().await);
+        let response = replay_request_handler(
+            State(state),
+            Path(id.to_string()),
+            Bytes::from_static(b"{not json"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    /// Fixed tunnel list standing in for a server's session store
+    #[derive(Debug)]
+    struct StaticSource(Vec<DashboardTunnelInfo>);
+
+    impl crate::dashboard::models::TunnelSource for StaticSource {
+        fn tunnels(&self) -> Vec<DashboardTunnelInfo> {
+            self.0.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tunnels_from_source() {
+        let live = DashboardTunnelInfo::connected(
+            Uuid::new_v4(),
+            "live".to_string(),
+            "10.0.0.1:5000".to_string(),
+            std::time::Duration::from_secs(60),
+        );
+        let mut state =
+            DashboardState::new(10).with_tunnel_source(Arc::new(StaticSource(vec![live.clone()])));
+        // Tunnels added to the state are ignored once a source is set
+        state.add_tunnel(DashboardTunnelInfo::connected(
+            Uuid::new_v4(),
+            "stale".to_string(),
+            "10.0.0.2:5000".to_string(),
+            std::time::Duration::ZERO,
+        ));
+        let state = Arc::new(RwLock::new(state));
+
+        let Json(tunnels) = list_tunnels_handler(State(state.clone())).await;
+        assert_eq!(tunnels.len(), 1);
+        assert_eq!(tunnels[0].subdomain.as_deref(), Some("live"));
+        assert!(tunnels[0].created_at < Utc::now());
+
+        let res = get_tunnel_handler(State(state.clone()), Path(live.id.to_string())).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = get_tunnel_handler(State(state), Path(Uuid::new_v4().to_string())).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,6 +1,6 @@
 //! Dashboard API module for tunnel inspection and monitoring.
 //!
-//! This module provides a REST API and SSE endpoint for the FerroTunnel dashboard.
+//! This module provides a REST API plus SSE and WebSocket endpoints for the FerroTunnel dashboard.
 //!
 //! # Usage
 //!
@@ -27,8 +27,8 @@ pub mod models;
 
 pub use events::{DashboardEvent, EventBroadcaster};
 pub use models::{
-    ApiError, DashboardState, DashboardTunnelInfo, HealthResponse, RequestDetails, RequestLogEntry,
-    SharedDashboardState, TunnelStatus,
+    ApiError, DashboardState, DashboardTunnelInfo, HealthResponse, ReplayOverrides, RequestDetails,
+    RequestLogEntry, SharedDashboardState, TunnelSource, TunnelStatus,
 };
 
 use std::sync::Arc;
@@ -39,29 +39,8 @@ use axum::{
     routing::{get, post},
     Router,
 };
-use tower_http::{
-    compression::CompressionLayer,
-    cors::{Any, CorsLayer},
-    trace::TraceLayer,
-};
+use tower_http::{compression::CompressionLayer, trace::TraceLayer};
 
-/// Creates the dashboard API router with all endpoints.
-///
-/// # Arguments
-///
-/// * `state` - Shared dashboard state for tunnel and request data.
-/// * `broadcaster` - Event broadcaster for SSE streaming.
-///
-/// # Endpoints
-///
-/// - `GET /api/v1/health` - Health check
-/// - `GET /api/v1/tunnels` - List all tunnels
-/// - `GET /api/v1/tunnels/:id` - Get tunnel by ID
-/// - `GET /api/v1/requests` - List recent requests
-/// - `GET /api/v1/requests/:id` - Get request details
-/// - `GET /api/v1/requests/:id/replay` - Replay a request
-/// - `GET /api/v1/metrics` - Prometheus metrics
-/// - `GET /api/v1/events` - SSE event stream
 // Embedded assets
 #[derive(rust_embed::RustEmbed)]
 #[folder = "src/dashboard/static/"]
@@ -84,6 +63,27 @@ async fn static_handler(uri: axum::http::Uri) -> impl IntoResponse {
     }
 }
 
+/// Creates the dashboard API router with all endpoints.
+///
+/// # Arguments
+///
+/// * `state` - Shared dashboard state for tunnel and request data.
+/// * `broadcaster` - Event broadcaster for SSE and WebSocket streaming.
+///
+/// # Endpoints
+///
+/// - `GET /api/v1/health` - Health check
+/// - `GET /api/v1/tunnels` - List all tunnels
+/// - `GET /api/v1/tunnels/:id` - Get tunnel by ID
+/// - `GET /api/v1/requests` - List recent requests
+/// - `GET /api/v1/requests/:id` - Get request details
+/// - `POST /api/v1/requests/:id/replay` - Replay a request, optionally with overrides
+/// - `GET /api/v1/metrics` - Prometheus metrics
+/// - `GET /api/v1/events` - SSE event stream
+/// - `GET /api/v1/ws` - WebSocket event stream with pause/resume control
+///
+/// No CORS headers are sent, so browsers only let the bundled UI, served
+/// from the same origin, call the API.
 pub fn create_router(state: SharedDashboardState, broadcaster: Arc<EventBroadcaster>) -> Router {
     let api_routes = Router::new()
         .route("/health", get(handlers::health_handler))
@@ -98,6 +98,7 @@ pub fn create_router(state: SharedDashboardState, broadcaster: Arc<EventBroadcas
         .route("/metrics", get(handlers::metrics_handler))
         .with_state(state)
         .route("/events", get(events::events_handler))
+        .route("/ws", get(events::ws_handler))
         .with_state(broadcaster);
 
     Router::new()
@@ -105,12 +106,6 @@ pub fn create_router(state: SharedDashboardState, broadcaster: Arc<EventBroadcas
         .fallback(static_handler)
         .layer(CompressionLayer::new())
         .layer(TraceLayer::new_for_http())
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(Any)
-                .allow_headers(Any),
-        )
 }
 
 /// Configuration for the dashboard server.
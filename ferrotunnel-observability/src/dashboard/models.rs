@@ -4,6 +4,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
@@ -22,9 +23,42 @@ pub struct DashboardTunnelInfo {
     pub id: Uuid,
     pub subdomain: Option<String>,
     pub public_url: Option<String>,
+    /// Local service the client forwards to; empty when unknown, as on a
+    /// server
     pub local_addr: String,
+    /// Address the tunnel's client connected from, when seen by a server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_addr: Option<String>,
     pub created_at: DateTime<Utc>,
     pub status: TunnelStatus,
+    /// Data bytes received from the server through this tunnel
+    #[serde(default)]
+    pub bytes_in: u64,
+    /// Data bytes sent to the server through this tunnel
+    #[serde(default)]
+    pub bytes_out: u64,
+}
+
+impl DashboardTunnelInfo {
+    /// A tunnel connected to a server for `uptime`.
+    ///
+    /// The server cannot see the client's local service, so `local_addr` is
+    /// left empty and `client_addr` holds the address the client connected
+    /// from.
+    pub fn connected(id: Uuid, tunnel_id: String, client_addr: String, uptime: Duration) -> Self {
+        let uptime = chrono::Duration::from_std(uptime).unwrap_or_default();
+        Self {
+            id,
+            subdomain: Some(tunnel_id),
+            public_url: None,
+            local_addr: String::new(),
+            client_addr: Some(client_addr),
+            created_at: Utc::now() - uptime,
+            status: TunnelStatus::Connected,
+            bytes_in: 0,
+            bytes_out: 0,
+        }
+    }
 }
 
 /// Summary of a request for listing.
@@ -69,6 +103,20 @@ impl From<&RequestDetails> for RequestLogEntry {
     }
 }
 
+/// Overrides applied to a stored request before it is replayed.
+///
+/// Every field is optional; an empty override replays the request unchanged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplayOverrides {
+    /// Replacement HTTP method.
+    pub method: Option<String>,
+    /// Headers to set (string value) or remove (`null`), matched case-insensitively.
+    pub headers: HashMap<String, Option<String>>,
+    /// Replacement request body.
+    pub body: Option<String>,
+}
+
 /// API error response.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiError {
@@ -83,12 +131,23 @@ pub struct HealthResponse {
     pub version: String,
 }
 
+/// Live list of tunnels, read on every request to the tunnel endpoints.
+///
+/// Servers implement it over their session store so the dashboard shows the
+/// tunnels actually connected rather than a copy kept in [`DashboardState`].
+pub trait TunnelSource: Send + Sync + std::fmt::Debug {
+    /// Every tunnel currently connected.
+    fn tunnels(&self) -> Vec<DashboardTunnelInfo>;
+}
+
 /// Shared dashboard state containing tunnels and request history.
 #[derive(Debug)]
 pub struct DashboardState {
     pub tunnels: HashMap<Uuid, DashboardTunnelInfo>,
     pub requests: VecDeque<RequestDetails>,
     pub max_requests: usize,
+    /// Replaces `tunnels` as the tunnel list when set.
+    pub tunnel_source: Option<Arc<dyn TunnelSource>>,
 }
 
 impl DashboardState {
@@ -98,6 +157,30 @@ impl DashboardState {
             tunnels: HashMap::new(),
             requests: VecDeque::with_capacity(max_requests),
             max_requests,
+            tunnel_source: None,
+        }
+    }
+
+    /// List tunnels from `source` instead of the ones added to this state.
+    #[must_use]
+    pub fn with_tunnel_source(mut self, source: Arc<dyn TunnelSource>) -> Self {
+        self.tunnel_source = Some(source);
+        self
+    }
+
+    /// Tunnels to show: the live source when set, otherwise the added ones.
+    pub fn list_tunnels(&self) -> Vec<DashboardTunnelInfo> {
+        match &self.tunnel_source {
+            Some(source) => source.tunnels(),
+            None => self.tunnels.values().cloned().collect(),
+        }
+    }
+
+    /// The tunnel with `id`, looked up like [`list_tunnels`](Self::list_tunnels).
+    pub fn find_tunnel(&self, id: Uuid) -> Option<DashboardTunnelInfo> {
+        match &self.tunnel_source {
+            Some(source) => source.tunnels().into_iter().find(|tunnel| tunnel.id == id),
+            None => self.tunnels.get(&id).cloned(),
         }
     }
 
@@ -114,6 +197,14 @@ impl DashboardState {
         self.tunnels.insert(tunnel.id, tunnel);
     }
 
+    /// Updates a tunnel's byte counters; unknown tunnels are ignored.
+    pub fn set_tunnel_traffic(&mut self, id: Uuid, bytes_in: u64, bytes_out: u64) {
+        if let Some(tunnel) = self.tunnels.get_mut(&id) {
+            tunnel.bytes_in = bytes_in;
+            tunnel.bytes_out = bytes_out;
+        }
+    }
+
     /// Removes a tunnel from the state.
     pub fn remove_tunnel(&mut self, id: Uuid) -> Option<DashboardTunnelInfo> {
         self.tunnels.remove(&id)
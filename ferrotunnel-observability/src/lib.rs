@@ -5,7 +5,8 @@ pub mod tracing;
 pub mod dashboard;
 
 pub use metrics::{
-    gather_metrics, init_metrics, metrics_enabled, tunnel_metrics, TunnelMetrics, REGISTRY,
+    gather_metrics, init_metrics, metrics_enabled, tunnel_metrics, TunnelMetrics, TunnelUsage,
+    TunnelUsageMetrics, DEFAULT_MAX_TUNNEL_LABELS, OVERFLOW_TUNNEL_LABEL, REGISTRY,
 };
 pub use tracing::{init_tracing, shutdown_tracing, TracingConfig};
 
@@ -5,9 +5,15 @@
 //! - **Units**: in the name (e.g. `_seconds`, `_bytes`)
 //! - **Gauges**: descriptive names, no `_total`
 
-use prometheus::{register_counter, register_gauge, register_histogram, Counter, Gauge, Histogram};
+use prometheus::core::Collector;
+use prometheus::{
+    register_counter, register_counter_vec, register_gauge, register_histogram, Counter,
+    CounterVec, Gauge, Histogram, Opts,
+};
+use std::collections::HashMap;
 use std::sync::LazyLock;
 use std::sync::OnceLock;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use prometheus::Registry;
@@ -18,7 +24,15 @@ pub static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
 /// Global tunnel metrics. Set when [`init_metrics`] is called.
 static TUNNEL_METRICS: OnceLock<TunnelMetrics> = OnceLock::new();
 
-/// Tunnel-level metrics: frames, bytes, decode/encode latency, queue depth.
+/// Default number of tunnels labelled in per-tunnel usage metrics.
+pub const DEFAULT_MAX_TUNNEL_LABELS: usize = 1000;
+
+/// `tunnel_id` label shared by tunnels beyond the label cap.
+pub const OVERFLOW_TUNNEL_LABEL: &str = "other";
+
+/// Tunnel-level metrics: frames, bytes, decode/encode latency, queue depth,
+/// active sessions/streams, stream lifetimes, control-connection RTT and
+/// handshake outcomes and latency.
 ///
 /// All values are exported to Prometheus when [`gather_metrics`] is called.
 #[derive(Debug)]
@@ -28,6 +42,14 @@ pub struct TunnelMetrics {
     decode_latency: Histogram,
     encode_latency: Histogram,
     queue_depth: Gauge,
+    active_sessions: Gauge,
+    active_streams: Gauge,
+    stream_duration: Histogram,
+    control_rtt_ms: Gauge,
+    handshakes: CounterVec,
+    handshake_duration: Histogram,
+    client_connect_duration: Histogram,
+    tunnel_usage: OnceLock<TunnelUsageMetrics>,
 }
 
 impl TunnelMetrics {
@@ -63,15 +85,85 @@ impl TunnelMetrics {
         )
         .expect("register ferrotunnel_tunnel_queue_depth");
 
+        let active_sessions = register_gauge!(
+            "ferrotunnel_active_sessions",
+            "Current number of connected tunnel sessions"
+        )
+        .expect("register ferrotunnel_active_sessions");
+
+        let active_streams = register_gauge!(
+            "ferrotunnel_active_streams",
+            "Current number of open virtual streams"
+        )
+        .expect("register ferrotunnel_active_streams");
+
+        let stream_duration = register_histogram!(
+            "ferrotunnel_stream_duration_seconds",
+            "Lifetime of virtual streams from open to close",
+            vec![0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 15.0, 60.0, 300.0, 1800.0, 3600.0]
+        )
+        .expect("register ferrotunnel_stream_duration_seconds");
+
+        let control_rtt_ms = register_gauge!(
+            "ferrotunnel_control_rtt_ms",
+            "Round-trip time of the last heartbeat on the client control connection"
+        )
+        .expect("register ferrotunnel_control_rtt_ms");
+
+        let handshakes = register_counter_vec!(
+            "ferrotunnel_handshakes_total",
+            "Server-side handshakes by outcome",
+            &["status"]
+        )
+        .expect("register ferrotunnel_handshakes_total");
+
+        let handshake_duration = register_histogram!(
+            "ferrotunnel_handshake_duration_seconds",
+            "Time from accepting a control connection to answering its handshake"
+        )
+        .expect("register ferrotunnel_handshake_duration_seconds");
+
+        let client_connect_duration = register_histogram!(
+            "ferrotunnel_client_connect_duration_seconds",
+            "Client time to connect to the server and complete the handshake"
+        )
+        .expect("register ferrotunnel_client_connect_duration_seconds");
+
         Self {
             frames_processed,
             bytes_transferred,
             decode_latency,
             encode_latency,
             queue_depth,
+            active_sessions,
+            active_streams,
+            stream_duration,
+            control_rtt_ms,
+            handshakes,
+            handshake_duration,
+            client_connect_duration,
+            tunnel_usage: OnceLock::new(),
         }
     }
 
+    /// Start counting requests and bytes per tunnel, labelling at most
+    /// `max_tunnels` tunnels by ID; the rest are counted under
+    /// [`OVERFLOW_TUNNEL_LABEL`]. Later calls keep the first cap.
+    pub fn enable_tunnel_usage(&self, max_tunnels: usize) {
+        self.tunnel_usage.get_or_init(|| {
+            let usage = TunnelUsageMetrics::new(max_tunnels);
+            if let Err(e) = usage.register(prometheus::default_registry()) {
+                tracing::warn!("Failed to register per-tunnel metrics: {}", e);
+            }
+            usage
+        });
+    }
+
+    /// Per-tunnel usage metrics, if enabled with [`Self::enable_tunnel_usage`].
+    pub fn tunnel_usage(&self) -> Option<&TunnelUsageMetrics> {
+        self.tunnel_usage.get()
+    }
+
     /// Record a decode operation (frames decoded, bytes, and latency).
     #[inline]
     pub fn record_decode(&self, frames: usize, bytes: usize, latency: Duration) {
@@ -99,6 +191,75 @@ impl TunnelMetrics {
     pub fn record_bytes(&self, bytes: usize) {
         self.bytes_transferred.inc_by(bytes as f64);
     }
+
+    /// Set the current number of active sessions (gauge).
+    #[inline]
+    pub fn set_active_sessions(&self, count: usize) {
+        self.active_sessions.set(count as f64);
+    }
+
+    /// Record a virtual stream being opened.
+    #[inline]
+    pub fn stream_opened(&self) {
+        self.active_streams.inc();
+    }
+
+    /// Record a virtual stream being closed after `lifetime`.
+    #[inline]
+    pub fn stream_closed(&self, lifetime: Duration) {
+        self.active_streams.dec();
+        self.stream_duration.observe(lifetime.as_secs_f64());
+    }
+
+    /// Set the round-trip time of the last heartbeat (gauge, milliseconds).
+    #[inline]
+    pub fn set_control_rtt(&self, rtt: Duration) {
+        self.control_rtt_ms.set(rtt.as_secs_f64() * 1000.0);
+    }
+
+    /// Record a server-side handshake that ended with `status` (e.g. `success`,
+    /// `invalid_token`) after `duration`.
+    #[inline]
+    pub fn record_handshake(&self, status: &str, duration: Duration) {
+        self.handshakes.with_label_values(&[status]).inc();
+        self.handshake_duration.observe(duration.as_secs_f64());
+    }
+
+    /// Record a client connecting and completing its handshake in `duration`.
+    #[inline]
+    pub fn record_client_connect(&self, duration: Duration) {
+        self.client_connect_duration.observe(duration.as_secs_f64());
+    }
+
+    /// Number of server-side handshakes that ended with `status`.
+    pub fn handshakes(&self, status: &str) -> f64 {
+        self.handshakes.with_label_values(&[status]).get()
+    }
+
+    /// Number of client connects recorded.
+    pub fn client_connects(&self) -> u64 {
+        self.client_connect_duration.get_sample_count()
+    }
+
+    /// Round-trip time of the last heartbeat, in milliseconds.
+    pub fn control_rtt_ms(&self) -> f64 {
+        self.control_rtt_ms.get()
+    }
+
+    /// Current number of open virtual streams.
+    pub fn active_streams(&self) -> f64 {
+        self.active_streams.get()
+    }
+
+    /// Number of virtual streams recorded as closed.
+    pub fn streams_closed(&self) -> u64 {
+        self.stream_duration.get_sample_count()
+    }
+
+    /// Current number of active sessions.
+    pub fn active_sessions(&self) -> f64 {
+        self.active_sessions.get()
+    }
 }
 
 impl Default for TunnelMetrics {
@@ -107,6 +268,190 @@ impl Default for TunnelMetrics {
     }
 }
 
+/// Per-tunnel request and byte counters for usage reporting (billing, quotas).
+///
+/// Exported as `ferrotunnel_requests_total{tunnel_id}` and
+/// `ferrotunnel_bytes_total{tunnel_id, direction}`, where `direction` is `in`
+/// for bytes received from the tunnel client and `out` for bytes sent to it.
+/// At most `max_tunnels` connected tunnels get their own label; others share
+/// [`OVERFLOW_TUNNEL_LABEL`] so label cardinality stays bounded. A tunnel's
+/// label is released, and its series removed, once its last
+/// [`TunnelUsage`] handle is dropped.
+#[derive(Debug)]
+pub struct TunnelUsageMetrics {
+    requests: CounterVec,
+    bytes: CounterVec,
+    max_tunnels: usize,
+    /// Live [`TunnelUsage`] handles of each tunnel that has its own label
+    labelled: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl TunnelUsageMetrics {
+    /// Create unregistered counters labelling at most `max_tunnels` tunnels.
+    pub fn new(max_tunnels: usize) -> Self {
+        let requests = CounterVec::new(
+            Opts::new(
+                "ferrotunnel_requests_total",
+                "Requests forwarded through each tunnel",
+            ),
+            &["tunnel_id"],
+        )
+        .unwrap_or_else(|_| unreachable!("valid ferrotunnel_requests_total options"));
+        let bytes = CounterVec::new(
+            Opts::new(
+                "ferrotunnel_bytes_total",
+                "Payload bytes carried by each tunnel, by direction",
+            ),
+            &["tunnel_id", "direction"],
+        )
+        .unwrap_or_else(|_| unreachable!("valid ferrotunnel_bytes_total options"));
+        Self {
+            requests,
+            bytes,
+            max_tunnels,
+            labelled: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Register the counters with `registry`.
+    pub fn register(&self, registry: &Registry) -> prometheus::Result<()> {
+        registry.register(Box::new(self.requests.clone()))?;
+        registry.register(Box::new(self.bytes.clone()))
+    }
+
+    /// Counters for `tunnel_id`, resolved once so recording takes no lock or
+    /// label lookup. Claims a label for the tunnel while one is free;
+    /// otherwise its traffic is counted under [`OVERFLOW_TUNNEL_LABEL`].
+    pub fn tunnel(&self, tunnel_id: &str) -> TunnelUsage {
+        let slot = self.claim_label(tunnel_id);
+        let label = if slot.is_some() {
+            tunnel_id
+        } else {
+            OVERFLOW_TUNNEL_LABEL
+        };
+        TunnelUsage {
+            requests: self.requests.with_label_values(&[label]),
+            bytes_in: self.bytes.with_label_values(&[label, "in"]),
+            bytes_out: self.bytes.with_label_values(&[label, "out"]),
+            _slot: slot.map(Arc::new),
+        }
+    }
+
+    /// Requests counted under the `tunnel_id` label.
+    pub fn requests(&self, tunnel_id: &str) -> f64 {
+        counter_value(&self.requests, &[("tunnel_id", tunnel_id)])
+    }
+
+    /// Bytes counted under the `tunnel_id` label in `direction`.
+    pub fn bytes(&self, tunnel_id: &str, direction: &str) -> f64 {
+        counter_value(
+            &self.bytes,
+            &[("tunnel_id", tunnel_id), ("direction", direction)],
+        )
+    }
+
+    /// Number of tunnels with their own label.
+    pub fn labelled_tunnels(&self) -> usize {
+        self.labelled.lock().map_or(0, |labelled| labelled.len())
+    }
+
+    /// Take a handle on `tunnel_id`'s label, claiming a free one for new IDs
+    fn claim_label(&self, tunnel_id: &str) -> Option<LabelSlot> {
+        let mut labelled = self.labelled.lock().ok()?;
+        if let Some(handles) = labelled.get_mut(tunnel_id) {
+            *handles += 1;
+        } else if labelled.len() < self.max_tunnels {
+            labelled.insert(tunnel_id.to_string(), 1);
+        } else {
+            return None;
+        }
+        Some(LabelSlot {
+            tunnel_id: tunnel_id.to_string(),
+            requests: self.requests.clone(),
+            bytes: self.bytes.clone(),
+            labelled: self.labelled.clone(),
+        })
+    }
+}
+
+/// Value of the series of `vec` with exactly `labels`, 0 when there is none;
+/// unlike `with_label_values`, never creates the series
+fn counter_value(vec: &CounterVec, labels: &[(&str, &str)]) -> f64 {
+    vec.collect()
+        .iter()
+        .flat_map(prometheus::proto::MetricFamily::get_metric)
+        .find(|metric| {
+            let pairs = metric.get_label();
+            pairs.len() == labels.len()
+                && labels.iter().all(|(name, value)| {
+                    pairs
+                        .iter()
+                        .any(|pair| pair.name() == *name && pair.value() == *value)
+                })
+        })
+        .map_or(0.0, |metric| metric.get_counter().value())
+}
+
+/// Usage counters of one tunnel, from [`TunnelUsageMetrics::tunnel`]
+///
+/// Clones share the tunnel's label; it is released when the last clone of
+/// the last handle for the tunnel is dropped.
+#[derive(Debug, Clone)]
+pub struct TunnelUsage {
+    requests: Counter,
+    bytes_in: Counter,
+    bytes_out: Counter,
+    _slot: Option<Arc<LabelSlot>>,
+}
+
+impl TunnelUsage {
+    /// Record a request forwarded to the tunnel.
+    #[inline]
+    pub fn record_request(&self) {
+        self.requests.inc();
+    }
+
+    /// Record `bytes` received from the tunnel client.
+    #[inline]
+    pub fn record_bytes_in(&self, bytes: usize) {
+        self.bytes_in.inc_by(bytes as f64);
+    }
+
+    /// Record `bytes` sent to the tunnel client.
+    #[inline]
+    pub fn record_bytes_out(&self, bytes: usize) {
+        self.bytes_out.inc_by(bytes as f64);
+    }
+}
+
+/// A [`TunnelUsage`] handle's claim on its tunnel's label
+#[derive(Debug)]
+struct LabelSlot {
+    tunnel_id: String,
+    requests: CounterVec,
+    bytes: CounterVec,
+    labelled: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl Drop for LabelSlot {
+    fn drop(&mut self) {
+        let Ok(mut labelled) = self.labelled.lock() else {
+            return;
+        };
+        let Some(handles) = labelled.get_mut(&self.tunnel_id) else {
+            return;
+        };
+        *handles -= 1;
+        if *handles == 0 {
+            labelled.remove(&self.tunnel_id);
+            let tunnel_id = self.tunnel_id.as_str();
+            let _ = self.requests.remove_label_values(&[tunnel_id]);
+            let _ = self.bytes.remove_label_values(&[tunnel_id, "in"]);
+            let _ = self.bytes.remove_label_values(&[tunnel_id, "out"]);
+        }
+    }
+}
+
 /// Returns the global tunnel metrics, if metrics have been initialized.
 #[inline]
 pub fn tunnel_metrics() -> Option<&'static TunnelMetrics> {
@@ -116,7 +461,7 @@ pub fn tunnel_metrics() -> Option<&'static TunnelMetrics> {
 /// Initialize the metrics system and register tunnel metrics.
 pub fn init_metrics() {
     let _ = LazyLock::force(&REGISTRY);
-    let _ = TUNNEL_METRICS.set(TunnelMetrics::new());
+    TUNNEL_METRICS.get_or_init(TunnelMetrics::new);
     tracing::info!("Metrics infrastructure initialized");
 }
 
@@ -135,3 +480,79 @@ pub fn gather_metrics() -> String {
     encoder.encode(&metric_families, &mut buffer).unwrap();
     String::from_utf8(buffer).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tunnel_usage_counts_per_tunnel() {
+        let usage = TunnelUsageMetrics::new(10);
+        let app = usage.tunnel("app");
+        let api = usage.tunnel("api");
+        app.record_request();
+        app.clone().record_request();
+        api.record_request();
+        app.record_bytes_in(100);
+        app.record_bytes_out(40);
+        app.record_bytes_in(5);
+
+        assert!((usage.requests("app") - 2.0).abs() < f64::EPSILON);
+        assert!((usage.requests("api") - 1.0).abs() < f64::EPSILON);
+        assert!((usage.bytes("app", "in") - 105.0).abs() < f64::EPSILON);
+        assert!((usage.bytes("app", "out") - 40.0).abs() < f64::EPSILON);
+        assert_eq!(usage.labelled_tunnels(), 2);
+    }
+
+    #[test]
+    fn test_tunnel_usage_caps_labels() {
+        let usage = TunnelUsageMetrics::new(2);
+        let handles: Vec<_> = ["a", "b", "c", "d"]
+            .into_iter()
+            .map(|tunnel_id| usage.tunnel(tunnel_id))
+            .collect();
+        for handle in &handles {
+            handle.record_request();
+            handle.record_bytes_out(10);
+        }
+        // Known tunnels keep their label once the cap is reached
+        usage.tunnel("a").record_request();
+
+        assert_eq!(usage.labelled_tunnels(), 2);
+        assert!((usage.requests("a") - 2.0).abs() < f64::EPSILON);
+        assert!((usage.requests(OVERFLOW_TUNNEL_LABEL) - 2.0).abs() < f64::EPSILON);
+        assert!((usage.bytes(OVERFLOW_TUNNEL_LABEL, "out") - 20.0).abs() < f64::EPSILON);
+        assert!(usage.requests("c").abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_tunnel_usage_releases_labels() {
+        let usage = TunnelUsageMetrics::new(1);
+        let first = usage.tunnel("a");
+        let second = usage.tunnel("a");
+        first.record_request();
+        drop(first);
+        // Still held by the other session of the tunnel
+        assert_eq!(usage.labelled_tunnels(), 1);
+        assert!((usage.requests("a") - 1.0).abs() < f64::EPSILON);
+
+        drop(second);
+        assert_eq!(usage.labelled_tunnels(), 0);
+        assert!(usage.requests("a").abs() < f64::EPSILON);
+
+        // The freed label goes to the next tunnel
+        let third = usage.tunnel("b");
+        third.record_request();
+        assert!((usage.requests("b") - 1.0).abs() < f64::EPSILON);
+        assert!(usage.requests(OVERFLOW_TUNNEL_LABEL).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_tunnel_usage_getters_create_no_series() {
+        let usage = TunnelUsageMetrics::new(10);
+        assert!(usage.requests("never-seen").abs() < f64::EPSILON);
+        assert!(usage.bytes("never-seen", "in").abs() < f64::EPSILON);
+        assert!(usage.requests.collect()[0].get_metric().is_empty());
+        assert!(usage.bytes.collect()[0].get_metric().is_empty());
+    }
+}
@@ -1,9 +1,12 @@
 use opentelemetry::global;
+use opentelemetry::propagation::TextMapPropagator;
 use opentelemetry::trace::TracerProvider;
 use opentelemetry_otlp::{SpanExporter, WithExportConfig};
 use opentelemetry_sdk::propagation::TraceContextPropagator;
 use opentelemetry_sdk::trace::SdkTracerProvider;
 use opentelemetry_sdk::Resource;
+use std::collections::HashMap;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{EnvFilter, Registry};
@@ -69,6 +72,24 @@ pub fn init_tracing(config: TracingConfig) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Run `f` with the remote span named by a W3C `traceparent` value as the
+/// current OpenTelemetry context, so spans created inside `f` with no local
+/// parent span are exported as its children
+pub fn with_remote_parent<T>(traceparent: &str, f: impl FnOnce() -> T) -> T {
+    let carrier = HashMap::from([("traceparent".to_string(), traceparent.to_string())]);
+    let context = TraceContextPropagator::new().extract(&carrier);
+    let _guard = context.attach();
+    f()
+}
+
+/// W3C `traceparent` value naming `span` as exported to OpenTelemetry;
+/// `None` when spans are not being exported
+pub fn span_traceparent(span: &tracing::Span) -> Option<String> {
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(&span.context(), &mut carrier);
+    carrier.remove("traceparent")
+}
+
 /// Shutdown the tracing system and flush spans
 pub fn shutdown_tracing() {
     if let Some(provider) = TRACER_PROVIDER.get() {
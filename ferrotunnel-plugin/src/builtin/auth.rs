@@ -29,6 +29,11 @@ impl Plugin for TokenAuthPlugin {
         "token-auth"
     }
 
+    /// Authentication runs before everything else
+    fn priority(&self) -> i32 {
+        -100
+    }
+
     async fn on_request(
         &self,
         req: &mut http::Request<()>,
@@ -52,6 +57,7 @@ impl Plugin for TokenAuthPlugin {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::traits::ByteCounter;
 
     #[tokio::test]
     async fn test_valid_token_allows_request() {
@@ -66,8 +72,10 @@ mod tests {
         let ctx = RequestContext {
             tunnel_id: "test".into(),
             session_id: "session".into(),
+            request_id: "request".into(),
             remote_addr: "127.0.0.1:1234".parse().unwrap(),
             timestamp: std::time::SystemTime::now(),
+            request_bytes: ByteCounter::default(),
         };
 
         let action = plugin.on_request(&mut req, &ctx).await.unwrap();
@@ -87,8 +95,10 @@ mod tests {
         let ctx = RequestContext {
             tunnel_id: "test".into(),
             session_id: "session".into(),
+            request_id: "request".into(),
             remote_addr: "127.0.0.1:1234".parse().unwrap(),
             timestamp: std::time::SystemTime::now(),
+            request_bytes: ByteCounter::default(),
         };
 
         let action = plugin.on_request(&mut req, &ctx).await.unwrap();
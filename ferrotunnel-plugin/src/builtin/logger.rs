@@ -1,15 +1,138 @@
 use crate::traits::{Plugin, PluginAction, RequestContext, ResponseContext};
 use async_trait::async_trait;
+use serde_json::Value;
+use std::fmt::Write;
 use tracing::{info, warn};
 
+/// Replacement for redacted values
+const REDACTED: &str = "***";
+
+/// Header names and JSON body keys whose values are masked in logs
+///
+/// Names are matched case-insensitively. JSON keys are masked at any depth;
+/// non-JSON bodies are logged as-is.
+#[derive(Debug, Clone)]
+pub struct RedactionConfig {
+    /// Header names to mask
+    pub headers: Vec<String>,
+    /// JSON object keys to mask
+    pub body_keys: Vec<String>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            headers: [
+                "authorization",
+                "proxy-authorization",
+                "cookie",
+                "set-cookie",
+                "x-api-key",
+                "x-auth-token",
+                "x-tunnel-token",
+            ]
+            .map(String::from)
+            .to_vec(),
+            body_keys: [
+                "password",
+                "secret",
+                "token",
+                "access_token",
+                "refresh_token",
+                "api_key",
+                "client_secret",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+}
+
+impl RedactionConfig {
+    /// Mask nothing
+    pub fn none() -> Self {
+        Self {
+            headers: Vec::new(),
+            body_keys: Vec::new(),
+        }
+    }
+
+    fn is_redacted_header(&self, name: &str) -> bool {
+        self.headers.iter().any(|h| h.eq_ignore_ascii_case(name))
+    }
+
+    /// `value`, or the mask when header `name` is redacted
+    pub fn header_value<'a>(&self, name: &str, value: &'a str) -> &'a str {
+        if self.is_redacted_header(name) {
+            REDACTED
+        } else {
+            value
+        }
+    }
+
+    fn is_redacted_key(&self, key: &str) -> bool {
+        self.body_keys.iter().any(|k| k.eq_ignore_ascii_case(key))
+    }
+
+    /// `name: value` pairs separated by `, ` with redacted values masked
+    pub fn format_headers(&self, headers: &http::HeaderMap) -> String {
+        let mut out = String::new();
+        for (name, value) in headers {
+            if !out.is_empty() {
+                out.push_str(", ");
+            }
+            if self.is_redacted_header(name.as_str()) {
+                let _ = write!(out, "{name}: {REDACTED}");
+            } else {
+                let _ = write!(out, "{name}: {}", String::from_utf8_lossy(value.as_bytes()));
+            }
+        }
+        out
+    }
+
+    /// Body as text, with redacted keys masked when it is JSON
+    pub fn format_body(&self, body: &[u8]) -> String {
+        match serde_json::from_slice::<Value>(body) {
+            Ok(mut json) => {
+                self.redact_json(&mut json);
+                json.to_string()
+            }
+            Err(_) => String::from_utf8_lossy(body).into_owned(),
+        }
+    }
+
+    fn redact_json(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    if self.is_redacted_key(key) {
+                        *value = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact_json(value);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_json(item)),
+            _ => {}
+        }
+    }
+}
+
 /// Logs all requests and responses
+///
+/// With body logging on, headers and response bodies are logged too, masked
+/// by a [`RedactionConfig`] (common secret headers and keys by default).
 pub struct LoggerPlugin {
     log_bodies: bool,
+    redaction: RedactionConfig,
 }
 
 impl LoggerPlugin {
     pub fn new() -> Self {
-        Self { log_bodies: false }
+        Self {
+            log_bodies: false,
+            redaction: RedactionConfig::default(),
+        }
     }
 
     #[must_use]
@@ -17,6 +140,13 @@ impl LoggerPlugin {
         self.log_bodies = true;
         self
     }
+
+    /// Replace the default set of masked headers and body keys
+    #[must_use]
+    pub fn with_redaction(mut self, redaction: RedactionConfig) -> Self {
+        self.redaction = redaction;
+        self
+    }
 }
 
 impl Default for LoggerPlugin {
@@ -31,6 +161,11 @@ impl Plugin for LoggerPlugin {
         "logger"
     }
 
+    /// Logging runs last so it observes the final request/response
+    fn priority(&self) -> i32 {
+        100
+    }
+
     async fn on_request(
         &self,
         req: &mut http::Request<()>,
@@ -45,10 +180,14 @@ impl Plugin for LoggerPlugin {
             "Incoming request"
         );
 
-        // Body logging disabled until streaming support is added
-        // if self.log_bodies && !req.body().is_empty() {
-        //     info!(body_size = req.body().len(), "Request body");
-        // }
+        // Request bodies are streamed past plugins, so only headers are logged
+        if self.log_bodies {
+            info!(
+                tunnel_id = %ctx.tunnel_id,
+                headers = %self.redaction.format_headers(req.headers()),
+                "Request headers"
+            );
+        }
 
         Ok(PluginAction::Continue)
     }
@@ -76,6 +215,21 @@ impl Plugin for LoggerPlugin {
             );
         }
 
+        if self.log_bodies && ctx.body_streamed {
+            info!(
+                tunnel_id = %ctx.tunnel_id,
+                headers = %self.redaction.format_headers(res.headers()),
+                "Response headers (body streamed)"
+            );
+        } else if self.log_bodies {
+            info!(
+                tunnel_id = %ctx.tunnel_id,
+                headers = %self.redaction.format_headers(res.headers()),
+                body = %self.redaction.format_body(res.body()),
+                "Response body"
+            );
+        }
+
         Ok(PluginAction::Continue)
     }
 }
@@ -83,6 +237,7 @@ impl Plugin for LoggerPlugin {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::traits::ByteCounter;
 
     #[test]
     fn test_logger_plugin_name() {
@@ -114,14 +269,116 @@ mod tests {
         let ctx = RequestContext {
             tunnel_id: "tunnel123".into(),
             session_id: "session456".into(),
+            request_id: "request".into(),
             remote_addr: "192.168.1.100:54321".parse().unwrap(),
             timestamp: std::time::SystemTime::now(),
+            request_bytes: ByteCounter::default(),
         };
 
         let action = plugin.on_request(&mut req, &ctx).await.unwrap();
         assert_eq!(action, PluginAction::Continue);
     }
 
+    #[test]
+    fn test_redaction_config_masks_case_insensitively() {
+        let redaction = RedactionConfig::default();
+        let mut headers = http::HeaderMap::new();
+        headers.insert("authorization", "Bearer secret".parse().unwrap());
+        headers.insert("content-type", "application/json".parse().unwrap());
+        assert_eq!(
+            redaction.format_headers(&headers),
+            "authorization: ***, content-type: application/json"
+        );
+        assert_eq!(redaction.header_value("Set-Cookie", "id=1"), "***");
+        assert_eq!(redaction.header_value("accept", "*/*"), "*/*");
+
+        let body = br#"{"user":"amy","Password":"hunter2","nested":[{"token":"t"}]}"#;
+        let redacted: Value = serde_json::from_str(&redaction.format_body(body)).unwrap();
+        assert_eq!(
+            redacted,
+            serde_json::json!({"user": "amy", "Password": "***", "nested": [{"token": "***"}]})
+        );
+
+        // Non-JSON bodies pass through untouched
+        assert_eq!(
+            redaction.format_body(b"password=hunter2"),
+            "password=hunter2"
+        );
+        assert!(RedactionConfig::none()
+            .format_body(body)
+            .contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn test_logger_masks_secrets_in_log_output() {
+        let logs = SharedBuf::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let plugin = LoggerPlugin::new().with_body_logging();
+        let mut req = http::Request::builder()
+            .uri("/login")
+            .header("Authorization", "Bearer hunter2")
+            .body(())
+            .unwrap();
+        let ctx = RequestContext {
+            tunnel_id: "tunnel123".into(),
+            session_id: "session456".into(),
+            request_id: "request".into(),
+            remote_addr: "192.168.1.100:54321".parse().unwrap(),
+            timestamp: std::time::SystemTime::now(),
+            request_bytes: ByteCounter::default(),
+        };
+        plugin.on_request(&mut req, &ctx).await.unwrap();
+
+        let mut res = http::Response::builder()
+            .status(200)
+            .header("content-type", "application/json")
+            .body(br#"{"user":"amy","password":"hunter2"}"#.to_vec())
+            .unwrap();
+        let ctx = ResponseContext {
+            tunnel_id: "tunnel123".into(),
+            session_id: "session456".into(),
+            request_id: "request".into(),
+            status_code: 200,
+            duration_ms: 42,
+            body_streamed: false,
+            request_bytes: ByteCounter::default(),
+            response_bytes: ByteCounter::default(),
+        };
+        plugin.on_response(&mut res, &ctx).await.unwrap();
+
+        let output = logs.contents();
+        assert!(output.contains("authorization: ***"), "{output}");
+        assert!(output.contains(r#""password":"***""#), "{output}");
+        assert!(!output.contains("hunter2"), "{output}");
+    }
+
+    /// Log sink shared between the subscriber and the test
+    #[derive(Clone, Default)]
+    struct SharedBuf(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl SharedBuf {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
     #[tokio::test]
     async fn test_logger_on_response_returns_continue() {
         let plugin = LoggerPlugin::new();
@@ -130,8 +387,12 @@ mod tests {
         let ctx = ResponseContext {
             tunnel_id: "tunnel123".into(),
             session_id: "session456".into(),
+            request_id: "request".into(),
             status_code: 200,
             duration_ms: 42,
+            body_streamed: false,
+            request_bytes: ByteCounter::default(),
+            response_bytes: ByteCounter::default(),
         };
 
         let action = plugin.on_response(&mut res, &ctx).await.unwrap();
@@ -5,5 +5,5 @@ pub mod rate_limit;
 
 pub use auth::TokenAuthPlugin;
 pub use circuit_breaker::{CircuitBreakerConfig, CircuitBreakerPlugin, CircuitState};
-pub use logger::LoggerPlugin;
+pub use logger::{LoggerPlugin, RedactionConfig};
 pub use rate_limit::{InvalidRateLimit, RateLimitPlugin};
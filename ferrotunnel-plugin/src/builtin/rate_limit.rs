@@ -42,6 +42,11 @@ impl Plugin for RateLimitPlugin {
         "rate-limit"
     }
 
+    /// Rate limiting runs right after authentication
+    fn priority(&self) -> i32 {
+        -50
+    }
+
     async fn on_request(
         &self,
         _req: &mut http::Request<()>,
@@ -62,13 +67,16 @@ impl Plugin for RateLimitPlugin {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::traits::ByteCounter;
 
     fn make_ctx(ip: &str) -> RequestContext {
         RequestContext {
             tunnel_id: "test".into(),
             session_id: "session".into(),
+            request_id: "request".into(),
             remote_addr: ip.parse().unwrap(),
             timestamp: std::time::SystemTime::now(),
+            request_bytes: ByteCounter::default(),
         }
     }
 
@@ -58,7 +58,7 @@
 //! #
 //! #[tokio::main]
 //! async fn main() {
-//!     let mut registry = PluginRegistry::new();
+//!     let registry = PluginRegistry::new();
 //!     
 //!     // Register your custom plugin
 //!     registry.register(Arc::new(RwLock::new(MyPlugin)));
@@ -72,16 +72,22 @@
 //!
 //! ### Logger Plugin
 //!
-//! Logs all HTTP requests and responses:
+//! Logs all HTTP requests and responses. With body logging on, secret headers
+//! and JSON body keys are masked; [`RedactionConfig`](builtin::RedactionConfig)
+//! overrides the default set:
 //!
 //! ```rust
-//! use ferrotunnel_plugin::builtin::LoggerPlugin;
+//! use ferrotunnel_plugin::builtin::{LoggerPlugin, RedactionConfig};
 //! # use ferrotunnel_plugin::PluginRegistry;
 //! # use std::sync::Arc;
 //! # use tokio::sync::RwLock;
 //!
-//! let logger = LoggerPlugin::new().with_body_logging();
-//! # let mut registry = PluginRegistry::new();
+//! let mut redaction = RedactionConfig::default();
+//! redaction.body_keys.push("ssn".into());
+//! let logger = LoggerPlugin::new()
+//!     .with_body_logging()
+//!     .with_redaction(redaction);
+//! # let registry = PluginRegistry::new();
 //! registry.register(Arc::new(RwLock::new(logger)));
 //! ```
 //!
@@ -96,7 +102,7 @@
 //! # use tokio::sync::RwLock;
 //!
 //! let auth = TokenAuthPlugin::new(vec!["secret-token".to_string()]);
-//! # let mut registry = PluginRegistry::new();
+//! # let registry = PluginRegistry::new();
 //! registry.register(Arc::new(RwLock::new(auth)));
 //! ```
 //!
@@ -112,10 +118,18 @@
 //! # use std::num::NonZero;
 //!
 //! let rate_limiter = RateLimitPlugin::new(NonZero::new(100).unwrap()); // 100 requests/second
-//! # let mut registry = PluginRegistry::new();
+//! # let registry = PluginRegistry::new();
 //! registry.register(Arc::new(RwLock::new(rate_limiter)));
 //! ```
 //!
+//! ## Execution Order
+//!
+//! Request hooks run in ascending [`Plugin::priority`] order and response hooks
+//! run in reverse, like a middleware stack. Built-in plugins default to auth
+//! (`-100`), rate limiting (`-50`) and logging (`100`); custom plugins default
+//! to `0`. Ties run in registration order. Use
+//! [`PluginRegistry::register_with_priority`] to override a plugin's default.
+//!
 //! ## Plugin Actions
 //!
 //! Plugins can return different actions:
@@ -123,6 +137,8 @@
 //! - `PluginAction::Continue` - Allow request, continue to next plugin
 //! - `PluginAction::Reject { status, reason }` - Reject with HTTP status
 //! - `PluginAction::Respond { status, headers, body }` - Send custom response
+//! - `PluginAction::Delay { duration, then }` - Wait, then apply another action
+//! - `PluginAction::SetTimeout(duration)` - Override the response timeout, continue
 //!
 //! ## See Also
 //!
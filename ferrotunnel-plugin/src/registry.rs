@@ -1,29 +1,140 @@
 use crate::traits::{Plugin, PluginAction, RequestContext, ResponseContext};
+use bytes::Bytes;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Shared handle to a plugin
+pub type SharedPlugin = Arc<RwLock<dyn Plugin>>;
+
+/// A registered plugin together with its execution priority
+#[derive(Clone)]
+struct RegisteredPlugin {
+    priority: i32,
+    plugin: Arc<RwLock<dyn Plugin>>,
+}
+
 /// Registry manages all loaded plugins
+///
+/// Request hooks run in ascending priority order and response hooks in the
+/// reverse order, like a middleware stack. Plugins with equal priority keep
+/// their registration order.
+///
+/// Plugins can be added, removed or replaced while the registry is shared,
+/// e.g. to update an IP blocklist without a restart. Each hook run works on the
+/// plugin list as it was when the run started, so a concurrent change never
+/// disrupts a request that is already passing through the plugins.
 #[derive(Default)]
 pub struct PluginRegistry {
-    plugins: Vec<Arc<RwLock<dyn Plugin>>>,
+    /// Copied on write, so hook runs can hold on to a snapshot
+    plugins: std::sync::RwLock<Arc<Vec<RegisteredPlugin>>>,
 }
 
 impl PluginRegistry {
     pub fn new() -> Self {
-        Self {
-            plugins: Vec::new(),
+        Self::default()
+    }
+
+    /// The current plugin list
+    fn snapshot(&self) -> Arc<Vec<RegisteredPlugin>> {
+        match self.plugins.read() {
+            Ok(plugins) => plugins.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Change the plugin list. `edit` works on a copy, so snapshots taken
+    /// by running hooks are left alone.
+    fn update<T>(&self, edit: impl FnOnce(&mut Vec<RegisteredPlugin>) -> T) -> T {
+        let mut plugins = match self.plugins.write() {
+            Ok(plugins) => plugins,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut next = Vec::clone(&plugins);
+        let result = edit(&mut next);
+        *plugins = Arc::new(next);
+        result
+    }
+
+    /// Register a plugin using its own [`Plugin::priority`]
+    ///
+    /// A plugin whose lock is held elsewhere gets the default priority (0);
+    /// use [`register_with_priority`](Self::register_with_priority) for it.
+    pub fn register(&self, plugin: Arc<RwLock<dyn Plugin>>) {
+        let priority = plugin.try_read().map_or_else(
+            |_| {
+                tracing::warn!("Plugin locked while registering, using the default priority 0");
+                0
+            },
+            |p| p.priority(),
+        );
+        self.register_with_priority(plugin, priority);
+    }
+
+    /// Register a plugin with an explicit priority, overriding [`Plugin::priority`]
+    pub fn register_with_priority(&self, plugin: Arc<RwLock<dyn Plugin>>, priority: i32) {
+        self.update(|plugins| {
+            // Insert after every plugin with priority <= ours so ties keep registration order
+            let index = plugins.partition_point(|p| p.priority <= priority);
+            plugins.insert(index, RegisteredPlugin { priority, plugin });
+        });
+    }
+
+    /// Remove the first plugin named `name`, returning it
+    ///
+    /// Hook runs already in progress finish with the plugin; later ones skip
+    /// it, including the response hooks of requests that ran its request
+    /// hook. The plugin is not shut down, so call [`Plugin::shutdown`] on the
+    /// returned handle if it holds resources.
+    pub async fn remove(&self, name: &str) -> Option<SharedPlugin> {
+        let target = self.find(name).await?;
+        self.update(|plugins| {
+            let index = plugins
+                .iter()
+                .position(|entry| Arc::ptr_eq(&entry.plugin, &target))?;
+            Some(plugins.remove(index).plugin)
+        })
+    }
+
+    /// Swap the first plugin named `name` for `plugin`, which takes over its
+    /// priority, returning the old plugin. Returns `None`, leaving the
+    /// registry unchanged, when no plugin has that name.
+    ///
+    /// As with [`remove`](Self::remove), hook runs in progress keep the old
+    /// plugin and neither plugin's lifecycle hooks are called: initialize the
+    /// new one before swapping it in.
+    pub async fn replace(&self, name: &str, plugin: SharedPlugin) -> Option<SharedPlugin> {
+        let target = self.find(name).await?;
+        self.update(|plugins| {
+            let entry = plugins
+                .iter_mut()
+                .find(|entry| Arc::ptr_eq(&entry.plugin, &target))?;
+            Some(std::mem::replace(&mut entry.plugin, plugin))
+        })
+    }
+
+    /// Names of the registered plugins, in request hook order
+    pub async fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for entry in self.snapshot().iter() {
+            names.push(entry.plugin.read().await.name().to_string());
         }
+        names
     }
 
-    /// Register a plugin
-    pub fn register(&mut self, plugin: Arc<RwLock<dyn Plugin>>) {
-        self.plugins.push(plugin);
+    /// The first plugin named `name`
+    async fn find(&self, name: &str) -> Option<SharedPlugin> {
+        for entry in self.snapshot().iter() {
+            if entry.plugin.read().await.name() == name {
+                return Some(entry.plugin.clone());
+            }
+        }
+        None
     }
 
     /// Initialize all plugins
     pub async fn init_all(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-        for plugin_lock in &self.plugins {
-            let mut plugin = plugin_lock.write().await;
+        for entry in self.snapshot().iter() {
+            let mut plugin = entry.plugin.write().await;
             tracing::info!("Initializing plugin: {}", plugin.name());
             plugin.init().await?;
         }
@@ -31,43 +142,76 @@ impl PluginRegistry {
     }
 
     /// Execute request hooks on all plugins
+    ///
+    /// Returns the first action other than `Continue` or `SetTimeout`. If no
+    /// plugin short-circuits, returns the last `SetTimeout`, or `Continue`.
     pub async fn execute_request_hooks(
         &self,
         req: &mut http::Request<()>,
         ctx: &RequestContext,
     ) -> Result<PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>> {
-        for plugin_lock in &self.plugins {
-            let plugin = plugin_lock.read().await;
-            match plugin.on_request(req, ctx).await? {
-                PluginAction::Continue => continue,
+        let mut timeout = None;
+        for entry in self.snapshot().iter() {
+            let action = entry.plugin.read().await.on_request(req, ctx).await?;
+            match resolve_delay(action).await {
+                PluginAction::Continue => {}
+                PluginAction::SetTimeout(duration) => timeout = Some(duration),
                 action => return Ok(action), // Short-circuit on non-Continue
             }
         }
+        Ok(timeout.map_or(PluginAction::Continue, PluginAction::SetTimeout))
+    }
+
+    /// Execute request body chunk hooks on the plugins that need request bodies
+    ///
+    /// Each plugin sees the chunk as left by the plugins before it. Returns
+    /// the first action that stops the request, otherwise `Continue`.
+    pub async fn execute_request_chunk_hooks(
+        &self,
+        chunk: &mut Bytes,
+        ctx: &RequestContext,
+    ) -> Result<PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        for entry in self.snapshot().iter() {
+            let action = {
+                let plugin = entry.plugin.read().await;
+                if !plugin.needs_request_body() {
+                    continue;
+                }
+                plugin.on_request_chunk(chunk, ctx).await?
+            };
+            match resolve_delay(action).await {
+                // Headers are already on their way, so timeouts and
+                // modifications no longer apply
+                PluginAction::Continue | PluginAction::Modify {} | PluginAction::SetTimeout(_) => {}
+                action => return Ok(action),
+            }
+        }
         Ok(PluginAction::Continue)
     }
 
-    /// Execute response hooks on all plugins
+    /// Execute response hooks on all plugins, in reverse priority order
     pub async fn execute_response_hooks(
         &self,
         res: &mut http::Response<Vec<u8>>,
         ctx: &ResponseContext,
     ) -> Result<PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>> {
-        for plugin_lock in &self.plugins {
-            let plugin = plugin_lock.read().await;
-            match plugin.on_response(res, ctx).await? {
-                PluginAction::Continue => continue,
+        for entry in self.snapshot().iter().rev() {
+            let action = entry.plugin.read().await.on_response(res, ctx).await?;
+            match resolve_delay(action).await {
+                // Too late to change the timeout once the response has arrived
+                PluginAction::Continue | PluginAction::SetTimeout(_) => continue,
                 action => return Ok(action),
             }
         }
         Ok(PluginAction::Continue)
     }
 
-    /// Shutdown all plugins
+    /// Shutdown all plugins, in reverse priority order
     pub async fn shutdown_all(
         &self,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-        for plugin_lock in &self.plugins {
-            let mut plugin = plugin_lock.write().await;
+        for entry in self.snapshot().iter().rev() {
+            let mut plugin = entry.plugin.write().await;
             tracing::info!("Shutting down plugin: {}", plugin.name());
             plugin.shutdown().await?;
         }
@@ -77,8 +221,8 @@ impl PluginRegistry {
     /// Returns true if any plugin needs to inspect/modify response bodies.
     /// When false, responses can be streamed without buffering for better performance.
     pub async fn needs_response_buffering(&self) -> bool {
-        for plugin_lock in &self.plugins {
-            let plugin = plugin_lock.read().await;
+        for entry in self.snapshot().iter() {
+            let plugin = entry.plugin.read().await;
             if plugin.needs_response_body() {
                 return true;
             }
@@ -86,15 +230,37 @@ impl PluginRegistry {
         false
     }
 
+    /// Returns true if any plugin inspects request bodies chunk by chunk
+    pub async fn needs_request_body(&self) -> bool {
+        for entry in self.snapshot().iter() {
+            if entry.plugin.read().await.needs_request_body() {
+                return true;
+            }
+        }
+        false
+    }
+
     /// Returns true if no plugins are registered
     pub fn is_empty(&self) -> bool {
-        self.plugins.is_empty()
+        self.snapshot().is_empty()
+    }
+}
+
+/// Sleep out any [`PluginAction::Delay`] wrappers and return the action they
+/// carry. Runs after the plugin's lock is released, so a delayed request does
+/// not hold up hooks for other requests.
+async fn resolve_delay(mut action: PluginAction) -> PluginAction {
+    while let PluginAction::Delay { duration, then } = action {
+        tokio::time::sleep(duration).await;
+        action = *then;
     }
+    action
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::traits::ByteCounter;
     use async_trait::async_trait;
 
     // Test plugin that always rejects
@@ -139,12 +305,97 @@ mod tests {
         }
     }
 
+    // Test plugin that records the order in which its hooks run
+    struct OrderPlugin {
+        name: &'static str,
+        priority: i32,
+        log: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Plugin for OrderPlugin {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn priority(&self) -> i32 {
+            self.priority
+        }
+
+        async fn on_request(
+            &self,
+            _req: &mut http::Request<()>,
+            _ctx: &RequestContext,
+        ) -> Result<PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>> {
+            self.log.lock().unwrap().push(format!("req:{}", self.name));
+            Ok(PluginAction::Continue)
+        }
+
+        async fn on_response(
+            &self,
+            _res: &mut http::Response<Vec<u8>>,
+            _ctx: &ResponseContext,
+        ) -> Result<PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>> {
+            self.log.lock().unwrap().push(format!("res:{}", self.name));
+            Ok(PluginAction::Continue)
+        }
+    }
+
+    // Test plugin that delays, then applies a fixed action
+    struct DelayPlugin {
+        delay: std::time::Duration,
+        then: PluginAction,
+    }
+
+    #[async_trait]
+    impl Plugin for DelayPlugin {
+        fn name(&self) -> &str {
+            "delay"
+        }
+
+        async fn on_request(
+            &self,
+            _req: &mut http::Request<()>,
+            _ctx: &RequestContext,
+        ) -> Result<PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>> {
+            Ok(PluginAction::Delay {
+                duration: self.delay,
+                then: Box::new(self.then.clone()),
+            })
+        }
+    }
+
+    // Test plugin that upper-cases request body chunks
+    struct UppercasePlugin;
+
+    #[async_trait]
+    impl Plugin for UppercasePlugin {
+        fn name(&self) -> &str {
+            "uppercase"
+        }
+
+        fn needs_request_body(&self) -> bool {
+            true
+        }
+
+        async fn on_request_chunk(
+            &self,
+            chunk: &mut Bytes,
+            _ctx: &RequestContext,
+        ) -> Result<PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>> {
+            *chunk = Bytes::from(chunk.to_ascii_uppercase());
+            Ok(PluginAction::Continue)
+        }
+    }
+
     fn make_request_ctx() -> RequestContext {
         RequestContext {
             tunnel_id: "test".into(),
             session_id: "sess".into(),
+            request_id: "request".into(),
             remote_addr: "127.0.0.1:80".parse().unwrap(),
             timestamp: std::time::SystemTime::now(),
+            request_bytes: ByteCounter::default(),
         }
     }
 
@@ -156,7 +407,7 @@ mod tests {
 
     #[test]
     fn test_registry_not_empty_after_register() {
-        let mut registry = PluginRegistry::new();
+        let registry = PluginRegistry::new();
         registry.register(Arc::new(RwLock::new(PassthroughPlugin)));
         assert!(!registry.is_empty());
     }
@@ -164,7 +415,7 @@ mod tests {
     #[tokio::test]
     async fn test_registry_executes_plugins() {
         let plugin = Arc::new(RwLock::new(RejectPlugin));
-        let mut registry = PluginRegistry::new();
+        let registry = PluginRegistry::new();
         registry.register(plugin);
 
         let mut req = http::Request::builder().body(()).unwrap();
@@ -195,7 +446,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_registry_passthrough_returns_continue() {
-        let mut registry = PluginRegistry::new();
+        let registry = PluginRegistry::new();
         registry.register(Arc::new(RwLock::new(PassthroughPlugin)));
 
         let mut req = http::Request::builder().body(()).unwrap();
@@ -210,7 +461,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_registry_init_all_success() {
-        let mut registry = PluginRegistry::new();
+        let registry = PluginRegistry::new();
         registry.register(Arc::new(RwLock::new(PassthroughPlugin)));
 
         let result = registry.init_all().await;
@@ -219,7 +470,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_registry_shutdown_all_success() {
-        let mut registry = PluginRegistry::new();
+        let registry = PluginRegistry::new();
         registry.register(Arc::new(RwLock::new(PassthroughPlugin)));
 
         let result = registry.shutdown_all().await;
@@ -234,14 +485,14 @@ mod tests {
 
     #[tokio::test]
     async fn test_registry_needs_response_buffering_detects_plugin() {
-        let mut registry = PluginRegistry::new();
+        let registry = PluginRegistry::new();
         registry.register(Arc::new(RwLock::new(BodyInspectorPlugin)));
         assert!(registry.needs_response_buffering().await);
     }
 
     #[tokio::test]
     async fn test_registry_short_circuits_on_reject() {
-        let mut registry = PluginRegistry::new();
+        let registry = PluginRegistry::new();
         // RejectPlugin first, then PassthroughPlugin
         registry.register(Arc::new(RwLock::new(RejectPlugin)));
         registry.register(Arc::new(RwLock::new(PassthroughPlugin)));
@@ -259,4 +510,313 @@ mod tests {
             _ => panic!("Expected reject - should short-circuit"),
         }
     }
+
+    #[tokio::test]
+    async fn test_registry_executes_in_priority_order() {
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let plugin = |name, priority| {
+            Arc::new(RwLock::new(OrderPlugin {
+                name,
+                priority,
+                log: log.clone(),
+            }))
+        };
+
+        let registry = PluginRegistry::new();
+        // Registered in deliberately scrambled order
+        registry.register(plugin("logger", 100));
+        registry.register(plugin("custom-a", 0));
+        registry.register(plugin("auth", -100));
+        registry.register(plugin("custom-b", 0));
+        registry.register_with_priority(plugin("rate-limit", 100), -50);
+
+        let mut req = http::Request::builder().body(()).unwrap();
+        registry
+            .execute_request_hooks(&mut req, &make_request_ctx())
+            .await
+            .unwrap();
+
+        let mut res = http::Response::builder().body(Vec::new()).unwrap();
+        let res_ctx = ResponseContext {
+            tunnel_id: "test".into(),
+            session_id: "sess".into(),
+            request_id: "request".into(),
+            status_code: 200,
+            duration_ms: 1,
+            body_streamed: false,
+            request_bytes: ByteCounter::default(),
+            response_bytes: ByteCounter::default(),
+        };
+        registry
+            .execute_response_hooks(&mut res, &res_ctx)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "req:auth",
+                "req:rate-limit",
+                "req:custom-a",
+                "req:custom-b",
+                "req:logger",
+                "res:logger",
+                "res:custom-b",
+                "res:custom-a",
+                "res:rate-limit",
+                "res:auth",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_register_locked_plugin_uses_default_priority() {
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let plugin = |name, priority| {
+            Arc::new(RwLock::new(OrderPlugin {
+                name,
+                priority,
+                log: log.clone(),
+            }))
+        };
+        let auth = plugin("auth", -100);
+        let registry = PluginRegistry::new();
+        registry.register(plugin("custom", 0));
+        {
+            let _guard = auth.try_write().unwrap();
+            registry.register(auth.clone());
+        }
+        registry.register_with_priority(auth, -100);
+
+        let mut req = http::Request::builder().body(()).unwrap();
+        registry
+            .execute_request_hooks(&mut req, &make_request_ctx())
+            .await
+            .unwrap();
+
+        // The locked registration tied with custom at 0
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["req:auth", "req:custom", "req:auth"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_registry_delay_then_reject() {
+        let delay = std::time::Duration::from_millis(100);
+        let registry = PluginRegistry::new();
+        registry.register(Arc::new(RwLock::new(DelayPlugin {
+            delay,
+            then: PluginAction::Reject {
+                status: 429,
+                reason: "Slow down".into(),
+            },
+        })));
+        registry.register(Arc::new(RwLock::new(PassthroughPlugin)));
+
+        let mut req = http::Request::builder().body(()).unwrap();
+        let start = std::time::Instant::now();
+        let action = registry
+            .execute_request_hooks(&mut req, &make_request_ctx())
+            .await
+            .unwrap();
+
+        assert!(start.elapsed() >= delay);
+        assert_eq!(
+            action,
+            PluginAction::Reject {
+                status: 429,
+                reason: "Slow down".into(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_registry_delay_then_continue_runs_next_plugin() {
+        let delay = std::time::Duration::from_millis(50);
+        let registry = PluginRegistry::new();
+        registry.register_with_priority(
+            Arc::new(RwLock::new(DelayPlugin {
+                delay,
+                then: PluginAction::Continue,
+            })),
+            -10,
+        );
+        registry.register(Arc::new(RwLock::new(RejectPlugin)));
+
+        let mut req = http::Request::builder().body(()).unwrap();
+        let start = std::time::Instant::now();
+        let action = registry
+            .execute_request_hooks(&mut req, &make_request_ctx())
+            .await
+            .unwrap();
+
+        assert!(start.elapsed() >= delay);
+        assert!(matches!(action, PluginAction::Reject { status: 403, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_registry_delay_does_not_block_other_requests() {
+        let registry = PluginRegistry::new();
+        registry.register(Arc::new(RwLock::new(DelayPlugin {
+            delay: std::time::Duration::from_millis(500),
+            then: PluginAction::Continue,
+        })));
+        let registry = Arc::new(registry);
+
+        let slow = tokio::spawn({
+            let registry = registry.clone();
+            async move {
+                let mut req = http::Request::builder().body(()).unwrap();
+                registry
+                    .execute_request_hooks(&mut req, &make_request_ctx())
+                    .await
+                    .unwrap()
+            }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        // A shutdown needs the plugin's write lock, which a sleeping delay must not hold
+        tokio::time::timeout(
+            std::time::Duration::from_millis(200),
+            registry.shutdown_all(),
+        )
+        .await
+        .expect("delay held the plugin lock")
+        .unwrap();
+        assert_eq!(slow.await.unwrap(), PluginAction::Continue);
+    }
+
+    #[tokio::test]
+    async fn test_registry_set_timeout_runs_later_plugins() {
+        let timeout = std::time::Duration::from_secs(300);
+        let set_timeout = |then| {
+            Arc::new(RwLock::new(DelayPlugin {
+                delay: std::time::Duration::ZERO,
+                then,
+            }))
+        };
+
+        let registry = PluginRegistry::new();
+        registry.register_with_priority(set_timeout(PluginAction::SetTimeout(timeout)), -10);
+        registry.register(Arc::new(RwLock::new(PassthroughPlugin)));
+        let mut req = http::Request::builder().body(()).unwrap();
+        let action = registry
+            .execute_request_hooks(&mut req, &make_request_ctx())
+            .await
+            .unwrap();
+        assert_eq!(action, PluginAction::SetTimeout(timeout));
+
+        // A later rejection still wins over the override
+        registry.register(Arc::new(RwLock::new(RejectPlugin)));
+        let action = registry
+            .execute_request_hooks(&mut req, &make_request_ctx())
+            .await
+            .unwrap();
+        assert!(matches!(action, PluginAction::Reject { status: 403, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_registry_request_chunk_hooks_only_for_opted_in_plugins() {
+        let registry = PluginRegistry::new();
+        registry.register(Arc::new(RwLock::new(RejectPlugin)));
+        assert!(!registry.needs_request_body().await);
+
+        // RejectPlugin does not opt in, so it never sees the chunk
+        registry.register(Arc::new(RwLock::new(UppercasePlugin)));
+        assert!(registry.needs_request_body().await);
+
+        let mut chunk = Bytes::from_static(b"hello");
+        let action = registry
+            .execute_request_chunk_hooks(&mut chunk, &make_request_ctx())
+            .await
+            .unwrap();
+        assert_eq!(action, PluginAction::Continue);
+        assert_eq!(chunk, "HELLO");
+    }
+
+    #[tokio::test]
+    async fn test_registry_remove_and_replace() {
+        let registry = PluginRegistry::new();
+        registry.register(Arc::new(RwLock::new(PassthroughPlugin)));
+        registry.register_with_priority(Arc::new(RwLock::new(RejectPlugin)), -10);
+        assert_eq!(registry.names().await, ["rejector", "passthrough"]);
+
+        let mut req = http::Request::builder().body(()).unwrap();
+        let ctx = make_request_ctx();
+        let action = registry
+            .execute_request_hooks(&mut req, &ctx)
+            .await
+            .unwrap();
+        assert!(matches!(action, PluginAction::Reject { status: 403, .. }));
+
+        // The replacement keeps the rejector's place in the order
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let order = OrderPlugin {
+            name: "order",
+            priority: 50,
+            log: log.clone(),
+        };
+        assert!(registry
+            .replace("rejector", Arc::new(RwLock::new(order)))
+            .await
+            .is_some());
+        assert_eq!(registry.names().await, ["order", "passthrough"]);
+        let action = registry
+            .execute_request_hooks(&mut req, &ctx)
+            .await
+            .unwrap();
+        assert_eq!(action, PluginAction::Continue);
+        assert_eq!(*log.lock().unwrap(), ["req:order"]);
+
+        assert!(registry.remove("order").await.is_some());
+        assert_eq!(registry.names().await, ["passthrough"]);
+        assert!(registry.remove("order").await.is_none());
+        assert!(registry
+            .replace("missing", Arc::new(RwLock::new(RejectPlugin)))
+            .await
+            .is_none());
+        assert_eq!(registry.names().await, ["passthrough"]);
+
+        assert!(registry.remove("passthrough").await.is_some());
+        assert!(registry.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_registry_remove_does_not_disrupt_running_hooks() {
+        let registry = PluginRegistry::new();
+        registry.register_with_priority(
+            Arc::new(RwLock::new(DelayPlugin {
+                delay: std::time::Duration::from_millis(200),
+                then: PluginAction::Continue,
+            })),
+            0,
+        );
+        registry.register_with_priority(Arc::new(RwLock::new(RejectPlugin)), 10);
+        let registry = Arc::new(registry);
+
+        let running = {
+            let registry = registry.clone();
+            tokio::spawn(async move {
+                let mut req = http::Request::builder().body(()).unwrap();
+                registry
+                    .execute_request_hooks(&mut req, &make_request_ctx())
+                    .await
+                    .unwrap()
+            })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        // Removed while the running request waits in the delay plugin
+        assert!(registry.remove("rejector").await.is_some());
+        let action = running.await.unwrap();
+        assert!(matches!(action, PluginAction::Reject { status: 403, .. }));
+
+        let mut req = http::Request::builder().body(()).unwrap();
+        let action = registry
+            .execute_request_hooks(&mut req, &make_request_ctx())
+            .await
+            .unwrap();
+        assert_eq!(action, PluginAction::Continue);
+    }
 }
@@ -1,5 +1,8 @@
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
+use bytes::Bytes;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 /// Action that a plugin can return
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -21,6 +24,22 @@ pub enum PluginAction {
         headers: Vec<(String, String)>,
         body: Vec<u8>,
     },
+
+    /// Wait for `duration`, then apply `then`
+    ///
+    /// Useful for tarpitting abusive clients or simulating latency. The wait
+    /// does not block other connections, but it holds the request (and its
+    /// tunnel stream slot) open for the whole duration, so keep delays short.
+    Delay {
+        duration: std::time::Duration,
+        then: Box<PluginAction>,
+    },
+
+    /// Override the ingress response timeout for this request and continue
+    ///
+    /// Later plugins still run; if several set a timeout, the last one wins.
+    /// The ingress caps the value at `IngressConfig::max_response_timeout`.
+    SetTimeout(std::time::Duration),
 }
 
 /// Request context passed to plugins
@@ -28,8 +47,16 @@ pub enum PluginAction {
 pub struct RequestContext {
     pub tunnel_id: String,
     pub session_id: String,
+    /// Unique to each request, and the same in its response context, so
+    /// plugins can key per-request state on it
+    #[serde(default)]
+    pub request_id: String,
     pub remote_addr: std::net::SocketAddr,
     pub timestamp: std::time::SystemTime,
+    /// Request body bytes forwarded to the tunnel; still 0 in `on_request`,
+    /// final once the body has been read
+    #[serde(default)]
+    pub request_bytes: ByteCounter,
 }
 
 /// Response context passed to plugins
@@ -37,8 +64,56 @@ pub struct RequestContext {
 pub struct ResponseContext {
     pub tunnel_id: String,
     pub session_id: String,
+    /// `request_id` of the request this response answers
+    #[serde(default)]
+    pub request_id: String,
     pub status_code: u16,
     pub duration_ms: u64,
+    /// The body streams to the client without being buffered, as for
+    /// Server-Sent Events, so `on_response` sees an empty body and changes
+    /// to it are ignored
+    #[serde(default)]
+    pub body_streamed: bool,
+    /// Same counter as [`RequestContext::request_bytes`]
+    #[serde(default)]
+    pub request_bytes: ByteCounter,
+    /// Response body bytes received from the tunnel: the whole body when it
+    /// was buffered, or the bytes streamed so far when `body_streamed` is set
+    #[serde(default)]
+    pub response_bytes: ByteCounter,
+}
+
+/// Byte count of a request or response body, kept up to date by the ingress
+/// as the body streams
+///
+/// Clones share the count, so a context kept past its hook still sees the
+/// bytes forwarded afterwards. Serializes as the count at that moment.
+#[derive(Debug, Clone, Default)]
+pub struct ByteCounter(Arc<AtomicU64>);
+
+impl ByteCounter {
+    /// Bytes counted so far
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Count `bytes` more
+    pub fn add(&self, bytes: u64) {
+        self.0.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+impl Serialize for ByteCounter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteCounter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let count = u64::deserialize(deserializer)?;
+        Ok(Self(Arc::new(AtomicU64::new(count))))
+    }
 }
 
 /// Stream context for data plugins
@@ -66,6 +141,13 @@ pub trait Plugin: Send + Sync {
         "1.0.0"
     }
 
+    /// Execution priority; lower values run earlier on requests and later on responses.
+    ///
+    /// Plugins with equal priority run in registration order.
+    fn priority(&self) -> i32 {
+        0
+    }
+
     /// Initialize plugin (called once on startup)
     async fn init(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
         Ok(())
@@ -97,10 +179,35 @@ pub trait Plugin: Send + Sync {
     /// Returns true if this plugin needs to inspect/modify response bodies.
     /// Override to return true if your plugin uses on_response with body access.
     /// When false, responses can be streamed without buffering for better performance.
+    ///
+    /// Event streams (`text/event-stream`) are never buffered: `on_response`
+    /// runs on their head only, with `ResponseContext::body_streamed` set.
     fn needs_response_body(&self) -> bool {
         false
     }
 
+    /// Returns true if this plugin inspects request bodies with `on_request_chunk`.
+    /// When no plugin does, request bodies stream through untouched.
+    fn needs_request_body(&self) -> bool {
+        false
+    }
+
+    /// Hook: For each chunk of a request body as it streams to the tunnel
+    ///
+    /// Only called when `needs_request_body` returns true. The chunk may be
+    /// replaced or emptied; an empty chunk is not forwarded. Once the body
+    /// ends the hook runs one last time with an empty chunk, so plugins that
+    /// hold back bytes across chunk boundaries can flush them. Returning
+    /// `Reject` or `Respond` aborts forwarding and answers the client with
+    /// that status.
+    async fn on_request_chunk(
+        &self,
+        _chunk: &mut Bytes,
+        _ctx: &RequestContext,
+    ) -> Result<PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        Ok(PluginAction::Continue)
+    }
+
     /// Hook: When stream data flows through tunnel
     async fn on_stream_data(
         &self,
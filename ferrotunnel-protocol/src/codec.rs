@@ -5,20 +5,27 @@
 //! - 1-byte type discriminator
 //! - Variable payload
 //!
+//! - Optional 4-byte CRC32 trailer
+//!
 //! This is similar to Rathole's approach and avoids COBS overhead.
 
+use crate::compression::FrameCompression;
 use crate::constants::MAX_FRAME_SIZE;
 use crate::frame::{Frame, ZeroCopyFrame};
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::io;
 use tokio_util::codec::{Decoder, Encoder};
 
 /// Frame header size: 4 bytes length + 1 byte type
 const HEADER_SIZE: usize = 5;
 
+/// CRC32 trailer size when checksums are enabled
+const CHECKSUM_SIZE: usize = 4;
+
 const FRAME_TYPE_CONTROL: u8 = 0x00;
 const FRAME_TYPE_DATA: u8 = 0x01;
 const FLAG_EOS: u8 = 0x01;
+const FLAG_COMPRESSED: u8 = 0x02;
 
 /// Tunnel protocol codec using length-delimited framing
 ///
@@ -35,15 +42,25 @@ const FLAG_EOS: u8 = 0x01;
 /// Payload format depends on Type:
 /// - Control (0x00): `bincode(Frame)` (excluding `Frame::Data`)
 /// - Data (0x01): `[StreamID(u32)][Flags(u8)][Raw Bytes...]`
+///
+/// With [checksums](Self::with_checksum) enabled, every frame ends with a
+/// CRC32 (u32 big-endian) of Type + Payload, and Length includes it.
+///
+/// With [compression](Self::with_compression) enabled, data payloads that
+/// shrink are sent compressed with flag bit 0x02 set.
 #[derive(Debug, Clone, Copy)]
 pub struct TunnelCodec {
     max_frame_size: usize,
+    checksum: bool,
+    compression: Option<FrameCompression>,
 }
 
 impl Default for TunnelCodec {
     fn default() -> Self {
         Self {
             max_frame_size: MAX_FRAME_SIZE as usize,
+            checksum: false,
+            compression: None,
         }
     }
 }
@@ -58,7 +75,34 @@ impl TunnelCodec {
     /// Create a new codec instance with a custom max frame size
     #[inline]
     pub fn with_max_frame_size(max_frame_size: usize) -> Self {
-        Self { max_frame_size }
+        Self {
+            max_frame_size,
+            ..Self::default()
+        }
+    }
+
+    /// Append a CRC32 to every encoded frame and verify it on decode
+    ///
+    /// Off by default: TCP and TLS already protect the stream. Both peers
+    /// must agree, which they do through
+    /// [`CHECKSUM_CAPABILITY`](crate::constants::CHECKSUM_CAPABILITY).
+    #[inline]
+    #[must_use]
+    pub fn with_checksum(mut self, enabled: bool) -> Self {
+        self.checksum = enabled;
+        self
+    }
+
+    /// Compress data frame payloads with `compression`, and accept
+    /// compressed payloads from the peer
+    ///
+    /// Off by default. Both peers must agree, which they do through the
+    /// [compression capabilities](crate::compression).
+    #[inline]
+    #[must_use]
+    pub fn with_compression(mut self, compression: Option<FrameCompression>) -> Self {
+        self.compression = compression;
+        self
     }
 
     /// Get the configured max frame size
@@ -67,6 +111,45 @@ impl TunnelCodec {
         self.max_frame_size
     }
 
+    /// Whether frames carry a CRC32 trailer
+    #[inline]
+    pub fn checksum(&self) -> bool {
+        self.checksum
+    }
+
+    /// Algorithm data frame payloads are compressed with, if any
+    #[inline]
+    pub fn compression(&self) -> Option<FrameCompression> {
+        self.compression
+    }
+
+    /// Compress a data frame payload with the configured algorithm
+    ///
+    /// Returns the payload to send and whether it is compressed; without
+    /// compression, or when compressing would not shrink it, `data` is
+    /// returned as it is.
+    pub fn compress_payload(&self, data: Bytes) -> Result<(Bytes, bool), io::Error> {
+        let Some(compression) = self.compression.filter(|_| !data.is_empty()) else {
+            return Ok((data, false));
+        };
+        let compressed = compression.compress(&data)?;
+        if compressed.len() < data.len() {
+            Ok((Bytes::from(compressed), true))
+        } else {
+            Ok((data, false))
+        }
+    }
+
+    /// Bytes the checksum adds to each frame
+    #[inline]
+    fn trailer_size(&self) -> usize {
+        if self.checksum {
+            CHECKSUM_SIZE
+        } else {
+            0
+        }
+    }
+
     /// Decode as many complete frames as possible from `src`, appending to `out`.
     /// Returns the number of bytes consumed from `src`. Call with the same `BytesMut` and
     /// remaining bytes for incremental reading. Data frames are copied to owned `Frame`.
@@ -92,6 +175,8 @@ impl TunnelCodec {
     /// Parse data frames from `buf` without copying payload (zero-copy).
     /// Only complete data frames are returned; control frames are skipped (caller should use
     /// `decode` for mixed frames). Returns (bytes consumed, zero-copy data frames).
+    ///
+    /// Compressed data frames cannot be borrowed and fail with `InvalidData`.
     pub fn decode_data_frames_zerocopy<'a>(
         &self,
         buf: &'a [u8],
@@ -116,24 +201,28 @@ impl TunnelCodec {
             if buf.len() < offset + total_size {
                 break;
             }
-            let frame_type = buf[offset + 4];
-            if frame_type == FRAME_TYPE_DATA {
+            let mut body = &buf[offset + 4..offset + total_size];
+            if self.checksum {
+                body = verify_checksum(body)?;
+            }
+            if body[0] == FRAME_TYPE_DATA {
                 // type(1) + stream_id(4) + flags(1) = 6
-                if length < 6 {
+                if body.len() < 6 {
                     return Err(io::Error::new(
                         io::ErrorKind::InvalidData,
                         "Data frame payload too short",
                     ));
                 }
-                let stream_id = u32::from_be_bytes([
-                    buf[offset + 5],
-                    buf[offset + 6],
-                    buf[offset + 7],
-                    buf[offset + 8],
-                ]);
-                let flags = buf[offset + 9];
+                let stream_id = u32::from_be_bytes([body[1], body[2], body[3], body[4]]);
+                let flags = body[5];
+                if flags & FLAG_COMPRESSED != 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Compressed data frame cannot be decoded zero-copy",
+                    ));
+                }
                 let fin = (flags & FLAG_EOS) != 0;
-                let data = &buf[offset + 10..offset + total_size];
+                let data = &body[6..];
                 out.push(ZeroCopyFrame::Data {
                     stream_id,
                     data,
@@ -188,6 +277,10 @@ impl Decoder for TunnelCodec {
         // Consume the frame
         let mut frame_bytes = src.split_to(total_size).freeze();
         frame_bytes.advance(4);
+        if self.checksum {
+            let body_len = verify_checksum(&frame_bytes)?.len();
+            frame_bytes.truncate(body_len);
+        }
 
         // Parse type and payload
         let frame_type = frame_bytes.get_u8();
@@ -205,7 +298,16 @@ impl Decoder for TunnelCodec {
                 let flags = frame_bytes.get_u8();
                 let end_of_stream = (flags & FLAG_EOS) != 0;
                 // Zero-copy slice of the remaining payload.
-                let data = frame_bytes.split_to(frame_bytes.remaining());
+                let mut data = frame_bytes.split_to(frame_bytes.remaining());
+                if flags & FLAG_COMPRESSED != 0 {
+                    let Some(compression) = self.compression else {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "Compressed data frame without negotiated compression",
+                        ));
+                    };
+                    data = compression.decompress(&data, self.max_frame_size)?.into();
+                }
 
                 Ok(Some(Frame::Data {
                     stream_id,
@@ -245,7 +347,12 @@ impl Encoder<Frame> for TunnelCodec {
             } => {
                 // Data frame: [Length][Type][StreamID][Flags][Data]
                 // Payload = type(1) + stream_id(4) + flags(1) + data.len()
-                let payload_len = 1 + 4 + 1 + data.len();
+                let (data, compressed) = self.compress_payload(data)?;
+                let mut flags = if end_of_stream { FLAG_EOS } else { 0 };
+                if compressed {
+                    flags |= FLAG_COMPRESSED;
+                }
+                let payload_len = 1 + 4 + 1 + data.len() + self.trailer_size();
                 if payload_len > self.max_frame_size {
                     return Err(io::Error::new(
                         io::ErrorKind::InvalidData,
@@ -258,6 +365,7 @@ impl Encoder<Frame> for TunnelCodec {
 
                 // Reserve space for entire frame
                 dst.reserve(4 + payload_len);
+                let start = dst.len();
 
                 // Write length (type + payload, not including length field)
                 dst.put_u32(payload_len as u32);
@@ -266,9 +374,13 @@ impl Encoder<Frame> for TunnelCodec {
                 // Write stream_id
                 dst.put_u32(stream_id);
                 // Write flags
-                dst.put_u8(if end_of_stream { FLAG_EOS } else { 0 });
+                dst.put_u8(flags);
                 // Write data directly - no copy needed if data is contiguous
                 dst.extend_from_slice(&data);
+                if self.checksum {
+                    let crc = crc32fast::hash(&dst[start + 4..]);
+                    dst.put_u32(crc);
+                }
             }
             control_frame => {
                 // Control frame: [Length][Type][bincode payload]
@@ -279,7 +391,7 @@ impl Encoder<Frame> for TunnelCodec {
                         io::Error::new(io::ErrorKind::InvalidData, format!("Encode error: {e}"))
                     })?;
 
-                let payload_len = 1 + serialized.len(); // type + serialized
+                let payload_len = 1 + serialized.len() + self.trailer_size(); // type + serialized
                 if payload_len > self.max_frame_size {
                     return Err(io::Error::new(
                         io::ErrorKind::InvalidData,
@@ -295,16 +407,69 @@ impl Encoder<Frame> for TunnelCodec {
                 dst.put_u32(payload_len as u32);
                 dst.put_u8(FRAME_TYPE_CONTROL);
                 dst.extend_from_slice(&serialized);
+                if self.checksum {
+                    dst.put_u32(frame_checksum(&[&[FRAME_TYPE_CONTROL], &serialized]));
+                }
             }
         }
         Ok(())
     }
 }
 
+/// CRC32 of a frame's Type + Payload, which may be split across `parts`
+pub fn frame_checksum(parts: &[&[u8]]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize()
+}
+
+/// Check the CRC32 trailer of `frame` (Type + Payload + CRC) and return the
+/// frame without it
+fn verify_checksum(frame: &[u8]) -> Result<&[u8], io::Error> {
+    if frame.len() <= CHECKSUM_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Frame too short for checksum: {} bytes", frame.len()),
+        ));
+    }
+    let (body, trailer) = frame.split_at(frame.len() - CHECKSUM_SIZE);
+    let expected = u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let actual = crc32fast::hash(body);
+    if actual != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Checksum mismatch in {} ({} bytes): expected {expected:#010x}, got {actual:#010x}",
+                describe_frame(body),
+                body.len()
+            ),
+        ));
+    }
+    Ok(body)
+}
+
+/// Frame kind for error messages; `body` is Type + Payload
+fn describe_frame(body: &[u8]) -> String {
+    match body {
+        [FRAME_TYPE_DATA, a, b, c, d, ..] => {
+            format!(
+                "data frame for stream {}",
+                u32::from_be_bytes([*a, *b, *c, *d])
+            )
+        }
+        [FRAME_TYPE_DATA, ..] => "data frame".to_string(),
+        [FRAME_TYPE_CONTROL, ..] => "control frame".to_string(),
+        [frame_type, ..] => format!("frame of type {frame_type}"),
+        [] => "empty frame".to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bytes::Bytes;
+    use crate::frame::{HandshakeFrame, HandshakeStatus};
 
     #[test]
     fn test_codec_round_trip() {
@@ -529,4 +694,280 @@ mod tests {
             }
         ));
     }
+
+    #[test]
+    fn test_checksum_round_trip() {
+        let mut codec = TunnelCodec::new().with_checksum(true);
+        let mut buf = BytesMut::new();
+
+        let frames = vec![
+            Frame::Heartbeat { timestamp: 7 },
+            Frame::Data {
+                stream_id: 42,
+                data: Bytes::from("hello world"),
+                end_of_stream: true,
+            },
+            Frame::Data {
+                stream_id: 43,
+                data: Bytes::new(),
+                end_of_stream: false,
+            },
+        ];
+        for frame in &frames {
+            codec.encode(frame.clone(), &mut buf).unwrap();
+        }
+        // Each frame carries a 4-byte trailer
+        let mut plain = BytesMut::new();
+        for frame in &frames {
+            TunnelCodec::new()
+                .encode(frame.clone(), &mut plain)
+                .unwrap();
+        }
+        assert_eq!(buf.len(), plain.len() + frames.len() * CHECKSUM_SIZE);
+
+        let mut out = Vec::new();
+        codec.decode_batch(&mut buf, &mut out).unwrap();
+        assert_eq!(out, frames);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_checksum_zerocopy() {
+        let mut codec = TunnelCodec::new().with_checksum(true);
+        let mut wire = BytesMut::new();
+        codec
+            .encode(
+                Frame::Data {
+                    stream_id: 9,
+                    data: Bytes::from("abcd"),
+                    end_of_stream: true,
+                },
+                &mut wire,
+            )
+            .unwrap();
+
+        let mut out = Vec::new();
+        let consumed = codec
+            .decode_data_frames_zerocopy(wire.as_ref(), &mut out)
+            .unwrap();
+        assert_eq!(consumed, wire.len());
+        let ZeroCopyFrame::Data {
+            stream_id,
+            data,
+            fin,
+        } = &out[0];
+        assert_eq!((*stream_id, *data, *fin), (9, &b"abcd"[..], true));
+    }
+
+    #[test]
+    fn test_checksum_detects_corruption() {
+        let mut codec = TunnelCodec::new().with_checksum(true);
+        let mut buf = BytesMut::new();
+        codec
+            .encode(
+                Frame::Data {
+                    stream_id: 42,
+                    data: Bytes::from("hello world"),
+                    end_of_stream: false,
+                },
+                &mut buf,
+            )
+            .unwrap();
+
+        // Flip one bit in the payload
+        let last = buf.len() - CHECKSUM_SIZE - 1;
+        buf[last] ^= 0x10;
+
+        let err = codec.decode(&mut buf.clone()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let message = err.to_string();
+        assert!(message.contains("Checksum mismatch"), "{message}");
+        assert!(message.contains("data frame for stream 42"), "{message}");
+
+        let mut out = Vec::new();
+        let err = codec
+            .decode_data_frames_zerocopy(buf.as_ref(), &mut out)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(out.is_empty());
+
+        // A corrupted control frame is caught before bincode sees it
+        let mut buf = BytesMut::new();
+        codec
+            .encode(Frame::Heartbeat { timestamp: 1 }, &mut buf)
+            .unwrap();
+        buf[5] ^= 0x01;
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"), "{err}");
+    }
+
+    #[test]
+    fn test_checksum_frame_too_short() {
+        let mut codec = TunnelCodec::new().with_checksum(true);
+        let mut buf = BytesMut::new();
+        buf.put_u32(3);
+        buf.extend_from_slice(&[FRAME_TYPE_CONTROL, 0, 0]);
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    /// Version 1 handshake fields: token, tunnel ID, min and max version,
+    /// capabilities
+    type V1Handshake = (String, Option<String>, u8, u8, Vec<String>);
+
+    /// Layout of the handshake frames in protocol version 1, which later
+    /// releases keep so that earlier peers can still decode them
+    #[derive(serde::Serialize)]
+    enum V1Frame {
+        Handshake(Box<V1Handshake>),
+        HandshakeAck(uuid::Uuid, HandshakeStatus, u8, Vec<String>),
+    }
+
+    #[test]
+    fn test_handshake_keeps_version_1_layout() {
+        let config = bincode_next::config::standard();
+        let session_id = uuid::Uuid::new_v4();
+        let frames = [
+            (
+                V1Frame::Handshake(Box::new((
+                    "token".into(),
+                    Some("app".into()),
+                    1,
+                    1,
+                    vec!["basic".into()],
+                ))),
+                Frame::Handshake(Box::new(HandshakeFrame {
+                    token: "token".into(),
+                    tunnel_id: Some("app".into()),
+                    min_version: 1,
+                    max_version: 1,
+                    capabilities: vec!["basic".into()],
+                })),
+            ),
+            (
+                V1Frame::HandshakeAck(session_id, HandshakeStatus::Success, 1, vec![]),
+                Frame::HandshakeAck {
+                    session_id,
+                    status: HandshakeStatus::Success,
+                    version: 1,
+                    server_capabilities: vec![],
+                },
+            ),
+        ];
+        for (v1, frame) in frames {
+            assert_eq!(
+                bincode_next::serde::encode_to_vec(&frame, config).unwrap(),
+                bincode_next::serde::encode_to_vec(&v1, config).unwrap(),
+                "{frame:?}"
+            );
+        }
+    }
+
+    /// Wire bytes of a compressible data frame encoded with `compression`
+    fn encode_compressible(compression: Option<FrameCompression>) -> (Bytes, BytesMut) {
+        let data = Bytes::from("tunnel ".repeat(200));
+        let mut wire = BytesMut::new();
+        TunnelCodec::new()
+            .with_compression(compression)
+            .encode(
+                Frame::Data {
+                    stream_id: 5,
+                    data: data.clone(),
+                    end_of_stream: true,
+                },
+                &mut wire,
+            )
+            .unwrap();
+        (data, wire)
+    }
+
+    #[test]
+    fn test_compressed_data_round_trip() {
+        for compression in FrameCompression::default_preference() {
+            let mut codec = TunnelCodec::new()
+                .with_compression(Some(compression))
+                .with_checksum(true);
+            let data = Bytes::from("tunnel ".repeat(200));
+            let frames = vec![
+                Frame::Data {
+                    stream_id: 5,
+                    data,
+                    end_of_stream: true,
+                },
+                // Too small to shrink, so sent as is
+                Frame::Data {
+                    stream_id: 6,
+                    data: Bytes::from("x"),
+                    end_of_stream: false,
+                },
+                Frame::Heartbeat { timestamp: 3 },
+            ];
+            let mut buf = BytesMut::new();
+            for frame in &frames {
+                codec.encode(frame.clone(), &mut buf).unwrap();
+            }
+            let mut out = Vec::new();
+            codec.decode_batch(&mut buf, &mut out).unwrap();
+            assert_eq!(out, frames, "{}", compression.name());
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_used_for_data_frames() {
+        let (data, wire) = encode_compressible(Some(FrameCompression::Zstd));
+        assert_eq!(wire[9], FLAG_EOS | FLAG_COMPRESSED);
+        // zstd frame magic number
+        assert_eq!(&wire[10..14], &[0x28, 0xB5, 0x2F, 0xFD]);
+        assert_eq!(
+            zstd::bulk::decompress(&wire[10..], data.len()).unwrap(),
+            data
+        );
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_lz4_used_for_data_frames() {
+        let (data, wire) = encode_compressible(Some(FrameCompression::Lz4));
+        assert_eq!(wire[9], FLAG_EOS | FLAG_COMPRESSED);
+        assert_eq!(
+            lz4_flex::block::decompress_size_prepended(&wire[10..]).unwrap(),
+            data
+        );
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_used_for_data_frames() {
+        use std::io::Read;
+
+        let (data, wire) = encode_compressible(Some(FrameCompression::Gzip));
+        assert_eq!(wire[9], FLAG_EOS | FLAG_COMPRESSED);
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&wire[10..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_compressed_frame_needs_negotiated_compression() {
+        let Some(compression) = FrameCompression::default_preference().first().copied() else {
+            return;
+        };
+        let (_, wire) = encode_compressible(Some(compression));
+
+        let err = TunnelCodec::new().decode(&mut wire.clone()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = TunnelCodec::new()
+            .with_compression(Some(compression))
+            .decode_data_frames_zerocopy(wire.as_ref(), &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Without compression the payload goes out untouched
+        let (data, plain) = encode_compressible(None);
+        assert_eq!(plain[9], FLAG_EOS);
+        assert_eq!(&plain[10..], &data[..]);
+    }
 }
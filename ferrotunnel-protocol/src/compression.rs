@@ -0,0 +1,245 @@
+//! Data frame compression negotiated per session
+//!
+//! A client lists the algorithms it can use as `comp:<name>` capabilities;
+//! the server grants the first algorithm in its own preference order that the
+//! client offered, and both peers then compress data frame payloads with it
+//! (see [`TunnelCodec::with_compression`](crate::TunnelCodec::with_compression)).
+//!
+//! Each algorithm sits behind a cargo feature of the same name (`zstd`,
+//! `lz4`, `gzip`), so binaries only link the codecs they use. A peer never
+//! advertises or grants an algorithm it was built without.
+
+use std::io;
+
+/// Prefix of the capabilities that offer and grant a compression algorithm,
+/// e.g. `comp:zstd`
+pub const COMPRESSION_CAPABILITY_PREFIX: &str = "comp:";
+
+/// zstd level for data frames: fast, with most of the ratio of higher levels
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 3;
+
+/// Size of the uncompressed-length prefix of an lz4 payload
+#[cfg(feature = "lz4")]
+const LZ4_SIZE_PREFIX: usize = 4;
+
+/// Algorithm used to compress data frame payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameCompression {
+    /// Best ratio, for bandwidth-constrained links
+    #[cfg(feature = "zstd")]
+    Zstd,
+    /// Lowest CPU cost and latency
+    #[cfg(feature = "lz4")]
+    Lz4,
+    /// Widely available fallback
+    #[cfg(feature = "gzip")]
+    Gzip,
+}
+
+impl FrameCompression {
+    /// Every compiled-in algorithm, best ratio first: zstd, lz4, gzip
+    pub fn default_preference() -> Vec<Self> {
+        vec![
+            #[cfg(feature = "zstd")]
+            Self::Zstd,
+            #[cfg(feature = "lz4")]
+            Self::Lz4,
+            #[cfg(feature = "gzip")]
+            Self::Gzip,
+        ]
+    }
+
+    /// Name used in the capability, e.g. `zstd`
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "zstd")]
+            Self::Zstd => "zstd",
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => "lz4",
+            #[cfg(feature = "gzip")]
+            Self::Gzip => "gzip",
+        }
+    }
+
+    /// Capability offering or granting this algorithm, e.g. `comp:zstd`
+    pub fn capability(self) -> String {
+        format!("{COMPRESSION_CAPABILITY_PREFIX}{}", self.name())
+    }
+
+    /// Algorithm named by `capability`, if it is a compression capability
+    /// for an algorithm this build supports
+    pub fn from_capability(capability: &str) -> Option<Self> {
+        let name = capability.strip_prefix(COMPRESSION_CAPABILITY_PREFIX)?;
+        Self::default_preference()
+            .into_iter()
+            .find(|algorithm| algorithm.name() == name)
+    }
+
+    /// Algorithm granted in a handshake ack's capabilities, if any
+    pub fn from_capabilities(capabilities: &[String]) -> Option<Self> {
+        capabilities
+            .iter()
+            .find_map(|capability| Self::from_capability(capability))
+    }
+
+    /// First algorithm in `preference` that the peer `offered`
+    pub fn negotiate(offered: &[String], preference: &[Self]) -> Option<Self> {
+        preference.iter().copied().find(|algorithm| {
+            offered
+                .iter()
+                .any(|capability| Self::from_capability(capability) == Some(*algorithm))
+        })
+    }
+
+    /// Compress a data frame payload
+    #[cfg_attr(
+        not(any(feature = "zstd", feature = "lz4", feature = "gzip")),
+        allow(unused_variables)
+    )]
+    pub fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "zstd")]
+            Self::Zstd => zstd::bulk::compress(data, ZSTD_LEVEL),
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => Ok(lz4_flex::block::compress_prepend_size(data)),
+            #[cfg(feature = "gzip")]
+            Self::Gzip => {
+                use std::io::Write;
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    /// Decompress a data frame payload, failing if it would expand beyond
+    /// `max_size` bytes
+    #[cfg_attr(
+        not(any(feature = "zstd", feature = "lz4", feature = "gzip")),
+        allow(unused_variables)
+    )]
+    pub fn decompress(self, data: &[u8], max_size: usize) -> io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "zstd")]
+            Self::Zstd => zstd::bulk::decompress(data, max_size),
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => {
+                let (prefix, block) = data
+                    .split_first_chunk::<LZ4_SIZE_PREFIX>()
+                    .ok_or_else(|| invalid("lz4 payload too short"))?;
+                let size = u32::from_le_bytes(*prefix) as usize;
+                if size > max_size {
+                    return Err(too_large(max_size));
+                }
+                lz4_flex::block::decompress(block, size).map_err(|e| invalid(&e.to_string()))
+            }
+            #[cfg(feature = "gzip")]
+            Self::Gzip => {
+                use std::io::Read;
+                let mut out = Vec::new();
+                flate2::read::GzDecoder::new(data)
+                    .take(max_size as u64 + 1)
+                    .read_to_end(&mut out)?;
+                if out.len() > max_size {
+                    return Err(too_large(max_size));
+                }
+                Ok(out)
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "lz4", feature = "gzip"))]
+fn too_large(max_size: usize) -> io::Error {
+    invalid(&format!("Decompressed payload exceeds {max_size} bytes"))
+}
+
+#[cfg(any(feature = "lz4", feature = "gzip"))]
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offered(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| format!("comp:{name}")).collect()
+    }
+
+    #[test]
+    fn test_unknown_capabilities_ignored() {
+        assert_eq!(FrameCompression::from_capability("comp:brotli"), None);
+        assert_eq!(FrameCompression::from_capability("zstd"), None);
+        assert_eq!(
+            FrameCompression::negotiate(
+                &offered(&["brotli"]),
+                &FrameCompression::default_preference()
+            ),
+            None
+        );
+        assert_eq!(
+            FrameCompression::from_capabilities(&["basic".to_string(), "crc".to_string()]),
+            None
+        );
+    }
+
+    #[cfg(all(feature = "zstd", feature = "lz4", feature = "gzip"))]
+    #[test]
+    fn test_negotiation_follows_preference() {
+        let all = FrameCompression::default_preference();
+        assert_eq!(
+            all,
+            [
+                FrameCompression::Zstd,
+                FrameCompression::Lz4,
+                FrameCompression::Gzip
+            ]
+        );
+        // The preference order wins over the order things were offered in
+        assert_eq!(
+            FrameCompression::negotiate(&offered(&["gzip", "lz4", "zstd"]), &all),
+            Some(FrameCompression::Zstd)
+        );
+        assert_eq!(
+            FrameCompression::negotiate(&offered(&["gzip", "lz4"]), &all),
+            Some(FrameCompression::Lz4)
+        );
+        let latency_first = [FrameCompression::Lz4, FrameCompression::Zstd];
+        assert_eq!(
+            FrameCompression::negotiate(&offered(&["zstd", "lz4"]), &latency_first),
+            Some(FrameCompression::Lz4)
+        );
+        assert_eq!(
+            FrameCompression::negotiate(&offered(&["gzip"]), &latency_first),
+            None
+        );
+        assert_eq!(
+            FrameCompression::from_capabilities(&["basic".to_string(), "comp:gzip".to_string()]),
+            Some(FrameCompression::Gzip)
+        );
+    }
+
+    #[test]
+    fn test_round_trip_and_size_limit() {
+        let data = b"ferrotunnel ".repeat(100);
+        for algorithm in FrameCompression::default_preference() {
+            let compressed = algorithm.compress(&data).unwrap();
+            assert!(compressed.len() < data.len(), "{}", algorithm.name());
+            assert_eq!(algorithm.decompress(&compressed, data.len()).unwrap(), data);
+            let err = algorithm
+                .decompress(&compressed, data.len() - 1)
+                .unwrap_err();
+            assert!(
+                matches!(
+                    err.kind(),
+                    io::ErrorKind::InvalidData | io::ErrorKind::Other
+                ),
+                "{}: {err}",
+                algorithm.name()
+            );
+        }
+    }
+}
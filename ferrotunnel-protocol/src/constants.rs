@@ -12,6 +12,61 @@ pub const MAX_PROTOCOL_VERSION: u8 = 1;
 /// Maximum frame size (16MB)
 pub const MAX_FRAME_SIZE: u32 = 16 * 1024 * 1024;
 
+/// Smallest max frame size a peer may advertise (16KB)
+pub const MIN_FRAME_SIZE: u32 = 16 * 1024;
+
+/// Capability prefix a client advertises with the largest frame it accepts,
+/// e.g. `max_frame:65536`; the server grants it back with the smaller of
+/// both peers' limits. Peers without it accept up to [`MAX_FRAME_SIZE`].
+pub const MAX_FRAME_SIZE_CAPABILITY: &str = "max_frame";
+
+/// Capability a client advertises to ask for a CRC32 on every frame after
+/// the handshake; see [`TunnelCodec::with_checksum`](crate::TunnelCodec::with_checksum)
+pub const CHECKSUM_CAPABILITY: &str = "crc";
+
+/// Capability a client advertises to prove its token by answering an
+/// `AuthChallenge` instead of sending the token in the handshake
+pub const CHALLENGE_AUTH_CAPABILITY: &str = "hmac";
+
+/// Capability a client advertises to accept raw TCP streams
+pub const TCP_CAPABILITY: &str = "tcp";
+
+/// Capability a client advertises to accept UDP streams
+pub const UDP_CAPABILITY: &str = "udp";
+
+/// Capability a client advertises to accept upgraded WebSocket connections
+pub const WEBSOCKET_CAPABILITY: &str = "websocket";
+
+/// Capability a client advertises to declare that its `tcp`, `udp` and
+/// `websocket` capabilities list every stream protocol it accepts
+///
+/// Earlier clients never advertise `websocket`, so sessions without this
+/// capability are sent WebSocket upgrades regardless.
+pub const STREAM_PROTOCOLS_CAPABILITY: &str = "protocols";
+
+/// Capability a client advertises to learn its tunnel's public URL: servers
+/// that know it grant the capability back and follow the `HandshakeAck` with
+/// a `RegisterAck` carrying the URL
+pub const PUBLIC_URL_CAPABILITY: &str = "public_url";
+
+/// Capability a client advertises when it follows its handshake with a
+/// `PathRules` frame, without waiting for a reply
+pub const PATH_RULES_CAPABILITY: &str = "path_rules";
+
+/// Capability prefix a reconnecting client advertises with the session it
+/// held before, e.g. `resume:<session id>`, so the server replaces that
+/// session instead of rejecting its tunnel ID as taken
+pub const RESUME_CAPABILITY: &str = "resume";
+
+/// Capability a peer advertises to answer `Ping` frames with `Pong`
+///
+/// Earlier peers do not know these frames, so pings are only sent once both
+/// sides have negotiated it.
+pub const PING_CAPABILITY: &str = "ping";
+
+/// Size of the random nonce in an `AuthChallenge`, in bytes
+pub const AUTH_NONCE_SIZE: usize = 32;
+
 /// Heartbeat interval in seconds
 pub const HEARTBEAT_INTERVAL_SECS: u64 = 30;
 
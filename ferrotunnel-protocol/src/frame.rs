@@ -1,5 +1,7 @@
 //! Protocol frame definitions
 
+use crate::constants::{TCP_CAPABILITY, UDP_CAPABILITY, WEBSOCKET_CAPABILITY};
+use crate::path_rules::PathRules;
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -98,7 +100,8 @@ pub enum Frame {
         metadata: HashMap<String, String>,
     },
 
-    /// Registration response
+    /// Registration response; also sent right after a `HandshakeAck`
+    /// granting the `public_url` capability, with the tunnel's public URL
     RegisterAck {
         public_url: String,
         status: RegisterStatus,
@@ -123,10 +126,12 @@ pub enum Frame {
     /// Close a stream
     CloseStream { stream_id: u32, reason: CloseReason },
 
-    /// Heartbeat ping
+    /// Heartbeat ping; `timestamp` is opaque to the receiver, which echoes it
+    /// back. The client sends a monotonic clock reading in microseconds.
     Heartbeat { timestamp: u64 },
 
-    /// Heartbeat acknowledgment
+    /// Heartbeat acknowledgment, echoing the `timestamp` of the heartbeat it
+    /// answers so the sender can measure round-trip time
     HeartbeatAck { timestamp: u64 },
 
     /// Error frame
@@ -138,6 +143,44 @@ pub enum Frame {
 
     /// Plugin data (for future use)
     PluginData { plugin_id: String, data: Bytes },
+
+    /// Return `delta` bytes of send credit for a stream (flow control)
+    WindowUpdate { stream_id: u32, delta: u32 },
+
+    /// Application-level liveness probe; the peer answers with a `Pong`
+    /// echoing `nonce`
+    Ping { nonce: u64 },
+
+    /// Answer to a `Ping`
+    Pong { nonce: u64 },
+
+    /// Sent by a server that is about to close the connection on purpose,
+    /// with an optional hint for how long clients should wait before
+    /// reconnecting
+    Shutdown {
+        reason: String,
+        reconnect_after_ms: Option<u64>,
+    },
+
+    /// Sent by the server in reply to a handshake advertising the `hmac`
+    /// capability, with a random nonce fresh to this connection
+    AuthChallenge { nonce: Bytes },
+
+    /// Client answer to an `AuthChallenge`: `HMAC-SHA256(token, nonce)`,
+    /// followed by the server's `HandshakeAck`
+    AuthResponse { mac: Bytes },
+
+    /// Abort a stream: unlike `CloseStream`, whatever the stream carried so
+    /// far is incomplete and must be discarded. Readers on the peer get an
+    /// error, never EOF.
+    ResetStream {
+        stream_id: u32,
+        error_code: ResetCode,
+    },
+
+    /// Paths of the tunnel the HTTP ingress may forward, sent by a client
+    /// right after a handshake advertising the `path_rules` capability
+    PathRules(Box<PathRules>),
 }
 
 /// Handshake status codes
@@ -149,6 +192,14 @@ pub enum HandshakeStatus {
     VersionMismatch,
     RateLimited,
     TunnelIdTaken,
+    /// The client certificate is not allowed to register the tunnel ID
+    Unauthorized,
+    /// The handshake's path rules are malformed or over the limits
+    InvalidPathRules,
+    /// The server is at its session limit; the client may retry later
+    ServerFull,
+    /// The handshake's max frame size is below the protocol minimum
+    InvalidFrameSize,
 }
 
 /// Registration status codes
@@ -177,9 +228,26 @@ pub enum Protocol {
     WebSocket,
     GRPC,
     TCP,
+    UDP,
+}
+
+impl Protocol {
+    /// Capability a session must have been granted before it is sent
+    /// streams of this protocol; `None` for protocols every client handles
+    pub const fn capability(self) -> Option<&'static str> {
+        match self {
+            Self::TCP => Some(TCP_CAPABILITY),
+            Self::UDP => Some(UDP_CAPABILITY),
+            Self::WebSocket => Some(WEBSOCKET_CAPABILITY),
+            Self::HTTP | Self::HTTPS | Self::HTTP2 | Self::GRPC => None,
+        }
+    }
 }
 
 /// Stream close reasons
+///
+/// Anything other than `Normal` or `Shutdown` is an abnormal close and is
+/// reported to the peer's reader as an error rather than EOF.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum CloseReason {
     Normal,
@@ -187,6 +255,23 @@ pub enum CloseReason {
     Error(String),
     LocalServiceUnreachable,
     ProtocolViolation,
+    /// A stream, byte or rate quota was exhausted
+    QuotaExceeded,
+    /// The multiplexer carrying the stream went away
+    Shutdown,
+}
+
+/// Why a stream was reset with `ResetStream`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ResetCode {
+    /// The stream is no longer wanted
+    Cancel,
+    /// The local service failed partway through its response
+    LocalServiceFailed,
+    /// The sender hit an internal error
+    InternalError,
+    /// The peer broke the stream's protocol
+    ProtocolError,
 }
 
 /// Zero-copy view of a data frame (borrows from parse buffer).
@@ -285,6 +370,39 @@ mod tests {
                 stream_id: 1,
                 reason: CloseReason::Normal,
             },
+            Frame::CloseStream {
+                stream_id: 1,
+                reason: CloseReason::QuotaExceeded,
+            },
+            Frame::CloseStream {
+                stream_id: 1,
+                reason: CloseReason::Shutdown,
+            },
+            Frame::WindowUpdate {
+                stream_id: 1,
+                delta: 65_536,
+            },
+            Frame::Ping { nonce: 42 },
+            Frame::Pong { nonce: u64::MAX },
+            Frame::Shutdown {
+                reason: "restarting".to_string(),
+                reconnect_after_ms: Some(5_000),
+            },
+            Frame::AuthChallenge {
+                nonce: Bytes::from_static(&[7; 32]),
+            },
+            Frame::AuthResponse {
+                mac: Bytes::from_static(&[9; 32]),
+            },
+            Frame::ResetStream {
+                stream_id: 1,
+                error_code: ResetCode::LocalServiceFailed,
+            },
+            Frame::PathRules(Box::new(
+                PathRules::default()
+                    .with_allow("/webhooks/**")
+                    .with_deny("/webhooks/internal/**"),
+            )),
             Frame::Error {
                 stream_id: Some(1),
                 code: ErrorCode::ProtocolError,
@@ -299,4 +417,28 @@ mod tests {
                 bincode_next::serde::decode_from_slice(&encoded, config).unwrap();
         }
     }
+
+    #[test]
+    fn test_protocol_capability() {
+        assert_eq!(Protocol::TCP.capability(), Some("tcp"));
+        assert_eq!(Protocol::UDP.capability(), Some("udp"));
+        assert_eq!(Protocol::WebSocket.capability(), Some("websocket"));
+        assert_eq!(Protocol::HTTP.capability(), None);
+        assert_eq!(Protocol::GRPC.capability(), None);
+    }
+
+    #[test]
+    fn test_reset_stream_round_trip() {
+        let frame = Frame::ResetStream {
+            stream_id: 7,
+            error_code: ResetCode::Cancel,
+        };
+
+        let config = bincode_next::config::standard();
+        let encoded = bincode_next::serde::encode_to_vec(&frame, config).unwrap();
+        let (decoded, _): (Frame, usize) =
+            bincode_next::serde::decode_from_slice(&encoded, config).unwrap();
+
+        assert_eq!(frame, decoded);
+    }
 }
@@ -4,13 +4,17 @@
 //! `FerroTunnel` clients and servers.
 
 pub mod codec;
+pub mod compression;
 pub mod constants;
 pub mod frame;
+pub mod path_rules;
 pub mod validation;
 
 pub use codec::TunnelCodec;
+pub use compression::FrameCompression;
 pub use frame::{
-    CloseReason, Frame, HandshakeStatus, Protocol, RegisterStatus, StreamPriority, StreamStatus,
-    ZeroCopyFrame,
+    CloseReason, Frame, HandshakeStatus, Protocol, RegisterStatus, ResetCode, StreamPriority,
+    StreamStatus, ZeroCopyFrame,
 };
+pub use path_rules::{PathRuleError, PathRules};
 pub use validation::{validate_frame, ValidationError, ValidationLimits};
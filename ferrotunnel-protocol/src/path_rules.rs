@@ -0,0 +1,297 @@
+//! Per-tunnel HTTP path access rules
+//!
+//! A client may register its tunnel with glob patterns limiting which request
+//! paths the HTTP ingress forwards, e.g. exposing `/webhooks/**` but never
+//! `/admin/**`. The server validates the rules during the handshake and the
+//! ingress checks every request against the rules of the tunnel it targets.
+//!
+//! Patterns are matched against the whole path, without the query string:
+//! - `*` matches any run of characters within one segment
+//! - `**` matches any run of characters, across segments
+//! - `?` matches one byte other than `/`
+//!
+//! Paths are percent-decoded and their `.`/`..` segments resolved before
+//! matching, so `/webhooks/..%2Fadmin` is checked as `/admin`.
+
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of allow plus deny patterns
+pub const MAX_PATH_RULES: usize = 64;
+
+/// Maximum length of one pattern in bytes
+pub const MAX_PATH_RULE_LEN: usize = 256;
+
+/// Path rule validation errors
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PathRuleError {
+    #[error("Too many path rules: {count} exceeds limit of {limit}")]
+    TooMany { count: usize, limit: usize },
+
+    #[error("Path rule too long: {len} bytes exceeds limit of {limit} bytes")]
+    TooLong { len: usize, limit: usize },
+
+    #[error("Path rule '{0}' must start with '/'")]
+    NotAbsolute(String),
+
+    #[error("Path rule '{0}' has more than two consecutive '*'")]
+    InvalidWildcard(String),
+}
+
+/// Allow and deny glob patterns for the paths of a tunnel
+///
+/// A path is forwarded when it matches no deny pattern and, if any allow
+/// patterns are set, at least one of them. The default allows every path.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PathRules {
+    /// Paths that may be forwarded; empty allows every path not denied
+    pub allow: Vec<String>,
+    /// Paths that are never forwarded, even when allowed
+    pub deny: Vec<String>,
+}
+
+impl PathRules {
+    /// Add an allow pattern
+    #[must_use]
+    pub fn with_allow(mut self, pattern: impl Into<String>) -> Self {
+        self.allow.push(pattern.into());
+        self
+    }
+
+    /// Add a deny pattern
+    #[must_use]
+    pub fn with_deny(mut self, pattern: impl Into<String>) -> Self {
+        self.deny.push(pattern.into());
+        self
+    }
+
+    /// Whether no patterns are set, so every path is allowed
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Check the rules are well-formed and within limits
+    pub fn validate(&self) -> Result<(), PathRuleError> {
+        let count = self.allow.len() + self.deny.len();
+        if count > MAX_PATH_RULES {
+            return Err(PathRuleError::TooMany {
+                count,
+                limit: MAX_PATH_RULES,
+            });
+        }
+        for pattern in self.allow.iter().chain(&self.deny) {
+            if pattern.len() > MAX_PATH_RULE_LEN {
+                return Err(PathRuleError::TooLong {
+                    len: pattern.len(),
+                    limit: MAX_PATH_RULE_LEN,
+                });
+            }
+            if !pattern.starts_with('/') {
+                return Err(PathRuleError::NotAbsolute(pattern.clone()));
+            }
+            if pattern.contains("***") {
+                return Err(PathRuleError::InvalidWildcard(pattern.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a request for `path` may be forwarded
+    pub fn permits(&self, path: &str) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let Some(path) = normalize_path(path) else {
+            return false;
+        };
+        let matches = |pattern: &String| glob_match(pattern, &path);
+        !self.deny.iter().any(matches) && (self.allow.is_empty() || self.allow.iter().any(matches))
+    }
+}
+
+/// Percent-decode `path` and resolve its dot segments, the way the local
+/// service may interpret it. `None` when it does not decode to UTF-8.
+fn normalize_path(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    let decoded = String::from_utf8(decoded).ok()?;
+
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    let trailing = decoded.len() > 1 && decoded.ends_with('/');
+    let mut normalized = format!("/{}", segments.join("/"));
+    if trailing && !segments.is_empty() {
+        normalized.push('/');
+    }
+    Some(normalized)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Byte(u8),
+    /// `?`
+    One,
+    /// `*`
+    Segment,
+    /// `**`
+    Any,
+}
+
+fn tokenize(pattern: &str) -> Vec<Token> {
+    let bytes = pattern.as_bytes();
+    let mut tokens = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let token = match bytes[i] {
+            b'*' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 1;
+                Token::Any
+            }
+            b'*' => Token::Segment,
+            b'?' => Token::One,
+            byte => Token::Byte(byte),
+        };
+        tokens.push(token);
+        i += 1;
+    }
+    tokens
+}
+
+/// Match `path` against `pattern`, tracking every pattern position reachable
+/// after each path byte so the cost stays linear in the path length
+fn glob_match(pattern: &str, path: &str) -> bool {
+    let tokens = tokenize(pattern);
+    let mut states = vec![false; tokens.len() + 1];
+    states[0] = true;
+    skip_wildcards(&tokens, &mut states);
+
+    for &byte in path.as_bytes() {
+        let mut next = vec![false; tokens.len() + 1];
+        for (i, token) in tokens.iter().enumerate() {
+            if !states[i] {
+                continue;
+            }
+            match *token {
+                Token::Byte(expected) if expected == byte => next[i + 1] = true,
+                Token::One if byte != b'/' => next[i + 1] = true,
+                Token::Segment if byte != b'/' => next[i] = true,
+                Token::Any => next[i] = true,
+                _ => {}
+            }
+        }
+        skip_wildcards(&tokens, &mut next);
+        if !next.contains(&true) {
+            return false;
+        }
+        states = next;
+    }
+    states[tokens.len()]
+}
+
+/// Wildcards may also match nothing
+fn skip_wildcards(tokens: &[Token], states: &mut [bool]) {
+    for (i, token) in tokens.iter().enumerate() {
+        if states[i] && matches!(token, Token::Segment | Token::Any) {
+            states[i + 1] = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("/webhooks/*", "/webhooks/github"));
+        assert!(!glob_match("/webhooks/*", "/webhooks/github/push"));
+        assert!(glob_match("/webhooks/**", "/webhooks/github/push"));
+        assert!(!glob_match("/webhooks/**", "/webhooksx"));
+        assert!(glob_match("/api/v?/users", "/api/v2/users"));
+        assert!(!glob_match("/api/v?/users", "/api/v10/users"));
+        assert!(glob_match("/**/*.json", "/a/b/c.json"));
+        assert!(glob_match("/exact", "/exact"));
+        assert!(!glob_match("/exact", "/exact/"));
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("/a/./b/../c").unwrap(), "/a/c");
+        assert_eq!(normalize_path("/webhooks/..%2Fadmin").unwrap(), "/admin");
+        assert_eq!(normalize_path("/%61dmin//users/").unwrap(), "/admin/users/");
+        assert_eq!(normalize_path("/../..").unwrap(), "/");
+        assert_eq!(normalize_path("/100%").unwrap(), "/100%");
+        assert!(normalize_path("/%ff").is_none());
+    }
+
+    #[test]
+    fn test_permits() {
+        let rules = PathRules::default()
+            .with_allow("/webhooks/**")
+            .with_allow("/health")
+            .with_deny("/webhooks/internal/**");
+
+        assert!(rules.permits("/webhooks/github"));
+        assert!(rules.permits("/health"));
+        assert!(!rules.permits("/admin"));
+        assert!(!rules.permits("/webhooks/internal/keys"));
+        assert!(!rules.permits("/webhooks/../admin"));
+        assert!(!rules.permits("/webhooks/%69nternal/keys"));
+
+        let deny_only = PathRules::default().with_deny("/admin/**");
+        assert!(deny_only.permits("/"));
+        assert!(!deny_only.permits("/admin/users"));
+        assert!(PathRules::default().permits("/anything"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(PathRules::default().with_allow("/a/**").validate().is_ok());
+        assert_eq!(
+            PathRules::default().with_allow("admin").validate(),
+            Err(PathRuleError::NotAbsolute("admin".into()))
+        );
+        assert_eq!(
+            PathRules::default().with_deny("/a/***").validate(),
+            Err(PathRuleError::InvalidWildcard("/a/***".into()))
+        );
+        let long = format!("/{}", "a".repeat(MAX_PATH_RULE_LEN));
+        assert!(matches!(
+            PathRules::default().with_allow(long).validate(),
+            Err(PathRuleError::TooLong { .. })
+        ));
+        let mut many = PathRules::default();
+        for i in 0..=MAX_PATH_RULES {
+            many = many.with_allow(format!("/{i}"));
+        }
+        assert!(matches!(
+            many.validate(),
+            Err(PathRuleError::TooMany { .. })
+        ));
+    }
+}
@@ -0,0 +1,179 @@
+//! Dashboard API for an embedded [`Server`](crate::Server).
+//!
+//! Serves the observability dashboard router with its tunnel list read from
+//! the server's live session store and its request log fed by the HTTP
+//! ingress, so embedders get the same admin API as the standalone binary
+//! without wiring it up themselves. Every endpoint requires the configured
+//! bearer token, and logged headers carrying credentials are masked.
+
+use async_trait::async_trait;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use ferrotunnel_common::Result;
+use ferrotunnel_core::auth::constant_time_eq;
+use ferrotunnel_core::tunnel::server::public_url;
+use ferrotunnel_core::tunnel::session::SessionStoreBackend;
+use ferrotunnel_http::{IngressInspector, RequestParts, ResponseParts};
+use ferrotunnel_observability::dashboard::{
+    create_router, DashboardEvent, DashboardState, DashboardTunnelInfo, EventBroadcaster,
+    RequestDetails, RequestLogEntry, SharedDashboardState, TunnelSource,
+};
+use ferrotunnel_plugin::builtin::RedactionConfig;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Requests kept in the dashboard's request log
+const MAX_LOGGED_REQUESTS: usize = 1000;
+
+/// Events buffered for each dashboard subscriber
+const EVENT_CAPACITY: usize = 100;
+
+/// Tunnels connected to the server, as the dashboard lists them
+#[derive(Debug)]
+struct LiveTunnels {
+    sessions: SessionStoreBackend,
+    public_base: Option<String>,
+}
+
+impl TunnelSource for LiveTunnels {
+    fn tunnels(&self) -> Vec<DashboardTunnelInfo> {
+        let now = Instant::now();
+        let mut tunnels = Vec::with_capacity(self.sessions.count());
+        self.sessions.for_each(|session| {
+            let mut info = DashboardTunnelInfo::connected(
+                session.id,
+                session.tunnel_id.clone(),
+                session.client_addr.to_string(),
+                now.saturating_duration_since(session.connected_at),
+            );
+            info.public_url = self
+                .public_base
+                .as_deref()
+                .map(|base| public_url(base, &session.tunnel_id));
+            // The dashboard counts traffic from the client's side
+            info.bytes_in = session.traffic.bytes_out();
+            info.bytes_out = session.traffic.bytes_in();
+            tunnels.push(info);
+        });
+        tunnels.sort_by(|a, b| a.subdomain.cmp(&b.subdomain));
+        tunnels
+    }
+}
+
+/// Dashboard state shared by the admin API and the ingress feeding its
+/// request log
+#[derive(Clone)]
+pub(crate) struct AdminApi {
+    state: SharedDashboardState,
+    events: Arc<EventBroadcaster>,
+    sessions: SessionStoreBackend,
+    token: Arc<str>,
+    /// Masks credentials in logged headers, e.g. other clients' cookies
+    redaction: Arc<RedactionConfig>,
+}
+
+impl AdminApi {
+    pub(crate) fn new(
+        sessions: SessionStoreBackend,
+        public_base: Option<String>,
+        token: &str,
+    ) -> Self {
+        let source = LiveTunnels {
+            sessions: sessions.clone(),
+            public_base: public_base.map(|base| base.trim_end_matches('/').to_string()),
+        };
+        let state = DashboardState::new(MAX_LOGGED_REQUESTS).with_tunnel_source(Arc::new(source));
+        Self {
+            state: Arc::new(RwLock::new(state)),
+            events: Arc::new(EventBroadcaster::new(EVENT_CAPACITY)),
+            sessions,
+            token: token.into(),
+            redaction: Arc::new(RedactionConfig::default()),
+        }
+    }
+
+    /// Serve the dashboard API on `listener` until the task is dropped.
+    pub(crate) async fn serve(self, listener: TcpListener) -> Result<()> {
+        let router = create_router(self.state, self.events)
+            .layer(middleware::from_fn_with_state(self.token, require_token));
+        axum::serve(listener, router).await?;
+        Ok(())
+    }
+}
+
+/// Answer 401 unless the request carries `Authorization: Bearer <token>`
+async fn require_token(State(token): State<Arc<str>>, req: Request, next: Next) -> Response {
+    if bearer_token(req.headers()).is_some_and(|t| constant_time_eq(t.as_bytes(), token.as_bytes()))
+    {
+        return next.run(req).await;
+    }
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        "Unauthorized",
+    )
+        .into_response()
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+/// String form of `headers` with `redaction` applied, skipping values that
+/// are not valid UTF-8
+fn header_map(headers: &HeaderMap, redaction: &RedactionConfig) -> HashMap<String, String> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            let value = redaction.header_value(name.as_str(), value.to_str().ok()?);
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[async_trait]
+impl IngressInspector for AdminApi {
+    async fn on_exchange(
+        &self,
+        tunnel_id: &str,
+        request: &RequestParts,
+        response: &ResponseParts,
+        duration: Duration,
+    ) {
+        // Requests are listed under the session, as tunnels are
+        let Some(session_id) = self.sessions.get_by_tunnel_id(tunnel_id).map(|s| s.id) else {
+            return;
+        };
+        // Bodies stream through the ingress unbuffered, so none are kept
+        let details = RequestDetails {
+            id: Uuid::new_v4(),
+            tunnel_id: session_id,
+            method: request.method.to_string(),
+            path: request
+                .uri
+                .path_and_query()
+                .map_or_else(|| "/".to_string(), ToString::to_string),
+            request_headers: header_map(&request.headers, &self.redaction),
+            request_body: None,
+            status: response.status.as_u16(),
+            response_headers: header_map(&response.headers, &self.redaction),
+            response_body: None,
+            duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+            timestamp: Utc::now() - chrono::Duration::from_std(duration).unwrap_or_default(),
+        };
+        self.events
+            .send(DashboardEvent::NewRequest(RequestLogEntry::from(&details)));
+        self.state.write().await.add_request(details);
+    }
+}
@@ -25,11 +25,55 @@ use ferrotunnel_core::transport::{tls::TlsTransportConfig, TransportConfig};
 use ferrotunnel_core::TunnelClient;
 use ferrotunnel_http::HttpProxy;
 use ferrotunnel_protocol::frame::Protocol;
+use ferrotunnel_protocol::PathRules;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::net::TcpStream;
 use tokio::sync::{oneshot, watch};
 use tokio::task::JoinHandle;
-use tracing::{error, info};
+use tokio::time::Instant;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+/// Pause between readiness probes of the local service
+const LOCAL_PROBE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Connection lifecycle event passed to [`ClientBuilder::on_event`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientEvent {
+    /// The handshake succeeded and the tunnel is serving requests
+    Connected { session_id: Uuid },
+    /// An established connection was lost
+    Disconnected { error: String },
+    /// Waiting `delay` before reconnection attempt number `attempt`,
+    /// counted from the last successful handshake
+    Reconnecting { attempt: usize, delay: Duration },
+    /// [`Client::shutdown`] or [`Client::stop`] was called
+    ShuttingDown,
+}
+
+/// Callback for [`ClientEvent`]s; a no-op unless one is set
+#[derive(Clone)]
+struct EventHandler(Arc<dyn Fn(ClientEvent) + Send + Sync>);
+
+impl EventHandler {
+    fn emit(&self, event: ClientEvent) {
+        (self.0)(event);
+    }
+}
+
+impl Default for EventHandler {
+    fn default() -> Self {
+        Self(Arc::new(|_| {}))
+    }
+}
+
+impl std::fmt::Debug for EventHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventHandler")
+    }
+}
 
 /// A tunnel client that can be embedded in your application.
 ///
@@ -40,6 +84,7 @@ pub struct Client {
     transport_config: TransportConfig,
     shutdown_tx: Option<watch::Sender<bool>>,
     task: Option<JoinHandle<()>>,
+    events: EventHandler,
 }
 
 /// Builder for constructing a [`Client`] with ergonomic configuration.
@@ -47,6 +92,7 @@ pub struct Client {
 pub struct ClientBuilder {
     config: ClientConfig,
     transport_config: Option<TransportConfig>,
+    events: EventHandler,
 }
 
 impl Client {
@@ -73,15 +119,24 @@ impl Client {
     /// The client will run until [`shutdown()`](Self::shutdown) is called or the connection fails.
     ///
     /// If `auto_reconnect` is enabled, the client will automatically
-    /// reconnect on connection loss.
+    /// reconnect on connection loss, up to
+    /// [`max_reconnect_attempts`](ClientBuilder::max_reconnect_attempts) times
+    /// in a row. Authentication and protocol version errors are never retried.
     ///
     /// # Errors
     ///
-    /// Returns an error if the client is already running.
+    /// Returns an error if the client is already running, if the local service
+    /// is not reachable within [`wait_for_local`](ClientBuilder::wait_for_local),
+    /// or the error from the last connection attempt if the client gives up
+    /// before ever connecting.
+    #[allow(clippy::too_many_lines)]
     pub async fn start(&mut self) -> Result<TunnelInfo> {
         if self.task.is_some() {
             return Err(TunnelError::InvalidState("client already started".into()));
         }
+        if let Some(timeout) = self.config.wait_for_local {
+            wait_for_local(&self.config.local_addr, timeout).await?;
+        }
 
         let config = self.config.clone();
         let (shutdown_tx, shutdown_rx) = watch::channel(false);
@@ -93,24 +148,54 @@ impl Client {
         let token = config.token.clone();
         let local_addr = config.local_addr.clone();
         let tunnel_id = config.tunnel_id.clone();
+        let path_rules = config.path_rules.clone();
         let auto_reconnect = config.auto_reconnect;
         let reconnect_delay = config.reconnect_delay;
+        let max_reconnect_attempts = config.max_reconnect_attempts;
+        let heartbeat_interval = config.heartbeat_interval;
+        let heartbeat_timeout = config.heartbeat_timeout;
         let transport_config = self.transport_config.clone();
+        let events = self.events.clone();
 
         let info_tx = Arc::new(std::sync::Mutex::new(Some(info_tx)));
 
         let task = tokio::spawn(async move {
             let proxy = Arc::new(HttpProxy::new(local_addr));
             let mut shutdown_rx = shutdown_rx;
+            // Reconnection attempts since the last successful handshake
+            let mut attempts = 0usize;
 
             loop {
-                let mut client = TunnelClient::new(server_addr.clone(), token.clone())
-                    .with_transport(transport_config.clone());
+                // Every stream goes to the HTTP proxy, so raw TCP and UDP
+                // streams are not accepted
+                let client = TunnelClient::new(server_addr.clone(), token.clone())
+                    .with_stream_protocols(&[Protocol::HTTP, Protocol::WebSocket, Protocol::GRPC])
+                    .with_transport(transport_config.clone())
+                    .with_heartbeat_interval(heartbeat_interval);
+                let mut client = match client {
+                    Ok(client) => client
+                        .with_heartbeat_timeout(heartbeat_timeout)
+                        .with_path_rules(path_rules.clone()),
+                    Err(e) => {
+                        // Unreachable after build() validated the config, but
+                        // never retried either way
+                        if let Ok(mut lock) = info_tx.lock() {
+                            if let Some(tx) = lock.take() {
+                                let _ = tx.send(Err(e));
+                            }
+                        }
+                        break;
+                    }
+                };
                 if let Some(ref id) = tunnel_id {
                     client = client.with_tunnel_id(id.clone());
                 }
                 let proxy_ref = proxy.clone();
-                let info_tx = info_tx.clone();
+                let connected_info_tx = info_tx.clone();
+                let connected = Arc::new(AtomicBool::new(false));
+                let connected_flag = connected.clone();
+                let public_url = client.public_url_handle();
+                let connected_events = events.clone();
 
                 let connect_result = tokio::select! {
                     result = client.connect_and_run_with_callback(move |stream| {
@@ -123,18 +208,21 @@ impl Client {
                             }
                         }
                     }, move |session_id| {
+                        connected_flag.store(true, Ordering::Relaxed);
                         // Send connection info on successful handshake (only once)
-                        if let Ok(mut lock) = info_tx.lock() {
+                        if let Ok(mut lock) = connected_info_tx.lock() {
                             if let Some(tx) = lock.take() {
-                                let _ = tx.send(TunnelInfo {
+                                let _ = tx.send(Ok(TunnelInfo {
                                     session_id: Some(session_id),
-                                    public_url: None,
-                                });
+                                    public_url: public_url.get(),
+                                }));
                             }
                         }
+                        connected_events.emit(ClientEvent::Connected { session_id });
                     }) => result,
                     _ = shutdown_rx.changed() => {
                         info!("Client shutdown requested");
+                        events.emit(ClientEvent::ShuttingDown);
                         break;
                     }
                 };
@@ -145,12 +233,34 @@ impl Client {
                         break;
                     }
                     Err(e) => {
-                        error!("Connection error: {}", e);
-                        if !auto_reconnect {
+                        if matches!(e, TunnelError::ServerShutdown { .. }) {
+                            info!("Disconnected: {}", e);
+                        } else {
+                            error!("Connection error: {}", e);
+                        }
+                        if connected.load(Ordering::Relaxed) {
+                            attempts = 0;
+                            events.emit(ClientEvent::Disconnected {
+                                error: e.to_string(),
+                            });
+                        }
+                        if !should_reconnect(&e, auto_reconnect, attempts, max_reconnect_attempts) {
+                            // Surface the error to `start()` if it is still waiting
+                            if let Ok(mut lock) = info_tx.lock() {
+                                if let Some(tx) = lock.take() {
+                                    let _ = tx.send(Err(e));
+                                }
+                            }
                             break;
                         }
-                        info!("Reconnecting in {:?}...", reconnect_delay);
-                        tokio::time::sleep(reconnect_delay).await;
+                        attempts += 1;
+                        let delay = reconnect_delay_after(&e, reconnect_delay);
+                        info!("Reconnecting in {:?}...", delay);
+                        events.emit(ClientEvent::Reconnecting {
+                            attempt: attempts,
+                            delay,
+                        });
+                        tokio::time::sleep(delay).await;
                     }
                 }
             }
@@ -161,7 +271,7 @@ impl Client {
         // Wait for initial connection
         info_rx
             .await
-            .map_err(|_| TunnelError::Connection("Failed to establish connection".into()))
+            .map_err(|_| TunnelError::Connection("Failed to establish connection".into()))?
     }
 
     /// Shutdown the tunnel client and wait for cleanup.
@@ -211,6 +321,63 @@ impl Drop for Client {
     }
 }
 
+/// Wait until `local_addr` accepts a TCP connection, probing every
+/// [`LOCAL_PROBE_INTERVAL`] for at most `timeout`
+async fn wait_for_local(local_addr: &str, timeout: Duration) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match tokio::time::timeout(remaining, TcpStream::connect(local_addr)).await {
+            Ok(Ok(_)) => {
+                info!("Local service {} is accepting connections", local_addr);
+                return Ok(());
+            }
+            Ok(Err(e)) => debug!("Local service {} not ready: {}", local_addr, e),
+            Err(_) => {}
+        }
+        if Instant::now() + LOCAL_PROBE_INTERVAL >= deadline {
+            return Err(TunnelError::Timeout(format!(
+                "local service {local_addr} not reachable within {timeout:?}"
+            )));
+        }
+        tokio::time::sleep(LOCAL_PROBE_INTERVAL).await;
+    }
+}
+
+/// Whether to reconnect after `err`, given the attempts made since the last
+/// successful handshake
+fn should_reconnect(
+    err: &TunnelError,
+    auto_reconnect: bool,
+    attempts: usize,
+    max_attempts: Option<usize>,
+) -> bool {
+    if !auto_reconnect {
+        return false;
+    }
+    if !err.is_retryable() {
+        error!("Not reconnecting: error is not transient");
+        return false;
+    }
+    if max_attempts.is_some_and(|max| attempts >= max) {
+        error!("Giving up after {} reconnection attempts", attempts);
+        return false;
+    }
+    true
+}
+
+/// How long to wait before reconnecting after `err`: the delay the server
+/// suggested when it announced a shutdown, `default` otherwise
+fn reconnect_delay_after(err: &TunnelError, default: Duration) -> Duration {
+    match err {
+        TunnelError::ServerShutdown {
+            reconnect_after: Some(delay),
+            ..
+        } => *delay,
+        _ => default,
+    }
+}
+
 impl ClientBuilder {
     /// Set the server address to connect to.
     ///
@@ -246,6 +413,18 @@ impl ClientBuilder {
         self
     }
 
+    /// Limit which HTTP paths the server's ingress forwards to this tunnel.
+    ///
+    /// Requests for other paths are answered with `403 Forbidden` by the
+    /// server without reaching the local service.
+    ///
+    /// Default: every path is forwarded
+    #[must_use]
+    pub fn path_rules(mut self, rules: PathRules) -> Self {
+        self.config.path_rules = rules;
+        self
+    }
+
     /// Enable or disable automatic reconnection.
     ///
     /// Default: `true`
@@ -264,6 +443,51 @@ impl ClientBuilder {
         self
     }
 
+    /// Limit how many times in a row the client reconnects after a failure.
+    ///
+    /// `None` (the default) retries forever; `Some(n)` gives up after `n`
+    /// failed reconnection attempts and surfaces the last error. The count
+    /// restarts after every successful handshake. Authentication and protocol
+    /// version errors are never retried.
+    #[must_use]
+    pub fn max_reconnect_attempts(mut self, attempts: Option<usize>) -> Self {
+        self.config.max_reconnect_attempts = attempts;
+        self
+    }
+
+    /// Set the interval between heartbeats sent to the server.
+    ///
+    /// Default: 30 seconds
+    #[must_use]
+    pub fn heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.config.heartbeat_interval = interval;
+        self
+    }
+
+    /// Set how long to wait for a heartbeat ack before treating the connection as dead.
+    ///
+    /// When it expires the connection is dropped and, if enabled, auto-reconnect kicks in.
+    ///
+    /// Default: 90 seconds (three missed heartbeats)
+    #[must_use]
+    pub fn heartbeat_timeout(mut self, timeout: Duration) -> Self {
+        self.config.heartbeat_timeout = timeout;
+        self
+    }
+
+    /// Wait for the local service to accept TCP connections before
+    /// connecting to the server.
+    ///
+    /// [`Client::start`] probes `local_addr` until it answers and fails with
+    /// [`TunnelError::Timeout`] if it is still down after `timeout`.
+    ///
+    /// Default: disabled
+    #[must_use]
+    pub fn wait_for_local(mut self, timeout: Duration) -> Self {
+        self.config.wait_for_local = Some(timeout);
+        self
+    }
+
     /// Configure TLS for the connection.
     ///
     /// When enabled, the client will use TLS to connect to the server.
@@ -275,6 +499,16 @@ impl ClientBuilder {
         self
     }
 
+    /// Call `handler` on every connection lifecycle transition.
+    ///
+    /// It runs on the client's background task, which waits for it to
+    /// return, so it should be quick and must not block on the client.
+    #[must_use]
+    pub fn on_event(mut self, handler: impl Fn(ClientEvent) + Send + Sync + 'static) -> Self {
+        self.events = EventHandler(Arc::new(handler));
+        self
+    }
+
     /// Build the client with the configured options.
     ///
     /// # Errors
@@ -289,6 +523,7 @@ impl ClientBuilder {
             transport_config: self.transport_config.unwrap_or_default(),
             shutdown_tx: None,
             task: None,
+            events: self.events,
         })
     }
 }
@@ -315,6 +550,8 @@ mod tests {
             .local_addr("127.0.0.1:3000")
             .auto_reconnect(false)
             .reconnect_delay(Duration::from_secs(10))
+            .heartbeat_interval(Duration::from_secs(5))
+            .heartbeat_timeout(Duration::from_secs(15))
             .build()
             .expect("should build successfully");
 
@@ -323,6 +560,61 @@ mod tests {
         assert_eq!(client.config().local_addr, "127.0.0.1:3000");
         assert!(!client.config().auto_reconnect);
         assert_eq!(client.config().reconnect_delay, Duration::from_secs(10));
+        assert_eq!(client.config().heartbeat_interval, Duration::from_secs(5));
+        assert_eq!(client.config().heartbeat_timeout, Duration::from_secs(15));
+    }
+
+    #[test]
+    fn test_client_builder_max_reconnect_attempts() {
+        let client = Client::builder()
+            .server_addr("localhost:7835")
+            .token("secret")
+            .build()
+            .unwrap();
+        assert_eq!(client.config().max_reconnect_attempts, None);
+
+        let client = Client::builder()
+            .server_addr("localhost:7835")
+            .token("secret")
+            .max_reconnect_attempts(Some(3))
+            .build()
+            .unwrap();
+        assert_eq!(client.config().max_reconnect_attempts, Some(3));
+    }
+
+    #[test]
+    fn test_should_reconnect() {
+        let transient = TunnelError::Connection("refused".into());
+        assert!(should_reconnect(&transient, true, 100, None));
+        assert!(should_reconnect(&transient, true, 1, Some(2)));
+        assert!(!should_reconnect(&transient, true, 2, Some(2)));
+        assert!(!should_reconnect(&transient, false, 0, None));
+
+        let auth = TunnelError::Authentication("Handshake rejected: InvalidToken".into());
+        assert!(!should_reconnect(&auth, true, 0, None));
+        let version = TunnelError::VersionMismatch("no common version".into());
+        assert!(!should_reconnect(&version, true, 0, None));
+    }
+
+    #[test]
+    fn test_reconnect_delay_honors_server_hint() {
+        let default = Duration::from_secs(5);
+        let hinted = TunnelError::ServerShutdown {
+            reason: "restarting".into(),
+            reconnect_after: Some(Duration::from_millis(750)),
+        };
+        assert_eq!(
+            reconnect_delay_after(&hinted, default),
+            Duration::from_millis(750)
+        );
+
+        let unhinted = TunnelError::ServerShutdown {
+            reason: "restarting".into(),
+            reconnect_after: None,
+        };
+        assert_eq!(reconnect_delay_after(&unhinted, default), default);
+        let dropped = TunnelError::Connection("Connection closed".into());
+        assert_eq!(reconnect_delay_after(&dropped, default), default);
     }
 
     #[test]
@@ -383,4 +675,66 @@ mod tests {
         // TLS disabled means no transport config set
         assert!(!client.config().server_addr.is_empty());
     }
+
+    /// A loopback address with nothing listening on it
+    async fn unused_addr() -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_local_already_up() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        wait_for_local(&addr, Duration::from_secs(1)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_local_comes_up_later() {
+        let addr = unused_addr().await;
+        let server = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+            let _ = listener.accept().await;
+        });
+
+        let started = std::time::Instant::now();
+        wait_for_local(&addr.to_string(), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_local_times_out() {
+        let addr = unused_addr().await;
+        let started = std::time::Instant::now();
+        let err = wait_for_local(&addr.to_string(), Duration::from_millis(300))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, TunnelError::Timeout(_)), "{err}");
+        assert!(err.to_string().contains(&addr.to_string()), "{err}");
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_start_fails_when_local_service_is_down() {
+        let local = unused_addr().await;
+        let mut client = Client::builder()
+            .server_addr("127.0.0.1:1")
+            .token("secret")
+            .local_addr(local.to_string())
+            .wait_for_local(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        assert_eq!(
+            client.config().wait_for_local,
+            Some(Duration::from_millis(200))
+        );
+
+        let err = client.start().await.unwrap_err();
+        assert!(matches!(err, TunnelError::Timeout(_)), "{err}");
+        assert!(!client.is_running());
+    }
 }
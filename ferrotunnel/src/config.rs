@@ -6,8 +6,18 @@
 use ferrotunnel_common::{
     Result, TunnelError, DEFAULT_HTTP_PORT, DEFAULT_LOCAL_ADDR, DEFAULT_TUNNEL_PORT,
 };
+use ferrotunnel_core::resource_limits::ServerResourceLimits;
+use ferrotunnel_core::transport::listener::{
+    ListenerConfig, DUAL_STACK_SUPPORTED, REUSE_PORT_SUPPORTED,
+};
+use ferrotunnel_core::tunnel::client::{DEFAULT_HEARTBEAT_INTERVAL, DEFAULT_HEARTBEAT_TIMEOUT};
+use ferrotunnel_core::tunnel::server::DEFAULT_IDLE_TIMEOUT;
+use ferrotunnel_core::tunnel::session::{PoolPolicy, Session};
+use ferrotunnel_protocol::PathRules;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Configuration for the tunnel client.
 ///
@@ -31,6 +41,24 @@ pub struct ClientConfig {
 
     /// Delay between reconnection attempts
     pub reconnect_delay: Duration,
+
+    /// Reconnection attempts after a failure before giving up; `None` retries
+    /// forever. The count restarts after every successful handshake.
+    pub max_reconnect_attempts: Option<usize>,
+
+    /// Interval between heartbeats sent to the server
+    pub heartbeat_interval: Duration,
+
+    /// Time without a heartbeat ack before the connection is considered dead
+    pub heartbeat_timeout: Duration,
+
+    /// Wait up to this long for `local_addr` to accept connections before
+    /// connecting to the server; `None` connects immediately
+    pub wait_for_local: Option<Duration>,
+
+    /// HTTP paths the server's ingress may forward to this tunnel; empty
+    /// allows every path
+    pub path_rules: PathRules,
 }
 
 impl ClientConfig {
@@ -45,6 +73,19 @@ impl ClientConfig {
         if self.local_addr.is_empty() {
             return Err(TunnelError::Config("local_addr is required".into()));
         }
+        if self.heartbeat_interval.is_zero() {
+            return Err(TunnelError::Config(
+                "heartbeat_interval must be greater than zero".into(),
+            ));
+        }
+        if self.heartbeat_timeout <= self.heartbeat_interval {
+            return Err(TunnelError::Config(
+                "heartbeat_timeout must be greater than heartbeat_interval".into(),
+            ));
+        }
+        self.path_rules
+            .validate()
+            .map_err(|e| TunnelError::Config(e.to_string()))?;
         Ok(())
     }
 }
@@ -58,6 +99,11 @@ impl Default for ClientConfig {
             tunnel_id: None,
             auto_reconnect: true,
             reconnect_delay: Duration::from_secs(5),
+            max_reconnect_attempts: None,
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            heartbeat_timeout: DEFAULT_HEARTBEAT_TIMEOUT,
+            wait_for_local: None,
+            path_rules: PathRules::default(),
         }
     }
 }
@@ -75,6 +121,61 @@ pub struct ServerConfig {
 
     /// Authentication token (clients must provide this)
     pub token: String,
+
+    /// Address to bind the raw TCP ingress (disabled when `None`)
+    pub tcp_bind_addr: Option<SocketAddr>,
+
+    /// Extra TCP ingress ports routed to tunnels advertising a capability
+    pub tcp_port_capabilities: HashMap<u16, String>,
+
+    /// Per-server resource limits (defaults to [`ServerResourceLimits::default`])
+    pub resource_limits: Option<ServerResourceLimits>,
+
+    /// Maximum concurrent sessions, overriding the cap in `resource_limits`
+    pub max_sessions: Option<usize>,
+
+    /// Number of shards for the session store (unsharded when `None`)
+    pub session_shards: Option<usize>,
+
+    /// Close tunnel connections that send nothing for this long
+    pub idle_timeout: Duration,
+
+    /// Let several clients share a tunnel ID, balanced with this policy
+    /// (one client per tunnel ID when `None`)
+    pub pool_policy: Option<PoolPolicy>,
+
+    /// Reconnect delay suggested to clients when the server shuts down
+    /// (clients use their own backoff when `None`)
+    pub shutdown_reconnect_delay: Option<Duration>,
+
+    /// Base URL tunnels are published under, reported to clients as their
+    /// public URL (not reported when `None`)
+    pub public_base: Option<String>,
+
+    /// Address to serve the dashboard API on (not served when `None`)
+    #[cfg(feature = "admin-api")]
+    pub admin_bind_addr: Option<SocketAddr>,
+
+    /// Bearer token the dashboard API requires on every request
+    #[cfg(feature = "admin-api")]
+    pub admin_token: String,
+
+    /// Let listeners bound to an IPv6 address such as `[::]` accept IPv4
+    /// clients too (defaults to true except on OpenBSD, which lacks
+    /// dual-stack sockets)
+    pub dual_stack: bool,
+
+    /// Maximum queue of connections waiting to be accepted on each listener
+    /// (default: 1024; the OS may cap it)
+    pub backlog: u32,
+
+    /// Set `SO_REUSEPORT` on the listeners so other processes can bind the
+    /// same addresses (default: false; Unix only)
+    pub reuse_port: bool,
+
+    /// Listeners bound on the control and HTTP addresses, each with its own
+    /// accept loop (default: 1). More than one enables `reuse_port`.
+    pub accept_tasks: usize,
 }
 
 impl ServerConfig {
@@ -83,8 +184,75 @@ impl ServerConfig {
         if self.token.is_empty() {
             return Err(TunnelError::Config("token is required".into()));
         }
+        if !self.tcp_port_capabilities.is_empty() && self.tcp_bind_addr.is_none() {
+            return Err(TunnelError::Config(
+                "tcp_bind is required when mapping TCP ports to capabilities".into(),
+            ));
+        }
+        if self.max_sessions == Some(0) {
+            return Err(TunnelError::Config(
+                "max_sessions must be greater than zero".into(),
+            ));
+        }
+        if self.session_shards == Some(0) {
+            return Err(TunnelError::Config(
+                "sharded_sessions must be greater than zero".into(),
+            ));
+        }
+        if self.idle_timeout.is_zero() {
+            return Err(TunnelError::Config(
+                "idle_timeout must be greater than zero".into(),
+            ));
+        }
+        if self.effective_resource_limits().max_streams_per_session == 0 {
+            return Err(TunnelError::Config(
+                "max_streams_per_session must be greater than zero".into(),
+            ));
+        }
+        if self.accept_tasks == 0 {
+            return Err(TunnelError::Config(
+                "accept_tasks must be greater than zero".into(),
+            ));
+        }
+        if (self.reuse_port || self.accept_tasks > 1) && !REUSE_PORT_SUPPORTED {
+            return Err(TunnelError::Config(
+                "reuse_port and multiple accept_tasks need SO_REUSEPORT, \
+                 which this platform lacks"
+                    .into(),
+            ));
+        }
+        #[cfg(feature = "admin-api")]
+        if self.admin_bind_addr.is_some() && self.admin_token.is_empty() {
+            return Err(TunnelError::Config(
+                "admin_token is required to serve the admin API".into(),
+            ));
+        }
         Ok(())
     }
+
+    /// Socket options for the listeners the server binds.
+    #[must_use]
+    pub fn listener_options(&self) -> ListenerConfig {
+        ListenerConfig::default()
+            .with_dual_stack(self.dual_stack)
+            .with_backlog(self.backlog)
+            .with_reuse_port(self.reuse_port)
+            .with_accept_tasks(self.accept_tasks)
+    }
+
+    /// Resource limits with [`max_sessions`](Self::max_sessions) applied.
+    #[must_use]
+    pub fn effective_resource_limits(&self) -> ServerResourceLimits {
+        let limits = self.resource_limits.clone().unwrap_or_default();
+        match self.max_sessions {
+            Some(max) => ServerResourceLimits::new(
+                max,
+                limits.max_streams_per_session,
+                limits.max_inflight_frames,
+            ),
+            None => limits,
+        }
+    }
 }
 
 impl Default for ServerConfig {
@@ -93,6 +261,23 @@ impl Default for ServerConfig {
             bind_addr: ([0, 0, 0, 0], DEFAULT_TUNNEL_PORT).into(),
             http_bind_addr: ([0, 0, 0, 0], DEFAULT_HTTP_PORT).into(),
             token: String::new(),
+            tcp_bind_addr: None,
+            tcp_port_capabilities: HashMap::new(),
+            resource_limits: None,
+            max_sessions: None,
+            session_shards: None,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            pool_policy: None,
+            shutdown_reconnect_delay: None,
+            public_base: None,
+            #[cfg(feature = "admin-api")]
+            admin_bind_addr: None,
+            #[cfg(feature = "admin-api")]
+            admin_token: String::new(),
+            dual_stack: DUAL_STACK_SUPPORTED,
+            backlog: ListenerConfig::default().backlog,
+            reuse_port: false,
+            accept_tasks: 1,
         }
     }
 }
@@ -106,10 +291,53 @@ pub struct TunnelInfo {
     /// the core library exposes the server-assigned session ID.
     pub session_id: Option<uuid::Uuid>,
 
-    /// The public URL where the tunnel is accessible (if applicable)
+    /// The public URL where the tunnel is accessible, as reported by the
+    /// server (`None` when the server has no public base configured)
     pub public_url: Option<String>,
 }
 
+/// Point-in-time view of a tunnel connected to a [`Server`](crate::Server).
+#[derive(Debug, Clone, Serialize)]
+pub struct TunnelSnapshot {
+    /// Server-assigned session ID
+    pub session_id: uuid::Uuid,
+
+    /// Tunnel ID used for routing
+    pub tunnel_id: String,
+
+    /// Address the client connected from
+    pub client_addr: SocketAddr,
+
+    /// Time since the client connected
+    pub uptime: Duration,
+
+    /// Time since the last heartbeat from the client
+    pub last_heartbeat_age: Duration,
+
+    /// Capabilities advertised by the client
+    pub capabilities: Vec<String>,
+
+    /// Streams currently open on the tunnel
+    pub active_streams: usize,
+}
+
+impl TunnelSnapshot {
+    pub(crate) fn from_session(session: &Session, now: Instant) -> Self {
+        Self {
+            session_id: session.id,
+            tunnel_id: session.tunnel_id.clone(),
+            client_addr: session.client_addr,
+            uptime: now.saturating_duration_since(session.connected_at),
+            last_heartbeat_age: now.saturating_duration_since(session.last_heartbeat),
+            capabilities: session.capabilities.clone(),
+            active_streams: session
+                .multiplexer
+                .as_ref()
+                .map_or(0, ferrotunnel_core::stream::Multiplexer::active_streams),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,6 +350,8 @@ mod tests {
         assert_eq!(config.local_addr, "127.0.0.1:8080");
         assert!(config.auto_reconnect);
         assert_eq!(config.reconnect_delay, Duration::from_secs(5));
+        assert_eq!(config.heartbeat_interval, Duration::from_secs(30));
+        assert_eq!(config.heartbeat_timeout, Duration::from_secs(90));
     }
 
     #[test]
@@ -171,6 +401,38 @@ mod tests {
         assert!(err.to_string().contains("local_addr"));
     }
 
+    #[test]
+    fn test_client_config_validate_heartbeat_timeout() {
+        let config = ClientConfig {
+            server_addr: "localhost:7835".to_string(),
+            token: "secret".to_string(),
+            heartbeat_interval: Duration::from_secs(30),
+            heartbeat_timeout: Duration::from_secs(30),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("heartbeat_timeout"));
+
+        let config = ClientConfig {
+            heartbeat_interval: Duration::ZERO,
+            ..config
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("heartbeat_interval"));
+    }
+
+    #[test]
+    fn test_client_config_validate_path_rules() {
+        let config = ClientConfig {
+            server_addr: "localhost:7835".to_string(),
+            token: "secret".to_string(),
+            path_rules: PathRules::default().with_allow("webhooks/*"),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("must start with '/'"));
+    }
+
     #[test]
     fn test_server_config_default() {
         let config = ServerConfig::default();
@@ -180,6 +442,30 @@ mod tests {
             SocketAddr::from(([0, 0, 0, 0], 8080))
         );
         assert!(config.token.is_empty());
+        assert!(config.tcp_bind_addr.is_none());
+        assert!(config.tcp_port_capabilities.is_empty());
+        assert_eq!(config.listener_options(), ListenerConfig::default());
+    }
+
+    #[test]
+    fn test_server_config_listener_options() {
+        let config = ServerConfig {
+            backlog: 4096,
+            accept_tasks: 4,
+            ..Default::default()
+        };
+        let options = config.listener_options();
+        assert_eq!(options.backlog, 4096);
+        assert_eq!(options.accept_tasks, 4);
+        assert!(options.reuse_port);
+
+        let config = ServerConfig {
+            token: "secret-token".to_string(),
+            accept_tasks: 0,
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("accept_tasks"));
     }
 
     #[test]
@@ -221,4 +507,42 @@ mod tests {
             Some("https://tunnel.example.com".to_string())
         );
     }
+
+    #[test]
+    fn test_server_config_validate_port_capabilities_need_tcp_bind() {
+        let config = ServerConfig {
+            token: "secret".to_string(),
+            tcp_port_capabilities: HashMap::from([(2222, "ssh".to_string())]),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("tcp_bind"));
+    }
+
+    #[test]
+    fn test_server_config_validate_zero_limits() {
+        let config = ServerConfig {
+            token: "secret".to_string(),
+            max_sessions: Some(0),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("max_sessions"));
+
+        let config = ServerConfig {
+            token: "secret".to_string(),
+            session_shards: Some(0),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("sharded_sessions"));
+
+        let config = ServerConfig {
+            token: "secret".to_string(),
+            resource_limits: Some(ServerResourceLimits::new(10, 0, 100)),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("max_streams_per_session"));
+    }
 }
@@ -53,6 +53,11 @@
 //! }
 //! ```
 //!
+//! ## Feature Flags
+//!
+//! - `admin-api` - Serve the dashboard API from an embedded server with
+//!   `ServerBuilder::with_admin_api`
+//!
 //! ## Architecture
 //!
 //! `FerroTunnel` consists of several crates:
@@ -69,6 +74,8 @@
 //! for convenience.
 
 // Modules
+#[cfg(feature = "admin-api")]
+mod admin;
 pub mod client;
 pub mod config;
 pub mod server;
@@ -80,15 +87,15 @@ pub use ferrotunnel_http as http;
 pub use ferrotunnel_protocol as protocol;
 
 // Public API exports
-pub use client::{Client, ClientBuilder};
-pub use config::{ClientConfig, ServerConfig, TunnelInfo};
-pub use server::{Server, ServerBuilder};
+pub use client::{Client, ClientBuilder, ClientEvent};
+pub use config::{ClientConfig, ServerConfig, TunnelInfo, TunnelSnapshot};
+pub use server::{Server, ServerBuilder, TunnelDirectory};
 
 /// Prelude module for convenient imports
 pub mod prelude {
     // Builder API
-    pub use crate::client::{Client, ClientBuilder};
-    pub use crate::config::{ClientConfig, ServerConfig, TunnelInfo};
+    pub use crate::client::{Client, ClientBuilder, ClientEvent};
+    pub use crate::config::{ClientConfig, ServerConfig, TunnelInfo, TunnelSnapshot};
     pub use crate::server::{Server, ServerBuilder};
 
     // Common types
@@ -96,12 +103,14 @@ pub mod prelude {
 
     // Protocol types
     pub use crate::protocol::{
-        CloseReason, Frame, HandshakeStatus, Protocol, RegisterStatus, StreamStatus, TunnelCodec,
+        CloseReason, Frame, HandshakeStatus, Protocol, RegisterStatus, ResetCode, StreamStatus,
+        TunnelCodec,
     };
 }
 
 // Convenience re-exports at crate root
 pub use common::{Result, TunnelError};
 pub use protocol::{
-    CloseReason, Frame, HandshakeStatus, Protocol, RegisterStatus, StreamStatus, TunnelCodec,
+    CloseReason, Frame, HandshakeStatus, Protocol, RegisterStatus, ResetCode, StreamStatus,
+    TunnelCodec,
 };
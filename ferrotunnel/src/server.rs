@@ -17,19 +17,27 @@
 //! # }
 //! ```
 
-use crate::config::ServerConfig;
+use crate::config::{ServerConfig, TunnelSnapshot};
 use ferrotunnel_common::config::TlsConfig;
 use ferrotunnel_common::{Result, TunnelError};
-use ferrotunnel_core::transport::{tls::TlsTransportConfig, TransportConfig};
-use ferrotunnel_core::TunnelServer;
-use ferrotunnel_http::HttpIngress;
+use ferrotunnel_core::resource_limits::ServerResourceLimits;
+use ferrotunnel_core::transport::{tls::TlsTransportConfig, TransportConfig, TransportListener};
+use ferrotunnel_core::tunnel::session::{PoolPolicy, SessionStoreBackend, ShardedSessionStore};
+use ferrotunnel_core::{announce_shutdown, TunnelServer};
+use ferrotunnel_http::{HttpIngress, TcpIngress, TcpIngressConfig};
 use ferrotunnel_plugin::PluginRegistry;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
 use tokio::sync::{watch, RwLock};
 use tokio::task::JoinHandle;
 use tracing::info;
 
+/// Longest time [`Server::shutdown()`] waits for notified clients to
+/// disconnect before stopping
+const SHUTDOWN_NOTICE_GRACE: Duration = Duration::from_secs(1);
+
 /// A tunnel server that can be embedded in your application.
 ///
 /// Use [`Server::builder()`] to create a new server with the builder pattern.
@@ -37,8 +45,27 @@ use tracing::info;
 pub struct Server {
     config: ServerConfig,
     transport_config: TransportConfig,
+    sessions: SessionStoreBackend,
     shutdown_tx: Option<watch::Sender<bool>>,
     task: Option<JoinHandle<Result<()>>>,
+    listeners: Option<Listeners>,
+    control_addr: Option<SocketAddr>,
+    http_addr: Option<SocketAddr>,
+    tcp_addr: Option<SocketAddr>,
+    #[cfg(feature = "admin-api")]
+    admin_addr: Option<SocketAddr>,
+}
+
+/// Sockets bound by [`Server::bind_now()`], held until [`Server::start()`]
+#[derive(Debug)]
+struct Listeners {
+    /// `None` for the in-memory transport, which binds no socket
+    control: Option<Vec<TcpListener>>,
+    /// One listener per accept task
+    http: Vec<TcpListener>,
+    tcp: Option<TcpListener>,
+    #[cfg(feature = "admin-api")]
+    admin: Option<TcpListener>,
 }
 
 /// Builder for constructing a [`Server`] with ergonomic configuration.
@@ -66,32 +93,143 @@ impl Server {
         ServerBuilder::default()
     }
 
+    /// Bind the configured addresses without starting to accept connections.
+    ///
+    /// Afterwards [`control_addr()`](Self::control_addr),
+    /// [`http_addr()`](Self::http_addr), [`tcp_addr()`](Self::tcp_addr) and,
+    /// with the `admin-api` feature, `admin_addr()` report the bound
+    /// addresses, including the ports assigned when binding port 0. The
+    /// sockets are held until [`start()`](Self::start) serves them.
+    /// Calling it again once bound does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an address cannot be bound.
+    // Binding no longer awaits, but callers already await this
+    #[allow(clippy::unused_async)]
+    pub async fn bind_now(&mut self) -> Result<()> {
+        if self.listeners.is_some() {
+            return Ok(());
+        }
+        let options = self.config.listener_options();
+        let control = match self.transport_config {
+            TransportConfig::Memory(_) => None,
+            _ => Some(options.bind_all(self.config.bind_addr)?),
+        };
+        let http = options.bind_all(self.config.http_bind_addr)?;
+        let tcp = self
+            .config
+            .tcp_bind_addr
+            .map(|addr| options.bind(addr))
+            .transpose()?;
+        #[cfg(feature = "admin-api")]
+        let admin = self
+            .config
+            .admin_bind_addr
+            .map(|addr| options.bind(addr))
+            .transpose()?;
+
+        self.control_addr = control
+            .as_ref()
+            .and_then(|listeners| listeners.first())
+            .map(TcpListener::local_addr)
+            .transpose()?;
+        self.http_addr = http.first().map(TcpListener::local_addr).transpose()?;
+        self.tcp_addr = tcp.as_ref().map(TcpListener::local_addr).transpose()?;
+        #[cfg(feature = "admin-api")]
+        {
+            self.admin_addr = admin.as_ref().map(TcpListener::local_addr).transpose()?;
+        }
+        self.listeners = Some(Listeners {
+            control,
+            http,
+            tcp,
+            #[cfg(feature = "admin-api")]
+            admin,
+        });
+        Ok(())
+    }
+
+    /// Address the tunnel control listener is bound to, once bound by
+    /// [`bind_now()`](Self::bind_now) or [`start()`](Self::start).
+    ///
+    /// `None` before binding and for the in-memory transport.
+    pub fn control_addr(&self) -> Option<SocketAddr> {
+        self.control_addr
+    }
+
+    /// Address the HTTP ingress is bound to, once bound.
+    pub fn http_addr(&self) -> Option<SocketAddr> {
+        self.http_addr
+    }
+
+    /// Address the TCP ingress is bound to, once bound; `None` without
+    /// [`tcp_bind`](ServerBuilder::tcp_bind).
+    pub fn tcp_addr(&self) -> Option<SocketAddr> {
+        self.tcp_addr
+    }
+
+    /// Address the dashboard API is bound to, once bound; `None` without
+    /// [`with_admin_api`](ServerBuilder::with_admin_api).
+    #[cfg(feature = "admin-api")]
+    pub fn admin_addr(&self) -> Option<SocketAddr> {
+        self.admin_addr
+    }
+
     /// Start the tunnel server.
     ///
-    /// This will bind to the configured addresses and start accepting connections.
-    /// The server runs until [`shutdown()`](Self::shutdown) is called.
+    /// This will bind to the configured addresses, unless
+    /// [`bind_now()`](Self::bind_now) already did, and start accepting
+    /// connections. The server runs until [`shutdown()`](Self::shutdown) is
+    /// called.
     ///
     /// # Errors
     ///
-    /// Returns an error if the server is already running.
+    /// Returns an error if the server is already running or an address cannot
+    /// be bound.
+    #[allow(clippy::too_many_lines)]
     pub async fn start(&mut self) -> Result<()> {
         if self.task.is_some() {
             return Err(TunnelError::InvalidState("server already started".into()));
         }
+        self.bind_now().await?;
+        let Some(listeners) = self.listeners.take() else {
+            return Err(TunnelError::InvalidState("server already started".into()));
+        };
 
         let config = self.config.clone();
         let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
         self.shutdown_tx = Some(shutdown_tx);
 
         info!("Starting `FerroTunnel` Server");
-        info!("  Tunnel bind: {}", config.bind_addr);
-        info!("  HTTP bind: {}", config.http_bind_addr);
+        info!(
+            "  Tunnel bind: {}",
+            self.control_addr.unwrap_or(config.bind_addr)
+        );
+        info!(
+            "  HTTP bind: {}",
+            self.http_addr.unwrap_or(config.http_bind_addr)
+        );
+        if let Some(tcp_addr) = self.tcp_addr {
+            info!("  TCP bind: {}", tcp_addr);
+        }
+        #[cfg(feature = "admin-api")]
+        if let Some(admin_addr) = self.admin_addr {
+            info!("  Admin API bind: {}", admin_addr);
+        }
 
-        let tunnel_server = TunnelServer::new(config.bind_addr, config.token)
-            .with_transport(self.transport_config.clone());
+        let resource_limits = config.effective_resource_limits();
+        let mut tunnel_server = TunnelServer::new(config.bind_addr, config.token.clone())
+            .with_transport(self.transport_config.clone())
+            .with_session_store(self.sessions.clone())
+            .with_resource_limits(resource_limits)
+            .with_idle_timeout(config.idle_timeout);
+        if let Some(base) = config.public_base.clone() {
+            tunnel_server = tunnel_server.with_public_base(base);
+        }
 
         // Initialize plugins
-        let mut registry = PluginRegistry::new();
+        let registry = PluginRegistry::new();
         // Add default plugins
         registry.register(Arc::new(RwLock::new(
             ferrotunnel_plugin::builtin::LoggerPlugin::new(),
@@ -105,11 +243,68 @@ impl Server {
 
         let registry = Arc::new(registry);
         let sessions = tunnel_server.sessions();
+        let tcp_ingress = config.tcp_bind_addr.map(|tcp_addr| {
+            let tcp_config = TcpIngressConfig {
+                port_capabilities: config.tcp_port_capabilities.clone(),
+                listener: config.listener_options(),
+                ..Default::default()
+            };
+            TcpIngress::with_config(tcp_addr, sessions.clone(), tcp_config)
+        });
         let ingress = HttpIngress::new(config.http_bind_addr, sessions, registry);
+        #[cfg(feature = "admin-api")]
+        let admin_api = listeners.admin.as_ref().map(|_| {
+            crate::admin::AdminApi::new(
+                self.sessions.clone(),
+                config.public_base.clone(),
+                &config.admin_token,
+            )
+        });
+        #[cfg(feature = "admin-api")]
+        let ingress = match &admin_api {
+            Some(api) => ingress.with_inspector(Arc::new(api.clone())),
+            None => ingress,
+        };
 
-        // Spawn both services
-        let tunnel_handle = tokio::spawn(async move { tunnel_server.run().await });
-        let ingress_handle = tokio::spawn(async move { ingress.start().await });
+        // Spawn services
+        let Listeners {
+            control,
+            http,
+            tcp,
+            #[cfg(feature = "admin-api")]
+            admin,
+        } = listeners;
+        let tunnel_handle = tokio::spawn(async move {
+            match control {
+                Some(listeners) => {
+                    let listeners = listeners.into_iter().map(TransportListener::Tcp).collect();
+                    tunnel_server.serve_all(listeners).await
+                }
+                None => tunnel_server.run().await,
+            }
+        });
+        let ingress_handle = tokio::spawn(async move { ingress.serve_all(http).await });
+        let tcp_handle = tcp_ingress
+            .zip(tcp)
+            .map(|(ingress, listener)| tokio::spawn(async move { ingress.serve(listener).await }));
+        let tcp_result = async move {
+            match tcp_handle {
+                Some(handle) => handle.await,
+                None => std::future::pending().await,
+            }
+        };
+        #[cfg(feature = "admin-api")]
+        let admin_handle = admin
+            .zip(admin_api)
+            .map(|(listener, api)| tokio::spawn(api.serve(listener)));
+        #[cfg(not(feature = "admin-api"))]
+        let admin_handle: Option<JoinHandle<Result<()>>> = None;
+        let admin_result = async move {
+            match admin_handle {
+                Some(handle) => handle.await,
+                None => std::future::pending().await,
+            }
+        };
 
         // Wait for shutdown or either service to exit
         tokio::select! {
@@ -125,6 +320,18 @@ impl Server {
                     Err(e) => return Err(TunnelError::Connection(format!("Ingress task panicked: {e}"))),
                 }
             }
+            result = tcp_result => {
+                match result {
+                    Ok(inner) => inner?,
+                    Err(e) => return Err(TunnelError::Connection(format!("TCP ingress task panicked: {e}"))),
+                }
+            }
+            result = admin_result => {
+                match result {
+                    Ok(inner) => inner?,
+                    Err(e) => return Err(TunnelError::Connection(format!("Admin API task panicked: {e}"))),
+                }
+            }
             _ = shutdown_rx.changed() => {
                 info!("Server shutdown requested");
             }
@@ -136,8 +343,26 @@ impl Server {
     /// Shutdown the tunnel server and wait for cleanup.
     ///
     /// This will gracefully shut down the server and close all connections.
+    /// Connected clients are told first, with the configured
+    /// [`shutdown_reconnect_delay`](ServerBuilder::shutdown_reconnect_delay),
+    /// and given up to a second to disconnect before the server stops.
     pub async fn shutdown(&mut self) -> Result<()> {
         if let Some(tx) = self.shutdown_tx.take() {
+            let notified = announce_shutdown(
+                &self.sessions,
+                "server shutting down",
+                self.config.shutdown_reconnect_delay,
+            )
+            .await;
+            if notified > 0 {
+                info!("Notified {} clients of shutdown", notified);
+                // Give the connections a moment to flush the notices; clients
+                // disconnect as soon as they read theirs
+                let deadline = Instant::now() + SHUTDOWN_NOTICE_GRACE;
+                while self.sessions.count() > 0 && Instant::now() < deadline {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            }
             let _ = tx.send(true);
         }
         if let Some(task) = self.task.take() {
@@ -168,6 +393,53 @@ impl Server {
     pub fn config(&self) -> &ServerConfig {
         &self.config
     }
+
+    /// Snapshot of every tunnel currently connected, ordered by tunnel ID.
+    pub fn tunnels(&self) -> Vec<TunnelSnapshot> {
+        self.directory().tunnels()
+    }
+
+    /// Snapshot of the tunnel registered as `tunnel_id`, if connected.
+    pub fn tunnel(&self, tunnel_id: &str) -> Option<TunnelSnapshot> {
+        self.directory().tunnel(tunnel_id)
+    }
+
+    /// Cloneable handle for listing tunnels from other tasks.
+    ///
+    /// [`start()`](Self::start) borrows the server until shutdown, so admin
+    /// endpoints should take a directory before starting it.
+    pub fn directory(&self) -> TunnelDirectory {
+        TunnelDirectory {
+            sessions: self.sessions.clone(),
+        }
+    }
+}
+
+/// Read-only view of the tunnels connected to a [`Server`].
+///
+/// Obtained from [`Server::directory()`]; stays valid while the server runs.
+#[derive(Debug, Clone)]
+pub struct TunnelDirectory {
+    sessions: SessionStoreBackend,
+}
+
+impl TunnelDirectory {
+    /// Snapshot of every tunnel currently connected, ordered by tunnel ID.
+    pub fn tunnels(&self) -> Vec<TunnelSnapshot> {
+        let now = Instant::now();
+        let mut tunnels = Vec::with_capacity(self.sessions.count());
+        self.sessions
+            .for_each(|session| tunnels.push(TunnelSnapshot::from_session(session, now)));
+        tunnels.sort_by(|a, b| a.tunnel_id.cmp(&b.tunnel_id));
+        tunnels
+    }
+
+    /// Snapshot of the tunnel registered as `tunnel_id`, if connected.
+    pub fn tunnel(&self, tunnel_id: &str) -> Option<TunnelSnapshot> {
+        self.sessions
+            .get_by_tunnel_id(tunnel_id)
+            .map(|session| TunnelSnapshot::from_session(&session, Instant::now()))
+    }
 }
 
 impl Drop for Server {
@@ -198,6 +470,71 @@ impl ServerBuilder {
         self
     }
 
+    /// Let listeners bound to an IPv6 address accept IPv4 clients too.
+    ///
+    /// With the default of `true`, binding `[::]` serves both address
+    /// families on every platform that supports it; IPv4 clients appear as
+    /// IPv4-mapped addresses. Set `false` to accept IPv6 only, e.g. to bind
+    /// `0.0.0.0` and `[::]` separately.
+    #[must_use]
+    pub fn dual_stack(mut self, dual_stack: bool) -> Self {
+        self.config.dual_stack = dual_stack;
+        self
+    }
+
+    /// Set the accept backlog of each listener.
+    ///
+    /// Default: 1024 (the OS may cap it, e.g. at `net.core.somaxconn`)
+    #[must_use]
+    pub fn backlog(mut self, backlog: u32) -> Self {
+        self.config.backlog = backlog;
+        self
+    }
+
+    /// Set `SO_REUSEPORT` on the listeners, so other processes can bind the
+    /// same addresses and share incoming connections.
+    ///
+    /// Default: `false`. [`build()`](Self::build) fails where the platform
+    /// lacks `SO_REUSEPORT`.
+    #[must_use]
+    pub fn reuse_port(mut self, reuse_port: bool) -> Self {
+        self.config.reuse_port = reuse_port;
+        self
+    }
+
+    /// Bind this many listeners on the control and HTTP addresses, each
+    /// served by its own accept loop, with `SO_REUSEPORT` spreading
+    /// connections across them.
+    ///
+    /// Default: 1. [`build()`](Self::build) rejects 0.
+    #[must_use]
+    pub fn accept_tasks(mut self, accept_tasks: usize) -> Self {
+        self.config.accept_tasks = accept_tasks;
+        self
+    }
+
+    /// Enable the raw TCP ingress on the given address.
+    ///
+    /// Connections are routed to tunnels advertising the `"tcp"` capability
+    /// unless the port is mapped with [`tcp_port_capability`](Self::tcp_port_capability).
+    #[must_use]
+    pub fn tcp_bind(mut self, addr: SocketAddr) -> Self {
+        self.config.tcp_bind_addr = Some(addr);
+        self
+    }
+
+    /// Route an additional TCP ingress port to tunnels advertising `capability`.
+    ///
+    /// The port is bound on the same IP as [`tcp_bind`](Self::tcp_bind), e.g.
+    /// port 2222 to clients advertising `"ssh"`.
+    #[must_use]
+    pub fn tcp_port_capability(mut self, port: u16, capability: impl Into<String>) -> Self {
+        self.config
+            .tcp_port_capabilities
+            .insert(port, capability.into());
+        self
+    }
+
     /// Set the authentication token.
     ///
     /// Clients must provide this token to connect.
@@ -207,6 +544,90 @@ impl ServerBuilder {
         self
     }
 
+    /// Cap the number of concurrent tunnel sessions.
+    ///
+    /// Clients connecting beyond the cap are rejected during the handshake.
+    /// Overrides the session cap of [`resource_limits`](Self::resource_limits).
+    /// Must be non-zero.
+    #[must_use]
+    pub fn max_sessions(mut self, max: usize) -> Self {
+        self.config.max_sessions = Some(max);
+        self
+    }
+
+    /// Use a session store split into `n_shards` shards.
+    ///
+    /// Lowers lock contention with many concurrent tunnels. Must be non-zero.
+    #[must_use]
+    pub fn sharded_sessions(mut self, n_shards: usize) -> Self {
+        self.config.session_shards = Some(n_shards);
+        self
+    }
+
+    /// Set the session, stream and in-flight frame limits.
+    ///
+    /// Default: [`ServerResourceLimits::default()`]
+    #[must_use]
+    pub fn resource_limits(mut self, limits: ServerResourceLimits) -> Self {
+        self.config.resource_limits = Some(limits);
+        self
+    }
+
+    /// Close tunnel connections that send no frames for `timeout`.
+    ///
+    /// Default: 90 seconds
+    #[must_use]
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.config.idle_timeout = timeout;
+        self
+    }
+
+    /// Let several clients register the same tunnel ID, spreading requests
+    /// across them with `policy`.
+    ///
+    /// Default: off (a second client claiming a tunnel ID is rejected)
+    #[must_use]
+    pub fn pool_policy(mut self, policy: PoolPolicy) -> Self {
+        self.config.pool_policy = Some(policy);
+        self
+    }
+
+    /// Ask clients to wait `delay` before reconnecting when the server shuts
+    /// down, e.g. the expected length of a planned restart.
+    ///
+    /// Default: none (clients use their own reconnect delay)
+    #[must_use]
+    pub fn shutdown_reconnect_delay(mut self, delay: Duration) -> Self {
+        self.config.shutdown_reconnect_delay = Some(delay);
+        self
+    }
+
+    /// Publish tunnels as subdomains of `base`, e.g.
+    /// `https://tunnel.example.com`, and tell each client its public URL.
+    ///
+    /// Default: none (clients are not told a public URL)
+    #[must_use]
+    pub fn public_base(mut self, base: impl Into<String>) -> Self {
+        self.config.public_base = Some(base.into());
+        self
+    }
+
+    /// Serve the dashboard API on `addr`, listing the tunnels connected to
+    /// this server under `/api/v1/tunnels` and the requests the HTTP ingress
+    /// forwards under `/api/v1/requests`.
+    ///
+    /// Every request must carry `Authorization: Bearer <token>`; others are
+    /// answered with 401. The API sends no CORS headers.
+    ///
+    /// Default: not served
+    #[cfg(feature = "admin-api")]
+    #[must_use]
+    pub fn with_admin_api(mut self, addr: SocketAddr, token: impl Into<String>) -> Self {
+        self.config.admin_bind_addr = Some(addr);
+        self.config.admin_token = token.into();
+        self
+    }
+
     /// Configure TLS for the server.
     ///
     /// When enabled, the server will use TLS for all connections.
@@ -222,15 +643,30 @@ impl ServerBuilder {
     ///
     /// # Errors
     ///
-    /// Returns an error if required configuration is missing:
+    /// Returns an error if required configuration is missing or invalid:
     /// - `token` must be set
+    /// - `max_sessions`, `sharded_sessions` and `idle_timeout` must be non-zero
     pub fn build(self) -> Result<Server> {
         self.config.validate()?;
+        let sessions = match self.config.session_shards {
+            Some(n_shards) => {
+                SessionStoreBackend::Sharded(ShardedSessionStore::with_shards(n_shards))
+            }
+            None => SessionStoreBackend::default(),
+        }
+        .with_pool_policy(self.config.pool_policy);
         Ok(Server {
             config: self.config,
             transport_config: self.transport_config.unwrap_or_default(),
+            sessions,
             shutdown_tx: None,
             task: None,
+            listeners: None,
+            control_addr: None,
+            http_addr: None,
+            tcp_addr: None,
+            #[cfg(feature = "admin-api")]
+            admin_addr: None,
         })
     }
 }
@@ -266,6 +702,134 @@ mod tests {
         assert_eq!(server.config().token, "my-token");
     }
 
+    #[test]
+    fn test_server_builder_pool_policy() {
+        let server = Server::builder().token("secret").build().unwrap();
+        assert_eq!(server.sessions.pool_policy(), None);
+
+        let server = Server::builder()
+            .token("secret")
+            .sharded_sessions(4)
+            .pool_policy(PoolPolicy::LeastStreams)
+            .build()
+            .unwrap();
+        assert_eq!(server.config().pool_policy, Some(PoolPolicy::LeastStreams));
+        assert_eq!(
+            server.sessions.pool_policy(),
+            Some(PoolPolicy::LeastStreams)
+        );
+    }
+
+    #[test]
+    fn test_server_builder_tcp_ports() {
+        let server = Server::builder()
+            .token("secret")
+            .tcp_bind("0.0.0.0:5000".parse().unwrap())
+            .tcp_port_capability(2222, "ssh")
+            .build()
+            .expect("should build");
+
+        assert_eq!(
+            server.config().tcp_bind_addr,
+            Some("0.0.0.0:5000".parse().unwrap())
+        );
+        assert_eq!(
+            server
+                .config()
+                .tcp_port_capabilities
+                .get(&2222)
+                .map(String::as_str),
+            Some("ssh")
+        );
+    }
+
+    #[test]
+    fn test_server_builder_session_limits() {
+        let server = Server::builder()
+            .token("secret")
+            .resource_limits(ServerResourceLimits::new(10, 5, 50))
+            .max_sessions(3)
+            .sharded_sessions(8)
+            .idle_timeout(Duration::from_secs(30))
+            .build()
+            .expect("should build");
+
+        let limits = server.config().effective_resource_limits();
+        assert_eq!(limits.available_sessions(), 3);
+        assert_eq!(limits.max_streams_per_session, 5);
+        assert_eq!(limits.max_inflight_frames, 50);
+        assert_eq!(server.config().session_shards, Some(8));
+        assert_eq!(server.config().idle_timeout, Duration::from_secs(30));
+    }
+
+    #[cfg(feature = "admin-api")]
+    #[tokio::test]
+    async fn test_server_binds_admin_api() {
+        let mut server = Server::builder()
+            .token("secret")
+            .bind("127.0.0.1:0".parse().unwrap())
+            .http_bind("127.0.0.1:0".parse().unwrap())
+            .with_admin_api("127.0.0.1:0".parse().unwrap(), "admin-secret")
+            .build()
+            .expect("should build");
+        assert_eq!(server.admin_addr(), None);
+
+        server.bind_now().await.expect("should bind");
+        let admin_addr = server.admin_addr().expect("admin address");
+        assert_ne!(admin_addr.port(), 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_server_binds_accept_tasks() {
+        let mut server = Server::builder()
+            .token("secret")
+            .bind("127.0.0.1:0".parse().unwrap())
+            .http_bind("127.0.0.1:0".parse().unwrap())
+            .backlog(64)
+            .accept_tasks(3)
+            .build()
+            .expect("should build");
+        server.bind_now().await.expect("should bind");
+
+        let listeners = server.listeners.as_ref().expect("bound listeners");
+        assert_eq!(listeners.http.len(), 3);
+        assert_eq!(listeners.control.as_ref().map(Vec::len), Some(3));
+        assert_ne!(server.http_addr().expect("http address").port(), 0);
+    }
+
+    #[test]
+    fn test_server_builder_zero_accept_tasks() {
+        let result = Server::builder().token("secret").accept_tasks(0).build();
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "admin-api")]
+    #[test]
+    fn test_admin_api_requires_token() {
+        let result = Server::builder()
+            .token("secret")
+            .with_admin_api("127.0.0.1:0".parse().unwrap(), "")
+            .build();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_server_no_tunnels_before_start() {
+        let server = Server::builder()
+            .token("secret")
+            .build()
+            .expect("should build");
+        assert!(server.tunnels().is_empty());
+        assert!(server.tunnel("missing").is_none());
+    }
+
+    #[test]
+    fn test_server_builder_zero_max_sessions() {
+        let result = Server::builder().token("secret").max_sessions(0).build();
+        assert!(result.unwrap_err().to_string().contains("max_sessions"));
+    }
+
     #[test]
     fn test_server_builder_missing_token() {
         let result = Server::builder()
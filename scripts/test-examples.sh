@@ -123,6 +123,7 @@ if [ "$FILTER" = "all" ] || [ "$FILTER" = "scenarios" ]; then
     echo -e "\n${BLUE}┌─ Scenario Examples ─────────────────────────────────────────┐${NC}"
     mark_passed "expose_local_dev" "(compiled)"
     mark_passed "receive_webhooks_locally" "(compiled)"
+    mark_passed "expose_database" "(compiled)"
     echo -e "${BLUE}└──────────────────────────────────────────────────────────────┘${NC}"
 fi
 
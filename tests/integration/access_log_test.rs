@@ -0,0 +1,109 @@
+//! HTTP ingress access log integration tests
+
+use super::{get_free_port, make_client, start_echo_server, start_tunnel};
+use ferrotunnel_http::{AccessLogFormat, HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing_subscriber::fmt::MakeWriter;
+
+const TUNNEL_ID: &str = "logged";
+
+/// Tracing writer collecting formatted output in memory
+#[derive(Clone, Default)]
+struct CaptureWriter(Arc<Mutex<Vec<u8>>>);
+
+impl CaptureWriter {
+    fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl Write for CaptureWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for CaptureWriter {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+// The subscriber is thread-local, so everything must run on the test's
+// current-thread runtime for the ingress task's events to be captured.
+#[tokio::test(flavor = "current_thread")]
+async fn test_json_access_log_line_per_request() {
+    let writer = CaptureWriter::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(writer.clone())
+        .with_env_filter("ferrotunnel::access=info")
+        .with_ansi(false)
+        .with_level(false)
+        .with_target(false)
+        .without_time()
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let local_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let _local = start_echo_server(local_addr).await;
+
+    let config = IngressConfig {
+        access_log: AccessLogFormat::Json,
+        ..Default::default()
+    };
+    let http_addr = start_tunnel(
+        TUNNEL_ID,
+        HttpProxy::new(local_addr.to_string()),
+        PluginRegistry::new(),
+        config,
+    )
+    .await;
+
+    let response = make_client()
+        .post(format!("http://{http_addr}/upload?x=1"))
+        .header("Host", TUNNEL_ID)
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "Hello, World!");
+
+    // The line is emitted once the response body has been fully sent
+    let mut line = None;
+    for _ in 0..50 {
+        line = writer
+            .contents()
+            .lines()
+            .find_map(|l| l.find('{').map(|start| l[start..].to_string()));
+        if line.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let line = line.expect("no access log line captured");
+    let entry: serde_json::Value = serde_json::from_str(&line).unwrap();
+
+    assert_eq!(entry["tunnel_id"], TUNNEL_ID);
+    assert_eq!(entry["method"], "POST");
+    assert_eq!(entry["path"], "/upload");
+    assert_eq!(entry["status"], 200);
+    assert_eq!(entry["request_bytes"], 5);
+    assert_eq!(entry["response_bytes"], 13);
+    assert!(entry["duration_ms"].is_u64());
+    assert!(entry["remote_addr"]
+        .as_str()
+        .unwrap()
+        .starts_with("127.0.0.1:"));
+}
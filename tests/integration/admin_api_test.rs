@@ -0,0 +1,167 @@
+//! Dashboard API served by the embedded server
+
+use super::{make_client, start_echo_server, TestConfig};
+use ferrotunnel::{Client, Server};
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+const ADMIN_TOKEN: &str = "admin-secret";
+
+/// JSON list served by the admin API at `path`
+async fn get_list(admin_addr: SocketAddr, path: &str) -> Vec<Value> {
+    let response = make_client()
+        .get(format!("http://{admin_addr}{path}"))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.unwrap()
+}
+
+/// Poll the list at `path` until `done` accepts it, failing after a deadline
+async fn wait_for_list(
+    admin_addr: SocketAddr,
+    path: &str,
+    done: impl Fn(&[Value]) -> bool,
+) -> Vec<Value> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let list = get_list(admin_addr, path).await;
+        if done(&list) {
+            return list;
+        }
+        assert!(Instant::now() < deadline, "{path} did not settle: {list:?}");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+/// Tunnels listed by the admin API
+async fn list_tunnels(admin_addr: SocketAddr) -> Vec<Value> {
+    get_list(admin_addr, "/api/v1/tunnels").await
+}
+
+#[tokio::test]
+async fn test_admin_api_lists_live_tunnel() {
+    let config = TestConfig::default();
+    let _echo_handle = start_echo_server(config.local_service_addr).await;
+
+    let mut server = Server::builder()
+        .bind("127.0.0.1:0".parse().unwrap())
+        .http_bind("127.0.0.1:0".parse().unwrap())
+        .with_admin_api("127.0.0.1:0".parse().unwrap(), ADMIN_TOKEN)
+        .public_base("https://tunnel.example.com")
+        .token(config.token)
+        .build()
+        .expect("Failed to build server");
+    server.bind_now().await.expect("Failed to bind");
+    let control_addr = server.control_addr().expect("control address");
+    let http_addr = server.http_addr().expect("http address");
+    let admin_addr = server.admin_addr().expect("admin address");
+    let server_handle = tokio::spawn(async move { server.start().await });
+
+    assert!(list_tunnels(admin_addr).await.is_empty());
+
+    let mut client = Client::builder()
+        .server_addr(control_addr.to_string())
+        .token(config.token)
+        .local_addr(config.local_service_addr.to_string())
+        .tunnel_id("dashboard")
+        .build()
+        .expect("Failed to build client");
+    let info = client.start().await.expect("Client failed to connect");
+
+    let tunnels = wait_for_list(admin_addr, "/api/v1/tunnels", |list| !list.is_empty()).await;
+    assert_eq!(tunnels.len(), 1, "{tunnels:?}");
+    let tunnel = &tunnels[0];
+    assert_eq!(tunnel["subdomain"], "dashboard");
+    assert_eq!(tunnel["status"], "connected");
+    assert_eq!(tunnel["public_url"], "https://dashboard.tunnel.example.com");
+    assert_eq!(
+        tunnel["id"],
+        info.session_id.expect("session ID").to_string()
+    );
+
+    // The tunnel can be fetched by ID as well
+    let id = tunnel["id"].as_str().unwrap();
+    let response = make_client()
+        .get(format!("http://{admin_addr}/api/v1/tunnels/{id}"))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Requests through the ingress show up in the request log
+    let response = make_client()
+        .get(format!("http://{http_addr}/hello?x=1"))
+        .header("Host", "dashboard")
+        .header("Authorization", "Bearer client-secret")
+        .header("Cookie", "session=client-session")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let requests = wait_for_list(admin_addr, "/api/v1/requests", |list| !list.is_empty()).await;
+    assert_eq!(requests.len(), 1, "{requests:?}");
+    assert_eq!(requests[0]["method"], "GET");
+    assert_eq!(requests[0]["path"], "/hello?x=1");
+    assert_eq!(requests[0]["status"], 200);
+    assert_eq!(requests[0]["tunnel_id"], tunnel["id"]);
+
+    // Credentials sent through the tunnel are masked in the request details
+    let request_id = requests[0]["id"].as_str().unwrap();
+    let details: Value = make_client()
+        .get(format!("http://{admin_addr}/api/v1/requests/{request_id}"))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let headers = &details["request_headers"];
+    assert_eq!(headers["authorization"], "***", "{details}");
+    assert_eq!(headers["cookie"], "***", "{details}");
+    assert_eq!(headers["host"], "dashboard", "{details}");
+
+    // Disconnected tunnels drop out of the list
+    let _ = client.shutdown().await;
+    wait_for_list(admin_addr, "/api/v1/tunnels", <[Value]>::is_empty).await;
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_admin_api_requires_token() {
+    let mut server = Server::builder()
+        .bind("127.0.0.1:0".parse().unwrap())
+        .http_bind("127.0.0.1:0".parse().unwrap())
+        .with_admin_api("127.0.0.1:0".parse().unwrap(), ADMIN_TOKEN)
+        .token("secret")
+        .build()
+        .expect("Failed to build server");
+    server.bind_now().await.expect("Failed to bind");
+    let admin_addr = server.admin_addr().expect("admin address");
+    let server_handle = tokio::spawn(async move { server.start().await });
+
+    let url = format!("http://{admin_addr}/api/v1/tunnels");
+    let response = make_client().get(&url).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let response = make_client()
+        .get(&url)
+        .header("Origin", "https://elsewhere.example")
+        .bearer_auth("wrong")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(response
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+    assert!(list_tunnels(admin_addr).await.is_empty());
+
+    server_handle.abort();
+}
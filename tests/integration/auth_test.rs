@@ -0,0 +1,182 @@
+//! Multi-token authentication integration tests
+
+use super::{start_tunnel_server, TUNNEL_TOKEN};
+use async_trait::async_trait;
+use ferrotunnel_core::auth::{AuthGrant, AuthResult, Authenticator};
+use ferrotunnel_core::TunnelClient;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Connect with `token` and report whether the handshake succeeded
+async fn handshake_succeeds(server_addr: String, token: &str) -> bool {
+    client_handshake_succeeds(TunnelClient::new(server_addr, token.to_string())).await
+}
+
+/// Run `client` until its handshake completes and report whether it succeeded
+async fn client_handshake_succeeds(client: TunnelClient) -> bool {
+    match connect_client(client).await {
+        Some(handle) => {
+            handle.abort();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Run `client` in the background; `None` if its handshake did not succeed
+async fn connect_client(
+    mut client: TunnelClient,
+) -> Option<tokio::task::JoinHandle<ferrotunnel_common::Result<()>>> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    let handle = tokio::spawn(async move {
+        client
+            .connect_and_run_with_callback(
+                |_stream| async {},
+                move |_session_id| {
+                    let _ = tx.send(());
+                },
+            )
+            .await
+    });
+
+    let connected = tokio::time::timeout(Duration::from_secs(5), rx)
+        .await
+        .is_ok_and(|r| r.is_ok());
+    if connected {
+        Some(handle)
+    } else {
+        handle.abort();
+        None
+    }
+}
+
+#[tokio::test]
+async fn test_server_accepts_any_configured_token() {
+    let (server_addr, _) = start_tunnel_server(|server| {
+        server.with_tokens(vec!["old-token".into(), "new-token".into()])
+    })
+    .await;
+
+    let addr = server_addr.to_string();
+    assert!(handshake_succeeds(addr.clone(), "old-token").await);
+    assert!(handshake_succeeds(addr.clone(), "new-token").await);
+    // The constructor token is replaced
+    assert!(!handshake_succeeds(addr.clone(), TUNNEL_TOKEN).await);
+    assert!(!handshake_succeeds(addr, "wrong-token").await);
+}
+
+#[tokio::test]
+async fn test_server_token_rotation_without_restart() {
+    let mut tokens = None;
+    let (server_addr, _) = start_tunnel_server(|server| {
+        let server = server.with_tokens(vec!["old-token".into()]);
+        tokens = Some(server.tokens());
+        server
+    })
+    .await;
+    let tokens = tokens.unwrap();
+
+    let addr = server_addr.to_string();
+    assert!(handshake_succeeds(addr.clone(), "old-token").await);
+    assert!(!handshake_succeeds(addr.clone(), "new-token").await);
+
+    tokens.replace(vec!["old-token".into(), "new-token".into()]);
+    assert!(handshake_succeeds(addr.clone(), "new-token").await);
+
+    tokens.replace(vec!["new-token".into()]);
+    assert!(!handshake_succeeds(addr.clone(), "old-token").await);
+    assert!(handshake_succeeds(addr, "new-token").await);
+}
+
+/// Grants by token prefix: `admin:` gets everything, `team-a:` only the
+/// `team-a` tunnel and the `web` capability, anything else is denied
+struct PrefixAuthenticator;
+
+#[async_trait]
+impl Authenticator for PrefixAuthenticator {
+    async fn authenticate(
+        &self,
+        token: &str,
+        _tunnel_id: Option<&str>,
+        peer: SocketAddr,
+    ) -> AuthResult {
+        assert!(peer.ip().is_loopback());
+        if token.starts_with("admin:") {
+            AuthResult::granted()
+        } else if token.starts_with("team-a:") {
+            AuthResult::Granted(
+                AuthGrant::default()
+                    .with_tunnel_ids(vec!["team-a".into()])
+                    .with_capabilities(vec!["web".into()]),
+            )
+        } else {
+            AuthResult::Denied
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_custom_authenticator_grants_by_token_prefix() {
+    let (server_addr, sessions) =
+        start_tunnel_server(|server| server.with_authenticator(PrefixAuthenticator)).await;
+
+    let addr = server_addr.to_string();
+    let client = |token: &str| TunnelClient::new(addr.clone(), token.to_string());
+
+    assert!(client_handshake_succeeds(client("admin:root")).await);
+    assert!(client_handshake_succeeds(client("team-a:key").with_tunnel_id("team-a")).await);
+    // The static token is no longer consulted
+    assert!(!client_handshake_succeeds(client(TUNNEL_TOKEN)).await);
+    assert!(!client_handshake_succeeds(client("guest:key")).await);
+    // Tokens over the static list's length cap still reach the authenticator
+    let long_token = format!("admin:{}", "x".repeat(2048));
+    assert!(client_handshake_succeeds(client(&long_token)).await);
+    // Restricted grants only cover their own tunnel IDs
+    assert!(!client_handshake_succeeds(client("team-a:key").with_tunnel_id("team-b")).await);
+    assert!(!client_handshake_succeeds(client("team-a:key")).await);
+
+    // Capabilities outside the grant are left off the session
+    let handle = connect_client(
+        client("team-a:key")
+            .with_tunnel_id("team-a")
+            .with_capability("web")
+            .with_capability("ssh"),
+    )
+    .await
+    .expect("team-a client should connect");
+    assert!(sessions.find_multiplexer_with_capability("web").is_some());
+    assert!(sessions.find_multiplexer_with_capability("ssh").is_none());
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_server_capabilities_limit_granted_set() {
+    let (server_addr, sessions) = start_tunnel_server(|server| {
+        server.with_capabilities(vec!["basic".into(), "udp".into(), "ssh".into()])
+    })
+    .await;
+
+    let client = TunnelClient::new(server_addr.to_string(), TUNNEL_TOKEN.into())
+        .with_capability("ssh")
+        .with_capability("web");
+    let granted = client.granted_capabilities_handle();
+    assert!(granted.get().is_empty());
+
+    let handle = connect_client(client).await.expect("client should connect");
+    assert!(granted.contains("basic"));
+    assert!(granted.contains("ssh"));
+    // TCP is disabled server-side, and `web` is unknown to the server
+    assert!(!granted.contains("tcp"));
+    assert!(!granted.contains("web"));
+    assert!(granted
+        .get()
+        .iter()
+        .any(|cap| cap.starts_with("flow_control:")));
+    // Like flow control, pings are granted outside the server list
+    assert!(granted.contains("ping"));
+
+    assert!(sessions.find_multiplexer_with_capability("ssh").is_some());
+    assert!(sessions.find_multiplexer_with_capability("tcp").is_none());
+    handle.abort();
+}
@@ -0,0 +1,139 @@
+//! Upload backpressure integration tests
+//!
+//! A slow local service must throttle the external client instead of the
+//! ingress buffering the request body.
+
+use super::start_tunnel;
+use bytes::Bytes;
+use ferrotunnel_http::{HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use http_body_util::{BodyExt, Full};
+use hyper::Request;
+use hyper_util::rt::TokioIo;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Notify;
+
+const TUNNEL_ID: &str = "slow-sink";
+const UPLOAD_SIZE: usize = 64 * 1024 * 1024;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+fn pattern_byte(offset: usize) -> u8 {
+    u8::try_from(offset % 251).unwrap()
+}
+
+/// Local service that waits for `start` before reading the request body, then
+/// replies with the number of bytes that matched the upload pattern.
+async fn start_gated_sink(start: Arc<Notify>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let service = hyper::service::service_fn(move |req: Request<hyper::body::Incoming>| {
+            let start = start.clone();
+            async move {
+                start.notified().await;
+                let mut body = req.into_body();
+                let mut offset = 0;
+                let mut matched = 0usize;
+                while let Some(frame) = body.frame().await {
+                    if let Ok(data) = frame?.into_data() {
+                        for byte in &data {
+                            if *byte == pattern_byte(offset) {
+                                matched += 1;
+                            }
+                            offset += 1;
+                        }
+                    }
+                }
+                Ok::<_, hyper::Error>(hyper::Response::new(Full::new(Bytes::from(
+                    matched.to_string(),
+                ))))
+            }
+        });
+        let _ = hyper::server::conn::http1::Builder::new()
+            .serve_connection(TokioIo::new(stream), service)
+            .await;
+    });
+
+    addr
+}
+
+#[tokio::test]
+async fn test_slow_local_service_throttles_upload() {
+    let start = Arc::new(Notify::new());
+    let local_addr = start_gated_sink(start.clone()).await;
+    let http_addr = start_tunnel(
+        TUNNEL_ID,
+        HttpProxy::new(local_addr),
+        PluginRegistry::new(),
+        IngressConfig::default(),
+    )
+    .await;
+
+    let mut conn = TcpStream::connect(http_addr).await.unwrap();
+    let head = format!(
+        "POST /upload HTTP/1.1\r\nHost: {TUNNEL_ID}\r\nContent-Length: {UPLOAD_SIZE}\r\n\r\n"
+    );
+    conn.write_all(head.as_bytes()).await.unwrap();
+    let (mut reader, mut writer) = conn.into_split();
+
+    let written = Arc::new(AtomicUsize::new(0));
+    let progress = written.clone();
+    let upload = tokio::spawn(async move {
+        let mut chunk = vec![0u8; CHUNK_SIZE];
+        for offset in (0..UPLOAD_SIZE).step_by(CHUNK_SIZE) {
+            for (i, byte) in chunk.iter_mut().enumerate() {
+                *byte = pattern_byte(offset + i);
+            }
+            writer.write_all(&chunk).await.unwrap();
+            progress.fetch_add(CHUNK_SIZE, Ordering::SeqCst);
+        }
+        writer
+    });
+
+    // While the sink is not reading, the upload stalls once the socket buffers
+    // and the tunnel stream window are full instead of being absorbed in memory
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let stalled_at = written.load(Ordering::SeqCst);
+    assert!(
+        stalled_at < UPLOAD_SIZE / 2,
+        "upload was not throttled: {stalled_at} of {UPLOAD_SIZE} bytes accepted"
+    );
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(
+        written.load(Ordering::SeqCst) - stalled_at < 16 * CHUNK_SIZE,
+        "upload kept progressing while the sink was paused"
+    );
+
+    start.notify_one();
+    let _writer = tokio::time::timeout(Duration::from_secs(60), upload)
+        .await
+        .expect("upload did not finish after the sink resumed")
+        .unwrap();
+
+    let mut response = Vec::new();
+    let mut buf = vec![0u8; 1024];
+    let expected = UPLOAD_SIZE.to_string();
+    while !String::from_utf8_lossy(&response).ends_with(&expected) {
+        let n = tokio::time::timeout(Duration::from_secs(30), reader.read(&mut buf))
+            .await
+            .expect("timed out waiting for response")
+            .unwrap();
+        assert!(
+            n > 0,
+            "connection closed early: {}",
+            String::from_utf8_lossy(&response)
+        );
+        response.extend_from_slice(&buf[..n]);
+    }
+    let response = String::from_utf8_lossy(&response);
+    assert!(
+        response.starts_with("HTTP/1.1 200"),
+        "unexpected response: {response}"
+    );
+}
@@ -0,0 +1,62 @@
+//! HTTP ingress base-domain (subdomain) routing integration tests
+
+use super::{get_free_port, make_client, start_echo_server, start_tunnel};
+use ferrotunnel_http::{HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use std::net::SocketAddr;
+
+const BASE_DOMAIN: &str = "tunnel.test";
+
+/// Start a server, a base-domain ingress and a client registered as `myapp`.
+/// Returns the ingress address.
+async fn start_subdomain_tunnel(strict: bool) -> SocketAddr {
+    let local_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let _local = start_echo_server(local_addr).await;
+
+    let config = IngressConfig {
+        base_domain: Some(BASE_DOMAIN.to_string()),
+        strict_base_domain: strict,
+        ..Default::default()
+    };
+    start_tunnel(
+        "myapp",
+        HttpProxy::new(local_addr.to_string()),
+        PluginRegistry::new(),
+        config,
+    )
+    .await
+}
+
+async fn status_for_host(http_addr: SocketAddr, host: &str) -> u16 {
+    make_client()
+        .get(format!("http://{http_addr}/"))
+        .header("Host", host)
+        .send()
+        .await
+        .unwrap()
+        .status()
+        .as_u16()
+}
+
+#[tokio::test]
+async fn test_subdomain_routes_to_tunnel_label() {
+    let http_addr = start_subdomain_tunnel(false).await;
+
+    assert_eq!(status_for_host(http_addr, "myapp.tunnel.test").await, 200);
+    assert_eq!(
+        status_for_host(http_addr, "MyApp.Tunnel.Test:8080").await,
+        200
+    );
+    assert_eq!(status_for_host(http_addr, "other.tunnel.test").await, 404);
+    assert_eq!(status_for_host(http_addr, BASE_DOMAIN).await, 404);
+    // Outside the base domain the whole host is the tunnel ID
+    assert_eq!(status_for_host(http_addr, "myapp").await, 200);
+}
+
+#[tokio::test]
+async fn test_strict_base_domain_rejects_other_hosts() {
+    let http_addr = start_subdomain_tunnel(true).await;
+
+    assert_eq!(status_for_host(http_addr, "myapp.tunnel.test").await, 200);
+    assert_eq!(status_for_host(http_addr, "myapp").await, 404);
+}
@@ -0,0 +1,175 @@
+//! HTTP ingress request body and header limit integration tests
+
+use super::{make_client, start_tunnel};
+use bytes::Bytes;
+use ferrotunnel_http::{HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use http_body_util::{BodyExt, Full};
+use hyper::Request;
+use hyper_util::rt::TokioIo;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const TUNNEL_ID: &str = "limits";
+const MAX_REQUEST_SIZE: usize = 1024;
+const MAX_HEADER_COUNT: usize = 16;
+const MAX_HEADER_BYTES: usize = 2048;
+
+/// Local HTTP/1.1 service that drains the request body and counts requests
+async fn start_counting_server() -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                break;
+            };
+            let counter = counter.clone();
+            tokio::spawn(async move {
+                let service =
+                    hyper::service::service_fn(move |req: Request<hyper::body::Incoming>| {
+                        let counter = counter.clone();
+                        async move {
+                            counter.fetch_add(1, Ordering::SeqCst);
+                            let body = req.into_body().collect().await?.to_bytes();
+                            Ok::<_, hyper::Error>(hyper::Response::new(Full::new(Bytes::from(
+                                body.len().to_string(),
+                            ))))
+                        }
+                    });
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+
+    (addr, hits)
+}
+
+/// Start a tunnel server, an HTTP ingress limited to `MAX_REQUEST_SIZE` and the
+/// header limits, and a client forwarding to `local_addr`. Returns the ingress
+/// address.
+async fn start_limited_tunnel(local_addr: String) -> SocketAddr {
+    let config = IngressConfig {
+        max_request_size: MAX_REQUEST_SIZE,
+        max_header_count: MAX_HEADER_COUNT,
+        max_header_bytes: MAX_HEADER_BYTES,
+        ..Default::default()
+    };
+    start_tunnel(
+        TUNNEL_ID,
+        HttpProxy::new(local_addr),
+        PluginRegistry::new(),
+        config,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_content_length_over_limit_rejected_before_tunnel() {
+    let (local_addr, hits) = start_counting_server().await;
+    let http_addr = start_limited_tunnel(local_addr).await;
+    let client = make_client();
+    let url = format!("http://{http_addr}/upload");
+
+    let small = client
+        .post(&url)
+        .header("Host", TUNNEL_ID)
+        .body(vec![b'a'; MAX_REQUEST_SIZE])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(small.status(), 200);
+    assert_eq!(small.text().await.unwrap(), MAX_REQUEST_SIZE.to_string());
+
+    let large = client
+        .post(&url)
+        .header("Host", TUNNEL_ID)
+        .body(vec![b'a'; MAX_REQUEST_SIZE + 1])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(large.status(), 413);
+
+    // Only the request within the limit reached the local service
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_chunked_body_cut_off_while_streaming() {
+    let (local_addr, _hits) = start_counting_server().await;
+    let http_addr = start_limited_tunnel(local_addr).await;
+
+    let mut conn = TcpStream::connect(http_addr).await.unwrap();
+    let head =
+        format!("POST /upload HTTP/1.1\r\nHost: {TUNNEL_ID}\r\nTransfer-Encoding: chunked\r\n\r\n");
+    conn.write_all(head.as_bytes()).await.unwrap();
+
+    // Four times the limit, without a Content-Length for the fast path to check.
+    // Writes may fail once the ingress has answered and closed the body.
+    let chunk = vec![b'a'; 512];
+    for _ in 0..(4 * MAX_REQUEST_SIZE / chunk.len()) {
+        let mut framed = format!("{:x}\r\n", chunk.len()).into_bytes();
+        framed.extend_from_slice(&chunk);
+        framed.extend_from_slice(b"\r\n");
+        if conn.write_all(&framed).await.is_err() {
+            break;
+        }
+    }
+    let _ = conn.write_all(b"0\r\n\r\n").await;
+
+    let mut response = vec![0u8; 1024];
+    let n = tokio::time::timeout(Duration::from_secs(5), conn.read(&mut response))
+        .await
+        .expect("timed out waiting for response")
+        .unwrap();
+    let response = String::from_utf8_lossy(&response[..n]);
+    assert!(
+        response.starts_with("HTTP/1.1 413"),
+        "unexpected response: {response}"
+    );
+}
+
+#[tokio::test]
+async fn test_oversized_headers_rejected_with_431() {
+    let (local_addr, hits) = start_counting_server().await;
+    let http_addr = start_limited_tunnel(local_addr).await;
+    let client = make_client();
+    let url = format!("http://{http_addr}/");
+
+    let normal = client
+        .get(&url)
+        .header("Host", TUNNEL_ID)
+        .header("X-Custom", "value")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(normal.status(), 200);
+
+    let mut too_many = client.get(&url).header("Host", TUNNEL_ID);
+    for i in 0..MAX_HEADER_COUNT {
+        too_many = too_many.header(format!("X-Extra-{i}"), "1");
+    }
+    let too_many = too_many.send().await.unwrap();
+    assert_eq!(too_many.status(), 431);
+
+    let too_large = client
+        .get(&url)
+        .header("Host", TUNNEL_ID)
+        .header("X-Large", "a".repeat(MAX_HEADER_BYTES))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(too_large.status(), 431);
+
+    // Only the normal request reached the local service
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
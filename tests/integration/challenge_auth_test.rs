@@ -0,0 +1,205 @@
+//! Challenge-response handshake integration tests
+
+use super::{get_free_port, wait_for_server};
+use bytes::Bytes;
+use ferrotunnel_core::auth::challenge_response;
+use ferrotunnel_core::{TunnelClient, TunnelServer};
+use ferrotunnel_protocol::constants::{
+    CHALLENGE_AUTH_CAPABILITY, MAX_PROTOCOL_VERSION, MIN_PROTOCOL_VERSION, RESUME_CAPABILITY,
+};
+use ferrotunnel_protocol::frame::{Frame, HandshakeFrame, HandshakeStatus};
+use ferrotunnel_protocol::TunnelCodec;
+use futures_util::{SinkExt, StreamExt};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
+use uuid::Uuid;
+
+const TOKEN: &str = "test-token";
+
+/// Start the server returned by `build` for a free local address
+async fn start_server(build: impl FnOnce(SocketAddr) -> TunnelServer) -> SocketAddr {
+    let addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let server = build(addr);
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    assert!(wait_for_server(addr, Duration::from_secs(5)).await);
+    addr
+}
+
+/// Raw connection that sent a tokenless handshake asking for challenge auth
+struct Challenged {
+    framed: Framed<TcpStream, TunnelCodec>,
+    nonce: Bytes,
+}
+
+impl Challenged {
+    async fn connect(addr: SocketAddr) -> Self {
+        Self::connect_with(addr, None, None).await
+    }
+
+    /// Like [`Self::connect`], asking for `tunnel_id` and to resume `previous`
+    async fn connect_with(
+        addr: SocketAddr,
+        tunnel_id: Option<&str>,
+        previous: Option<Uuid>,
+    ) -> Self {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut framed = Framed::new(stream, TunnelCodec::new());
+        let mut capabilities = vec!["basic".into(), CHALLENGE_AUTH_CAPABILITY.into()];
+        capabilities.extend(previous.map(|id| format!("{RESUME_CAPABILITY}:{id}")));
+        framed
+            .send(Frame::Handshake(Box::new(HandshakeFrame {
+                token: String::new(),
+                tunnel_id: tunnel_id.map(str::to_string),
+                min_version: MIN_PROTOCOL_VERSION,
+                max_version: MAX_PROTOCOL_VERSION,
+                capabilities,
+            })))
+            .await
+            .unwrap();
+        let Frame::AuthChallenge { nonce } = next_frame(&mut framed).await else {
+            panic!("expected an auth challenge");
+        };
+        Self { framed, nonce }
+    }
+
+    /// Send `mac` as the answer and return the handshake status
+    async fn answer(self, mac: &[u8]) -> HandshakeStatus {
+        self.answer_session(mac).await.0
+    }
+
+    /// Send `mac` as the answer and return the handshake status, the session
+    /// ID and the connection, which holds the session open
+    async fn answer_session(
+        mut self,
+        mac: &[u8],
+    ) -> (HandshakeStatus, Uuid, Framed<TcpStream, TunnelCodec>) {
+        self.framed
+            .send(Frame::AuthResponse {
+                mac: Bytes::copy_from_slice(mac),
+            })
+            .await
+            .unwrap();
+        match next_frame(&mut self.framed).await {
+            Frame::HandshakeAck {
+                status, session_id, ..
+            } => (status, session_id, self.framed),
+            frame => panic!("expected a handshake ack, got {frame:?}"),
+        }
+    }
+}
+
+async fn next_frame(framed: &mut Framed<TcpStream, TunnelCodec>) -> Frame {
+    tokio::time::timeout(Duration::from_secs(5), framed.next())
+        .await
+        .expect("no frame from server")
+        .unwrap()
+        .unwrap()
+}
+
+/// Run `client` until its handshake completes and report whether it succeeded
+async fn client_connects(mut client: TunnelClient) -> bool {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let handle = tokio::spawn(async move {
+        client
+            .connect_and_run_with_callback(
+                |_stream| async {},
+                move |_session_id| {
+                    let _ = tx.send(());
+                },
+            )
+            .await
+    });
+    let connected = tokio::time::timeout(Duration::from_secs(5), rx)
+        .await
+        .is_ok_and(|r| r.is_ok());
+    handle.abort();
+    connected
+}
+
+#[tokio::test]
+async fn test_client_answers_challenge() {
+    let addr = start_server(|addr| TunnelServer::new(addr, TOKEN.into())).await;
+
+    let client = TunnelClient::new(addr.to_string(), TOKEN.into()).with_challenge_auth();
+    assert!(client_connects(client).await);
+
+    let client = TunnelClient::new(addr.to_string(), "wrong-token".into()).with_challenge_auth();
+    assert!(!client_connects(client).await);
+
+    // Plain token handshakes keep working
+    assert!(client_connects(TunnelClient::new(addr.to_string(), TOKEN.into())).await);
+}
+
+#[tokio::test]
+async fn test_wrong_answer_rejected() {
+    let addr = start_server(|addr| TunnelServer::new(addr, TOKEN.into())).await;
+
+    let challenged = Challenged::connect(addr).await;
+    assert_eq!(challenged.nonce.len(), 32);
+    let mac = challenge_response("wrong-token", &challenged.nonce);
+    assert_eq!(challenged.answer(&mac).await, HandshakeStatus::InvalidToken);
+
+    // The raw token is no answer either
+    let challenged = Challenged::connect(addr).await;
+    assert_eq!(
+        challenged.answer(TOKEN.as_bytes()).await,
+        HandshakeStatus::InvalidToken
+    );
+}
+
+#[tokio::test]
+async fn test_replayed_answer_rejected() {
+    let addr = start_server(|addr| TunnelServer::new(addr, TOKEN.into())).await;
+
+    let first = Challenged::connect(addr).await;
+    let captured = challenge_response(TOKEN, &first.nonce);
+    let first_nonce = first.nonce.clone();
+    assert_eq!(first.answer(&captured).await, HandshakeStatus::Success);
+
+    // Every connection gets a fresh nonce, so the captured answer is stale
+    let second = Challenged::connect(addr).await;
+    assert_ne!(second.nonce, first_nonce);
+    assert_eq!(
+        second.answer(&captured).await,
+        HandshakeStatus::InvalidToken
+    );
+}
+
+#[tokio::test]
+async fn test_challenge_refused_by_server() {
+    let addr = start_server(|addr| {
+        TunnelServer::new(addr, TOKEN.into()).with_capabilities(vec!["basic".into(), "tcp".into()])
+    })
+    .await;
+
+    // Without the capability the server expects a token, and gets none
+    let client = TunnelClient::new(addr.to_string(), TOKEN.into()).with_challenge_auth();
+    assert!(!client_connects(client).await);
+}
+
+#[tokio::test]
+async fn test_resume_needs_the_same_token() {
+    let addr = start_server(|addr| {
+        TunnelServer::new(addr, TOKEN.into()).with_tokens(vec![TOKEN.into(), "other-token".into()])
+    })
+    .await;
+
+    let victim = Challenged::connect_with(addr, Some("victim"), None).await;
+    let mac = challenge_response(TOKEN, &victim.nonce);
+    let (status, victim_id, _victim_conn) = victim.answer_session(&mac).await;
+    assert_eq!(status, HandshakeStatus::Success);
+
+    // Another valid token cannot take the session over by naming it
+    let intruder = Challenged::connect_with(addr, Some("victim"), Some(victim_id)).await;
+    let mac = challenge_response("other-token", &intruder.nonce);
+    assert_eq!(intruder.answer(&mac).await, HandshakeStatus::TunnelIdTaken);
+
+    // The client that holds the session still can
+    let owner = Challenged::connect_with(addr, Some("victim"), Some(victim_id)).await;
+    let mac = challenge_response(TOKEN, &owner.nonce);
+    assert_eq!(owner.answer(&mac).await, HandshakeStatus::Success);
+}
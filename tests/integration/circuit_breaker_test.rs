@@ -0,0 +1,78 @@
+//! Per-tunnel circuit breaker integration tests
+
+use super::{
+    connect_tunnel, get_free_port, make_client, start_echo_server, start_ingress,
+    start_tunnel_server, TUNNEL_TOKEN,
+};
+use ferrotunnel_core::TunnelClient;
+use ferrotunnel_http::{HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+const TUNNEL_ID: &str = "breaker";
+const COOLDOWN: Duration = Duration::from_millis(300);
+
+async fn get_status(client: &reqwest::Client, url: &str) -> u16 {
+    client
+        .get(url)
+        .header("Host", TUNNEL_ID)
+        .send()
+        .await
+        .unwrap()
+        .status()
+        .as_u16()
+}
+
+#[tokio::test]
+async fn test_circuit_opens_on_tunnel_failures_and_recovers() {
+    let local_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let _local = start_echo_server(local_addr).await;
+
+    let (server_addr, sessions) = start_tunnel_server(|server| server).await;
+
+    let config = IngressConfig {
+        circuit_failure_threshold: 2,
+        circuit_cooldown: COOLDOWN,
+        response_timeout: Duration::from_secs(2),
+        ..Default::default()
+    };
+    let http_addr = start_ingress(sessions.clone(), PluginRegistry::new(), config).await;
+
+    // While unhealthy the client closes every stream without answering,
+    // so the ingress sees the upstream connection drop mid-request.
+    let healthy = Arc::new(AtomicBool::new(false));
+    let proxy = Arc::new(HttpProxy::new(local_addr.to_string()));
+    let client =
+        TunnelClient::new(server_addr.to_string(), TUNNEL_TOKEN.into()).with_tunnel_id(TUNNEL_ID);
+    let client_healthy = healthy.clone();
+    connect_tunnel(&sessions, TUNNEL_ID, client, move |mut stream| {
+        let proxy = proxy.clone();
+        let healthy = client_healthy.load(Ordering::SeqCst);
+        async move {
+            if healthy {
+                proxy.handle_stream(stream);
+            } else {
+                let _ = stream.shutdown().await;
+            }
+        }
+    })
+    .await;
+
+    let http_client = make_client();
+    let url = format!("http://{http_addr}/");
+    assert_eq!(get_status(&http_client, &url).await, 502);
+    assert_eq!(get_status(&http_client, &url).await, 502);
+    // Threshold reached: rejected without touching the tunnel
+    assert_eq!(get_status(&http_client, &url).await, 503);
+
+    // Backend recovers; the first request after the cooldown probes and closes the circuit
+    healthy.store(true, Ordering::SeqCst);
+    assert_eq!(get_status(&http_client, &url).await, 503);
+    tokio::time::sleep(COOLDOWN + Duration::from_millis(100)).await;
+    assert_eq!(get_status(&http_client, &url).await, 200);
+    assert_eq!(get_status(&http_client, &url).await, 200);
+}
@@ -0,0 +1,93 @@
+//! Client-initiated stream integration tests
+
+use super::{get_free_port, wait_for_server};
+use ferrotunnel_core::{StreamOpener, TunnelClient, TunnelServer};
+use ferrotunnel_protocol::frame::Protocol;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const TUNNEL_ID: &str = "custom-protocol";
+
+/// Start `server` on a free local port and return its address
+async fn start_server(build: impl FnOnce(SocketAddr) -> TunnelServer) -> SocketAddr {
+    let addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let server = build(addr);
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    assert!(wait_for_server(addr, Duration::from_secs(5)).await);
+    addr
+}
+
+/// Connect a client for `TUNNEL_ID` and return its stream opener once the
+/// handshake has completed
+async fn connect_client(addr: SocketAddr) -> StreamOpener {
+    let mut client =
+        TunnelClient::new(addr.to_string(), "test-token".into()).with_tunnel_id(TUNNEL_ID);
+    let opener = client.stream_opener();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
+        let _ = client
+            .connect_and_run_with_callback(
+                |_stream| async {},
+                move |_session_id| {
+                    let _ = tx.send(());
+                },
+            )
+            .await;
+    });
+    tokio::time::timeout(Duration::from_secs(5), rx)
+        .await
+        .unwrap()
+        .unwrap();
+    opener
+}
+
+#[tokio::test]
+async fn test_server_handler_echoes_client_stream() {
+    let addr = start_server(|addr| {
+        TunnelServer::new(addr, "test-token".into()).on_client_stream(
+            |tunnel_id, mut stream| async move {
+                assert_eq!(tunnel_id, TUNNEL_ID);
+                let mut buf = [0u8; 64];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    if n == 0 || stream.write_all(&buf[..n]).await.is_err() {
+                        break;
+                    }
+                }
+            },
+        )
+    })
+    .await;
+    let opener = connect_client(addr).await;
+
+    let mut stream = opener.open_stream(Protocol::TCP).await.unwrap();
+    stream.write_all(b"hello server").await.unwrap();
+    let mut echoed = [0u8; 12];
+    tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut echoed))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(&echoed, b"hello server");
+}
+
+#[tokio::test]
+async fn test_client_stream_closed_without_handler() {
+    let addr = start_server(|addr| TunnelServer::new(addr, "test-token".into())).await;
+    let opener = connect_client(addr).await;
+
+    let mut stream = opener.open_stream(Protocol::TCP).await.unwrap();
+    let _ = stream.write_all(b"anyone there?").await;
+    let mut buf = [0u8; 16];
+    let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+        .await
+        .unwrap();
+    assert!(matches!(read, Ok(0) | Err(_)));
+}
+
+#[tokio::test]
+async fn test_open_stream_fails_when_not_connected() {
+    let client = TunnelClient::new("127.0.0.1:1".into(), "test-token".into());
+    assert!(client.open_stream(Protocol::TCP).await.is_err());
+}
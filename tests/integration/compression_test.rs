@@ -0,0 +1,92 @@
+//! HTTP ingress response compression integration tests
+
+use super::{get_free_port, make_client, start_tunnel};
+use ferrotunnel_http::{CompressionConfig, HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use std::io::Read;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+fn large_text() -> String {
+    "The quick brown fox jumps over the lazy dog.\n".repeat(500)
+}
+
+/// Local service answering every request with a large `text/plain` body
+async fn start_text_server(addr: SocketAddr) {
+    let listener = TcpListener::bind(addr).await.unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 4096];
+                if socket.read(&mut buf).await.unwrap_or(0) == 0 {
+                    return;
+                }
+                let body = large_text();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\n\
+                     Content-Type: text/plain\r\n\
+                     Content-Length: {}\r\n\
+                     \r\n\
+                     {body}",
+                    body.len()
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+}
+
+/// Start a server, a compressing ingress and a client. Returns the ingress address.
+async fn start_compressing_tunnel() -> SocketAddr {
+    let local_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    start_text_server(local_addr).await;
+
+    let config = IngressConfig::default().compression(CompressionConfig::default());
+    start_tunnel(
+        "localhost",
+        HttpProxy::new(local_addr.to_string()),
+        PluginRegistry::new(),
+        config,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_large_text_response_gzip_encoded() {
+    let http_addr = start_compressing_tunnel().await;
+
+    let resp = make_client()
+        .get(format!("http://{http_addr}/"))
+        .header("Host", "localhost")
+        .header("Accept-Encoding", "gzip")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-encoding"], "gzip");
+    assert!(resp.headers().get("content-length").is_none());
+
+    let compressed = resp.bytes().await.unwrap();
+    assert!(compressed.len() < large_text().len());
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(&compressed[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, large_text());
+}
+
+#[tokio::test]
+async fn test_response_uncompressed_without_accept_encoding() {
+    let http_addr = start_compressing_tunnel().await;
+
+    let resp = make_client()
+        .get(format!("http://{http_addr}/"))
+        .header("Host", "localhost")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers().get("content-encoding").is_none());
+    assert_eq!(resp.text().await.unwrap(), large_text());
+}
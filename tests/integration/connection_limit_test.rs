@@ -0,0 +1,69 @@
+//! HTTP ingress connection limit integration tests
+
+use super::{get_free_port, wait_for_server};
+use ferrotunnel_core::TunnelServer;
+use ferrotunnel_http::{HttpIngress, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Send a GET on a fresh connection and return the raw response
+async fn get(http_addr: SocketAddr) -> String {
+    let mut stream = TcpStream::connect(http_addr).await.unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: app\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response))
+        .await
+        .expect("connection was not closed")
+        .unwrap();
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn test_connections_over_limit_get_503_with_retry_after() {
+    let server_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let http_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let sessions = TunnelServer::new(server_addr, "test-token".into()).sessions();
+
+    let config = IngressConfig {
+        max_connections: 2,
+        connection_limit_retry_after: Duration::from_secs(7),
+        connection_limit_body: "Ingress saturated".to_string(),
+        ..Default::default()
+    };
+    let ingress =
+        HttpIngress::with_config(http_addr, sessions, Arc::new(PluginRegistry::new()), config);
+    tokio::spawn(async move {
+        let _ = ingress.start().await;
+    });
+    assert!(wait_for_server(http_addr, Duration::from_secs(5)).await);
+    // Let the readiness probe's connection release its permit
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // Saturate the limit with idle connections
+    let mut held = Vec::new();
+    for _ in 0..2 {
+        held.push(TcpStream::connect(http_addr).await.unwrap());
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let response = get(http_addr).await;
+    assert!(response.starts_with("HTTP/1.1 503"), "{response}");
+    assert!(
+        response.to_ascii_lowercase().contains("retry-after: 7\r\n"),
+        "{response}"
+    );
+    assert!(response.ends_with("Ingress saturated"), "{response}");
+
+    // Freed permits admit connections again; no tunnel, so 404
+    drop(held);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let response = get(http_addr).await;
+    assert!(response.starts_with("HTTP/1.1 404"), "{response}");
+}
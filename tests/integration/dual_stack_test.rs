@@ -0,0 +1,70 @@
+//! Dual-stack (`[::]`) listener integration tests
+
+use super::{
+    connect_proxy_tunnel, get_free_port, make_client, start_echo_server, wait_for_server,
+    TUNNEL_TOKEN,
+};
+use ferrotunnel_core::TunnelServer;
+use ferrotunnel_http::{HttpIngress, HttpProxy};
+use ferrotunnel_plugin::PluginRegistry;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+const TUNNEL_ID: &str = "dual";
+
+fn ipv6_available() -> bool {
+    std::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0)).is_ok()
+}
+
+fn unspecified_v6(port: u16) -> SocketAddr {
+    (Ipv6Addr::UNSPECIFIED, port).into()
+}
+
+#[tokio::test]
+async fn test_unspecified_ipv6_serves_both_families() {
+    if !ipv6_available() {
+        return;
+    }
+
+    let server_port = get_free_port();
+    let server = TunnelServer::new(unspecified_v6(server_port), TUNNEL_TOKEN.into());
+    let sessions = server.sessions();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    let server_v4 = SocketAddr::from((Ipv4Addr::LOCALHOST, server_port));
+    assert!(wait_for_server(server_v4, Duration::from_secs(5)).await);
+
+    let http_port = get_free_port();
+    let ingress = HttpIngress::new(
+        unspecified_v6(http_port),
+        sessions.clone(),
+        Arc::new(PluginRegistry::new()),
+    );
+    tokio::spawn(async move {
+        let _ = ingress.start().await;
+    });
+    let http_v4 = SocketAddr::from((Ipv4Addr::LOCALHOST, http_port));
+    let http_v6 = SocketAddr::from((Ipv6Addr::LOCALHOST, http_port));
+    assert!(wait_for_server(http_v4, Duration::from_secs(5)).await);
+
+    let local_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    start_echo_server(local_addr).await;
+
+    // The client reaches the control listener over IPv4
+    let proxy = Arc::new(HttpProxy::new(local_addr.to_string()));
+    connect_proxy_tunnel(server_v4, &sessions, TUNNEL_ID, proxy).await;
+
+    let http = make_client();
+    for addr in [http_v4, http_v6] {
+        let response = http
+            .get(format!("http://{addr}/"))
+            .header("Host", TUNNEL_ID)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200, "{addr}");
+        assert_eq!(response.text().await.unwrap(), "Hello, World!");
+    }
+}
@@ -0,0 +1,89 @@
+//! Custom ingress error page integration tests
+
+use super::{make_client, start_tunnel};
+use ferrotunnel_http::{ErrorPage, ErrorPageSet, HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use hyper::StatusCode;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const TUNNEL_ID: &str = "app";
+
+/// Local service answering every request with its own 404
+async fn start_not_found_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 4096];
+                if socket.read(&mut buf).await.unwrap_or(0) > 0 {
+                    let response = "HTTP/1.1 404 Not Found\r\n\
+                         Content-Type: text/plain\r\n\
+                         Content-Length: 12\r\n\
+                         \r\n\
+                         no such page";
+                    let _ = socket.write_all(response.as_bytes()).await;
+                }
+            });
+        }
+    });
+    addr
+}
+
+/// Start a tunnel server, an HTTP ingress using `config` and a client for
+/// `TUNNEL_ID` in front of the 404 service. Returns the ingress address.
+async fn start_app_tunnel(config: IngressConfig) -> SocketAddr {
+    let proxy = HttpProxy::new(start_not_found_server().await);
+    start_tunnel(TUNNEL_ID, proxy, PluginRegistry::new(), config).await
+}
+
+/// Status, content type and body of a GET for `host`
+async fn get(http_addr: SocketAddr, host: &str) -> (u16, Option<String>, String) {
+    let response = make_client()
+        .get(format!("http://{http_addr}/"))
+        .header("Host", host)
+        .send()
+        .await
+        .unwrap();
+    let status = response.status().as_u16();
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .map(|value| value.to_str().unwrap().to_string());
+    (status, content_type, response.text().await.unwrap())
+}
+
+#[tokio::test]
+async fn test_custom_not_found_page() {
+    let pages = ErrorPageSet::new().with_page(
+        StatusCode::NOT_FOUND,
+        ErrorPage::html("<h1>No tunnel named {tunnel_id}</h1><p>{message}</p>"),
+    );
+    let http_addr = start_app_tunnel(IngressConfig::default().error_pages(pages)).await;
+
+    let (status, content_type, body) = get(http_addr, "missing").await;
+    assert_eq!(status, 404);
+    assert_eq!(content_type.as_deref(), Some("text/html; charset=utf-8"));
+    assert_eq!(
+        body,
+        "<h1>No tunnel named missing</h1><p>Tunnel not found</p>"
+    );
+
+    // A 404 from the local service is passed through
+    let (status, content_type, body) = get(http_addr, TUNNEL_ID).await;
+    assert_eq!(status, 404);
+    assert_eq!(content_type.as_deref(), Some("text/plain"));
+    assert_eq!(body, "no such page");
+}
+
+#[tokio::test]
+async fn test_default_error_body_without_pages() {
+    let http_addr = start_app_tunnel(IngressConfig::default()).await;
+
+    let (status, content_type, body) = get(http_addr, "missing").await;
+    assert_eq!(status, 404);
+    assert_eq!(content_type, None);
+    assert_eq!(body, "Tunnel not found");
+}
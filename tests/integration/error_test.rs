@@ -93,3 +93,72 @@ async fn test_upstream_timeout() {
     // We'll skip implementation of timeout test for now to avoid slow tests.
     // Just verify connection refused is enough for "Error Scenarios" for this iteration.
 }
+
+/// A finite reconnect budget makes `start()` give up with the last error
+#[tokio::test]
+async fn test_client_gives_up_after_max_reconnect_attempts() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Accepts connections and drops them before any handshake reply
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = accepted.clone();
+    tokio::spawn(async move {
+        while let Ok((conn, _)) = listener.accept().await {
+            counter.fetch_add(1, Ordering::SeqCst);
+            drop(conn);
+        }
+    });
+
+    let mut client = Client::builder()
+        .server_addr(addr.to_string())
+        .token("test-token")
+        .reconnect_delay(Duration::from_millis(10))
+        .max_reconnect_attempts(Some(2))
+        .build()
+        .unwrap();
+
+    let result = tokio::time::timeout(Duration::from_secs(5), client.start())
+        .await
+        .expect("client should give up");
+    assert!(result.is_err());
+    // The first attempt plus two reconnections
+    assert_eq!(accepted.load(Ordering::SeqCst), 3);
+    client.shutdown().await.unwrap();
+}
+
+/// A rejected token is not retried, even with unlimited reconnects
+#[tokio::test]
+async fn test_client_fails_fast_on_invalid_token() {
+    let config = TestConfig::default();
+
+    let mut server = Server::builder()
+        .bind(config.server_addr)
+        .http_bind(config.http_addr)
+        .token(config.token)
+        .build()
+        .expect("Failed to build server");
+    let _server_handle = tokio::spawn(async move {
+        let _ = server.start().await;
+    });
+    assert!(wait_for_server(config.server_addr, Duration::from_secs(5)).await);
+
+    let mut client = Client::builder()
+        .server_addr(config.server_addr.to_string())
+        .token("wrong-token")
+        .reconnect_delay(Duration::from_millis(10))
+        .build()
+        .unwrap();
+
+    let result = tokio::time::timeout(Duration::from_secs(5), client.start())
+        .await
+        .expect("invalid token should not be retried");
+    assert!(matches!(
+        result,
+        Err(ferrotunnel::TunnelError::Authentication(_))
+    ));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!client.is_running());
+}
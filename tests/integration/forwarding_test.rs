@@ -0,0 +1,235 @@
+//! Forwarding header integration tests for `HttpProxy` and `HttpIngress`
+
+use super::{make_client, start_tunnel};
+use bytes::Bytes;
+use ferrotunnel_http::{ForwardedHeaders, ForwardingConfig, HttpProxy, IngressConfig, TraceParent};
+use ferrotunnel_plugin::PluginRegistry;
+use http_body_util::Full;
+use hyper::{HeaderMap, Request};
+use hyper_util::rt::TokioIo;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+/// Local HTTP/1.1 service that reports the headers of every request it sees
+async fn start_capture_server() -> (String, mpsc::UnboundedReceiver<HeaderMap>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                break;
+            };
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let service =
+                    hyper::service::service_fn(move |req: Request<hyper::body::Incoming>| {
+                        let _ = tx.send(req.headers().clone());
+                        async {
+                            Ok::<_, hyper::Error>(hyper::Response::new(Full::new(Bytes::new())))
+                        }
+                    });
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+
+    (addr, rx)
+}
+
+/// Start a tunnel server, HTTP ingress and a client whose proxy applies
+/// `forwarding`. Returns the ingress address.
+async fn start_forwarding_tunnel(
+    tunnel_id: &'static str,
+    local_addr: String,
+    forwarding: ForwardingConfig,
+) -> SocketAddr {
+    start_tunnel_with_ingress(tunnel_id, local_addr, forwarding, IngressConfig::default()).await
+}
+
+/// Like [`start_forwarding_tunnel`], with the ingress built from `config`
+async fn start_tunnel_with_ingress(
+    tunnel_id: &'static str,
+    local_addr: String,
+    forwarding: ForwardingConfig,
+    config: IngressConfig,
+) -> SocketAddr {
+    let proxy = HttpProxy::new(local_addr).with_forwarding(forwarding);
+    start_tunnel(tunnel_id, proxy, PluginRegistry::new(), config).await
+}
+
+#[tokio::test]
+async fn test_forwarding_headers_reach_local_service() {
+    let (local_addr, mut seen) = start_capture_server().await;
+    let forwarding = ForwardingConfig {
+        forwarded_for: true,
+        forwarded_host: true,
+        forwarded_proto: Some("https".to_string()),
+        host_override: Some("app.internal:3000".to_string()),
+    };
+    let http_addr = start_forwarding_tunnel("forwarded", local_addr, forwarding).await;
+
+    let response = make_client()
+        .get(format!("http://{http_addr}/"))
+        .header("Host", "forwarded")
+        .header("X-Forwarded-For", "198.51.100.1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let headers = tokio::time::timeout(Duration::from_secs(5), seen.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    // The ingress drops the client's own chain and records its address once
+    assert_eq!(headers["x-forwarded-for"], "127.0.0.1");
+    assert_eq!(headers["x-forwarded-host"], "forwarded");
+    assert_eq!(headers["x-forwarded-proto"], "https");
+    assert_eq!(headers["host"], "app.internal:3000");
+}
+
+#[tokio::test]
+async fn test_proxy_adds_nothing_without_forwarding() {
+    let (local_addr, mut seen) = start_capture_server().await;
+    let http_addr =
+        start_forwarding_tunnel("passthrough", local_addr, ForwardingConfig::default()).await;
+
+    let response = make_client()
+        .get(format!("http://{http_addr}/"))
+        .header("Host", "passthrough")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let headers = tokio::time::timeout(Duration::from_secs(5), seen.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(headers["host"], "passthrough");
+    // Only what the ingress sets
+    assert_eq!(headers["x-forwarded-for"], "127.0.0.1");
+    assert_eq!(headers["x-forwarded-host"], "passthrough");
+    assert_eq!(headers["x-forwarded-proto"], "http");
+}
+
+#[tokio::test]
+async fn test_spoofed_forwarded_for_replaced_by_ingress() {
+    let (local_addr, mut seen) = start_capture_server().await;
+    let config =
+        IngressConfig::default().forwarded_headers(ForwardedHeaders::trust_proxies(["10.0.0.0/8"
+            .parse()
+            .unwrap()]));
+    let http_addr =
+        start_tunnel_with_ingress("spoofed", local_addr, ForwardingConfig::default(), config).await;
+
+    let response = make_client()
+        .get(format!("http://{http_addr}/"))
+        .header("Host", "spoofed")
+        .header("X-Forwarded-For", "10.0.0.1")
+        .header("X-Forwarded-Host", "admin.internal")
+        .header("Forwarded", "for=10.0.0.1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let headers = tokio::time::timeout(Duration::from_secs(5), seen.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    // 127.0.0.1 is not a trusted proxy
+    assert_eq!(headers["x-forwarded-for"], "127.0.0.1");
+    assert_eq!(headers["x-forwarded-host"], "spoofed");
+    assert!(!headers.contains_key("forwarded"));
+}
+
+#[tokio::test]
+async fn test_trusted_proxy_chain_preserved_and_appended() {
+    let (local_addr, mut seen) = start_capture_server().await;
+    let config = IngressConfig::default().forwarded_headers(ForwardedHeaders::trust_proxies([
+        "127.0.0.0/8".parse().unwrap(),
+    ]));
+    let forwarding = ForwardingConfig {
+        forwarded_for: true,
+        ..Default::default()
+    };
+    let http_addr = start_tunnel_with_ingress("trusted", local_addr, forwarding, config).await;
+
+    let response = make_client()
+        .get(format!("http://{http_addr}/"))
+        .header("Host", "trusted")
+        .header("X-Forwarded-For", "198.51.100.1, 10.0.0.2")
+        .header("X-Forwarded-Proto", "https")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let headers = tokio::time::timeout(Duration::from_secs(5), seen.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    // The proxy does not repeat the hop the ingress appended
+    assert_eq!(
+        headers["x-forwarded-for"],
+        "198.51.100.1, 10.0.0.2, 127.0.0.1"
+    );
+    assert_eq!(headers["x-forwarded-proto"], "https");
+}
+
+#[tokio::test]
+async fn test_traceparent_propagated_to_local_service() {
+    let (local_addr, mut seen) = start_capture_server().await;
+    let http_addr =
+        start_forwarding_tunnel("traced", local_addr, ForwardingConfig::default()).await;
+    let incoming = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    let response = make_client()
+        .get(format!("http://{http_addr}/"))
+        .header("Host", "traced")
+        .header("traceparent", incoming)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let headers = tokio::time::timeout(Duration::from_secs(5), seen.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    let forwarded = TraceParent::parse(headers["traceparent"].to_str().unwrap())
+        .expect("forwarded traceparent is not valid");
+    let incoming = TraceParent::parse(incoming).unwrap();
+    // Same trace, with the ingress span as the new parent
+    assert_eq!(forwarded.trace_id, incoming.trace_id);
+    assert_ne!(forwarded.parent_id, incoming.parent_id);
+    assert_eq!(forwarded.flags, incoming.flags);
+}
+
+#[tokio::test]
+async fn test_traceparent_created_when_absent() {
+    let (local_addr, mut seen) = start_capture_server().await;
+    let http_addr =
+        start_forwarding_tunnel("untraced", local_addr, ForwardingConfig::default()).await;
+
+    let response = make_client()
+        .get(format!("http://{http_addr}/"))
+        .header("Host", "untraced")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let headers = tokio::time::timeout(Duration::from_secs(5), seen.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(TraceParent::parse(headers["traceparent"].to_str().unwrap()).is_some());
+}
@@ -0,0 +1,93 @@
+//! Per-frame checksum integration tests
+
+use ferrotunnel_core::stream::Multiplexer;
+use ferrotunnel_core::transport::{MemoryTransport, TransportConfig};
+use ferrotunnel_core::tunnel::session::SessionStoreBackend;
+use ferrotunnel_core::{TunnelClient, TunnelServer};
+use ferrotunnel_protocol::constants::CHECKSUM_CAPABILITY;
+use ferrotunnel_protocol::frame::Protocol;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const TUNNEL_ID: &str = "checked";
+
+/// Run `server` and an echo client over an in-memory transport, returning
+/// the server's sessions
+fn start(server: TunnelServer, client: TunnelClient) -> SessionStoreBackend {
+    let sessions = server.sessions();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    let mut client = client.with_tunnel_id(TUNNEL_ID);
+    tokio::spawn(async move {
+        let _ = client
+            .connect_and_run(|mut stream| async move {
+                let mut buf = vec![0u8; 64 * 1024];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    if n == 0 || stream.write_all(&buf[..n]).await.is_err() {
+                        break;
+                    }
+                }
+            })
+            .await;
+    });
+    sessions
+}
+
+/// Multiplexer and granted capabilities of the session for `TUNNEL_ID`
+async fn wait_for_session(sessions: &SessionStoreBackend) -> (Multiplexer, Vec<String>) {
+    for _ in 0..50 {
+        if let Some(session) = sessions.get_by_tunnel_id(TUNNEL_ID) {
+            if let Some(multiplexer) = session.multiplexer.clone() {
+                return (multiplexer, session.capabilities.clone());
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("session not registered");
+}
+
+/// Send a payload through the tunnel and check it comes back intact
+async fn assert_echo(multiplexer: &Multiplexer) {
+    let payload: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    let mut stream = multiplexer.open_stream(Protocol::TCP).await.unwrap();
+    stream.write_all(&payload).await.unwrap();
+    let mut echoed = vec![0u8; payload.len()];
+    tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut echoed))
+        .await
+        .expect("echo timed out")
+        .unwrap();
+    assert_eq!(echoed, payload);
+}
+
+#[tokio::test]
+async fn test_checksum_negotiated() {
+    let transport = TransportConfig::Memory(MemoryTransport::new());
+    let server = TunnelServer::new("127.0.0.1:0".parse().unwrap(), "test-token".into())
+        .with_transport(transport.clone());
+    let client = TunnelClient::new("in-memory".into(), "test-token".into())
+        .with_transport(transport)
+        .with_frame_checksum();
+    let sessions = start(server, client);
+
+    let (multiplexer, capabilities) = wait_for_session(&sessions).await;
+    assert!(capabilities.iter().any(|cap| cap == CHECKSUM_CAPABILITY));
+    assert_echo(&multiplexer).await;
+}
+
+#[tokio::test]
+async fn test_checksum_refused_by_server() {
+    let transport = TransportConfig::Memory(MemoryTransport::new());
+    let server = TunnelServer::new("127.0.0.1:0".parse().unwrap(), "test-token".into())
+        .with_transport(transport.clone())
+        .with_capabilities(vec!["basic".into(), "tcp".into()]);
+    let client = TunnelClient::new("in-memory".into(), "test-token".into())
+        .with_transport(transport)
+        .with_frame_checksum();
+    let sessions = start(server, client);
+
+    // Both sides fall back to plain frames
+    let (multiplexer, capabilities) = wait_for_session(&sessions).await;
+    assert!(!capabilities.iter().any(|cap| cap == CHECKSUM_CAPABILITY));
+    assert_echo(&multiplexer).await;
+}
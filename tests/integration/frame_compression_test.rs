@@ -0,0 +1,158 @@
+//! Data frame compression negotiation integration tests
+
+use super::{get_free_port, wait_for_server};
+use ferrotunnel_core::{GrantedCapabilities, StreamOpener, TunnelClient, TunnelServer};
+use ferrotunnel_protocol::frame::Protocol;
+use ferrotunnel_protocol::FrameCompression;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Start a server whose client stream handler echoes everything back
+async fn start_echo_server(preference: Vec<FrameCompression>) -> SocketAddr {
+    let addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let server = TunnelServer::new(addr, "test-token".into())
+        .with_frame_compression(preference)
+        .on_client_stream(|_tunnel_id, stream| async move {
+            let (mut reader, mut writer) = tokio::io::split(stream);
+            let _ = tokio::io::copy(&mut reader, &mut writer).await;
+            let _ = writer.shutdown().await;
+        });
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    assert!(wait_for_server(addr, Duration::from_secs(5)).await);
+    addr
+}
+
+/// Forward connections to `target`, counting the bytes sent towards it
+async fn start_counting_relay(target: SocketAddr) -> (SocketAddr, Arc<AtomicU64>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let upstream_bytes = Arc::new(AtomicU64::new(0));
+
+    let counter = upstream_bytes.clone();
+    tokio::spawn(async move {
+        while let Ok((inbound, _)) = listener.accept().await {
+            let counter = counter.clone();
+            tokio::spawn(async move {
+                let outbound = TcpStream::connect(target).await.unwrap();
+                let (mut in_read, mut in_write) = inbound.into_split();
+                let (mut out_read, mut out_write) = outbound.into_split();
+                tokio::spawn(async move {
+                    let _ = tokio::io::copy(&mut out_read, &mut in_write).await;
+                });
+                let mut buf = vec![0u8; 16 * 1024];
+                while let Ok(n) = in_read.read(&mut buf).await {
+                    if n == 0 || out_write.write_all(&buf[..n]).await.is_err() {
+                        break;
+                    }
+                    counter.fetch_add(n as u64, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+
+    (addr, upstream_bytes)
+}
+
+/// Connect `client` and wait for its handshake to complete
+async fn connect(mut client: TunnelClient) -> (StreamOpener, GrantedCapabilities) {
+    let opener = client.stream_opener();
+    let granted = client.granted_capabilities_handle();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
+        let _ = client
+            .connect_and_run_with_callback(
+                |_stream| async {},
+                move |_session_id| {
+                    let _ = tx.send(());
+                },
+            )
+            .await;
+    });
+    tokio::time::timeout(Duration::from_secs(5), rx)
+        .await
+        .unwrap()
+        .unwrap();
+    (opener, granted)
+}
+
+/// Send `payload` through an echoed stream and check it comes back intact
+async fn echo(opener: &StreamOpener, payload: &[u8]) {
+    let mut stream = opener.open_stream(Protocol::TCP).await.unwrap();
+    stream.write_all(payload).await.unwrap();
+    let mut echoed = vec![0u8; payload.len()];
+    tokio::time::timeout(Duration::from_secs(10), stream.read_exact(&mut echoed))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(echoed, payload);
+}
+
+#[tokio::test]
+async fn test_each_algorithm_compresses_data_frames() {
+    let payload = "compressible tunnel payload ".repeat(8 * 1024).into_bytes();
+
+    for algorithm in FrameCompression::default_preference() {
+        let server_addr = start_echo_server(FrameCompression::default_preference()).await;
+        let (relay_addr, upstream_bytes) = start_counting_relay(server_addr).await;
+        let client = TunnelClient::new(relay_addr.to_string(), "test-token".into())
+            .with_frame_compression(&[algorithm]);
+        let (opener, granted) = connect(client).await;
+        assert!(
+            granted.contains(&algorithm.capability()),
+            "{}: {:?}",
+            algorithm.name(),
+            granted.get()
+        );
+
+        echo(&opener, &payload).await;
+        // Handshake and framing overhead is far below the savings
+        let sent = upstream_bytes.load(Ordering::Relaxed);
+        assert!(
+            sent < payload.len() as u64 / 4,
+            "{} sent {sent} bytes for a {}-byte payload",
+            algorithm.name(),
+            payload.len()
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_server_preference_picks_algorithm() {
+    // Latency over ratio: lz4 wins even though the client lists zstd first
+    let server_addr = start_echo_server(vec![
+        FrameCompression::Lz4,
+        FrameCompression::Zstd,
+        FrameCompression::Gzip,
+    ])
+    .await;
+    let client = TunnelClient::new(server_addr.to_string(), "test-token".into())
+        .with_frame_compression(&FrameCompression::default_preference());
+    let (opener, granted) = connect(client).await;
+
+    let compression: Vec<String> = granted
+        .get()
+        .into_iter()
+        .filter(|cap| cap.starts_with("comp:"))
+        .collect();
+    assert_eq!(compression, ["comp:lz4"]);
+    echo(&opener, b"hello").await;
+}
+
+#[tokio::test]
+async fn test_no_compression_unless_offered() {
+    let server_addr = start_echo_server(FrameCompression::default_preference()).await;
+    let (relay_addr, upstream_bytes) = start_counting_relay(server_addr).await;
+    let client = TunnelClient::new(relay_addr.to_string(), "test-token".into());
+    let (opener, granted) = connect(client).await;
+    assert!(!granted.get().iter().any(|cap| cap.starts_with("comp:")));
+
+    let payload = "x".repeat(64 * 1024).into_bytes();
+    echo(&opener, &payload).await;
+    assert!(upstream_bytes.load(Ordering::Relaxed) >= payload.len() as u64);
+}
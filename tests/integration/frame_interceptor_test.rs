@@ -0,0 +1,190 @@
+//! Frame interceptor integration tests
+
+use ferrotunnel_core::interceptor::FrameInterceptor;
+use ferrotunnel_core::recorder::{Direction, RecordingReader};
+use ferrotunnel_core::stream::Multiplexer;
+use ferrotunnel_core::transport::{MemoryTransport, TransportConfig};
+use ferrotunnel_core::tunnel::session::SessionStoreBackend;
+use ferrotunnel_core::{TunnelClient, TunnelServer};
+use ferrotunnel_protocol::frame::{Frame, Protocol};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const TUNNEL_ID: &str = "intercepted";
+
+/// Counts data and control frames in each direction
+#[derive(Debug, Default)]
+struct FrameCounter {
+    sent_data: AtomicUsize,
+    sent_control: AtomicUsize,
+    recv_data: AtomicUsize,
+    recv_control: AtomicUsize,
+}
+
+impl FrameCounter {
+    fn counts(&self) -> [usize; 4] {
+        [
+            self.sent_data.load(Ordering::SeqCst),
+            self.sent_control.load(Ordering::SeqCst),
+            self.recv_data.load(Ordering::SeqCst),
+            self.recv_control.load(Ordering::SeqCst),
+        ]
+    }
+}
+
+impl FrameInterceptor for FrameCounter {
+    fn on_send(&self, frame: &Frame) {
+        match frame {
+            Frame::Data { .. } => &self.sent_data,
+            _ => &self.sent_control,
+        }
+        .fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_recv(&self, frame: &Frame) {
+        match frame {
+            Frame::Data { .. } => &self.recv_data,
+            _ => &self.recv_control,
+        }
+        .fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn test_interceptors_count_frames_both_ways() {
+    let transport = TransportConfig::Memory(MemoryTransport::new());
+    let server_counter = Arc::new(FrameCounter::default());
+    let client_counter = Arc::new(FrameCounter::default());
+
+    let server = TunnelServer::new("127.0.0.1:0".parse().unwrap(), "test-token".into())
+        .with_transport(transport.clone())
+        .with_frame_interceptor(server_counter.clone());
+    let sessions = server.sessions();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+
+    let mut client = TunnelClient::new("in-memory".into(), "test-token".into())
+        .with_transport(transport)
+        .with_tunnel_id(TUNNEL_ID)
+        .with_frame_interceptor(client_counter.clone());
+    tokio::spawn(async move {
+        let _ = client
+            .connect_and_run(|mut stream| async move {
+                let mut buf = [0u8; 5];
+                if stream.read_exact(&mut buf).await.is_ok() {
+                    let _ = stream.write_all(&buf).await;
+                }
+            })
+            .await;
+    });
+
+    let multiplexer = wait_for_multiplexer(&sessions).await;
+
+    let mut stream = multiplexer.open_stream(Protocol::TCP).await.unwrap();
+    stream.write_all(b"hello").await.unwrap();
+    let mut reply = [0u8; 5];
+    tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut reply))
+        .await
+        .expect("echo timed out")
+        .unwrap();
+    assert_eq!(&reply, b"hello");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let [sent_data, sent_control, recv_data, recv_control] = server_counter.counts();
+    // HandshakeAck and OpenStream out, the client's heartbeat in
+    assert!(sent_control >= 2, "{:?}", server_counter);
+    assert!(recv_control >= 1, "{:?}", server_counter);
+    assert_eq!(sent_data, 1);
+    assert_eq!(recv_data, 1);
+
+    // The client sees the mirror image: one echo out, one payload in
+    let [sent_data, sent_control, recv_data, recv_control] = client_counter.counts();
+    assert!(sent_control >= 1, "{:?}", client_counter);
+    assert!(recv_control >= 1, "{:?}", client_counter);
+    assert_eq!(sent_data, 1);
+    assert_eq!(recv_data, 1);
+}
+
+/// Multiplexer of the session registered for `TUNNEL_ID`, once it appears
+async fn wait_for_multiplexer(sessions: &SessionStoreBackend) -> Multiplexer {
+    for _ in 0..50 {
+        if let Some(multiplexer) = sessions
+            .get_by_tunnel_id(TUNNEL_ID)
+            .and_then(|session| session.multiplexer.clone())
+        {
+            return multiplexer;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("session not registered");
+}
+
+#[tokio::test]
+async fn test_frame_recorder_captures_session() {
+    let transport = TransportConfig::Memory(MemoryTransport::new());
+    let path = std::env::temp_dir().join(format!("ferrotunnel-capture-{}", uuid::Uuid::new_v4()));
+
+    let server = TunnelServer::new("127.0.0.1:0".parse().unwrap(), "test-token".into())
+        .with_transport(transport.clone());
+    let sessions = server.sessions();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+
+    let mut client = TunnelClient::new("in-memory".into(), "test-token".into())
+        .with_transport(transport)
+        .with_tunnel_id(TUNNEL_ID)
+        .with_frame_recorder(&path)
+        .unwrap();
+    let recorder = client.frame_recorder().unwrap();
+    tokio::spawn(async move {
+        let _ = client
+            .connect_and_run(|mut stream| async move {
+                let mut buf = [0u8; 5];
+                if stream.read_exact(&mut buf).await.is_ok() {
+                    let _ = stream.write_all(&buf).await;
+                }
+            })
+            .await;
+    });
+
+    let multiplexer = wait_for_multiplexer(&sessions).await;
+    let mut stream = multiplexer.open_stream(Protocol::TCP).await.unwrap();
+    stream.write_all(b"hello").await.unwrap();
+    let mut reply = [0u8; 5];
+    tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut reply))
+        .await
+        .expect("echo timed out")
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    recorder.flush().unwrap();
+
+    let frames: Vec<(Direction, Frame)> = RecordingReader::open(&path)
+        .unwrap()
+        .map(|record| {
+            let record = record.unwrap();
+            (record.direction, record.frame)
+        })
+        .filter(|(_, frame)| !matches!(frame, Frame::Heartbeat { .. } | Frame::HeartbeatAck { .. }))
+        .collect();
+    let position = |direction: Direction, wanted: fn(&Frame) -> bool| {
+        frames
+            .iter()
+            .position(|(d, frame)| *d == direction && wanted(frame))
+            .unwrap_or_else(|| panic!("frame missing from capture: {frames:?}"))
+    };
+    let is_hello =
+        |frame: &Frame| matches!(frame, Frame::Data { data, .. } if data.as_ref() == b"hello");
+
+    // The stream is opened, its payload arrives and the echo goes back out
+    let opened = position(Direction::Received, |frame| {
+        matches!(frame, Frame::OpenStream(_))
+    });
+    let payload = position(Direction::Received, is_hello);
+    let echo = position(Direction::Sent, is_hello);
+    assert!(opened < payload && payload < echo, "{frames:?}");
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,94 @@
+//! Max frame size negotiation integration tests
+
+use ferrotunnel_core::interceptor::FrameInterceptor;
+use ferrotunnel_core::transport::{MemoryTransport, TransportConfig};
+use ferrotunnel_core::{TunnelClient, TunnelServer};
+use ferrotunnel_protocol::frame::{Frame, Protocol};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const TUNNEL_ID: &str = "frame-size";
+
+/// Payload bytes of a data frame besides the data: type, stream ID and flags
+const DATA_FRAME_HEADER_SIZE: usize = 6;
+
+/// Largest data frame payload sent
+#[derive(Debug, Default)]
+struct LargestDataFrame(AtomicUsize);
+
+impl FrameInterceptor for LargestDataFrame {
+    fn on_send(&self, frame: &Frame) {
+        if let Frame::Data { data, .. } = frame {
+            self.0.fetch_max(data.len(), Ordering::SeqCst);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_data_chunked_to_negotiated_max_frame_size() {
+    const CLIENT_MAX: u32 = 16 * 1024;
+    const PAYLOAD_LEN: usize = 200 * 1024;
+
+    let transport = TransportConfig::Memory(MemoryTransport::new());
+    let server_largest = Arc::new(LargestDataFrame::default());
+    let client_largest = Arc::new(LargestDataFrame::default());
+
+    // The server keeps the default 16MB limit; the client's is smaller
+    let server = TunnelServer::new("127.0.0.1:0".parse().unwrap(), "test-token".into())
+        .with_transport(transport.clone())
+        .with_frame_interceptor(server_largest.clone());
+    let sessions = server.sessions();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+
+    let mut client = TunnelClient::new("in-memory".into(), "test-token".into())
+        .with_transport(transport)
+        .with_tunnel_id(TUNNEL_ID)
+        .with_max_frame_size(CLIENT_MAX)
+        .unwrap()
+        .with_frame_interceptor(client_largest.clone());
+    tokio::spawn(async move {
+        let _ = client
+            .connect_and_run(|mut stream| async move {
+                let mut buf = vec![0u8; PAYLOAD_LEN];
+                if stream.read_exact(&mut buf).await.is_ok() {
+                    let _ = stream.write_all(&buf).await;
+                }
+            })
+            .await;
+    });
+
+    let mut multiplexer = None;
+    for _ in 0..50 {
+        multiplexer = sessions
+            .get_by_tunnel_id(TUNNEL_ID)
+            .and_then(|session| session.multiplexer.clone());
+        if multiplexer.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let multiplexer = multiplexer.expect("session not registered");
+    let max_payload = CLIENT_MAX as usize - DATA_FRAME_HEADER_SIZE;
+    assert_eq!(multiplexer.max_data_payload(), max_payload);
+
+    // Larger than a default 64KB data frame, so the limit drives chunking
+    let payload: Vec<u8> = (0..PAYLOAD_LEN)
+        .map(|i| u8::try_from(i % 251).unwrap())
+        .collect();
+    let mut stream = multiplexer.open_stream(Protocol::TCP).await.unwrap();
+    stream.write_all(&payload).await.unwrap();
+    let mut echoed = vec![0u8; PAYLOAD_LEN];
+    tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut echoed))
+        .await
+        .expect("echo timed out")
+        .unwrap();
+    assert_eq!(echoed, payload);
+
+    // Neither side sent a data frame the client would reject
+    assert_eq!(server_largest.0.load(Ordering::SeqCst), max_payload);
+    assert_eq!(client_largest.0.load(Ordering::SeqCst), max_payload);
+}
@@ -23,6 +23,9 @@ use tokio::net::TcpListener;
 /// - `content-type: application/grpc`
 /// - body: empty gRPC message frame (5-byte prefix + 0 bytes)
 /// - HTTP/2 trailers: `grpc-status: 0`
+///
+/// Requests for `/test.EchoService/Missing` instead stream two empty messages
+/// and end with `grpc-status: 5` and a `grpc-message` trailer.
 async fn start_grpc_server(addr: SocketAddr) -> tokio::task::JoinHandle<()> {
     let listener = TcpListener::bind(addr)
         .await
@@ -37,18 +40,24 @@ async fn start_grpc_server(addr: SocketAddr) -> tokio::task::JoinHandle<()> {
                         .serve_connection(
                             io,
                             hyper::service::service_fn(
-                                |_req: Request<hyper::body::Incoming>| async {
+                                |req: Request<hyper::body::Incoming>| async move {
                                     // Minimal 5-byte gRPC message frame for an empty message
                                     let grpc_frame = Bytes::from_static(b"\x00\x00\x00\x00\x00");
                                     let mut trailers = hyper::HeaderMap::new();
-                                    trailers.insert(
-                                        hyper::header::HeaderName::from_static("grpc-status"),
-                                        hyper::header::HeaderValue::from_static("0"),
-                                    );
-                                    let frames = vec![
-                                        Ok::<Frame<Bytes>, hyper::Error>(Frame::data(grpc_frame)),
-                                        Ok(Frame::trailers(trailers)),
-                                    ];
+                                    let mut frames = vec![Ok::<Frame<Bytes>, hyper::Error>(
+                                        Frame::data(grpc_frame.clone()),
+                                    )];
+                                    if req.uri().path() == "/test.EchoService/Missing" {
+                                        frames.push(Ok(Frame::data(grpc_frame)));
+                                        trailers.insert("grpc-status", "5".parse().unwrap());
+                                        trailers.insert(
+                                            "grpc-message",
+                                            "user%20not%20found".parse().unwrap(),
+                                        );
+                                    } else {
+                                        trailers.insert("grpc-status", "0".parse().unwrap());
+                                    }
+                                    frames.push(Ok(Frame::trailers(trailers)));
                                     let resp = hyper::Response::builder()
                                         .status(200)
                                         .header("content-type", "application/grpc")
@@ -288,3 +297,96 @@ async fn test_non_grpc_not_classified_as_grpc() {
         "Expected text/plain, not gRPC content-type"
     );
 }
+
+/// Response head, data frames and trailers of a gRPC call
+struct GrpcReply {
+    parts: http::response::Parts,
+    messages: Vec<Bytes>,
+    trailers: Option<hyper::HeaderMap>,
+}
+
+/// Make a unary gRPC call to `path` through the ingress over h2c
+async fn grpc_call(http_addr: SocketAddr, host: &str, path: &str) -> GrpcReply {
+    let tcp = tokio::net::TcpStream::connect(http_addr)
+        .await
+        .expect("Failed to connect to HTTP ingress");
+    let (mut sender, conn) =
+        hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(tcp))
+            .await
+            .expect("HTTP/2 handshake with ingress failed");
+    tokio::spawn(async move {
+        let _ = conn.await;
+    });
+
+    let req = Request::builder()
+        .method("POST")
+        .uri(path)
+        .header("content-type", "application/grpc")
+        .header("te", "trailers")
+        .header("host", host)
+        .body(Full::new(Bytes::from_static(b"\x00\x00\x00\x00\x00")))
+        .unwrap();
+    let (parts, mut body) = sender.send_request(req).await.unwrap().into_parts();
+
+    let mut messages = Vec::new();
+    let mut trailers = None;
+    while let Some(frame) = body.frame().await {
+        match frame.unwrap().into_data() {
+            Ok(data) => messages.push(data),
+            Err(frame) => trailers = frame.into_trailers().ok(),
+        }
+    }
+    GrpcReply {
+        parts,
+        messages,
+        trailers,
+    }
+}
+
+/// Verifies that every streamed message and the `grpc-status` and
+/// `grpc-message` trailers of a failed call reach the client unchanged.
+#[tokio::test]
+async fn test_grpc_error_trailers_forwarded() {
+    let server_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let http_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let local_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let _grpc_handle = start_grpc_server(local_addr).await;
+
+    let mut server = Server::builder()
+        .bind(server_addr)
+        .http_bind(http_addr)
+        .token("test-secret-token")
+        .build()
+        .expect("Failed to build server");
+    let _server_handle = tokio::spawn(async move {
+        let _ = server.start().await;
+    });
+    assert!(wait_for_server(server_addr, Duration::from_secs(5)).await);
+
+    let mut client = Client::builder()
+        .server_addr(server_addr.to_string())
+        .token("test-secret-token")
+        .local_addr(local_addr.to_string())
+        .tunnel_id("grpc-errors")
+        .build()
+        .expect("Failed to build client");
+    // Returns once the server has registered the tunnel
+    client.start().await.expect("Client failed to connect");
+
+    let reply = grpc_call(http_addr, "grpc-errors", "/test.EchoService/Missing").await;
+    assert_eq!(reply.parts.status, StatusCode::OK);
+    assert_eq!(reply.messages.concat().len(), 10, "both messages streamed");
+    let trailers = reply.trailers.expect("missing trailers");
+    assert_eq!(trailers["grpc-status"], "5");
+    assert_eq!(trailers["grpc-message"], "user%20not%20found");
+
+    // Ingress errors become trailers-only gRPC responses
+    let reply = grpc_call(http_addr, "no-such-tunnel", "/test.EchoService/Echo").await;
+    assert_eq!(reply.parts.status, StatusCode::OK);
+    assert_eq!(reply.parts.headers["content-type"], "application/grpc");
+    assert_eq!(reply.parts.headers["grpc-status"], "12");
+    assert_eq!(reply.parts.headers["grpc-message"], "Tunnel not found");
+    assert!(reply.messages.iter().all(Bytes::is_empty));
+
+    let _ = client.shutdown().await;
+}
@@ -0,0 +1,151 @@
+//! Handshake outcome metrics integration tests
+//!
+//! Metrics are process-wide and other tests handshake concurrently, so each
+//! test checks that its outcome's counter grew rather than exact values.
+
+use super::{get_free_port, wait_for_server};
+use ferrotunnel_core::{TunnelClient, TunnelServer};
+use ferrotunnel_observability::{init_metrics, tunnel_metrics, TunnelMetrics};
+use ferrotunnel_protocol::frame::{Frame, HandshakeFrame, HandshakeStatus};
+use ferrotunnel_protocol::TunnelCodec;
+use futures_util::{SinkExt, StreamExt};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
+
+fn metrics() -> &'static TunnelMetrics {
+    init_metrics();
+    tunnel_metrics().unwrap()
+}
+
+async fn start_server() -> SocketAddr {
+    let addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let server = TunnelServer::new(addr, "test-token".into());
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    assert!(wait_for_server(addr, Duration::from_secs(5)).await);
+    addr
+}
+
+/// Send a raw handshake and return the frame the server answers with
+async fn raw_handshake(addr: SocketAddr, token: &str, min_version: u8, max_version: u8) -> Frame {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, TunnelCodec::new());
+    framed
+        .send(Frame::Handshake(Box::new(HandshakeFrame {
+            token: token.into(),
+            tunnel_id: None,
+            min_version,
+            max_version,
+            capabilities: vec!["basic".into()],
+        })))
+        .await
+        .unwrap();
+    tokio::time::timeout(Duration::from_secs(5), framed.next())
+        .await
+        .expect("no handshake ack")
+        .unwrap()
+        .unwrap()
+}
+
+/// Wait up to 2s for `check` to hold; metrics are recorded just after the
+/// handshake ack is sent
+async fn eventually(check: impl Fn() -> bool) -> bool {
+    for _ in 0..100 {
+        if check() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    false
+}
+
+/// Wait until the `status` handshake counter exceeds `before`
+async fn counter_grew(status: &str, before: f64) -> bool {
+    eventually(|| metrics().handshakes(status) > before).await
+}
+
+/// Connect a client with `tunnel_id` and keep it running in the background
+async fn connect_client(addr: SocketAddr, tunnel_id: &str) {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let mut client =
+        TunnelClient::new(addr.to_string(), "test-token".into()).with_tunnel_id(tunnel_id);
+    tokio::spawn(async move {
+        let _ = client
+            .connect_and_run_with_callback(
+                |_stream| async {},
+                move |_session_id| {
+                    let _ = tx.send(());
+                },
+            )
+            .await;
+    });
+    tokio::time::timeout(Duration::from_secs(5), rx)
+        .await
+        .expect("client did not connect")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_successful_handshake_counted() {
+    let addr = start_server().await;
+    let before = metrics().handshakes("success");
+    let connects_before = metrics().client_connects();
+
+    connect_client(addr, "metrics-success").await;
+
+    assert!(counter_grew("success", before).await);
+    assert!(eventually(|| metrics().client_connects() > connects_before).await);
+}
+
+#[tokio::test]
+async fn test_invalid_token_counted() {
+    let addr = start_server().await;
+    let before = metrics().handshakes("invalid_token");
+
+    let ack = raw_handshake(addr, "wrong-token", 1, 1).await;
+    assert!(matches!(
+        ack,
+        Frame::HandshakeAck {
+            status: HandshakeStatus::InvalidToken,
+            ..
+        }
+    ));
+    assert!(counter_grew("invalid_token", before).await);
+}
+
+#[tokio::test]
+async fn test_version_mismatch_counted() {
+    let addr = start_server().await;
+    let before = metrics().handshakes("version_mismatch");
+
+    let ack = raw_handshake(addr, "test-token", 200, 250).await;
+    assert!(matches!(
+        ack,
+        Frame::HandshakeAck {
+            status: HandshakeStatus::VersionMismatch,
+            ..
+        }
+    ));
+    assert!(counter_grew("version_mismatch", before).await);
+}
+
+#[tokio::test]
+async fn test_tunnel_taken_counted() {
+    let addr = start_server().await;
+    connect_client(addr, "metrics-taken").await;
+    let before = metrics().handshakes("tunnel_taken");
+
+    let mut second =
+        TunnelClient::new(addr.to_string(), "test-token".into()).with_tunnel_id("metrics-taken");
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        second.connect_and_run(|_stream| async {}),
+    )
+    .await
+    .expect("second client was not rejected");
+    assert!(result.is_err());
+    assert!(counter_grew("tunnel_taken", before).await);
+}
@@ -0,0 +1,106 @@
+//! HTTP/2 control transport integration tests
+
+use super::{
+    get_free_port, make_client, start_echo_server, start_ingress, start_tunnel_server,
+    wait_for_tunnel, TUNNEL_TOKEN,
+};
+use ferrotunnel_core::transport::http2::Http2TransportConfig;
+use ferrotunnel_core::transport::TransportConfig;
+use ferrotunnel_core::TunnelClient;
+use ferrotunnel_http::{HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+const TUNNEL_ID: &str = "over-h2";
+
+#[tokio::test]
+async fn test_session_and_heartbeats_over_http2() {
+    let local_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let _local = start_echo_server(local_addr).await;
+
+    let transport = TransportConfig::Http2(Http2TransportConfig::default());
+    let (server_addr, sessions) =
+        start_tunnel_server(|server| server.with_transport(transport.clone())).await;
+    let http_addr = start_ingress(
+        sessions.clone(),
+        PluginRegistry::new(),
+        IngressConfig::default(),
+    )
+    .await;
+
+    let proxy = Arc::new(HttpProxy::new(local_addr.to_string()));
+    let mut client = TunnelClient::new(server_addr.to_string(), TUNNEL_TOKEN.into())
+        .with_transport(transport)
+        .with_tunnel_id(TUNNEL_ID)
+        .with_heartbeat_interval(Duration::from_millis(100))
+        .unwrap()
+        .with_heartbeat_timeout(Duration::from_millis(500));
+    tokio::spawn(async move {
+        let _ = client
+            .connect_and_run(move |stream| {
+                let proxy = proxy.clone();
+                async move { proxy.handle_stream(stream) }
+            })
+            .await;
+    });
+
+    // Handshake completes over the HTTP/2 stream
+    let mut registered = None;
+    for _ in 0..50 {
+        if let Some(session) = sessions.get_by_tunnel_id(TUNNEL_ID) {
+            registered = Some((session.id, session.last_heartbeat));
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let (session_id, first_heartbeat) = registered.expect("session not registered over HTTP/2");
+
+    // Several heartbeat intervals later the server has seen heartbeats and the
+    // client has received acks: otherwise its timeout would have dropped the
+    // session and reconnected under a new ID.
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    {
+        let session = sessions
+            .get_by_tunnel_id(TUNNEL_ID)
+            .expect("session dropped");
+        assert_eq!(session.id, session_id);
+        assert!(session.last_heartbeat > first_heartbeat);
+    }
+
+    // Streams are multiplexed over the same transport
+    let response = make_client()
+        .get(format!("http://{http_addr}/"))
+        .header("Host", TUNNEL_ID)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "Hello, World!");
+}
+
+#[tokio::test]
+async fn test_stalled_handshake_does_not_block_accepts() {
+    let transport = TransportConfig::Http2(Http2TransportConfig::default());
+    let (server_addr, sessions) = start_tunnel_server(|server| {
+        server
+            .with_transport(transport.clone())
+            .with_transport_handshake_timeout(Duration::from_secs(30))
+    })
+    .await;
+
+    // Connects but never sends the HTTP/2 preface
+    let _stalled = tokio::net::TcpStream::connect(server_addr).await.unwrap();
+
+    let mut client = TunnelClient::new(server_addr.to_string(), TUNNEL_TOKEN.into())
+        .with_transport(transport)
+        .with_tunnel_id(TUNNEL_ID);
+    tokio::spawn(async move {
+        let _ = client.connect_and_run(|_stream| async move {}).await;
+    });
+    assert!(
+        wait_for_tunnel(&sessions, TUNNEL_ID, Duration::from_secs(5)).await,
+        "client behind a stalled handshake was not accepted"
+    );
+}
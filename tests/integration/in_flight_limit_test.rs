@@ -0,0 +1,132 @@
+//! Per-tunnel in-flight request limit integration tests
+
+use super::{connect_proxy_tunnel, make_client, start_ingress, start_tunnel_server};
+use async_trait::async_trait;
+use ferrotunnel_core::auth::{AuthGrant, AuthResult, Authenticator};
+use ferrotunnel_core::tunnel::session::SessionStoreBackend;
+use ferrotunnel_http::{HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use futures_util::future::join_all;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const SLOW_RESPONSE: Duration = Duration::from_secs(1);
+
+/// Local service that takes `SLOW_RESPONSE` to answer each request
+async fn start_slow_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 4096];
+                if socket.read(&mut buf).await.unwrap_or(0) > 0 {
+                    tokio::time::sleep(SLOW_RESPONSE).await;
+                    let response = "HTTP/1.1 200 OK\r\n\
+                         Content-Length: 4\r\n\
+                         Connection: close\r\n\
+                         \r\n\
+                         done";
+                    let _ = socket.write_all(response.as_bytes()).await;
+                }
+            });
+        }
+    });
+    addr
+}
+
+/// Grants tunnel `roomy` a limit of 4, leaving other tunnels on the default
+struct RoomyAuthenticator;
+
+#[async_trait]
+impl Authenticator for RoomyAuthenticator {
+    async fn authenticate(
+        &self,
+        _token: &str,
+        tunnel_id: Option<&str>,
+        _peer: SocketAddr,
+    ) -> AuthResult {
+        if tunnel_id == Some("roomy") {
+            AuthResult::Granted(AuthGrant::default().with_max_in_flight_requests(4))
+        } else {
+            AuthResult::granted()
+        }
+    }
+}
+
+/// Start a tunnel server with a client per tunnel ID, all proxying to the
+/// slow service
+async fn start_tunnels(tunnel_ids: &[&str]) -> SessionStoreBackend {
+    let (server_addr, sessions) =
+        start_tunnel_server(|server| server.with_authenticator(RoomyAuthenticator)).await;
+
+    let proxy = Arc::new(HttpProxy::new(start_slow_server().await));
+    for tunnel_id in tunnel_ids {
+        connect_proxy_tunnel(server_addr, &sessions, tunnel_id, proxy.clone()).await;
+    }
+    sessions
+}
+
+/// Start an HTTP ingress allowing `limit` requests in flight per tunnel
+async fn start_limited_ingress(sessions: SessionStoreBackend, limit: usize) -> SocketAddr {
+    let config = IngressConfig {
+        max_in_flight_per_tunnel: Some(limit),
+        ..Default::default()
+    };
+    start_ingress(sessions, PluginRegistry::new(), config).await
+}
+
+/// Send `count` concurrent requests for `host` and return their statuses
+async fn fire(http_addr: SocketAddr, host: &str, count: usize) -> Vec<u16> {
+    let client = make_client();
+    let requests = (0..count).map(|_| {
+        let client = client.clone();
+        async move {
+            client
+                .get(format!("http://{http_addr}/"))
+                .header("Host", host)
+                .send()
+                .await
+                .unwrap()
+                .status()
+                .as_u16()
+        }
+    });
+    join_all(requests).await
+}
+
+fn count(statuses: &[u16], status: u16) -> usize {
+    statuses.iter().filter(|s| **s == status).count()
+}
+
+#[tokio::test]
+async fn test_excess_requests_rejected_per_tunnel() {
+    let sessions = start_tunnels(&["busy", "quiet"]).await;
+    let http_addr = start_limited_ingress(sessions, 2).await;
+
+    let (busy, quiet) = tokio::join!(fire(http_addr, "busy", 5), async {
+        // Arrive while `busy` is saturated
+        tokio::time::sleep(SLOW_RESPONSE / 4).await;
+        fire(http_addr, "quiet", 2).await
+    });
+    assert_eq!(count(&busy, 200), 2, "{busy:?}");
+    assert_eq!(count(&busy, 503), 3, "{busy:?}");
+    assert_eq!(quiet, vec![200, 200]);
+
+    // Permits are returned once responses complete
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(fire(http_addr, "busy", 2).await, vec![200, 200]);
+}
+
+#[tokio::test]
+async fn test_tunnel_limit_from_registration() {
+    let sessions = start_tunnels(&["roomy"]).await;
+    let http_addr = start_limited_ingress(sessions, 2).await;
+
+    let statuses = fire(http_addr, "roomy", 6).await;
+    assert_eq!(count(&statuses, 200), 4, "{statuses:?}");
+    assert_eq!(count(&statuses, 503), 2, "{statuses:?}");
+}
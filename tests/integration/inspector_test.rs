@@ -0,0 +1,72 @@
+//! Traffic inspector integration tests
+
+use super::{get_free_port, make_client, start_echo_server, start_tunnel};
+use async_trait::async_trait;
+use ferrotunnel_http::{HttpProxy, IngressConfig, RequestParts, ResponseParts, TrafficInspector};
+use ferrotunnel_plugin::PluginRegistry;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const TUNNEL_ID: &str = "inspected";
+
+#[derive(Default)]
+struct CountingInspector {
+    requests: AtomicUsize,
+    responses: AtomicUsize,
+    paths: Mutex<Vec<String>>,
+    statuses: Mutex<Vec<u16>>,
+}
+
+#[async_trait]
+impl TrafficInspector for CountingInspector {
+    async fn on_request(&self, request: &RequestParts) {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        self.paths
+            .lock()
+            .unwrap()
+            .push(request.uri.path().to_string());
+    }
+
+    async fn on_response(&self, response: &ResponseParts, _duration: Duration) {
+        self.responses.fetch_add(1, Ordering::SeqCst);
+        self.statuses.lock().unwrap().push(response.status.as_u16());
+    }
+}
+
+#[tokio::test]
+async fn test_inspector_counts_requests() {
+    let local_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let _local = start_echo_server(local_addr).await;
+
+    let inspector = Arc::new(CountingInspector::default());
+    let proxy = HttpProxy::new(local_addr.to_string()).with_inspector(inspector.clone());
+    let http_addr = start_tunnel(
+        TUNNEL_ID,
+        proxy,
+        PluginRegistry::new(),
+        IngressConfig::default(),
+    )
+    .await;
+
+    let http = make_client();
+    for i in 0..3 {
+        let response = http
+            .get(format!("http://{http_addr}/item/{i}"))
+            .header("Host", TUNNEL_ID)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.text().await.unwrap(), "Hello, World!");
+    }
+
+    assert_eq!(inspector.requests.load(Ordering::SeqCst), 3);
+    assert_eq!(inspector.responses.load(Ordering::SeqCst), 3);
+    assert_eq!(
+        *inspector.paths.lock().unwrap(),
+        vec!["/item/0", "/item/1", "/item/2"]
+    );
+    assert_eq!(*inspector.statuses.lock().unwrap(), vec![200, 200, 200]);
+}
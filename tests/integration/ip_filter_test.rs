@@ -0,0 +1,66 @@
+//! Control-plane IP filter integration tests
+
+use super::{start_tunnel_server, TUNNEL_TOKEN};
+use ferrotunnel_core::ip_filter::IpFilter;
+use ferrotunnel_core::TunnelClient;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+
+/// Start a server on a free port with `filter` and return its address
+async fn start_filtered_server(filter: IpFilter) -> String {
+    let (addr, _) = start_tunnel_server(|server| server.with_ip_filter(filter)).await;
+    addr.to_string()
+}
+
+/// Connect a tunnel client and report whether the handshake succeeded
+async fn handshake_succeeds(server_addr: String) -> bool {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let mut client = TunnelClient::new(server_addr, TUNNEL_TOKEN.into());
+    let handle = tokio::spawn(async move {
+        client
+            .connect_and_run_with_callback(
+                |_stream| async {},
+                move |_session_id| {
+                    let _ = tx.send(());
+                },
+            )
+            .await
+    });
+    let connected = tokio::time::timeout(Duration::from_secs(5), rx)
+        .await
+        .is_ok_and(|r| r.is_ok());
+    handle.abort();
+    connected
+}
+
+#[tokio::test]
+async fn test_denied_cidr_dropped_before_handshake() {
+    let filter = IpFilter::from_lists(Vec::<&str>::new(), ["127.0.0.0/8"]).unwrap();
+    let addr = start_filtered_server(filter).await;
+
+    // The TCP accept completes, but the server closes the socket without
+    // waiting for (or answering) a handshake
+    let mut conn = TcpStream::connect(&addr).await.unwrap();
+    let mut buf = [0u8; 16];
+    let read = tokio::time::timeout(Duration::from_secs(2), conn.read(&mut buf))
+        .await
+        .expect("denied connection was not closed");
+    assert!(matches!(read, Ok(0) | Err(_)), "{read:?}");
+
+    assert!(!handshake_succeeds(addr).await);
+}
+
+#[tokio::test]
+async fn test_allowed_cidr_proceeds() {
+    let filter = IpFilter::from_lists(["127.0.0.1/32", "::1"], ["10.0.0.0/8"]).unwrap();
+    let addr = start_filtered_server(filter).await;
+    assert!(handshake_succeeds(addr).await);
+}
+
+#[tokio::test]
+async fn test_peer_outside_allow_list_dropped() {
+    let filter = IpFilter::from_lists(["10.0.0.0/8", "2001:db8::/32"], Vec::<&str>::new()).unwrap();
+    let addr = start_filtered_server(filter).await;
+    assert!(!handshake_succeeds(addr).await);
+}
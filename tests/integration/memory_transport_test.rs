@@ -0,0 +1,102 @@
+//! In-memory transport integration tests
+
+use ferrotunnel_core::stream::Multiplexer;
+use ferrotunnel_core::transport::{MemoryTransport, TransportConfig};
+use ferrotunnel_core::tunnel::session::SessionStoreBackend;
+use ferrotunnel_core::{TunnelClient, TunnelServer};
+use ferrotunnel_protocol::frame::Protocol;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const TUNNEL_ID: &str = "in-memory";
+
+/// Start a server and an echoing client over one in-memory transport and
+/// return the session store once the tunnel has registered
+async fn start_echo_tunnel() -> SessionStoreBackend {
+    let transport = TransportConfig::Memory(MemoryTransport::new());
+
+    // The address is never bound with the in-memory transport
+    let server = TunnelServer::new("127.0.0.1:0".parse().unwrap(), "test-token".into())
+        .with_transport(transport.clone());
+    let sessions = server.sessions();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+
+    let mut client = TunnelClient::new("in-memory".into(), "test-token".into())
+        .with_transport(transport)
+        .with_tunnel_id(TUNNEL_ID);
+    tokio::spawn(async move {
+        let _ = client
+            .connect_and_run(|mut stream| async move {
+                // Echo everything back
+                let mut buf = vec![0u8; 1024];
+                loop {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => {
+                            if stream.write_all(&buf[..n]).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+            })
+            .await;
+    });
+
+    for _ in 0..50 {
+        if sessions.get_by_tunnel_id(TUNNEL_ID).is_some() {
+            return sessions;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("session not registered in memory");
+}
+
+fn multiplexer(sessions: &SessionStoreBackend) -> Multiplexer {
+    sessions
+        .get_by_tunnel_id(TUNNEL_ID)
+        .and_then(|session| session.multiplexer.clone())
+        .expect("session has no multiplexer")
+}
+
+#[tokio::test]
+async fn test_handshake_and_round_trip_in_memory() {
+    let sessions = start_echo_tunnel().await;
+    let multiplexer = multiplexer(&sessions);
+
+    let mut stream = multiplexer.open_stream(Protocol::TCP).await.unwrap();
+    stream.write_all(b"hello over a pipe").await.unwrap();
+    let mut reply = [0u8; 17];
+    tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut reply))
+        .await
+        .expect("echo timed out")
+        .unwrap();
+    assert_eq!(&reply, b"hello over a pipe");
+}
+
+#[tokio::test]
+async fn test_session_counts_stream_bytes() {
+    let sessions = start_echo_tunnel().await;
+    let multiplexer = multiplexer(&sessions);
+
+    // Several frames' worth, so large writes are chunked
+    let payload = vec![42u8; 200 * 1024];
+    let stream = multiplexer.open_stream(Protocol::TCP).await.unwrap();
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let write_payload = payload.clone();
+    let writer_task = tokio::spawn(async move { writer.write_all(&write_payload).await });
+    let mut echoed = vec![0u8; payload.len()];
+    tokio::time::timeout(Duration::from_secs(5), reader.read_exact(&mut echoed))
+        .await
+        .expect("echo timed out")
+        .unwrap();
+    writer_task.await.unwrap().unwrap();
+    assert_eq!(echoed, payload);
+
+    let session = sessions.get_by_tunnel_id(TUNNEL_ID).unwrap();
+    let expected = payload.len() as u64;
+    assert_eq!(session.traffic.bytes_out(), expected);
+    assert_eq!(session.traffic.bytes_in(), expected);
+}
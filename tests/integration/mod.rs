@@ -4,20 +4,72 @@
 //!
 //! These tests verify end-to-end functionality of the tunnel system.
 
+mod access_log_test;
+mod admin_api_test;
+mod auth_test;
+mod backpressure_test;
+mod base_domain_test;
+mod body_limit_test;
+mod challenge_auth_test;
+mod circuit_breaker_test;
+mod client_stream_test;
+mod compression_test;
 mod concurrent_test;
+mod connection_limit_test;
+mod dual_stack_test;
+mod error_pages_test;
 mod error_test;
+mod forwarding_test;
+mod frame_checksum_test;
+mod frame_compression_test;
+mod frame_interceptor_test;
+mod frame_size_test;
 mod grpc_test;
+mod handshake_metrics_test;
+mod http2_transport_test;
+mod in_flight_limit_test;
+mod inspector_test;
+mod ip_filter_test;
+mod memory_transport_test;
 mod multi_client_test;
+mod path_rules_test;
 mod plugin_test;
+mod proxy_fallback_test;
+mod proxy_protocol_test;
+mod reconnect_test;
+mod request_body_plugin_test;
+mod response_cache_test;
+mod response_timeout_test;
+#[cfg(target_os = "linux")]
+mod reuse_port_test;
+mod shutdown_test;
+mod sse_test;
+mod stream_priority_test;
 mod tcp_test;
 mod tls_test;
+mod truncated_response_test;
+mod truncated_upload_test;
+mod tunnel_pool_test;
 mod tunnel_test;
+mod tunnel_usage_metrics_test;
+mod udp_test;
 mod websocket_test;
 
+use async_trait::async_trait;
+use ferrotunnel_core::stream::VirtualStream;
+use ferrotunnel_core::tunnel::session::SessionStoreBackend;
+use ferrotunnel_core::{TunnelClient, TunnelServer};
+use ferrotunnel_http::{HttpIngress, HttpProxy, IngressConfig};
+use ferrotunnel_plugin::{Plugin, PluginAction, PluginRegistry, ResponseContext};
+use std::future::Future;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
 
+/// Token shared by the tunnel servers and clients of [`start_tunnel`]
+pub const TUNNEL_TOKEN: &str = "test-token";
+
 /// Test configuration with high ports to avoid conflicts
 pub struct TestConfig {
     pub server_addr: SocketAddr,
@@ -65,6 +117,164 @@ pub async fn wait_for_server(addr: SocketAddr, timeout: Duration) -> bool {
     false
 }
 
+/// Wait until a session for `tunnel_id` is registered in `sessions`
+pub async fn wait_for_tunnel(
+    sessions: &SessionStoreBackend,
+    tunnel_id: &str,
+    timeout: Duration,
+) -> bool {
+    let start = std::time::Instant::now();
+    while start.elapsed() < timeout {
+        if sessions.get_by_tunnel_id(tunnel_id).is_some() {
+            return true;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    false
+}
+
+/// Start a tunnel server on a free port, returning its address and sessions
+///
+/// `configure` adjusts the server before it runs, e.g. to add an
+/// authenticator.
+pub async fn start_tunnel_server(
+    configure: impl FnOnce(TunnelServer) -> TunnelServer,
+) -> (SocketAddr, SessionStoreBackend) {
+    let server_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let server = configure(TunnelServer::new(server_addr, TUNNEL_TOKEN.into()));
+    let sessions = server.sessions();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    assert!(wait_for_server(server_addr, Duration::from_secs(5)).await);
+    (server_addr, sessions)
+}
+
+/// Start an HTTP ingress on a free port routing to `sessions`, returning its
+/// address
+pub async fn start_ingress(
+    sessions: SessionStoreBackend,
+    registry: impl Into<Arc<PluginRegistry>>,
+    config: IngressConfig,
+) -> SocketAddr {
+    let http_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let ingress = HttpIngress::with_config(http_addr, sessions, registry.into(), config);
+    tokio::spawn(async move {
+        let _ = ingress.start().await;
+    });
+    assert!(wait_for_server(http_addr, Duration::from_secs(5)).await);
+    http_addr
+}
+
+/// Connect `client` to the server owning `sessions`, handing each stream to
+/// `handler`, and wait until the server has registered it as `tunnel_id`
+pub async fn connect_tunnel<F, Fut>(
+    sessions: &SessionStoreBackend,
+    tunnel_id: &str,
+    mut client: TunnelClient,
+    handler: F,
+) where
+    F: Fn(VirtualStream) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let _ = client.connect_and_run(handler).await;
+    });
+    assert!(
+        wait_for_tunnel(sessions, tunnel_id, Duration::from_secs(5)).await,
+        "tunnel {tunnel_id} did not register"
+    );
+}
+
+/// Connect a client for `tunnel_id` to the server at `server_addr`, proxying
+/// its streams through `proxy`
+pub async fn connect_proxy_tunnel(
+    server_addr: SocketAddr,
+    sessions: &SessionStoreBackend,
+    tunnel_id: &str,
+    proxy: Arc<HttpProxy<tower::layer::util::Identity>>,
+) {
+    let client =
+        TunnelClient::new(server_addr.to_string(), TUNNEL_TOKEN.into()).with_tunnel_id(tunnel_id);
+    connect_tunnel(sessions, tunnel_id, client, move |stream| {
+        let proxy = proxy.clone();
+        async move { proxy.handle_stream(stream) }
+    })
+    .await;
+}
+
+/// Start a tunnel server, an HTTP ingress running `registry` with `config`
+/// and a client for `tunnel_id` forwarding through `proxy`
+///
+/// Returns the ingress address once the tunnel is registered, so requests
+/// sent to it are routed.
+pub async fn start_tunnel(
+    tunnel_id: &str,
+    proxy: HttpProxy<tower::layer::util::Identity>,
+    registry: impl Into<Arc<PluginRegistry>>,
+    config: IngressConfig,
+) -> SocketAddr {
+    let (server_addr, sessions) = start_tunnel_server(|server| server).await;
+    let http_addr = start_ingress(sessions.clone(), registry, config).await;
+    connect_proxy_tunnel(server_addr, &sessions, tunnel_id, Arc::new(proxy)).await;
+    http_addr
+}
+
+/// Plugin asking for buffered response bodies, as a dashboard or body
+/// logger would, that tags the responses it sees with `x-plugin-seen`
+pub struct BufferingPlugin;
+
+#[async_trait]
+impl Plugin for BufferingPlugin {
+    fn name(&self) -> &str {
+        "buffering"
+    }
+
+    fn needs_response_body(&self) -> bool {
+        true
+    }
+
+    async fn on_response(
+        &self,
+        res: &mut http::Response<Vec<u8>>,
+        ctx: &ResponseContext,
+    ) -> Result<PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let seen = if ctx.body_streamed {
+            "streamed"
+        } else {
+            "buffered"
+        };
+        res.headers_mut()
+            .insert("x-plugin-seen", http::HeaderValue::from_static(seen));
+        Ok(PluginAction::Continue)
+    }
+}
+
+/// Plugin rejecting every response it sees with `451 Blocked`
+pub struct RejectResponsePlugin;
+
+#[async_trait]
+impl Plugin for RejectResponsePlugin {
+    fn name(&self) -> &str {
+        "reject-response"
+    }
+
+    fn needs_response_body(&self) -> bool {
+        true
+    }
+
+    async fn on_response(
+        &self,
+        _res: &mut http::Response<Vec<u8>>,
+        _ctx: &ResponseContext,
+    ) -> Result<PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        Ok(PluginAction::Reject {
+            status: 451,
+            reason: "Blocked".into(),
+        })
+    }
+}
+
 /// Start a simple HTTP server that echoes requests
 pub async fn start_echo_server(addr: SocketAddr) -> tokio::task::JoinHandle<()> {
     use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -4,6 +4,8 @@
 
 use super::{start_echo_server, wait_for_server, TestConfig};
 use ferrotunnel::{Client, Server};
+use ferrotunnel_common::TunnelError;
+use ferrotunnel_core::TunnelClient;
 use std::time::Duration;
 
 /// Test multiple clients connecting to same server
@@ -122,3 +124,51 @@ async fn test_client_reconnect() {
 
     let _ = client2.shutdown().await;
 }
+
+/// Clients beyond `ServerBuilder::max_sessions` are turned away
+#[tokio::test]
+async fn test_max_sessions_rejects_extra_client() {
+    let config = TestConfig::default();
+    let _echo = start_echo_server(config.local_service_addr).await;
+
+    let mut server = Server::builder()
+        .bind(config.server_addr)
+        .http_bind(config.http_addr)
+        .token(config.token)
+        .max_sessions(1)
+        .sharded_sessions(4)
+        .build()
+        .expect("Failed to build server");
+    let _server_handle = tokio::spawn(async move {
+        let _ = server.start().await;
+    });
+    assert!(wait_for_server(config.server_addr, Duration::from_secs(5)).await);
+    // Let the readiness probe's connection release its session slot
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut client = Client::builder()
+        .server_addr(config.server_addr.to_string())
+        .token(config.token)
+        .local_addr(config.local_service_addr.to_string())
+        .build()
+        .expect("Failed to build client");
+    client
+        .start()
+        .await
+        .expect("First client failed to connect");
+
+    let mut extra = TunnelClient::new(config.server_addr.to_string(), config.token.to_string());
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        extra.connect_and_run(|_stream| async {}),
+    )
+    .await
+    .expect("Extra client should be rejected, not left hanging");
+    // The server answers with ServerFull, which the client may retry later
+    assert!(
+        matches!(&result, Err(TunnelError::ResourceExhausted(msg)) if msg.contains("ServerFull")),
+        "{result:?}"
+    );
+
+    let _ = client.shutdown().await;
+}
@@ -0,0 +1,78 @@
+//! Per-tunnel HTTP path access rules integration tests
+
+use super::{make_client, start_echo_server, wait_for_server, TestConfig};
+use ferrotunnel::{Client, Server};
+use ferrotunnel_common::TunnelError;
+use ferrotunnel_core::{TunnelClient, TunnelServer};
+use ferrotunnel_protocol::PathRules;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_path_rules_block_disallowed_paths() {
+    let config = TestConfig::default();
+    let _echo = start_echo_server(config.local_service_addr).await;
+
+    let mut server = Server::builder()
+        .bind(config.server_addr)
+        .http_bind(config.http_addr)
+        .token(config.token)
+        .build()
+        .unwrap();
+    tokio::spawn(async move {
+        let _ = server.start().await;
+    });
+    assert!(wait_for_server(config.server_addr, Duration::from_secs(5)).await);
+
+    let rules = PathRules::default()
+        .with_allow("/webhooks/**")
+        .with_deny("/webhooks/internal/**");
+    let mut client = Client::builder()
+        .server_addr(config.server_addr.to_string())
+        .token(config.token)
+        .local_addr(config.local_service_addr.to_string())
+        .tunnel_id("acl")
+        .path_rules(rules)
+        .build()
+        .unwrap();
+    // The server registers the session before acknowledging the handshake,
+    // so the tunnel is routable once `start` returns
+    client.start().await.expect("client failed to connect");
+
+    let http = make_client();
+    let get = |path: &str| {
+        http.get(format!("http://{}{path}", config.http_addr))
+            .header("Host", "acl")
+            .send()
+    };
+
+    let allowed = get("/webhooks/github?delivery=1").await.unwrap();
+    assert_eq!(allowed.status(), 200);
+    assert_eq!(allowed.text().await.unwrap(), "Hello, World!");
+
+    for path in ["/admin", "/", "/webhooks/internal/keys"] {
+        let blocked = get(path).await.unwrap();
+        assert_eq!(blocked.status(), 403, "{path}");
+        assert_eq!(blocked.text().await.unwrap(), "Path not allowed");
+    }
+}
+
+#[tokio::test]
+async fn test_invalid_path_rules_rejected_at_handshake() {
+    let config = TestConfig::default();
+    let server = TunnelServer::new(config.server_addr, config.token.into());
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    assert!(wait_for_server(config.server_addr, Duration::from_secs(5)).await);
+
+    let mut client = TunnelClient::new(config.server_addr.to_string(), config.token.into())
+        .with_path_rules(PathRules::default().with_allow("no-leading-slash"));
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        client.connect_and_run(|_stream| async {}),
+    )
+    .await
+    .expect("handshake was not answered");
+
+    assert!(matches!(result, Err(TunnelError::Config(_))), "{result:?}");
+}
@@ -2,8 +2,15 @@
 //!
 //! Tests plugin system with auth and rate limiting
 
-use ferrotunnel_plugin::{Plugin, PluginAction, PluginRegistry, RequestContext};
+use super::{make_client, start_tunnel, RejectResponsePlugin};
+use async_trait::async_trait;
+use ferrotunnel_http::{HttpProxy, IngressConfig};
+use ferrotunnel_plugin::{
+    ByteCounter, Plugin, PluginAction, PluginRegistry, RequestContext, ResponseContext,
+};
 use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
 use tokio::sync::RwLock;
 
 /// Test auth plugin rejects unauthorized requests
@@ -19,8 +26,10 @@ async fn test_auth_plugin_rejects_unauthorized() {
     let ctx = RequestContext {
         tunnel_id: "test-tunnel".to_string(),
         session_id: "test-session".to_string(),
+        request_id: "request".to_string(),
         remote_addr: "127.0.0.1:12345".parse().unwrap(),
         timestamp: std::time::SystemTime::now(),
+        request_bytes: ByteCounter::default(),
     };
 
     let action = plugin.on_request(&mut request, &ctx).await;
@@ -50,8 +59,10 @@ async fn test_auth_plugin_allows_authorized() {
     let ctx = RequestContext {
         tunnel_id: "test-tunnel".to_string(),
         session_id: "test-session".to_string(),
+        request_id: "request".to_string(),
         remote_addr: "127.0.0.1:12345".parse().unwrap(),
         timestamp: std::time::SystemTime::now(),
+        request_bytes: ByteCounter::default(),
     };
 
     let action = plugin.on_request(&mut request, &ctx).await;
@@ -80,8 +91,10 @@ async fn test_rate_limit_enforces() {
         let ctx = RequestContext {
             tunnel_id: "test-tunnel".to_string(),
             session_id: "test-session".to_string(),
+            request_id: "request".to_string(),
             remote_addr: addr,
             timestamp: std::time::SystemTime::now(),
+            request_bytes: ByteCounter::default(),
         };
         let action = plugin.on_request(&mut request, &ctx).await;
         assert!(
@@ -95,8 +108,10 @@ async fn test_rate_limit_enforces() {
     let ctx = RequestContext {
         tunnel_id: "test-tunnel".to_string(),
         session_id: "test-session".to_string(),
+        request_id: "request".to_string(),
         remote_addr: addr,
         timestamp: std::time::SystemTime::now(),
+        request_bytes: ByteCounter::default(),
     };
     let action = plugin.on_request(&mut request, &ctx).await;
 
@@ -115,7 +130,7 @@ async fn test_rate_limit_enforces() {
 async fn test_registry_executes_in_order() {
     use ferrotunnel_plugin::builtin::LoggerPlugin;
 
-    let mut registry = PluginRegistry::new();
+    let registry = PluginRegistry::new();
     registry.register(Arc::new(RwLock::new(LoggerPlugin::default())));
 
     // Initialize plugins
@@ -126,8 +141,10 @@ async fn test_registry_executes_in_order() {
     let ctx = RequestContext {
         tunnel_id: "test-tunnel".to_string(),
         session_id: "test-session".to_string(),
+        request_id: "request".to_string(),
         remote_addr: "127.0.0.1:12345".parse().unwrap(),
         timestamp: std::time::SystemTime::now(),
+        request_bytes: ByteCounter::default(),
     };
 
     let action = registry.execute_request_hooks(&mut request, &ctx).await;
@@ -139,3 +156,132 @@ async fn test_registry_executes_in_order() {
         .await
         .expect("Failed to shutdown plugins");
 }
+
+/// Plugin that reports the byte counts it sees in response headers
+struct ByteCountPlugin;
+
+#[async_trait]
+impl Plugin for ByteCountPlugin {
+    fn name(&self) -> &str {
+        "byte-count"
+    }
+
+    fn needs_response_body(&self) -> bool {
+        true
+    }
+
+    async fn on_response(
+        &self,
+        res: &mut http::Response<Vec<u8>>,
+        ctx: &ResponseContext,
+    ) -> Result<PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let headers = res.headers_mut();
+        headers.insert("x-request-bytes", ctx.request_bytes.get().into());
+        headers.insert("x-response-bytes", ctx.response_bytes.get().into());
+        Ok(PluginAction::Continue)
+    }
+}
+
+/// Local service that reads a five byte request body and answers "hello world"
+async fn start_fixed_origin() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                loop {
+                    let head_end = request.windows(4).position(|w| w == b"\r\n\r\n");
+                    if head_end.is_some_and(|end| request.len() >= end + 4 + 5) {
+                        break;
+                    }
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let response = "HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\nhello world";
+                let _ = socket.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+
+    addr
+}
+
+/// Test plugins see the request and response body sizes
+#[tokio::test]
+async fn test_response_context_byte_counts() {
+    let registry = PluginRegistry::new();
+    registry.register(Arc::new(RwLock::new(ByteCountPlugin)));
+    let http_addr = start_tunnel(
+        "bytes",
+        HttpProxy::new(start_fixed_origin().await),
+        registry,
+        IngressConfig::default(),
+    )
+    .await;
+
+    let response = make_client()
+        .post(format!("http://{http_addr}/"))
+        .header("Host", "bytes")
+        .body("12345")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-request-bytes"], "5");
+    assert_eq!(response.headers()["x-response-bytes"], "11");
+    assert_eq!(response.text().await.unwrap(), "hello world");
+}
+
+/// Test a buffered response hook can replace the upstream response
+#[tokio::test]
+async fn test_response_hook_reject() {
+    let registry = PluginRegistry::new();
+    registry.register(Arc::new(RwLock::new(RejectResponsePlugin)));
+    let http_addr = start_tunnel(
+        "rejected",
+        HttpProxy::new(start_fixed_origin().await),
+        registry,
+        IngressConfig::default(),
+    )
+    .await;
+
+    let response = make_client()
+        .post(format!("http://{http_addr}/"))
+        .header("Host", "rejected")
+        .body("12345")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 451);
+    assert_eq!(response.text().await.unwrap(), "Blocked");
+}
+
+/// Test plugins registered into a registry an ingress is already using
+/// take effect on the next request
+#[tokio::test]
+async fn test_register_into_running_ingress() {
+    let registry = Arc::new(PluginRegistry::new());
+    let http_addr = start_tunnel(
+        "hot",
+        HttpProxy::new(start_fixed_origin().await),
+        registry.clone(),
+        IngressConfig::default(),
+    )
+    .await;
+    let send = || {
+        make_client()
+            .post(format!("http://{http_addr}/"))
+            .header("Host", "hot")
+            .body("12345")
+            .send()
+    };
+
+    assert_eq!(send().await.unwrap().status(), 200);
+    registry.register(Arc::new(RwLock::new(RejectResponsePlugin)));
+    assert_eq!(send().await.unwrap().status(), 451);
+}
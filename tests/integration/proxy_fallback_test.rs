@@ -0,0 +1,70 @@
+//! Client-side proxy fallback response integration tests
+
+use super::{make_client, start_tunnel};
+use ferrotunnel_http::{FallbackResponse, HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+const TUNNEL_ID: &str = "app";
+
+/// Address nothing listens on
+async fn dead_addr() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+/// Start a tunnel server and ingress with a client for `TUNNEL_ID` whose
+/// local service is down, returning the ingress address
+async fn start_fallback_tunnel(fallback: Option<FallbackResponse>) -> SocketAddr {
+    let mut proxy = HttpProxy::new(dead_addr().await);
+    if let Some(fallback) = fallback {
+        proxy = proxy.with_fallback(fallback);
+    }
+    start_tunnel(
+        TUNNEL_ID,
+        proxy,
+        PluginRegistry::new(),
+        IngressConfig::default(),
+    )
+    .await
+}
+
+/// GET `/` through the tunnel
+async fn get(http_addr: SocketAddr) -> reqwest::Response {
+    make_client()
+        .get(format!("http://{http_addr}/"))
+        .header("Host", TUNNEL_ID)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_fallback_served_when_backend_down() {
+    let fallback = FallbackResponse::maintenance_page("<h1>Down for maintenance</h1>")
+        .with_retry_after(Duration::from_secs(60));
+    let http_addr = start_fallback_tunnel(Some(fallback)).await;
+
+    let response = get(http_addr).await;
+    assert_eq!(response.status().as_u16(), 503);
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/html; charset=utf-8"
+    );
+    assert_eq!(response.headers()["retry-after"], "60");
+    assert_eq!(
+        response.text().await.unwrap(),
+        "<h1>Down for maintenance</h1>"
+    );
+}
+
+#[tokio::test]
+async fn test_bad_gateway_without_fallback() {
+    let http_addr = start_fallback_tunnel(None).await;
+
+    let response = get(http_addr).await;
+    assert_eq!(response.status().as_u16(), 502);
+    assert!(response.text().await.unwrap().contains("Failed to connect"));
+}
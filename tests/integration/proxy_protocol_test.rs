@@ -0,0 +1,171 @@
+//! PROXY protocol v2 ingress integration tests
+
+use super::{
+    connect_tunnel, get_free_port, start_echo_server, start_tunnel_server, wait_for_server,
+    TUNNEL_TOKEN,
+};
+use async_trait::async_trait;
+use ferrotunnel_core::TunnelClient;
+use ferrotunnel_http::proxy_protocol::encode_header;
+use ferrotunnel_http::{HttpProxy, IngressConfig, TcpIngress, TcpIngressConfig};
+use ferrotunnel_plugin::{Plugin, PluginAction, PluginRegistry, RequestContext};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::RwLock;
+
+const TUNNEL_ID: &str = "proxied";
+
+/// Address of the client as the load balancer saw it
+const CLIENT_ADDR: &str = "203.0.113.7:4242";
+
+/// Records the remote address of every request
+#[derive(Default)]
+struct RemoteAddrRecorder {
+    seen: Arc<Mutex<Vec<SocketAddr>>>,
+}
+
+#[async_trait]
+impl Plugin for RemoteAddrRecorder {
+    fn name(&self) -> &str {
+        "remote-addr-recorder"
+    }
+
+    async fn on_request(
+        &self,
+        _req: &mut http::Request<()>,
+        ctx: &RequestContext,
+    ) -> Result<PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.seen.lock().unwrap().push(ctx.remote_addr);
+        Ok(PluginAction::Continue)
+    }
+}
+
+/// Start a tunnel server plus an HTTP ingress expecting PROXY headers and a
+/// client forwarding to an echo server. Returns the ingress address and the
+/// remote addresses plugins saw.
+async fn start_tunnel() -> (SocketAddr, Arc<Mutex<Vec<SocketAddr>>>) {
+    let local_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let _echo = start_echo_server(local_addr).await;
+
+    let recorder = RemoteAddrRecorder::default();
+    let seen = recorder.seen.clone();
+    let registry = PluginRegistry::new();
+    registry.register(Arc::new(RwLock::new(recorder)));
+    let config = IngressConfig {
+        proxy_protocol: true,
+        ..Default::default()
+    };
+    let http_addr = super::start_tunnel(
+        TUNNEL_ID,
+        HttpProxy::new(local_addr.to_string()),
+        registry,
+        config,
+    )
+    .await;
+
+    (http_addr, seen)
+}
+
+/// Send `preamble` then a GET request and return everything the ingress
+/// answered before closing
+async fn raw_request(http_addr: SocketAddr, preamble: &[u8]) -> String {
+    let mut stream = TcpStream::connect(http_addr).await.unwrap();
+    stream.write_all(preamble).await.unwrap();
+    let request = format!("GET / HTTP/1.1\r\nHost: {TUNNEL_ID}\r\nConnection: close\r\n\r\n");
+    // The ingress may already have closed the connection
+    let _ = stream.write_all(request.as_bytes()).await;
+
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response))
+        .await
+        .expect("ingress did not close the connection");
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn test_http_ingress_uses_proxy_source_addr() {
+    let (http_addr, seen) = start_tunnel().await;
+
+    let header = encode_header(CLIENT_ADDR.parse().unwrap(), http_addr);
+    let response = raw_request(http_addr, &header).await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    assert!(response.ends_with("Hello, World!"), "{response}");
+    assert_eq!(*seen.lock().unwrap(), [CLIENT_ADDR.parse().unwrap()]);
+}
+
+#[tokio::test]
+async fn test_http_ingress_rejects_missing_proxy_header() {
+    let (http_addr, seen) = start_tunnel().await;
+
+    let response = raw_request(http_addr, b"").await;
+    assert!(response.is_empty(), "{response}");
+    assert!(seen.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_tcp_ingress_requires_proxy_header() {
+    let echo_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let echo_addr = echo_listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = echo_listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 1024];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let _ = socket.write_all(&buf[..n]).await;
+            });
+        }
+    });
+
+    let (server_addr, sessions) = start_tunnel_server(|server| server).await;
+    let tcp_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+
+    let config = TcpIngressConfig {
+        proxy_protocol: true,
+        ..Default::default()
+    };
+    let tcp_ingress = TcpIngress::with_config(tcp_addr, sessions.clone(), config);
+    tokio::spawn(async move {
+        let _ = tcp_ingress.start().await;
+    });
+    assert!(wait_for_server(tcp_addr, Duration::from_secs(5)).await);
+
+    let client =
+        TunnelClient::new(server_addr.to_string(), TUNNEL_TOKEN.into()).with_tunnel_id(TUNNEL_ID);
+    connect_tunnel(&sessions, TUNNEL_ID, client, move |mut stream| {
+        let echo_addr = echo_addr.clone();
+        async move {
+            tokio::spawn(async move {
+                let mut local = TcpStream::connect(&echo_addr).await.unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut stream, &mut local).await;
+            });
+        }
+    })
+    .await;
+
+    // With a header the payload after it reaches the tunnel untouched
+    let mut stream = TcpStream::connect(tcp_addr).await.unwrap();
+    let mut wire = encode_header(CLIENT_ADDR.parse().unwrap(), tcp_addr);
+    wire.extend_from_slice(b"HELLO");
+    stream.write_all(&wire).await.unwrap();
+    let mut buf = [0u8; 5];
+    tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut buf))
+        .await
+        .expect("echo timed out")
+        .unwrap();
+    assert_eq!(&buf, b"HELLO");
+
+    // Without one the connection is closed
+    let mut stream = TcpStream::connect(tcp_addr).await.unwrap();
+    stream
+        .write_all(b"HELLO, this is not a PROXY header")
+        .await
+        .unwrap();
+    let mut rest = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut rest))
+        .await
+        .expect("connection was not closed");
+    assert!(rest.is_empty());
+}
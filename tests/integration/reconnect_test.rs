@@ -0,0 +1,111 @@
+//! Session takeover on reconnect integration tests
+//!
+//! A client whose connection drops without the server noticing reconnects
+//! naming its previous session, and takes over its tunnel ID right away
+//! instead of being turned away until the old session times out.
+
+use super::{get_free_port, wait_for_server};
+use ferrotunnel_core::tunnel::session::SessionStoreBackend;
+use ferrotunnel_core::TunnelServer;
+use ferrotunnel_protocol::constants::{
+    MAX_PROTOCOL_VERSION, MIN_PROTOCOL_VERSION, RESUME_CAPABILITY,
+};
+use ferrotunnel_protocol::frame::{Frame, HandshakeFrame, HandshakeStatus};
+use ferrotunnel_protocol::TunnelCodec;
+use futures_util::{SinkExt, StreamExt};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
+use uuid::Uuid;
+
+const TUNNEL_ID: &str = "reconnect";
+
+type Connection = Framed<TcpStream, TunnelCodec>;
+
+async fn start_server() -> (SocketAddr, SessionStoreBackend) {
+    let addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let server = TunnelServer::new(addr, "test-token".into());
+    let sessions = server.sessions();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    assert!(wait_for_server(addr, Duration::from_secs(5)).await);
+    (addr, sessions)
+}
+
+/// Handshake for `TUNNEL_ID`, optionally resuming `previous`. Returns the
+/// connection and the status and session ID from the server's ack.
+async fn handshake(
+    addr: SocketAddr,
+    previous: Option<Uuid>,
+) -> (Connection, HandshakeStatus, Uuid) {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut framed = Framed::new(stream, TunnelCodec::new());
+    framed
+        .send(Frame::Handshake(Box::new(HandshakeFrame {
+            token: "test-token".into(),
+            tunnel_id: Some(TUNNEL_ID.into()),
+            min_version: MIN_PROTOCOL_VERSION,
+            max_version: MAX_PROTOCOL_VERSION,
+            capabilities: previous
+                .map(|id| format!("{RESUME_CAPABILITY}:{id}"))
+                .into_iter()
+                .collect(),
+        })))
+        .await
+        .unwrap();
+    let ack = tokio::time::timeout(Duration::from_secs(5), framed.next())
+        .await
+        .expect("no handshake ack")
+        .unwrap()
+        .unwrap();
+    let Frame::HandshakeAck {
+        status, session_id, ..
+    } = ack
+    else {
+        panic!("expected a handshake ack, got {ack:?}");
+    };
+    (framed, status, session_id)
+}
+
+#[tokio::test]
+async fn test_reconnect_replaces_previous_session() {
+    let (addr, sessions) = start_server().await;
+    let (mut old, status, old_id) = handshake(addr, None).await;
+    assert_eq!(status, HandshakeStatus::Success);
+
+    // The old connection goes silent without closing, as after a network
+    // drop; its session is still registered
+    let (_new, status, new_id) = handshake(addr, Some(old_id)).await;
+    assert_eq!(status, HandshakeStatus::Success);
+    assert_ne!(new_id, old_id);
+
+    assert_eq!(sessions.count(), 1);
+    assert!(sessions.get(&old_id).is_none());
+    assert_eq!(sessions.get_by_tunnel_id(TUNNEL_ID).unwrap().id, new_id);
+
+    // The server hangs up the replaced connection
+    let closed = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(Ok(_)) = old.next().await {}
+    })
+    .await;
+    assert!(closed.is_ok(), "replaced connection was left open");
+    assert_eq!(sessions.get_by_tunnel_id(TUNNEL_ID).unwrap().id, new_id);
+}
+
+#[tokio::test]
+async fn test_reconnect_without_previous_session_rejected() {
+    let (addr, sessions) = start_server().await;
+    let (_old, status, old_id) = handshake(addr, None).await;
+    assert_eq!(status, HandshakeStatus::Success);
+
+    // Same token, but not the client that held the session
+    let (_, status, _) = handshake(addr, None).await;
+    assert_eq!(status, HandshakeStatus::TunnelIdTaken);
+    let (_, status, _) = handshake(addr, Some(Uuid::new_v4())).await;
+    assert_eq!(status, HandshakeStatus::TunnelIdTaken);
+
+    assert_eq!(sessions.count(), 1);
+    assert!(sessions.get(&old_id).is_some());
+}
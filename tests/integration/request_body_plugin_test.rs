@@ -0,0 +1,186 @@
+//! Streaming request body plugin hook integration tests
+
+use super::start_tunnel;
+use async_trait::async_trait;
+use bytes::Bytes;
+use ferrotunnel_http::{HttpProxy, IngressConfig};
+use ferrotunnel_plugin::{Plugin, PluginAction, PluginRegistry, RequestContext};
+use http_body_util::{BodyExt, Full};
+use hyper::Request;
+use hyper_util::rt::TokioIo;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::RwLock;
+
+const TUNNEL_ID: &str = "body-hooks";
+const SECRET: &[u8] = b"secret";
+const MASK: &[u8] = b"******";
+
+type HookResult = Result<PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>>;
+
+/// Masks every "secret" in request bodies, even when split across chunks
+#[derive(Default)]
+struct RedactPlugin {
+    /// Tail of the previous chunk per request, which may start a match
+    held: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+#[async_trait]
+impl Plugin for RedactPlugin {
+    fn name(&self) -> &str {
+        "redact"
+    }
+
+    fn needs_request_body(&self) -> bool {
+        true
+    }
+
+    async fn on_request_chunk(&self, chunk: &mut Bytes, ctx: &RequestContext) -> HookResult {
+        let mut held = self.held.lock().unwrap();
+        let mut data = held.remove(&ctx.request_id).unwrap_or_default();
+        data.extend_from_slice(chunk);
+
+        let mut i = 0;
+        while i + SECRET.len() <= data.len() {
+            if data[i..].starts_with(SECRET) {
+                data[i..i + SECRET.len()].copy_from_slice(MASK);
+                i += SECRET.len();
+            } else {
+                i += 1;
+            }
+        }
+
+        // An empty chunk ends the body, so nothing is held back
+        if !chunk.is_empty() {
+            let keep = data.len().min(SECRET.len() - 1);
+            held.insert(ctx.request_id.clone(), data.split_off(data.len() - keep));
+        }
+        *chunk = Bytes::from(data);
+        Ok(PluginAction::Continue)
+    }
+}
+
+/// Rejects any request whose body contains "EVIL"
+struct AbortPlugin;
+
+#[async_trait]
+impl Plugin for AbortPlugin {
+    fn name(&self) -> &str {
+        "abort"
+    }
+
+    fn needs_request_body(&self) -> bool {
+        true
+    }
+
+    async fn on_request_chunk(&self, chunk: &mut Bytes, _ctx: &RequestContext) -> HookResult {
+        if chunk.windows(4).any(|window| window == b"EVIL") {
+            return Ok(PluginAction::Reject {
+                status: 422,
+                reason: "Forbidden payload".to_string(),
+            });
+        }
+        Ok(PluginAction::Continue)
+    }
+}
+
+/// Local HTTP/1.1 service echoing request bodies, counting complete ones
+async fn start_echo_body_server() -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let completed = Arc::new(AtomicUsize::new(0));
+    let counter = completed.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                break;
+            };
+            let counter = counter.clone();
+            tokio::spawn(async move {
+                let service =
+                    hyper::service::service_fn(move |req: Request<hyper::body::Incoming>| {
+                        let counter = counter.clone();
+                        async move {
+                            let body = req.into_body().collect().await?.to_bytes();
+                            counter.fetch_add(1, Ordering::SeqCst);
+                            Ok::<_, hyper::Error>(hyper::Response::new(Full::new(body)))
+                        }
+                    });
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+
+    (addr, completed)
+}
+
+/// Start a tunnel whose ingress runs `plugin`, forwarding to `local_addr`.
+/// Returns the ingress address.
+async fn start_plugin_tunnel(plugin: impl Plugin + 'static, local_addr: String) -> SocketAddr {
+    let registry = PluginRegistry::new();
+    registry.register(Arc::new(RwLock::new(plugin)));
+    start_tunnel(
+        TUNNEL_ID,
+        HttpProxy::new(local_addr),
+        registry,
+        IngressConfig::default(),
+    )
+    .await
+}
+
+/// POST `chunks` as a chunked body, pausing between them so each reaches the
+/// ingress as its own frame. Returns the raw response.
+async fn post_chunked(http_addr: SocketAddr, chunks: &[&str]) -> String {
+    let mut stream = TcpStream::connect(http_addr).await.unwrap();
+    let head = format!(
+        "POST /upload HTTP/1.1\r\nHost: {TUNNEL_ID}\r\n\
+         Transfer-Encoding: chunked\r\nConnection: close\r\n\r\n"
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    for chunk in chunks {
+        let frame = format!("{:x}\r\n{chunk}\r\n", chunk.len());
+        // The ingress may answer and stop reading before the body is sent
+        if stream.write_all(frame.as_bytes()).await.is_err() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let _ = stream.write_all(b"0\r\n\r\n").await;
+
+    let mut response = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(10), stream.read_to_end(&mut response)).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn test_request_body_redacted_across_chunks() {
+    let (local_addr, completed) = start_echo_body_server().await;
+    let http_addr = start_plugin_tunnel(RedactPlugin::default(), local_addr).await;
+
+    let response = post_chunked(http_addr, &["token=se", "cret&next=", "secre", "t"]).await;
+
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    assert!(response.ends_with("token=******&next=******"), "{response}");
+    assert_eq!(completed.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_request_body_hook_aborts_forwarding() {
+    let (local_addr, completed) = start_echo_body_server().await;
+    let http_addr = start_plugin_tunnel(AbortPlugin, local_addr).await;
+
+    let response = post_chunked(http_addr, &["hello ", "EVIL", " world"]).await;
+
+    assert!(response.starts_with("HTTP/1.1 422"), "{response}");
+    assert!(response.ends_with("Forbidden payload"), "{response}");
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(completed.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,204 @@
+//! HTTP ingress response cache integration tests
+
+use super::{make_client, start_tunnel};
+use bytes::Bytes;
+use ferrotunnel_http::{CacheConfig, HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use http_body_util::Full;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+
+const TUNNEL_ID: &str = "app";
+
+/// Path and `If-None-Match` of every request the local service answered
+type Seen = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+/// Response of the local service for `path`:
+/// - `/cached`: fresh for a minute, ETag `"c1"`
+/// - `/public`: `public`, fresh for a minute, ETag `"s1"`
+/// - `/private`: `no-store`
+/// - `/unmarked`: no freshness information, ETag `"u1"`
+/// - `/etag`: `no-cache`, ETag `"e1"`, 304 when the ETag matches
+fn respond(path: &str, if_none_match: Option<&str>) -> Response<Full<Bytes>> {
+    let (cache_control, etag) = match path {
+        "/cached" => (Some("max-age=60"), "\"c1\""),
+        "/public" => (Some("public, max-age=60"), "\"s1\""),
+        "/private" => (Some("no-store"), "\"p1\""),
+        "/unmarked" => (None, "\"u1\""),
+        _ => (Some("no-cache"), "\"e1\""),
+    };
+    let mut builder = Response::builder().header("etag", etag);
+    if let Some(cache_control) = cache_control {
+        builder = builder.header("cache-control", cache_control);
+    }
+    if if_none_match == Some(etag) {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Full::new(Bytes::new()))
+            .unwrap();
+    }
+    builder
+        .body(Full::new(Bytes::from(format!("body of {path}"))))
+        .unwrap()
+}
+
+/// Local HTTP/1.1 service that records the requests it answers
+async fn start_origin() -> (String, Seen) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let seen = Seen::default();
+
+    let recorded = seen.clone();
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                break;
+            };
+            let recorded = recorded.clone();
+            tokio::spawn(async move {
+                let service =
+                    hyper::service::service_fn(move |req: Request<hyper::body::Incoming>| {
+                        let path = req.uri().path().to_string();
+                        let if_none_match = req
+                            .headers()
+                            .get("if-none-match")
+                            .map(|v| v.to_str().unwrap().to_string());
+                        let res = respond(&path, if_none_match.as_deref());
+                        recorded.lock().unwrap().push((path, if_none_match));
+                        async { Ok::<_, hyper::Error>(res) }
+                    });
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+
+    (addr, seen)
+}
+
+/// Start a tunnel server, caching ingress and client for `TUNNEL_ID` in front
+/// of a recording origin, returning the ingress address and the origin's log
+async fn start_cached_tunnel() -> (SocketAddr, Seen) {
+    let (local_addr, seen) = start_origin().await;
+    let config = IngressConfig::default().cache(CacheConfig::default());
+    let http_addr = start_tunnel(
+        TUNNEL_ID,
+        HttpProxy::new(local_addr),
+        PluginRegistry::new(),
+        config,
+    )
+    .await;
+    (http_addr, seen)
+}
+
+/// GET `path` through the tunnel with `headers`
+async fn get(http_addr: SocketAddr, path: &str, headers: &[(&str, &str)]) -> reqwest::Response {
+    let mut request = make_client()
+        .get(format!("http://{http_addr}{path}"))
+        .header("Host", TUNNEL_ID);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    request.send().await.unwrap()
+}
+
+fn requests_for(seen: &Seen, path: &str) -> Vec<Option<String>> {
+    seen.lock()
+        .unwrap()
+        .iter()
+        .filter(|(seen_path, _)| seen_path == path)
+        .map(|(_, if_none_match)| if_none_match.clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_fresh_response_served_from_cache() {
+    let (http_addr, seen) = start_cached_tunnel().await;
+
+    let first = get(http_addr, "/cached", &[]).await;
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(first.text().await.unwrap(), "body of /cached");
+
+    let second = get(http_addr, "/cached", &[]).await;
+    assert_eq!(second.status().as_u16(), 200);
+    assert!(second.headers().contains_key("age"));
+    assert_eq!(second.text().await.unwrap(), "body of /cached");
+
+    // A matching validator is answered with 304 by the cache itself
+    let conditional = get(http_addr, "/cached", &[("If-None-Match", "\"c1\"")]).await;
+    assert_eq!(conditional.status().as_u16(), 304);
+    assert_eq!(conditional.headers()["etag"], "\"c1\"");
+
+    // The client can still insist on going upstream
+    let reload = get(http_addr, "/cached", &[("Cache-Control", "no-cache")]).await;
+    assert_eq!(reload.status().as_u16(), 200);
+
+    assert_eq!(requests_for(&seen, "/cached").len(), 2);
+}
+
+#[tokio::test]
+async fn test_no_store_response_not_cached() {
+    let (http_addr, seen) = start_cached_tunnel().await;
+
+    for _ in 0..2 {
+        let response = get(http_addr, "/private", &[]).await;
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.text().await.unwrap(), "body of /private");
+    }
+
+    assert_eq!(requests_for(&seen, "/private").len(), 2);
+}
+
+#[tokio::test]
+async fn test_stale_response_revalidated_with_etag() {
+    let (http_addr, seen) = start_cached_tunnel().await;
+
+    let first = get(http_addr, "/etag", &[]).await;
+    assert_eq!(first.text().await.unwrap(), "body of /etag");
+
+    // The origin answers the revalidation with 304; the client gets the
+    // cached body
+    let second = get(http_addr, "/etag", &[]).await;
+    assert_eq!(second.status().as_u16(), 200);
+    assert_eq!(second.text().await.unwrap(), "body of /etag");
+
+    assert_eq!(
+        requests_for(&seen, "/etag"),
+        vec![None, Some("\"e1\"".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn test_response_without_freshness_not_cached() {
+    let (http_addr, seen) = start_cached_tunnel().await;
+
+    for _ in 0..2 {
+        let response = get(http_addr, "/unmarked", &[]).await;
+        assert_eq!(response.text().await.unwrap(), "body of /unmarked");
+    }
+
+    assert_eq!(requests_for(&seen, "/unmarked").len(), 2);
+}
+
+#[tokio::test]
+async fn test_requests_with_credentials_share_only_public_responses() {
+    let (http_addr, seen) = start_cached_tunnel().await;
+
+    let cookie = [("Cookie", "session=alice")];
+    for _ in 0..2 {
+        let response = get(http_addr, "/cached", &cookie).await;
+        assert_eq!(response.text().await.unwrap(), "body of /cached");
+    }
+    // Neither personalized response was stored or served from the cache
+    assert_eq!(requests_for(&seen, "/cached").len(), 2);
+
+    for _ in 0..2 {
+        let response = get(http_addr, "/public", &cookie).await;
+        assert_eq!(response.text().await.unwrap(), "body of /public");
+    }
+    assert_eq!(requests_for(&seen, "/public").len(), 1);
+}
@@ -0,0 +1,245 @@
+//! Per-request response timeout override integration tests
+
+use super::{make_client, start_tunnel};
+use async_trait::async_trait;
+use ferrotunnel_http::{HttpProxy, IngressConfig};
+use ferrotunnel_plugin::{Plugin, PluginAction, PluginRegistry, RequestContext};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+
+const TUNNEL_ID: &str = "timeouts";
+const TIMEOUT_HEADER: &str = "X-Tunnel-Timeout";
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(200);
+/// How long the local service waits before sending response headers
+const SLOW: Duration = Duration::from_millis(600);
+
+/// Local service answering every request after `SLOW`, except `/events`,
+/// which immediately streams three Server-Sent Events 200ms apart. Other
+/// paths report whether the timeout header reached the service.
+async fn start_slow_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut socket, _)) = listener.accept().await else {
+                break;
+            };
+            tokio::spawn(async move {
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request).to_lowercase();
+
+                if request.starts_with("get /events ") {
+                    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                                Connection: close\r\n\r\n";
+                    let _ = socket.write_all(head.as_bytes()).await;
+                    for i in 0..3 {
+                        let event = format!("data: event-{i}\n\n");
+                        let _ = socket.write_all(event.as_bytes()).await;
+                        tokio::time::sleep(Duration::from_millis(200)).await;
+                    }
+                } else {
+                    tokio::time::sleep(SLOW).await;
+                    let body = if request.contains(&TIMEOUT_HEADER.to_lowercase()) {
+                        "leaked"
+                    } else {
+                        "done"
+                    };
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                }
+            });
+        }
+    });
+
+    addr
+}
+
+/// Plugin extending the response timeout of every request
+struct LongTimeoutPlugin;
+
+#[async_trait]
+impl Plugin for LongTimeoutPlugin {
+    fn name(&self) -> &str {
+        "long-timeout"
+    }
+
+    async fn on_request(
+        &self,
+        _req: &mut http::Request<()>,
+        _ctx: &RequestContext,
+    ) -> Result<PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        Ok(PluginAction::SetTimeout(Duration::from_secs(5)))
+    }
+}
+
+/// Ingress config with a short response timeout, overridable up to
+/// `max_response_timeout` through the timeout header
+fn timeout_config(max_response_timeout: Duration) -> IngressConfig {
+    IngressConfig {
+        response_timeout: RESPONSE_TIMEOUT,
+        timeout_header: Some(TIMEOUT_HEADER.to_string()),
+        max_response_timeout,
+        ..Default::default()
+    }
+}
+
+/// Start a tunnel server, an HTTP ingress using `config` and a client
+/// forwarding to a slow local service. Returns the ingress address.
+async fn start_slow_tunnel(registry: PluginRegistry, config: IngressConfig) -> SocketAddr {
+    let proxy = HttpProxy::new(start_slow_server().await);
+    start_tunnel(TUNNEL_ID, proxy, registry, config).await
+}
+
+#[tokio::test]
+async fn test_slow_response_times_out_by_default() {
+    let http_addr = start_slow_tunnel(
+        PluginRegistry::new(),
+        timeout_config(Duration::from_secs(5)),
+    )
+    .await;
+
+    let res = make_client()
+        .get(format!("http://{http_addr}/slow"))
+        .header("Host", TUNNEL_ID)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 504);
+}
+
+#[tokio::test]
+async fn test_timeout_header_extends_response_timeout() {
+    let http_addr = start_slow_tunnel(
+        PluginRegistry::new(),
+        timeout_config(Duration::from_secs(5)),
+    )
+    .await;
+
+    let res = make_client()
+        .get(format!("http://{http_addr}/slow"))
+        .header("Host", TUNNEL_ID)
+        .header(TIMEOUT_HEADER, "2")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    // The header is consumed by the ingress, not forwarded
+    assert_eq!(res.text().await.unwrap(), "done");
+}
+
+#[tokio::test]
+async fn test_timeout_override_is_capped() {
+    let http_addr = start_slow_tunnel(
+        PluginRegistry::new(),
+        timeout_config(Duration::from_millis(300)),
+    )
+    .await;
+
+    let res = make_client()
+        .get(format!("http://{http_addr}/slow"))
+        .header("Host", TUNNEL_ID)
+        .header(TIMEOUT_HEADER, "3600")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 504);
+}
+
+#[tokio::test]
+async fn test_plugin_set_timeout_extends_response_timeout() {
+    let registry = PluginRegistry::new();
+    registry.register(Arc::new(RwLock::new(LongTimeoutPlugin)));
+    let http_addr = start_slow_tunnel(registry, timeout_config(Duration::from_secs(5))).await;
+
+    let res = make_client()
+        .get(format!("http://{http_addr}/slow"))
+        .header("Host", TUNNEL_ID)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.text().await.unwrap(), "done");
+}
+
+#[tokio::test]
+async fn test_event_stream_body_outlives_response_timeout() {
+    let http_addr = start_slow_tunnel(
+        PluginRegistry::new(),
+        timeout_config(Duration::from_secs(5)),
+    )
+    .await;
+
+    let started = Instant::now();
+    let res = make_client()
+        .get(format!("http://{http_addr}/events"))
+        .header("Host", TUNNEL_ID)
+        .header("Accept", "text/event-stream")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        "text/event-stream"
+    );
+
+    let body = res.text().await.unwrap();
+    assert_eq!(body, "data: event-0\n\ndata: event-1\n\ndata: event-2\n\n");
+    assert!(started.elapsed() > RESPONSE_TIMEOUT * 2);
+}
+
+#[tokio::test]
+async fn test_accepting_event_stream_does_not_bypass_timeout() {
+    let http_addr = start_slow_tunnel(
+        PluginRegistry::new(),
+        timeout_config(Duration::from_secs(5)),
+    )
+    .await;
+
+    let res = make_client()
+        .get(format!("http://{http_addr}/slow"))
+        .header("Host", TUNNEL_ID)
+        .header("Accept", "text/event-stream")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 504);
+}
+
+#[tokio::test]
+async fn test_idle_event_stream_is_closed() {
+    let config = IngressConfig {
+        event_stream_idle_timeout: Duration::from_millis(100),
+        ..timeout_config(Duration::from_secs(5))
+    };
+    let http_addr = start_slow_tunnel(PluginRegistry::new(), config).await;
+
+    let res = make_client()
+        .get(format!("http://{http_addr}/events"))
+        .header("Host", TUNNEL_ID)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    // The stream is ended after the first event, before the next arrives
+    let body = tokio::time::timeout(Duration::from_secs(5), res.text())
+        .await
+        .expect("idle event stream was not closed")
+        .unwrap();
+    assert_eq!(body, "data: event-0\n\n");
+}
@@ -0,0 +1,58 @@
+//! `SO_REUSEPORT` listener integration tests
+
+use super::{
+    connect_proxy_tunnel, get_free_port, make_client, start_echo_server, start_ingress,
+    start_tunnel_server,
+};
+use ferrotunnel_core::transport::ListenerConfig;
+use ferrotunnel_http::{HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+const ACCEPT_TASKS: usize = 4;
+
+fn free_addr() -> SocketAddr {
+    format!("127.0.0.1:{}", get_free_port()).parse().unwrap()
+}
+
+#[tokio::test]
+async fn test_reuse_port_server_and_ingress() {
+    let listener_config = ListenerConfig::default()
+        .with_backlog(2048)
+        .with_accept_tasks(ACCEPT_TASKS);
+
+    let (server_addr, sessions) =
+        start_tunnel_server(|server| server.with_listener_config(listener_config.clone())).await;
+
+    // Another SO_REUSEPORT socket can join the port; a plain one cannot
+    let joined = listener_config.bind(server_addr);
+    assert!(joined.is_ok());
+    drop(joined);
+    assert!(tokio::net::TcpListener::bind(server_addr).await.is_err());
+
+    let local_addr = free_addr();
+    start_echo_server(local_addr).await;
+
+    // Several clients, so connections are spread over the accept loops
+    for i in 0..ACCEPT_TASKS {
+        let proxy = Arc::new(HttpProxy::new(local_addr.to_string()));
+        connect_proxy_tunnel(server_addr, &sessions, &format!("app{i}"), proxy).await;
+    }
+
+    let config = IngressConfig::default().listener(listener_config);
+    let http_addr = start_ingress(sessions, PluginRegistry::new(), config).await;
+
+    let client = make_client();
+    for i in 0..ACCEPT_TASKS * 4 {
+        let response = client
+            .get(format!("http://{http_addr}/"))
+            .header("Host", format!("app{}", i % ACCEPT_TASKS))
+            .header("Connection", "close")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.text().await.unwrap(), "Hello, World!");
+    }
+}
@@ -0,0 +1,98 @@
+//! Server shutdown notice integration tests
+
+use super::{start_tunnel_server, TUNNEL_TOKEN};
+use ferrotunnel::{Client, ClientEvent};
+use ferrotunnel_core::announce_shutdown;
+use ferrotunnel_core::tunnel::session::SessionStoreBackend;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// IDs of the sessions currently registered
+fn session_ids(sessions: &SessionStoreBackend) -> Vec<Uuid> {
+    let mut ids = Vec::new();
+    sessions.for_each(|session| ids.push(session.id));
+    ids
+}
+
+#[tokio::test]
+async fn test_client_waits_suggested_delay_after_shutdown_notice() {
+    let (server_addr, sessions) = start_tunnel_server(|server| server).await;
+
+    // The client's own reconnect delay is far longer than the server's hint
+    let mut client = Client::builder()
+        .server_addr(server_addr.to_string())
+        .token(TUNNEL_TOKEN)
+        .local_addr("127.0.0.1:1")
+        .reconnect_delay(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let first = client.start().await.unwrap().session_id.unwrap();
+
+    let hint = Duration::from_millis(500);
+    let announced = Instant::now();
+    assert_eq!(
+        announce_shutdown(&sessions, "planned restart", Some(hint)).await,
+        1
+    );
+
+    let reconnected = loop {
+        assert!(
+            announced.elapsed() < Duration::from_secs(10),
+            "client did not reconnect after the suggested delay"
+        );
+        if session_ids(&sessions).iter().any(|id| *id != first) {
+            break announced.elapsed();
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    };
+    assert!(reconnected >= hint, "reconnected after {reconnected:?}");
+
+    let _ = client.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_client_reports_lifecycle_events_across_reconnect() {
+    let (server_addr, sessions) = start_tunnel_server(|server| server).await;
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    let mut client = Client::builder()
+        .server_addr(server_addr.to_string())
+        .token(TUNNEL_TOKEN)
+        .local_addr("127.0.0.1:1")
+        .on_event(move |event| recorded.lock().unwrap().push(event))
+        .build()
+        .unwrap();
+    let first = client.start().await.unwrap().session_id.unwrap();
+
+    // Force a disconnect; the client reconnects after the hinted delay
+    let hint = Duration::from_millis(100);
+    announce_shutdown(&sessions, "planned restart", Some(hint)).await;
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while events.lock().unwrap().len() < 4 {
+        assert!(Instant::now() < deadline, "client did not reconnect");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    client.shutdown().await.unwrap();
+
+    let events = events.lock().unwrap().clone();
+    assert_eq!(events.len(), 5, "{events:?}");
+    assert_eq!(events[0], ClientEvent::Connected { session_id: first });
+    assert!(
+        matches!(&events[1], ClientEvent::Disconnected { error } if error.contains("restart")),
+        "{events:?}"
+    );
+    assert_eq!(
+        events[2],
+        ClientEvent::Reconnecting {
+            attempt: 1,
+            delay: hint
+        }
+    );
+    assert!(
+        matches!(events[3], ClientEvent::Connected { session_id } if session_id != first),
+        "{events:?}"
+    );
+    assert_eq!(events[4], ClientEvent::ShuttingDown);
+}
@@ -0,0 +1,124 @@
+//! Server-Sent Events streaming integration tests
+
+use super::{make_client, start_tunnel, BufferingPlugin, RejectResponsePlugin};
+use ferrotunnel_http::{HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, RwLock};
+
+const TUNNEL_ID: &str = "events";
+const EVENTS: usize = 3;
+
+/// Local service streaming `EVENTS` Server-Sent Events. After the first,
+/// each event is only sent once the test asks for it, so the stream cannot
+/// finish unless earlier events reached the client on their own.
+async fn start_sse_server() -> (String, mpsc::UnboundedSender<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (next_tx, mut next_rx) = mpsc::unbounded_channel::<()>();
+
+    tokio::spawn(async move {
+        let Ok((mut socket, _)) = listener.accept().await else {
+            return;
+        };
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            match socket.read(&mut buf).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => request.extend_from_slice(&buf[..n]),
+            }
+        }
+
+        let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                    Cache-Control: no-cache\r\nConnection: close\r\n\r\n";
+        let _ = socket.write_all(head.as_bytes()).await;
+        for i in 0..EVENTS {
+            if i > 0 && next_rx.recv().await.is_none() {
+                return;
+            }
+            let event = format!("data: event-{i}\n\n");
+            let _ = socket.write_all(event.as_bytes()).await;
+        }
+    });
+
+    (addr, next_tx)
+}
+
+#[tokio::test]
+async fn test_event_stream_bypasses_response_buffering() {
+    let registry = PluginRegistry::new();
+    registry.register(Arc::new(RwLock::new(BufferingPlugin)));
+    assert!(registry.needs_response_buffering().await);
+
+    let (local_addr, next_event) = start_sse_server().await;
+    let http_addr = start_tunnel(
+        TUNNEL_ID,
+        HttpProxy::new(local_addr),
+        registry,
+        IngressConfig::default(),
+    )
+    .await;
+
+    let mut res = tokio::time::timeout(
+        Duration::from_secs(5),
+        make_client()
+            .get(format!("http://{http_addr}/events"))
+            .header("Host", TUNNEL_ID)
+            .header("Accept", "text/event-stream")
+            .send(),
+    )
+    .await
+    .expect("event stream head was held back")
+    .unwrap();
+    assert_eq!(res.status(), 200);
+    // The plugin ran on the head while the body streamed past it
+    assert_eq!(res.headers()["x-plugin-seen"], "streamed");
+
+    let mut received = String::new();
+    for i in 0..EVENTS {
+        let expected = format!("data: event-{i}\n\n");
+        while received.len() < expected.len() {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), res.chunk())
+                .await
+                .unwrap_or_else(|_| panic!("event {i} was not streamed"))
+                .unwrap()
+                .expect("event stream ended early");
+            received.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+        assert_eq!(received, expected);
+        received.clear();
+        let _ = next_event.send(());
+    }
+}
+
+#[tokio::test]
+async fn test_event_stream_rejected_by_response_hook() {
+    let registry = PluginRegistry::new();
+    registry.register(Arc::new(RwLock::new(RejectResponsePlugin)));
+
+    let (local_addr, _next_event) = start_sse_server().await;
+    let http_addr = start_tunnel(
+        TUNNEL_ID,
+        HttpProxy::new(local_addr),
+        registry,
+        IngressConfig::default(),
+    )
+    .await;
+
+    let res = tokio::time::timeout(
+        Duration::from_secs(5),
+        make_client()
+            .get(format!("http://{http_addr}/events"))
+            .header("Host", TUNNEL_ID)
+            .send(),
+    )
+    .await
+    .expect("rejection was held back")
+    .unwrap();
+    assert_eq!(res.status(), 451);
+    assert_eq!(res.text().await.unwrap(), "Blocked");
+}
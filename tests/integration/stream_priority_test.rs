@@ -0,0 +1,130 @@
+//! HTTP ingress stream priority policy integration tests
+
+use super::{connect_tunnel, start_ingress, start_tunnel_server, TUNNEL_TOKEN};
+use ferrotunnel_core::TunnelClient;
+use ferrotunnel_http::{HttpProxy, IngressConfig, PriorityPolicy};
+use ferrotunnel_plugin::PluginRegistry;
+use ferrotunnel_protocol::frame::StreamPriority;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::Request;
+use hyper_util::rt::TokioIo;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const TUNNEL_ID: &str = "priority";
+const PRIORITY_HEADER: &str = "x-tunnel-priority";
+
+/// Local HTTP/1.1 service that reads the body and answers whether the
+/// priority header reached it
+async fn start_local_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                break;
+            };
+            tokio::spawn(async move {
+                let service =
+                    hyper::service::service_fn(|req: Request<hyper::body::Incoming>| async move {
+                        let forwarded = req.headers().contains_key(PRIORITY_HEADER);
+                        req.into_body().collect().await?;
+                        let body = if forwarded { "forwarded" } else { "stripped" };
+                        Ok::<_, hyper::Error>(hyper::Response::new(Full::new(Bytes::from(body))))
+                    });
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+    addr
+}
+
+/// Start a tunnel whose ingress uses `policy`. Returns the ingress address
+/// and the priority of every stream the client received, in order.
+async fn start_priority_tunnel(
+    policy: PriorityPolicy,
+) -> (SocketAddr, Arc<Mutex<Vec<StreamPriority>>>) {
+    let local_addr = start_local_server().await;
+    let (server_addr, sessions) = start_tunnel_server(|server| server).await;
+    let config = IngressConfig::default().priority(policy);
+    let http_addr = start_ingress(sessions.clone(), PluginRegistry::new(), config).await;
+
+    let priorities = Arc::new(Mutex::new(Vec::new()));
+    let seen = priorities.clone();
+    let proxy = Arc::new(HttpProxy::new(local_addr));
+    let client =
+        TunnelClient::new(server_addr.to_string(), TUNNEL_TOKEN.into()).with_tunnel_id(TUNNEL_ID);
+    connect_tunnel(&sessions, TUNNEL_ID, client, move |stream| {
+        seen.lock().unwrap().push(stream.priority());
+        let proxy = proxy.clone();
+        async move { proxy.handle_stream(stream) }
+    })
+    .await;
+
+    (http_addr, priorities)
+}
+
+/// Send a request with an optional priority header and a body of `body_len`
+/// bytes. Returns the raw response.
+async fn send(http_addr: SocketAddr, priority: Option<&str>, body_len: usize) -> String {
+    let mut stream = TcpStream::connect(http_addr).await.unwrap();
+    let priority_line =
+        priority.map_or_else(String::new, |p| format!("{PRIORITY_HEADER}: {p}\r\n"));
+    let head = format!(
+        "POST /upload HTTP/1.1\r\nHost: {TUNNEL_ID}\r\n{priority_line}\
+         Content-Length: {body_len}\r\nConnection: close\r\n\r\n"
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(&vec![b'x'; body_len]).await.unwrap();
+
+    let mut response = Vec::new();
+    tokio::time::timeout(Duration::from_secs(10), stream.read_to_end(&mut response))
+        .await
+        .expect("response timed out")
+        .unwrap();
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn test_ingress_assigns_stream_priority() {
+    let policy = PriorityPolicy::default().with_header(PRIORITY_HEADER);
+    let (http_addr, priorities) = start_priority_tunnel(policy).await;
+
+    // A trusted header wins over the size rules, and is not forwarded
+    let response = send(http_addr, Some("high"), 2 * 1024 * 1024).await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    assert!(response.ends_with("stripped"), "{response}");
+    // Large upload
+    let response = send(http_addr, None, 2 * 1024 * 1024).await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    // Small request
+    send(http_addr, None, 100).await;
+    // Neither small nor large
+    send(http_addr, None, 64 * 1024).await;
+
+    assert_eq!(
+        *priorities.lock().unwrap(),
+        [
+            StreamPriority::High,
+            StreamPriority::Low,
+            StreamPriority::High,
+            StreamPriority::Normal,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_priority_header_untrusted_by_default() {
+    // Without a configured header, the client cannot pick its priority
+    let (http_addr, priorities) = start_priority_tunnel(PriorityPolicy::default()).await;
+
+    let response = send(http_addr, Some("high"), 2 * 1024 * 1024).await;
+    assert!(response.ends_with("forwarded"), "{response}");
+    assert_eq!(*priorities.lock().unwrap(), [StreamPriority::Low]);
+}
@@ -1,5 +1,13 @@
+use super::{connect_tunnel, start_tunnel_server, TUNNEL_TOKEN};
+use ferrotunnel_core::ip_filter::IpFilter;
+use ferrotunnel_core::tunnel::session::SessionStoreBackend;
 use ferrotunnel_core::{TunnelClient, TunnelServer};
-use ferrotunnel_http::TcpIngress;
+use ferrotunnel_http::{TcpAuth, TcpIngress, TcpIngressConfig};
+use ferrotunnel_protocol::frame::Protocol;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
@@ -69,3 +77,258 @@ async fn test_tcp_tunnel_echo() {
 
     assert_eq!(&buf, b"HELLO");
 }
+
+/// Start a local TCP server that replies with `tag` followed by what it read
+async fn start_tagged_server(tag: &'static [u8]) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 1024];
+                let n = socket.read(&mut buf).await.unwrap();
+                socket.write_all(tag).await.unwrap();
+                socket.write_all(&buf[..n]).await.unwrap();
+            });
+        }
+    });
+    addr
+}
+
+/// Connect a tunnel client advertising `capability` that forwards to
+/// `local_addr`, returning once it is registered under that name
+async fn start_capability_client(
+    server_addr: SocketAddr,
+    sessions: &SessionStoreBackend,
+    capability: &str,
+    local_addr: String,
+) {
+    let client = TunnelClient::new(server_addr.to_string(), TUNNEL_TOKEN.into())
+        .with_tunnel_id(capability)
+        .with_capability(capability);
+    connect_tunnel(sessions, capability, client, move |mut stream| {
+        let local_addr = local_addr.clone();
+        async move {
+            tokio::spawn(async move {
+                let mut local = TcpStream::connect(&local_addr).await.unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut stream, &mut local).await;
+            });
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_tcp_ports_route_by_capability() {
+    let alpha_addr = start_tagged_server(b"alpha:").await;
+    let beta_addr = start_tagged_server(b"beta:").await;
+
+    let (server_addr, sessions) = start_tunnel_server(|server| server).await;
+
+    let alpha_port = super::get_free_port();
+    let beta_port = super::get_free_port();
+    let config = TcpIngressConfig {
+        port_capabilities: HashMap::from([
+            (alpha_port, "alpha".to_string()),
+            (beta_port, "beta".to_string()),
+        ]),
+        ..Default::default()
+    };
+    let tcp_ingress = TcpIngress::with_config(
+        format!("127.0.0.1:{alpha_port}").parse().unwrap(),
+        sessions.clone(),
+        config,
+    );
+    tokio::spawn(async move {
+        tcp_ingress.start().await.unwrap();
+    });
+
+    start_capability_client(server_addr, &sessions, "alpha", alpha_addr).await;
+    start_capability_client(server_addr, &sessions, "beta", beta_addr).await;
+
+    let routes: [(u16, &[u8]); 2] = [(alpha_port, b"alpha:ping"), (beta_port, b"beta:ping")];
+    for (port, expected) in routes {
+        let mut conn = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        conn.write_all(b"ping").await.unwrap();
+        let mut buf = vec![0u8; expected.len()];
+        tokio::time::timeout(Duration::from_secs(5), conn.read_exact(&mut buf))
+            .await
+            .expect("timed out waiting for routed response")
+            .unwrap();
+        assert_eq!(buf, expected);
+    }
+}
+
+#[tokio::test]
+async fn test_tcp_port_skips_tunnels_without_tcp() {
+    let alpha_addr = start_tagged_server(b"alpha:").await;
+    let (server_addr, sessions) = start_tunnel_server(|server| server).await;
+
+    let alpha_port = super::get_free_port();
+    let config = TcpIngressConfig {
+        port_capabilities: HashMap::from([(alpha_port, "alpha".to_string())]),
+        ..Default::default()
+    };
+    let tcp_ingress = TcpIngress::with_config(
+        format!("127.0.0.1:{alpha_port}").parse().unwrap(),
+        sessions.clone(),
+        config,
+    );
+    tokio::spawn(async move {
+        tcp_ingress.start().await.unwrap();
+    });
+
+    // An HTTP-only client holding the port's capability
+    let http_streams = Arc::new(AtomicUsize::new(0));
+    let counted = http_streams.clone();
+    let http_only = TunnelClient::new(server_addr.to_string(), TUNNEL_TOKEN.into())
+        .with_tunnel_id("http-only")
+        .with_stream_protocols(&[Protocol::HTTP])
+        .with_capability("alpha");
+    connect_tunnel(&sessions, "http-only", http_only, move |_stream| {
+        counted.fetch_add(1, Ordering::SeqCst);
+        async {}
+    })
+    .await;
+
+    // Rejected at the ingress instead of reaching a client that cannot
+    // handle raw TCP
+    let mut conn = TcpStream::connect(("127.0.0.1", alpha_port)).await.unwrap();
+    let _ = conn.write_all(b"ping").await;
+    let mut buf = Vec::new();
+    let closed = tokio::time::timeout(Duration::from_secs(5), conn.read_to_end(&mut buf))
+        .await
+        .expect("connection was not rejected");
+    assert!(closed.is_err() || buf.is_empty());
+    assert_eq!(http_streams.load(Ordering::SeqCst), 0);
+
+    // A TCP-capable client with the same capability takes the port
+    start_capability_client(server_addr, &sessions, "alpha", alpha_addr).await;
+
+    let mut conn = TcpStream::connect(("127.0.0.1", alpha_port)).await.unwrap();
+    conn.write_all(b"ping").await.unwrap();
+    let mut buf = [0u8; 10];
+    tokio::time::timeout(Duration::from_secs(5), conn.read_exact(&mut buf))
+        .await
+        .expect("timed out waiting for routed response")
+        .unwrap();
+    assert_eq!(&buf, b"alpha:ping");
+    assert_eq!(http_streams.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_tcp_half_close_keeps_reverse_direction() {
+    // Local service that reads the whole request (until the peer half-closes)
+    // before answering, like SMTP's QUIT or an FTP upload
+    let local_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let local_addr = local_listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        let (mut socket, _) = local_listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        socket.read_to_end(&mut request).await.unwrap();
+        let reply = format!("received {} bytes", request.len());
+        socket.write_all(reply.as_bytes()).await.unwrap();
+    });
+
+    let (server_addr, sessions) = start_tunnel_server(|server| server).await;
+
+    let tcp_port = super::get_free_port();
+    let tcp_ingress = TcpIngress::new(
+        format!("127.0.0.1:{tcp_port}").parse().unwrap(),
+        sessions.clone(),
+    );
+    tokio::spawn(async move {
+        tcp_ingress.start().await.unwrap();
+    });
+
+    let client =
+        TunnelClient::new(server_addr.to_string(), TUNNEL_TOKEN.into()).with_tunnel_id("half");
+    connect_tunnel(&sessions, "half", client, move |mut stream| {
+        let local_addr = local_addr.clone();
+        async move {
+            let mut local = TcpStream::connect(&local_addr).await.unwrap();
+            let _ = tokio::io::copy_bidirectional(&mut stream, &mut local).await;
+        }
+    })
+    .await;
+
+    let mut conn = TcpStream::connect(("127.0.0.1", tcp_port)).await.unwrap();
+    conn.write_all(&vec![b'x'; 100_000]).await.unwrap();
+    // Half-close: done writing, still reading
+    conn.shutdown().await.unwrap();
+
+    let mut reply = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), conn.read_to_end(&mut reply))
+        .await
+        .expect("timed out waiting for reply after half-close")
+        .unwrap();
+    assert_eq!(reply, b"received 100000 bytes");
+}
+
+/// Start a tunnel whose TCP ingress is gated by `auth`, forwarding to a
+/// tagged local service. Returns the ingress port.
+async fn start_gated_tunnel(auth: TcpAuth) -> u16 {
+    let db_addr = start_tagged_server(b"db:").await;
+    let (server_addr, sessions) = start_tunnel_server(|server| server).await;
+
+    let tcp_port = super::get_free_port();
+    let config = TcpIngressConfig {
+        port_auth: HashMap::from([(tcp_port, auth)]),
+        ..Default::default()
+    };
+    let tcp_ingress = TcpIngress::with_config(
+        format!("127.0.0.1:{tcp_port}").parse().unwrap(),
+        sessions.clone(),
+        config,
+    );
+    tokio::spawn(async move {
+        tcp_ingress.start().await.unwrap();
+    });
+
+    start_capability_client(server_addr, &sessions, "tcp", db_addr).await;
+    tcp_port
+}
+
+/// Send `preamble` then `ping` and return everything read until close
+async fn gated_exchange(tcp_port: u16, preamble: &[u8]) -> Vec<u8> {
+    let mut conn = TcpStream::connect(("127.0.0.1", tcp_port)).await.unwrap();
+    conn.write_all(preamble).await.unwrap();
+    // A rejected connection may already be closed
+    let _ = conn.write_all(b"ping").await;
+    let mut reply = Vec::new();
+    let _ = tokio::time::timeout(Duration::from_secs(5), conn.read_to_end(&mut reply))
+        .await
+        .expect("gated connection was not closed");
+    reply
+}
+
+#[tokio::test]
+async fn test_tcp_auth_secret_accepts_and_rejects() {
+    let tcp_port = start_gated_tunnel(TcpAuth {
+        secret: Some(b"s3cret".to_vec()),
+        ..Default::default()
+    })
+    .await;
+
+    // The secret is stripped; the service only sees the payload
+    assert_eq!(gated_exchange(tcp_port, b"s3cret").await, b"db:ping");
+    assert!(gated_exchange(tcp_port, b"wrong!").await.is_empty());
+}
+
+#[tokio::test]
+async fn test_tcp_auth_ip_filter_rejects_outside_allowlist() {
+    let allowed = start_gated_tunnel(TcpAuth {
+        ip_filter: IpFilter::from_lists(["127.0.0.0/8"], Vec::<&str>::new()).unwrap(),
+        ..Default::default()
+    })
+    .await;
+    assert_eq!(gated_exchange(allowed, b"").await, b"db:ping");
+
+    let denied = start_gated_tunnel(TcpAuth {
+        ip_filter: IpFilter::from_lists(["10.0.0.0/8"], Vec::<&str>::new()).unwrap(),
+        ..Default::default()
+    })
+    .await;
+    assert!(gated_exchange(denied, b"").await.is_empty());
+}
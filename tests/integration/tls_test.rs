@@ -3,7 +3,11 @@
 use super::{wait_for_server, TestConfig};
 use ferrotunnel::{Client, Server};
 use ferrotunnel_common::config::TlsConfig;
+use ferrotunnel_common::TunnelError;
+use ferrotunnel_core::{TunnelClient, TunnelServer};
 use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 /// Test client connecting to server over TLS
@@ -93,3 +97,273 @@ async fn test_tls_connection() {
     let _ = client.shutdown().await;
     let _ = std::fs::remove_dir_all(temp_dir);
 }
+
+/// Make two sequential TLS connections and return how each handshake went
+async fn handshake_kinds(session_resumption: bool) -> Vec<Option<rustls::HandshakeKind>> {
+    use ferrotunnel_core::transport::tls::{accept_tls, connect_tls, TlsTransportConfig};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let _ = rustls::crypto::ring::default_provider()
+        .install_default()
+        .ok();
+
+    let temp_dir = std::env::temp_dir().join(format!(
+        "ferrotunnel_test_tls_resume_{}",
+        uuid::Uuid::new_v4()
+    ));
+    std::fs::create_dir_all(&temp_dir).unwrap();
+    let (cert_pem, key_pem) =
+        super::generate_self_signed_cert(vec!["localhost".to_string(), "127.0.0.1".to_string()]);
+    let cert_path = temp_dir.join("server.crt");
+    let key_path = temp_dir.join("server.key");
+    std::fs::write(&cert_path, cert_pem).unwrap();
+    std::fs::write(&key_path, key_pem).unwrap();
+
+    let server_config = TlsTransportConfig {
+        cert_path: cert_path.to_string_lossy().to_string(),
+        key_path: key_path.to_string_lossy().to_string(),
+        session_resumption,
+        ..Default::default()
+    };
+    let client_config = TlsTransportConfig {
+        ca_cert_path: Some(cert_path.to_string_lossy().to_string()),
+        server_name: Some("localhost".to_string()),
+        session_resumption,
+        ..Default::default()
+    };
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        loop {
+            let Ok((tcp, _)) = listener.accept().await else {
+                break;
+            };
+            let config = server_config.clone();
+            tokio::spawn(async move {
+                let mut tls = accept_tls(tcp, &config).await.unwrap();
+                // Session tickets go out ahead of this byte
+                tls.write_all(b"x").await.unwrap();
+                tls.flush().await.unwrap();
+                let mut rest = Vec::new();
+                let _ = tls.read_to_end(&mut rest).await;
+            });
+        }
+    });
+
+    let mut kinds = Vec::new();
+    for _ in 0..2 {
+        let mut tls = connect_tls(&addr, &client_config, Vec::new())
+            .await
+            .unwrap();
+        let mut byte = [0u8; 1];
+        tls.read_exact(&mut byte).await.unwrap();
+        kinds.push(tls.get_ref().1.handshake_kind());
+        tls.shutdown().await.unwrap();
+    }
+
+    let _ = std::fs::remove_dir_all(temp_dir);
+    kinds
+}
+
+#[tokio::test]
+async fn test_tls_session_resumption() {
+    let kinds = handshake_kinds(true).await;
+    assert_eq!(kinds[0], Some(rustls::HandshakeKind::Full));
+    assert_eq!(kinds[1], Some(rustls::HandshakeKind::Resumed));
+}
+
+#[tokio::test]
+async fn test_tls_session_resumption_disabled_by_default() {
+    let kinds = handshake_kinds(false).await;
+    assert_eq!(kinds[1], Some(rustls::HandshakeKind::Full));
+}
+
+/// Write a CA, a server certificate for `localhost` and one client
+/// certificate per name (CN `<name>`, SAN `<name>.clients.test`), all signed
+/// by the CA, into a fresh temp directory
+fn write_mtls_certs(clients: &[&str]) -> PathBuf {
+    use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, Issuer, KeyPair};
+
+    let dir = std::env::temp_dir().join(format!("ferrotunnel_test_mtls_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params
+        .distinguished_name
+        .push(DnType::CommonName, "FerroTunnel Test CA");
+    let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+    std::fs::write(dir.join("ca.crt"), ca_cert.pem()).unwrap();
+    let issuer = Issuer::new(ca_params, ca_key);
+
+    let leaves = std::iter::once(("server", "localhost".to_string())).chain(
+        clients
+            .iter()
+            .map(|name| (*name, format!("{name}.clients.test"))),
+    );
+    for (name, san) in leaves {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(vec![san]).unwrap();
+        params.distinguished_name.push(DnType::CommonName, name);
+        let cert = params.signed_by(&key, &issuer).unwrap();
+        std::fs::write(dir.join(format!("{name}.crt")), cert.pem()).unwrap();
+        std::fs::write(dir.join(format!("{name}.key")), key.serialize_pem()).unwrap();
+    }
+    dir
+}
+
+fn mtls_client(addr: SocketAddr, dir: &Path, name: &str) -> TunnelClient {
+    TunnelClient::new(addr.to_string(), "test-token".into())
+        .with_tls_ca(dir.join("ca.crt"))
+        .with_server_name("localhost")
+        .with_tls(
+            dir.join(format!("{name}.crt")),
+            dir.join(format!("{name}.key")),
+        )
+        .with_tunnel_id("team-a")
+}
+
+/// Only the certificate the authorizer accepts may register the tunnel ID
+#[tokio::test]
+async fn test_mtls_authorizer_checks_certificate_identity() {
+    let _ = rustls::crypto::ring::default_provider()
+        .install_default()
+        .ok();
+    let dir = write_mtls_certs(&["agent-a", "agent-b"]);
+    let addr: SocketAddr = format!("127.0.0.1:{}", super::get_free_port())
+        .parse()
+        .unwrap();
+
+    let server = TunnelServer::new(addr, "test-token".into())
+        .with_tls(dir.join("server.crt"), dir.join("server.key"))
+        .with_client_auth(dir.join("ca.crt"))
+        .with_authorizer(|identity, tunnel_id| {
+            identity.common_name.as_deref() == Some("agent-a") && tunnel_id == "team-a"
+        });
+    let sessions = server.sessions();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    assert!(wait_for_server(addr, Duration::from_secs(5)).await);
+
+    // agent-b has a valid certificate but is not allowed to use team-a
+    let mut rejected = mtls_client(addr, &dir, "agent-b");
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        rejected.connect_and_run(|_| async {}),
+    )
+    .await
+    .expect("rejected handshake timed out");
+    assert!(
+        matches!(result, Err(TunnelError::Authentication(_))),
+        "{result:?}"
+    );
+    assert!(sessions.get_by_tunnel_id("team-a").is_none());
+
+    let mut accepted = mtls_client(addr, &dir, "agent-a");
+    tokio::spawn(async move {
+        let _ = accepted.connect_and_run(|_| async {}).await;
+    });
+    let mut identity = None;
+    for _ in 0..50 {
+        if let Some(session) = sessions.get_by_tunnel_id("team-a") {
+            identity.clone_from(&session.peer_identity);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let identity = identity.expect("agent-a session not registered with an identity");
+    assert_eq!(identity.common_name.as_deref(), Some("agent-a"));
+    assert!(
+        identity.subject.contains("CN=agent-a"),
+        "{}",
+        identity.subject
+    );
+    assert_eq!(identity.sans, vec!["agent-a.clients.test".to_string()]);
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+/// Run one TLS handshake with the given ALPN settings and return the
+/// protocol each side ended up with (or the error it failed with)
+async fn alpn_handshake(
+    server_alpn: &[&str],
+    client_alpn: &[&str],
+) -> (
+    std::io::Result<Option<Vec<u8>>>,
+    std::io::Result<Option<Vec<u8>>>,
+) {
+    use ferrotunnel_core::transport::tls::{accept_tls, connect_tls, TlsTransportConfig};
+
+    let _ = rustls::crypto::ring::default_provider()
+        .install_default()
+        .ok();
+
+    let temp_dir =
+        std::env::temp_dir().join(format!("ferrotunnel_test_alpn_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&temp_dir).unwrap();
+    let (cert_pem, key_pem) = super::generate_self_signed_cert(vec!["localhost".to_string()]);
+    let cert_path = temp_dir.join("server.crt");
+    let key_path = temp_dir.join("server.key");
+    std::fs::write(&cert_path, cert_pem).unwrap();
+    std::fs::write(&key_path, key_pem).unwrap();
+
+    let to_vec = |ids: &[&str]| ids.iter().map(ToString::to_string).collect();
+    let server_config = TlsTransportConfig {
+        cert_path: cert_path.to_string_lossy().to_string(),
+        key_path: key_path.to_string_lossy().to_string(),
+        ..Default::default()
+    }
+    .alpn(to_vec(server_alpn));
+    let client_config = TlsTransportConfig {
+        ca_cert_path: Some(cert_path.to_string_lossy().to_string()),
+        server_name: Some("localhost".to_string()),
+        ..Default::default()
+    }
+    .alpn(to_vec(client_alpn));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let server = tokio::spawn(async move {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let tls = accept_tls(tcp, &server_config).await?;
+        Ok::<_, std::io::Error>(tls.get_ref().1.alpn_protocol().map(<[u8]>::to_vec))
+    });
+
+    let client = connect_tls(&addr, &client_config, Vec::new())
+        .await
+        .map(|tls| tls.get_ref().1.alpn_protocol().map(<[u8]>::to_vec));
+
+    let server = server.await.unwrap();
+    let _ = std::fs::remove_dir_all(temp_dir);
+    (server, client)
+}
+
+#[tokio::test]
+async fn test_tls_custom_alpn_negotiated() {
+    let (server, client) = alpn_handshake(&["ferrotunnel/1"], &["ferrotunnel/1"]).await;
+    assert_eq!(server.unwrap(), Some(b"ferrotunnel/1".to_vec()));
+    assert_eq!(client.unwrap(), Some(b"ferrotunnel/1".to_vec()));
+}
+
+#[tokio::test]
+async fn test_tls_alpn_mismatch_rejected() {
+    // Client offers a different protocol: the handshake fails
+    let (server, client) = alpn_handshake(&["ferrotunnel/1"], &["http/1.1"]).await;
+    assert!(server.is_err());
+    assert!(client.is_err());
+
+    // Client offers no ALPN at all: the server drops the connection
+    let (server, _) = alpn_handshake(&["ferrotunnel/1"], &[]).await;
+    let err = server.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+}
+
+#[tokio::test]
+async fn test_tls_without_alpn_by_default() {
+    let (server, client) = alpn_handshake(&[], &[]).await;
+    assert_eq!(server.unwrap(), None);
+    assert_eq!(client.unwrap(), None);
+}
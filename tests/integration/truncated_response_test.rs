@@ -0,0 +1,97 @@
+//! Truncated response propagation integration tests
+
+use super::{make_client, start_tunnel, BufferingPlugin};
+use ferrotunnel_http::{HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+
+const TUNNEL_ID: &str = "app";
+const PARTIAL_BODY: &[u8] = b"first part";
+
+/// Local service that declares a 1000 byte body, sends only a few bytes
+/// of it and then drops the connection
+async fn start_failing_origin() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let head = "HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n";
+                let _ = socket.write_all(head.as_bytes()).await;
+                let _ = socket.write_all(PARTIAL_BODY).await;
+                // Let the head reach the ingress before the failure
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            });
+        }
+    });
+
+    addr
+}
+
+#[tokio::test]
+async fn test_reset_response_is_bad_gateway_when_buffered() {
+    let registry = PluginRegistry::new();
+    registry.register(Arc::new(RwLock::new(BufferingPlugin)));
+    let http_addr = start_tunnel(
+        TUNNEL_ID,
+        HttpProxy::new(start_failing_origin().await),
+        registry,
+        IngressConfig::default(),
+    )
+    .await;
+
+    let response = tokio::time::timeout(
+        Duration::from_secs(5),
+        make_client()
+            .get(format!("http://{http_addr}/"))
+            .header("Host", TUNNEL_ID)
+            .send(),
+    )
+    .await
+    .expect("reset did not reach the ingress")
+    .unwrap();
+    assert_eq!(response.status(), 502);
+    assert_ne!(response.bytes().await.unwrap(), PARTIAL_BODY);
+}
+
+#[tokio::test]
+async fn test_reset_response_fails_streamed_body() {
+    let http_addr = start_tunnel(
+        TUNNEL_ID,
+        HttpProxy::new(start_failing_origin().await),
+        PluginRegistry::new(),
+        IngressConfig::default(),
+    )
+    .await;
+
+    let response = tokio::time::timeout(
+        Duration::from_secs(5),
+        make_client()
+            .get(format!("http://{http_addr}/"))
+            .header("Host", TUNNEL_ID)
+            .send(),
+    )
+    .await
+    .expect("response head was held back")
+    .unwrap();
+    // The head was already forwarded, so the client sees the body fail
+    // rather than a complete 200
+    assert_eq!(response.status(), 200);
+    let body = tokio::time::timeout(Duration::from_secs(5), response.bytes())
+        .await
+        .expect("reset did not reach the client");
+    assert!(body.is_err());
+}
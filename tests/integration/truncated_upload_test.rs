@@ -0,0 +1,72 @@
+//! Truncated upload propagation integration tests
+
+use super::start_tunnel;
+use ferrotunnel_http::{HttpProxy, IngressConfig};
+use ferrotunnel_plugin::PluginRegistry;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+const TUNNEL_ID: &str = "app";
+const DECLARED_LENGTH: usize = 1024 * 1024;
+
+/// Local service that reports how many body bytes each request delivered
+/// before its connection ended
+async fn start_origin() -> (String, mpsc::UnboundedReceiver<usize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let mut received = Vec::new();
+                let mut buf = [0u8; 16 * 1024];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    if n == 0 {
+                        break;
+                    }
+                    received.extend_from_slice(&buf[..n]);
+                }
+                let body_start = received
+                    .windows(4)
+                    .position(|w| w == b"\r\n\r\n")
+                    .map_or(received.len(), |pos| pos + 4);
+                let _ = tx.send(received.len() - body_start);
+            });
+        }
+    });
+
+    (addr, rx)
+}
+
+#[tokio::test]
+async fn test_client_disconnect_mid_upload_reaches_local_service() {
+    let (local_addr, mut delivered) = start_origin().await;
+    let http_addr = start_tunnel(
+        TUNNEL_ID,
+        HttpProxy::new(local_addr),
+        PluginRegistry::new(),
+        IngressConfig::default(),
+    )
+    .await;
+
+    let mut upload = TcpStream::connect(http_addr).await.unwrap();
+    let head = format!(
+        "POST /upload HTTP/1.1\r\nHost: {TUNNEL_ID}\r\nContent-Length: {DECLARED_LENGTH}\r\n\r\n"
+    );
+    upload.write_all(head.as_bytes()).await.unwrap();
+    upload.write_all(&vec![b'x'; 64 * 1024]).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    drop(upload);
+
+    // Without the abort, the tunnel stream would stay open and the local
+    // service would wait for the rest of the body indefinitely
+    let received = tokio::time::timeout(Duration::from_secs(5), delivered.recv())
+        .await
+        .expect("local connection should be torn down")
+        .unwrap();
+    assert!(received < DECLARED_LENGTH, "received {received} bytes");
+}
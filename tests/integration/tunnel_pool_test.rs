@@ -0,0 +1,180 @@
+//! Tunnel pool integration tests
+//!
+//! Several clients registering one tunnel ID behind a server in pool mode.
+
+use super::{get_free_port, make_client, wait_for_server};
+use ferrotunnel_core::stream::Multiplexer;
+use ferrotunnel_core::tunnel::session::{PoolPolicy, Session, SessionStoreBackend};
+use ferrotunnel_core::{TunnelClient, TunnelServer};
+use ferrotunnel_http::{HttpIngress, HttpProxy};
+use ferrotunnel_plugin::PluginRegistry;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+const TUNNEL_ID: &str = "pool";
+
+/// Local HTTP service answering every request with `name`
+async fn start_named_server(name: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut socket, _)) = listener.accept().await else {
+                break;
+            };
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 4096];
+                if socket.read(&mut buf).await.unwrap_or(0) > 0 {
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{name}",
+                        name.len()
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                }
+            });
+        }
+    });
+
+    addr
+}
+
+/// Start a tunnel server and HTTP ingress; returns both addresses and the
+/// server's session store
+async fn start_server(policy: Option<PoolPolicy>) -> (SocketAddr, SocketAddr, SessionStoreBackend) {
+    let server_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let http_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+
+    let mut server = TunnelServer::new(server_addr, "test-token".into());
+    if let Some(policy) = policy {
+        server = server.with_pool_policy(policy);
+    }
+    let sessions = server.sessions();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    assert!(wait_for_server(server_addr, Duration::from_secs(5)).await);
+
+    let ingress = HttpIngress::new(http_addr, sessions.clone(), Arc::new(PluginRegistry::new()));
+    tokio::spawn(async move {
+        let _ = ingress.start().await;
+    });
+    assert!(wait_for_server(http_addr, Duration::from_secs(5)).await);
+
+    (server_addr, http_addr, sessions)
+}
+
+/// Connect a client registering `TUNNEL_ID` that forwards to `local_addr`
+fn spawn_client(
+    server_addr: SocketAddr,
+    local_addr: String,
+) -> JoinHandle<ferrotunnel_common::Result<()>> {
+    let proxy = Arc::new(HttpProxy::new(local_addr));
+    let mut client =
+        TunnelClient::new(server_addr.to_string(), "test-token".into()).with_tunnel_id(TUNNEL_ID);
+    tokio::spawn(async move {
+        client
+            .connect_and_run(move |stream| {
+                let proxy = proxy.clone();
+                async move { proxy.handle_stream(stream) }
+            })
+            .await
+    })
+}
+
+async fn wait_for_sessions(sessions: &SessionStoreBackend, count: usize) {
+    for _ in 0..100 {
+        if sessions.count() == count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("expected {count} sessions, found {}", sessions.count());
+}
+
+/// Send `n` requests through the ingress and count the answering backends
+async fn tally_backends(http_addr: SocketAddr, n: usize) -> HashMap<String, usize> {
+    let client = make_client();
+    let mut tally = HashMap::new();
+    for _ in 0..n {
+        let res = client
+            .get(format!("http://{http_addr}/"))
+            .header("Host", TUNNEL_ID)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        *tally.entry(res.text().await.unwrap()).or_insert(0) += 1;
+    }
+    tally
+}
+
+#[tokio::test]
+async fn test_round_robin_spreads_requests_across_clients() {
+    let (server_addr, http_addr, sessions) = start_server(Some(PoolPolicy::RoundRobin)).await;
+    let _blue = spawn_client(server_addr, start_named_server("blue").await);
+    let _green = spawn_client(server_addr, start_named_server("green").await);
+    wait_for_sessions(&sessions, 2).await;
+
+    let tally = tally_backends(http_addr, 10).await;
+    assert_eq!(tally.get("blue"), Some(&5), "{tally:?}");
+    assert_eq!(tally.get("green"), Some(&5), "{tally:?}");
+}
+
+#[tokio::test]
+async fn test_least_streams_uses_every_client() {
+    let (server_addr, http_addr, sessions) = start_server(Some(PoolPolicy::LeastStreams)).await;
+    let _blue = spawn_client(server_addr, start_named_server("blue").await);
+    let _green = spawn_client(server_addr, start_named_server("green").await);
+    wait_for_sessions(&sessions, 2).await;
+
+    // Idle backends tie, and ties are broken round-robin
+    let tally = tally_backends(http_addr, 10).await;
+    assert!(tally.get("blue").is_some_and(|n| *n > 0), "{tally:?}");
+    assert!(tally.get("green").is_some_and(|n| *n > 0), "{tally:?}");
+}
+
+#[tokio::test]
+async fn test_requests_retry_on_another_client_when_one_is_gone() {
+    let (server_addr, http_addr, sessions) = start_server(Some(PoolPolicy::RoundRobin)).await;
+    let _blue = spawn_client(server_addr, start_named_server("blue").await);
+    wait_for_sessions(&sessions, 1).await;
+
+    // A pool member whose connection has already gone: opening a stream on
+    // its multiplexer fails
+    let (frame_tx, frame_rx) = kanal::bounded_async(1);
+    drop(frame_rx);
+    let (dead, _) = Multiplexer::new(frame_tx, false);
+    let dead_session = Session::new(
+        uuid::Uuid::new_v4(),
+        TUNNEL_ID.into(),
+        "127.0.0.1:1".parse().unwrap(),
+        "test-token".into(),
+        vec![],
+        Some(dead),
+    );
+    sessions.add(dead_session).unwrap();
+
+    let tally = tally_backends(http_addr, 6).await;
+    assert_eq!(tally.get("blue"), Some(&6), "{tally:?}");
+}
+
+#[tokio::test]
+async fn test_duplicate_tunnel_id_rejected_without_pool() {
+    let (server_addr, _http_addr, sessions) = start_server(None).await;
+    let _blue = spawn_client(server_addr, start_named_server("blue").await);
+    wait_for_sessions(&sessions, 1).await;
+
+    let green = spawn_client(server_addr, start_named_server("green").await);
+    let result = tokio::time::timeout(Duration::from_secs(5), green)
+        .await
+        .expect("second client should be turned away")
+        .unwrap();
+    assert!(result.is_err());
+    assert_eq!(sessions.count(), 1);
+}
@@ -206,3 +206,134 @@ async fn test_large_payload() {
 
     let _ = client.shutdown().await;
 }
+
+/// Connected tunnels are visible through the server's directory
+#[tokio::test]
+async fn test_server_lists_connected_tunnels() {
+    let config = TestConfig::default();
+    let _echo_handle = start_echo_server(config.local_service_addr).await;
+
+    let mut server = Server::builder()
+        .bind(config.server_addr)
+        .http_bind(config.http_addr)
+        .token(config.token)
+        .build()
+        .expect("Failed to build server");
+    let directory = server.directory();
+    let server_handle = tokio::spawn(async move { server.start().await });
+    assert!(wait_for_server(config.server_addr, Duration::from_secs(5)).await);
+    assert!(directory.tunnels().is_empty());
+
+    let mut client = Client::builder()
+        .server_addr(config.server_addr.to_string())
+        .token(config.token)
+        .local_addr(config.local_service_addr.to_string())
+        .tunnel_id("listed")
+        .build()
+        .expect("Failed to build client");
+    let info = client.start().await.expect("Client failed to connect");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let tunnels = directory.tunnels();
+    assert_eq!(tunnels.len(), 1);
+    let tunnel = &tunnels[0];
+    assert_eq!(tunnel.tunnel_id, "listed");
+    assert_eq!(Some(tunnel.session_id), info.session_id);
+    assert!(tunnel.client_addr.ip().is_loopback());
+    assert!(tunnel.capabilities.iter().any(|c| c == "websocket"));
+    assert!(tunnel.last_heartbeat_age <= tunnel.uptime);
+
+    let single = directory.tunnel("listed").expect("tunnel should be listed");
+    assert_eq!(single.session_id, tunnel.session_id);
+    assert!(directory.tunnel("other").is_none());
+
+    let json = serde_json::to_value(tunnel).unwrap();
+    assert_eq!(json["tunnel_id"], "listed");
+
+    let _ = client.shutdown().await;
+    server_handle.abort();
+}
+
+/// Test that the client learns its public URL from the server
+#[tokio::test]
+async fn test_client_receives_public_url() {
+    let config = TestConfig::default();
+
+    let mut server = Server::builder()
+        .bind(config.server_addr)
+        .http_bind(config.http_addr)
+        .token(config.token)
+        .public_base("https://tunnel.example.com:8443/")
+        .build()
+        .expect("Failed to build server");
+    let _server_handle = tokio::spawn(async move {
+        let _ = server.start().await;
+    });
+    assert!(
+        wait_for_server(config.server_addr, Duration::from_secs(5)).await,
+        "Server did not start"
+    );
+
+    let mut client = Client::builder()
+        .server_addr(config.server_addr.to_string())
+        .token(config.token)
+        .local_addr(config.local_service_addr.to_string())
+        .tunnel_id("myapp")
+        .build()
+        .expect("Failed to build client");
+
+    let info = client.start().await.expect("Client failed to connect");
+    assert_eq!(
+        info.public_url.as_deref(),
+        Some("https://myapp.tunnel.example.com:8443")
+    );
+
+    let _ = client.shutdown().await;
+}
+
+/// Test binding port 0 and reading back the assigned addresses
+#[tokio::test]
+async fn test_server_reports_bound_addresses() {
+    let config = TestConfig::default();
+    let _echo_handle = start_echo_server(config.local_service_addr).await;
+
+    let mut server = Server::builder()
+        .bind("127.0.0.1:0".parse().unwrap())
+        .http_bind("127.0.0.1:0".parse().unwrap())
+        .token(config.token)
+        .build()
+        .expect("Failed to build server");
+    assert_eq!(server.control_addr(), None);
+
+    server.bind_now().await.expect("Failed to bind");
+    let control_addr = server.control_addr().expect("control address");
+    let http_addr = server.http_addr().expect("HTTP address");
+    assert_ne!(control_addr.port(), 0);
+    assert_ne!(http_addr.port(), 0);
+    assert_eq!(server.tcp_addr(), None);
+
+    // The listeners are held, so clients can connect before start() runs
+    let _server_handle = tokio::spawn(async move {
+        let _ = server.start().await;
+    });
+
+    let mut client = Client::builder()
+        .server_addr(control_addr.to_string())
+        .token(config.token)
+        .local_addr(config.local_service_addr.to_string())
+        .tunnel_id("bound")
+        .build()
+        .expect("Failed to build client");
+    // Returns once the server has registered the tunnel
+    client.start().await.expect("Client failed to connect");
+
+    let response = super::make_client()
+        .get(format!("http://{http_addr}/"))
+        .header("Host", "bound")
+        .send()
+        .await
+        .expect("Failed to send HTTP request");
+    assert_eq!(response.status(), 200);
+
+    let _ = client.shutdown().await;
+}
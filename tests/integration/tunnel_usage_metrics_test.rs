@@ -0,0 +1,59 @@
+//! Per-tunnel usage metrics integration tests
+//!
+//! Metrics are process-wide, so the test uses its own tunnel ID and checks
+//! that tunnel's counters only.
+
+use super::{get_free_port, make_client, start_echo_server, start_tunnel};
+use ferrotunnel_http::{HttpProxy, IngressConfig};
+use ferrotunnel_observability::{
+    init_metrics, tunnel_metrics, TunnelUsageMetrics, DEFAULT_MAX_TUNNEL_LABELS,
+};
+use ferrotunnel_plugin::PluginRegistry;
+use std::net::SocketAddr;
+
+const TUNNEL_ID: &str = "usage-metrics";
+
+fn usage() -> &'static TunnelUsageMetrics {
+    init_metrics();
+    let metrics = tunnel_metrics().unwrap();
+    metrics.enable_tunnel_usage(DEFAULT_MAX_TUNNEL_LABELS);
+    metrics.tunnel_usage().unwrap()
+}
+
+/// Start a server, ingress and client for `TUNNEL_ID` in front of an echo
+/// service, returning the ingress address
+async fn start_echo_tunnel() -> SocketAddr {
+    let local_addr: SocketAddr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let _echo = start_echo_server(local_addr).await;
+    start_tunnel(
+        TUNNEL_ID,
+        HttpProxy::new(local_addr.to_string()),
+        PluginRegistry::new(),
+        IngressConfig::default(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_requests_and_bytes_counted_per_tunnel() {
+    let usage = usage();
+    let http_addr = start_echo_tunnel().await;
+
+    for _ in 0..3 {
+        let response = make_client()
+            .post(format!("http://{http_addr}/upload"))
+            .header("Host", TUNNEL_ID)
+            .body("payload")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.text().await.unwrap(), "Hello, World!");
+    }
+
+    assert!((usage.requests(TUNNEL_ID) - 3.0).abs() < f64::EPSILON);
+    // Requests go out to the client and responses come back in
+    assert!(usage.bytes(TUNNEL_ID, "out") > 0.0);
+    assert!(usage.bytes(TUNNEL_ID, "in") >= 3.0 * "Hello, World!".len() as f64);
+    assert!(usage.labelled_tunnels() <= DEFAULT_MAX_TUNNEL_LABELS);
+}
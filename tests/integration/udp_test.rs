@@ -0,0 +1,63 @@
+use super::{connect_tunnel, get_free_port, start_tunnel_server, TUNNEL_TOKEN};
+use ferrotunnel_core::TunnelClient;
+use ferrotunnel_http::udp_ingress::relay_udp_stream;
+use ferrotunnel_http::UdpIngress;
+use ferrotunnel_protocol::frame::Protocol;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+#[tokio::test]
+async fn test_udp_tunnel_echo() {
+    // 1. Start local UDP echo server
+    let echo_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let echo_addr = echo_socket.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let mut buf = vec![0u8; 2048];
+        loop {
+            let (n, peer) = echo_socket.recv_from(&mut buf).await.unwrap();
+            echo_socket.send_to(&buf[..n], peer).await.unwrap();
+        }
+    });
+
+    // 2. Start tunnel server
+    let (server_addr, sessions) = start_tunnel_server(|server| server).await;
+
+    // 3. Start UDP ingress
+    let udp_addr = format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let udp_ingress = UdpIngress::new(udp_addr, sessions.clone());
+
+    tokio::spawn(async move {
+        udp_ingress.start().await.unwrap();
+    });
+
+    // 4. Start tunnel client relaying UDP streams to the echo server
+    let client =
+        TunnelClient::new(server_addr.to_string(), TUNNEL_TOKEN.into()).with_tunnel_id("udp-echo");
+    let local_addr = echo_addr.to_string();
+    connect_tunnel(&sessions, "udp-echo", client, move |stream| {
+        let local_addr = local_addr.clone();
+        async move {
+            assert_eq!(stream.protocol(), Protocol::UDP);
+            tokio::spawn(async move {
+                let _ = relay_udp_stream(stream, &local_addr, Duration::from_secs(5)).await;
+            });
+        }
+    })
+    .await;
+
+    // 5. Send several datagrams and expect each to come back intact
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(udp_addr).await.unwrap();
+
+    let mut buf = vec![0u8; 2048];
+    let payloads: [&[u8]; 3] = [b"ping", b"hello world", &[0xAB; 1200]];
+    for payload in payloads {
+        socket.send(payload).await.unwrap();
+        let n = tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut buf))
+            .await
+            .expect("timed out waiting for echoed datagram")
+            .unwrap();
+        assert_eq!(&buf[..n], payload);
+    }
+}
@@ -1,4 +1,7 @@
-use super::{get_free_port, wait_for_server};
+use super::{
+    connect_tunnel, get_free_port, start_ingress, start_tunnel_server, wait_for_server,
+    TUNNEL_TOKEN,
+};
 use ferrotunnel::{Client, Server};
 use futures_util::{SinkExt, StreamExt};
 use std::time::Duration;
@@ -443,3 +446,377 @@ async fn test_websocket_failed_upgrade_404() {
 
     let _ = client.shutdown().await;
 }
+
+/// Start a tunnel whose ingress applies `limits` to WebSocket upgrades, in
+/// front of a WS echo server. Returns the ingress address.
+async fn start_limited_tunnel(limits: ferrotunnel_http::WebSocketLimits) -> std::net::SocketAddr {
+    use ferrotunnel_http::IngressConfig;
+    use ferrotunnel_plugin::PluginRegistry;
+
+    let local_addr: std::net::SocketAddr =
+        format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let _ws_handle = start_ws_echo_server(local_addr).await;
+
+    let config = IngressConfig {
+        websocket: limits,
+        ..Default::default()
+    };
+    start_ws_tunnel(local_addr, config, PluginRegistry::new()).await
+}
+
+/// Start a tunnel with tunnel ID "ws" forwarding to `local_addr`, whose
+/// ingress uses `config` and `registry`. Returns the ingress address.
+async fn start_ws_tunnel(
+    local_addr: std::net::SocketAddr,
+    config: ferrotunnel_http::IngressConfig,
+    registry: ferrotunnel_plugin::PluginRegistry,
+) -> std::net::SocketAddr {
+    use ferrotunnel_core::tunnel::client::DEFAULT_STREAM_PROTOCOLS;
+
+    start_ws_tunnel_with_protocols(local_addr, config, registry, &DEFAULT_STREAM_PROTOCOLS).await
+}
+
+/// [`start_ws_tunnel`] with a client accepting only `protocols`
+async fn start_ws_tunnel_with_protocols(
+    local_addr: std::net::SocketAddr,
+    config: ferrotunnel_http::IngressConfig,
+    registry: ferrotunnel_plugin::PluginRegistry,
+    protocols: &[ferrotunnel_protocol::frame::Protocol],
+) -> std::net::SocketAddr {
+    use ferrotunnel_core::TunnelClient;
+    use ferrotunnel_http::HttpProxy;
+    use std::sync::Arc;
+
+    let (server_addr, sessions) = start_tunnel_server(|server| server).await;
+    let http_addr = start_ingress(sessions.clone(), registry, config).await;
+
+    let proxy = Arc::new(HttpProxy::new(local_addr.to_string()));
+    let client = TunnelClient::new(server_addr.to_string(), TUNNEL_TOKEN.into())
+        .with_tunnel_id("ws")
+        .with_stream_protocols(protocols);
+    connect_tunnel(&sessions, "ws", client, move |stream| {
+        let proxy = proxy.clone();
+        async move { proxy.handle_stream(stream) }
+    })
+    .await;
+
+    http_addr
+}
+
+#[tokio::test]
+async fn test_websocket_rejected_without_capability() {
+    use ferrotunnel_protocol::frame::Protocol;
+
+    let local_addr: std::net::SocketAddr =
+        format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let _ws_handle = start_ws_echo_server(local_addr).await;
+    let http_addr = start_ws_tunnel_with_protocols(
+        local_addr,
+        ferrotunnel_http::IngressConfig::default(),
+        ferrotunnel_plugin::PluginRegistry::new(),
+        &[Protocol::HTTP],
+    )
+    .await;
+
+    let mut tcp = tokio::net::TcpStream::connect(http_addr).await.unwrap();
+    let raw_request = "GET /ws HTTP/1.1\r\n\
+                       Host: ws\r\n\
+                       Connection: Upgrade\r\n\
+                       Upgrade: websocket\r\n\
+                       Sec-WebSocket-Version: 13\r\n\
+                       Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                       \r\n";
+    tcp.write_all(raw_request.as_bytes()).await.unwrap();
+
+    let head = tokio::time::timeout(Duration::from_secs(5), read_head(&mut tcp))
+        .await
+        .expect("Timeout reading response");
+    assert!(
+        head.starts_with("HTTP/1.1 501"),
+        "Expected 501 response, got: {head}"
+    );
+}
+
+async fn connect_ws(
+    http_addr: std::net::SocketAddr,
+) -> tokio_tungstenite::WebSocketStream<tokio::net::TcpStream> {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    let mut request = format!("ws://{http_addr}/ws")
+        .into_client_request()
+        .unwrap();
+    request.headers_mut().insert("Host", "ws".parse().unwrap());
+    let tcp_stream = tokio::net::TcpStream::connect(http_addr).await.unwrap();
+    let (ws_stream, _) = tokio_tungstenite::client_async(request, tcp_stream)
+        .await
+        .expect("WebSocket connection failed");
+    ws_stream
+}
+
+#[tokio::test]
+async fn test_idle_websocket_is_closed() {
+    let http_addr = start_limited_tunnel(ferrotunnel_http::WebSocketLimits {
+        idle_timeout: Some(Duration::from_millis(300)),
+        ..Default::default()
+    })
+    .await;
+
+    let mut ws = connect_ws(http_addr).await;
+    ws.send(Message::Text("hello".into())).await.unwrap();
+    assert_eq!(
+        ws.next().await.unwrap().unwrap(),
+        Message::Text("hello".into())
+    );
+
+    // Nothing moves, so the ingress tears the connection down
+    let closed = tokio::time::timeout(Duration::from_secs(5), ws.next())
+        .await
+        .expect("idle WebSocket was not closed");
+    assert!(
+        !matches!(closed, Some(Ok(Message::Text(_) | Message::Binary(_)))),
+        "{closed:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_active_websocket_stays_open() {
+    let http_addr = start_limited_tunnel(ferrotunnel_http::WebSocketLimits {
+        idle_timeout: Some(Duration::from_millis(300)),
+        max_message_size: Some(1024),
+    })
+    .await;
+
+    let mut ws = connect_ws(http_addr).await;
+    // Traffic every 100ms keeps the connection alive well past the timeout
+    for i in 0..10 {
+        let msg = Message::Text(format!("message {i}").into());
+        ws.send(msg.clone()).await.unwrap();
+        assert_eq!(ws.next().await.unwrap().unwrap(), msg);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    // An oversized message closes it
+    ws.send(Message::Binary(vec![0u8; 4096].into()))
+        .await
+        .unwrap();
+    let closed = tokio::time::timeout(Duration::from_secs(5), ws.next())
+        .await
+        .expect("oversized message did not close the WebSocket");
+    assert!(
+        !matches!(closed, Some(Ok(Message::Binary(_)))),
+        "{closed:?}"
+    );
+}
+
+/// Extension offer sent by the external client and accepted by the local server
+const DEFLATE_OFFER: &str =
+    "permessage-deflate; client_no_context_takeover; server_no_context_takeover";
+
+type HookResult =
+    Result<ferrotunnel_plugin::PluginAction, Box<dyn std::error::Error + Send + Sync + 'static>>;
+
+/// Drops the extension offer, as a header filtering plugin might
+struct StripExtensionsPlugin;
+
+#[async_trait::async_trait]
+impl ferrotunnel_plugin::Plugin for StripExtensionsPlugin {
+    fn name(&self) -> &str {
+        "strip-extensions"
+    }
+
+    async fn on_request(
+        &self,
+        req: &mut http::Request<()>,
+        _ctx: &ferrotunnel_plugin::RequestContext,
+    ) -> HookResult {
+        req.headers_mut().remove("sec-websocket-extensions");
+        Ok(ferrotunnel_plugin::PluginAction::Continue)
+    }
+}
+
+/// Read an HTTP/1.1 head, up to and including the blank line
+async fn read_head(stream: &mut tokio::net::TcpStream) -> String {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(stream.read_u8().await.unwrap());
+    }
+    String::from_utf8(head).unwrap()
+}
+
+/// Value of header `name` in a raw HTTP head
+fn head_header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+/// Compress one message as permessage-deflate does (RFC 7692 section 7.2.1)
+fn deflate_message(data: &[u8]) -> Vec<u8> {
+    let mut compress = flate2::Compress::new(flate2::Compression::default(), false);
+    let mut out = Vec::with_capacity(data.len() + 64);
+    compress
+        .compress_vec(data, &mut out, flate2::FlushCompress::Sync)
+        .unwrap();
+    assert!(out.ends_with(&[0, 0, 0xff, 0xff]));
+    out.truncate(out.len() - 4);
+    out
+}
+
+fn inflate_message(data: &[u8]) -> Vec<u8> {
+    let mut input = data.to_vec();
+    input.extend_from_slice(&[0, 0, 0xff, 0xff]);
+    let mut decompress = flate2::Decompress::new(false);
+    let mut out = Vec::with_capacity(64 * 1024);
+    decompress
+        .decompress_vec(&input, &mut out, flate2::FlushDecompress::Sync)
+        .unwrap();
+    out
+}
+
+/// Write a single-frame text message with RSV1 set, marking it compressed
+async fn write_compressed(
+    stream: &mut tokio::net::TcpStream,
+    payload: &[u8],
+    mask: Option<[u8; 4]>,
+) {
+    let mut frame = vec![0x80 | 0x40 | 0x1];
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match u16::try_from(payload.len()) {
+        Ok(len) if len < 126 => frame.push(mask_bit | u8::try_from(len).unwrap()),
+        Ok(len) => {
+            frame.push(mask_bit | 0x7e);
+            frame.extend_from_slice(&len.to_be_bytes());
+        }
+        Err(err) => panic!("test payload too large: {err}"),
+    }
+    match mask {
+        Some(key) => {
+            frame.extend_from_slice(&key);
+            frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ key[i % 4]));
+        }
+        None => frame.extend_from_slice(payload),
+    }
+    stream.write_all(&frame).await.unwrap();
+}
+
+/// Read one frame, returning its first header byte and unmasked payload
+async fn read_frame(stream: &mut tokio::net::TcpStream) -> (u8, Vec<u8>) {
+    let first = stream.read_u8().await.unwrap();
+    let second = stream.read_u8().await.unwrap();
+    let len = match second & 0x7f {
+        126 => usize::from(stream.read_u16().await.unwrap()),
+        127 => usize::try_from(stream.read_u64().await.unwrap()).unwrap(),
+        len => usize::from(len),
+    };
+    let mut key = [0u8; 4];
+    if second & 0x80 != 0 {
+        stream.read_exact(&mut key).await.unwrap();
+    }
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload).await.unwrap();
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= key[i % 4];
+    }
+    (first, payload)
+}
+
+/// Local WS server that accepts permessage-deflate and echoes compressed
+/// messages. Sends the extension offer it received on `offer_tx`.
+async fn start_deflate_echo_server(
+    addr: std::net::SocketAddr,
+    offer_tx: tokio::sync::oneshot::Sender<Option<String>>,
+) {
+    use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+
+    let listener = TcpListener::bind(addr).await.unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let head = read_head(&mut stream).await;
+        let offer = head_header(&head, "sec-websocket-extensions").map(str::to_string);
+        let key = head_header(&head, "sec-websocket-key").unwrap();
+        let mut response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n",
+            derive_accept_key(key.as_bytes())
+        );
+        if offer.as_deref() == Some(DEFLATE_OFFER) {
+            response.push_str("Sec-WebSocket-Extensions: ");
+            response.push_str(DEFLATE_OFFER);
+            response.push_str("\r\n");
+        }
+        response.push_str("\r\n");
+        stream.write_all(response.as_bytes()).await.unwrap();
+        let _ = offer_tx.send(offer);
+
+        loop {
+            let (first, payload) = read_frame(&mut stream).await;
+            assert_eq!(first & 0x40, 0x40, "message arrived without RSV1");
+            let message = inflate_message(&payload);
+            write_compressed(&mut stream, &deflate_message(&message), None).await;
+        }
+    });
+}
+
+#[tokio::test]
+async fn test_websocket_permessage_deflate_through_tunnel() {
+    use ferrotunnel_plugin::PluginRegistry;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    let local_addr: std::net::SocketAddr =
+        format!("127.0.0.1:{}", get_free_port()).parse().unwrap();
+    let (offer_tx, offer_rx) = tokio::sync::oneshot::channel();
+    start_deflate_echo_server(local_addr, offer_tx).await;
+
+    // The ingress must not let plugins alter the negotiation
+    let registry = PluginRegistry::new();
+    registry.register(Arc::new(RwLock::new(StripExtensionsPlugin)));
+    let http_addr = start_ws_tunnel(
+        local_addr,
+        ferrotunnel_http::IngressConfig::default(),
+        registry,
+    )
+    .await;
+
+    let mut stream = tokio::net::TcpStream::connect(http_addr).await.unwrap();
+    let request = format!(
+        "GET /ws HTTP/1.1\r\n\
+         Host: ws\r\n\
+         Connection: Upgrade\r\n\
+         Upgrade: websocket\r\n\
+         Sec-WebSocket-Version: 13\r\n\
+         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+         Sec-WebSocket-Extensions: {DEFLATE_OFFER}\r\n\
+         \r\n"
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let head = tokio::time::timeout(Duration::from_secs(5), read_head(&mut stream))
+        .await
+        .expect("no upgrade response");
+    assert!(head.starts_with("HTTP/1.1 101"), "{head}");
+    // The offer reached the local server unmodified, and its answer came back
+    assert_eq!(offer_rx.await.unwrap().as_deref(), Some(DEFLATE_OFFER));
+    assert_eq!(
+        head_header(&head, "sec-websocket-extensions"),
+        Some(DEFLATE_OFFER),
+        "{head}"
+    );
+
+    for i in 0..3 {
+        let message = format!("compressed message {i} ").repeat(50);
+        let compressed = deflate_message(message.as_bytes());
+        assert!(compressed.len() < message.len());
+        write_compressed(&mut stream, &compressed, Some([0x12, 0x34, 0x56, 0x78])).await;
+
+        let (first, payload) =
+            tokio::time::timeout(Duration::from_secs(5), read_frame(&mut stream))
+                .await
+                .expect("no echo");
+        assert_eq!(first, 0x80 | 0x40 | 0x1);
+        assert_eq!(inflate_message(&payload), message.as_bytes());
+    }
+}
@@ -1,27 +1,51 @@
 //! FerroTunnel Load Testing Tool
 //!
 //! Tests concurrent stream handling and measures latency/throughput.
+//!
+//! The `baseline` and `echo-client` modes measure plain TCP. The `tunnel`
+//! mode connects a tunnel client to a running server and sends HTTP requests
+//! through the server's public ingress, so the numbers include the tunnel.
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use bytes::Bytes;
 use clap::Parser;
+use ferrotunnel::Client;
 use hdrhistogram::Histogram;
+use std::io;
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::Barrier;
+use tokio::task::JoinHandle;
 use tracing::{error, info, warn};
 
 #[derive(Parser, Debug)]
 #[command(name = "ferrotunnel-loadgen")]
 #[command(about = "Load testing tool for FerroTunnel")]
 struct Args {
-    /// Test mode: echo-server, echo-client, baseline
+    /// Test mode: echo-server, echo-client, baseline, tunnel
     #[arg(long, default_value = "baseline")]
     mode: String,
 
+    /// Tunnel server control address for tunnel mode
+    #[arg(long, default_value = "127.0.0.1:7835")]
+    server: String,
+
+    /// Server's public HTTP ingress address for tunnel mode
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    ingress: String,
+
+    /// Authentication token for tunnel mode
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Tunnel ID (and Host header) used in tunnel mode
+    #[arg(long, default_value = "loadgen")]
+    tunnel_id: String,
+
     /// Target address for client mode
     #[arg(long, default_value = "127.0.0.1:9999")]
     target: String,
@@ -208,16 +232,19 @@ async fn run_load_client(
 
     // Report metrics
     metrics.report();
+    report_latency("Latency", &histogram);
+
+    Ok(())
+}
 
-    // Report latency percentiles
-    info!("=== Latency (microseconds) ===");
+/// Report latency percentiles under `title`
+fn report_latency(title: &str, histogram: &Histogram<u64>) {
+    info!("=== {} (microseconds) ===", title);
     info!("p50: {} µs", histogram.value_at_quantile(0.50));
     info!("p90: {} µs", histogram.value_at_quantile(0.90));
     info!("p95: {} µs", histogram.value_at_quantile(0.95));
     info!("p99: {} µs", histogram.value_at_quantile(0.99));
     info!("max: {} µs", histogram.max());
-
-    Ok(())
 }
 
 /// Run baseline test (local echo, no tunnel)
@@ -251,6 +278,223 @@ async fn run_baseline(
     Ok(())
 }
 
+/// Settings for the tunnel mode
+#[derive(Debug, Clone)]
+struct TunnelLoad {
+    server: String,
+    ingress: String,
+    token: String,
+    tunnel_id: String,
+    concurrency: usize,
+    requests_per_conn: usize,
+    payload_size: usize,
+}
+
+/// Run the tunnel test: serve a local HTTP echo service through a tunnel
+/// client and load it via the server's ingress
+async fn run_tunnel(load: &TunnelLoad) -> Result<(Arc<Metrics>, Histogram<u64>)> {
+    let (local_addr, local_handle) = start_http_echo_server().await?;
+    info!("Local HTTP echo service on {}", local_addr);
+
+    let mut client = Client::builder()
+        .server_addr(&load.server)
+        .token(&load.token)
+        .local_addr(local_addr.to_string())
+        .tunnel_id(&load.tunnel_id)
+        .auto_reconnect(false)
+        .build()?;
+    client
+        .start()
+        .await
+        .with_context(|| format!("connecting tunnel client to {}", load.server))?;
+    info!(
+        "Tunnel '{}' connected via {}, loading ingress {}",
+        load.tunnel_id, load.server, load.ingress
+    );
+
+    let result = run_tunnel_load(load).await;
+
+    let _ = client.shutdown().await;
+    local_handle.abort();
+    let (metrics, histogram) = result?;
+
+    metrics.report();
+    report_latency("Latency through the tunnel", &histogram);
+    Ok((metrics, histogram))
+}
+
+/// Send `requests_per_conn` HTTP requests on each of `concurrency` keep-alive
+/// connections to the ingress, echoing `payload_size` bytes each
+async fn run_tunnel_load(load: &TunnelLoad) -> Result<(Arc<Metrics>, Histogram<u64>)> {
+    let metrics = Arc::new(Metrics::new());
+    let barrier = Arc::new(Barrier::new(load.concurrency));
+    let mut histogram = Histogram::<u64>::new(3)?;
+
+    let payload = Bytes::from(vec![b'X'; load.payload_size]);
+    let head = format!(
+        "POST /echo HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\n\r\n",
+        load.tunnel_id, load.payload_size
+    );
+    let request = Bytes::from([head.as_bytes(), &payload].concat());
+
+    info!(
+        "Starting tunnel load test: {} connections, {} requests each, {} byte payload",
+        load.concurrency, load.requests_per_conn, load.payload_size
+    );
+
+    let mut handles = Vec::with_capacity(load.concurrency);
+    for _ in 0..load.concurrency {
+        let ingress = load.ingress.clone();
+        let metrics = Arc::clone(&metrics);
+        let barrier = Arc::clone(&barrier);
+        let request = request.clone();
+        let payload_len = payload.len();
+        let requests_per_conn = load.requests_per_conn;
+
+        handles.push(tokio::spawn(async move {
+            barrier.wait().await;
+
+            let mut latencies = Vec::with_capacity(requests_per_conn);
+            let mut conn: Option<TcpStream> = None;
+            let mut buf = Vec::new();
+            for _ in 0..requests_per_conn {
+                if conn.is_none() {
+                    match TcpStream::connect(&ingress).await {
+                        Ok(stream) => {
+                            buf.clear();
+                            conn = Some(stream);
+                        }
+                        Err(e) => {
+                            error!("Connection to ingress {} failed: {}", ingress, e);
+                            metrics.record_error();
+                            continue;
+                        }
+                    }
+                }
+                let Some(stream) = conn.as_mut() else {
+                    continue;
+                };
+
+                let start = Instant::now();
+                match exchange(stream, &request, &mut buf).await {
+                    Ok((head, body)) if is_ok_status(&head) && body.len() == payload_len => {
+                        latencies.push(start.elapsed().as_micros() as u64);
+                        metrics.record_request((request.len() + body.len()) as u64);
+                    }
+                    Ok((head, _)) => {
+                        warn!("Unexpected response: {}", head.lines().next().unwrap_or(""));
+                        metrics.record_error();
+                    }
+                    Err(_) => {
+                        // Reconnect for the next request
+                        conn = None;
+                        metrics.record_error();
+                    }
+                }
+            }
+            latencies
+        }));
+    }
+
+    for handle in handles {
+        match handle.await {
+            Ok(latencies) => {
+                for lat in latencies {
+                    if histogram.record(lat).is_err() {
+                        warn!("Failed to record latency");
+                    }
+                }
+            }
+            Err(e) => error!("Task failed: {}", e),
+        }
+    }
+
+    Ok((metrics, histogram))
+}
+
+/// Write `request` and read the response that follows
+async fn exchange(
+    stream: &mut TcpStream,
+    request: &[u8],
+    buf: &mut Vec<u8>,
+) -> io::Result<(String, Vec<u8>)> {
+    stream.write_all(request).await?;
+    read_http_message(stream, buf)
+        .await?
+        .ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+}
+
+/// Start a local HTTP/1.1 service echoing each request body back, on an
+/// ephemeral port
+async fn start_http_echo_server() -> Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let handle = tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = Vec::new();
+                while let Ok(Some((_, body))) = read_http_message(&mut socket, &mut buf).await {
+                    let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len());
+                    if socket.write_all(head.as_bytes()).await.is_err()
+                        || socket.write_all(&body).await.is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    Ok((addr, handle))
+}
+
+/// Read one HTTP/1.1 message with a `Content-Length` body, keeping any bytes
+/// past its end in `buf` for the next one. `None` when the peer closed the
+/// connection between messages.
+async fn read_http_message(
+    stream: &mut TcpStream,
+    buf: &mut Vec<u8>,
+) -> io::Result<Option<(String, Vec<u8>)>> {
+    let mut chunk = vec![0u8; 16 * 1024];
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+    let end = head_end + content_length(&head);
+    while buf.len() < end {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let body = buf[head_end..end].to_vec();
+    buf.drain(..end);
+    Ok(Some((head, body)))
+}
+
+fn content_length(head: &str) -> usize {
+    head.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn is_ok_status(head: &str) -> bool {
+    head.split_whitespace().nth(1) == Some("200")
+}
+
 #[cfg(target_os = "linux")]
 fn print_memory_usage() {
     if let Ok(me) = procfs::process::Process::myself() {
@@ -298,6 +542,22 @@ async fn main() -> Result<()> {
             info!("Running baseline test (local echo, no tunnel)");
             run_baseline(args.concurrency, args.requests, args.payload_size).await?;
         }
+        "tunnel" => {
+            let Some(token) = args.token else {
+                bail!("tunnel mode needs --token");
+            };
+            info!("Running tunnel test (HTTP through the ingress and tunnel)");
+            run_tunnel(&TunnelLoad {
+                server: args.server,
+                ingress: args.ingress,
+                token,
+                tunnel_id: args.tunnel_id,
+                concurrency: args.concurrency,
+                requests_per_conn: args.requests,
+                payload_size: args.payload_size,
+            })
+            .await?;
+        }
         _ => {
             error!("Unknown mode: {}", args.mode);
             std::process::exit(1);
@@ -307,3 +567,49 @@ async fn main() -> Result<()> {
     print_memory_usage();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ferrotunnel::Server;
+
+    #[tokio::test]
+    async fn test_tunnel_mode_sends_requests_through_tunnel() {
+        let mut server = Server::builder()
+            .bind("127.0.0.1:0".parse().unwrap())
+            .http_bind("127.0.0.1:0".parse().unwrap())
+            .token("loadgen-token")
+            .build()
+            .unwrap();
+        server.bind_now().await.unwrap();
+        let control_addr = server.control_addr().unwrap();
+        let http_addr = server.http_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = server.start().await;
+        });
+
+        let load = TunnelLoad {
+            server: control_addr.to_string(),
+            ingress: http_addr.to_string(),
+            token: "loadgen-token".into(),
+            tunnel_id: "loadgen".into(),
+            concurrency: 4,
+            requests_per_conn: 10,
+            payload_size: 512,
+        };
+        let (metrics, histogram) = run_tunnel(&load).await.unwrap();
+
+        assert_eq!(metrics.total_requests.load(Ordering::Relaxed), 40);
+        assert_eq!(metrics.errors.load(Ordering::Relaxed), 0);
+        assert_eq!(histogram.len(), 40);
+    }
+
+    #[test]
+    fn test_content_length_parsing() {
+        let head = "HTTP/1.1 200 OK\r\ncontent-length: 12\r\n\r\n";
+        assert_eq!(content_length(head), 12);
+        assert!(is_ok_status(head));
+        assert_eq!(content_length("HTTP/1.1 204 No Content\r\n\r\n"), 0);
+        assert!(!is_ok_status("HTTP/1.1 502 Bad Gateway\r\n\r\n"));
+    }
+}